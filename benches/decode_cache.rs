@@ -0,0 +1,71 @@
+//! Compares stepping with and without the predecoded instruction cache.
+//!
+//! Run with `cargo bench --bench decode_cache`; Criterion keeps the previous
+//! run under `target/criterion` and reports the change against it.
+
+use chip8_emulator::Chip8;
+use criterion::{BatchSize, BenchmarkId, Criterion, black_box, criterion_group, criterion_main};
+
+const STEPS: usize = 10_000;
+
+// A tight counting loop that never writes memory:
+// 200: 7001  V0 += 1
+// 202: 8104  V1 += V0
+// 204: 3000  skip if V0 == 0
+// 206: 1200  jump 0x200
+// 208: 1200  jump 0x200
+const LONG_LOOP: [u8; 10] = [0x70, 0x01, 0x81, 0x04, 0x30, 0x00, 0x12, 0x00, 0x12, 0x00];
+
+// Stores over its own first instruction on every iteration, so each pass
+// pays for an invalidation and a fresh decode:
+// 200: 6A01  VA = 0x01        <- overwritten with the same bytes
+// 202: 606A  V0 = 0x6A
+// 204: 6101  V1 = 0x01
+// 206: A200  I = 0x200
+// 208: F155  store V0..V1 at I
+// 20A: 1200  jump 0x200
+const SELF_MODIFYING: [u8; 12] = [
+    0x6A, 0x01, 0x60, 0x6A, 0x61, 0x01, 0xA2, 0x00, 0xF1, 0x55, 0x12, 0x00,
+];
+
+fn setup(program: &[u8], cached: bool) -> Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.set_decode_cache(cached);
+    chip8.load_rom_bytes(program).unwrap();
+    chip8
+}
+
+fn run(mut chip8: Chip8) -> Chip8 {
+    for _ in 0..STEPS {
+        let _ = chip8.step();
+    }
+    chip8
+}
+
+fn bench_workload(c: &mut Criterion, name: &str, program: &[u8]) {
+    let mut group = c.benchmark_group(name);
+
+    for cached in [false, true] {
+        let label = if cached { "cached" } else { "uncached" };
+        group.bench_with_input(BenchmarkId::from_parameter(label), &cached, |b, &cached| {
+            b.iter_batched(
+                || setup(program, cached),
+                |chip8| black_box(run(chip8)),
+                BatchSize::SmallInput,
+            );
+        });
+    }
+
+    group.finish();
+}
+
+fn long_loop(c: &mut Criterion) {
+    bench_workload(c, "long_loop", &LONG_LOOP);
+}
+
+fn self_modifying(c: &mut Criterion) {
+    bench_workload(c, "self_modifying", &SELF_MODIFYING);
+}
+
+criterion_group!(benches, long_loop, self_modifying);
+criterion_main!(benches);
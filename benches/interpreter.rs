@@ -0,0 +1,99 @@
+//! The interpreter's hot paths: a mix of ordinary instructions, DXYN, and
+//! turning the display into a pixel buffer.
+//!
+//! Run with `cargo bench --bench interpreter`. To compare a change, run it
+//! once with `-- --save-baseline before` on the old code, then with
+//! `-- --baseline before` on the new; Criterion reports each benchmark's
+//! difference and whether it's more than noise.
+
+use chip8_emulator::{Chip8, FONT_BASE};
+use criterion::{BatchSize, BenchmarkId, Criterion, black_box, criterion_group, criterion_main};
+
+const FRAMES: u32 = 1_000;
+const INSTRUCTIONS_PER_FRAME: u32 = 10;
+
+// Something of everything a game does between draws, in a loop:
+// 200: 6A05  VA = 5
+// 202: 7B03  VB += 3
+// 204: 8AB4  VA += VB
+// 206: 8AB5  VA -= VB
+// 208: 8A06  VA >>= 1
+// 20A: 8AB2  VA &= VB
+// 20C: 3A00  skip if VA == 0
+// 20E: 4B00  skip if VB != 0
+// 210: A300  I = 0x300
+// 212: FB33  BCD of VB at I
+// 214: F265  load V0..V2 from I
+// 216: CAFF  VA = random
+// 218: 221E  call 21E
+// 21A: 1200  jump 0x200
+// 21C: 0000
+// 21E: FA1E  I += VA
+// 220: 00EE  return
+const INSTRUCTION_MIX: [u8; 34] = [
+    0x6A, 0x05, 0x7B, 0x03, 0x8A, 0xB4, 0x8A, 0xB5, 0x8A, 0x06, 0x8A, 0xB2, 0x3A, 0x00, 0x4B, 0x00, 0xA3, 0x00,
+    0xFB, 0x33, 0xF2, 0x65, 0xCA, 0xFF, 0x22, 0x1E, 0x12, 0x00, 0x00, 0x00, 0xFA, 0x1E, 0x00, 0xEE,
+];
+
+// Draws the 0 glyph at (V0, V1) over and over:
+// 200: D015  draw 5 rows at (V0, V1)
+// 202: 1200  jump 0x200
+const DRAW_LOOP: [u8; 4] = [0xD0, 0x15, 0x12, 0x00];
+
+fn instruction_mix(c: &mut Criterion) {
+    c.bench_function("instruction_mix", |b| {
+        b.iter_batched(
+            || {
+                let mut chip8 = Chip8::new();
+                chip8.load_rom_bytes(&INSTRUCTION_MIX).unwrap();
+                chip8
+            },
+            |mut chip8| {
+                chip8.emulate_frames(FRAMES, INSTRUCTIONS_PER_FRAME).unwrap();
+                black_box(chip8)
+            },
+            BatchSize::SmallInput,
+        );
+    });
+}
+
+fn draw(c: &mut Criterion) {
+    let mut group = c.benchmark_group("draw");
+
+    // A sprite at a multiple of 8 lands in one byte of each row; anywhere
+    // else it's split across two.
+    for (label, x) in [("aligned", 8), ("unaligned", 3)] {
+        group.bench_with_input(BenchmarkId::from_parameter(label), &x, |b, &x| {
+            b.iter_batched(
+                || {
+                    let mut chip8 = Chip8::new();
+                    chip8.load_rom_bytes(&DRAW_LOOP).unwrap();
+                    chip8.v[0] = x;
+                    chip8.v[1] = 4;
+                    chip8.i = FONT_BASE;
+                    chip8
+                },
+                |mut chip8| {
+                    for _ in 0..FRAMES * INSTRUCTIONS_PER_FRAME {
+                        let _ = chip8.step();
+                    }
+                    black_box(chip8)
+                },
+                BatchSize::SmallInput,
+            );
+        });
+    }
+
+    group.finish();
+}
+
+fn display_buffer(c: &mut Criterion) {
+    let mut chip8 = Chip8::new();
+    for (index, pixel) in chip8.display.pixels_mut().iter_mut().enumerate() {
+        *pixel = index % 3 == 0;
+    }
+    c.bench_function("get_display_buffer", |b| b.iter(|| black_box(chip8.get_display_buffer())));
+}
+
+criterion_group!(benches, instruction_mix, draw, display_buffer);
+criterion_main!(benches);
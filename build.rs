@@ -0,0 +1,32 @@
+//! Generates the C header for the `ffi` feature into `$OUT_DIR/chip8.h`.
+//! `tests/ffi.rs` checks that the copy in `include/` matches it.
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "ffi")]
+    ffi_header();
+}
+
+#[cfg(feature = "ffi")]
+fn ffi_header() {
+    use std::env;
+    use std::path::PathBuf;
+
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+
+    let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("chip8.h");
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml"))
+        .expect("cbindgen.toml is invalid");
+
+    // Only the FFI module, so the rest of the crate's public constants stay
+    // out of the header.
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(crate_dir.join("src/ffi.rs"))
+        .generate()
+        .expect("failed to generate the C header")
+        .write_to_file(out);
+}
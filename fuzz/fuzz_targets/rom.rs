@@ -0,0 +1,57 @@
+//! Any bytes at all as a ROM, under any profile, with keys going up and
+//! down: nothing may panic, and every fault has to come back from `step`.
+//!
+//! Run from the repository root with a nightly toolchain:
+//!
+//! ```text
+//! cargo install cargo-fuzz
+//! cargo +nightly fuzz run rom
+//! ```
+//!
+//! Crashes are saved under `fuzz/artifacts/rom/`; replay one with
+//! `cargo +nightly fuzz run rom fuzz/artifacts/rom/<file>`, then add the ROM
+//! to `tests/no_panic.rs` once it's fixed.
+
+#![no_main]
+
+use chip8_emulator::{Chip8, EXTENDED_MEMORY_SIZE, Quirks, Rng};
+use libfuzzer_sys::fuzz_target;
+
+const STEPS: usize = 5_000;
+
+// The first byte picks the profile, whether the decode cache is on and, with
+// 64 KB of memory, whether to start from a copy of the ROM at the top of it,
+// where the PC wraps. The next eight seed the generator for CXNN and the
+// keys, and the rest is the ROM.
+fuzz_target!(|data: &[u8]| {
+    let Some((&[setup], rest)) = data.split_first_chunk::<1>() else { return };
+    let Some((seed, rom)) = rest.split_first_chunk::<8>() else { return };
+    let seed = u64::from_le_bytes(*seed);
+
+    let profile = Quirks::PROFILES[setup as usize % Quirks::PROFILES.len()];
+    let mut chip8 = Chip8::with_rng(Rng::new(seed));
+    chip8.quirks = Quirks::from_profile(profile).unwrap();
+    chip8.set_decode_cache(setup & 0x80 != 0);
+    if chip8.load_rom_bytes(rom).is_err() {
+        return;
+    }
+    if chip8.quirks.extended_memory && setup & 0x40 != 0 {
+        let top = EXTENDED_MEMORY_SIZE - rom.len();
+        chip8.memory[top..].copy_from_slice(rom);
+        chip8.pc = top as u16;
+    }
+
+    let mut keys = Rng::new(!seed);
+    for step in 0..STEPS {
+        if step % 16 == 0 {
+            let mask = u16::from_le_bytes([keys.next_u8(), keys.next_u8()]);
+            chip8.keys = core::array::from_fn(|key| mask & (1 << key) != 0);
+            chip8.tick_timers();
+        }
+        if let Err(e) = chip8.step()
+            && !e.is_recoverable()
+        {
+            break;
+        }
+    }
+});
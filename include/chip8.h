@@ -0,0 +1,251 @@
+#ifndef CHIP8_H
+#define CHIP8_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Display width in pixels, until a SUPER-CHIP program switches to 128x64.
+ */
+#define CHIP8_DISPLAY_WIDTH 64
+
+/**
+ * Display height in pixels, until a SUPER-CHIP program switches to 128x64.
+ */
+#define CHIP8_DISPLAY_HEIGHT 32
+
+/**
+ * Quirk flag for `chip8_set_quirks`: 8XY6/8XYE copy VY into VX before
+ * shifting.
+ */
+#define CHIP8_QUIRK_SHIFT_USES_VY (1 << 0)
+
+/**
+ * Quirk flag for `chip8_set_quirks`: FX1E sets VF when I goes past 0xFFF.
+ */
+#define CHIP8_QUIRK_I_OVERFLOW_SETS_VF (1 << 1)
+
+/**
+ * Quirk flag for `chip8_set_quirks`: FX55/FX65 leave I at I + X + 1.
+ */
+#define CHIP8_QUIRK_LOAD_STORE_INCREMENTS_I (1 << 2)
+
+/**
+ * Quirk flag for `chip8_set_quirks`: DXYN wraps sprites around the screen
+ * edges instead of clipping them.
+ */
+#define CHIP8_QUIRK_WRAP_SPRITES (1 << 3)
+
+/**
+ * Quirk flag for `chip8_set_quirks`: `chip8_run_frame` stops after a DXYN.
+ */
+#define CHIP8_QUIRK_DISPLAY_WAIT (1 << 4)
+
+/**
+ * Quirk flag for `chip8_set_quirks`: 8XY1/8XY2/8XY3 clear VF.
+ */
+#define CHIP8_QUIRK_VF_RESET (1 << 5)
+
+/**
+ * Quirk flag for `chip8_set_quirks`: BXNN jumps to XNN plus VX.
+ */
+#define CHIP8_QUIRK_JUMP_USES_VX (1 << 6)
+
+/**
+ * Quirk flag for `chip8_set_quirks`: 00CN/00FB/00FC scroll half as far in
+ * 64x32 mode.
+ */
+#define CHIP8_QUIRK_HALF_SCROLL (1 << 7)
+
+/**
+ * Quirk flag for `chip8_set_quirks`: memory is XO-CHIP's 64 KB, with
+ * F000 NNNN.
+ */
+#define CHIP8_QUIRK_EXTENDED_MEMORY (1 << 8)
+
+/**
+ * Quirk flag for `chip8_set_quirks`: FX55/FX65 leave I at I + X, winning
+ * over `CHIP8_QUIRK_LOAD_STORE_INCREMENTS_I`.
+ */
+#define CHIP8_QUIRK_LOAD_STORE_INCREMENTS_I_BY_X (1 << 9)
+
+/**
+ * Quirk flag for `chip8_set_quirks`: HI-RES CHIP-8 ROMs switch to 64x64.
+ */
+#define CHIP8_QUIRK_TWO_PAGE_HIRES (1 << 10)
+
+/**
+ * Quirk flag for `chip8_set_quirks`: CHIP-8X's color and other
+ * instructions run.
+ */
+#define CHIP8_QUIRK_CHIP8X (1 << 11)
+
+/**
+ * Result of every call. The variants after `Panic` mirror `Chip8Error`.
+ */
+typedef enum Chip8Status {
+  CHIP8_STATUS_OK = 0,
+  /**
+   * A required pointer was null.
+   */
+  CHIP8_STATUS_NULL_POINTER,
+  /**
+   * An argument was out of range, such as a too small buffer.
+   */
+  CHIP8_STATUS_INVALID_ARGUMENT,
+  /**
+   * The interpreter panicked; the machine may be in an inconsistent state.
+   */
+  CHIP8_STATUS_PANIC,
+  CHIP8_STATUS_ROM_TOO_LARGE,
+  CHIP8_STATUS_INVALID_OPCODE,
+  CHIP8_STATUS_STACK_OVERFLOW,
+  CHIP8_STATUS_STACK_UNDERFLOW,
+  CHIP8_STATUS_MEMORY_OUT_OF_BOUNDS,
+  CHIP8_STATUS_INVALID_KEY,
+  CHIP8_STATUS_PC_OUT_OF_BOUNDS,
+  CHIP8_STATUS_STATE_VERSION_MISMATCH,
+  CHIP8_STATUS_INVALID_STATE,
+  CHIP8_STATUS_LOAD,
+  CHIP8_STATUS_IO,
+  CHIP8_STATUS_UNSUPPORTED_PLATFORM,
+  CHIP8_STATUS_MACHINE_CODE_CALL,
+  CHIP8_STATUS_PROTECTED_WRITE,
+  CHIP8_STATUS_BLOCK_OUT_OF_BOUNDS,
+  CHIP8_STATUS_FATAL_OPCODE,
+  CHIP8_STATUS_EMPTY_ROM,
+} Chip8Status;
+
+/**
+ * A machine owned by C code, created with `chip8_new` and released with
+ * `chip8_free`.
+ */
+typedef struct Chip8Machine Chip8Machine;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Creates a machine with an empty program, or returns null if that fails.
+ */
+struct Chip8Machine *chip8_new(void);
+
+/**
+ * Releases a machine. Null is ignored.
+ *
+ * # Safety
+ *
+ * `machine` must be null or a pointer from [`chip8_new`] that hasn't been
+ * freed yet.
+ */
+void chip8_free(struct Chip8Machine *machine);
+
+/**
+ * Copies `len` bytes of program from `rom` into memory at 0x200. The rest
+ * of the machine is left as it is, so load into a fresh one.
+ *
+ * # Safety
+ *
+ * `machine` must be null or live, and `rom` must be null or point to `len`
+ * readable bytes.
+ */
+enum Chip8Status chip8_load_rom(struct Chip8Machine *machine, const uint8_t *rom, size_t len);
+
+/**
+ * Reseeds the CXNN random generator so runs can be reproduced.
+ *
+ * # Safety
+ *
+ * `machine` must be null or live.
+ */
+enum Chip8Status chip8_seed(struct Chip8Machine *machine, uint64_t seed);
+
+/**
+ * Sets the quirks from a combination of `CHIP8_QUIRK_*` flags, replacing
+ * the previous ones. Unknown flags are rejected with `InvalidArgument`.
+ *
+ * # Safety
+ *
+ * `machine` must be null or live.
+ */
+enum Chip8Status chip8_set_quirks(struct Chip8Machine *machine, uint32_t flags);
+
+/**
+ * Executes one instruction.
+ *
+ * # Safety
+ *
+ * `machine` must be null or live.
+ */
+enum Chip8Status chip8_step(struct Chip8Machine *machine);
+
+/**
+ * Runs one 60 Hz frame with the keypad state `keys`, where bit N is set
+ * while key N is held, then ticks the timers. Unknown opcodes are skipped
+ * as in the desktop emulator.
+ *
+ * # Safety
+ *
+ * `machine` must be null or live.
+ */
+enum Chip8Status chip8_run_frame(struct Chip8Machine *machine, uint16_t keys);
+
+/**
+ * Sets how many instructions `chip8_run_frame` executes; the default is 10.
+ *
+ * # Safety
+ *
+ * `machine` must be null or live.
+ */
+enum Chip8Status chip8_set_instructions_per_frame(struct Chip8Machine *machine, uint32_t count);
+
+/**
+ * Copies the display into `out`, one byte per pixel (1 lit, 0 unlit), row
+ * by row. `len` must be at least `chip8_display_width(machine) *
+ * chip8_display_height(machine)`.
+ *
+ * # Safety
+ *
+ * `machine` must be null or live, and `out` must be null or point to `len`
+ * writable bytes.
+ */
+enum Chip8Status chip8_display(struct Chip8Machine *machine, uint8_t *out, size_t len);
+
+/**
+ * Display width in pixels right now, 64 or 128. Zero for a null machine.
+ *
+ * # Safety
+ *
+ * `machine` must be null or live.
+ */
+size_t chip8_display_width(const struct Chip8Machine *machine);
+
+/**
+ * Display height in pixels right now, 32 or 64. Zero for a null machine.
+ *
+ * # Safety
+ *
+ * `machine` must be null or live.
+ */
+size_t chip8_display_height(const struct Chip8Machine *machine);
+
+/**
+ * Whether the buzzer should be sounding. False for a null machine.
+ *
+ * # Safety
+ *
+ * `machine` must be null or live.
+ */
+bool chip8_sound_active(const struct Chip8Machine *machine);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CHIP8_H */
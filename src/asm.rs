@@ -0,0 +1,258 @@
+//! An assembler for the mnemonics [`disassemble`](crate::disassemble)
+//! prints, for writing small test programs without encoding them by hand.
+//!
+//! Each line holds an optional `label:`, then an instruction or a `db` (or
+//! `.byte`) of comma-separated bytes, then an optional `;` comment.
+//! Mnemonics, registers and the `I`, `DT`, `ST`, `K`, `F`, `HF`, `B`, `[I]`
+//! and `R` operands are case-insensitive. Numbers are decimal, `0x` hex or
+//! `0b` binary. `JP`, `CALL` and `LD I` take a label anywhere in the program,
+//! before or after them. `LD I` of a number past 0xFFF is XO-CHIP's F000
+//! NNNN, as is `LD I, long N`.
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use thiserror::Error;
+
+use crate::instruction::Instruction;
+
+/// A line [`assemble`] couldn't make sense of, counting from 1.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("line {line}: {message}")]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+/// The program in `source`, assembled to be loaded at `start`.
+pub fn assemble(source: &str, start: u16) -> Result<Vec<u8>, AsmError> {
+    // Sizes don't depend on labels, so one pass finds every label's address
+    // and a second encodes.
+    let mut labels = BTreeMap::new();
+    let mut statements = Vec::new();
+    let mut addr = start as u32;
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let error = |message: String| AsmError { line, message };
+        let mut text = text.split(';').next().unwrap_or_default().trim();
+        if let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim();
+            if !is_label(label) {
+                return Err(error(format!("{:?} isn't a label name", label)));
+            }
+            if labels.insert(label.to_ascii_lowercase(), addr).is_some() {
+                return Err(error(format!("{} is defined twice", label)));
+            }
+            text = rest.trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+        let statement = Statement::parse(text).map_err(error)?;
+        addr += statement.len() as u32;
+        statements.push((line, statement));
+    }
+
+    let mut rom = Vec::new();
+    for (line, statement) in statements {
+        statement.encode(&labels, &mut rom).map_err(|message| AsmError { line, message })?;
+    }
+    Ok(rom)
+}
+
+fn is_label(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+enum Statement {
+    Bytes(Vec<Value>),
+    Instruction { mnemonic: String, operands: Vec<Operand> },
+}
+
+impl Statement {
+    fn parse(text: &str) -> Result<Self, String> {
+        let (mnemonic, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let mnemonic = mnemonic.to_ascii_uppercase();
+        let rest = rest.trim();
+        if mnemonic == "DB" || mnemonic == ".BYTE" {
+            return rest.split(',').map(|byte| Value::parse(byte.trim())).collect::<Result<_, _>>().map(Self::Bytes);
+        }
+        // SAVE and LOAD take a range of registers, VX - VY.
+        let separator = if mnemonic == "SAVE" || mnemonic == "LOAD" { '-' } else { ',' };
+        let operands = if rest.is_empty() {
+            Vec::new()
+        } else {
+            rest.split(separator).map(|operand| Operand::parse(operand.trim())).collect::<Result<_, _>>()?
+        };
+        Ok(Self::Instruction { mnemonic, operands })
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Bytes(bytes) => bytes.len(),
+            Self::Instruction { mnemonic, operands } => match (mnemonic.as_str(), operands.as_slice()) {
+                ("LD", [Operand::I, Operand::Long(_)]) => 4,
+                ("LD", [Operand::I, Operand::Value(Value::Number(n))]) if *n > 0xFFF => 4,
+                _ => 2,
+            },
+        }
+    }
+
+    fn encode(&self, labels: &BTreeMap<String, u32>, rom: &mut Vec<u8>) -> Result<(), String> {
+        let (mnemonic, operands) = match self {
+            Self::Bytes(bytes) => {
+                for byte in bytes {
+                    rom.push(byte.resolve(labels, 0xFF)? as u8);
+                }
+                return Ok(());
+            }
+            Self::Instruction { mnemonic, operands } => (mnemonic.as_str(), operands.as_slice()),
+        };
+        let value = |value: &Value, max: u32| value.resolve(labels, max);
+        let nibble = |v: &Value| value(v, 0xF).map(|n| n as u8);
+        let byte = |v: &Value| value(v, 0xFF).map(|n| n as u8);
+        let addr = |v: &Value| value(v, 0xFFF).map(|n| n as u16);
+        use Operand::{B, Dt, F, Hf, I, Indirect, K, Long, R, St, V};
+        use Operand::Value as N;
+        let instruction = match (mnemonic, operands) {
+            ("CLS", []) => Instruction::ClearScreen,
+            ("RET", []) => Instruction::Return,
+            ("SCR", []) => Instruction::ScrollRight,
+            ("SCL", []) => Instruction::ScrollLeft,
+            ("EXIT", []) => Instruction::Exit,
+            ("LOW", []) => Instruction::LowRes,
+            ("HIGH", []) => Instruction::HighRes,
+            ("AUDIO", []) => Instruction::LoadAudio,
+            ("SCD", [N(n)]) => Instruction::ScrollDown(nibble(n)?),
+            ("SCU", [N(n)]) => Instruction::ScrollUp(nibble(n)?),
+            ("PLANE", [N(n)]) => Instruction::SelectPlanes(nibble(n)?),
+            ("JP", [N(nnn)]) => Instruction::Jump(addr(nnn)?),
+            ("JP", [V(0), N(nnn)]) => Instruction::JumpOffset(addr(nnn)?),
+            ("CALL", [N(nnn)]) => Instruction::Call(addr(nnn)?),
+            ("SE", [V(x), N(nn)]) => Instruction::SkipIfEq { x: *x, nn: byte(nn)? },
+            ("SE", [V(x), V(y)]) => Instruction::SkipIfRegEq { x: *x, y: *y },
+            ("SNE", [V(x), N(nn)]) => Instruction::SkipIfNe { x: *x, nn: byte(nn)? },
+            ("SNE", [V(x), V(y)]) => Instruction::SkipIfRegNe { x: *x, y: *y },
+            ("SAVE", [V(x), V(y)]) => Instruction::StoreRange { x: *x, y: *y },
+            ("LOAD", [V(x), V(y)]) => Instruction::LoadRange { x: *x, y: *y },
+            ("LD", [V(x), N(nn)]) => Instruction::SetVx { x: *x, nn: byte(nn)? },
+            ("LD", [V(x), V(y)]) => Instruction::Copy { x: *x, y: *y },
+            ("LD", [I, Long(nnnn)] | [I, N(nnnn @ Value::Number(0x1000..))]) => {
+                let nnnn = value(nnnn, 0xFFFF)? as u16;
+                rom.extend_from_slice(&Instruction::LongIndex.opcode().to_be_bytes());
+                rom.extend_from_slice(&nnnn.to_be_bytes());
+                return Ok(());
+            }
+            ("LD", [I, N(nnn)]) => Instruction::SetIndex(addr(nnn)?),
+            ("LD", [V(x), Dt]) => Instruction::GetDelay { x: *x },
+            ("LD", [V(x), K]) => Instruction::WaitKey { x: *x },
+            ("LD", [Dt, V(x)]) => Instruction::SetDelay { x: *x },
+            ("LD", [St, V(x)]) => Instruction::SetSound { x: *x },
+            ("LD", [F, V(x)]) => Instruction::FontChar { x: *x },
+            ("LD", [Hf, V(x)]) => Instruction::BigFontChar { x: *x },
+            ("LD", [B, V(x)]) => Instruction::Bcd { x: *x },
+            ("LD", [Indirect, V(x)]) => Instruction::StoreRegs { x: *x },
+            ("LD", [V(x), Indirect]) => Instruction::LoadRegs { x: *x },
+            ("LD", [R, V(x)]) => Instruction::StoreFlags { x: *x },
+            ("LD", [V(x), R]) => Instruction::LoadFlags { x: *x },
+            ("ADD", [V(x), N(nn)]) => Instruction::AddVx { x: *x, nn: byte(nn)? },
+            ("ADD", [V(x), V(y)]) => Instruction::AddReg { x: *x, y: *y },
+            ("ADD", [I, V(x)]) => Instruction::AddIndex { x: *x },
+            ("OR", [V(x), V(y)]) => Instruction::Or { x: *x, y: *y },
+            ("AND", [V(x), V(y)]) => Instruction::And { x: *x, y: *y },
+            ("XOR", [V(x), V(y)]) => Instruction::Xor { x: *x, y: *y },
+            ("SUB", [V(x), V(y)]) => Instruction::SubReg { x: *x, y: *y },
+            ("SUBN", [V(x), V(y)]) => Instruction::SubReverse { x: *x, y: *y },
+            ("SHR", [V(x)]) => Instruction::ShiftRight { x: *x, y: *x },
+            ("SHR", [V(x), V(y)]) => Instruction::ShiftRight { x: *x, y: *y },
+            ("SHL", [V(x)]) => Instruction::ShiftLeft { x: *x, y: *x },
+            ("SHL", [V(x), V(y)]) => Instruction::ShiftLeft { x: *x, y: *y },
+            ("RND", [V(x), N(nn)]) => Instruction::Random { x: *x, nn: byte(nn)? },
+            ("DRW", [V(x), V(y), N(n)]) => Instruction::Draw { x: *x, y: *y, n: nibble(n)? },
+            ("SKP", [V(x)]) => Instruction::SkipIfKey { x: *x },
+            ("SKNP", [V(x)]) => Instruction::SkipIfNotKey { x: *x },
+            ("PITCH", [V(x)]) => Instruction::SetPitch { x: *x },
+            _ => return Err(format!("unknown instruction {} with {} operands", mnemonic, operands.len())),
+        };
+        rom.extend_from_slice(&instruction.opcode().to_be_bytes());
+        Ok(())
+    }
+}
+
+enum Operand {
+    V(usize),
+    I,
+    /// `[I]`, FX55's and FX65's memory at I.
+    Indirect,
+    Dt,
+    St,
+    K,
+    F,
+    Hf,
+    B,
+    R,
+    /// `long N`, F000's 16-bit address.
+    Long(Value),
+    Value(Value),
+}
+
+impl Operand {
+    fn parse(text: &str) -> Result<Self, String> {
+        let upper = text.to_ascii_uppercase();
+        let operand = match upper.as_str() {
+            "I" => Self::I,
+            "[I]" => Self::Indirect,
+            "DT" => Self::Dt,
+            "ST" => Self::St,
+            "K" => Self::K,
+            "F" => Self::F,
+            "HF" => Self::Hf,
+            "B" => Self::B,
+            "R" => Self::R,
+            _ => match upper.strip_prefix('V').and_then(|x| (x.len() == 1).then(|| usize::from_str_radix(x, 16).ok()).flatten()) {
+                Some(x) => Self::V(x),
+                None => match upper.strip_prefix("LONG ") {
+                    Some(_) => Self::Long(Value::parse(text[5..].trim())?),
+                    None => Self::Value(Value::parse(text)?),
+                },
+            },
+        };
+        Ok(operand)
+    }
+}
+
+enum Value {
+    Number(u32),
+    Label(String),
+}
+
+impl Value {
+    fn parse(text: &str) -> Result<Self, String> {
+        let lower = text.to_ascii_lowercase();
+        let number = if let Some(hex) = lower.strip_prefix("0x") {
+            u32::from_str_radix(hex, 16)
+        } else if let Some(binary) = lower.strip_prefix("0b") {
+            u32::from_str_radix(binary, 2)
+        } else if is_label(text) {
+            return Ok(Self::Label(lower));
+        } else {
+            lower.parse()
+        };
+        number.map(Self::Number).map_err(|_| format!("{:?} isn't a number or a label", text))
+    }
+
+    /// The value, which mustn't be more than `max`.
+    fn resolve(&self, labels: &BTreeMap<String, u32>, max: u32) -> Result<u32, String> {
+        let value = match self {
+            Self::Number(n) => *n,
+            Self::Label(label) => *labels.get(label).ok_or_else(|| format!("no label called {}", label))?,
+        };
+        if value > max {
+            return Err(format!("0x{:X} is more than 0x{:X}", value, max));
+        }
+        Ok(value)
+    }
+}
@@ -0,0 +1,70 @@
+//! The system clipboard, reached through the platform's command-line tools
+//! so the binary doesn't need a clipboard library. The screen goes on it as
+//! a PNG where a tool takes images, as on Wayland and X11, and as ASCII art
+//! everywhere else.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use chip8_emulator::frontend::clipboard::{Clipboard, ClipboardImage};
+
+/// The tools to try, in order, each as a program and its arguments.
+#[cfg(target_os = "macos")]
+const TOOLS: &[&[&str]] = &[&["pbcopy"]];
+#[cfg(windows)]
+const TOOLS: &[&[&str]] = &[&["clip"]];
+#[cfg(not(any(target_os = "macos", windows)))]
+const TOOLS: &[&[&str]] = &[&["wl-copy"], &["xclip", "-selection", "clipboard"], &["xsel", "--clipboard", "--input"]];
+
+/// The tools that take a PNG, likewise.
+#[cfg(any(target_os = "macos", windows))]
+const IMAGE_TOOLS: &[&[&str]] = &[];
+#[cfg(not(any(target_os = "macos", windows)))]
+const IMAGE_TOOLS: &[&[&str]] =
+    &[&["wl-copy", "--type", "image/png"], &["xclip", "-selection", "clipboard", "-t", "image/png"]];
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClipboard;
+
+/// Runs `tool` with `bytes` on its standard input.
+fn pipe_to(tool: &[&str], bytes: &[u8]) -> Result<(), String> {
+    let mut child = Command::new(tool[0])
+        .args(&tool[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("{}: {}", tool[0], e))?;
+    let written = child.stdin.take().map(|mut stdin| stdin.write_all(bytes));
+    let status = child.wait().map_err(|e| format!("{}: {}", tool[0], e))?;
+    match written {
+        Some(Err(e)) => Err(format!("{}: {}", tool[0], e)),
+        _ if !status.success() => Err(format!("{}: {}", tool[0], status)),
+        _ => Ok(()),
+    }
+}
+
+/// Pipes `bytes` to the first of `tools` that takes them.
+fn pipe_to_any(tools: &[&[&str]], bytes: &[u8]) -> Result<(), String> {
+    let mut errors = Vec::new();
+    for tool in tools {
+        match pipe_to(tool, bytes) {
+            Ok(()) => return Ok(()),
+            Err(e) => errors.push(e),
+        }
+    }
+    if errors.is_empty() {
+        return Err("no clipboard tool takes images here".to_string());
+    }
+    Err(errors.join("; "))
+}
+
+impl Clipboard for SystemClipboard {
+    fn set_text(&mut self, text: &str) -> Result<(), String> {
+        pipe_to_any(TOOLS, text.as_bytes())
+    }
+
+    fn set_image(&mut self, image: &ClipboardImage) -> Result<(), String> {
+        pipe_to_any(IMAGE_TOOLS, &image.to_png())
+    }
+}
@@ -0,0 +1,333 @@
+use chip8_emulator::frontend::Frame;
+use chip8_emulator::frontend::console::{CONSOLE_ROWS, ConsoleView};
+use chip8_emulator::frontend::help::{HelpLine, HelpPage, help_page_lines, help_text_scale};
+use chip8_emulator::frontend::keypad_card::{KeypadCard, card_layout};
+use chip8_emulator::frontend::menu::MenuView;
+use chip8_emulator::frontend::registers::{REGISTER_ROWS, RegistersView};
+use chip8_emulator::frontend::timer::TimerView;
+use chip8_emulator::overlay::indicators::draw_indicators;
+use chip8_emulator::overlay::{self, CHAR_ADVANCE, LINE_HEIGHT};
+
+use super::WINDOW_SCALE;
+
+pub const ERROR_SCREEN_WIDTH: usize = 64 * WINDOW_SCALE;
+pub const ERROR_SCREEN_HEIGHT: usize = 32 * WINDOW_SCALE;
+const ERROR_TEXT_SCALE: usize = 2;
+const INDICATOR_SCALE: usize = 2;
+const CONSOLE_TEXT_SCALE: usize = 2;
+const REGISTER_TEXT_SCALE: usize = 1;
+
+// The help screen's title, a blank line, the page, another blank line and
+// its footer fit between the margins at either text size.
+const fn help_fits(text_scale: usize) -> bool {
+    (help_page_lines(text_scale) + 4) * LINE_HEIGHT * text_scale + 4 * CHAR_ADVANCE * text_scale <= ERROR_SCREEN_HEIGHT
+}
+const _: () = assert!(help_fits(help_text_scale(1)) && help_fits(help_text_scale(WINDOW_SCALE)));
+
+/// Renders `title` and the word-wrapped `message` at window resolution, over
+/// a dimmed copy of the `background` frame if there is one.
+pub fn render_error_screen(title: &str, message: &str, background: Option<&[u32]>) -> Vec<u32> {
+    let mut screen = vec![0u32; ERROR_SCREEN_WIDTH * ERROR_SCREEN_HEIGHT];
+
+    if let Some(frame) = background {
+        upscale(frame, &mut screen);
+        dim(&mut screen);
+    }
+
+    let margin = 2 * CHAR_ADVANCE * ERROR_TEXT_SCALE;
+    let columns = (ERROR_SCREEN_WIDTH - 2 * margin) / (CHAR_ADVANCE * ERROR_TEXT_SCALE);
+    let line_height = (LINE_HEIGHT * ERROR_TEXT_SCALE) as i32;
+
+    let mut lines = vec![title.to_string(), String::new()];
+    lines.extend(overlay::wrap_text(message, columns));
+    lines.push(String::new());
+    lines.push("Press ESC to exit".to_string());
+
+    let mut y = margin as i32;
+    for (index, line) in lines.iter().enumerate() {
+        let color = if index == 0 { 0xFF5555 } else { 0xFFFFFF };
+        overlay::draw_text(
+            &mut screen,
+            ERROR_SCREEN_WIDTH,
+            margin as i32,
+            y,
+            line,
+            color,
+            None,
+            ERROR_TEXT_SCALE,
+        );
+        y += line_height;
+    }
+
+    screen
+}
+
+/// What to show instead of `frame`'s plain display while it isn't halted:
+/// the keypad card or help overlay if either is open, or else the pause menu or the display
+/// with the state hash, under the status indicators, the speedrun timer, a
+/// notice, the register readout and the console. `None`
+/// when there's nothing to add, so the window gets the display as it is.
+pub fn overlay_screen(frame: &Frame<'_>) -> Option<Vec<u32>> {
+    if let Some(card) = frame.keypad_card {
+        return Some(render_keypad_screen(&frame.to_rgb(), card));
+    }
+    if let Some(help) = frame.help {
+        return Some(render_help_screen(&frame.to_rgb(), help, frame.scale.unwrap_or(WINDOW_SCALE)));
+    }
+    let mut screen = if let Some(menu) = frame.menu {
+        render_menu_screen(&frame.to_rgb(), menu)
+    } else if let Some(hash) = frame.state_hash {
+        render_hash_screen(&frame.to_rgb(), hash)
+    } else if !frame.indicators.is_empty()
+        || frame.timer.is_some()
+        || frame.notice.is_some()
+        || frame.registers.is_some()
+        || frame.console.is_some()
+    {
+        let mut screen = vec![0u32; ERROR_SCREEN_WIDTH * ERROR_SCREEN_HEIGHT];
+        upscale(&frame.to_rgb(), &mut screen);
+        screen
+    } else {
+        return None;
+    };
+    draw_indicators(&mut screen, ERROR_SCREEN_WIDTH, frame.indicators, INDICATOR_SCALE);
+    if let Some(timer) = frame.timer {
+        draw_timer(&mut screen, timer);
+    }
+    if let Some(notice) = frame.notice {
+        draw_notice(&mut screen, notice);
+    }
+    if let Some(registers) = frame.registers {
+        draw_registers(&mut screen, registers);
+    }
+    if let Some(console) = frame.console {
+        draw_console(&mut screen, console);
+    }
+    Some(screen)
+}
+
+/// Draws the register readout in the bottom-left corner, small enough to
+/// leave the timer its corner, what changed in yellow.
+fn draw_registers(screen: &mut [u32], registers: &RegistersView) {
+    let margin = INDICATOR_SCALE as i32;
+    let advance = (CHAR_ADVANCE * REGISTER_TEXT_SCALE) as i32;
+    let line_height = (LINE_HEIGHT * REGISTER_TEXT_SCALE) as i32;
+    let top = ERROR_SCREEN_HEIGHT as i32 - margin - REGISTER_ROWS as i32 * line_height;
+    for (row, column, cell) in registers.cells() {
+        let (x, y) = (margin + column as i32 * advance, top + row as i32 * line_height);
+        let color = if cell.changed { 0xFFFF55 } else { 0xFFFFFF };
+        // The space after each value too, so the rows read as one box.
+        let text = format!("{} ", cell.text);
+        overlay::draw_text(screen, ERROR_SCREEN_WIDTH, x, y, &text, color, Some(0x000000), REGISTER_TEXT_SCALE);
+    }
+}
+
+/// Draws the speedrun timer in the bottom-right corner, its last splits
+/// above it: grey while it isn't counting, red once turbo has spoiled the
+/// run.
+fn draw_timer(screen: &mut [u32], timer: &TimerView) {
+    let time_color = match (timer.invalid, timer.running) {
+        (true, _) => 0xFF5555,
+        (false, true) => 0xFFFFFF,
+        (false, false) => 0xAAAAAA,
+    };
+    let mut lines: Vec<(String, u32)> = timer.split_lines().map(|line| (line, 0xAAAAAA)).collect();
+    lines.push((timer.time(), time_color));
+
+    let margin = INDICATOR_SCALE as i32;
+    let line_height = (LINE_HEIGHT * INDICATOR_SCALE) as i32;
+    let bottom = ERROR_SCREEN_HEIGHT as i32 - margin;
+    for (index, (line, color)) in lines.iter().rev().enumerate() {
+        let (width, _) = overlay::measure_text(line, INDICATOR_SCALE);
+        let (x, y) = (ERROR_SCREEN_WIDTH as i32 - margin - width as i32, bottom - (index as i32 + 1) * line_height);
+        overlay::draw_text(screen, ERROR_SCREEN_WIDTH, x, y, line, *color, Some(0x000000), INDICATOR_SCALE);
+    }
+}
+
+/// Draws `notice` boxed in the middle of the screen.
+fn draw_notice(screen: &mut [u32], notice: &str) {
+    let (width, height) = overlay::measure_text(notice, ERROR_TEXT_SCALE);
+    let padding = CHAR_ADVANCE * ERROR_TEXT_SCALE;
+    let (w, h) = (width + 2 * padding, height + 2 * padding);
+    let x = (ERROR_SCREEN_WIDTH - w) as i32 / 2;
+    let y = (ERROR_SCREEN_HEIGHT - h) as i32 / 2;
+    overlay::fill_rect(screen, ERROR_SCREEN_WIDTH, x, y, w, h, 0x000000);
+    overlay::draw_box(screen, ERROR_SCREEN_WIDTH, x, y, w, h, 0xFFFFFF);
+    let (text_x, text_y) = (x + padding as i32, y + padding as i32);
+    overlay::draw_text(screen, ERROR_SCREEN_WIDTH, text_x, text_y, notice, 0xFFFFFF, None, ERROR_TEXT_SCALE);
+}
+
+/// Drops the console down over the top of `screen`: its output, then the
+/// input line with a block for the cursor.
+fn draw_console(screen: &mut [u32], console: &ConsoleView) {
+    let line_height = LINE_HEIGHT * CONSOLE_TEXT_SCALE;
+    let margin = CHAR_ADVANCE * CONSOLE_TEXT_SCALE;
+    let height = (CONSOLE_ROWS + 1) * line_height + 2 * margin;
+    overlay::fill_rect(screen, ERROR_SCREEN_WIDTH, 0, 0, ERROR_SCREEN_WIDTH, height, 0x101010);
+    overlay::fill_rect(screen, ERROR_SCREEN_WIDTH, 0, height as i32, ERROR_SCREEN_WIDTH, 1, 0x555555);
+
+    let mut draw = |row: usize, column: usize, text: &str, color: u32, background: Option<u32>| {
+        let (x, y) = (margin + column * margin, margin + row * line_height);
+        overlay::draw_text(screen, ERROR_SCREEN_WIDTH, x as i32, y as i32, text, color, background, CONSOLE_TEXT_SCALE);
+    };
+    let first = CONSOLE_ROWS - console.lines.len();
+    for (row, line) in console.lines.iter().enumerate() {
+        let color = if line.starts_with("error:") { 0xFF5555 } else { 0xFFFFFF };
+        draw(first + row, 0, line, color, None);
+    }
+    if console.scrolled {
+        draw(CONSOLE_ROWS - 1, ERROR_SCREEN_WIDTH / margin - 4, "...", 0xAAAAAA, None);
+    }
+
+    // A line too long for the screen scrolls to keep the cursor in view.
+    let columns = (ERROR_SCREEN_WIDTH - 2 * margin) / margin - 2;
+    let skip = (console.cursor + 1).saturating_sub(columns);
+    let input: String = console.input.chars().skip(skip).take(columns).collect();
+    draw(CONSOLE_ROWS, 0, &format!("> {}", input), 0xFFFF55, None);
+    let under: String = console.input.chars().nth(console.cursor).map_or(" ".to_string(), String::from);
+    draw(CONSOLE_ROWS, 2 + console.cursor - skip, &under, 0x101010, Some(0xFFFF55));
+}
+
+/// Renders the pause menu at window resolution over a dimmed copy of the
+/// `frame`.
+pub fn render_menu_screen(frame: &[u32], menu: &MenuView) -> Vec<u32> {
+    let mut screen = vec![0u32; ERROR_SCREEN_WIDTH * ERROR_SCREEN_HEIGHT];
+    upscale(frame, &mut screen);
+    dim(&mut screen);
+
+    let margin = 2 * CHAR_ADVANCE * ERROR_TEXT_SCALE;
+    frame_panel(&mut screen, margin);
+    let line_height = (LINE_HEIGHT * ERROR_TEXT_SCALE) as i32;
+    let mut draw = |y: i32, text: &str, color: u32| {
+        overlay::draw_text(&mut screen, ERROR_SCREEN_WIDTH, margin as i32, y, text, color, None, ERROR_TEXT_SCALE);
+    };
+
+    let top = margin as i32;
+    draw(top, "Paused", 0xFF5555);
+    for (index, label) in menu.labels.iter().enumerate() {
+        let y = top + (index as i32 + 2) * line_height;
+        if index == menu.selected {
+            draw(y, &format!("> {}", label), 0xFFFF55);
+        } else {
+            draw(y, &format!("  {}", label), 0xFFFFFF);
+        }
+    }
+    screen
+}
+
+/// Renders a page of the help overlay at window resolution over a dimmed
+/// copy of the `frame`, its text sized for a window at `scale`.
+pub fn render_help_screen(frame: &[u32], help: &HelpPage, scale: usize) -> Vec<u32> {
+    let mut screen = vec![0u32; ERROR_SCREEN_WIDTH * ERROR_SCREEN_HEIGHT];
+    upscale(frame, &mut screen);
+    dim(&mut screen);
+
+    let text_scale = help_text_scale(scale);
+    let margin = 2 * CHAR_ADVANCE * text_scale;
+    frame_panel(&mut screen, margin);
+    let line_height = (LINE_HEIGHT * text_scale) as i32;
+    let columns = (ERROR_SCREEN_WIDTH - 2 * margin) / (CHAR_ADVANCE * text_scale);
+    let mut draw = |row: usize, text: &str, color: u32| {
+        let y = margin as i32 + row as i32 * line_height;
+        overlay::draw_text(&mut screen, ERROR_SCREEN_WIDTH, margin as i32, y, text, color, None, text_scale);
+    };
+
+    draw(0, &help.title(), 0xFF5555);
+    for (index, line) in help.lines.iter().enumerate() {
+        let color = if matches!(line, HelpLine::Heading(_)) { 0xFFFF55 } else { 0xFFFFFF };
+        draw(index + 2, &line.to_string(), color);
+    }
+    let footer = ["F1/ESC close, arrows page, Enter keypad", "F1/ESC close, arrows page"]
+        .into_iter()
+        .find(|footer| footer.len() <= columns)
+        .unwrap_or_default();
+    draw(help_page_lines(text_scale) + 3, footer, 0xAAAAAA);
+    screen
+}
+
+/// Renders the keypad card at window resolution over a dimmed copy of the
+/// `frame`: a box per key with its digit, and under it the keys bound
+/// to it, as large as fits.
+pub fn render_keypad_screen(frame: &[u32], card: &KeypadCard) -> Vec<u32> {
+    let mut screen = vec![0u32; ERROR_SCREEN_WIDTH * ERROR_SCREEN_HEIGHT];
+    upscale(frame, &mut screen);
+    dim(&mut screen);
+
+    let margin = 2 * CHAR_ADVANCE * ERROR_TEXT_SCALE;
+    frame_panel(&mut screen, margin);
+    overlay::draw_text(&mut screen, ERROR_SCREEN_WIDTH, margin as i32, margin as i32, "Keypad", 0xFF5555, None, ERROR_TEXT_SCALE);
+
+    let top = margin + LINE_HEIGHT * ERROR_TEXT_SCALE;
+    let padding = CHAR_ADVANCE;
+    for cell in card_layout(ERROR_SCREEN_WIDTH - 2 * margin, ERROR_SCREEN_HEIGHT - top - margin, padding) {
+        let (x, y) = ((margin + cell.x) as i32, (top + cell.y) as i32);
+        let awaiting = card.awaiting == Some(cell.key);
+        if awaiting {
+            overlay::fill_rect(&mut screen, ERROR_SCREEN_WIDTH, x, y, cell.width, cell.height, 0x555500);
+        }
+        overlay::draw_box(&mut screen, ERROR_SCREEN_WIDTH, x, y, cell.width, cell.height, 0xAAAAAA);
+
+        let inner = cell.width.saturating_sub(2 * padding);
+        let (x, mut y) = (x + padding as i32, y + padding as i32);
+        overlay::draw_text(&mut screen, ERROR_SCREEN_WIDTH, x, y, &format!("{:X}", cell.key), 0xFFFF55, None, ERROR_TEXT_SCALE);
+        y += (LINE_HEIGHT * ERROR_TEXT_SCALE) as i32;
+
+        // Ever more keys bound to one cell get smaller text, then wrap.
+        let label = card.label(cell.key);
+        let scale = if overlay::measure_text(&label, ERROR_TEXT_SCALE).0 <= inner { ERROR_TEXT_SCALE } else { 1 };
+        let room = cell.height.saturating_sub(padding + LINE_HEIGHT * ERROR_TEXT_SCALE) / (LINE_HEIGHT * scale);
+        for line in overlay::wrap_text(&label, inner / (CHAR_ADVANCE * scale)).iter().take(room) {
+            overlay::draw_text(&mut screen, ERROR_SCREEN_WIDTH, x, y, line, 0xFFFFFF, None, scale);
+            y += (LINE_HEIGHT * scale) as i32;
+        }
+    }
+    screen
+}
+
+/// Renders the `frame` at window resolution with `hash` in its
+/// bottom-left corner, for `--show-hash`.
+pub fn render_hash_screen(frame: &[u32], hash: u64) -> Vec<u32> {
+    let mut screen = vec![0u32; ERROR_SCREEN_WIDTH * ERROR_SCREEN_HEIGHT];
+    upscale(frame, &mut screen);
+
+    let text = format!("{:016x}", hash);
+    let (_, height) = overlay::measure_text(&text, 1);
+    let y = (ERROR_SCREEN_HEIGHT - height) as i32;
+    overlay::draw_text(&mut screen, ERROR_SCREEN_WIDTH, 0, y, &text, 0xFFFF55, Some(0x000000), 1);
+    screen
+}
+
+/// Outlines the panel that menus and help are drawn in, halfway into the
+/// `margin` around their text.
+fn frame_panel(screen: &mut [u32], margin: usize) {
+    let inset = margin / 2;
+    overlay::draw_box(
+        screen,
+        ERROR_SCREEN_WIDTH,
+        inset as i32,
+        inset as i32,
+        ERROR_SCREEN_WIDTH - 2 * inset,
+        ERROR_SCREEN_HEIGHT - 2 * inset,
+        0x555555,
+    );
+}
+
+/// Stretches `frame` over `screen`: a 64x32 frame's pixels go
+/// [`WINDOW_SCALE`] wide, a 128x64 one's half that. Either is twice as
+/// wide as it's tall, which is all it takes to tell them apart.
+fn upscale(frame: &[u32], screen: &mut [u32]) {
+    let width = (2 * frame.len()).isqrt();
+    let scale = ERROR_SCREEN_WIDTH / width;
+    for (index, pixel) in screen.iter_mut().enumerate() {
+        let x = (index % ERROR_SCREEN_WIDTH) / scale;
+        let y = (index / ERROR_SCREEN_WIDTH) / scale;
+        *pixel = frame[y * width + x];
+    }
+}
+
+/// Darkens `screen` so text over it stands out.
+fn dim(screen: &mut [u32]) {
+    for pixel in screen {
+        *pixel = (*pixel >> 2) & 0x3F3F3F;
+    }
+}
@@ -0,0 +1,45 @@
+use std::time::{Duration, Instant};
+
+/// Paces backends that don't block on vsync to minifb's update rate of one
+/// frame every 16.6 ms.
+pub struct FrameLimiter {
+    next_frame: Instant,
+}
+
+impl FrameLimiter {
+    const FRAME_TIME: Duration = Duration::from_micros(16600);
+
+    pub fn new() -> Self {
+        Self {
+            next_frame: Instant::now(),
+        }
+    }
+
+    /// When the next frame is due, for event loops that wait on their own.
+    #[cfg(feature = "backend-pixels")]
+    pub fn next_frame(&self) -> Instant {
+        self.next_frame
+    }
+
+    /// Starts a frame if one is due, returning whether it was.
+    #[cfg(feature = "backend-pixels")]
+    pub fn try_start(&mut self) -> bool {
+        let now = Instant::now();
+        if self.next_frame > now {
+            return false;
+        }
+        // Keep to the schedule, unless so far behind it a burst would follow.
+        self.next_frame = (self.next_frame + Self::FRAME_TIME).max(now);
+        true
+    }
+
+    /// Sleeps until the next frame is due.
+    #[cfg(any(feature = "backend-sdl2", feature = "backend-terminal"))]
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        if self.next_frame > now {
+            std::thread::sleep(self.next_frame - now);
+        }
+        self.next_frame = self.next_frame.max(now) + Self::FRAME_TIME;
+    }
+}
@@ -0,0 +1,435 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use log::{error, warn};
+use minifb::{InputCallback, Key, KeyRepeat, ScaleMode, Window, WindowOptions};
+
+use chip8_emulator::frontend::console::ConsoleKey;
+use chip8_emulator::frontend::help::Binding;
+use chip8_emulator::frontend::menu::MenuKey;
+use chip8_emulator::frontend::secondary::{self, SECONDARY_KEYS, SecondaryKey};
+use chip8_emulator::frontend::speed::BASE_HZ;
+use chip8_emulator::frontend::{
+    Frame, InputEvent, InputSource, VideoSink, WindowToggle,
+};
+
+use super::error_screen::{ERROR_SCREEN_HEIGHT, ERROR_SCREEN_WIDTH, overlay_screen, render_error_screen};
+use super::{CTRL_KEYPAD_KEYS, MODIFIED_KEYS, WindowConfig, ctrl_digit, toggled_message, window_title, with_modifiers};
+use crate::FRONTEND_TARGET;
+
+/// Keypad layout: the left four columns of the keyboard.
+const KEYPAD: [(Key, &str, u8); 16] = [
+    (Key::Key1, "1", 0x1),
+    (Key::Key2, "2", 0x2),
+    (Key::Key3, "3", 0x3),
+    (Key::Key4, "4", 0xC),
+    (Key::Q, "Q", 0x4),
+    (Key::W, "W", 0x5),
+    (Key::E, "E", 0x6),
+    (Key::R, "R", 0xD),
+    (Key::A, "A", 0x7),
+    (Key::S, "S", 0x8),
+    (Key::D, "D", 0x9),
+    (Key::F, "F", 0xE),
+    (Key::Z, "Z", 0xA),
+    (Key::X, "X", 0x0),
+    (Key::C, "C", 0xB),
+    (Key::V, "V", 0xF),
+];
+
+/// The window's key for each secondary binding.
+fn secondary_key(key: SecondaryKey) -> Key {
+    match key {
+        SecondaryKey::Up => Key::Up,
+        SecondaryKey::Down => Key::Down,
+        SecondaryKey::Left => Key::Left,
+        SecondaryKey::Right => Key::Right,
+        SecondaryKey::Space => Key::Space,
+        SecondaryKey::Enter => Key::Enter,
+    }
+}
+
+/// 1 to 9 on the digit row, for Ctrl and a digit; 0 is a hotkey.
+const SCALE_DIGITS: [Key; 9] = [
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Key4,
+    Key::Key5,
+    Key::Key6,
+    Key::Key7,
+    Key::Key8,
+    Key::Key9,
+];
+
+/// Every other key, and whether it repeats while held.
+const HOTKEYS: [(Key, &str, InputEvent, KeyRepeat); 25] = [
+    (Key::Equal, "=", InputEvent::SpeedUp, KeyRepeat::No),
+    (Key::Minus, "-", InputEvent::SlowDown, KeyRepeat::No),
+    (Key::Key0, "0", InputEvent::ResetSpeed, KeyRepeat::No),
+    (Key::Escape, "Esc", InputEvent::Menu(MenuKey::Escape), KeyRepeat::No),
+    (Key::Up, "Up", InputEvent::Menu(MenuKey::Up), KeyRepeat::Yes),
+    (Key::Down, "Down", InputEvent::Menu(MenuKey::Down), KeyRepeat::Yes),
+    (Key::Left, "Left", InputEvent::Menu(MenuKey::Left), KeyRepeat::Yes),
+    (Key::Right, "Right", InputEvent::Menu(MenuKey::Right), KeyRepeat::Yes),
+    (Key::Enter, "Enter", InputEvent::Menu(MenuKey::Enter), KeyRepeat::No),
+    (Key::F1, "F1", InputEvent::Help, KeyRepeat::No),
+    (Key::F2, "F2", InputEvent::Split, KeyRepeat::No),
+    (Key::F6, "F6", InputEvent::SaveGameSettings, KeyRepeat::No),
+    (Key::F7, "F7", InputEvent::Window(WindowToggle::AlwaysOnTop), KeyRepeat::No),
+    (Key::F8, "F8", InputEvent::Window(WindowToggle::Borderless), KeyRepeat::No),
+    (Key::F9, "F9", InputEvent::DumpState, KeyRepeat::No),
+    (Key::F10, "F10", InputEvent::AsciiDump, KeyRepeat::No),
+    (Key::F12, "F12", InputEvent::Window(WindowToggle::Resizable), KeyRepeat::No),
+    (Key::P, "P", InputEvent::Pause, KeyRepeat::No),
+    (Key::N, "N", InputEvent::Step, KeyRepeat::Yes),
+    (Key::Period, ".", InputEvent::Step, KeyRepeat::Yes),
+    (Key::F3, "F3", InputEvent::Registers, KeyRepeat::No),
+    (Key::Backquote, "`", InputEvent::Console(ConsoleKey::Toggle), KeyRepeat::No),
+    (Key::Backspace, "Backspace", InputEvent::Console(ConsoleKey::Backspace), KeyRepeat::Yes),
+    (Key::PageUp, "PageUp", InputEvent::Console(ConsoleKey::PageUp), KeyRepeat::Yes),
+    (Key::PageDown, "PageDown", InputEvent::Console(ConsoleKey::PageDown), KeyRepeat::Yes),
+];
+
+/// What the window's keys send, for the help overlay, with the secondary
+/// bindings if they're on.
+pub fn bindings(secondary: bool) -> Vec<Binding> {
+    let keypad = KEYPAD.iter().map(|&(_, name, key)| Binding {
+        key: name,
+        event: InputEvent::Key { key, pressed: true },
+    });
+    let secondary = secondary.then(secondary::bindings).into_iter().flatten();
+    let hotkeys = HOTKEYS.iter().map(|&(_, name, event, _)| Binding { key: name, event });
+    keypad.chain(secondary).chain(hotkeys).chain(MODIFIED_KEYS).collect()
+}
+
+/// The keypad keys the 4x4 block holds.
+pub fn read_keys(window: &Window) -> [bool; 16] {
+    let mut keys = [false; 16];
+    for (code, _, key) in KEYPAD {
+        keys[key as usize] = window.is_key_down(code);
+    }
+    keys
+}
+
+/// The keypad keys the secondary bindings hold.
+fn read_secondary_keys(window: &Window) -> [bool; 16] {
+    let mut keys = [false; 16];
+    for (secondary, _, key) in SECONDARY_KEYS {
+        keys[key as usize] |= window.is_key_down(secondary_key(secondary));
+    }
+    keys
+}
+
+/// Opens a window of `width` x `height` pixels that refreshes at 60 Hz.
+pub fn open_window(title: &str, width: usize, height: usize) -> Window {
+    build_window(title, width, height, WindowOptions::default()).expect("Failed to create window")
+}
+
+fn build_window(title: &str, width: usize, height: usize, options: WindowOptions) -> minifb::Result<Window> {
+    let mut window = Window::new(title, width, height, options)?;
+    window.limit_update_rate(Some(Duration::from_micros(16600)));
+    Ok(window)
+}
+
+/// `config`'s options, letterboxing the display when the window is resized
+/// out of its 2:1 shape.
+fn window_options(config: WindowConfig) -> WindowOptions {
+    WindowOptions {
+        borderless: config.borderless,
+        resize: config.resizable,
+        topmost: config.always_on_top,
+        scale_mode: ScaleMode::AspectRatioStretch,
+        ..WindowOptions::default()
+    }
+}
+
+/// minifb only keeps windows on top on Windows and macOS.
+const CAN_STAY_ON_TOP: bool = cfg!(any(windows, target_os = "macos"));
+
+/// The window both halves use, with what it takes to build it again when an
+/// option changes.
+struct SharedWindow {
+    window: Window,
+    config: WindowConfig,
+    title: String,
+    typed: Rc<RefCell<Vec<char>>>,
+    /// The window was replaced since the input half last looked.
+    rebuilt: bool,
+}
+
+impl SharedWindow {
+    /// Replaces the window with one `width` x `height` built from the
+    /// config, where it was, with its title and char callback. Returns
+    /// whether that worked; if not, the old window stays.
+    fn rebuild(&mut self, width: usize, height: usize) -> bool {
+        let (x, y) = self.window.get_position();
+        match build_window(&self.title, width, height, window_options(self.config)) {
+            Ok(mut window) => {
+                window.set_position(x, y);
+                window.set_input_callback(Box::new(Typed(Rc::clone(&self.typed))));
+                self.window = window;
+                self.rebuilt = true;
+                true
+            }
+            Err(e) => {
+                warn!(target: FRONTEND_TARGET, "Could not rebuild the window, keeping it as it was: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Flips `toggle`. Keeping on top changes in place, but minifb only
+    /// applies the other options to a new window, the size this one is.
+    fn toggle(&mut self, toggle: WindowToggle) {
+        let on = self.config.toggle(toggle);
+        if toggle == WindowToggle::AlwaysOnTop {
+            if !CAN_STAY_ON_TOP {
+                warn!(target: FRONTEND_TARGET, "minifb can't keep windows on top on this platform");
+            }
+            self.window.topmost(on);
+        } else {
+            let (width, height) = self.window.get_size();
+            if !self.rebuild(width, height) {
+                self.config.toggle(toggle);
+                return;
+            }
+        }
+        println!("{}", toggled_message(toggle, on));
+    }
+
+    /// Rebuilds the window at `scale`, since minifb can't resize one.
+    fn set_scale(&mut self, scale: usize) {
+        let old = std::mem::replace(&mut self.config.scale, scale);
+        if self.rebuild(64 * scale, 32 * scale) {
+            println!("Window scale: {}x", scale);
+        } else {
+            self.config.scale = old;
+        }
+    }
+}
+
+/// The display half of the emulator window.
+pub struct MinifbVideo {
+    window: Rc<RefCell<SharedWindow>>,
+    speed_hz: u32,
+    stopped: bool,
+    overlaid: bool,
+}
+
+/// The keyboard half of the emulator window.
+pub struct MinifbInput {
+    window: Rc<RefCell<SharedWindow>>,
+    keys: [bool; 16],
+    /// Keypad keys held when the window was rebuilt, which the new window
+    /// doesn't know are down. They stay held until it sees them pressed, or
+    /// it loses focus.
+    carried: [bool; 16],
+    focused: bool,
+    /// Whether the arrows, Space and Enter press keypad keys too.
+    secondary: bool,
+    typed: Rc<RefCell<Vec<char>>>,
+}
+
+/// Collects the characters the window's char callback reports, for the
+/// console.
+struct Typed(Rc<RefCell<Vec<char>>>);
+
+impl InputCallback for Typed {
+    fn add_char(&mut self, uni_char: u32) {
+        if let Some(ch) = char::from_u32(uni_char) {
+            self.0.borrow_mut().push(ch);
+        }
+    }
+}
+
+/// Opens the emulator window, split into its video and input halves, with
+/// the `secondary` bindings or without.
+pub fn open(config: WindowConfig, secondary: bool) -> (MinifbVideo, MinifbInput) {
+    if config.fullscreen {
+        warn!(target: FRONTEND_TARGET, "minifb has no fullscreen mode, opening a window instead");
+    }
+    if config.always_on_top && !CAN_STAY_ON_TOP {
+        warn!(target: FRONTEND_TARGET, "minifb can't keep windows on top on this platform");
+    }
+    let title = "Chip-8 Emulator".to_string();
+    let mut window = build_window(&title, 64 * config.scale, 32 * config.scale, window_options(config))
+        .expect("Failed to create window");
+    let typed = Rc::new(RefCell::new(Vec::new()));
+    window.set_input_callback(Box::new(Typed(Rc::clone(&typed))));
+    let window = Rc::new(RefCell::new(SharedWindow {
+        window,
+        config,
+        title,
+        typed: Rc::clone(&typed),
+        rebuilt: false,
+    }));
+
+    let video = MinifbVideo {
+        window: Rc::clone(&window),
+        speed_hz: BASE_HZ,
+        stopped: false,
+        overlaid: false,
+    };
+    let input = MinifbInput {
+        window,
+        keys: [false; 16],
+        carried: [false; 16],
+        focused: true,
+        secondary,
+        typed,
+    };
+    (video, input)
+}
+
+impl VideoSink for MinifbVideo {
+    fn present(&mut self, frame: &Frame<'_>) {
+        let shared = &mut *self.window.borrow_mut();
+
+        if frame.speed.hz() != self.speed_hz {
+            self.speed_hz = frame.speed.hz();
+            println!("Speed: {}", frame.speed.label());
+        }
+
+        let rebuilt = match frame.scale {
+            Some(scale) if scale != shared.config.scale => {
+                shared.set_scale(scale);
+                true
+            }
+            _ => false,
+        };
+
+        let title = window_title(frame);
+        if title != shared.title {
+            shared.window.set_title(&title);
+            shared.title = title;
+        }
+
+        let window = &mut shared.window;
+        frame.report_stop(&mut self.stopped);
+        if frame.is_still(&mut self.overlaid) && !rebuilt {
+            // Still pumps the window's events.
+            window.update();
+            return;
+        }
+        let buffer = frame.to_rgb();
+        match frame.halted {
+            Some(e) => {
+                let screen = render_error_screen("Machine stopped", &e.to_string(), Some(&buffer));
+                window.update_with_buffer(&screen, ERROR_SCREEN_WIDTH, ERROR_SCREEN_HEIGHT)
+            }
+            None => match overlay_screen(frame) {
+                Some(screen) => window.update_with_buffer(&screen, ERROR_SCREEN_WIDTH, ERROR_SCREEN_HEIGHT),
+                None => window.update_with_buffer(&buffer, frame.width, frame.height),
+            },
+        }
+        .expect("Failed to update window");
+    }
+}
+
+impl InputSource for MinifbInput {
+    fn drain_events(&mut self) -> Vec<InputEvent> {
+        let shared = &mut *self.window.borrow_mut();
+        let mut events = Vec::new();
+
+        if !shared.window.is_open() {
+            events.push(InputEvent::Quit);
+            return events;
+        }
+
+        if std::mem::take(&mut shared.rebuilt) {
+            self.carried = self.keys;
+        }
+        let window = &mut shared.window;
+        let focused = window.is_active();
+        if focused != self.focused {
+            events.push(InputEvent::Focus(focused));
+            self.focused = focused;
+            if !focused {
+                self.carried = [false; 16];
+            }
+        }
+
+        let shift = window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift);
+        let ctrl = window.is_key_down(Key::LeftCtrl) || window.is_key_down(Key::RightCtrl);
+        let mut keys = read_keys(window);
+        if self.secondary {
+            keys = secondary::merge(keys, read_secondary_keys(window));
+        }
+        for (held, carried) in keys.iter_mut().zip(self.carried.iter_mut()) {
+            *carried &= !*held;
+            *held |= *carried;
+        }
+        // Ctrl+C copies the screen and Ctrl and a digit sets the scale,
+        // rather than pressing keypad keys.
+        if ctrl {
+            if window.is_key_pressed(Key::C, KeyRepeat::No) {
+                events.push(InputEvent::CopyScreen);
+            }
+            for (digit, &key) in SCALE_DIGITS.iter().enumerate() {
+                if window.is_key_pressed(key, KeyRepeat::No) {
+                    events.push(ctrl_digit(digit + 1));
+                }
+            }
+            for key in CTRL_KEYPAD_KEYS {
+                keys[key as usize] = self.keys[key as usize];
+            }
+        }
+        for (key, (&now, &before)) in keys.iter().zip(self.keys.iter()).enumerate() {
+            if now != before {
+                events.push(InputEvent::Key {
+                    key: key as u8,
+                    pressed: now,
+                });
+            }
+        }
+        self.keys = keys;
+
+        let pressed: Vec<_> = HOTKEYS
+            .into_iter()
+            .filter(|&(key, _, _, repeat)| shared.window.is_key_pressed(key, repeat))
+            .map(|(_, _, event, _)| event)
+            .collect();
+        for event in pressed {
+            if let InputEvent::Window(toggle) = event {
+                shared.toggle(toggle);
+            }
+            events.push(with_modifiers(event, shift, ctrl));
+        }
+        // Rebuilt for a toggle just now.
+        if std::mem::take(&mut shared.rebuilt) {
+            self.carried = self.keys;
+        }
+        events.extend(self.typed.borrow_mut().drain(..).map(|ch| InputEvent::Console(ConsoleKey::Char(ch))));
+
+        events
+    }
+}
+
+/// Opens a window showing an error screen and waits for it to be dismissed.
+pub fn show_error_window(title: &str, message: &str) {
+    let mut window = match Window::new(
+        "Chip-8 Emulator - error",
+        ERROR_SCREEN_WIDTH,
+        ERROR_SCREEN_HEIGHT,
+        WindowOptions::default(),
+    ) {
+        Ok(window) => window,
+        Err(e) => {
+            error!(target: FRONTEND_TARGET, "Could not open a window for the error: {}", e);
+            return;
+        }
+    };
+    window.limit_update_rate(Some(Duration::from_micros(16600)));
+
+    let screen = render_error_screen(title, message, None);
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        if window
+            .update_with_buffer(&screen, ERROR_SCREEN_WIDTH, ERROR_SCREEN_HEIGHT)
+            .is_err()
+        {
+            break;
+        }
+    }
+}
@@ -0,0 +1,181 @@
+//! Implementations of the library's frontend traits for the desktop binary.
+
+#[cfg(any(
+    feature = "frontend-minifb",
+    feature = "backend-sdl2",
+    feature = "backend-pixels"
+))]
+pub mod error_screen;
+#[cfg(any(
+    feature = "backend-sdl2",
+    feature = "backend-terminal",
+    feature = "backend-pixels"
+))]
+mod limiter;
+#[cfg(feature = "frontend-minifb")]
+pub mod minifb_window;
+#[cfg(feature = "backend-pixels")]
+pub mod pixels_window;
+pub mod clipboard;
+#[cfg(feature = "audio-rodio")]
+pub mod rodio_audio;
+#[cfg(feature = "backend-sdl2")]
+pub mod sdl;
+#[cfg(feature = "backend-terminal")]
+pub mod terminal;
+
+#[cfg(any(feature = "frontend-minifb", feature = "backend-pixels"))]
+use chip8_emulator::frontend::WindowToggle;
+#[cfg(any(
+    feature = "frontend-minifb",
+    feature = "backend-sdl2",
+    feature = "backend-pixels"
+))]
+use chip8_emulator::frontend::{Frame, InputEvent, help::Binding};
+
+/// Window pixels per CHIP-8 pixel.
+pub const WINDOW_SCALE: usize = 10;
+
+/// How the windowed backends size and decorate their window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowConfig {
+    /// Window pixels per CHIP-8 pixel.
+    pub scale: usize,
+    pub fullscreen: bool,
+    /// Keep the window above other windows.
+    pub always_on_top: bool,
+    /// Leave off the title bar and frame.
+    pub borderless: bool,
+    /// Let the window be resized by dragging its edges.
+    pub resizable: bool,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            scale: WINDOW_SCALE,
+            fullscreen: false,
+            always_on_top: false,
+            borderless: false,
+            resizable: true,
+        }
+    }
+}
+
+#[cfg(any(feature = "frontend-minifb", feature = "backend-pixels"))]
+impl WindowConfig {
+    /// Flips the option `toggle` names, returning what it's now set to.
+    pub fn toggle(&mut self, toggle: WindowToggle) -> bool {
+        let option = match toggle {
+            WindowToggle::AlwaysOnTop => &mut self.always_on_top,
+            WindowToggle::Borderless => &mut self.borderless,
+            WindowToggle::Resizable => &mut self.resizable,
+        };
+        *option = !*option;
+        *option
+    }
+}
+
+/// What a window option toggled to, as the backends print it.
+#[cfg(any(feature = "frontend-minifb", feature = "backend-pixels"))]
+pub fn toggled_message(toggle: WindowToggle, on: bool) -> String {
+    let option = match toggle {
+        WindowToggle::AlwaysOnTop => "Always on top",
+        WindowToggle::Borderless => "Borderless",
+        WindowToggle::Resizable => "Resizable",
+    };
+    format!("{}: {}", option, if on { "on" } else { "off" })
+}
+
+/// The emulator window's title for the state shown in `frame`, saying when
+/// it's running faster than the base clock.
+#[cfg(any(
+    feature = "frontend-minifb",
+    feature = "backend-sdl2",
+    feature = "backend-pixels"
+))]
+pub fn window_title(frame: &Frame<'_>) -> String {
+    let state = if frame.halted.is_some() {
+        " - stopped"
+    } else if frame.finished.is_some() {
+        " - halted"
+    } else if frame.paused {
+        " - paused"
+    } else {
+        ""
+    };
+    let overclock = if frame.speed.is_overclocked() { " overclocked" } else { "" };
+    format!("Chip-8 Emulator - {}{}{}", frame.speed.label(), overclock, state)
+}
+
+/// The keys with modifiers, which the windowed backends check for
+/// themselves rather than list in their hotkey tables.
+#[cfg(any(
+    feature = "frontend-minifb",
+    feature = "backend-sdl2",
+    feature = "backend-pixels"
+))]
+pub const MODIFIED_KEYS: [Binding; 15] = [
+    Binding { key: "Shift+=", event: InputEvent::FineSpeedUp },
+    Binding { key: "Shift+-", event: InputEvent::FineSlowDown },
+    Binding { key: "Ctrl+=", event: InputEvent::ScaleUp },
+    Binding { key: "Ctrl+-", event: InputEvent::ScaleDown },
+    Binding { key: "Ctrl+C", event: InputEvent::CopyScreen },
+    Binding { key: "Ctrl+1", event: InputEvent::SetScale(1) },
+    Binding { key: "Ctrl+2", event: InputEvent::SetScale(2) },
+    Binding { key: "Ctrl+3", event: InputEvent::SetScale(3) },
+    Binding { key: "Ctrl+4", event: InputEvent::SetScale(4) },
+    Binding { key: "Ctrl+5", event: InputEvent::SetScale(5) },
+    Binding { key: "Ctrl+6", event: InputEvent::SetScale(6) },
+    Binding { key: "Ctrl+7", event: InputEvent::SetScale(7) },
+    Binding { key: "Ctrl+8", event: InputEvent::SetScale(8) },
+    Binding { key: "Ctrl+9", event: InputEvent::SetScale(9) },
+    Binding { key: "Ctrl+0", event: InputEvent::SetScale(10) },
+];
+
+/// Keypad keys Ctrl takes over: those on 1 to 4, for the window scale, and
+/// on C, for copying the screen.
+#[cfg(any(
+    feature = "frontend-minifb",
+    feature = "backend-sdl2",
+    feature = "backend-pixels"
+))]
+pub const CTRL_KEYPAD_KEYS: [u8; 5] = [0x1, 0x2, 0x3, 0xC, 0xB];
+
+/// What Ctrl and `digit` sets the window scale to: 1x to 9x, and 10x for 0.
+#[cfg(any(
+    feature = "frontend-minifb",
+    feature = "backend-sdl2",
+    feature = "backend-pixels"
+))]
+pub fn ctrl_digit(digit: usize) -> InputEvent {
+    InputEvent::SetScale(if digit == 0 { 10 } else { digit })
+}
+
+/// What a hotkey sends with Shift or Ctrl held, as [`MODIFIED_KEYS`]
+/// lists: the speed keys step finely with Shift, and with Ctrl step the
+/// window scale instead, Ctrl+0 making it 10x. 1 to 9 and C are keypad keys
+/// or nothing rather than hotkeys, so the backends look for those with Ctrl
+/// themselves; see [`ctrl_digit`] and [`CTRL_KEYPAD_KEYS`].
+#[cfg(any(
+    feature = "frontend-minifb",
+    feature = "backend-sdl2",
+    feature = "backend-pixels"
+))]
+pub fn with_modifiers(event: InputEvent, shift: bool, ctrl: bool) -> InputEvent {
+    match event {
+        InputEvent::SpeedUp if ctrl => InputEvent::ScaleUp,
+        InputEvent::SlowDown if ctrl => InputEvent::ScaleDown,
+        InputEvent::ResetSpeed if ctrl => ctrl_digit(0),
+        InputEvent::SpeedUp if shift => InputEvent::FineSpeedUp,
+        InputEvent::SlowDown if shift => InputEvent::FineSlowDown,
+        event => event,
+    }
+}
+
+#[cfg(any(
+    feature = "backend-sdl2",
+    feature = "backend-terminal",
+    feature = "backend-pixels"
+))]
+pub use limiter::FrameLimiter;
@@ -0,0 +1,519 @@
+use std::sync::Arc;
+
+use log::{error, info};
+use pixels::{Pixels, SurfaceTexture};
+use winit::application::ApplicationHandler;
+use winit::dpi::{LogicalSize, PhysicalSize};
+use winit::event::{ElementState, KeyEvent, WindowEvent};
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
+use winit::keyboard::{KeyCode, ModifiersState, PhysicalKey};
+use winit::window::{Fullscreen, Window, WindowId, WindowLevel};
+
+use chip8_emulator::Chip8;
+use chip8_emulator::Chip8Error;
+use chip8_emulator::frontend::console::ConsoleKey;
+use chip8_emulator::frontend::help::Binding;
+use chip8_emulator::frontend::menu::MenuKey;
+use chip8_emulator::frontend::secondary::{self, KeypadLayers, Layer, SecondaryKey};
+use chip8_emulator::frontend::speed::BASE_HZ;
+use chip8_emulator::frontend::{
+    AudioSink, Driver, Frame, InputEvent, InputSource, PixelFormat, VideoSink, WindowToggle,
+};
+use chip8_emulator::render::rgb_into;
+
+use super::error_screen::{ERROR_SCREEN_HEIGHT, ERROR_SCREEN_WIDTH, overlay_screen, render_error_screen};
+use super::{FrameLimiter, MODIFIED_KEYS, WindowConfig, ctrl_digit, toggled_message, window_title, with_modifiers};
+use crate::FRONTEND_TARGET;
+
+/// Keypad layout by physical key position, so it's the same on any keyboard
+/// layout.
+const KEYPAD: [(KeyCode, &str, u8); 16] = [
+    (KeyCode::Digit1, "1", 0x1),
+    (KeyCode::Digit2, "2", 0x2),
+    (KeyCode::Digit3, "3", 0x3),
+    (KeyCode::Digit4, "4", 0xC),
+    (KeyCode::KeyQ, "Q", 0x4),
+    (KeyCode::KeyW, "W", 0x5),
+    (KeyCode::KeyE, "E", 0x6),
+    (KeyCode::KeyR, "R", 0xD),
+    (KeyCode::KeyA, "A", 0x7),
+    (KeyCode::KeyS, "S", 0x8),
+    (KeyCode::KeyD, "D", 0x9),
+    (KeyCode::KeyF, "F", 0xE),
+    (KeyCode::KeyZ, "Z", 0xA),
+    (KeyCode::KeyX, "X", 0x0),
+    (KeyCode::KeyC, "C", 0xB),
+    (KeyCode::KeyV, "V", 0xF),
+];
+
+/// 1 to 9 on the digit row, for Ctrl and a digit; 0 is a hotkey.
+const SCALE_DIGITS: [KeyCode; 9] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+/// Every other key, and whether it repeats while held. The arrows and the
+/// console's editing keys do.
+const HOTKEYS: [(KeyCode, &str, InputEvent, bool); 25] = [
+    (KeyCode::Equal, "=", InputEvent::SpeedUp, false),
+    (KeyCode::Minus, "-", InputEvent::SlowDown, false),
+    (KeyCode::Digit0, "0", InputEvent::ResetSpeed, false),
+    (KeyCode::Escape, "Esc", InputEvent::Menu(MenuKey::Escape), false),
+    (KeyCode::ArrowUp, "Up", InputEvent::Menu(MenuKey::Up), true),
+    (KeyCode::ArrowDown, "Down", InputEvent::Menu(MenuKey::Down), true),
+    (KeyCode::ArrowLeft, "Left", InputEvent::Menu(MenuKey::Left), true),
+    (KeyCode::ArrowRight, "Right", InputEvent::Menu(MenuKey::Right), true),
+    (KeyCode::Enter, "Enter", InputEvent::Menu(MenuKey::Enter), false),
+    (KeyCode::F1, "F1", InputEvent::Help, false),
+    (KeyCode::F2, "F2", InputEvent::Split, false),
+    (KeyCode::F6, "F6", InputEvent::SaveGameSettings, false),
+    (KeyCode::F7, "F7", InputEvent::Window(WindowToggle::AlwaysOnTop), false),
+    (KeyCode::F8, "F8", InputEvent::Window(WindowToggle::Borderless), false),
+    (KeyCode::F9, "F9", InputEvent::DumpState, false),
+    (KeyCode::F10, "F10", InputEvent::AsciiDump, false),
+    (KeyCode::F12, "F12", InputEvent::Window(WindowToggle::Resizable), false),
+    (KeyCode::KeyP, "P", InputEvent::Pause, false),
+    (KeyCode::KeyN, "N", InputEvent::Step, true),
+    (KeyCode::Period, ".", InputEvent::Step, true),
+    (KeyCode::F3, "F3", InputEvent::Registers, false),
+    (KeyCode::Backquote, "`", InputEvent::Console(ConsoleKey::Toggle), false),
+    (KeyCode::Backspace, "Backspace", InputEvent::Console(ConsoleKey::Backspace), true),
+    (KeyCode::PageUp, "PageUp", InputEvent::Console(ConsoleKey::PageUp), true),
+    (KeyCode::PageDown, "PageDown", InputEvent::Console(ConsoleKey::PageDown), true),
+];
+
+/// What the window's keys send, for the help overlay, with the secondary
+/// bindings if they're on.
+pub fn bindings(secondary: bool) -> Vec<Binding> {
+    let keypad = KEYPAD.iter().map(|&(_, name, key)| Binding {
+        key: name,
+        event: InputEvent::Key { key, pressed: true },
+    });
+    let keypad = keypad.chain(secondary.then(secondary::bindings).into_iter().flatten());
+    let hotkeys = HOTKEYS.iter().map(|&(_, name, event, _)| Binding { key: name, event });
+    keypad.chain(hotkeys).chain(MODIFIED_KEYS).collect()
+}
+
+fn keypad_key(code: KeyCode) -> Option<u8> {
+    KEYPAD.iter().find(|(key_code, _, _)| *key_code == code).map(|&(_, _, key)| key)
+}
+
+/// The keypad key the secondary binding on `code` presses, if there's one.
+fn secondary_key(code: KeyCode) -> Option<u8> {
+    let key = match code {
+        KeyCode::ArrowUp => SecondaryKey::Up,
+        KeyCode::ArrowDown => SecondaryKey::Down,
+        KeyCode::ArrowLeft => SecondaryKey::Left,
+        KeyCode::ArrowRight => SecondaryKey::Right,
+        KeyCode::Space => SecondaryKey::Space,
+        KeyCode::Enter => SecondaryKey::Enter,
+        _ => return None,
+    };
+    Some(secondary::keypad_key(key))
+}
+
+/// The emulator window: the display uploaded to a GPU surface, which pixels
+/// scales by whole multiples and letterboxes to fit the window.
+pub struct PixelsVideo {
+    // The surface has to go before the window it draws to.
+    pixels: Pixels<'static>,
+    window: Arc<Window>,
+    buffer_size: (usize, usize),
+    speed_hz: u32,
+    scale: usize,
+    title: String,
+    stopped: bool,
+    overlaid: bool,
+    /// Whether the surface was resized since the last frame was drawn, which
+    /// leaves it to be drawn again.
+    resized: bool,
+}
+
+/// Keyboard and focus events from the window, queued by the event loop until
+/// the driver drains them.
+#[derive(Debug, Default)]
+pub struct PixelsInput {
+    events: Vec<InputEvent>,
+    modifiers: ModifiersState,
+    layers: KeypadLayers,
+    /// Window options to flip, which the event loop applies to the window.
+    toggles: Vec<WindowToggle>,
+}
+
+fn open_video(
+    event_loop: &ActiveEventLoop,
+    title: &str,
+    config: WindowConfig,
+    buffer_size: (usize, usize),
+) -> Result<PixelsVideo, String> {
+    let mut attributes = Window::default_attributes()
+        .with_title(title)
+        .with_inner_size(LogicalSize::new(
+            (64 * config.scale) as f64,
+            (32 * config.scale) as f64,
+        ))
+        .with_min_inner_size(LogicalSize::new(64.0, 32.0))
+        .with_decorations(!config.borderless)
+        .with_resizable(config.resizable)
+        .with_window_level(window_level(config.always_on_top));
+    if config.fullscreen {
+        attributes = attributes.with_fullscreen(Some(Fullscreen::Borderless(None)));
+    }
+    let window = Arc::new(event_loop.create_window(attributes).map_err(|e| e.to_string())?);
+
+    let size = window.inner_size();
+    let surface = SurfaceTexture::new(size.width, size.height, Arc::clone(&window));
+    let pixels = Pixels::new(buffer_size.0 as u32, buffer_size.1 as u32, surface)
+        .map_err(|e| e.to_string())?;
+
+    Ok(PixelsVideo {
+        pixels,
+        window,
+        buffer_size,
+        speed_hz: BASE_HZ,
+        scale: config.scale,
+        title: "Chip-8 Emulator".to_string(),
+        stopped: false,
+        overlaid: false,
+        resized: false,
+    })
+}
+
+fn window_level(always_on_top: bool) -> WindowLevel {
+    if always_on_top { WindowLevel::AlwaysOnTop } else { WindowLevel::Normal }
+}
+
+impl PixelsVideo {
+    /// Flips `toggle` in `config` and on the window. winit changes all of
+    /// them in place, ignoring any a platform can't do.
+    fn toggle(&self, config: &mut WindowConfig, toggle: WindowToggle) {
+        let on = config.toggle(toggle);
+        match toggle {
+            WindowToggle::AlwaysOnTop => self.window.set_window_level(window_level(on)),
+            WindowToggle::Borderless => self.window.set_decorations(!on),
+            WindowToggle::Resizable => self.window.set_resizable(on),
+        }
+        println!("{}", toggled_message(toggle, on));
+    }
+
+    fn resize(&mut self, size: PhysicalSize<u32>) {
+        // Minimized windows report a zero size, which can't back a surface.
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+        if let Err(e) = self.pixels.resize_surface(size.width, size.height) {
+            error!(target: FRONTEND_TARGET, "Could not resize the window surface: {}", e);
+        }
+        self.resized = true;
+    }
+
+    /// Has `fill` write a `width` x `height` RGBA image into the surface,
+    /// then shows it scaled to the window.
+    fn draw(&mut self, width: usize, height: usize, fill: impl FnOnce(&mut [u8])) {
+        let size = (width, height);
+        if size != self.buffer_size {
+            self.pixels
+                .resize_buffer(size.0 as u32, size.1 as u32)
+                .expect("Failed to update window");
+            self.buffer_size = size;
+        }
+
+        fill(self.pixels.frame_mut());
+        self.pixels.render().expect("Failed to update window");
+    }
+
+    /// Shows a window-resolution screen, such as one from [`render_error_screen`].
+    fn draw_screen(&mut self, screen: &[u32]) {
+        self.draw(ERROR_SCREEN_WIDTH, ERROR_SCREEN_HEIGHT, |buf| {
+            rgb_into(screen, buf, PixelFormat::Rgba8888);
+        });
+    }
+}
+
+impl VideoSink for PixelsVideo {
+    fn present(&mut self, frame: &Frame<'_>) {
+        if frame.speed.hz() != self.speed_hz {
+            self.speed_hz = frame.speed.hz();
+            println!("Speed: {}", frame.speed.label());
+        }
+
+        if let Some(scale) = frame.scale
+            && scale != self.scale
+        {
+            let size = LogicalSize::new((64 * scale) as f64, (32 * scale) as f64);
+            // The surface follows when the window reports its new size.
+            let _ = self.window.request_inner_size(size);
+            println!("Window scale: {}x", scale);
+            self.scale = scale;
+        }
+
+        let title = window_title(frame);
+        if title != self.title {
+            self.window.set_title(&title);
+            self.title = title;
+        }
+
+        frame.report_stop(&mut self.stopped);
+        let resized = std::mem::take(&mut self.resized);
+        if frame.is_still(&mut self.overlaid) && !resized {
+            return;
+        }
+        match frame.halted {
+            Some(e) => {
+                let screen = render_error_screen("Machine stopped", &e.to_string(), Some(&frame.to_rgb()));
+                self.draw_screen(&screen);
+            }
+            None => match overlay_screen(frame) {
+                Some(screen) => self.draw_screen(&screen),
+                None => self.draw(frame.width, frame.height, |buf| {
+                    frame.render_into(buf, PixelFormat::Rgba8888);
+                }),
+            },
+        }
+    }
+}
+
+impl PixelsInput {
+    fn window_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::CloseRequested => self.events.push(InputEvent::Quit),
+            WindowEvent::Focused(focused) => self.events.push(InputEvent::Focus(*focused)),
+            WindowEvent::KeyboardInput { event, .. } => self.key_event(event),
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+            _ => {}
+        }
+    }
+
+    fn key_event(&mut self, event: &KeyEvent) {
+        let PhysicalKey::Code(code) = event.physical_key else {
+            return;
+        };
+        let pressed = event.state == ElementState::Pressed;
+        let first_press = pressed && !event.repeat;
+        if pressed && let Some(text) = &event.text {
+            self.events.extend(text.chars().map(|ch| InputEvent::Console(ConsoleKey::Char(ch))));
+        }
+
+        if let Some(&(_, _, hotkey, repeats)) = HOTKEYS.iter().find(|(key_code, ..)| *key_code == code) {
+            if pressed && (repeats || first_press) {
+                if let InputEvent::Window(toggle) = hotkey {
+                    self.toggles.push(toggle);
+                }
+                let modifiers = self.modifiers;
+                self.events.push(with_modifiers(hotkey, modifiers.shift_key(), modifiers.control_key()));
+            }
+        } else if code == KeyCode::KeyC && pressed && self.modifiers.control_key() {
+            if first_press {
+                self.events.push(InputEvent::CopyScreen);
+            }
+        } else if let Some(digit) = SCALE_DIGITS.iter().position(|&digit| digit == code)
+            && pressed
+            && self.modifiers.control_key()
+        {
+            if first_press {
+                self.events.push(ctrl_digit(digit + 1));
+            }
+        } else if let Some(key) = keypad_key(code)
+            && !event.repeat
+        {
+            self.events.extend(self.layers.set(Layer::Primary, key, pressed));
+        }
+        if let Some(key) = secondary_key(code)
+            && !event.repeat
+        {
+            self.events.extend(self.layers.set(Layer::Secondary, key, pressed));
+        }
+    }
+}
+
+impl InputSource for PixelsInput {
+    fn drain_events(&mut self) -> Vec<InputEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+/// The running emulator, handed control by winit's event loop.
+///
+/// Window events are queued as input, and a frame is run from
+/// `about_to_wait` whenever one is due, after which the loop sleeps until the
+/// next.
+struct Emulator<'a, A> {
+    chip8: &'a mut Chip8,
+    driver: Driver,
+    config: WindowConfig,
+    video: Option<PixelsVideo>,
+    input: PixelsInput,
+    audio: A,
+    limiter: FrameLimiter,
+}
+
+impl<A: AudioSink> ApplicationHandler for Emulator<'_, A> {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.video.is_some() {
+            return;
+        }
+        let video = open_video(event_loop, "Chip-8 Emulator", self.config, (64, 32))
+            .unwrap_or_else(|e| panic!("Failed to create window: {}", e));
+        info!(target: FRONTEND_TARGET, "Window opened");
+        self.video = Some(video);
+    }
+
+    fn window_event(&mut self, _: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        match (&event, &mut self.video) {
+            (WindowEvent::Resized(size), Some(video)) => video.resize(*size),
+            _ => self.input.window_event(&event),
+        }
+        for toggle in self.input.toggles.drain(..) {
+            if let Some(video) = &self.video {
+                video.toggle(&mut self.config, toggle);
+            }
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(video) = &mut self.video else {
+            return;
+        };
+
+        if self.limiter.try_start()
+            && !self
+                .driver
+                .frame(self.chip8, video, &mut self.input, &mut self.audio)
+        {
+            event_loop.exit();
+            return;
+        }
+        event_loop.set_control_flow(ControlFlow::WaitUntil(self.limiter.next_frame()));
+    }
+}
+
+/// Opens the emulator window and runs `chip8` in it with `driver` until the
+/// user quits, with the `secondary` bindings or without.
+pub fn run<A: AudioSink>(
+    chip8: &mut Chip8,
+    driver: Driver,
+    config: WindowConfig,
+    secondary: bool,
+    audio: A,
+) -> Result<(), Chip8Error> {
+    let event_loop = EventLoop::new().unwrap_or_else(|e| panic!("Failed to create window: {}", e));
+    let mut emulator = Emulator {
+        chip8,
+        driver,
+        config,
+        video: None,
+        input: PixelsInput { layers: KeypadLayers::new(secondary), ..PixelsInput::default() },
+        audio,
+        limiter: FrameLimiter::new(),
+    };
+    if let Err(e) = event_loop.run_app(&mut emulator) {
+        error!(target: FRONTEND_TARGET, "Window event loop failed: {}", e);
+    }
+    emulator.driver.finish()
+}
+
+/// An error screen in its own window, shown until it's dismissed.
+struct ErrorWindow {
+    screen: Vec<u32>,
+    video: Option<PixelsVideo>,
+    input: PixelsInput,
+}
+
+impl ApplicationHandler for ErrorWindow {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.video.is_some() {
+            return;
+        }
+        let size = (ERROR_SCREEN_WIDTH, ERROR_SCREEN_HEIGHT);
+        match open_video(event_loop, "Chip-8 Emulator - error", WindowConfig::default(), size) {
+            Ok(video) => self.video = Some(video),
+            Err(e) => {
+                error!(target: FRONTEND_TARGET, "Could not open a window for the error: {}", e);
+                event_loop.exit();
+            }
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        let Some(video) = &mut self.video else {
+            return;
+        };
+        match event {
+            WindowEvent::Resized(size) => {
+                video.resize(size);
+                video.window.request_redraw();
+            }
+            WindowEvent::RedrawRequested => video.draw_screen(&self.screen),
+            _ => {
+                self.input.window_event(&event);
+                let events = self.input.drain_events();
+                if events.iter().any(|event| matches!(event, InputEvent::Quit | InputEvent::Menu(MenuKey::Escape))) {
+                    event_loop.exit();
+                }
+            }
+        }
+    }
+}
+
+/// Opens a window showing an error screen and waits for it to be dismissed.
+pub fn show_error_window(title: &str, message: &str) {
+    let event_loop = match EventLoop::new() {
+        Ok(event_loop) => event_loop,
+        Err(e) => {
+            error!(target: FRONTEND_TARGET, "Could not open a window for the error: {}", e);
+            return;
+        }
+    };
+    let mut window = ErrorWindow {
+        screen: render_error_screen(title, message, None),
+        video: None,
+        input: PixelsInput::default(),
+    };
+    if let Err(e) = event_loop.run_app(&mut window) {
+        error!(target: FRONTEND_TARGET, "Could not open a window for the error: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keypad_covers_every_key_once() {
+        let mut keys: Vec<u8> = KEYPAD.iter().map(|&(_, _, key)| key).collect();
+        keys.sort();
+        assert_eq!(keys, (0..16).collect::<Vec<u8>>());
+        assert_eq!(keypad_key(KeyCode::KeyX), Some(0x0));
+        assert_eq!(keypad_key(KeyCode::Escape), None);
+        assert_eq!(secondary_key(KeyCode::ArrowLeft), Some(0x4));
+        assert_eq!(secondary_key(KeyCode::KeyX), None);
+    }
+
+    #[test]
+    fn window_events_become_input() {
+        let mut input = PixelsInput::default();
+        input.window_event(&WindowEvent::Focused(false));
+        input.window_event(&WindowEvent::CloseRequested);
+
+        assert_eq!(input.drain_events(), [InputEvent::Focus(false), InputEvent::Quit]);
+        assert!(input.drain_events().is_empty());
+    }
+
+    #[test]
+    fn window_options_flip_back_and_forth() {
+        let mut config = WindowConfig::default();
+        assert!(config.resizable && !config.borderless && !config.always_on_top);
+        assert!(config.toggle(WindowToggle::AlwaysOnTop));
+        assert!(!config.toggle(WindowToggle::Resizable));
+        assert!(!config.toggle(WindowToggle::AlwaysOnTop));
+        assert_eq!(config, WindowConfig { resizable: false, ..WindowConfig::default() });
+        assert_eq!(toggled_message(WindowToggle::Borderless, true), "Borderless: on");
+    }
+}
@@ -0,0 +1,101 @@
+use log::{debug, error, info};
+use std::time::Duration;
+
+use rodio::{OutputStream, Sink, Source};
+
+use chip8_emulator::frontend::{AudioCommand, AudioSink, PatternTone, Tone};
+
+const AUDIO_TARGET: &str = "chip8::audio";
+const SAMPLE_RATE: u32 = 48_000;
+
+/// Plays the buzzer, or XO-CHIP's pattern, on the default output device.
+pub struct RodioAudio {
+    // The stream has to outlive the sink, so it's kept alongside it.
+    output: Option<(OutputStream, Sink)>,
+}
+
+impl RodioAudio {
+    /// Opens the default output device, or runs muted if there isn't one.
+    pub fn open() -> Self {
+        let audio = OutputStream::try_default()
+            .map_err(|e| e.to_string())
+            .and_then(|(stream, handle)| {
+                Sink::try_new(&handle).map(|sink| (stream, sink)).map_err(|e| e.to_string())
+            });
+        let output = match audio {
+            Ok(output) => {
+                info!(target: AUDIO_TARGET, "Audio output opened");
+                Some(output)
+            }
+            Err(e) => {
+                error!(target: AUDIO_TARGET, "Audio unavailable, running muted: {}", e);
+                None
+            }
+        };
+
+        Self { output }
+    }
+
+    /// Replaces whatever's playing with `source`, which plays until it's
+    /// stopped.
+    fn play(&self, source: impl Source<Item = f32> + Send + 'static) {
+        if let Some((_, sink)) = &self.output {
+            sink.stop();
+            sink.append(source);
+        }
+    }
+}
+
+impl AudioSink for RodioAudio {
+    fn command(&mut self, command: AudioCommand) {
+        match command {
+            AudioCommand::BuzzerOn => {
+                debug!(target: AUDIO_TARGET, "Buzzer on");
+                self.play(Mono(Tone::new(SAMPLE_RATE), SAMPLE_RATE));
+            }
+            AudioCommand::PatternOn(pattern) => {
+                debug!(target: AUDIO_TARGET, "Pattern on at pitch {}", pattern.pitch);
+                self.play(Mono(PatternTone::new(pattern, SAMPLE_RATE), SAMPLE_RATE));
+            }
+            AudioCommand::BuzzerOff => {
+                debug!(target: AUDIO_TARGET, "Buzzer off");
+                if let Some((_, sink)) = &self.output {
+                    sink.stop();
+                }
+            }
+        }
+    }
+
+    fn muted(&self) -> bool {
+        self.output.is_none()
+    }
+}
+
+/// Endless mono samples at the given rate.
+struct Mono<T>(T, u32);
+
+impl<T: Iterator<Item = f32>> Iterator for Mono<T> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        self.0.next()
+    }
+}
+
+impl<T: Iterator<Item = f32>> Source for Mono<T> {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.1
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
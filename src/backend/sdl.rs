@@ -0,0 +1,505 @@
+use log::{debug, error, info, warn};
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Mod, Scancode};
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::{Canvas, Texture, TextureCreator};
+use sdl2::video::{Window, WindowContext};
+use sdl2::sys::SDL_WindowFlags;
+use sdl2::{EventPump, Sdl};
+
+use chip8_emulator::frontend::console::ConsoleKey;
+use chip8_emulator::frontend::help::Binding;
+use chip8_emulator::frontend::menu::MenuKey;
+use chip8_emulator::frontend::secondary::{self, KeypadLayers, Layer, SecondaryKey};
+use chip8_emulator::frontend::speed::BASE_HZ;
+use chip8_emulator::frontend::{
+    AudioCommand, AudioSink, Frame, InputEvent, InputSource, PixelFormat,
+    PatternTone, Tone, VideoSink,
+};
+use chip8_emulator::render::rgb_into;
+
+use super::{FrameLimiter, MODIFIED_KEYS, WindowConfig, ctrl_digit, window_title, with_modifiers};
+use super::error_screen::{ERROR_SCREEN_HEIGHT, ERROR_SCREEN_WIDTH, overlay_screen, render_error_screen};
+use crate::FRONTEND_TARGET;
+
+const AUDIO_TARGET: &str = "chip8::audio";
+
+/// Keypad layout by physical key position, so it's the same on any keyboard
+/// layout.
+const KEYPAD: [(Scancode, &str, u8); 16] = [
+    (Scancode::Num1, "1", 0x1),
+    (Scancode::Num2, "2", 0x2),
+    (Scancode::Num3, "3", 0x3),
+    (Scancode::Num4, "4", 0xC),
+    (Scancode::Q, "Q", 0x4),
+    (Scancode::W, "W", 0x5),
+    (Scancode::E, "E", 0x6),
+    (Scancode::R, "R", 0xD),
+    (Scancode::A, "A", 0x7),
+    (Scancode::S, "S", 0x8),
+    (Scancode::D, "D", 0x9),
+    (Scancode::F, "F", 0xE),
+    (Scancode::Z, "Z", 0xA),
+    (Scancode::X, "X", 0x0),
+    (Scancode::C, "C", 0xB),
+    (Scancode::V, "V", 0xF),
+];
+
+/// 1 to 9 on the digit row, for Ctrl and a digit; 0 is a hotkey.
+const SCALE_DIGITS: [Scancode; 9] = [
+    Scancode::Num1,
+    Scancode::Num2,
+    Scancode::Num3,
+    Scancode::Num4,
+    Scancode::Num5,
+    Scancode::Num6,
+    Scancode::Num7,
+    Scancode::Num8,
+    Scancode::Num9,
+];
+
+/// Every other key, and whether it repeats while held. The arrows and the
+/// console's editing keys do.
+const HOTKEYS: [(Scancode, &str, InputEvent, bool); 22] = [
+    (Scancode::Equals, "=", InputEvent::SpeedUp, false),
+    (Scancode::Minus, "-", InputEvent::SlowDown, false),
+    (Scancode::Num0, "0", InputEvent::ResetSpeed, false),
+    (Scancode::Escape, "Esc", InputEvent::Menu(MenuKey::Escape), false),
+    (Scancode::Up, "Up", InputEvent::Menu(MenuKey::Up), true),
+    (Scancode::Down, "Down", InputEvent::Menu(MenuKey::Down), true),
+    (Scancode::Left, "Left", InputEvent::Menu(MenuKey::Left), true),
+    (Scancode::Right, "Right", InputEvent::Menu(MenuKey::Right), true),
+    (Scancode::Return, "Enter", InputEvent::Menu(MenuKey::Enter), false),
+    (Scancode::F1, "F1", InputEvent::Help, false),
+    (Scancode::F2, "F2", InputEvent::Split, false),
+    (Scancode::F6, "F6", InputEvent::SaveGameSettings, false),
+    (Scancode::F9, "F9", InputEvent::DumpState, false),
+    (Scancode::F10, "F10", InputEvent::AsciiDump, false),
+    (Scancode::P, "P", InputEvent::Pause, false),
+    (Scancode::N, "N", InputEvent::Step, true),
+    (Scancode::Period, ".", InputEvent::Step, true),
+    (Scancode::F3, "F3", InputEvent::Registers, false),
+    (Scancode::Grave, "`", InputEvent::Console(ConsoleKey::Toggle), false),
+    (Scancode::Backspace, "Backspace", InputEvent::Console(ConsoleKey::Backspace), true),
+    (Scancode::PageUp, "PageUp", InputEvent::Console(ConsoleKey::PageUp), true),
+    (Scancode::PageDown, "PageDown", InputEvent::Console(ConsoleKey::PageDown), true),
+];
+
+/// What the window's keys send, for the help overlay, with the secondary
+/// bindings if they're on.
+pub fn bindings(secondary: bool) -> Vec<Binding> {
+    let keypad = KEYPAD.iter().map(|&(_, name, key)| Binding {
+        key: name,
+        event: InputEvent::Key { key, pressed: true },
+    });
+    let keypad = keypad.chain(secondary.then(secondary::bindings).into_iter().flatten());
+    let hotkeys = HOTKEYS.iter().map(|&(_, name, event, _)| Binding { key: name, event });
+    keypad.chain(hotkeys).chain(MODIFIED_KEYS).collect()
+}
+
+/// The event `scancode` sends, unless it's a repeat of a key that doesn't
+/// repeat.
+fn hotkey(scancode: Scancode, repeat: bool) -> Option<InputEvent> {
+    HOTKEYS
+        .iter()
+        .find(|&&(code, _, _, repeats)| code == scancode && (repeats || !repeat))
+        .map(|&(_, _, event, _)| event)
+}
+
+fn keypad_key(scancode: Scancode) -> Option<u8> {
+    KEYPAD.iter().find(|(code, _, _)| *code == scancode).map(|&(_, _, key)| key)
+}
+
+/// The keypad key the secondary binding on `scancode` presses, if there's
+/// one.
+fn secondary_key(scancode: Scancode) -> Option<u8> {
+    let key = match scancode {
+        Scancode::Up => SecondaryKey::Up,
+        Scancode::Down => SecondaryKey::Down,
+        Scancode::Left => SecondaryKey::Left,
+        Scancode::Right => SecondaryKey::Right,
+        Scancode::Space => SecondaryKey::Space,
+        Scancode::Return => SecondaryKey::Enter,
+        _ => return None,
+    };
+    Some(secondary::keypad_key(key))
+}
+
+/// The emulator window: the display drawn through a streaming texture with
+/// integer scaling.
+pub struct SdlVideo {
+    // Textures have to go before the renderer that owns them.
+    frame: Texture,
+    /// The display resolution `frame` was made for.
+    frame_size: (usize, usize),
+    error_screen: Texture,
+    canvas: Canvas<Window>,
+    textures: TextureCreator<WindowContext>,
+    limiter: FrameLimiter,
+    speed_hz: u32,
+    scale: usize,
+    title: String,
+    stopped: bool,
+    overlaid: bool,
+}
+
+/// Keyboard input from the SDL event queue.
+pub struct SdlInput {
+    events: EventPump,
+    layers: KeypadLayers,
+    _sdl: Sdl,
+}
+
+/// The buzzer, or XO-CHIP's pattern, generated in SDL's audio callback.
+pub struct SdlAudio {
+    device: Option<AudioDevice<Buzzer>>,
+}
+
+struct Buzzer {
+    tone: Tone,
+    /// Played instead of the tone when an XO-CHIP program has set one.
+    pattern: Option<PatternTone>,
+    on: bool,
+}
+
+impl AudioCallback for Buzzer {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        if self.on {
+            match &mut self.pattern {
+                Some(pattern) => pattern.fill(out),
+                None => self.tone.fill(out),
+            }
+        } else {
+            out.fill(0.0);
+        }
+    }
+}
+
+/// Opens the emulator window and audio device, with the `secondary`
+/// bindings or without. Audio is optional: without it the emulator runs
+/// muted.
+pub fn open(config: WindowConfig, secondary: bool) -> Result<(SdlVideo, SdlInput, SdlAudio), String> {
+    let sdl = sdl2::init()?;
+    let canvas = create_canvas(&sdl, "Chip-8 Emulator", config)?;
+
+    let textures = canvas.texture_creator();
+    let frame = streaming_texture(&textures, 64, 32)?;
+    let error_screen = streaming_texture(&textures, ERROR_SCREEN_WIDTH, ERROR_SCREEN_HEIGHT)?;
+
+    let video = SdlVideo {
+        frame,
+        frame_size: (64, 32),
+        error_screen,
+        canvas,
+        textures,
+        limiter: FrameLimiter::new(),
+        speed_hz: BASE_HZ,
+        scale: config.scale,
+        title: "Chip-8 Emulator".to_string(),
+        stopped: false,
+        overlaid: false,
+    };
+    let audio = SdlAudio {
+        device: match open_audio(&sdl) {
+            Ok(device) => {
+                info!(target: AUDIO_TARGET, "Audio output opened");
+                Some(device)
+            }
+            Err(e) => {
+                error!(target: AUDIO_TARGET, "Audio unavailable, running muted: {}", e);
+                None
+            }
+        },
+    };
+    let input = SdlInput {
+        events: sdl.event_pump()?,
+        layers: KeypadLayers::new(secondary),
+        _sdl: sdl,
+    };
+
+    Ok((video, input, audio))
+}
+
+fn create_canvas(sdl: &Sdl, title: &str, config: WindowConfig) -> Result<Canvas<Window>, String> {
+    let video = sdl.video()?;
+    let mut builder = video.window(title, (64 * config.scale) as u32, (32 * config.scale) as u32);
+    builder.position_centered();
+    if config.fullscreen {
+        builder.fullscreen_desktop();
+    }
+    if config.borderless {
+        builder.borderless();
+    }
+    if config.resizable {
+        builder.resizable();
+    }
+    if config.always_on_top {
+        let flags = builder.window_flags() | SDL_WindowFlags::SDL_WINDOW_ALWAYS_ON_TOP as u32;
+        builder.set_window_flags(flags);
+    }
+    let window = builder.build().map_err(|e| e.to_string())?;
+    let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+    canvas.set_integer_scale(true)?;
+    Ok(canvas)
+}
+
+fn streaming_texture(
+    textures: &TextureCreator<WindowContext>,
+    width: usize,
+    height: usize,
+) -> Result<Texture, String> {
+    textures
+        .create_texture_streaming(PixelFormatEnum::ARGB8888, width as u32, height as u32)
+        .map_err(|e| e.to_string())
+}
+
+fn open_audio(sdl: &Sdl) -> Result<AudioDevice<Buzzer>, String> {
+    let desired = AudioSpecDesired {
+        freq: Some(48_000),
+        channels: Some(1),
+        samples: None,
+    };
+    let device = sdl.audio()?.open_playback(None, &desired, |spec| Buzzer {
+        tone: Tone::new(spec.freq as u32),
+        pattern: None,
+        on: false,
+    })?;
+    device.resume();
+    Ok(device)
+}
+
+/// Uploads a `width` x `height` ARGB image into `texture` and shows it,
+/// scaled to the window.
+fn draw(canvas: &mut Canvas<Window>, texture: &mut Texture, bytes: &[u8], width: usize, height: usize) {
+
+    let result = texture
+        .update(None, bytes, width * 4)
+        .map_err(|e| e.to_string())
+        .and_then(|_| {
+            canvas
+                .set_logical_size(width as u32, height as u32)
+                .map_err(|e| e.to_string())
+        })
+        .and_then(|_| {
+            canvas.clear();
+            canvas.copy(texture, None, None)
+        });
+    result.expect("Failed to update window");
+    canvas.present();
+}
+
+/// Shows a window-resolution screen, such as one from [`render_error_screen`].
+fn draw_screen(canvas: &mut Canvas<Window>, texture: &mut Texture, screen: &[u32]) {
+    let mut bytes = vec![0; PixelFormat::Argb8888.buffer_len(screen.len())];
+    rgb_into(screen, &mut bytes, PixelFormat::Argb8888);
+    draw(canvas, texture, &bytes, ERROR_SCREEN_WIDTH, ERROR_SCREEN_HEIGHT);
+}
+
+impl VideoSink for SdlVideo {
+    fn present(&mut self, frame: &Frame<'_>) {
+        if frame.speed.hz() != self.speed_hz {
+            self.speed_hz = frame.speed.hz();
+            println!("Speed: {}", frame.speed.label());
+        }
+
+        let resized = frame.scale.is_some_and(|scale| scale != self.scale);
+        if let Some(scale) = frame.scale
+            && scale != self.scale
+        {
+            match self.canvas.window_mut().set_size((64 * scale) as u32, (32 * scale) as u32) {
+                Ok(()) => println!("Window scale: {}x", scale),
+                Err(e) => warn!(target: FRONTEND_TARGET, "Could not resize the window: {}", e),
+            }
+            self.scale = scale;
+        }
+
+        let title = window_title(frame);
+        if title != self.title {
+            let _ = self.canvas.window_mut().set_title(&title);
+            self.title = title;
+        }
+
+        frame.report_stop(&mut self.stopped);
+        if frame.is_still(&mut self.overlaid) && !resized {
+            self.limiter.wait();
+            return;
+        }
+        match frame.halted {
+            Some(e) => {
+                let screen = render_error_screen("Machine stopped", &e.to_string(), Some(&frame.to_rgb()));
+                draw_screen(&mut self.canvas, &mut self.error_screen, &screen);
+            }
+            None => match overlay_screen(frame) {
+                Some(screen) => draw_screen(&mut self.canvas, &mut self.error_screen, &screen),
+                None => {
+                    if (frame.width, frame.height) != self.frame_size {
+                        self.frame = streaming_texture(&self.textures, frame.width, frame.height)
+                            .expect("Failed to create a texture for the new resolution");
+                        self.frame_size = (frame.width, frame.height);
+                    }
+                    let mut bytes = vec![0; PixelFormat::Argb8888.buffer_len(frame.pixels.len())];
+                    frame.render_into(&mut bytes, PixelFormat::Argb8888);
+                    draw(&mut self.canvas, &mut self.frame, &bytes, frame.width, frame.height);
+                }
+            },
+        }
+
+        self.limiter.wait();
+    }
+}
+
+impl InputSource for SdlInput {
+    fn drain_events(&mut self) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+
+        for event in self.events.poll_iter() {
+            match event {
+                Event::Quit { .. } => events.push(InputEvent::Quit),
+                Event::Window {
+                    win_event: WindowEvent::FocusGained,
+                    ..
+                } => events.push(InputEvent::Focus(true)),
+                Event::Window {
+                    win_event: WindowEvent::FocusLost,
+                    ..
+                } => events.push(InputEvent::Focus(false)),
+                Event::KeyDown {
+                    scancode: Some(scancode),
+                    repeat,
+                    keymod,
+                    ..
+                } => {
+                    let shift = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+                    let ctrl = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+                    if let Some(event) = hotkey(scancode, repeat) {
+                        events.push(with_modifiers(event, shift, ctrl));
+                    } else if scancode == Scancode::C && ctrl {
+                        if !repeat {
+                            events.push(InputEvent::CopyScreen);
+                        }
+                    } else if let Some(digit) = SCALE_DIGITS.iter().position(|&code| code == scancode)
+                        && ctrl
+                    {
+                        if !repeat {
+                            events.push(ctrl_digit(digit + 1));
+                        }
+                    } else if let Some(key) = keypad_key(scancode)
+                        && !repeat
+                    {
+                        events.extend(self.layers.set(Layer::Primary, key, true));
+                    }
+                    if let Some(key) = secondary_key(scancode)
+                        && !repeat
+                    {
+                        events.extend(self.layers.set(Layer::Secondary, key, true));
+                    }
+                }
+                Event::KeyUp {
+                    scancode: Some(scancode),
+                    ..
+                } => {
+                    if let Some(key) = keypad_key(scancode) {
+                        events.extend(self.layers.set(Layer::Primary, key, false));
+                    }
+                    if let Some(key) = secondary_key(scancode) {
+                        events.extend(self.layers.set(Layer::Secondary, key, false));
+                    }
+                }
+                Event::TextInput { text, .. } => {
+                    events.extend(text.chars().map(|ch| InputEvent::Console(ConsoleKey::Char(ch))));
+                }
+                _ => {}
+            }
+        }
+
+        events
+    }
+}
+
+impl AudioSink for SdlAudio {
+    fn command(&mut self, command: AudioCommand) {
+        let on = command != AudioCommand::BuzzerOff;
+        debug!(target: AUDIO_TARGET, "Buzzer {}", if on { "on" } else { "off" });
+
+        if let Some(device) = &mut self.device {
+            let mut buzzer = device.lock();
+            buzzer.pattern = match command {
+                AudioCommand::PatternOn(pattern) => Some(PatternTone::new(pattern, buzzer.tone.sample_rate())),
+                _ => None,
+            };
+            buzzer.on = on;
+        }
+    }
+
+    fn muted(&self) -> bool {
+        self.device.is_none()
+    }
+}
+
+/// Opens a window showing an error screen and waits for it to be dismissed.
+pub fn show_error_window(title: &str, message: &str) {
+    let opened = sdl2::init().and_then(|sdl| {
+        let canvas = create_canvas(&sdl, "Chip-8 Emulator - error", WindowConfig::default())?;
+        let events = sdl.event_pump()?;
+        Ok((sdl, canvas, events))
+    });
+    let (_sdl, mut canvas, mut events) = match opened {
+        Ok(opened) => opened,
+        Err(e) => {
+            error!(target: FRONTEND_TARGET, "Could not open a window for the error: {}", e);
+            return;
+        }
+    };
+    let textures = canvas.texture_creator();
+    let Ok(mut texture) = streaming_texture(&textures, ERROR_SCREEN_WIDTH, ERROR_SCREEN_HEIGHT)
+    else {
+        return;
+    };
+
+    let screen = render_error_screen(title, message, None);
+    let mut limiter = FrameLimiter::new();
+    loop {
+        let dismissed = events.poll_iter().any(|event| {
+            matches!(
+                event,
+                Event::Quit { .. }
+                    | Event::KeyDown {
+                        scancode: Some(Scancode::Escape),
+                        ..
+                    }
+            )
+        });
+        if dismissed {
+            break;
+        }
+        draw_screen(&mut canvas, &mut texture, &screen);
+        limiter.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keypad_covers_every_key_once() {
+        let mut keys: Vec<u8> = KEYPAD.iter().map(|&(_, _, key)| key).collect();
+        keys.sort();
+        assert_eq!(keys, (0..16).collect::<Vec<u8>>());
+        assert_eq!(keypad_key(Scancode::X), Some(0x0));
+        assert_eq!(keypad_key(Scancode::Escape), None);
+        assert_eq!(secondary_key(Scancode::Return), Some(0x6));
+        assert_eq!(secondary_key(Scancode::X), None);
+    }
+
+    #[test]
+    fn only_the_arrows_repeat() {
+        assert_eq!(hotkey(Scancode::Up, true), Some(InputEvent::Menu(MenuKey::Up)));
+        assert_eq!(hotkey(Scancode::F1, false), Some(InputEvent::Help));
+        assert_eq!(hotkey(Scancode::F1, true), None);
+        assert_eq!(hotkey(Scancode::X, false), None);
+    }
+}
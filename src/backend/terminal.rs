@@ -0,0 +1,543 @@
+use std::io::{self, Stdout, Write};
+use std::panic;
+use std::time::{Duration, Instant};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{
+    self, DisableFocusChange, EnableFocusChange, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::terminal::{
+    self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode,
+    enable_raw_mode,
+};
+use crossterm::{execute, queue};
+use log::error;
+
+use chip8_emulator::frontend::cells::{CELL_COLUMNS, CELL_ROWS, Cell, render_cells};
+use chip8_emulator::frontend::console::{CONSOLE_ROWS, ConsoleKey, ConsoleView};
+use chip8_emulator::frontend::help::{Binding, HELP_PAGE_LINES, HelpLine, HelpPage};
+use chip8_emulator::frontend::keypad_card::{KeypadCard, card_layout};
+use chip8_emulator::frontend::menu::{MenuKey, MenuView};
+use chip8_emulator::frontend::registers::{REGISTER_ROWS, RegistersView};
+use chip8_emulator::frontend::secondary::{self, SecondaryKey};
+use chip8_emulator::frontend::timer::TimerView;
+use chip8_emulator::frontend::{
+    AudioCommand, AudioSink, Frame, InputEvent, InputSource, VideoSink,
+};
+use chip8_emulator::overlay::indicators::Indicator;
+
+use super::FrameLimiter;
+use crate::FRONTEND_TARGET;
+
+/// Without key release events a key counts as held this long after it's
+/// pressed, long enough to bridge the terminal's delay before auto-repeat...
+const INITIAL_HOLD: Duration = Duration::from_millis(300);
+/// ...and this long after each repeat.
+const REPEAT_HOLD: Duration = Duration::from_millis(100);
+
+/// Playfield plus the status line below it.
+const LAYOUT_ROWS: u16 = CELL_ROWS as u16 + 1;
+
+// The help overlay's title and a blank row go above its page.
+const _: () = assert!(HELP_PAGE_LINES + 2 <= CELL_ROWS);
+// The console's output and input line leave some of the playfield showing.
+const _: () = assert!(CONSOLE_ROWS + 1 < CELL_ROWS);
+const _: () = assert!(REGISTER_ROWS < CELL_ROWS);
+
+const KEYPAD: [(char, &str, u8); 16] = [
+    ('1', "1", 0x1),
+    ('2', "2", 0x2),
+    ('3', "3", 0x3),
+    ('4', "4", 0xC),
+    ('q', "Q", 0x4),
+    ('w', "W", 0x5),
+    ('e', "E", 0x6),
+    ('r', "R", 0xD),
+    ('a', "A", 0x7),
+    ('s', "S", 0x8),
+    ('d', "D", 0x9),
+    ('f', "F", 0xE),
+    ('z', "Z", 0xA),
+    ('x', "X", 0x0),
+    ('c', "C", 0xB),
+    ('v', "V", 0xF),
+];
+
+/// Every other key, the modifiers it needs, and whether it repeats while
+/// held. The arrows and the console's editing keys do.
+const HOTKEYS: [(KeyCode, KeyModifiers, &str, InputEvent, bool); 25] = [
+    (KeyCode::Char('c'), KeyModifiers::CONTROL, "Ctrl+C", InputEvent::Quit, false),
+    (KeyCode::Char('='), KeyModifiers::CONTROL, "Ctrl+=", InputEvent::ResetSpeed, false),
+    (KeyCode::Char('='), KeyModifiers::NONE, "=", InputEvent::SpeedUp, false),
+    (KeyCode::Char('-'), KeyModifiers::NONE, "-", InputEvent::SlowDown, false),
+    (KeyCode::Char('+'), KeyModifiers::NONE, "+", InputEvent::FineSpeedUp, false),
+    (KeyCode::Char('_'), KeyModifiers::NONE, "_", InputEvent::FineSlowDown, false),
+    (KeyCode::Char('0'), KeyModifiers::NONE, "0", InputEvent::ResetSpeed, false),
+    (KeyCode::Esc, KeyModifiers::NONE, "Esc", InputEvent::Menu(MenuKey::Escape), false),
+    (KeyCode::Up, KeyModifiers::NONE, "Up", InputEvent::Menu(MenuKey::Up), true),
+    (KeyCode::Down, KeyModifiers::NONE, "Down", InputEvent::Menu(MenuKey::Down), true),
+    (KeyCode::Left, KeyModifiers::NONE, "Left", InputEvent::Menu(MenuKey::Left), true),
+    (KeyCode::Right, KeyModifiers::NONE, "Right", InputEvent::Menu(MenuKey::Right), true),
+    (KeyCode::Enter, KeyModifiers::NONE, "Enter", InputEvent::Menu(MenuKey::Enter), false),
+    (KeyCode::F(1), KeyModifiers::NONE, "F1", InputEvent::Help, false),
+    (KeyCode::F(2), KeyModifiers::NONE, "F2", InputEvent::Split, false),
+    (KeyCode::F(6), KeyModifiers::NONE, "F6", InputEvent::SaveGameSettings, false),
+    (KeyCode::F(9), KeyModifiers::NONE, "F9", InputEvent::DumpState, false),
+    (KeyCode::Char('p'), KeyModifiers::NONE, "P", InputEvent::Pause, false),
+    (KeyCode::Char('n'), KeyModifiers::NONE, "N", InputEvent::Step, true),
+    (KeyCode::Char('.'), KeyModifiers::NONE, ".", InputEvent::Step, true),
+    (KeyCode::F(3), KeyModifiers::NONE, "F3", InputEvent::Registers, false),
+    (KeyCode::Char('`'), KeyModifiers::NONE, "`", InputEvent::Console(ConsoleKey::Toggle), false),
+    (KeyCode::Backspace, KeyModifiers::NONE, "Backspace", InputEvent::Console(ConsoleKey::Backspace), true),
+    (KeyCode::PageUp, KeyModifiers::NONE, "PageUp", InputEvent::Console(ConsoleKey::PageUp), true),
+    (KeyCode::PageDown, KeyModifiers::NONE, "PageDown", InputEvent::Console(ConsoleKey::PageDown), true),
+];
+
+/// What the terminal's keys send, for the help overlay, with the secondary
+/// bindings if they're on.
+pub fn bindings(secondary: bool) -> Vec<Binding> {
+    let keypad = KEYPAD.iter().map(|&(_, name, key)| Binding {
+        key: name,
+        event: InputEvent::Key { key, pressed: true },
+    });
+    let keypad = keypad.chain(secondary.then(secondary::bindings).into_iter().flatten());
+    let hotkeys = HOTKEYS.iter().map(|&(_, _, name, event, _)| Binding { key: name, event });
+    keypad.chain(hotkeys).collect()
+}
+
+fn keypad_key(c: char) -> Option<u8> {
+    let c = c.to_ascii_lowercase();
+    KEYPAD.iter().find(|(key_char, _, _)| *key_char == c).map(|&(_, _, key)| key)
+}
+
+/// The keypad key the secondary binding on `code` presses, if there's one.
+fn secondary_key(code: KeyCode) -> Option<u8> {
+    let key = match code {
+        KeyCode::Up => SecondaryKey::Up,
+        KeyCode::Down => SecondaryKey::Down,
+        KeyCode::Left => SecondaryKey::Left,
+        KeyCode::Right => SecondaryKey::Right,
+        KeyCode::Char(' ') => SecondaryKey::Space,
+        KeyCode::Enter => SecondaryKey::Enter,
+        _ => return None,
+    };
+    Some(secondary::keypad_key(key))
+}
+
+/// The hotkey `event` is, if it's one. Modifiers beyond the ones a hotkey
+/// needs are allowed, so Shift+`=` still counts as `+`.
+fn hotkey(event: &KeyEvent) -> Option<(InputEvent, bool)> {
+    HOTKEYS
+        .iter()
+        .find(|&&(code, modifiers, ..)| code == event.code && event.modifiers.contains(modifiers))
+        .map(|&(_, _, _, hotkey, repeats)| (hotkey, repeats))
+}
+
+/// Draws the display with half-block characters, centered in the terminal.
+pub struct TerminalVideo {
+    out: Stdout,
+    enhanced: bool,
+    size: (u16, u16),
+    /// What's currently on screen, so unchanged rows aren't redrawn.
+    drawn: Option<Vec<Vec<Cell>>>,
+    status: String,
+    overlaid: bool,
+    stopped: Option<String>,
+    /// Where the program ended, to print once the terminal is restored.
+    finished: Option<u16>,
+    /// The speedrun timer when the machine stopped, to print with why.
+    final_time: Option<String>,
+    limiter: FrameLimiter,
+}
+
+/// Keyboard input read from the terminal in raw mode.
+pub struct TerminalInput {
+    enhanced: bool,
+    /// Whether the arrows, Space and Enter press keypad keys too.
+    secondary: bool,
+    /// When each held key counts as released, for terminals that don't report
+    /// releases.
+    held: [Option<Instant>; 16],
+}
+
+/// Rings the terminal bell when the buzzer starts.
+pub struct TerminalAudio;
+
+/// Switches the terminal to raw mode on the alternate screen, reading keys
+/// with the `secondary` bindings or without. It's restored when the
+/// returned [`TerminalVideo`] is dropped, or on panic.
+pub fn open(secondary: bool) -> io::Result<(TerminalVideo, TerminalInput, TerminalAudio)> {
+    let mut out = io::stdout();
+    enable_raw_mode()?;
+    execute!(out, EnterAlternateScreen, Hide, EnableFocusChange, Clear(ClearType::All))?;
+
+    // Key release events need the kitty keyboard protocol.
+    let enhanced = terminal::supports_keyboard_enhancement().unwrap_or(false);
+    if enhanced {
+        execute!(
+            out,
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+        )?;
+    }
+
+    let previous_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        restore(enhanced);
+        previous_hook(info);
+    }));
+
+    let video = TerminalVideo {
+        out,
+        enhanced,
+        size: terminal::size()?,
+        drawn: None,
+        status: String::new(),
+        overlaid: false,
+        stopped: None,
+        finished: None,
+        final_time: None,
+        limiter: FrameLimiter::new(),
+    };
+    let input = TerminalInput {
+        enhanced,
+        secondary,
+        held: [None; 16],
+    };
+    Ok((video, input, TerminalAudio))
+}
+
+fn restore(enhanced: bool) {
+    let mut out = io::stdout();
+    if enhanced {
+        let _ = execute!(out, PopKeyboardEnhancementFlags);
+    }
+    let _ = execute!(out, ResetColor, Show, DisableFocusChange, LeaveAlternateScreen);
+    let _ = disable_raw_mode();
+}
+
+impl Drop for TerminalVideo {
+    fn drop(&mut self) {
+        restore(self.enhanced);
+        if let Some(message) = &self.stopped {
+            eprintln!("✗ Machine stopped: {}", message);
+        }
+        if let Some(pc) = self.finished {
+            println!("Program halted at 0x{:03X}", pc);
+        }
+        if let Some(time) = &self.final_time {
+            println!("Final time: {}", time);
+        }
+    }
+}
+
+fn color(rgb: u32) -> Color {
+    Color::Rgb {
+        r: (rgb >> 16) as u8,
+        g: (rgb >> 8) as u8,
+        b: rgb as u8,
+    }
+}
+
+impl TerminalVideo {
+    fn draw(&mut self, cells: Vec<Vec<Cell>>, status: String) -> io::Result<()> {
+        let size = terminal::size()?;
+        if size != self.size {
+            self.size = size;
+            self.drawn = None;
+            queue!(self.out, ResetColor, Clear(ClearType::All))?;
+        }
+        let (columns, rows) = size;
+        let left = columns.saturating_sub(CELL_COLUMNS as u16) / 2;
+        let top = rows.saturating_sub(LAYOUT_ROWS) / 2;
+
+        for (index, row) in cells.iter().enumerate() {
+            if self.drawn.as_ref().is_some_and(|drawn| drawn[index] == *row) {
+                continue;
+            }
+            queue!(self.out, MoveTo(left, top + index as u16))?;
+            for cell in row {
+                queue!(
+                    self.out,
+                    SetForegroundColor(color(cell.fg)),
+                    SetBackgroundColor(color(cell.bg)),
+                    Print(cell.ch)
+                )?;
+            }
+        }
+
+        if self.drawn.is_none() || status != self.status {
+            queue!(
+                self.out,
+                ResetColor,
+                MoveTo(left, top + CELL_ROWS as u16),
+                Clear(ClearType::UntilNewLine),
+                Print(&status)
+            )?;
+            self.status = status;
+        }
+
+        self.drawn = Some(cells);
+        self.out.flush()
+    }
+}
+
+impl VideoSink for TerminalVideo {
+    fn present(&mut self, frame: &Frame<'_>) {
+        let still = frame.is_still(&mut self.overlaid);
+        let cells = match (frame.keypad_card, frame.help, &self.drawn) {
+            // The rows on screen already show it, so none get redrawn.
+            (_, _, Some(drawn)) if still => drawn.clone(),
+            (Some(card), _, _) => keypad_cells(card),
+            (None, Some(help), _) => help_cells(help),
+            (None, None, _) => {
+                let mut cells = render_cells(frame.pixels, frame.width, frame.height, frame.palette);
+                if let Some(registers) = frame.registers {
+                    draw_registers(&mut cells, registers);
+                }
+                if let Some(console) = frame.console {
+                    draw_console(&mut cells, console);
+                }
+                cells
+            }
+        };
+        let state = match frame.halted {
+            Some(e) => format!("stopped: {}", e),
+            None if frame.finished.is_some() => "halted".to_string(),
+            None if frame.paused => "paused".to_string(),
+            None => "running".to_string(),
+        };
+        let escape = if frame.escape_quits { "ESC to exit" } else { "ESC for menu" };
+        let mut status = match (frame.help, frame.menu) {
+            (Some(_), _) if frame.keypad_card.is_some() => "Keypad | F1 or ESC to close | Enter for help".to_string(),
+            (Some(_), _) => "F1 or ESC to close | arrows to turn pages | Enter for keypad".to_string(),
+            (None, _) if frame.console.is_some() => "Console | Enter to run | ` or ESC to close".to_string(),
+            (None, Some(menu)) => menu_line(menu),
+            (None, None) => format!("Speed: {} | {} | {}", frame.speed.label(), state, escape),
+        };
+        if let Some(hash) = frame.state_hash {
+            status.push_str(&format!(" | state {:016x}", hash));
+        }
+        // The status line already says when the machine is paused.
+        for indicator in frame.indicators.active().filter(|&indicator| indicator != Indicator::Paused) {
+            status.push_str(" | ");
+            status.push_str(indicator.symbol());
+        }
+        if let Some(timer) = frame.timer {
+            status.push_str(&format!(" | ⏱ {}", timer.time()));
+            if let Some(split) = timer.split_lines().last() {
+                status.push_str(&format!(" ({})", split));
+            }
+        }
+        if let Some(e) = frame.halted {
+            self.stopped = Some(e.to_string());
+            self.final_time = frame.timer.map(TimerView::time);
+        }
+        if frame.finished.is_some() {
+            self.finished = frame.finished;
+            self.final_time = frame.timer.map(TimerView::time);
+        }
+
+        if let Err(e) = self.draw(cells, status) {
+            error!(target: FRONTEND_TARGET, "Could not draw to the terminal: {}", e);
+        }
+        self.limiter.wait();
+    }
+}
+
+/// The help overlay in place of the playfield: its title, a blank row, then
+/// the page's lines.
+fn help_cells(help: &HelpPage) -> Vec<Vec<Cell>> {
+    let text = |line: &str, fg: u32| -> Vec<Cell> {
+        line.chars()
+            .chain(std::iter::repeat(' '))
+            .take(CELL_COLUMNS)
+            .map(|ch| Cell { ch, fg, bg: 0x000000 })
+            .collect()
+    };
+    let mut rows = vec![text(&help.title(), 0xFF5555), text("", 0xFFFFFF)];
+    for line in &help.lines {
+        let fg = if matches!(line, HelpLine::Heading(_)) { 0xFFFF55 } else { 0xFFFFFF };
+        rows.push(text(&line.to_string(), fg));
+    }
+    rows.resize_with(CELL_ROWS, || text("", 0xFFFFFF));
+    rows
+}
+
+/// The keypad card in place of the playfield, each key a box four rows
+/// high with its digit and the keys bound to it.
+fn keypad_cells(card: &KeypadCard) -> Vec<Vec<Cell>> {
+    let blank = Cell { ch: ' ', fg: 0xFFFFFF, bg: 0x000000 };
+    let mut rows = vec![vec![blank; CELL_COLUMNS]; CELL_ROWS];
+    for cell in card_layout(CELL_COLUMNS, CELL_ROWS, 0) {
+        let bg = if card.awaiting == Some(cell.key) { 0x555500 } else { 0x000000 };
+        let inner = cell.width - 2;
+        let border = |left: char, right: char| format!("{}{}{}", left, "─".repeat(inner), right);
+        let text = |text: &str| format!("│ {:<width$.width$}│", text, width = inner - 1);
+        let lines = [
+            (border('┌', '┐'), 0xAAAAAA),
+            (text(&format!("{:X}", cell.key)), 0xFFFF55),
+            (text(&card.label(cell.key)), 0xFFFFFF),
+            (border('└', '┘'), 0xAAAAAA),
+        ];
+        for (offset, (line, fg)) in lines.iter().enumerate().take(cell.height) {
+            let row = &mut rows[cell.y + offset][cell.x..cell.x + cell.width];
+            for (slot, ch) in row.iter_mut().zip(line.chars()) {
+                *slot = Cell { ch, fg: *fg, bg };
+            }
+        }
+    }
+    rows
+}
+
+/// Drops the console down over the top rows of the playfield: its output,
+/// then the input line with the cursor's cell inverted.
+fn draw_console(rows: &mut [Vec<Cell>], console: &ConsoleView) {
+    let text = |line: &str, fg: u32| -> Vec<Cell> {
+        line.chars()
+            .chain(std::iter::repeat(' '))
+            .take(CELL_COLUMNS)
+            .map(|ch| Cell { ch, fg, bg: 0x101010 })
+            .collect()
+    };
+    let first = CONSOLE_ROWS - console.lines.len();
+    for (index, row) in rows.iter_mut().take(CONSOLE_ROWS).enumerate() {
+        *row = match index.checked_sub(first).map(|line| &console.lines[line]) {
+            Some(line) if line.starts_with("error:") => text(line, 0xFF5555),
+            Some(line) => text(line, 0xFFFFFF),
+            None => text("", 0xFFFFFF),
+        };
+    }
+    if console.scrolled {
+        let last = &mut rows[CONSOLE_ROWS - 1];
+        last[CELL_COLUMNS - 1] = Cell { ch: '…', fg: 0xAAAAAA, bg: 0x101010 };
+    }
+
+    // A line too long for the playfield scrolls to keep the cursor in view.
+    let columns = CELL_COLUMNS - 2;
+    let skip = (console.cursor + 1).saturating_sub(columns);
+    let input: String = console.input.chars().skip(skip).collect();
+    let mut line = text(&format!("> {}", input), 0xFFFF55);
+    let cursor = &mut line[2 + console.cursor - skip];
+    *cursor = Cell { ch: cursor.ch, fg: 0x101010, bg: 0xFFFF55 };
+    rows[CONSOLE_ROWS] = line;
+}
+
+/// Writes the register readout over the bottom rows of the playfield, what
+/// changed in yellow.
+fn draw_registers(rows: &mut [Vec<Cell>], registers: &RegistersView) {
+    let first = rows.len() - REGISTER_ROWS;
+    for row in &mut rows[first..] {
+        row.fill(Cell { ch: ' ', fg: 0xFFFFFF, bg: 0x101010 });
+    }
+    for (row, column, cell) in registers.cells() {
+        let fg = if cell.changed { 0xFFFF55 } else { 0xFFFFFF };
+        let row = &mut rows[first + row];
+        for (slot, ch) in row.iter_mut().skip(column).zip(cell.text.chars()) {
+            *slot = Cell { ch, fg, bg: 0x101010 };
+        }
+    }
+}
+
+/// The pause menu on one line, the selected item in brackets.
+fn menu_line(menu: &MenuView) -> String {
+    let items: Vec<String> = menu
+        .labels
+        .iter()
+        .enumerate()
+        .map(|(index, label)| if index == menu.selected { format!("[{}]", label) } else { label.clone() })
+        .collect();
+    format!("Paused: {}", items.join("  "))
+}
+
+impl TerminalInput {
+    fn key_event(&mut self, event: KeyEvent, now: Instant, events: &mut Vec<InputEvent>) {
+        let pressed = event.kind != KeyEventKind::Release;
+        if pressed
+            && let KeyCode::Char(ch) = event.code
+            && !event.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+        {
+            events.push(InputEvent::Console(ConsoleKey::Char(ch)));
+        }
+
+        let hotkey = hotkey(&event);
+        if let Some((hotkey, repeats)) = hotkey
+            && (event.kind == KeyEventKind::Press || (repeats && pressed))
+        {
+            events.push(hotkey);
+        }
+
+        let keypad = match event.code {
+            KeyCode::Char(c) if hotkey.is_none() => keypad_key(c),
+            _ => None,
+        };
+        // The arrows and Enter are hotkeys and keypad keys both.
+        let secondary = secondary_key(event.code).filter(|_| self.secondary);
+        let Some(key) = keypad.or(secondary) else {
+            return;
+        };
+        let held = &mut self.held[key as usize];
+
+        if !pressed {
+            if held.take().is_some() {
+                events.push(InputEvent::Key { key, pressed: false });
+            }
+            return;
+        }
+        if held.is_none() {
+            events.push(InputEvent::Key { key, pressed: true });
+        }
+        let hold = if event.kind == KeyEventKind::Repeat {
+            REPEAT_HOLD
+        } else {
+            INITIAL_HOLD
+        };
+        *held = Some(now + hold);
+    }
+}
+
+impl InputSource for TerminalInput {
+    fn drain_events(&mut self) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+        let now = Instant::now();
+
+        loop {
+            match event::poll(Duration::ZERO).and_then(|ready| ready.then(event::read).transpose()) {
+                Ok(Some(Event::Key(key_event))) => self.key_event(key_event, now, &mut events),
+                Ok(Some(Event::FocusGained)) => events.push(InputEvent::Focus(true)),
+                Ok(Some(Event::FocusLost)) => events.push(InputEvent::Focus(false)),
+                Ok(Some(_)) => {}
+                Ok(None) => break,
+                Err(e) => {
+                    error!(target: FRONTEND_TARGET, "Could not read from the terminal: {}", e);
+                    events.push(InputEvent::Quit);
+                    break;
+                }
+            }
+        }
+
+        if !self.enhanced {
+            for (key, held) in self.held.iter_mut().enumerate() {
+                if held.is_some_and(|until| until <= now) {
+                    *held = None;
+                    events.push(InputEvent::Key {
+                        key: key as u8,
+                        pressed: false,
+                    });
+                }
+            }
+        }
+
+        events
+    }
+}
+
+impl AudioSink for TerminalAudio {
+    fn command(&mut self, command: AudioCommand) {
+        if command != AudioCommand::BuzzerOff {
+            let mut out = io::stdout();
+            let _ = out.write_all(b"\x07").and_then(|_| out.flush());
+        }
+    }
+}
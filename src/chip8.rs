@@ -0,0 +1,2657 @@
+#[cfg(feature = "std")]
+use std::path::Path;
+
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, collections::BTreeSet, vec::Vec};
+
+use log::{info, trace, warn};
+
+#[cfg(feature = "alloc")]
+use crate::decode_cache::DecodeCache;
+use crate::color_map::{ColorMap, ZONE_COLUMNS, ZONE_ROWS};
+use crate::display::{Display, PLANES, Resolution};
+use crate::error::Chip8Error;
+use crate::events::MachineEvent;
+use crate::hex_font::{BIG_FONT_BASE, BIG_FONTSET, BIG_GLYPH_BYTES, FONT_BASE, GLYPH_BYTES, HexFont};
+use crate::instruction::{Instruction, decode};
+use crate::memory_map::MemoryMap;
+use crate::platform::{self, Detection, Platform};
+#[cfg(feature = "std")]
+use crate::loader;
+use crate::quirks::Quirks;
+#[cfg(feature = "alloc")]
+use crate::render::Palette;
+use crate::rom_db::{self, KnownRom};
+use crate::rng::{RandomSource, Rng};
+use crate::stack::Stack;
+use crate::watch::Access;
+#[cfg(feature = "alloc")]
+use crate::watch::{WatchHit, Watchpoint};
+
+const LOG_TARGET: &str = "chip8::cpu";
+
+/// Address at which programs are loaded and execution starts, unless
+/// [`Chip8::set_program_start`] says otherwise. Everything below it is the
+/// interpreter's.
+pub const PROGRAM_START: u16 = 0x200;
+
+/// Where a HI-RES CHIP-8 program carries on once its 1260 switches to
+/// 64x64, past the interpreter patch it brings along.
+const TWO_PAGE_START: u16 = 0x2C0;
+
+/// Size of the address space.
+pub const MEMORY_SIZE: usize = 4096;
+
+/// Size of XO-CHIP's address space, under [`Quirks::extended_memory`].
+pub const EXTENDED_MEMORY_SIZE: usize = 0x10000;
+
+/// Maximum depth of nested subroutine calls.
+pub const STACK_DEPTH: usize = 16;
+
+/// How many RPL user flags FX75 and FX85 have. SUPER-CHIP had 8, on the
+/// HP48; XO-CHIP allows all 16 registers.
+pub const RPL_FLAGS: usize = 16;
+
+/// Bytes in XO-CHIP's audio pattern, 128 one-bit samples.
+pub const AUDIO_PATTERN_BYTES: usize = 16;
+
+/// The pitch FX3A starts out at, which plays the pattern at 4000 samples a
+/// second.
+pub const DEFAULT_PITCH: u8 = 64;
+
+/// 64-bit FNV-1a, fed a piece at a time.
+pub(crate) struct Fnv1a(u64);
+
+impl Fnv1a {
+    pub(crate) const fn new() -> Self {
+        Self(0xCBF2_9CE4_8422_2325)
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0100_0000_01B3);
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash = Fnv1a::new();
+    hash.write(bytes);
+    hash.finish()
+}
+
+/// What to do with a 0NNN machine code call, which runs a routine written
+/// for the host CPU and can't be emulated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SysPolicy {
+    /// Skip it like any unknown opcode, logging it every time.
+    #[default]
+    Ignore,
+    /// Skip it without an error, logging only the first one.
+    WarnOnce,
+    /// Stop the machine with [`Chip8Error::MachineCodeCall`].
+    Halt,
+}
+
+impl SysPolicy {
+    /// Names accepted by [`SysPolicy::from_name`].
+    pub const NAMES: &'static [&'static str] = &["ignore", "warn-once", "halt"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ignore" => Some(Self::Ignore),
+            "warn-once" => Some(Self::WarnOnce),
+            "halt" => Some(Self::Halt),
+            _ => None,
+        }
+    }
+}
+
+/// What to do with an opcode the interpreter doesn't know.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OpcodePolicy {
+    /// Skip it with a recoverable [`Chip8Error::InvalidOpcode`], logging it
+    /// every time.
+    #[default]
+    Continue,
+    /// Skip it without an error, logging each opcode the first time it comes
+    /// up. Without the `alloc` feature every one is logged.
+    WarnOnce,
+    /// Stop the machine with [`Chip8Error::FatalOpcode`].
+    Halt,
+}
+
+impl OpcodePolicy {
+    /// Names accepted by [`OpcodePolicy::from_name`].
+    pub const NAMES: &'static [&'static str] = &["continue", "warn-once", "halt"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "continue" => Some(Self::Continue),
+            "warn-once" => Some(Self::WarnOnce),
+            "halt" => Some(Self::Halt),
+            _ => None,
+        }
+    }
+}
+
+/// FX0A's wait for a key, while the machine is in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct KeyWait {
+    /// Where the FX0A is.
+    at: u16,
+    /// The register the key goes in.
+    x: usize,
+    /// The key seen go down, which it waits to see released.
+    pressed: Option<u8>,
+}
+
+/// What one [`Chip8::step`] did, for debuggers, tracers and tests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepReport {
+    /// Where the instruction was.
+    pub pc: u16,
+    pub opcode: u16,
+    /// Whether the instruction drew on, cleared, scrolled or resized the
+    /// display, or recolored it under the [chip8x](Quirks::chip8x) quirk. A
+    /// DXYN of a blank sprite still counts.
+    pub display_changed: bool,
+    /// Whether the buzzer started or stopped: the sound timer went from zero
+    /// to more or back.
+    pub sound_changed: bool,
+    /// Whether it was an FX0A still waiting for a key to be pressed and
+    /// released, so the next step carries on waiting.
+    pub waiting_for_key: bool,
+    /// [`Chip8::finished`], after the instruction.
+    pub finished: Option<u16>,
+}
+
+/// The CHIP-8 machine state, drawing CXNN's random bytes from `R`.
+pub struct Chip8<R = Rng> {
+    /// Room for all of XO-CHIP's 64 KB, though only the first
+    /// [`memory_size`](Self::memory_size) bytes are addressable; the rest
+    /// stays 0.
+    pub memory: [u8; EXTENDED_MEMORY_SIZE],
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub stack: Stack,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub display: Display,
+    pub keys: [bool; 16],
+    /// What FX75 saved, which the frontend keeps in a file between runs so
+    /// high scores survive. Not part of the save state, so resets keep them.
+    pub rpl_flags: [u8; RPL_FLAGS],
+    /// The samples F002 loaded, MSB first, which XO-CHIP plays in a loop
+    /// while the sound timer runs. Until a program loads some the buzzer
+    /// sounds instead.
+    pub audio_pattern: Option<[u8; AUDIO_PATTERN_BYTES]>,
+    /// FX3A's pitch: the pattern plays at 4000 * 2^((pitch - 64) / 48)
+    /// samples a second.
+    pub pitch: u8,
+    /// The colors BXY0, BXYN and 02A0 set under the [CHIP-8X](Quirks::chip8x)
+    /// quirk.
+    pub color_map: ColorMap,
+    pub quirks: Quirks,
+    pub sys_policy: SysPolicy,
+    pub opcode_policy: OpcodePolicy,
+    /// Rejects instruction writes below [`PROGRAM_START`], where the font
+    /// lives, with [`Chip8Error::ProtectedWrite`] instead of letting them
+    /// through.
+    pub strict_memory: bool,
+    /// Warns when a ROM has an odd number of bytes, which usually means a
+    /// bad dump since every instruction is two.
+    pub warn_odd_length: bool,
+    pub(crate) rng: R,
+    pub(crate) rom_hash: Option<u64>,
+    rom_len: usize,
+    platform: Option<Detection>,
+    known_rom: Option<&'static KnownRom>,
+    font: HexFont,
+    font_base: u16,
+    program_start: u16,
+    vblank_wait: bool,
+    /// Whether [`SysPolicy::WarnOnce`] has warned yet.
+    sys_warned: bool,
+    /// The unknown opcodes [`OpcodePolicy::WarnOnce`] has warned about.
+    #[cfg(feature = "alloc")]
+    warned_opcodes: BTreeSet<u16>,
+    /// The FX0A the machine is waiting in, if it is.
+    pub(crate) key_wait: Option<KeyWait>,
+    #[cfg(feature = "alloc")]
+    decode_cache: Option<DecodeCache>,
+    #[cfg(feature = "alloc")]
+    pub(crate) observer: Option<Box<Observer>>,
+    #[cfg(feature = "alloc")]
+    watchpoints: Vec<Watchpoint>,
+    /// The first watched access of the last step.
+    #[cfg(feature = "alloc")]
+    watch_hit: Option<WatchHit>,
+}
+
+/// What [`Chip8::set_observer`] takes.
+#[cfg(feature = "alloc")]
+pub type Observer = dyn FnMut(MachineEvent) + Send;
+
+#[cfg(feature = "std")]
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Chip8 {
+    /// A machine whose RNG is seeded from the operating system.
+    #[cfg(feature = "std")]
+    pub fn new() -> Self {
+        Self::with_rng(Rng::from_entropy())
+    }
+
+    /// Reseeds the CXNN random generator so runs can be reproduced.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+}
+
+impl<R: RandomSource> Chip8<R> {
+    pub fn with_rng(rng: R) -> Self {
+        let mut memory = [0u8; EXTENDED_MEMORY_SIZE];
+        let v = [0u8; 16];
+
+        let font = FONT_BASE as usize;
+        memory[font..font + 80].copy_from_slice(HexFont::default().glyphs());
+        let big_font = BIG_FONT_BASE as usize;
+        memory[big_font..big_font + BIG_FONTSET.len()].copy_from_slice(&BIG_FONTSET);
+
+        Self {
+            memory,
+            v,
+            i: 0,
+            pc: PROGRAM_START,
+            stack: Stack::new(),
+            delay_timer: 0,
+            sound_timer: 0,
+            display: Display::default(),
+            keys: [false; 16],
+            rpl_flags: [0; RPL_FLAGS],
+            audio_pattern: None,
+            pitch: DEFAULT_PITCH,
+            color_map: ColorMap::new(),
+            quirks: Quirks::default(),
+            sys_policy: SysPolicy::default(),
+            strict_memory: false,
+            warn_odd_length: false,
+            rng,
+            rom_hash: None,
+            rom_len: 0,
+            platform: None,
+            known_rom: None,
+            font: HexFont::default(),
+            font_base: FONT_BASE,
+            program_start: PROGRAM_START,
+            vblank_wait: false,
+            sys_warned: false,
+            opcode_policy: OpcodePolicy::default(),
+            #[cfg(feature = "alloc")]
+            warned_opcodes: BTreeSet::new(),
+            key_wait: None,
+            #[cfg(feature = "alloc")]
+            decode_cache: None,
+            #[cfg(feature = "alloc")]
+            observer: None,
+            #[cfg(feature = "alloc")]
+            watchpoints: Vec::new(),
+            #[cfg(feature = "alloc")]
+            watch_hit: None,
+        }
+    }
+
+    /// Reads a ROM file and loads it with [`Chip8::load_rom_bytes`]. Missing
+    /// files come back with suggestions from [`default_search_path`].
+    ///
+    /// [`default_search_path`]: crate::loader::default_search_path
+    #[cfg(feature = "std")]
+    pub fn load_rom(&mut self, rom_path: impl AsRef<Path>) -> Result<(), Chip8Error> {
+        let max = self.memory_size() - self.program_start as usize;
+        let rom_data = loader::read_rom(rom_path.as_ref(), max, &loader::default_search_path())?;
+
+        self.load_rom_bytes(&rom_data)
+    }
+
+    /// Copies `rom_data` into memory at the [program start](Self::program_start).
+    pub fn load_rom_bytes(&mut self, rom_data: &[u8]) -> Result<(), Chip8Error> {
+        let start = self.program_start as usize;
+        let max = self.memory_size() - start;
+        if rom_data.len() > max {
+            return Err(Chip8Error::RomTooLarge {
+                size: rom_data.len(),
+                max,
+            });
+        }
+        if rom_data.is_empty() {
+            return Err(Chip8Error::EmptyRom);
+        }
+        if self.warn_odd_length && rom_data.len() % 2 == 1 {
+            warn!(target: LOG_TARGET, "ROM is {} bytes, an odd length; the last instruction is cut short", rom_data.len());
+        }
+
+        self.memory[start..start + rom_data.len()].copy_from_slice(rom_data);
+        self.rom_hash = Some(fnv1a(rom_data));
+        self.rom_len = rom_data.len();
+        self.platform = platform::prescan(rom_data, self.program_start);
+        self.known_rom = rom_db::lookup(rom_data);
+        info!(target: LOG_TARGET, "Loaded {} byte program at 0x{:03X}", rom_data.len(), start);
+
+        self.invalidate_decode_cache();
+        Ok(())
+    }
+
+    /// The 64-bit FNV-1a hash of the last ROM loaded, to tell which program a
+    /// machine is running.
+    pub fn rom_hash(&self) -> Option<u64> {
+        self.rom_hash
+    }
+
+    /// The unsupported extension the last ROM loaded looks to be written
+    /// for, going by [`prescan`](platform::prescan). CHIP-8X is supported
+    /// under the [chip8x](Quirks::chip8x) quirk.
+    pub fn platform(&self) -> Option<Detection> {
+        self.platform.filter(|detection| !(detection.platform == Platform::Chip8X && self.quirks.chip8x))
+    }
+
+    /// The [`rom_db`] entry for the last ROM loaded, if it's a game the
+    /// database knows.
+    pub fn known_rom(&self) -> Option<&'static KnownRom> {
+        self.known_rom
+    }
+
+    /// Where the font, the program and work RAM are, going by the last ROM
+    /// loaded.
+    pub fn memory_map(&self) -> MemoryMap {
+        // A ROM filling all 64 KB ends one past what a u16 holds; the map
+        // calls its last byte work RAM.
+        let rom_end = (self.program_start as usize + self.rom_len).min(self.memory_size()).min(u16::MAX as usize) as u16;
+        MemoryMap {
+            font: self.font_base..self.font_base + 16 * GLYPH_BYTES,
+            rom: self.program_start..rom_end,
+            size: self.memory_size(),
+            ..MemoryMap::new(self.rom_len)
+        }
+    }
+
+    /// How much memory programs can address: [`MEMORY_SIZE`], or
+    /// [`EXTENDED_MEMORY_SIZE`] under [`Quirks::extended_memory`].
+    pub fn memory_size(&self) -> usize {
+        if self.quirks.extended_memory { EXTENDED_MEMORY_SIZE } else { MEMORY_SIZE }
+    }
+
+    /// The addressable part of [`memory`](Self::memory).
+    pub fn addressable_memory(&self) -> &[u8] {
+        &self.memory[..self.memory_size()]
+    }
+
+    /// What I and the addresses computed from it wrap at.
+    fn address_mask(&self) -> usize {
+        self.memory_size() - 1
+    }
+
+    /// Replaces the font with `font` at `base`, clearing where it was.
+    ///
+    /// # Panics
+    ///
+    /// If the font wouldn't [fit](HexFont::fits_at) below [`PROGRAM_START`]
+    /// and clear of the big font.
+    pub fn load_font(&mut self, font: HexFont, base: u16) {
+        let glyphs = font.glyphs();
+        assert!(HexFont::fits_at(base), "a font at 0x{:03X} runs into the program or the big font", base);
+        let old = self.font_base as usize;
+        self.memory[old..old + glyphs.len()].fill(0);
+        let new = base as usize;
+        self.memory[new..new + glyphs.len()].copy_from_slice(glyphs);
+        self.font = font;
+        self.font_base = base;
+        self.invalidate_decode_cache();
+    }
+
+    /// The font [`load_font`](Self::load_font) last loaded.
+    pub fn font(&self) -> HexFont {
+        self.font
+    }
+
+    /// Where the font is, for FX29.
+    pub fn font_base(&self) -> u16 {
+        self.font_base
+    }
+
+    /// Loads programs at `addr` from now on and starts the PC there, for
+    /// machines like the ETI-660 that put them at 0x600. The ROMs that fit
+    /// shrink to match.
+    ///
+    /// # Panics
+    ///
+    /// If `addr` is below [`PROGRAM_START`] or outside of memory.
+    pub fn set_program_start(&mut self, addr: u16) {
+        assert!(
+            (PROGRAM_START as usize..MEMORY_SIZE).contains(&(addr as usize)),
+            "programs can't start at 0x{:03X}",
+            addr
+        );
+        self.program_start = addr;
+        self.pc = addr;
+    }
+
+    /// Where programs are loaded and execution starts.
+    pub fn program_start(&self) -> u16 {
+        self.program_start
+    }
+
+    /// Presses or releases keypad key `key`, returning `false`, and leaving
+    /// the keypad alone, for anything past 0xF.
+    pub fn set_key(&mut self, key: u8, pressed: bool) -> bool {
+        match self.keys.get_mut(key as usize) {
+            Some(down) => {
+                *down = pressed;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Decrements the delay and sound timers; call this at 60 Hz.
+    pub fn tick_timers(&mut self) {
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        if self.sound_timer == 1 {
+            self.notify(MachineEvent::SoundStopped);
+        }
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+    }
+
+    /// Calls `observer` with each [`MachineEvent`] from now on, in place of
+    /// any observer before it.
+    #[cfg(feature = "alloc")]
+    pub fn set_observer(&mut self, observer: impl FnMut(MachineEvent) + Send + 'static) {
+        self.observer = Some(Box::new(observer));
+    }
+
+    /// Stops sending events.
+    #[cfg(feature = "alloc")]
+    pub fn clear_observer(&mut self) {
+        self.observer = None;
+    }
+
+    /// Notes the first access an instruction makes to the addresses
+    /// `watchpoint` covers, for [`watch_hit`](Self::watch_hit). Watchpoints
+    /// aren't part of the machine's state; loading a state keeps them.
+    #[cfg(feature = "alloc")]
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    #[cfg(feature = "alloc")]
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+    }
+
+    #[cfg(feature = "alloc")]
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// The first watched access the last [`step`](Self::step) made, if it
+    /// made one.
+    #[cfg(feature = "alloc")]
+    pub fn watch_hit(&self) -> Option<WatchHit> {
+        self.watch_hit
+    }
+
+    /// Records an instruction's access to `addr` if it's watched and the
+    /// instruction hasn't hit a watchpoint yet.
+    fn watch(&mut self, addr: usize, access: Access, old: u8, new: u8) {
+        #[cfg(feature = "alloc")]
+        if self.watch_hit.is_none() && self.watchpoints.iter().any(|watchpoint| watchpoint.watches(addr, access)) {
+            let pc = self.instruction_pc();
+            self.watch_hit = Some(WatchHit { pc, opcode: self.peek_opcode(pc), addr: addr as u16, access, old, new });
+        }
+        #[cfg(not(feature = "alloc"))]
+        let _ = (addr, access, old, new);
+    }
+
+    fn observed(&self) -> bool {
+        #[cfg(feature = "alloc")]
+        let observed = self.observer.is_some();
+        #[cfg(not(feature = "alloc"))]
+        let observed = false;
+        observed
+    }
+
+    fn notify(&mut self, event: MachineEvent) {
+        #[cfg(feature = "alloc")]
+        if let Some(observer) = &mut self.observer {
+            observer(event);
+        }
+        #[cfg(not(feature = "alloc"))]
+        let _ = event;
+    }
+
+    /// Whether the last instruction was a DXYN waiting for the vertical
+    /// blank under [`Quirks::display_wait`], so the frame's instructions
+    /// should stop there.
+    pub fn waiting_for_vblank(&self) -> bool {
+        self.vblank_wait
+    }
+
+    /// `pc`, if the program has ended there: on a 00FD exit, or on a 1NNN
+    /// jumping to itself, which many test ROMs end on. Either way only the
+    /// timers can change from then on. FX0A waiting for a key isn't one;
+    /// it's still waiting on the FX0A. Nor is a 1NNN above 0xFFF, in
+    /// extended memory, which jumps somewhere below it.
+    pub fn finished(&self) -> Option<u16> {
+        let opcode = self.peek_opcode(self.pc);
+        (opcode == 0x00FD || self.pc <= 0xFFF && opcode == 0x1000 | self.pc).then_some(self.pc)
+    }
+
+    /// Enables or disables the predecoded instruction cache used by [`step`].
+    ///
+    /// The cache is invalidated by the interpreter's own memory writes and by
+    /// ROM loads. Code that writes to `memory` directly while the cache is
+    /// enabled should call [`invalidate_decode_cache`] afterwards.
+    ///
+    /// [`step`]: Chip8::step
+    /// [`invalidate_decode_cache`]: Chip8::invalidate_decode_cache
+    #[cfg(feature = "alloc")]
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.decode_cache = if enabled {
+            Some(DecodeCache::new(self.memory_size()))
+        } else {
+            None
+        };
+    }
+
+    #[cfg(feature = "alloc")]
+    pub fn decode_cache_enabled(&self) -> bool {
+        self.decode_cache.is_some()
+    }
+
+    /// Drops every cached instruction.
+    pub fn invalidate_decode_cache(&mut self) {
+        #[cfg(feature = "alloc")]
+        if let Some(cache) = &mut self.decode_cache {
+            cache.clear();
+        }
+    }
+
+    pub fn fetch(&mut self) -> Result<u16, Chip8Error> {
+        let pc = self.pc as usize;
+        if pc + 1 >= self.memory_size() {
+            return Err(Chip8Error::PcOutOfBounds { pc: self.pc });
+        }
+
+        let high_byte = self.memory[pc];
+        let low_byte = self.memory[pc + 1];
+
+        let opcode = ((high_byte as u16) << 8) | (low_byte as u16);
+        // Only wraps at the top of 64 KB; below that the next fetch is out
+        // of bounds first.
+        self.pc = self.pc.wrapping_add(2);
+
+        Ok(opcode)
+    }
+
+    /// Reads the opcode at `addr` without moving `pc`.
+    pub fn peek_opcode(&self, addr: u16) -> u16 {
+        let high_byte = self.memory[addr as usize % self.memory_size()];
+        let low_byte = self.memory[(addr as usize + 1) % self.memory_size()];
+
+        ((high_byte as u16) << 8) | (low_byte as u16)
+    }
+
+    /// Fetches and executes the instruction at `pc`, going through the
+    /// decode cache when it is enabled.
+    ///
+    /// An unknown opcode is handled as [`Chip8::opcode_policy`] says: by
+    /// default it is skipped over and reported as
+    /// [`Chip8Error::InvalidOpcode`], and the machine can keep stepping after
+    /// it. One from an extension the interpreter doesn't support is
+    /// [`Chip8Error::UnsupportedPlatform`] instead, which it can't.
+    pub fn step(&mut self) -> Result<StepReport, Chip8Error> {
+        let pc = self.pc;
+        let opcode = self.peek_opcode(pc);
+        let sounding = self.sound_timer > 0;
+        let resolution = self.display.resolution();
+        let was_finished = self.observed() && self.finished().is_some();
+        #[cfg(feature = "alloc")]
+        {
+            self.watch_hit = None;
+        }
+
+        let result = match self.key_wait {
+            Some(wait) if wait.at == pc => {
+                self.poll_key_wait(wait);
+                Ok(Some(Instruction::WaitKey { x: wait.x }))
+            }
+            _ => {
+                // Something moved the PC off the FX0A, which ends its wait.
+                self.key_wait = None;
+                self.fetch_and_execute(pc)
+            }
+        };
+        let instruction = match result {
+            Ok(instruction) => instruction,
+            Err(e) => {
+                if !e.is_recoverable() {
+                    self.notify(MachineEvent::Halted { pc });
+                }
+                return Err(e);
+            }
+        };
+        let display_changed = match instruction {
+            Some(
+                Instruction::ClearScreen
+                | Instruction::Draw { .. }
+                | Instruction::ScrollDown(_)
+                | Instruction::ScrollUp(_)
+                | Instruction::ScrollRight
+                | Instruction::ScrollLeft,
+            ) => true,
+            Some(Instruction::JumpOffset(_)) => self.quirks.chip8x,
+            Some(_) => false,
+            // 0230's clear and 02A0's background.
+            None => (opcode == 0x0230 && self.quirks.two_page_hires) || (opcode == 0x02A0 && self.quirks.chip8x),
+        };
+        let report = StepReport {
+            pc,
+            opcode,
+            display_changed: display_changed || self.display.resolution() != resolution,
+            sound_changed: (self.sound_timer > 0) != sounding,
+            waiting_for_key: self.key_wait.is_some(),
+            finished: self.finished(),
+        };
+        if report.sound_changed {
+            self.notify(if sounding { MachineEvent::SoundStopped } else { MachineEvent::SoundStarted });
+        }
+        if let Some(pc) = report.finished
+            && !was_finished
+        {
+            self.notify(MachineEvent::Finished { pc });
+        }
+        Ok(report)
+    }
+
+    /// Executes up to `count` instructions, skipping over recoverable errors
+    /// and stopping at the first one that isn't, or after a DXYN that waits
+    /// for the next frame.
+    pub fn run_instructions(&mut self, count: u32) -> Result<(), Chip8Error> {
+        for _ in 0..count {
+            if let Err(e) = self.step()
+                && !e.is_recoverable()
+            {
+                return Err(e);
+            }
+            if self.waiting_for_vblank() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs `frames` frames of [`run_instructions`](Self::run_instructions),
+    /// ticking the timers after each, the way a frontend would but with the
+    /// keys left as they are.
+    pub fn emulate_frames(&mut self, frames: u32, instructions_per_frame: u32) -> Result<(), Chip8Error> {
+        for _ in 0..frames {
+            self.run_instructions(instructions_per_frame)?;
+            self.tick_timers();
+        }
+        Ok(())
+    }
+
+    /// [`step`](Self::step) without the report: the instruction it ran, or
+    /// `None` for an opcode [`decode`] rejects.
+    fn fetch_and_execute(&mut self, pc: u16) -> Result<Option<Instruction>, Chip8Error> {
+        #[cfg(feature = "alloc")]
+        if let Some(instruction) = self.decode_cache.as_ref().and_then(|cache| cache.get(pc)) {
+            trace!(target: LOG_TARGET, "{:03X}: {:?} (cached)", pc, instruction);
+            self.pc = self.pc.wrapping_add(2);
+            return self.execute_instruction(instruction).map(|()| Some(instruction));
+        }
+
+        let opcode = self.fetch()?;
+        trace!(target: LOG_TARGET, "{:03X}: {:04X}", pc, opcode);
+        match decode(opcode) {
+            Some(instruction) => {
+                #[cfg(feature = "alloc")]
+                if let Some(cache) = &mut self.decode_cache {
+                    cache.insert(pc, instruction);
+                }
+                self.execute_instruction(instruction).map(|()| Some(instruction))
+            }
+            None => self.reject(opcode, pc).map(|()| None),
+        }
+    }
+
+    /// Executes an opcode that has already been fetched, so `pc` is expected
+    /// to point just past it.
+    pub fn execute(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        match decode(opcode) {
+            Some(instruction) => self.execute_instruction(instruction),
+            None => self.reject(opcode, self.instruction_pc()),
+        }
+    }
+
+    /// Handles an opcode [`decode`] rejects, going by [`Chip8::sys_policy`]
+    /// for 0NNN machine code calls and [`Chip8::opcode_policy`] for the rest.
+    /// HI-RES CHIP-8's 0230 clears the screen with the
+    /// [two-page hires](Quirks::two_page_hires) quirk, and CHIP-8X's own
+    /// opcodes run with the [chip8x](Quirks::chip8x) one, before either.
+    /// One that [identifies](platform::identify) an unsupported extension
+    /// stops the machine whatever the policy, and its error says everything
+    /// about it once.
+    fn reject(&mut self, opcode: u16, pc: u16) -> Result<(), Chip8Error> {
+        if opcode == 0x0230 && self.quirks.two_page_hires {
+            self.display.clear_selected();
+            self.notify(MachineEvent::DisplayCleared);
+            return Ok(());
+        }
+        if self.quirks.chip8x && self.op_chip8x(opcode) {
+            return Ok(());
+        }
+        if opcode & 0xF000 == 0 && platform::identify(opcode).is_none() {
+            match self.sys_policy {
+                SysPolicy::Ignore => {}
+                SysPolicy::WarnOnce => {
+                    if !self.sys_warned {
+                        warn!(target: LOG_TARGET, "Skipping machine code call 0x{:04X} at 0x{:03X}, and any after it", opcode, pc);
+                        self.sys_warned = true;
+                    }
+                    return Ok(());
+                }
+                SysPolicy::Halt => return Err(Chip8Error::MachineCodeCall { addr: opcode & 0x0FFF, pc }),
+            }
+        }
+        if let Some(platform) = platform::identify(opcode) {
+            return Err(Chip8Error::UnsupportedPlatform { platform, opcode, pc });
+        }
+        self.notify(MachineEvent::UnknownOpcode { opcode, pc });
+        match self.opcode_policy {
+            OpcodePolicy::Continue => {
+                warn!(target: LOG_TARGET, "Unknown opcode 0x{:04X} at 0x{:03X}", opcode, pc);
+                Err(Chip8Error::InvalidOpcode { opcode, pc })
+            }
+            OpcodePolicy::WarnOnce => {
+                #[cfg(feature = "alloc")]
+                let first = self.warned_opcodes.insert(opcode);
+                #[cfg(not(feature = "alloc"))]
+                let first = true;
+                if first {
+                    warn!(target: LOG_TARGET, "Skipping unknown opcode 0x{:04X} at 0x{:03X}", opcode, pc);
+                }
+                Ok(())
+            }
+            OpcodePolicy::Halt => Err(Chip8Error::FatalOpcode { opcode, pc }),
+        }
+    }
+
+    /// How far a SUPER-CHIP scroll of `pixels` moves the display, which
+    /// [`half_scroll`](Quirks::half_scroll) halves in 64x32 mode.
+    fn scroll_distance(&self, pixels: u8) -> usize {
+        if self.quirks.half_scroll && self.display.resolution() == Resolution::Low {
+            pixels as usize / 2
+        } else {
+            pixels as usize
+        }
+    }
+
+    /// Address of the instruction being executed: handlers run after `fetch`
+    /// has already moved `pc` past it.
+    fn instruction_pc(&self) -> u16 {
+        self.pc.wrapping_sub(2)
+    }
+
+    pub fn execute_instruction(&mut self, instruction: Instruction) -> Result<(), Chip8Error> {
+        self.vblank_wait = self.quirks.display_wait && matches!(instruction, Instruction::Draw { .. });
+        match instruction {
+            Instruction::ScrollDown(n) => self.display.scroll_down(self.scroll_distance(n)),
+            // XO-CHIP's, which never halved its scrolls.
+            Instruction::ScrollUp(n) => self.display.scroll_up(n as usize),
+            Instruction::ScrollRight => self.display.scroll_right(self.scroll_distance(4)),
+            Instruction::ScrollLeft => self.display.scroll_left(self.scroll_distance(4)),
+            Instruction::ClearScreen => {
+                self.display.clear_selected();
+                self.notify(MachineEvent::DisplayCleared);
+            }
+            Instruction::SelectPlanes(planes) => self.display.select_planes(planes),
+            Instruction::Return => self.op_00ee()?,
+            // Stays on the 00FD, so the machine stops there for good.
+            Instruction::Exit => self.pc = self.instruction_pc(),
+            Instruction::LowRes => self.display.set_resolution(Resolution::Low),
+            Instruction::HighRes => self.display.set_resolution(Resolution::High),
+            Instruction::Jump(0x260) if self.quirks.two_page_hires && self.instruction_pc() == PROGRAM_START => {
+                self.display.set_resolution(Resolution::TwoPage);
+                self.pc = TWO_PAGE_START;
+            }
+            Instruction::Jump(nnn) => self.pc = nnn,
+            Instruction::Call(nnn) => self.op_2nnn(nnn)?,
+            Instruction::SkipIfEq { x, nn } => self.op_3xnn(x, nn),
+            Instruction::SkipIfNe { x, nn } => self.op_4xnn(x, nn),
+            Instruction::SkipIfRegEq { x, y } => self.op_5xy0(x, y),
+            Instruction::StoreRange { x, y } => self.op_5xy2(x, y)?,
+            Instruction::LoadRange { x, y } => self.op_5xy3(x, y)?,
+            Instruction::SetVx { x, nn } => self.v[x] = nn,
+            Instruction::AddVx { x, nn } => self.v[x] = self.v[x].wrapping_add(nn),
+            Instruction::Copy { x, y } => self.op_8xy0(x, y),
+            Instruction::Or { x, y } => self.op_8xy1(x, y),
+            Instruction::And { x, y } => self.op_8xy2(x, y),
+            Instruction::Xor { x, y } => self.op_8xy3(x, y),
+            Instruction::AddReg { x, y } => self.op_8xy4(x, y),
+            Instruction::SubReg { x, y } => self.op_8xy5(x, y),
+            Instruction::ShiftRight { x, y } => self.op_8xy6(x, y),
+            Instruction::SubReverse { x, y } => self.op_8xy7(x, y),
+            Instruction::ShiftLeft { x, y } => self.op_8xye(x, y),
+            Instruction::SkipIfRegNe { x, y } => self.op_9xy0(x, y),
+            Instruction::SetIndex(nnn) => self.i = nnn,
+            Instruction::LongIndex => self.op_f000(),
+            Instruction::JumpOffset(nnn) if self.quirks.chip8x => self.op_bxyn(nnn),
+            Instruction::JumpOffset(nnn) => self.op_bnnn(nnn),
+            Instruction::Random { x, nn } => self.op_cxnn(x, nn),
+            Instruction::Draw { x, y, n } => self.op_dxyn(x, y, n),
+            Instruction::SkipIfKey { x } => self.op_ex9e(x)?,
+            Instruction::SkipIfNotKey { x } => self.op_exa1(x)?,
+            Instruction::GetDelay { x } => self.op_fx07(x),
+            Instruction::WaitKey { x } => self.op_fx0a(x),
+            Instruction::SetDelay { x } => self.op_fx15(x),
+            Instruction::SetSound { x } => self.op_fx18(x),
+            Instruction::LoadAudio => self.op_f002()?,
+            Instruction::SetPitch { x } => self.pitch = self.v[x],
+            Instruction::AddIndex { x } => self.op_fx1e(x),
+            Instruction::FontChar { x } => self.op_fx29(x),
+            Instruction::BigFontChar { x } => self.i = BIG_FONT_BASE + (self.v[x] as u16) * BIG_GLYPH_BYTES,
+            Instruction::Bcd { x } => self.op_fx33(x)?,
+            Instruction::StoreRegs { x } => self.op_fx55(x)?,
+            Instruction::LoadRegs { x } => self.op_fx65(x)?,
+            Instruction::StoreFlags { x } => self.rpl_flags[..=x].copy_from_slice(&self.v[..=x]),
+            Instruction::LoadFlags { x } => self.v[..=x].copy_from_slice(&self.rpl_flags[..=x]),
+        }
+
+        Ok(())
+    }
+
+    /// Reads a byte from memory on behalf of an instruction.
+    fn read_byte(&mut self, addr: usize) -> Result<u8, Chip8Error> {
+        let byte = self.addressable_memory()
+            .get(addr)
+            .copied()
+            .ok_or(Chip8Error::MemoryOutOfBounds {
+                addr,
+                pc: self.instruction_pc(),
+            })?;
+        self.watch(addr, Access::Read, byte, byte);
+        Ok(byte)
+    }
+
+    /// Writes a byte to memory on behalf of an instruction, keeping the
+    /// decode cache coherent and, in [strict mode](Chip8::strict_memory),
+    /// the font intact.
+    fn write_byte(&mut self, addr: usize, value: u8) -> Result<(), Chip8Error> {
+        let pc = self.instruction_pc();
+        if self.strict_memory && addr < PROGRAM_START as usize {
+            return Err(Chip8Error::ProtectedWrite { addr, pc });
+        }
+        let size = self.memory_size();
+        let byte = self.memory[..size]
+            .get_mut(addr)
+            .ok_or(Chip8Error::MemoryOutOfBounds { addr, pc })?;
+        let old = core::mem::replace(byte, value);
+        self.watch(addr, Access::Write, old, value);
+
+        #[cfg(feature = "alloc")]
+        if let Some(cache) = &mut self.decode_cache {
+            cache.invalidate(addr);
+        }
+        Ok(())
+    }
+
+    /// Sprite rows past the top of memory wrap around to 0x000, as I does.
+    ///
+    /// In high resolution DXY0 draws 16x16 from 32 bytes, two a row, and VF
+    /// counts the rows that hit a lit pixel or were clipped off the bottom,
+    /// as SUPER-CHIP 1.1 did. Otherwise VF is 1 for any collision.
+    /// Draws on each selected plane in turn, XO-CHIP style: with both
+    /// selected, plane 2's sprite follows plane 1's in memory. VF is set if
+    /// either collides.
+    fn op_dxyn(&mut self, x: usize, y: usize, n: u8) {
+        let hires = self.display.resolution() == Resolution::High;
+        let (rows, row_bytes) = if hires && n == 0 { (16, 2) } else { (n as usize, 1) };
+        let mut sprite = self.i as usize;
+        let mut vf = 0;
+        let (vx, vy) = (self.v[x], self.v[y]);
+        for plane in 0..PLANES {
+            if self.display.is_selected(plane) {
+                vf = vf.max(self.draw_sprite(plane, sprite, vx, vy, rows, row_bytes));
+                sprite += rows * row_bytes;
+            }
+        }
+        self.v[0xF] = vf;
+        self.notify(MachineEvent::SpriteDrawn { x: vx, y: vy, rows: rows as u8, collision: vf != 0 });
+    }
+
+    /// Draws `rows` rows of `row_bytes` bytes from `sprite` at (`vx`, `vy`)
+    /// on `plane`, returning what VF would be for that plane alone.
+    fn draw_sprite(&mut self, plane: usize, sprite: usize, vx: u8, vy: u8, rows: usize, row_bytes: usize) -> u8 {
+        let (width, height) = (self.display.width(), self.display.height());
+        let hires = self.display.resolution() == Resolution::High;
+        let memory_size = self.memory_size();
+        let x_start = (vx as usize) % width;
+        let y_start = (vy as usize) % height;
+
+        let mut hit_rows = 0;
+        for row in 0..rows {
+            let screen_y = y_start + row;
+            if screen_y >= height && !self.quirks.wrap_sprites {
+                if hires {
+                    hit_rows += rows - row;
+                }
+                break;
+            }
+
+            let mut hit = false;
+            for col in 0..8 * row_bytes {
+                let addr = (sprite + row * row_bytes + col / 8) % memory_size;
+                let byte = self.memory[addr];
+                self.watch(addr, Access::Read, byte, byte);
+                let bit = (byte >> (7 - col % 8)) & 1;
+
+                if bit == 1 {
+                    let screen_x = x_start + col;
+                    if screen_x >= width && !self.quirks.wrap_sprites {
+                        continue;
+                    }
+                    let pixel = &mut self.display.plane_mut(plane)[(screen_y % height) * width + screen_x % width];
+                    hit |= *pixel;
+                    *pixel ^= true;
+                }
+            }
+            hit_rows += hit as usize;
+        }
+        if hires { hit_rows as u8 } else { (hit_rows > 0) as u8 }
+    }
+
+    // The 8XY_ instructions write VF after the result, so with X = F the
+    // flag is what's left, and with Y = F the operand is VF as it was.
+
+    fn op_8xye(&mut self, x: usize, y: usize) {
+        let value = if self.quirks.shift_uses_vy { self.v[y] } else { self.v[x] };
+        self.v[x] = value << 1;
+        self.v[0xF] = value >> 7;
+    }
+
+    fn op_8xy0(&mut self, x: usize, y: usize) {
+        self.v[x] = self.v[y];
+    }
+
+    fn op_8xy1(&mut self, x: usize, y: usize) {
+        self.v[x] |= self.v[y];
+        self.reset_vf();
+    }
+
+    fn op_8xy2(&mut self, x: usize, y: usize) {
+        self.v[x] &= self.v[y];
+        self.reset_vf();
+    }
+
+    fn op_8xy3(&mut self, x: usize, y: usize) {
+        self.v[x] ^= self.v[y];
+        self.reset_vf();
+    }
+
+    /// Clears VF after a logic instruction under [`Quirks::vf_reset`].
+    fn reset_vf(&mut self) {
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
+    }
+
+    fn op_8xy4(&mut self, x: usize, y: usize) {
+        let (sum, carry) = self.v[x].overflowing_add(self.v[y]);
+        self.v[x] = sum;
+        self.v[0xF] = carry as u8;
+    }
+
+    fn op_8xy5(&mut self, x: usize, y: usize) {
+        let (difference, borrow) = self.v[x].overflowing_sub(self.v[y]);
+        self.v[x] = difference;
+        self.v[0xF] = !borrow as u8;
+    }
+
+    fn op_8xy6(&mut self, x: usize, y: usize) {
+        let value = if self.quirks.shift_uses_vy { self.v[y] } else { self.v[x] };
+        self.v[x] = value >> 1;
+        self.v[0xF] = value & 1;
+    }
+
+    fn op_8xy7(&mut self, x: usize, y: usize) {
+        let (difference, borrow) = self.v[y].overflowing_sub(self.v[x]);
+        self.v[x] = difference;
+        self.v[0xF] = !borrow as u8;
+    }
+
+    /// Skips the next instruction, all four bytes of it if it's XO-CHIP's
+    /// F000 NNNN.
+    fn skip(&mut self) {
+        let long = self.quirks.extended_memory && self.peek_opcode(self.pc) == 0xF000;
+        self.pc = self.pc.wrapping_add(if long { 4 } else { 2 });
+    }
+
+    fn op_3xnn(&mut self, x: usize, nn: u8) {
+        if self.v[x] == nn {
+            self.skip();
+        }
+    }
+
+    fn op_4xnn(&mut self, x: usize, nn: u8) {
+        if self.v[x] != nn {
+            self.skip();
+        }
+    }
+
+    fn op_5xy0(&mut self, x: usize, y: usize) {
+        if self.v[x] == self.v[y] {
+            self.skip();
+        }
+    }
+
+    /// Saves VX-VY to I on, leaving I where it was, whatever the
+    /// load/store quirk says.
+    fn op_5xy2(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
+        let i = self.block_for(0x5002 | (x as u16) << 8 | (y as u16) << 4, x, x.abs_diff(y) + 1)?;
+        for (offset, register) in register_range(x, y).enumerate() {
+            self.write_byte(i + offset, self.v[register])?;
+        }
+        Ok(())
+    }
+
+    fn op_5xy3(&mut self, x: usize, y: usize) -> Result<(), Chip8Error> {
+        let i = self.block_for(0x5003 | (x as u16) << 8 | (y as u16) << 4, x, x.abs_diff(y) + 1)?;
+        for (offset, register) in register_range(x, y).enumerate() {
+            self.v[register] = self.read_byte(i + offset)?;
+        }
+        Ok(())
+    }
+
+    fn op_9xy0(&mut self, x: usize, y: usize) {
+        if self.v[x] != self.v[y] {
+            self.skip();
+        }
+    }
+
+    /// Loads I from the two bytes after the F000 and steps over them, masked
+    /// to the address space like FX1E so I stays within memory.
+    fn op_f000(&mut self) {
+        self.i = (self.peek_opcode(self.pc) as usize & self.address_mask()) as u16;
+        self.pc = self.pc.wrapping_add(2);
+    }
+
+    fn op_2nnn(&mut self, nnn: u16) -> Result<(), Chip8Error> {
+        if !self.stack.push(self.pc) {
+            return Err(Chip8Error::StackOverflow { depth: STACK_DEPTH, pc: self.instruction_pc(), stack: self.stack });
+        }
+        self.pc = nnn;
+        Ok(())
+    }
+
+    fn op_00ee(&mut self) -> Result<(), Chip8Error> {
+        self.pc = self.stack.pop().ok_or(Chip8Error::StackUnderflow { pc: self.instruction_pc() })?;
+        Ok(())
+    }
+
+    fn op_bnnn(&mut self, nnn: u16) {
+        let offset = if self.quirks.jump_uses_vx { self.v[(nnn >> 8) as usize] } else { self.v[0x0] };
+        self.pc = nnn + offset as u16
+    }
+
+    /// CHIP-8X's BXY0 and BXYN, coloring zones with VY's color. For BXY0,
+    /// VX's low nibble is the first zone across and its high nibble how many
+    /// more to color; V(X+1) says the same going down. BXYN colors the zone
+    /// column VX is in for the N rows of pixels from V(X+1), widened to the
+    /// whole zones they touch.
+    fn op_bxyn(&mut self, xyn: u16) {
+        let (x, y, n) = ((xyn >> 8) as usize, (xyn >> 4 & 0xF) as usize, xyn & 0xF);
+        let (across, down, color) = (self.v[x] as usize, self.v[(x + 1) % 16] as usize, self.v[y]);
+        if n == 0 {
+            self.color_map.fill(across & 0xF, down & 0xF, (across >> 4) + 1, (down >> 4) + 1, color);
+        } else {
+            let (top, bottom) = (down / 4, (down + n as usize - 1) / 4);
+            self.color_map.fill(across / 8 % ZONE_COLUMNS, top % ZONE_ROWS, 1, bottom - top + 1, color);
+        }
+    }
+
+    /// Runs a CHIP-8X opcode [`decode`] rejects, returning whether it was
+    /// one. The second keypad is never pressed and the I/O port neither
+    /// sends nor reads: FXF8's tone goes nowhere and FXFB reads 0.
+    fn op_chip8x(&mut self, opcode: u16) -> bool {
+        let (x, y) = ((opcode >> 8 & 0xF) as usize, (opcode >> 4 & 0xF) as usize);
+        if opcode == 0x02A0 {
+            self.color_map.step_background();
+            return true;
+        }
+        match (opcode & 0xF00F, opcode & 0xF0FF) {
+            // Each nibble adds separately, keeping three bits.
+            (0x5001, _) => self.v[x] = ((self.v[x] & 0x77) + (self.v[y] & 0x77)) & 0x77,
+            (_, 0xE0F2 | 0xF0F8) => {}
+            (_, 0xE0F5) => self.skip(),
+            (_, 0xF0FB) => self.v[x] = 0,
+            _ => return false,
+        }
+        true
+    }
+
+     fn op_cxnn(&mut self, x: usize, nn: u8) {
+         let random_byte = self.rng.next_u8();
+         self.v[x] = random_byte & nn;
+     }
+
+     fn op_fx07(&mut self, x: usize) {
+        self.v[x] = self.delay_timer;
+    }
+
+    fn op_fx15(&mut self, x: usize) {
+        self.delay_timer = self.v[x];
+    }
+
+    fn op_fx18(&mut self, x: usize) {
+        self.sound_timer = self.v[x];
+    }
+
+    fn op_f002(&mut self) -> Result<(), Chip8Error> {
+        let i = self.block(0, 0x02, AUDIO_PATTERN_BYTES)?;
+        let mut pattern = [0; AUDIO_PATTERN_BYTES];
+        pattern.copy_from_slice(&self.memory[i..i + AUDIO_PATTERN_BYTES]);
+        for (offset, &byte) in pattern.iter().enumerate() {
+            self.watch(i + offset, Access::Read, byte, byte);
+        }
+        self.audio_pattern = Some(pattern);
+        Ok(())
+    }
+
+    /// I stays within memory, so later reads and writes through it can't
+    /// run off the end.
+    fn op_fx1e(&mut self, x: usize) {
+        let sum = self.i as usize + self.v[x] as usize;
+        let mask = self.address_mask();
+        self.i = (sum & mask) as u16;
+        if self.quirks.i_overflow_sets_vf {
+            self.v[0xF] = (sum > mask) as u8;
+        }
+    }
+
+    fn op_fx29(&mut self, x: usize) {
+        self.i = self.font_base + (self.v[x] as u16) * GLYPH_BYTES;
+    }
+
+    fn op_fx33(&mut self, x: usize) -> Result<(), Chip8Error> {
+        let i = self.block(x, 0x33, 3)?;
+        self.write_byte(i, self.v[x] / 100)?;
+        self.write_byte(i + 1, (self.v[x] / 10) % 10)?;
+        self.write_byte(i + 2, self.v[x] % 10)
+    }
+
+    fn op_fx55(&mut self, x: usize) -> Result<(), Chip8Error> {
+        let i = self.block(x, 0x55, x + 1)?;
+        for misc in 0..=x {
+            self.write_byte(i + misc, self.v[misc])?;
+        }
+        self.advance_i_past(x);
+        Ok(())
+    }
+
+    fn op_fx65(&mut self, x: usize) -> Result<(), Chip8Error> {
+        let i = self.block(x, 0x65, x + 1)?;
+        for misc in 0..=x {
+            self.v[misc] = self.read_byte(i + misc)?;
+        }
+        self.advance_i_past(x);
+        Ok(())
+    }
+
+    /// I, once it's checked that the `len` bytes FX`low` copies from there
+    /// are all in memory, so the instruction stops before copying any of
+    /// them rather than partway through.
+    fn block(&self, x: usize, low: u16, len: usize) -> Result<usize, Chip8Error> {
+        self.block_for(0xF000 | (x as u16) << 8 | low, x, len)
+    }
+
+    /// [`block`](Self::block) for any `opcode` that copies `len` bytes at I.
+    fn block_for(&self, opcode: u16, x: usize, len: usize) -> Result<usize, Chip8Error> {
+        let i = self.i as usize;
+        if i + len > self.memory_size() {
+            return Err(Chip8Error::BlockOutOfBounds {
+                opcode,
+                i: self.i,
+                x: x as u8,
+                pc: self.instruction_pc(),
+            });
+        }
+        Ok(i)
+    }
+
+    /// With the load/store quirk, moves I past the X + 1 bytes FX55/FX65
+    /// just copied, wrapping within memory like FX1E.
+    fn advance_i_past(&mut self, x: usize) {
+        let past = if self.quirks.load_store_increments_i_by_x {
+            x
+        } else if self.quirks.load_store_increments_i {
+            x + 1
+        } else {
+            return;
+        };
+        self.i = ((self.i as usize + past) & self.address_mask()) as u16;
+    }
+
+    /// Looks up the keypad state for the key number held in VX.
+    fn key_down(&self, x: usize) -> Result<bool, Chip8Error> {
+        let key = self.v[x];
+        self.keys
+            .get(key as usize)
+            .copied()
+            .ok_or(Chip8Error::InvalidKey {
+                key,
+                pc: self.instruction_pc(),
+            })
+    }
+
+    fn op_ex9e(&mut self, x: usize) -> Result<(), Chip8Error> {
+        if self.key_down(x)? {
+            self.skip();
+        }
+        Ok(())
+    }
+
+    fn op_exa1(&mut self, x: usize) -> Result<(), Chip8Error> {
+        if !self.key_down(x)? {
+            self.skip();
+        }
+        Ok(())
+    }
+
+    /// Waits for a key to be pressed and then released, as the COSMAC VIP
+    /// did, so a key held down satisfies only one FX0A. The machine stays on
+    /// the FX0A while it waits, and [`step`](Self::step) polls the keys
+    /// rather than fetching it again; the timers keep running meanwhile.
+    fn op_fx0a(&mut self, x: usize) {
+        let wait = KeyWait { at: self.instruction_pc(), x, pressed: None };
+        self.pc = wait.at;
+        self.poll_key_wait(wait);
+    }
+
+    /// Carries on `wait`, moving past its FX0A once the key it saw go down
+    /// comes back up.
+    fn poll_key_wait(&mut self, wait: KeyWait) {
+        self.key_wait = match wait.pressed {
+            Some(key) if !self.keys[key as usize] => {
+                self.v[wait.x] = key;
+                self.pc = wait.at.wrapping_add(2);
+                None
+            }
+            Some(_) => Some(wait),
+            None => Some(KeyWait { pressed: self.keys.iter().position(|&down| down).map(|key| key as u8), ..wait }),
+        };
+    }
+
+    /// Whether the machine is in an FX0A, waiting for a key to be pressed
+    /// and released.
+    pub fn waiting_for_key(&self) -> bool {
+        self.key_wait.is_some()
+    }
+
+    /// The display as 0RGB in the default [`Palette`]'s colors, white on
+    /// black for plane 1, [`Display::width`] pixels a row.
+    #[cfg(feature = "alloc")]
+    pub fn get_display_buffer(&self) -> Vec<u32> {
+        self.display_buffer(Palette::default())
+    }
+
+    /// The display as 0RGB, each pixel in `palette`'s color for the planes
+    /// it's lit in. Under the [chip8x](Quirks::chip8x) quirk the colors are
+    /// the [`color_map`](Self::color_map)'s instead.
+    #[cfg(feature = "alloc")]
+    pub fn display_buffer(&self, palette: Palette) -> Vec<u32> {
+        self.pixel_colors(palette).map(|(_, rgb)| rgb).collect()
+    }
+}
+
+/// VX to VY inclusive, in that order, so down when X > Y.
+fn register_range(x: usize, y: usize) -> impl Iterator<Item = usize> {
+    (0..=x.abs_diff(y)).map(move |offset| if x <= y { x + offset } else { x - offset })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hex_font::FONTSET;
+
+    #[cfg(feature = "alloc")]
+    fn run(chip8: &mut Chip8, steps: usize) {
+        for _ in 0..steps {
+            chip8.step().unwrap();
+        }
+    }
+
+    // 200: 6A01  VA = 0x01        <- rewritten to 7A05 (VA += 5)
+    // 202: 607A  V0 = 0x7A
+    // 204: 6105  V1 = 0x05
+    // 206: A200  I = 0x200
+    // 208: F155  store V0..V1 at I
+    // 20A: 1200  jump 0x200
+    #[cfg(feature = "alloc")]
+    const REWRITE_EARLIER: [u8; 12] = [
+        0x6A, 0x01, 0x60, 0x7A, 0x61, 0x05, 0xA2, 0x00, 0xF1, 0x55, 0x12, 0x00,
+    ];
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn cached_instruction_is_invalidated_when_rewritten() {
+        for enabled in [false, true] {
+            let mut chip8 = Chip8::with_rng(Rng::new(1));
+            chip8.set_decode_cache(enabled);
+            chip8.load_rom_bytes(&REWRITE_EARLIER).unwrap();
+
+            run(&mut chip8, 7);
+
+            assert_eq!(chip8.v[0xA], 6, "cache enabled: {enabled}");
+            assert_eq!(chip8.pc, 0x202);
+        }
+    }
+
+    // 200: A206  I = 0x206
+    // 202: 60A3  V0 = 0xA3
+    // 204: F055  store V0 at 0x206, turning the next instruction into A3xx
+    // 206: 6B01  VB = 0x01        <- becomes A301 (I = 0x301)
+    // 208: 1200  jump 0x200
+    #[cfg(feature = "alloc")]
+    const REWRITE_NEXT: [u8; 10] = [0xA2, 0x06, 0x60, 0xA3, 0xF0, 0x55, 0x6B, 0x01, 0x12, 0x00];
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn rewriting_the_next_instruction_takes_effect_immediately() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.set_decode_cache(true);
+        chip8.load_rom_bytes(&REWRITE_NEXT).unwrap();
+
+        // Warm the cache with the original instruction at 0x206 first.
+        chip8.pc = 0x206;
+        chip8.step().unwrap();
+        assert_eq!(chip8.v[0xB], 1);
+        chip8.v[0xB] = 0;
+        chip8.pc = 0x200;
+
+        run(&mut chip8, 4);
+
+        assert_eq!(chip8.v[0xB], 0);
+        assert_eq!(chip8.i, 0x301);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn write_to_low_byte_invalidates_instruction() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.set_decode_cache(true);
+        // 200: 6A01, then rewrite only the immediate at 0x201 to 0x09.
+        chip8.load_rom_bytes(&[0x6A, 0x01, 0x60, 0x09, 0xA2, 0x01, 0xF0, 0x55, 0x12, 0x00]).unwrap();
+
+        run(&mut chip8, 6);
+
+        assert_eq!(chip8.v[0xA], 9);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn loading_a_rom_clears_the_cache() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.set_decode_cache(true);
+        chip8.load_rom_bytes(&[0x6A, 0x01]).unwrap();
+        chip8.step().unwrap();
+
+        chip8.load_rom_bytes(&[0x6A, 0x02]).unwrap();
+        chip8.pc = PROGRAM_START;
+        chip8.step().unwrap();
+
+        assert_eq!(chip8.v[0xA], 2);
+    }
+
+    /// Runs `opcode` with V`x` and V`y` set to `vx` and `vy`, returning V`x`
+    /// and VF after.
+    fn arithmetic(opcode: u16, vx: u8, vy: u8) -> (u8, u8) {
+        let (x, y) = ((opcode >> 8 & 0xF) as usize, (opcode >> 4 & 0xF) as usize);
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.load_rom_bytes(&opcode.to_be_bytes()).unwrap();
+        chip8.v[x] = vx;
+        chip8.v[y] = vy;
+        chip8.step().unwrap();
+        (chip8.v[x], chip8.v[0xF])
+    }
+
+    #[test]
+    fn steps_report_draws_the_buzzer_and_key_waits() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        // 200: D015  draw 0 at (0, 0)   202: 6305  V3 = 5   204: F318  ST = V3
+        // 206: F00A  wait for a key
+        chip8.load_rom_bytes(&[0xD0, 0x15, 0x63, 0x05, 0xF3, 0x18, 0xF0, 0x0A]).unwrap();
+
+        let draw = chip8.step().unwrap();
+        assert_eq!((draw.pc, draw.opcode), (0x200, 0xD015));
+        assert!(draw.display_changed && !draw.sound_changed && !draw.waiting_for_key);
+        assert!(!chip8.step().unwrap().display_changed);
+
+        let sound = chip8.step().unwrap();
+        assert_eq!((sound.pc, sound.opcode), (0x204, 0xF318));
+        assert!(sound.sound_changed && !sound.display_changed);
+
+        for _ in 0..2 {
+            let wait = chip8.step().unwrap();
+            assert_eq!((wait.pc, wait.opcode), (0x206, 0xF00A));
+            assert!(wait.waiting_for_key && !wait.sound_changed);
+            assert_eq!(wait.finished, None);
+        }
+    }
+
+    #[test]
+    fn emulated_frames_tick_the_timers_and_end_at_a_vblank_wait() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.quirks.display_wait = true;
+        // 200: 7001  V0 += 1   202: D015  draw   204: 1200  loop
+        chip8.load_rom_bytes(&[0x70, 0x01, 0xD0, 0x15, 0x12, 0x00]).unwrap();
+        chip8.delay_timer = 10;
+
+        chip8.emulate_frames(3, 100).unwrap();
+        assert_eq!((chip8.v[0], chip8.delay_timer), (3, 7));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn watchpoints_catch_the_bcd_digits_and_the_reads_of_them() {
+        use crate::watch::{Access, Watchpoint};
+
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.add_watchpoint(Watchpoint::parse("0x6F0", Access::Write).unwrap());
+        chip8.add_watchpoint(Watchpoint::parse("0x6F1..0x6F3", Access::Read).unwrap());
+        // 200: A6F0  I = 0x6F0   202: 66FE  V6 = 254   204: F633  BCD of V6
+        // 206: F265  load V0-V2   208: A6F2  I = 0x6F2   20A: D011  draw
+        chip8.load_rom_bytes(&[0xA6, 0xF0, 0x66, 0xFE, 0xF6, 0x33, 0xF2, 0x65, 0xA6, 0xF2, 0xD0, 0x11]).unwrap();
+        chip8.memory[0x6F0] = 9;
+
+        let hits: Vec<_> = (0..6).map(|_| chip8.step().map(|_| chip8.watch_hit()).unwrap()).collect();
+        let wrote = WatchHit { pc: 0x204, opcode: 0xF633, addr: 0x6F0, access: Access::Write, old: 9, new: 2 };
+        let loaded = WatchHit { pc: 0x206, opcode: 0xF265, addr: 0x6F1, access: Access::Read, old: 5, new: 5 };
+        let drew = WatchHit { pc: 0x20A, opcode: 0xD011, addr: 0x6F2, access: Access::Read, old: 4, new: 4 };
+        assert_eq!(hits, [None, None, Some(wrote), Some(loaded), None, Some(drew)]);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn observers_hear_what_a_program_does_in_order() {
+        use std::sync::{Arc, Mutex};
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        let heard = Arc::clone(&events);
+        chip8.set_observer(move |event| heard.lock().unwrap().push(event));
+        // 200: 00E0  clear   202: A210  I = 0x210   204: D011  draw
+        // 206: D011  draw over it   208: 8008  unknown   20A: 6002  V0 = 2
+        // 20C: F018  ST = V0   20E: 120E  jump to self   210: 80  sprite
+        chip8
+            .load_rom_bytes(&[0x00, 0xE0, 0xA2, 0x10, 0xD0, 0x11, 0xD0, 0x11, 0x80, 0x08, 0x60, 0x02, 0xF0, 0x18, 0x12, 0x0E, 0x80])
+            .unwrap();
+        for _ in 0..9 {
+            let _ = chip8.step();
+        }
+        for _ in 0..3 {
+            chip8.tick_timers();
+        }
+
+        let sprite = |collision| MachineEvent::SpriteDrawn { x: 0, y: 0, rows: 1, collision };
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                MachineEvent::DisplayCleared,
+                sprite(false),
+                sprite(true),
+                MachineEvent::UnknownOpcode { opcode: 0x8008, pc: 0x208 },
+                MachineEvent::SoundStarted,
+                MachineEvent::Finished { pc: 0x20E },
+                MachineEvent::SoundStopped,
+            ]
+        );
+
+        chip8.clear_observer();
+        chip8.pc = 0x200;
+        chip8.step().unwrap();
+        assert_eq!(events.lock().unwrap().len(), 7);
+    }
+
+    #[test]
+    fn add_sets_the_carry() {
+        assert_eq!(arithmetic(0x8124, 0x10, 0x20), (0x30, 0));
+        assert_eq!(arithmetic(0x8124, 0xFF, 0x01), (0x00, 1));
+        assert_eq!(arithmetic(0x8124, 0xF0, 0xF0), (0xE0, 1));
+        assert_eq!(arithmetic(0x8114, 0x80, 0x80), (0x00, 1));
+    }
+
+    #[test]
+    fn subtracting_wraps_and_clears_vf_on_a_borrow() {
+        assert_eq!(arithmetic(0x8125, 0x30, 0x10), (0x20, 1));
+        assert_eq!(arithmetic(0x8125, 0x10, 0x10), (0x00, 1));
+        assert_eq!(arithmetic(0x8125, 0x10, 0x30), (0xE0, 0));
+        // 8XY7 is VY - VX, which used to overflow rather than wrap.
+        assert_eq!(arithmetic(0x8127, 0x10, 0x30), (0x20, 1));
+        assert_eq!(arithmetic(0x8127, 0x10, 0x10), (0x00, 1));
+        assert_eq!(arithmetic(0x8127, 0x30, 0x10), (0xE0, 0));
+        assert_eq!(arithmetic(0x8127, 0xFF, 0x00), (0x01, 0));
+    }
+
+    #[test]
+    fn with_vf_as_vx_the_flag_wins() {
+        // 8F14: VF = 0xFF + 0x01 is 0x00, then the carry.
+        assert_eq!(arithmetic(0x8F14, 0xFF, 0x01), (1, 1));
+        assert_eq!(arithmetic(0x8F14, 0x01, 0x01), (0, 0));
+        assert_eq!(arithmetic(0x8F15, 0x30, 0x10), (1, 1));
+        assert_eq!(arithmetic(0x8F15, 0x10, 0x30), (0, 0));
+        assert_eq!(arithmetic(0x8F17, 0x10, 0x30), (1, 1));
+        assert_eq!(arithmetic(0x8F17, 0x30, 0x10), (0, 0));
+        // The shifts, with VY = VX so the quirk doesn't matter.
+        assert_eq!(arithmetic(0x8F16, 0x02, 0x02), (0, 0));
+        assert_eq!(arithmetic(0x8F1E, 0xC0, 0xC0), (1, 1));
+    }
+
+    #[test]
+    fn with_vf_as_vy_the_operand_is_read_before_the_flag() {
+        // 81F5: V1 = 0x05 - VF(0x01) = 0x04, no borrow.
+        assert_eq!(arithmetic(0x81F5, 0x05, 0x01), (0x04, 1));
+        // 81F4: V1 = 0x01 + VF(0x02) = 0x03, no carry.
+        assert_eq!(arithmetic(0x81F4, 0x01, 0x02), (0x03, 0));
+        // 81F7: V1 = VF(0x00) - 0x01, which borrows.
+        assert_eq!(arithmetic(0x81F7, 0x01, 0x00), (0xFF, 0));
+    }
+
+    /// Runs the shift `opcode` on V1 = `vx` and V2 = `vy`, with or without
+    /// the COSMAC VIP's copy of VY, returning V1 and VF after.
+    fn shift(opcode: u16, vx: u8, vy: u8, uses_vy: bool) -> (u8, u8) {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.quirks.shift_uses_vy = uses_vy;
+        chip8.load_rom_bytes(&opcode.to_be_bytes()).unwrap();
+        chip8.v[1] = vx;
+        chip8.v[2] = vy;
+        chip8.step().unwrap();
+        (chip8.v[1], chip8.v[0xF])
+    }
+
+    #[test]
+    fn shifts_work_on_vx_in_place_by_default() {
+        assert_eq!(shift(0x8126, 0x05, 0x80, false), (0x02, 1));
+        assert_eq!(shift(0x8126, 0x04, 0x01, false), (0x02, 0));
+        assert_eq!(shift(0x812E, 0x81, 0x01, false), (0x02, 1));
+        assert_eq!(shift(0x812E, 0x01, 0x80, false), (0x02, 0));
+    }
+
+    #[test]
+    fn shifts_copy_vy_with_the_quirk() {
+        assert_eq!(shift(0x8126, 0x80, 0x05, true), (0x02, 1));
+        assert_eq!(shift(0x8126, 0x01, 0x04, true), (0x02, 0));
+        assert_eq!(shift(0x812E, 0x01, 0x81, true), (0x02, 1));
+        assert_eq!(shift(0x812E, 0x80, 0x01, true), (0x02, 0));
+    }
+
+    /// Runs the logic `opcode` with V`x` = `vx`, V`y` = `vy` and VF = 0x55
+    /// unless X or Y is F, returning V`x` and VF after.
+    fn logic(opcode: u16, vx: u8, vy: u8, vf_reset: bool) -> (u8, u8) {
+        let (x, y) = ((opcode >> 8 & 0xF) as usize, (opcode >> 4 & 0xF) as usize);
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.quirks.vf_reset = vf_reset;
+        chip8.load_rom_bytes(&opcode.to_be_bytes()).unwrap();
+        chip8.v[0xF] = 0x55;
+        chip8.v[x] = vx;
+        chip8.v[y] = vy;
+        chip8.step().unwrap();
+        (chip8.v[x], chip8.v[0xF])
+    }
+
+    #[test]
+    fn logic_leaves_vf_alone_by_default() {
+        assert_eq!(logic(0x8121, 0x0C, 0x0A, false), (0x0E, 0x55));
+        assert_eq!(logic(0x8122, 0x0C, 0x0A, false), (0x08, 0x55));
+        assert_eq!(logic(0x8123, 0x0C, 0x0A, false), (0x06, 0x55));
+        assert_eq!(logic(0x8F21, 0x0C, 0x0A, false), (0x0E, 0x0E));
+        assert_eq!(logic(0x81F3, 0x0C, 0x0A, false), (0x06, 0x0A));
+    }
+
+    #[test]
+    fn logic_clears_vf_with_the_quirk() {
+        assert_eq!(logic(0x8121, 0x0C, 0x0A, true), (0x0E, 0));
+        assert_eq!(logic(0x8122, 0x0C, 0x0A, true), (0x08, 0));
+        assert_eq!(logic(0x8123, 0x0C, 0x0A, true), (0x06, 0));
+        // With X = F the reset wins; with Y = F the old VF is the operand.
+        assert_eq!(logic(0x8F21, 0x0C, 0x0A, true), (0, 0));
+        assert_eq!(logic(0x8F22, 0x0C, 0x0A, true), (0, 0));
+        assert_eq!(logic(0x8F23, 0x0C, 0x0A, true), (0, 0));
+        assert_eq!(logic(0x81F1, 0x0C, 0x0A, true), (0x0E, 0));
+        assert_eq!(logic(0x81F2, 0x0C, 0x0A, true), (0x08, 0));
+        assert_eq!(logic(0x81F3, 0x0C, 0x0A, true), (0x06, 0));
+    }
+
+    fn add_to_i(i: u16, vx: u8, quirk: bool) -> (u16, u8) {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.quirks.i_overflow_sets_vf = quirk;
+        chip8.load_rom_bytes(&[0xF3, 0x1E]).unwrap();
+        chip8.i = i;
+        chip8.v[3] = vx;
+        chip8.v[0xF] = 0x55;
+        chip8.step().unwrap();
+        (chip8.i, chip8.v[0xF])
+    }
+
+    #[test]
+    fn fx1e_keeps_i_within_memory() {
+        assert_eq!(add_to_i(0x300, 0x10, false), (0x310, 0x55));
+        assert_eq!(add_to_i(0xFFF, 0x00, false), (0xFFF, 0x55));
+        assert_eq!(add_to_i(0xFFF, 0x01, false), (0x000, 0x55));
+        assert_eq!(add_to_i(0xFF0, 0xFF, false), (0x0EF, 0x55));
+    }
+
+    #[test]
+    fn fx1e_flags_the_overflow_with_the_quirk() {
+        assert_eq!(add_to_i(0x300, 0x10, true), (0x310, 0));
+        assert_eq!(add_to_i(0xFFE, 0x01, true), (0xFFF, 0));
+        assert_eq!(add_to_i(0xFFF, 0x01, true), (0x000, 1));
+        assert_eq!(add_to_i(0xFF0, 0xFF, true), (0x0EF, 1));
+    }
+
+    /// Runs FX55 or FX65 (`low` 0x55 or 0x65) for V0..=V3 from I = 0x300,
+    /// returning I after.
+    fn load_store(low: u8, quirk: bool) -> u16 {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.quirks.load_store_increments_i = quirk;
+        chip8.load_rom_bytes(&[0xF3, low]).unwrap();
+        chip8.i = 0x300;
+        chip8.step().unwrap();
+        chip8.i
+    }
+
+    #[test]
+    fn fx75_and_fx85_keep_registers_in_the_rpl_flags_not_memory() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        // 200: F275  V0..=V2 to the flags   202: FF85  all of them back
+        chip8.load_rom_bytes(&[0xF2, 0x75, 0xFF, 0x85]).unwrap();
+        chip8.i = 0x300;
+        chip8.v = [9; 16];
+        chip8.v[..3].copy_from_slice(&[1, 2, 3]);
+        chip8.rpl_flags[15] = 0x42;
+        chip8.step().unwrap();
+        assert_eq!(chip8.rpl_flags[..4], [1, 2, 3, 0]);
+        assert_eq!((chip8.i, chip8.memory[0x300]), (0x300, 0));
+
+        chip8.v = [0; 16];
+        chip8.step().unwrap();
+        assert_eq!(chip8.v[..4], [1, 2, 3, 0]);
+        assert_eq!(chip8.v[15], 0x42);
+    }
+
+    #[test]
+    fn load_and_store_leave_i_alone_by_default() {
+        assert_eq!(load_store(0x55, false), 0x300);
+        assert_eq!(load_store(0x65, false), 0x300);
+    }
+
+    #[test]
+    fn chip48_leaves_i_on_the_last_register() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.quirks = Quirks::chip48();
+        // 200: F355  store V0..=V3   202: F265  load V0..=V2
+        chip8.load_rom_bytes(&[0xF3, 0x55, 0xF2, 0x65]).unwrap();
+        chip8.i = 0x300;
+        chip8.step().unwrap();
+        assert_eq!(chip8.i, 0x303);
+        chip8.step().unwrap();
+        assert_eq!(chip8.i, 0x305);
+
+        chip8.quirks.load_store_increments_i = true;
+        chip8.pc = 0x200;
+        chip8.step().unwrap();
+        assert_eq!(chip8.i, 0x308, "by X wins");
+    }
+
+    #[test]
+    fn load_and_store_move_i_past_the_registers_with_the_quirk() {
+        assert_eq!(load_store(0x55, true), 0x304);
+        assert_eq!(load_store(0x65, true), 0x304);
+
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.quirks.load_store_increments_i = true;
+        // 200: F155  store V0..=V1   202: F165  load them back from after
+        chip8.load_rom_bytes(&[0xF1, 0x55, 0xF1, 0x65]).unwrap();
+        chip8.i = 0x300;
+        chip8.v[0] = 7;
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.i, 0x304);
+        assert_eq!(chip8.v[0], 0);
+    }
+
+    /// Draws the two-row sprite FF, 81 at (`x`, `y`) over a screen with only
+    /// (0, 0) lit, returning the machine after.
+    fn draw_at_edge(x: u8, y: u8, wrap: bool) -> Chip8 {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.quirks.wrap_sprites = wrap;
+        // 200: D012  draw 2 rows at (V0, V1) from I   202: FF 81  the sprite
+        chip8.load_rom_bytes(&[0xD0, 0x12, 0xFF, 0x81]).unwrap();
+        chip8.i = 0x202;
+        chip8.v[0] = x;
+        chip8.v[1] = y;
+        chip8.display[0][0] = true;
+        chip8.step().unwrap();
+        chip8
+    }
+
+    fn lit(chip8: &Chip8) -> Vec<(usize, usize)> {
+        let mut lit = Vec::new();
+        for (y, row) in chip8.display.rows().enumerate() {
+            for (x, &on) in row.iter().enumerate() {
+                if on {
+                    lit.push((x, y));
+                }
+            }
+        }
+        lit
+    }
+
+    #[test]
+    fn sprites_clip_at_the_right_and_bottom_edges() {
+        let chip8 = draw_at_edge(60, 5, false);
+        assert_eq!(lit(&chip8), [(0, 0), (60, 5), (61, 5), (62, 5), (63, 5), (60, 6)]);
+        assert_eq!(chip8.v[0xF], 0);
+
+        // The second row would wrap onto (0, 0), but it's clipped, so it
+        // neither erases it nor collides.
+        let chip8 = draw_at_edge(0, 31, false);
+        assert_eq!(lit(&chip8), [(0, 0), (0, 31), (1, 31), (2, 31), (3, 31), (4, 31), (5, 31), (6, 31), (7, 31)]);
+        assert_eq!(chip8.v[0xF], 0);
+
+        // Rows on screen still collide.
+        assert_eq!(draw_at_edge(0, 0, false).v[0xF], 1);
+    }
+
+    #[test]
+    fn the_start_wraps_even_when_clipping() {
+        let chip8 = draw_at_edge(64 + 60, 32 + 5, false);
+        assert_eq!(lit(&chip8), lit(&draw_at_edge(60, 5, false)));
+    }
+
+    #[test]
+    fn switching_resolution_clears_the_screen() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        // 200: 00FF  128x64   202: 00E0  clear   204: 00FE  64x32
+        chip8.load_rom_bytes(&[0x00, 0xFF, 0x00, 0xE0, 0x00, 0xFE]).unwrap();
+        chip8.display[0][0] = true;
+        chip8.step().unwrap();
+        assert_eq!((chip8.display.resolution(), chip8.display.lit()), (Resolution::High, 0));
+
+        chip8.display[63][127] = true;
+        chip8.step().unwrap();
+        assert_eq!((chip8.display.resolution(), chip8.display.lit()), (Resolution::High, 0));
+
+        chip8.display[0][0] = true;
+        chip8.step().unwrap();
+        assert_eq!((chip8.display.width(), chip8.display.lit()), (64, 0));
+    }
+
+    #[test]
+    fn scrolls_move_whole_pixels_at_either_resolution() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        // 200: 00C3  down 3   202: 00FB  right 4   204: 00FC  left 4
+        // 206: 00FF  128x64   208: 00C2  down 2    20A: 00FC  left 4
+        chip8
+            .load_rom_bytes(&[0x00, 0xC3, 0x00, 0xFB, 0x00, 0xFC, 0x00, 0xFF, 0x00, 0xC2, 0x00, 0xFC])
+            .unwrap();
+        chip8.display[0][0] = true;
+        chip8.display[29][60] = true;
+        chip8.step().unwrap();
+        assert_eq!(lit(&chip8), [(0, 3)]);
+
+        chip8.step().unwrap();
+        assert_eq!(lit(&chip8), [(4, 3)]);
+        assert!((0..32).all(|y| chip8.display[y][..4].iter().all(|&lit| !lit)));
+
+        chip8.display[10][2] = true;
+        chip8.step().unwrap();
+        assert_eq!(lit(&chip8), [(0, 3)]);
+        assert!((0..32).all(|y| chip8.display[y][60..].iter().all(|&lit| !lit)));
+
+        chip8.step().unwrap();
+        chip8.display[0][127] = true;
+        chip8.step().unwrap();
+        assert_eq!(lit(&chip8), [(127, 2)]);
+        chip8.step().unwrap();
+        assert_eq!(lit(&chip8), [(123, 2)]);
+    }
+
+    /// 200: 6012  V0 = 0x12   202: 6131  V1 = 0x31   204: 6205  V2 = 5
+    /// 206: B020  zones 2-3 across, 1-4 down, in V2's color
+    /// 208: 02A0  next background
+    /// 20A: 6328  V3 = 40     20C: 6409  V4 = 9      20E: 6603  V6 = 3
+    /// 210: B363  zone column 5, pixel rows 9-11, in V6's color
+    /// 212: 6777  V7 = 0x77   214: 6835  V8 = 0x35   216: 5781  V7 += V8 by nibbles
+    /// 218: E0F5  skips, the second keypad's never down   21C: E0F2  doesn't
+    const CHIP8X_ROM: [u8; 30] = [
+        0x60, 0x12, 0x61, 0x31, 0x62, 0x05, 0xB0, 0x20, 0x02, 0xA0, 0x63, 0x28, 0x64, 0x09, 0x66, 0x03, 0xB3, 0x63,
+        0x67, 0x77, 0x68, 0x35, 0x57, 0x81, 0xE0, 0xF5, 0x00, 0x00, 0xE0, 0xF2,
+    ];
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn chip8x_colors_zones_and_runs_its_other_opcodes() {
+        use crate::color_map::{BACKGROUNDS, FOREGROUNDS};
+
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.quirks = Quirks::chip8x();
+        chip8.load_rom_bytes(&CHIP8X_ROM).unwrap();
+        assert_eq!(chip8.platform(), None);
+        run(&mut chip8, 4);
+        let colored: Vec<_> = (0..64).filter(|&zone| chip8.color_map.zones()[zone] == 5).collect();
+        assert_eq!(colored, [10, 11, 18, 19, 26, 27, 34, 35]);
+
+        run(&mut chip8, 5);
+        assert_eq!(chip8.color_map.background(), 1);
+        assert_eq!(chip8.color_map.foreground(5, 2), 3);
+        assert_eq!(chip8.color_map.zones().iter().filter(|&&zone| zone == 3).count(), 1);
+
+        run(&mut chip8, 3);
+        assert_eq!(chip8.v[7], 0x24);
+        run(&mut chip8, 2);
+        assert_eq!(chip8.pc, 0x21E);
+
+        chip8.display[4][16] = true;
+        let buffer = chip8.get_display_buffer();
+        assert_eq!((buffer[4 * 64 + 16], buffer[0]), (FOREGROUNDS[5], BACKGROUNDS[1]));
+    }
+
+    #[test]
+    fn without_the_quirk_chip8x_roms_are_turned_away() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.quirks = Quirks::chip8();
+        chip8.load_rom_bytes(&CHIP8X_ROM).unwrap();
+        assert_eq!(chip8.platform().map(|detection| detection.platform), Some(Platform::Chip8X));
+        for _ in 0..4 {
+            chip8.step().unwrap();
+        }
+        assert_eq!((chip8.pc, chip8.color_map), (0x20 + 0x12, ColorMap::new()));
+    }
+
+    #[test]
+    fn keys_past_0xf_are_refused() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        assert!(chip8.set_key(0xF, true));
+        assert!(chip8.keys[0xF]);
+        assert!(!chip8.set_key(0x10, true));
+        assert!(chip8.set_key(0xF, false));
+        assert_eq!(chip8.keys, [false; 16]);
+    }
+
+    #[test]
+    fn hires_chip8_roms_switch_to_64x64_and_skip_their_patch() {
+        // 200: 1260  the patched interpreter   2C0: 0230  clear   2C2: 1260
+        let mut rom = [0; 0xC4];
+        rom[..2].copy_from_slice(&[0x12, 0x60]);
+        rom[0xC0..].copy_from_slice(&[0x02, 0x30, 0x12, 0x60]);
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.quirks = Quirks::hires_chip8();
+        chip8.sys_policy = SysPolicy::Halt;
+        chip8.load_rom_bytes(&rom).unwrap();
+        chip8.step().unwrap();
+        assert_eq!((chip8.display.resolution(), chip8.pc), (Resolution::TwoPage, 0x2C0));
+
+        chip8.display[63][0] = true;
+        chip8.step().unwrap();
+        assert_eq!(chip8.display.lit(), 0);
+        chip8.step().unwrap();
+        assert_eq!(chip8.pc, 0x260, "only the first 1260 switches");
+
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.quirks = Quirks::chip8();
+        chip8.load_rom_bytes(&rom).unwrap();
+        chip8.step().unwrap();
+        assert_eq!((chip8.display.resolution(), chip8.pc), (Resolution::Low, 0x260));
+    }
+
+    #[test]
+    fn scrolling_up_moves_only_the_selected_plane() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.quirks.half_scroll = true;
+        // 200: 00FF  128x64   202: F201  plane 2   204: 00D3  up 3
+        chip8.load_rom_bytes(&[0x00, 0xFF, 0xF2, 0x01, 0x00, 0xD3]).unwrap();
+        chip8.step().unwrap();
+        chip8.display.plane_mut(0)[5 * 128] = true;
+        chip8.display.plane_mut(1)[5 * 128 + 1] = true;
+        chip8.display.plane_mut(1)[63 * 128] = true;
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert!(chip8.display.plane(0)[5 * 128]);
+        assert!(chip8.display.plane(1)[2 * 128 + 1] && chip8.display.plane(1)[60 * 128]);
+        assert_eq!(chip8.display.lit(), 3);
+    }
+
+    #[test]
+    fn half_scroll_halves_lores_scrolls_only() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.quirks.half_scroll = true;
+        // 200: 00C3  down 1   202: 00FB  right 2   204: 00FF  128x64
+        // 206: 00C3  down 3   208: 00FB  right 4
+        chip8.load_rom_bytes(&[0x00, 0xC3, 0x00, 0xFB, 0x00, 0xFF, 0x00, 0xC3, 0x00, 0xFB]).unwrap();
+        chip8.display[0][0] = true;
+        chip8.step().unwrap();
+        assert_eq!(lit(&chip8), [(0, 1)]);
+        chip8.step().unwrap();
+        assert_eq!(lit(&chip8), [(2, 1)]);
+
+        chip8.step().unwrap();
+        chip8.display[0][0] = true;
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert_eq!(lit(&chip8), [(4, 3)]);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn planes_draw_separately_and_combine_into_four_colors() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        // 200: A300  I = 300      202: F101  plane 1   204: D011  draw at (V0, V1)
+        // 206: F201  plane 2      208: 6002  V0 = 2    20A: D011  draw
+        // 20C: F301  both planes  20E: A301  I = 301   210: 6000  V0 = 0
+        // 212: D011  draw 301 on plane 1 and 302 on plane 2
+        // 214: F201  plane 2      216: 00E0  clear it
+        chip8
+            .load_rom_bytes(&[
+                0xA3, 0x00, 0xF1, 0x01, 0xD0, 0x11, 0xF2, 0x01, 0x60, 0x02, 0xD0, 0x11, 0xF3, 0x01, 0xA3, 0x01,
+                0x60, 0x00, 0xD0, 0x11, 0xF2, 0x01, 0x00, 0xE0,
+            ])
+            .unwrap();
+        chip8.memory[0x300..0x303].copy_from_slice(&[0xF0, 0x80, 0x00]);
+        run(&mut chip8, 6);
+        assert_eq!(chip8.v[0xF], 0, "plane 2 was empty");
+        run(&mut chip8, 4);
+        assert_eq!(chip8.v[0xF], 1, "plane 1 collided");
+
+        let palette = Palette::default();
+        let (off, on, plane2, both) = (palette.off, palette.on, palette.plane2, palette.both);
+        assert_eq!(chip8.get_display_buffer()[..7], [off, on, both, both, plane2, plane2, off]);
+        let custom = Palette { off: 1, on: 2, plane2: 3, both: 4 };
+        assert_eq!(chip8.display_buffer(custom)[..7], [1, 2, 4, 4, 3, 3, 1]);
+
+        run(&mut chip8, 2);
+        assert!(chip8.display.colors().take(7).eq([0, 1, 1, 1, 0, 0, 0]));
+    }
+
+    #[test]
+    fn dxy0_draws_16x16_in_hires_and_counts_the_rows_that_collide() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        // 200: 00FF  128x64   202: D010  draw 16x16 at (V0, V1)
+        chip8.load_rom_bytes(&[0x00, 0xFF, 0xD0, 0x10]).unwrap();
+        chip8.i = 0x300;
+        // A 16-pixel line on every row, drawn over two dots.
+        for row in 0..16 {
+            chip8.memory[0x300 + 2 * row] = 0xFF;
+            chip8.memory[0x301 + 2 * row] = 0xFF;
+        }
+        (chip8.v[0], chip8.v[1]) = (110, 0);
+        chip8.step().unwrap();
+        chip8.display[3][110] = true;
+        chip8.display[5][125] = true;
+        chip8.step().unwrap();
+
+        assert_eq!(lit(&chip8).len(), 16 * 16 - 2);
+        assert!(chip8.display[15][125] && !chip8.display[5][125] && !chip8.display[16][110]);
+        assert_eq!(chip8.v[0xF], 2);
+
+        // Drawn again it erases itself but for the two dots it had erased:
+        // every row collides.
+        chip8.pc = 0x202;
+        chip8.step().unwrap();
+        assert_eq!(lit(&chip8), [(110, 3), (125, 5)]);
+        assert_eq!(chip8.v[0xF], 16);
+    }
+
+    #[test]
+    fn hires_rows_clipped_off_the_bottom_count_as_collisions() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        // 200: 00FF  128x64   202: D015  draw the font's 0 at (V0, V1)
+        chip8.load_rom_bytes(&[0x00, 0xFF, 0xD0, 0x15]).unwrap();
+        (chip8.v[0], chip8.v[1]) = (4, 61);
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert_eq!(lit(&chip8), [(4, 61), (5, 61), (6, 61), (7, 61), (4, 62), (7, 62), (4, 63), (7, 63)]);
+        assert_eq!(chip8.v[0xF], 2);
+
+        // In low resolution DXY0 still draws nothing, and VF is just a flag.
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.load_rom_bytes(&[0xD0, 0x10, 0xD0, 0x15]).unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.display.lit(), 0);
+        chip8.v[1] = 29;
+        chip8.display[29][0] = true;
+        chip8.step().unwrap();
+        assert_eq!(chip8.v[0xF], 1);
+    }
+
+    #[test]
+    fn sprite_rows_past_the_end_of_memory_wrap_to_the_start() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        // 200: D014  draw 4 rows at (0, 0) from I = 0xFFF
+        chip8.load_rom_bytes(&[0xD0, 0x14]).unwrap();
+        chip8.i = 0xFFF;
+        chip8.memory[0xFFF] = 0x80;
+        chip8.step().unwrap();
+
+        // The last byte of memory, then the font's 0 from 0x000.
+        let rows = [0x80, FONTSET[0], FONTSET[1], FONTSET[2]];
+        for (y, &bits) in rows.iter().enumerate() {
+            for x in 0..8 {
+                assert_eq!(chip8.display[y][x], bits & (0x80 >> x) != 0, "({x}, {y})");
+            }
+        }
+        assert_eq!(chip8.pc, 0x202);
+    }
+
+    #[test]
+    fn sprites_wrap_everywhere_with_the_quirk() {
+        let chip8 = draw_at_edge(60, 5, true);
+        assert_eq!(
+            lit(&chip8),
+            [(0, 0), (0, 5), (1, 5), (2, 5), (3, 5), (60, 5), (61, 5), (62, 5), (63, 5), (3, 6), (60, 6)]
+        );
+        assert_eq!(chip8.v[0xF], 0);
+
+        let chip8 = draw_at_edge(0, 31, true);
+        assert!(!chip8.display[0][0]);
+        assert!(chip8.display[0][7]);
+        assert_eq!(chip8.v[0xF], 1);
+    }
+
+    #[test]
+    fn display_wait_flags_the_draw_until_the_next_instruction() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        // 200: D011  draw   202: 6001  V0 = 1
+        chip8.load_rom_bytes(&[0xD0, 0x11, 0x60, 0x01]).unwrap();
+        chip8.step().unwrap();
+        assert!(!chip8.waiting_for_vblank());
+
+        chip8.pc = 0x200;
+        chip8.quirks.display_wait = true;
+        chip8.step().unwrap();
+        assert!(chip8.waiting_for_vblank());
+        chip8.step().unwrap();
+        assert!(!chip8.waiting_for_vblank());
+    }
+
+    #[test]
+    fn fx0a_completes_when_the_key_is_released() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        // 200: F30A  wait for a key into V3
+        chip8.load_rom_bytes(&[0xF3, 0x0A]).unwrap();
+        let mut held = [false; 16];
+        for keys in [[].as_slice(), &[0x5], &[0x5, 0x7]] {
+            held.fill(false);
+            for &key in keys {
+                held[key] = true;
+            }
+            chip8.keys = held;
+            chip8.step().unwrap();
+            assert_eq!(chip8.pc, 0x200, "keys {keys:?}");
+        }
+        // 5 went down first, and that's the one whose release counts.
+        assert_eq!(chip8.v[3], 0);
+        chip8.keys[0x5] = false;
+        chip8.step().unwrap();
+        assert_eq!((chip8.pc, chip8.v[3]), (0x202, 5));
+    }
+
+    #[test]
+    fn a_held_key_satisfies_one_fx0a() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        // 200: F30A   202: F40A
+        chip8.load_rom_bytes(&[0xF3, 0x0A, 0xF4, 0x0A]).unwrap();
+        chip8.keys[0xA] = true;
+        for _ in 0..3 {
+            chip8.step().unwrap();
+        }
+        assert_eq!(chip8.pc, 0x200);
+        chip8.keys[0xA] = false;
+        chip8.step().unwrap();
+        assert_eq!((chip8.pc, chip8.v[3]), (0x202, 0xA));
+        chip8.step().unwrap();
+        assert_eq!(chip8.pc, 0x202);
+    }
+
+    #[test]
+    fn timers_keep_running_while_a_key_wait_holds_the_machine() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        // 200: F50A  wait for a key   202: 6101  V1 = 1
+        chip8.load_rom_bytes(&[0xF5, 0x0A, 0x61, 0x01]).unwrap();
+        chip8.delay_timer = 30;
+        chip8.sound_timer = 5;
+
+        for _ in 0..10 {
+            chip8.step().unwrap();
+            chip8.tick_timers();
+        }
+        assert!(chip8.waiting_for_key());
+        assert_eq!((chip8.pc, chip8.delay_timer, chip8.sound_timer), (0x200, 20, 0));
+
+        chip8.keys[0xB] = true;
+        chip8.step().unwrap();
+        chip8.keys[0xB] = false;
+        chip8.step().unwrap();
+        assert!(!chip8.waiting_for_key());
+        assert_eq!((chip8.pc, chip8.v[5], chip8.delay_timer), (0x202, 0xB, 20));
+    }
+
+    #[test]
+    fn a_key_wait_at_address_zero_stays_on_it() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.load_rom_bytes(&[0x00, 0xE0]).unwrap();
+        chip8.memory[..2].copy_from_slice(&[0xF2, 0x0A]);
+        chip8.pc = 0;
+
+        chip8.keys[3] = true;
+        chip8.step().unwrap();
+        assert_eq!(chip8.pc, 0);
+        chip8.keys[3] = false;
+        chip8.step().unwrap();
+        assert_eq!((chip8.pc, chip8.v[2]), (2, 3));
+
+        // Moving the PC off an FX0A gives up on it.
+        chip8.pc = 0;
+        chip8.step().unwrap();
+        chip8.pc = 0x200;
+        chip8.step().unwrap();
+        assert!(!chip8.waiting_for_key());
+        assert_eq!(chip8.pc, 0x202);
+    }
+
+    #[test]
+    fn a_key_wait_at_the_top_of_memory_ends_where_the_pc_wraps_to() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.quirks.extended_memory = true;
+        chip8.load_rom_bytes(&[0x00, 0xE0]).unwrap();
+        chip8.memory[0xFFFE..].copy_from_slice(&[0xF2, 0x0A]);
+        chip8.pc = 0xFFFE;
+
+        chip8.keys[3] = true;
+        chip8.step().unwrap();
+        assert_eq!(chip8.pc, 0xFFFE);
+        chip8.keys[3] = false;
+        chip8.step().unwrap();
+        assert_eq!((chip8.pc, chip8.v[2]), (0x0000, 3));
+    }
+
+    /// Runs 200: 0123, 202: 6A01, 204: 0456 under `policy`, returning each
+    /// step's result.
+    fn machine_code_calls(policy: SysPolicy) -> (Chip8, Vec<Result<(), Chip8Error>>) {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.sys_policy = policy;
+        chip8.load_rom_bytes(&[0x01, 0x23, 0x6A, 0x01, 0x04, 0x56]).unwrap();
+        let results = (0..3).map(|_| chip8.step().map(|_| ())).collect();
+        (chip8, results)
+    }
+
+    #[test]
+    fn machine_code_calls_are_skipped_as_unknown_by_default() {
+        let (chip8, results) = machine_code_calls(SysPolicy::default());
+        assert!(matches!(results[0], Err(Chip8Error::InvalidOpcode { opcode: 0x0123, pc: 0x200 })));
+        assert!(results[0].as_ref().unwrap_err().is_recoverable());
+        assert!(matches!(results[2], Err(Chip8Error::InvalidOpcode { opcode: 0x0456, pc: 0x204 })));
+        assert_eq!((chip8.pc, chip8.v[0xA]), (0x206, 1));
+    }
+
+    #[test]
+    fn warn_once_skips_machine_code_calls_quietly() {
+        let (chip8, results) = machine_code_calls(SysPolicy::WarnOnce);
+        assert!(results.iter().all(Result::is_ok), "{results:?}");
+        assert!(chip8.sys_warned);
+        assert_eq!((chip8.pc, chip8.v[0xA]), (0x206, 1));
+    }
+
+    #[test]
+    fn halt_stops_at_the_first_machine_code_call() {
+        let (chip8, results) = machine_code_calls(SysPolicy::Halt);
+        let error = results[0].as_ref().unwrap_err();
+        assert!(matches!(error, Chip8Error::MachineCodeCall { addr: 0x123, pc: 0x200 }), "{error:?}");
+        assert!(!error.is_recoverable());
+        assert_eq!(error.to_string(), "machine code call to 0x123 at 0x200, which can't be emulated");
+        assert_eq!(chip8.v[0xA], 1);
+
+        // Other unknown opcodes are still just skipped.
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.sys_policy = SysPolicy::Halt;
+        chip8.load_rom_bytes(&[0x80, 0x08]).unwrap();
+        assert!(chip8.step().unwrap_err().is_recoverable());
+    }
+
+    /// Runs 200: 8008, 202: 8008, 204: E1FF, 206: 6A01 under `policy`,
+    /// returning each step's result.
+    fn unknown_opcodes(policy: OpcodePolicy) -> (Chip8, Vec<Result<(), Chip8Error>>) {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.opcode_policy = policy;
+        chip8.load_rom_bytes(&[0x80, 0x08, 0x80, 0x08, 0xE1, 0xFF, 0x6A, 0x01]).unwrap();
+        let results = (0..4).map(|_| chip8.step().map(|_| ())).collect();
+        (chip8, results)
+    }
+
+    #[test]
+    fn unknown_opcodes_are_skipped_with_an_error_by_default() {
+        let (chip8, results) = unknown_opcodes(OpcodePolicy::default());
+        assert!(matches!(results[0], Err(Chip8Error::InvalidOpcode { opcode: 0x8008, pc: 0x200 })));
+        assert!(matches!(results[2], Err(Chip8Error::InvalidOpcode { opcode: 0xE1FF, pc: 0x204 })));
+        assert!(results[..3].iter().all(|result| result.as_ref().unwrap_err().is_recoverable()));
+        assert_eq!((chip8.pc, chip8.v[0xA]), (0x208, 1));
+    }
+
+    #[test]
+    fn warn_once_remembers_each_unknown_opcode() {
+        let (chip8, results) = unknown_opcodes(OpcodePolicy::WarnOnce);
+        assert!(results.iter().all(Result::is_ok), "{results:?}");
+        #[cfg(feature = "alloc")]
+        assert_eq!(chip8.warned_opcodes.iter().copied().collect::<Vec<_>>(), [0x8008, 0xE1FF]);
+        assert_eq!((chip8.pc, chip8.v[0xA]), (0x208, 1));
+    }
+
+    #[test]
+    fn halt_stops_at_the_first_unknown_opcode() {
+        let (chip8, results) = unknown_opcodes(OpcodePolicy::Halt);
+        let error = results[0].as_ref().unwrap_err();
+        assert!(matches!(error, Chip8Error::FatalOpcode { opcode: 0x8008, pc: 0x200 }), "{error:?}");
+        assert!(!error.is_recoverable());
+        assert_eq!(chip8.v[0xA], 1);
+
+        // Machine code calls skipped as unknown follow the policy too, and
+        // unsupported extensions still say which one they are.
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.opcode_policy = OpcodePolicy::Halt;
+        chip8.load_rom_bytes(&[0x01, 0x23]).unwrap();
+        assert!(matches!(chip8.step(), Err(Chip8Error::FatalOpcode { opcode: 0x0123, .. })));
+        chip8.load_rom_bytes(&[0x00, 0x11]).unwrap();
+        chip8.pc = PROGRAM_START;
+        assert!(matches!(chip8.step(), Err(Chip8Error::UnsupportedPlatform { .. })));
+    }
+
+    #[test]
+    fn programs_finish_on_jumps_to_themselves() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        // 200: F00A  wait for a key
+        // 202: 1206  jump over 204
+        // 204: 1204  jump to itself
+        // 206: 1204
+        chip8.load_rom_bytes(&[0xF0, 0x0A, 0x12, 0x06, 0x12, 0x04, 0x12, 0x04]).unwrap();
+        chip8.step().unwrap();
+        assert_eq!((chip8.pc, chip8.finished()), (0x200, None));
+        chip8.keys[5] = true;
+        chip8.step().unwrap();
+        chip8.keys[5] = false;
+        chip8.step().unwrap();
+        assert_eq!((chip8.pc, chip8.finished()), (0x202, None));
+        chip8.step().unwrap();
+        assert_eq!(chip8.finished(), None);
+        chip8.step().unwrap();
+        assert_eq!(chip8.finished(), Some(0x204));
+        chip8.step().unwrap();
+        assert_eq!(chip8.finished(), Some(0x204));
+    }
+
+    #[test]
+    fn jumps_above_0xfff_are_not_to_themselves() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.quirks.extended_memory = true;
+        chip8.load_rom_bytes(&[0x00, 0xE0]).unwrap();
+        // 1234: 1234  jump to 234
+        chip8.memory[0x1234..0x1236].copy_from_slice(&[0x12, 0x34]);
+        chip8.pc = 0x1234;
+        assert_eq!(chip8.finished(), None);
+        chip8.step().unwrap();
+        assert_eq!(chip8.pc, 0x234);
+    }
+
+    #[test]
+    fn exit_stops_the_machine_on_the_00fd() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        // 200: 6A01  VA = 1
+        // 202: 00FD  exit
+        // 204: 6A02  never runs
+        chip8.load_rom_bytes(&[0x6A, 0x01, 0x00, 0xFD, 0x6A, 0x02]).unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.finished(), Some(0x202));
+        for _ in 0..3 {
+            chip8.step().unwrap();
+        }
+        assert_eq!((chip8.pc, chip8.v[0xA], chip8.finished()), (0x202, 1, Some(0x202)));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn jumps_past_the_end_of_memory_stop_at_the_fetch() {
+        for decode_cache in [false, true] {
+            let mut chip8 = Chip8::with_rng(Rng::new(1));
+            chip8.set_decode_cache(decode_cache);
+            // 200: 60FF  V0 = 0xFF
+            // 202: BFFF  jump to 0xFFF + V0
+            chip8.load_rom_bytes(&[0x60, 0xFF, 0xBF, 0xFF]).unwrap();
+            chip8.step().unwrap();
+            chip8.step().unwrap();
+            assert!(matches!(chip8.step(), Err(Chip8Error::PcOutOfBounds { pc: 0x10FE })));
+            assert_eq!(chip8.pc, 0x10FE);
+        }
+    }
+
+    #[test]
+    fn bnnn_adds_vx_with_the_jump_quirk() {
+        for (quirk, pc) in [(false, 0x345), (true, 0x350)] {
+            let mut chip8 = Chip8::with_rng(Rng::new(1));
+            chip8.quirks.jump_uses_vx = quirk;
+            // 200: B340  jump to 340 + V0, or 340 + V3
+            chip8.load_rom_bytes(&[0xB3, 0x40]).unwrap();
+            chip8.v[0] = 0x05;
+            chip8.v[3] = 0x10;
+            chip8.step().unwrap();
+            assert_eq!(chip8.pc, pc);
+        }
+    }
+
+    #[test]
+    fn loading_a_known_rom_identifies_it() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.load_rom_bytes(include_bytes!("../roms/games/Vertical Brix [Paul Robson, 1996].ch8")).unwrap();
+        assert_eq!(chip8.known_rom().map(|known| known.profile), Some("chip8"));
+        // The quirks are the caller's to apply.
+        assert_eq!(chip8.quirks, Quirks::default());
+        chip8.load_rom_bytes(&[0x12, 0x00]).unwrap();
+        assert_eq!(chip8.known_rom(), None);
+    }
+
+    /// Runs FX33 or FX55 (`low` 0x33 or 0x55) for V0..=VF = 0xFF with
+    /// I = 0x050, where the font starts.
+    fn write_over_font(low: u8, strict: bool) -> (Chip8, Result<(), Chip8Error>) {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.strict_memory = strict;
+        chip8.load_rom_bytes(&[0xFF, low]).unwrap();
+        chip8.i = 0x050;
+        chip8.v = [0xFF; 16];
+        let result = chip8.step().map(|_| ());
+        (chip8, result)
+    }
+
+    #[test]
+    fn strict_memory_keeps_the_font() {
+        for low in [0x33, 0x55] {
+            let (chip8, result) = write_over_font(low, true);
+            let error = result.unwrap_err();
+            assert!(matches!(error, Chip8Error::ProtectedWrite { addr: 0x050, pc: 0x200 }), "{error:?}");
+            assert!(!error.is_recoverable());
+            assert_eq!(chip8.memory[FONT_BASE as usize..][..FONTSET.len()], FONTSET);
+        }
+
+        // Writes from 0x200 up go through as usual.
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.strict_memory = true;
+        chip8.load_rom_bytes(&[0xF0, 0x55]).unwrap();
+        chip8.i = 0x300;
+        chip8.v[0] = 7;
+        chip8.step().unwrap();
+        assert_eq!(chip8.memory[0x300], 7);
+    }
+
+    #[test]
+    fn permissive_memory_lets_roms_write_over_the_font() {
+        let (chip8, result) = write_over_font(0x33, false);
+        result.unwrap();
+        assert_eq!(chip8.memory[0x050..0x053], [2, 5, 5]);
+        let (chip8, result) = write_over_font(0x55, false);
+        result.unwrap();
+        assert_eq!(chip8.memory[0x050..0x060], [0xFF; 16]);
+    }
+
+    #[test]
+    fn only_roms_that_fit_are_loaded() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        let max = MEMORY_SIZE - PROGRAM_START as usize;
+        assert!(matches!(
+            chip8.load_rom_bytes(&[0xAA; MEMORY_SIZE]),
+            Err(Chip8Error::RomTooLarge { size: MEMORY_SIZE, max: 3584 })
+        ));
+        assert!(matches!(chip8.load_rom_bytes(&[]), Err(Chip8Error::EmptyRom)));
+        // Neither touched memory.
+        assert_eq!(chip8.addressable_memory()[PROGRAM_START as usize..], [0; 3584]);
+        assert_eq!(chip8.rom_hash(), None);
+
+        chip8.load_rom_bytes(&[0xAA; 3584]).unwrap();
+        assert_eq!(chip8.addressable_memory()[PROGRAM_START as usize..], [0xAA; 3584]);
+        assert_eq!(chip8.memory_map().rom.len(), max);
+    }
+
+    #[test]
+    fn fx29_finds_the_glyph_wherever_the_font_is() {
+        for font in [HexFont::Standard, HexFont::Vip, HexFont::Rounded] {
+            for base in [0x000, 0x050, 0x1B0] {
+                let mut chip8 = Chip8::with_rng(Rng::new(1));
+                chip8.load_font(font, base);
+                // 200: F029  I = glyph for V0
+                // 202: F129  I = glyph for V1
+                chip8.load_rom_bytes(&[0xF0, 0x29, 0xF1, 0x29]).unwrap();
+                chip8.v[0] = 0x0;
+                chip8.v[1] = 0xF;
+                for (digit, glyph) in [(0x0, &font.glyphs()[..5]), (0xF, &font.glyphs()[75..])] {
+                    chip8.step().unwrap();
+                    assert_eq!(chip8.i, base + digit * 5, "{font:?} at 0x{base:03X}");
+                    assert_eq!(&chip8.memory[chip8.i as usize..][..5], glyph, "{font:?} at 0x{base:03X}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn loading_a_font_clears_the_old_one() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.load_font(HexFont::Vip, 0x050);
+        assert_eq!(chip8.memory[..0x050], [0; 0x050]);
+        assert_eq!((chip8.font(), chip8.font_base()), (HexFont::Vip, 0x050));
+        assert_eq!(chip8.memory_map().font, 0x050..0x0A0);
+    }
+
+    #[test]
+    #[should_panic(expected = "runs into the program")]
+    fn fonts_must_fit_below_the_program() {
+        Chip8::with_rng(Rng::new(1)).load_font(HexFont::Standard, 0x1B1);
+    }
+
+    #[test]
+    #[should_panic(expected = "or the big font")]
+    fn fonts_must_stay_clear_of_the_big_font() {
+        Chip8::with_rng(Rng::new(1)).load_font(HexFont::Standard, 0x060);
+    }
+
+    #[test]
+    fn fx30_points_at_the_big_digit_dxya_draws() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        // 200: 00FF  128x64   202: F230  I = big glyph for V2
+        // 204: D01A  draw 10 rows at (V0, V1)
+        chip8.load_rom_bytes(&[0x00, 0xFF, 0xF2, 0x30, 0xD0, 0x1A]).unwrap();
+        (chip8.v[0], chip8.v[1], chip8.v[2]) = (8, 4, 7);
+        for _ in 0..3 {
+            chip8.step().unwrap();
+        }
+        assert_eq!(chip8.i, BIG_FONT_BASE + 70);
+
+        let glyph = &BIG_FONTSET[70..80];
+        for (y, row) in chip8.display.rows().enumerate() {
+            for (x, &lit) in row.iter().enumerate() {
+                let expected = (4..14).contains(&y) && (8..16).contains(&x) && glyph[y - 4] & (0x80 >> (x - 8)) != 0;
+                assert_eq!(lit, expected, "({x}, {y})");
+            }
+        }
+        assert_eq!(chip8.display.lit(), glyph.iter().map(|row| row.count_ones() as usize).sum::<usize>());
+    }
+
+    #[test]
+    fn f002_loads_the_pattern_at_i_and_fx3a_sets_the_pitch() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        // 200: A206  I = 206   202: F002  load the pattern   204: F33A  pitch = V3
+        // 206: the pattern
+        let pattern = [0xF0, 0x0F, 0xAA, 0x55, 0, 0, 0, 0, 0xFF, 0xFF, 0, 0, 1, 2, 3, 4];
+        chip8.load_rom_bytes(&[0xA2, 0x06, 0xF0, 0x02, 0xF3, 0x3A]).unwrap();
+        chip8.memory[0x206..0x216].copy_from_slice(&pattern);
+        chip8.v[3] = 112;
+        assert_eq!((chip8.audio_pattern, chip8.pitch), (None, DEFAULT_PITCH));
+
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.audio_pattern, Some(pattern));
+        chip8.step().unwrap();
+        assert_eq!(chip8.pitch, 112);
+
+        chip8.i = 0xFF8;
+        chip8.pc = 0x202;
+        assert!(matches!(chip8.step(), Err(Chip8Error::BlockOutOfBounds { opcode: 0xF002, i: 0xFF8, .. })));
+        assert_eq!(chip8.audio_pattern, Some(pattern));
+    }
+
+    #[test]
+    fn register_ranges_save_and_load_in_either_direction_without_moving_i() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.quirks.load_store_increments_i = true;
+        // 200: 5252  save V2..=V5   202: 5722  save V7 down to V2
+        // 204: 5993  load V9        206: 5A83  load VA down to V8
+        chip8.load_rom_bytes(&[0x52, 0x52, 0x57, 0x22, 0x59, 0x93, 0x5A, 0x83]).unwrap();
+        chip8.v = core::array::from_fn(|register| 0x10 + register as u8);
+        chip8.i = 0x300;
+
+        chip8.step().unwrap();
+        assert_eq!(chip8.memory[0x300..0x305], [0x12, 0x13, 0x14, 0x15, 0]);
+        chip8.step().unwrap();
+        assert_eq!(chip8.memory[0x300..0x307], [0x17, 0x16, 0x15, 0x14, 0x13, 0x12, 0]);
+        assert_eq!(chip8.i, 0x300);
+
+        chip8.step().unwrap();
+        assert_eq!(chip8.v[9], 0x17);
+        chip8.step().unwrap();
+        assert_eq!(chip8.v[8..=0xA], [0x15, 0x16, 0x17]);
+        assert_eq!(chip8.i, 0x300);
+    }
+
+    #[test]
+    fn register_ranges_past_the_end_of_memory_stop_before_copying() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        // 200: 5302  save V3 down to V0 at I = 0xFFE
+        chip8.load_rom_bytes(&[0x53, 0x02]).unwrap();
+        chip8.i = 0xFFE;
+        chip8.v = [0x77; 16];
+        let error = chip8.step().unwrap_err();
+        assert!(
+            matches!(error, Chip8Error::BlockOutOfBounds { opcode: 0x5302, i: 0xFFE, x: 3, pc: 0x200 }),
+            "{error:?}"
+        );
+        assert_eq!(chip8.addressable_memory()[0xFFE..], [0, 0]);
+    }
+
+    #[test]
+    fn eti_660_programs_load_and_start_at_0x600() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.set_program_start(0x600);
+        assert_eq!(chip8.pc, 0x600);
+        // 600: 6042  V0 = 0x42
+        chip8.load_rom_bytes(&[0x60, 0x42]).unwrap();
+        assert_eq!(chip8.peek_opcode(chip8.pc), 0x6042);
+        assert_eq!(chip8.memory[PROGRAM_START as usize], 0);
+        assert_eq!(chip8.memory_map().rom, 0x600..0x602);
+        chip8.step().unwrap();
+        assert_eq!((chip8.v[0], chip8.pc), (0x42, 0x602));
+
+        let max = MEMORY_SIZE - 0x600;
+        assert!(matches!(
+            chip8.load_rom_bytes(&[0xAA; 3584]),
+            Err(Chip8Error::RomTooLarge { size: 3584, max: 2560 })
+        ));
+        chip8.load_rom_bytes(&[0xAA; 2560]).unwrap();
+        assert_eq!(chip8.memory_map().rom.len(), max);
+    }
+
+    #[test]
+    #[should_panic(expected = "programs can't start at 0x1FE")]
+    fn programs_start_at_or_above_0x200() {
+        Chip8::with_rng(Rng::new(1)).set_program_start(0x1FE);
+    }
+
+    #[test]
+    fn copies_past_the_end_of_memory_stop_before_copying() {
+        for low in [0x55, 0x65] {
+            let mut chip8 = Chip8::with_rng(Rng::new(1));
+            // 200: F555 or F565  copy V0..=V5 at I = 0xFFE
+            chip8.load_rom_bytes(&[0xF5, low]).unwrap();
+            chip8.i = 0xFFE;
+            chip8.v = [0x77; 16];
+            let error = chip8.step().unwrap_err();
+            assert!(
+                matches!(error, Chip8Error::BlockOutOfBounds { opcode, i: 0xFFE, x: 5, pc: 0x200 } if opcode == 0xF500 | low as u16),
+                "{error:?}"
+            );
+            assert!(!error.is_recoverable());
+            assert_eq!(chip8.addressable_memory()[0xFFE..], [0, 0]);
+            assert_eq!(chip8.v, [0x77; 16]);
+        }
+    }
+
+    #[test]
+    fn copies_that_end_at_the_last_byte_go_through() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        // 200: F155  store V0..=V1 at I = 0xFFE
+        chip8.load_rom_bytes(&[0xF1, 0x55]).unwrap();
+        chip8.i = 0xFFE;
+        chip8.v[0] = 1;
+        chip8.v[1] = 2;
+        chip8.step().unwrap();
+        assert_eq!(chip8.addressable_memory()[0xFFE..], [1, 2]);
+    }
+
+    struct Constant(u8);
+
+    impl RandomSource for Constant {
+        fn next_u8(&mut self) -> u8 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn a_fixed_seed_gives_the_same_cxnn_bytes_everywhere() {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.seed_rng(0x5EED);
+        // 200: C0FF .. 20E: C7FF  V0..=V7 = random bytes
+        let mut program = [0xFF; 16];
+        for x in 0..8 {
+            program[x * 2] = 0xC0 | x as u8;
+        }
+        chip8.load_rom_bytes(&program).unwrap();
+        for _ in 0..8 {
+            chip8.step().unwrap();
+        }
+
+        // Pinned: changing these breaks every recorded run that uses CXNN.
+        assert_eq!(chip8.v[..8], [0x97, 0xC7, 0xE9, 0x14, 0x19, 0x69, 0x32, 0xD5]);
+    }
+
+    #[test]
+    fn cxnn_draws_from_the_plugged_in_rng() {
+        let mut chip8 = Chip8::with_rng(Constant(0xA5));
+        // 200: C00F  V0 = random & 0x0F
+        chip8.load_rom_bytes(&[0xC0, 0x0F]).unwrap();
+        chip8.step().unwrap();
+
+        assert_eq!(chip8.v[0], 0x05);
+    }
+}
@@ -0,0 +1,131 @@
+//! CHIP-8X's colors, from the VIP's VP-590 color board: one background for
+//! the whole screen, which 02A0 steps through, and a foreground for the lit
+//! pixels of each zone of 8x4, which BXY0 and BXYN set.
+
+/// Zones across the 64x32 screen, 8 pixels each.
+pub const ZONE_COLUMNS: usize = 8;
+/// Zones down it, 4 pixels each.
+pub const ZONE_ROWS: usize = 8;
+pub const ZONES: usize = ZONE_COLUMNS * ZONE_ROWS;
+
+/// The backgrounds 02A0 steps through, as 0RGB, from the one the board
+/// starts on: dark blue, black, green and red.
+pub const BACKGROUNDS: [u32; 4] = [0x000080, 0x000000, 0x008000, 0x800000];
+
+/// The foregrounds as 0RGB, by the low three bits of the register BXY0 and
+/// BXYN take them from, red, blue and green from the lowest bit up.
+pub const FOREGROUNDS: [u32; 8] = [0x000000, 0xFF0000, 0x0000FF, 0xFF00FF, 0x00FF00, 0xFFFF00, 0x00FFFF, 0xFFFFFF];
+
+/// What every zone starts as: red.
+const DEFAULT_FOREGROUND: u8 = 1;
+
+/// A background and a foreground per zone, each an index into
+/// [`BACKGROUNDS`] and [`FOREGROUNDS`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorMap {
+    background: u8,
+    /// Row-major, [`ZONE_COLUMNS`] a row.
+    zones: [u8; ZONES],
+}
+
+impl Default for ColorMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ColorMap {
+    /// The board as it powers on.
+    pub const fn new() -> Self {
+        Self { background: 0, zones: [DEFAULT_FOREGROUND; ZONES] }
+    }
+
+    /// A map with `background` and `zones`, or `None` if either is past the
+    /// colors there are.
+    pub fn from_parts(background: u8, zones: [u8; ZONES]) -> Option<Self> {
+        let known = (background as usize) < BACKGROUNDS.len() && zones.iter().all(|&zone| (zone as usize) < FOREGROUNDS.len());
+        known.then_some(Self { background, zones })
+    }
+
+    pub fn background(&self) -> u8 {
+        self.background
+    }
+
+    /// Moves on to the next background, as 02A0 does, back to the first
+    /// after the last.
+    pub fn step_background(&mut self) {
+        self.background = (self.background + 1) % BACKGROUNDS.len() as u8;
+    }
+
+    /// The foreground of every zone, row-major.
+    pub fn zones(&self) -> &[u8; ZONES] {
+        &self.zones
+    }
+
+    pub fn foreground(&self, column: usize, row: usize) -> u8 {
+        self.zones[row * ZONE_COLUMNS + column]
+    }
+
+    /// Gives the `width` x `height` zones from (`column`, `row`) the
+    /// foreground in `color`'s low three bits, wrapping around the edges.
+    pub fn fill(&mut self, column: usize, row: usize, width: usize, height: usize, color: u8) {
+        for y in row..row + height.min(ZONE_ROWS) {
+            for x in column..column + width.min(ZONE_COLUMNS) {
+                self.zones[y % ZONE_ROWS * ZONE_COLUMNS + x % ZONE_COLUMNS] = color & 0b111;
+            }
+        }
+    }
+
+    /// The 0RGB of pixel (`x`, `y`) on a `width` x `height` screen, lit or
+    /// not. Screens larger than 64x32 keep the same zones, just bigger.
+    pub fn rgb(&self, x: usize, y: usize, width: usize, height: usize, lit: bool) -> u32 {
+        if lit {
+            FOREGROUNDS[self.foreground(x * ZONE_COLUMNS / width, y * ZONE_ROWS / height) as usize]
+        } else {
+            BACKGROUNDS[self.background as usize]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_wrap_and_keep_three_bits() {
+        let mut map = ColorMap::new();
+        map.fill(7, 6, 2, 3, 0xFC);
+        for (column, row) in [(7, 6), (0, 6), (7, 7), (0, 0)] {
+            assert_eq!(map.foreground(column, row), 4, "({column}, {row})");
+        }
+        assert_eq!(map.zones().iter().filter(|&&zone| zone == 4).count(), 6);
+        assert_eq!(map.foreground(1, 6), DEFAULT_FOREGROUND);
+
+        map.fill(0, 0, 100, 100, 2);
+        assert!(map.zones().iter().all(|&zone| zone == 2));
+    }
+
+    #[test]
+    fn pixels_take_their_zones_color_at_any_resolution() {
+        let mut map = ColorMap::new();
+        map.fill(1, 2, 1, 1, 7);
+        assert_eq!(map.rgb(8, 8, 64, 32, true), 0xFFFFFF);
+        assert_eq!(map.rgb(7, 8, 64, 32, true), FOREGROUNDS[1]);
+        assert_eq!(map.rgb(16, 16, 128, 64, true), 0xFFFFFF);
+        assert_eq!(map.rgb(8, 8, 64, 32, false), BACKGROUNDS[0]);
+
+        for _ in 0..5 {
+            map.step_background();
+        }
+        assert_eq!((map.background(), map.rgb(0, 0, 64, 32, false)), (1, 0x000000));
+    }
+
+    #[test]
+    fn parts_past_the_colors_are_rejected() {
+        assert_eq!(ColorMap::from_parts(0, [1; ZONES]), Some(ColorMap::new()));
+        assert_eq!(ColorMap::from_parts(4, [1; ZONES]), None);
+        let mut zones = [0; ZONES];
+        zones[63] = 8;
+        assert_eq!(ColorMap::from_parts(3, zones), None);
+    }
+}
@@ -0,0 +1,119 @@
+use std::process::ExitCode;
+
+use log::info;
+use minifb::Key;
+
+use chip8_emulator::{Chip8, Chip8Config, Chip8Error, Lockstep, Quirks};
+
+use crate::backend::WINDOW_SCALE;
+use crate::backend::minifb_window::{open_window, read_keys};
+use crate::{EXIT_USAGE, FRONTEND_TARGET, exit_code};
+
+fn profile(name: &str) -> Result<Quirks, String> {
+    Quirks::from_profile(name).ok_or_else(|| {
+        format!(
+            "Unknown profile '{}' (expected one of: {})",
+            name,
+            Quirks::PROFILES.join(", ")
+        )
+    })
+}
+
+/// Width of the gap drawn between the two displays in compare mode.
+const DIVIDER_WIDTH: usize = 2;
+const COMPARE_WIDTH: usize = 64 * 2 + DIVIDER_WIDTH;
+
+fn side_by_side(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut buffer = Vec::with_capacity(COMPARE_WIDTH * 32);
+
+    for (row_a, row_b) in a.chunks(64).zip(b.chunks(64)) {
+        buffer.extend_from_slice(row_a);
+        buffer.extend_from_slice(&[0x808080; DIVIDER_WIDTH]);
+        buffer.extend_from_slice(row_b);
+    }
+
+    buffer
+}
+
+/// Runs the ROM under two quirk profiles with identical input and RNG seed,
+/// pausing at the first instruction after which the machines disagree.
+pub fn run_compare(rom_path: &str, profile_a: &str, profile_b: &str) -> ExitCode {
+    let quirks = match (profile(profile_a), profile(profile_b)) {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("✗ {}", e);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    let seed: u64 = rand::random();
+    let config = |quirks| Chip8Config { quirks, seed: Some(seed), ..Chip8Config::default() };
+    let mut machines = [Chip8::with_config(&config(quirks.0)), Chip8::with_config(&config(quirks.1))];
+    for chip8 in &mut machines {
+        if let Err(e) = chip8.load_rom(rom_path) {
+            eprintln!("✗ Failed to load ROM: {}", e);
+            return exit_code(&e);
+        }
+    }
+    let [a, b] = machines;
+    let mut lockstep = Lockstep::new(a, b);
+
+    println!("Comparing {} (left) against {} (right) on {}", profile_a, profile_b, rom_path);
+
+    let mut window = open_window(
+        &format!("Chip-8 Emulator - {} | {}", profile_a, profile_b),
+        COMPARE_WIDTH * WINDOW_SCALE,
+        32 * WINDOW_SCALE,
+    );
+
+    let instructions_per_frame = 10;
+    let mut diverged = false;
+    let mut halted: Option<Chip8Error> = None;
+
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        lockstep.set_keys(read_keys(&window));
+
+        if !diverged && halted.is_none() {
+            for _ in 0..instructions_per_frame {
+                let divergence = match lockstep.step() {
+                    Ok(divergence) => divergence,
+                    Err(e) if e.is_recoverable() => None,
+                    Err(e) => {
+                        eprintln!("✗ Both machines stopped: {}", e);
+                        halted = Some(e);
+                        break;
+                    }
+                };
+
+                if let Some(divergence) = divergence {
+                    info!(target: FRONTEND_TARGET, "Lockstep divergence at 0x{:03X}", divergence.pc);
+                    println!("{}", divergence);
+                    println!("\nPaused. Press ESC to exit.");
+                    window.set_title(&format!(
+                        "Chip-8 Emulator - {} | {} - diverged at 0x{:03X}",
+                        profile_a, profile_b, divergence.pc
+                    ));
+                    diverged = true;
+                    break;
+                }
+            }
+
+            if !diverged {
+                lockstep.tick_timers();
+            }
+        }
+
+        let buffer = side_by_side(
+            &lockstep.a.get_display_buffer(),
+            &lockstep.b.get_display_buffer(),
+        );
+        window
+            .update_with_buffer(&buffer, COMPARE_WIDTH, 32)
+            .expect("Failed to update window");
+    }
+
+    match halted {
+        Some(e) => exit_code(&e),
+        None => ExitCode::SUCCESS,
+    }
+}
@@ -0,0 +1,129 @@
+//! Everything about a machine that's chosen before it runs, in one value
+//! that can start as many identical machines as needed.
+
+use crate::chip8::{Chip8, OpcodePolicy, PROGRAM_START, SysPolicy};
+use crate::hex_font::{FONT_BASE, HexFont};
+use crate::quirks::Quirks;
+use crate::rng::RandomSource;
+#[cfg(feature = "std")]
+use crate::rng::Rng;
+
+/// How to set up a machine, for [`Chip8::with_config`]. The default is what
+/// [`Chip8::new`] gives; change fields with struct update syntax:
+///
+/// ```
+/// use chip8_emulator::{Chip8Config, Quirks};
+///
+/// let config = Chip8Config { quirks: Quirks::schip(), seed: Some(7), ..Chip8Config::default() };
+/// ```
+///
+/// How fast the machine runs is up to whoever steps it, such as the
+/// [`Driver`](crate::frontend::Driver), so it isn't here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chip8Config {
+    /// Including [`extended_memory`](Quirks::extended_memory), which sets the
+    /// memory size.
+    pub quirks: Quirks,
+    /// The CXNN generator's seed, or `None` to seed it from the operating
+    /// system.
+    pub seed: Option<u64>,
+    /// Where ROMs load and run from; see [`Chip8::set_program_start`].
+    pub program_start: u16,
+    pub font: HexFont,
+    /// Where the font goes; see [`Chip8::load_font`].
+    pub font_base: u16,
+    pub sys_policy: SysPolicy,
+    pub opcode_policy: OpcodePolicy,
+    /// See [`Chip8::strict_memory`].
+    pub strict_memory: bool,
+    /// See [`Chip8::warn_odd_length`].
+    pub warn_odd_length: bool,
+}
+
+impl Default for Chip8Config {
+    fn default() -> Self {
+        Self {
+            quirks: Quirks::default(),
+            seed: None,
+            program_start: PROGRAM_START,
+            font: HexFont::default(),
+            font_base: FONT_BASE,
+            sys_policy: SysPolicy::default(),
+            opcode_policy: OpcodePolicy::default(),
+            strict_memory: false,
+            warn_odd_length: false,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Chip8 {
+    /// A machine set up as `config` says, with no ROM loaded yet.
+    ///
+    /// # Panics
+    ///
+    /// If the font or program start is somewhere [`Chip8::load_font`] or
+    /// [`Chip8::set_program_start`] would panic at.
+    pub fn with_config(config: &Chip8Config) -> Self {
+        let rng = config.seed.map_or_else(Rng::from_entropy, Rng::new);
+        let mut chip8 = Self::with_rng(rng);
+        chip8.apply_config(config);
+        chip8
+    }
+}
+
+impl<R: RandomSource> Chip8<R> {
+    /// Sets up this machine as `config` says, all but the seed, which only
+    /// [`Chip8::with_config`] uses; machines with another generator keep
+    /// theirs. Call it before loading a ROM.
+    ///
+    /// # Panics
+    ///
+    /// As [`Chip8::with_config`] does.
+    pub fn apply_config(&mut self, config: &Chip8Config) {
+        self.quirks = config.quirks;
+        self.sys_policy = config.sys_policy;
+        self.opcode_policy = config.opcode_policy;
+        self.strict_memory = config.strict_memory;
+        self.warn_odd_length = config.warn_odd_length;
+        self.load_font(config.font, config.font_base);
+        self.set_program_start(config.program_start);
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::hex_font::FONTSET;
+
+    #[test]
+    fn configured_machines_start_where_and_how_they_were_told() {
+        let config = Chip8Config {
+            quirks: Quirks::schip(),
+            seed: Some(42),
+            program_start: 0x600,
+            font_base: 0x000,
+            strict_memory: true,
+            ..Chip8Config::default()
+        };
+        let mut a = Chip8::with_config(&config);
+        let mut b = Chip8::with_config(&config);
+
+        assert_eq!((a.pc, a.program_start()), (0x600, 0x600));
+        assert_eq!(a.quirks, Quirks::schip());
+        assert!(a.strict_memory);
+        assert_eq!(a.memory[..FONTSET.len()], FONTSET);
+        assert_eq!(a.font_base(), 0x000);
+
+        let mut seeded = Chip8::with_rng(Rng::new(42));
+        seeded.set_program_start(0x600);
+        // 600: C0FF  V0 = random   602: C1FF  V1 = random
+        for chip8 in [&mut a, &mut b, &mut seeded] {
+            chip8.load_rom_bytes(&[0xC0, 0xFF, 0xC1, 0xFF]).unwrap();
+            chip8.step().unwrap();
+            chip8.step().unwrap();
+        }
+        assert_eq!(a.v[..2], b.v[..2]);
+        assert_eq!(a.v[..2], seeded.v[..2]);
+    }
+}
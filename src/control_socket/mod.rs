@@ -0,0 +1,156 @@
+//! A TCP server that lets external tools drive a running emulator, speaking
+//! the newline-delimited JSON [`protocol`].
+//!
+//! Each connection is served on its own thread and turns requests into
+//! [`Command`]s for the [`Controller`] it was given, so the frame loop stays
+//! in charge of the machine.
+
+pub mod protocol;
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use log::{info, warn};
+use serde_json::Value;
+
+use crate::chip8::{EXTENDED_MEMORY_SIZE, PROGRAM_START};
+use crate::frontend::control::{Command, ControlError, Controller};
+use crate::loader::{default_search_path, read_rom};
+
+use protocol::{EventName, RequestCommand};
+
+const LOG_TARGET: &str = "chip8::control";
+
+/// Both responses and events are written to a connection, from different
+/// threads, a whole line at a time.
+type Writer = Arc<Mutex<TcpStream>>;
+
+/// Listens on `addr` and serves every connection from a background thread
+/// until the process exits. Returns the address it's listening on, which
+/// tells callers the port when they asked for port 0.
+pub fn spawn(addr: impl ToSocketAddrs, controller: Controller) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)?;
+    let local_addr = listener.local_addr()?;
+    info!(target: LOG_TARGET, "Control socket listening on {}", local_addr);
+
+    thread::Builder::new()
+        .name("control-socket".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let controller = controller.clone();
+                        let spawned = thread::Builder::new()
+                            .name("control-client".to_string())
+                            .spawn(move || serve(stream, controller));
+                        if let Err(e) = spawned {
+                            warn!(target: LOG_TARGET, "Couldn't start a client thread: {}", e);
+                        }
+                    }
+                    Err(e) => warn!(target: LOG_TARGET, "Failed to accept a connection: {}", e),
+                }
+            }
+        })?;
+    Ok(local_addr)
+}
+
+fn serve(stream: TcpStream, controller: Controller) {
+    let peer = stream
+        .peer_addr()
+        .map_or_else(|_| "unknown peer".to_string(), |addr| addr.to_string());
+    info!(target: LOG_TARGET, "{} connected", peer);
+
+    let writer = match stream.try_clone() {
+        Ok(clone) => Arc::new(Mutex::new(clone)),
+        Err(e) => {
+            warn!(target: LOG_TARGET, "Dropping {}: {}", peer, e);
+            return;
+        }
+    };
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else { break };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (response, running) = handle(&line, &controller, &writer);
+        if write_line(&writer, &response).is_err() || !running {
+            break;
+        }
+    }
+    info!(target: LOG_TARGET, "{} disconnected", peer);
+}
+
+/// Applies one request line, returning the response line and whether the
+/// emulator is still there to take more.
+fn handle(line: &str, controller: &Controller, writer: &Writer) -> (String, bool) {
+    let request = match protocol::parse_request(line) {
+        Ok(request) => request,
+        Err(e) => return (protocol::failure(&Value::Null, &e), true),
+    };
+    let command = match command(request.command, writer) {
+        Ok(command) => command,
+        Err(e) => return (protocol::failure(&request.id, &e), true),
+    };
+    match controller.send(command) {
+        Ok(reply) => (protocol::success(&request.id, &reply), true),
+        Err(e @ ControlError::Disconnected) => (protocol::failure(&request.id, &e.to_string()), false),
+        Err(e) => (protocol::failure(&request.id, &e.to_string()), true),
+    }
+}
+
+fn command(request: RequestCommand, writer: &Writer) -> Result<Command, String> {
+    Ok(match request {
+        RequestCommand::Pause => Command::Pause,
+        RequestCommand::Resume => Command::Resume,
+        RequestCommand::Reset => Command::Reset,
+        RequestCommand::Step => Command::Step,
+        RequestCommand::LoadRom { path: Some(path), data: None } => {
+            // The machine turns away what's too large for its memory.
+            let max = EXTENDED_MEMORY_SIZE - PROGRAM_START as usize;
+            let rom = read_rom(Path::new(&path), max, &default_search_path())
+                .map_err(|e| e.to_string())?;
+            Command::LoadRom(rom)
+        }
+        RequestCommand::LoadRom { path: None, data: Some(data) } => Command::LoadRom(data),
+        RequestCommand::LoadRom { .. } => {
+            return Err("load-rom needs either a path or data".to_string());
+        }
+        RequestCommand::Press { key } => Command::Key { key, pressed: true },
+        RequestCommand::Release { key } => Command::Key { key, pressed: false },
+        RequestCommand::Registers => Command::ReadRegisters,
+        RequestCommand::ReadMemory { addr, len } => Command::ReadMemory { addr, len },
+        RequestCommand::SaveState => Command::SaveState,
+        RequestCommand::SetBreakpoint { addr } => Command::SetBreakpoint(addr),
+        RequestCommand::ClearBreakpoint { addr } => Command::ClearBreakpoint(addr),
+        RequestCommand::Subscribe { events } => {
+            let (sender, receiver) = mpsc::channel();
+            let writer = Arc::clone(writer);
+            // Ends when the frame loop drops the sender, or on the first
+            // failed write, after which the frame loop drops it.
+            thread::Builder::new()
+                .name("control-events".to_string())
+                .spawn(move || {
+                    for event in receiver {
+                        if write_line(&writer, &protocol::event(&event)).is_err() {
+                            break;
+                        }
+                    }
+                })
+                .map_err(|e| e.to_string())?;
+            Command::Subscribe {
+                filter: EventName::filter(&events),
+                events: sender,
+            }
+        }
+    })
+}
+
+fn write_line(writer: &Writer, line: &str) -> io::Result<()> {
+    let mut stream = writer.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    stream.write_all(line.as_bytes())?;
+    stream.write_all(b"\n")
+}
@@ -0,0 +1,209 @@
+//! The wire format: one JSON object per line in each direction.
+//!
+//! Requests name a command in `cmd` and may carry an `id`, which is echoed
+//! back in the response:
+//!
+//! ```text
+//! {"id": 1, "cmd": "read-memory", "addr": 512, "len": 4}
+//! {"id": 1, "ok": true, "memory": [96, 5, 97, 3]}
+//! {"id": 2, "cmd": "press", "key": 16}
+//! {"id": 2, "ok": false, "error": "key 0x10 does not exist on the keypad (instruction at 0x208)"}
+//! ```
+//!
+//! Commands: `pause`, `resume`, `reset`, `step`, `load-rom` (with a `path`,
+//! or the program as a `data` array of bytes), `press` and `release` (with a
+//! `key`), `registers`, `read-memory` (`addr`, `len`), `save-state` (the
+//! binary save state, in base64), `set-breakpoint` and `clear-breakpoint`
+//! (`addr`), and `subscribe` (`events`, any of `halt`, `breakpoint` and
+//! `frame`).
+//!
+//! Subscribed events arrive on the same connection, marked by `event`:
+//!
+//! ```text
+//! {"event": "breakpoint", "pc": 520}
+//! {"event": "halt", "error": "stack underflow: return with an empty call stack"}
+//! {"event": "frame", "frame": 120, "display_hash": "9f2c1e0b6a7d4c83"}
+//! ```
+//!
+//! Display hashes are hex strings, since JSON numbers can't hold a `u64`
+//! exactly everywhere.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::frontend::control::{Event, EventFilter, Reply};
+
+/// A parsed request line.
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    /// Echoed back untouched, so it can be any JSON value.
+    #[serde(default)]
+    pub id: Value,
+    #[serde(flatten)]
+    pub command: RequestCommand,
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[serde(tag = "cmd", rename_all = "kebab-case")]
+pub enum RequestCommand {
+    Pause,
+    Resume,
+    Reset,
+    Step,
+    LoadRom {
+        path: Option<String>,
+        data: Option<Vec<u8>>,
+    },
+    Press {
+        key: u8,
+    },
+    Release {
+        key: u8,
+    },
+    Registers,
+    ReadMemory {
+        addr: u16,
+        len: u16,
+    },
+    SaveState,
+    SetBreakpoint {
+        addr: u16,
+    },
+    ClearBreakpoint {
+        addr: u16,
+    },
+    Subscribe {
+        events: Vec<EventName>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EventName {
+    Halt,
+    Breakpoint,
+    Frame,
+}
+
+impl EventName {
+    pub fn filter(names: &[EventName]) -> EventFilter {
+        EventFilter {
+            halt: names.contains(&EventName::Halt),
+            breakpoint: names.contains(&EventName::Breakpoint),
+            frame: names.contains(&EventName::Frame),
+        }
+    }
+}
+
+pub fn parse_request(line: &str) -> Result<Request, String> {
+    serde_json::from_str(line).map_err(|e| format!("invalid request: {}", e))
+}
+
+/// The response line for a command that was applied.
+pub fn success(id: &Value, reply: &Reply) -> String {
+    let mut response = json!({ "id": id, "ok": true });
+    match reply {
+        Reply::Done => {}
+        Reply::Stepped { pc } => response["pc"] = json!(pc),
+        Reply::Registers(registers) => {
+            response["registers"] = json!({
+                "v": registers.v,
+                "i": registers.i,
+                "pc": registers.pc,
+                "stack": registers.stack,
+                "delay_timer": registers.delay_timer,
+                "sound_timer": registers.sound_timer,
+                "paused": registers.paused,
+                "halted": registers.halted,
+            });
+        }
+        Reply::Memory(bytes) => response["memory"] = json!(bytes),
+        Reply::State { state, .. } => response["state"] = json!(BASE64.encode(state)),
+    }
+    response.to_string()
+}
+
+/// The response line for a request that failed.
+pub fn failure(id: &Value, error: &str) -> String {
+    json!({ "id": id, "ok": false, "error": error }).to_string()
+}
+
+pub fn event(event: &Event) -> String {
+    match event {
+        Event::Halted { reason } => json!({ "event": "halt", "error": reason }),
+        Event::Breakpoint { pc } => json!({ "event": "breakpoint", "pc": pc }),
+        Event::Frame { frame, display_hash } => json!({
+            "event": "frame",
+            "frame": frame,
+            "display_hash": format!("{:016x}", display_hash),
+        }),
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands_with_their_arguments() {
+        let request = parse_request(r#"{"id": 7, "cmd": "read-memory", "addr": 512, "len": 4}"#).unwrap();
+        assert_eq!(request.id, json!(7));
+        assert_eq!(request.command, RequestCommand::ReadMemory { addr: 512, len: 4 });
+
+        let request = parse_request(r#"{"cmd": "pause"}"#).unwrap();
+        assert_eq!(request.id, Value::Null);
+        assert_eq!(request.command, RequestCommand::Pause);
+
+        let request = parse_request(r#"{"cmd": "subscribe", "events": ["frame", "halt"]}"#).unwrap();
+        let RequestCommand::Subscribe { events } = request.command else {
+            panic!("{:?}", request.command);
+        };
+        assert_eq!(
+            EventName::filter(&events),
+            EventFilter { halt: true, breakpoint: false, frame: true }
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_requests() {
+        for line in [
+            "",
+            "pause",
+            r#"{"cmd": "jump"}"#,
+            r#"{"cmd": "press"}"#,
+            r#"{"cmd": "set-breakpoint", "addr": 70000}"#,
+            r#"{"cmd": "subscribe", "events": ["keys"]}"#,
+        ] {
+            assert!(parse_request(line).is_err(), "{line}");
+        }
+    }
+
+    #[test]
+    fn responses_echo_the_id() {
+        let id = json!("a");
+        let line: Value = serde_json::from_str(&success(&id, &Reply::Stepped { pc: 0x202 })).unwrap();
+        assert_eq!(line, json!({ "id": "a", "ok": true, "pc": 514 }));
+
+        let line: Value = serde_json::from_str(&failure(&id, "nope")).unwrap();
+        assert_eq!(line, json!({ "id": "a", "ok": false, "error": "nope" }));
+
+        let line: Value = serde_json::from_str(&success(&id, &Reply::State { state: b"C8ST".to_vec(), map: Default::default() })).unwrap();
+        assert_eq!(line["state"], json!("QzhTVA=="));
+    }
+
+    #[test]
+    fn display_hashes_are_fixed_width_hex() {
+        let line: Value = serde_json::from_str(&event(&Event::Frame {
+            frame: 3,
+            display_hash: 0xAB,
+        }))
+        .unwrap();
+        assert_eq!(
+            line,
+            json!({ "event": "frame", "frame": 3, "display_hash": "00000000000000ab" })
+        );
+    }
+}
@@ -0,0 +1,43 @@
+use alloc::boxed::Box;
+use alloc::vec;
+
+use crate::instruction::Instruction;
+
+/// Predecoded instructions keyed by the address of their first byte.
+///
+/// An instruction spans two bytes, so a write to `addr` invalidates the
+/// entries starting at both `addr` and `addr - 1`.
+pub(crate) struct DecodeCache {
+    entries: Box<[Option<Instruction>]>,
+}
+
+impl DecodeCache {
+    pub(crate) fn new(size: usize) -> Self {
+        Self {
+            entries: vec![None; size].into_boxed_slice(),
+        }
+    }
+
+    pub(crate) fn get(&self, addr: u16) -> Option<Instruction> {
+        self.entries.get(addr as usize).copied().flatten()
+    }
+
+    pub(crate) fn insert(&mut self, addr: u16, instruction: Instruction) {
+        if let Some(entry) = self.entries.get_mut(addr as usize) {
+            *entry = Some(instruction);
+        }
+    }
+
+    pub(crate) fn invalidate(&mut self, addr: usize) {
+        if let Some(entry) = self.entries.get_mut(addr) {
+            *entry = None;
+        }
+        if let Some(entry) = addr.checked_sub(1).and_then(|prev| self.entries.get_mut(prev)) {
+            *entry = None;
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.fill(None);
+    }
+}
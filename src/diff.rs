@@ -0,0 +1,322 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::chip8::Chip8;
+use crate::memory_map::MemoryMap;
+
+/// Most bytes of a changed memory range that [`StateDiff`]'s `Display`
+/// prints; longer ranges are only given as addresses.
+const MAX_SHOWN_BYTES: usize = 8;
+
+/// A register, in the order [`StateDiff`] lists them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Register {
+    V(u8),
+    I,
+    Pc,
+    DelayTimer,
+    SoundTimer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterChange {
+    pub register: Register,
+    pub old: u16,
+    pub new: u16,
+}
+
+/// A run of consecutive bytes that all changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryChange {
+    pub start: u16,
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+}
+
+impl MemoryChange {
+    /// The last address in the range.
+    pub fn end(&self) -> u16 {
+        self.start + self.old.len() as u16 - 1
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayChange {
+    /// Lit pixels before and after.
+    pub old_lit: usize,
+    pub new_lit: usize,
+    /// Pixels that flipped either way.
+    pub changed: usize,
+}
+
+/// What changed between two machine states, from [`Chip8::diff`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateDiff {
+    pub registers: Vec<RegisterChange>,
+    /// The old and new return addresses, oldest first, if they differ.
+    pub stack: Option<(Vec<u16>, Vec<u16>)>,
+    /// Changed memory, in address order.
+    pub memory: Vec<MemoryChange>,
+    pub display: Option<DisplayChange>,
+    /// The old machine's map, used to label changed memory.
+    pub map: MemoryMap,
+}
+
+impl StateDiff {
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty() && self.stack.is_none() && self.memory.is_empty() && self.display.is_none()
+    }
+
+    /// Bytes changed across all of [`StateDiff::memory`].
+    pub fn memory_bytes(&self) -> usize {
+        self.memory.iter().map(|change| change.old.len()).sum()
+    }
+}
+
+impl Chip8 {
+    /// What changed going from `self` to `other`. Keys, quirks and the RNG
+    /// are not compared.
+    pub fn diff(&self, other: &Chip8) -> StateDiff {
+        let mut registers = Vec::new();
+        let mut compare = |register, old: u16, new: u16| {
+            if old != new {
+                registers.push(RegisterChange { register, old, new });
+            }
+        };
+        for (x, (&old, &new)) in self.v.iter().zip(&other.v).enumerate() {
+            compare(Register::V(x as u8), old as u16, new as u16);
+        }
+        compare(Register::I, self.i, other.i);
+        compare(Register::Pc, self.pc, other.pc);
+        compare(Register::DelayTimer, self.delay_timer as u16, other.delay_timer as u16);
+        compare(Register::SoundTimer, self.sound_timer as u16, other.sound_timer as u16);
+
+        let stack = (self.stack != other.stack)
+            .then(|| (self.stack.as_slice().to_vec(), other.stack.as_slice().to_vec()));
+
+        // A new resolution changes every pixel, lit or not.
+        let changed = if self.display.resolution() == other.display.resolution() {
+            self.display.pixels().iter().zip(other.display.pixels()).filter(|(old, new)| old != new).count()
+        } else {
+            other.display.pixels().len()
+        };
+        let display = (changed > 0).then(|| DisplayChange {
+            old_lit: self.display.lit(),
+            new_lit: other.display.lit(),
+            changed,
+        });
+
+        StateDiff {
+            registers,
+            stack,
+            memory: memory_changes(&self.memory, &other.memory),
+            display,
+            map: self.memory_map(),
+        }
+    }
+}
+
+/// Coalesces the bytes that differ between `old` and `new` into ranges.
+fn memory_changes(old: &[u8], new: &[u8]) -> Vec<MemoryChange> {
+    let mut changes: Vec<MemoryChange> = Vec::new();
+    for (addr, (&was, &now)) in old.iter().zip(new).enumerate() {
+        if was == now {
+            continue;
+        }
+        match changes.last_mut() {
+            Some(last) if last.end() as usize + 1 == addr => {
+                last.old.push(was);
+                last.new.push(now);
+            }
+            _ => changes.push(MemoryChange {
+                start: addr as u16,
+                old: alloc::vec![was],
+                new: alloc::vec![now],
+            }),
+        }
+    }
+    changes
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Register::V(x) => write!(f, "V{:X}", x),
+            Register::I => write!(f, "I"),
+            Register::Pc => write!(f, "PC"),
+            Register::DelayTimer => write!(f, "DT"),
+            Register::SoundTimer => write!(f, "ST"),
+        }
+    }
+}
+
+impl fmt::Display for RegisterChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.register {
+            Register::V(_) => write!(f, "{}: 0x{:02X} -> 0x{:02X}", self.register, self.old, self.new),
+            Register::I | Register::Pc => {
+                write!(f, "{}: 0x{:03X} -> 0x{:03X}", self.register, self.old, self.new)
+            }
+            Register::DelayTimer | Register::SoundTimer => {
+                write!(f, "{}: {} -> {}", self.register, self.old, self.new)
+            }
+        }
+    }
+}
+
+struct Bytes<'a>(&'a [u8]);
+
+impl fmt::Display for Bytes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, byte) in self.0.iter().enumerate() {
+            if index > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{:02X}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for MemoryChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let len = self.old.len();
+        if len == 1 {
+            write!(f, "memory[0x{:03X}]: 0x{:02X} -> 0x{:02X}", self.start, self.old[0], self.new[0])
+        } else if len <= MAX_SHOWN_BYTES {
+            write!(
+                f,
+                "memory[0x{:03X}..=0x{:03X}]: {} -> {}",
+                self.start,
+                self.end(),
+                Bytes(&self.old),
+                Bytes(&self.new)
+            )
+        } else {
+            write!(f, "memory[0x{:03X}..=0x{:03X}]: {} bytes changed", self.start, self.end(), len)
+        }
+    }
+}
+
+impl fmt::Display for DisplayChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "display: {} -> {} pixels lit ({:+}), {} changed",
+            self.old_lit,
+            self.new_lit,
+            self.new_lit as isize - self.old_lit as isize,
+            self.changed
+        )
+    }
+}
+
+/// One change per line, or `no changes`.
+impl fmt::Display for StateDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no changes");
+        }
+        let mut lines = Lines { f, first: true };
+        for change in &self.registers {
+            lines.line(change)?;
+        }
+        if let Some((old, new)) = &self.stack {
+            lines.line(format_args!("stack: {:03X?} -> {:03X?}", old, new))?;
+        }
+        for change in &self.memory {
+            lines.line(format_args!("{} ({})", change, self.map.region(change.start as usize)))?;
+        }
+        if let Some(display) = &self.display {
+            lines.line(display)?;
+        }
+        Ok(())
+    }
+}
+
+struct Lines<'a, 'b> {
+    f: &'a mut fmt::Formatter<'b>,
+    first: bool,
+}
+
+impl Lines<'_, '_> {
+    fn line(&mut self, line: impl fmt::Display) -> fmt::Result {
+        if !core::mem::take(&mut self.first) {
+            writeln!(self.f)?;
+        }
+        write!(self.f, "{}", line)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn ranges_coalesce_consecutive_bytes_only() {
+        let old = [0u8; 16];
+        let mut new = old;
+        new[2] = 1;
+        new[3] = 2;
+        new[4] = 3;
+        new[6] = 4;
+        new[15] = 5;
+
+        let changes = memory_changes(&old, &new);
+        let ranges: Vec<_> = changes.iter().map(|c| (c.start, c.end())).collect();
+        assert_eq!(ranges, [(2, 4), (6, 6), (15, 15)]);
+        assert_eq!(changes[0].new, [1, 2, 3]);
+        assert!(memory_changes(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn a_cleared_page_is_one_range() {
+        let mut a = Chip8::new();
+        let b = Chip8::new();
+        a.memory[0x400..0x800].fill(0xAA);
+        let diff = a.diff(&b);
+        assert_eq!(diff.memory.len(), 1);
+        assert_eq!(diff.memory_bytes(), 0x400);
+        assert_eq!(diff.to_string(), "memory[0x400..=0x7FF]: 1024 bytes changed (work RAM+0x200)");
+    }
+
+    #[test]
+    fn formats_each_kind_of_change() {
+        let mut a = Chip8::new();
+        a.load_rom_bytes(&[0; 0x110]).unwrap();
+        let mut b = Chip8::new();
+        b.v[0] = 0x18;
+        b.v[0xF] = 1;
+        b.i = 0x2A0;
+        b.sound_timer = 3;
+        assert!(b.stack.push(0x202));
+        b.memory[0x300] = 1;
+        b.memory[0x310..0x314].copy_from_slice(&[1, 2, 3, 4]);
+        a.display[0][0] = true;
+        b.display[1][..3].fill(true);
+
+        let diff = a.diff(&b);
+        assert_eq!(diff.display, Some(DisplayChange { old_lit: 1, new_lit: 3, changed: 4 }));
+        assert_eq!(
+            diff.to_string(),
+            "V0: 0x00 -> 0x18\n\
+             VF: 0x00 -> 0x01\n\
+             I: 0x000 -> 0x2A0\n\
+             ST: 0 -> 3\n\
+             stack: [] -> [202]\n\
+             memory[0x300]: 0x00 -> 0x01 (ROM+0x100)\n\
+             memory[0x310..=0x313]: 00 00 00 00 -> 01 02 03 04 (work RAM+0x000)\n\
+             display: 1 -> 3 pixels lit (+2), 4 changed"
+        );
+        assert_eq!(b.diff(&a).display.unwrap().to_string(), "display: 3 -> 1 pixels lit (-2), 4 changed");
+    }
+
+    #[test]
+    fn identical_machines_have_no_changes() {
+        let diff = Chip8::new().diff(&Chip8::new());
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "no changes");
+    }
+}
@@ -0,0 +1,39 @@
+//! A linear disassembler: every two bytes from the start as an
+//! instruction, in Cowgod's mnemonics with SUPER-CHIP's and XO-CHIP's
+//! additions. It doesn't follow jumps, so sprites in among the code come out
+//! as whatever they happen to decode to.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::instruction::{Instruction, decode};
+
+/// The listing of `rom` loaded at `start`, a line per instruction such as
+/// `200: 6A02  LD VA, 0x02`. Opcodes that don't decode, and an odd byte at
+/// the end, are shown as `.byte`s.
+pub fn disassemble(rom: &[u8], start: u16) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while offset < rom.len() {
+        let addr = start as usize + offset;
+        let Some(&[high, low]) = rom.get(offset..offset + 2) else {
+            lines.push(format!("{:03X}: {:02X}    .byte 0x{:02X}", addr, rom[offset], rom[offset]));
+            break;
+        };
+        let opcode = u16::from_be_bytes([high, low]);
+        // F000's address is the next two bytes, when there are two.
+        let long = rom.get(offset + 2..offset + 4).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+        let line = match (decode(opcode), long) {
+            (Some(Instruction::LongIndex), Some(nnnn)) => {
+                offset += 2;
+                format!("{:03X}: {:04X}  LD I, 0x{:04X}", addr, opcode, nnnn)
+            }
+            (Some(instruction), _) => format!("{:03X}: {:04X}  {}", addr, opcode, instruction),
+            (None, _) => format!("{:03X}: {:04X}  .byte 0x{:02X}, 0x{:02X}", addr, opcode, high, low),
+        };
+        lines.push(line);
+        offset += 2;
+    }
+    lines
+}
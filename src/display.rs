@@ -0,0 +1,365 @@
+//! The screen DXYN draws on, at whichever resolution the program last asked
+//! for, in XO-CHIP's two planes.
+
+use core::fmt;
+use core::ops::{Index, IndexMut};
+use core::slice::ChunksExact;
+
+/// A display mode, switched with SUPER-CHIP's 00FE and 00FF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Resolution {
+    /// 64x32, the only mode plain CHIP-8 has.
+    #[default]
+    Low,
+    /// SUPER-CHIP's 128x64.
+    High,
+    /// 64x64, the COSMAC VIP's two-page HI-RES CHIP-8, which draws like
+    /// [`Low`](Self::Low) on a screen twice as tall.
+    TwoPage,
+}
+
+impl Resolution {
+    pub const fn width(self) -> usize {
+        match self {
+            Resolution::Low | Resolution::TwoPage => 64,
+            Resolution::High => 128,
+        }
+    }
+
+    pub const fn height(self) -> usize {
+        match self {
+            Resolution::Low => 32,
+            Resolution::High | Resolution::TwoPage => 64,
+        }
+    }
+
+    pub const fn pixels(self) -> usize {
+        self.width() * self.height()
+    }
+}
+
+/// Pixels in the largest mode.
+pub const MAX_PIXELS: usize = Resolution::High.pixels();
+
+/// Bitplanes, which XO-CHIP's FN01 chooses between. Anything else only ever
+/// draws on the first.
+pub const PLANES: usize = 2;
+
+/// The pixels of the current [`Resolution`], row-major and packed together,
+/// so `display[y][x]` and [`Display::pixels`] always cover exactly the
+/// screen the program sees. Those are plane 1's; a pixel's
+/// [color](Display::color) says which planes it's lit in.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Display {
+    resolution: Resolution,
+    /// Past the current resolution's pixels, always unlit.
+    planes: [[bool; MAX_PIXELS]; PLANES],
+    /// A bit per plane, plane 1 lowest, for the ones 00E0, DXYN and the
+    /// scrolls work on.
+    selected: u8,
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self::new(Resolution::Low)
+    }
+}
+
+impl Display {
+    /// A blank display at `resolution`, drawing on plane 1.
+    pub const fn new(resolution: Resolution) -> Self {
+        Self { resolution, planes: [[false; MAX_PIXELS]; PLANES], selected: 1 }
+    }
+
+    pub fn resolution(&self) -> Resolution {
+        self.resolution
+    }
+
+    /// Switches to `resolution`, clearing the screen as Octo does; the old
+    /// pixels wouldn't line up with the new ones anyway.
+    pub fn set_resolution(&mut self, resolution: Resolution) {
+        *self = Self { selected: self.selected, ..Self::new(resolution) };
+    }
+
+    /// The planes FN01 last selected, plane 1 in bit 0.
+    pub fn selected_planes(&self) -> u8 {
+        self.selected
+    }
+
+    /// Selects the planes in `mask`, as FN01 does with N; bits past the
+    /// planes there are are ignored.
+    pub fn select_planes(&mut self, mask: u8) {
+        self.selected = mask & ((1 << PLANES) - 1);
+    }
+
+    /// Whether plane `plane`, 0 for plane 1, is selected.
+    pub fn is_selected(&self, plane: usize) -> bool {
+        self.selected & (1 << plane) != 0
+    }
+
+    pub fn width(&self) -> usize {
+        self.resolution.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.resolution.height()
+    }
+
+    /// Unlights every pixel in every plane, keeping the resolution.
+    pub fn clear(&mut self) {
+        self.planes = [[false; MAX_PIXELS]; PLANES];
+    }
+
+    /// Unlights the selected planes, as 00E0 does.
+    pub fn clear_selected(&mut self) {
+        for plane in self.selected_planes_mut() {
+            plane.fill(false);
+        }
+    }
+
+    /// Plane 1, row-major, `width * height` of them.
+    pub fn pixels(&self) -> &[bool] {
+        self.plane(0)
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [bool] {
+        self.plane_mut(0)
+    }
+
+    /// Plane `plane`, 0 for plane 1, laid out like [`pixels`](Self::pixels).
+    pub fn plane(&self, plane: usize) -> &[bool] {
+        &self.planes[plane][..self.resolution.pixels()]
+    }
+
+    pub fn plane_mut(&mut self, plane: usize) -> &mut [bool] {
+        let pixels = self.resolution.pixels();
+        &mut self.planes[plane][..pixels]
+    }
+
+    /// Which planes pixel `index` is lit in, plane 1 in bit 0: an index
+    /// into a four-color [`Palette`](crate::Palette).
+    pub fn color(&self, index: usize) -> u8 {
+        self.planes.iter().enumerate().fold(0, |color, (plane, pixels)| color | (pixels[index] as u8) << plane)
+    }
+
+    /// [`color`](Self::color) for every pixel, row-major.
+    pub fn colors(&self) -> impl ExactSizeIterator<Item = u8> + '_ {
+        (0..self.resolution.pixels()).map(|index| self.color(index))
+    }
+
+    fn selected_planes_mut(&mut self) -> impl Iterator<Item = &mut [bool]> {
+        let (selected, pixels) = (self.selected, self.resolution.pixels());
+        self.planes
+            .iter_mut()
+            .enumerate()
+            .filter(move |&(plane, _)| selected & (1 << plane) != 0)
+            .map(move |(_, plane)| &mut plane[..pixels])
+    }
+
+    /// The rows from top to bottom.
+    pub fn rows(&self) -> ChunksExact<'_, bool> {
+        self.pixels().chunks_exact(self.width())
+    }
+
+    /// Moves the selected planes down `n` rows, leaving the top `n` blank.
+    pub fn scroll_down(&mut self, n: usize) {
+        let blank = n.min(self.height()) * self.width();
+        for pixels in self.selected_planes_mut() {
+            let len = pixels.len();
+            pixels.copy_within(..len - blank, blank);
+            pixels[..blank].fill(false);
+        }
+    }
+
+    /// Moves the selected planes up `n` rows, leaving the bottom `n` blank.
+    pub fn scroll_up(&mut self, n: usize) {
+        let blank = n.min(self.height()) * self.width();
+        for pixels in self.selected_planes_mut() {
+            let len = pixels.len();
+            pixels.copy_within(blank.., 0);
+            pixels[len - blank..].fill(false);
+        }
+    }
+
+    /// Moves the selected planes right `n` columns, leaving the left `n`
+    /// blank.
+    pub fn scroll_right(&mut self, n: usize) {
+        let width = self.width();
+        let n = n.min(width);
+        for row in self.selected_planes_mut().flat_map(|pixels| pixels.chunks_exact_mut(width)) {
+            row.copy_within(..width - n, n);
+            row[..n].fill(false);
+        }
+    }
+
+    /// Moves the selected planes left `n` columns, leaving the right `n`
+    /// blank.
+    pub fn scroll_left(&mut self, n: usize) {
+        let width = self.width();
+        let n = n.min(width);
+        for row in self.selected_planes_mut().flat_map(|pixels| pixels.chunks_exact_mut(width)) {
+            row.copy_within(n.., 0);
+            row[width - n..].fill(false);
+        }
+    }
+
+    /// How many pixels are lit, in any plane.
+    pub fn lit(&self) -> usize {
+        self.colors().filter(|&color| color != 0).count()
+    }
+}
+
+/// Row `y`.
+impl Index<usize> for Display {
+    type Output = [bool];
+
+    fn index(&self, y: usize) -> &[bool] {
+        let width = self.width();
+        &self.pixels()[y * width..(y + 1) * width]
+    }
+}
+
+impl IndexMut<usize> for Display {
+    fn index_mut(&mut self, y: usize) -> &mut [bool] {
+        let width = self.width();
+        &mut self.pixels_mut()[y * width..(y + 1) * width]
+    }
+}
+
+/// The resolution and how much is lit, not every pixel.
+impl fmt::Debug for Display {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Display {{ {}x{}, lit: {} }}", self.width(), self.height(), self.lit())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_are_as_wide_as_the_resolution() {
+        let mut display = Display::default();
+        display[31][63] = true;
+        assert_eq!(display.pixels().len(), 64 * 32);
+        assert_eq!(display.rows().len(), 32);
+        assert!(display.pixels()[64 * 32 - 1]);
+
+        display.set_resolution(Resolution::High);
+        assert_eq!((display.width(), display.height(), display.lit()), (128, 64, 0));
+        display[63][127] = true;
+        display[1][0] = true;
+        assert!(display.pixels()[128]);
+        assert!(display.rows().nth(63).unwrap()[127]);
+
+        display.clear();
+        assert_eq!((display.resolution(), display.lit()), (Resolution::High, 0));
+    }
+
+    #[test]
+    fn two_page_hires_is_64_square() {
+        let mut display = Display::new(Resolution::TwoPage);
+        display[63][63] = true;
+        display[32][0] = true;
+        assert_eq!((display.pixels().len(), display.rows().len()), (64 * 64, 64));
+        assert!(display.pixels()[64 * 32] && display.pixels()[64 * 64 - 1]);
+        display.scroll_up(32);
+        assert!(display[0][0] && display[31][63]);
+    }
+
+    #[test]
+    fn scrolling_moves_pixels_and_blanks_what_it_uncovers() {
+        let mut display = Display::default();
+        display[0][0] = true;
+        display[31][63] = true;
+        display[10][20] = true;
+
+        display.scroll_down(3);
+        assert!(display[3][0] && display[13][20]);
+        assert_eq!(display.lit(), 2, "the bottom row scrolled off");
+        assert!(display.rows().take(3).flatten().all(|&lit| !lit));
+
+        display.scroll_right(4);
+        assert!(display[3][4] && display[13][24]);
+        assert!(display.rows().all(|row| row[..4].iter().all(|&lit| !lit)));
+
+        display.scroll_left(8);
+        assert!(display[13][16]);
+        assert_eq!(display.lit(), 1, "the pixel at x 4 scrolled off");
+        assert!(display.rows().all(|row| row[56..].iter().all(|&lit| !lit)));
+
+        display.scroll_down(32);
+        assert_eq!(display.lit(), 0);
+    }
+
+    #[test]
+    fn scrolling_in_hires_covers_the_whole_screen() {
+        let mut display = Display::new(Resolution::High);
+        display[0][127] = true;
+        display[60][0] = true;
+        display.scroll_down(3);
+        assert!(display[3][127] && display[63][0]);
+        display.scroll_left(4);
+        assert!(display[3][123]);
+        assert_eq!(display.lit(), 1);
+        display.scroll_right(200);
+        assert_eq!(display.lit(), 0);
+    }
+
+    #[test]
+    fn scrolling_up_blanks_the_bottom_rows_at_either_resolution() {
+        for resolution in [Resolution::Low, Resolution::High] {
+            let mut display = Display::new(resolution);
+            let bottom = display.height() - 1;
+            for (y, x) in [(3, 0), (4, 1), (10, 7), (bottom, 5)] {
+                display[y][x] = true;
+            }
+            display.scroll_up(3);
+            assert!(display[0][0] && display[1][1] && display[7][7] && display[bottom - 3][5]);
+            assert_eq!(display.lit(), 4, "{resolution:?}");
+            assert!(display.rows().skip(bottom - 2).flatten().all(|&lit| !lit));
+
+            display.scroll_up(1);
+            assert_eq!(display.lit(), 3, "the top row scrolled off");
+            display.scroll_up(200);
+            assert_eq!(display.lit(), 0);
+        }
+    }
+
+    #[test]
+    fn clears_and_scrolls_only_touch_the_selected_planes() {
+        let mut display = Display::default();
+        display.select_planes(0b11);
+        display.plane_mut(0)[0] = true;
+        display.plane_mut(1)[0] = true;
+        display.plane_mut(1)[1] = true;
+        assert_eq!((display.color(0), display.color(1), display.color(2)), (3, 2, 0));
+
+        display.select_planes(0b10);
+        display.scroll_right(1);
+        assert!(display.colors().take(3).eq([1, 2, 2]));
+        display.plane_mut(0)[65] = true;
+        display.select_planes(0b01);
+        display.scroll_up(1);
+        assert!(display.colors().take(3).eq([0, 3, 2]));
+        assert_eq!(display.color(65), 0);
+        display.select_planes(0b10);
+
+        display.set_resolution(Resolution::High);
+        assert_eq!((display.selected_planes(), display.lit()), (0b10, 0));
+        display.plane_mut(0)[5] = true;
+        display.plane_mut(1)[6] = true;
+        display.clear_selected();
+        assert_eq!((display.color(5), display.color(6)), (1, 0));
+
+        display.select_planes(0xFF);
+        assert_eq!(display.selected_planes(), 0b11);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rows_past_the_bottom_are_out_of_bounds() {
+        let display = Display::default();
+        let _ = display[32][0];
+    }
+}
@@ -0,0 +1,353 @@
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use crate::platform::Platform;
+use crate::stack::Stack;
+
+/// Everything that can go wrong while loading, running, or restoring a
+/// machine.
+#[derive(Debug, Error)]
+pub enum Chip8Error {
+    #[error("ROM is {size} bytes but at most {max} bytes fit in memory")]
+    RomTooLarge { size: usize, max: usize },
+
+    #[error("ROM is empty, so there's no program to run")]
+    EmptyRom,
+
+    #[error("invalid opcode 0x{opcode:04X} at 0x{pc:03X}")]
+    InvalidOpcode { opcode: u16, pc: u16 },
+
+    #[error("invalid opcode 0x{opcode:04X} at 0x{pc:03X}, which stops the machine")]
+    FatalOpcode { opcode: u16, pc: u16 },
+
+    #[error("this ROM is for {platform}, which isn't supported (opcode 0x{opcode:04X} at 0x{pc:03X})")]
+    UnsupportedPlatform { platform: Platform, opcode: u16, pc: u16 },
+
+    #[error("machine code call to 0x{addr:03X} at 0x{pc:03X}, which can't be emulated")]
+    MachineCodeCall { addr: u16, pc: u16 },
+
+    #[error("stack overflow: more than {depth} nested subroutine calls (call at 0x{pc:03X}, stack {stack})")]
+    StackOverflow { depth: usize, pc: u16, stack: Stack },
+
+    #[error("stack underflow: return with an empty call stack (instruction at 0x{pc:03X})")]
+    StackUnderflow { pc: u16 },
+
+    #[error("memory access at 0x{addr:04X} is out of bounds (instruction at 0x{pc:03X})")]
+    MemoryOutOfBounds { addr: usize, pc: u16 },
+
+    #[error("0x{opcode:04X} with I = 0x{i:03X} and X = {x} runs past the end of memory (instruction at 0x{pc:03X})")]
+    BlockOutOfBounds { opcode: u16, i: u16, x: u8, pc: u16 },
+
+    #[error("write to 0x{addr:03X} would overwrite the interpreter and font below 0x200 (instruction at 0x{pc:03X})")]
+    ProtectedWrite { addr: usize, pc: u16 },
+
+    #[error("key 0x{key:02X} does not exist on the keypad (instruction at 0x{pc:03X})")]
+    InvalidKey { key: u8, pc: u16 },
+
+    #[error("program counter 0x{pc:04X} is outside of memory")]
+    PcOutOfBounds { pc: u16 },
+
+    #[error("save state version {found} is not supported (expected {expected})")]
+    StateVersionMismatch { found: u16, expected: u16 },
+
+    #[error("save state is invalid: {reason}")]
+    InvalidState { reason: &'static str },
+
+    #[cfg(feature = "serde")]
+    #[error("state JSON is invalid: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Load(#[from] LoadError),
+
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl Chip8Error {
+    /// Whether the machine can keep running after this error. Unknown opcodes
+    /// are skipped, as they always have been, unless the
+    /// [opcode policy](crate::OpcodePolicy) makes them a
+    /// [`FatalOpcode`](Chip8Error::FatalOpcode); anything else stops the
+    /// machine.
+    pub fn is_recoverable(&self) -> bool {
+        matches!(self, Chip8Error::InvalidOpcode { .. })
+    }
+}
+
+/// Why a ROM file couldn't be loaded. Paths are absolute so the message
+/// makes sense regardless of the working directory.
+#[cfg(feature = "std")]
+#[derive(Debug, Error)]
+pub enum LoadError {
+    #[error("{} does not exist{}", .path.display(), suggestion_list(.suggestions))]
+    NotFound {
+        path: PathBuf,
+        /// Existing files the user may have meant, most likely first.
+        suggestions: Vec<PathBuf>,
+    },
+
+    #[error("permission denied reading {}", .path.display())]
+    PermissionDenied { path: PathBuf },
+
+    #[error("{} is a directory, not a ROM file", .path.display())]
+    IsDirectory { path: PathBuf },
+
+    #[error("{} is {size} bytes but at most {max} bytes fit in memory", .path.display())]
+    TooLarge { path: PathBuf, size: u64, max: usize },
+
+    #[error("{} is empty, so there's no program to run", .path.display())]
+    Empty { path: PathBuf },
+
+    #[error("could not read {}: {source}", .path.display())]
+    Io { path: PathBuf, source: io::Error },
+}
+
+#[cfg(feature = "std")]
+impl LoadError {
+    /// The absolute path of the file that failed to load.
+    pub fn path(&self) -> &Path {
+        match self {
+            LoadError::NotFound { path, .. }
+            | LoadError::PermissionDenied { path }
+            | LoadError::IsDirectory { path }
+            | LoadError::TooLarge { path, .. }
+            | LoadError::Empty { path }
+            | LoadError::Io { path, .. } => path,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+fn suggestion_list(suggestions: &[PathBuf]) -> String {
+    let mut list = String::new();
+    if !suggestions.is_empty() {
+        list.push_str("\nDid you mean:");
+        for suggestion in suggestions {
+            list.push_str(&format!("\n  {}", suggestion.display()));
+        }
+    }
+    list
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::chip8::{Chip8, PROGRAM_START};
+    use crate::state::STATE_VERSION;
+
+    fn run(program: &[u8], steps: usize) -> String {
+        let mut chip8 = Chip8::new();
+        chip8.load_rom_bytes(program).unwrap();
+        for _ in 0..steps {
+            if let Err(e) = chip8.step() {
+                return e.to_string();
+            }
+        }
+        panic!("program ran without an error");
+    }
+
+    #[test]
+    fn rom_too_large() {
+        let mut chip8 = Chip8::new();
+        let e = chip8.load_rom_bytes(&[0; 3585]).unwrap_err();
+        assert_eq!(e.to_string(), "ROM is 3585 bytes but at most 3584 bytes fit in memory");
+    }
+
+    #[test]
+    fn empty_rom() {
+        let mut chip8 = Chip8::new();
+        let e = chip8.load_rom_bytes(&[]).unwrap_err();
+        assert_eq!(e.to_string(), "ROM is empty, so there's no program to run");
+    }
+
+    #[test]
+    fn invalid_opcode() {
+        assert_eq!(run(&[0x00, 0xE0, 0x0A, 0x23], 2), "invalid opcode 0x0A23 at 0x202");
+    }
+
+    #[test]
+    fn fatal_opcode() {
+        let mut chip8 = Chip8::new();
+        chip8.opcode_policy = crate::OpcodePolicy::Halt;
+        chip8.load_rom_bytes(&[0x00, 0xE0, 0x0A, 0x23]).unwrap();
+        chip8.step().unwrap();
+        let e = chip8.step().unwrap_err();
+        assert_eq!(e.to_string(), "invalid opcode 0x0A23 at 0x202, which stops the machine");
+        assert!(!e.is_recoverable());
+    }
+
+    #[test]
+    fn unsupported_platform() {
+        assert_eq!(
+            run(&[0x00, 0xE0, 0x00, 0x11], 2),
+            "this ROM is for MegaChip, which isn't supported (opcode 0x0011 at 0x202)"
+        );
+        let mut chip8 = Chip8::new();
+        chip8.load_rom_bytes(&[0xF0, 0xFB]).unwrap();
+        assert!(!chip8.step().unwrap_err().is_recoverable());
+    }
+
+    #[test]
+    fn stack_overflow() {
+        // 200: 6001  V0 = 1
+        // 202: 2202  call 0x202 forever
+        assert_eq!(
+            run(&[0x60, 0x01, 0x22, 0x02], 18),
+            "stack overflow: more than 16 nested subroutine calls (call at 0x202, stack [204, 204, 204, 204, \
+             204, 204, 204, 204, 204, 204, 204, 204, 204, 204, 204, 204])"
+        );
+    }
+
+    #[test]
+    fn stack_underflow() {
+        // 200: 00EE  return from the entry point
+        assert_eq!(
+            run(&[0x00, 0xEE], 1),
+            "stack underflow: return with an empty call stack (instruction at 0x200)"
+        );
+    }
+
+    #[test]
+    fn stack_errors_halt_the_machine() {
+        let mut chip8 = Chip8::new();
+        chip8.load_rom_bytes(&[0x22, 0x00]).unwrap();
+        for _ in 0..16 {
+            chip8.step().unwrap();
+        }
+        let e = chip8.step().unwrap_err();
+        assert!(matches!(e, super::Chip8Error::StackOverflow { depth: 16, pc: 0x200, .. }));
+        assert!(!e.is_recoverable());
+
+        let mut chip8 = Chip8::new();
+        chip8.load_rom_bytes(&[0x00, 0xEE]).unwrap();
+        let e = chip8.step().unwrap_err();
+        assert!(matches!(e, super::Chip8Error::StackUnderflow { pc: 0x200 }));
+        assert!(!e.is_recoverable());
+    }
+
+    #[test]
+    fn memory_out_of_bounds() {
+        // 200: AFFF  I = 0xFFF
+        // 202: 6063  V0 = 99
+        // 204: F033  BCD of V0 needs 0xFFF..=0x1001
+        assert_eq!(
+            run(&[0xAF, 0xFF, 0x60, 0x63, 0xF0, 0x33], 3),
+            "0xF033 with I = 0xFFF and X = 0 runs past the end of memory (instruction at 0x204)"
+        );
+    }
+
+    #[test]
+    fn invalid_key() {
+        // 200: 6010  V0 = 0x10
+        // 202: E09E  skip if key V0 is down
+        assert_eq!(
+            run(&[0x60, 0x10, 0xE0, 0x9E], 2),
+            "key 0x10 does not exist on the keypad (instruction at 0x202)"
+        );
+    }
+
+    #[test]
+    fn pc_out_of_bounds() {
+        // 200: 1FFF  jump to the last byte of memory
+        assert_eq!(run(&[0x1F, 0xFF], 2), "program counter 0x0FFF is outside of memory");
+    }
+
+    #[test]
+    fn state_version_mismatch() {
+        let mut state = Chip8::new().save_state();
+        state[4..6].copy_from_slice(&(STATE_VERSION + 1).to_le_bytes());
+
+        let e = Chip8::new().load_state(&state).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            format!(
+                "save state version {} is not supported (expected {})",
+                STATE_VERSION + 1,
+                STATE_VERSION
+            )
+        );
+    }
+
+    #[test]
+    fn invalid_state() {
+        let state = Chip8::new().save_state();
+
+        let e = Chip8::new().load_state(&state[..100]).unwrap_err();
+        assert_eq!(e.to_string(), "save state is invalid: truncated");
+
+        let e = Chip8::new().load_state(b"nope").unwrap_err();
+        assert_eq!(e.to_string(), "save state is invalid: missing C8ST header");
+
+        // A 64 KB state doesn't fit a 4 KB machine.
+        let mut xo = Chip8::new();
+        xo.quirks.extended_memory = true;
+        let e = Chip8::new().load_state(&xo.save_state()).unwrap_err();
+        assert_eq!(e.to_string(), "save state is invalid: memory size doesn't match the extended-memory quirk");
+
+        // The resolution comes after the memory size, memory, registers,
+        // empty stack and timers.
+        let mut state = state;
+        state[7 + 4096 + 16 + 2 + 2 + 1 + 2] = 3;
+        let e = Chip8::new().load_state(&state).unwrap_err();
+        assert_eq!(e.to_string(), "save state is invalid: unknown display resolution");
+
+        // Then the planes FN01 selected.
+        state[7 + 4096 + 16 + 2 + 2 + 1 + 2] = 0;
+        state[7 + 4096 + 16 + 2 + 2 + 1 + 2 + 1] = 4;
+        let e = Chip8::new().load_state(&state).unwrap_err();
+        assert_eq!(e.to_string(), "save state is invalid: unknown display planes");
+
+        // And whether there's an audio pattern, after both planes.
+        state[7 + 4096 + 16 + 2 + 2 + 1 + 2 + 1] = 1;
+        state[7 + 4096 + 16 + 2 + 2 + 1 + 2 + 1 + 1 + 2 * 256] = 2;
+        let e = Chip8::new().load_state(&state).unwrap_err();
+        assert_eq!(e.to_string(), "save state is invalid: unknown audio pattern flag");
+
+        // CHIP-8X's background comes after the pattern and pitch.
+        state[7 + 4096 + 16 + 2 + 2 + 1 + 2 + 1 + 1 + 2 * 256] = 0;
+        state[7 + 4096 + 16 + 2 + 2 + 1 + 2 + 1 + 1 + 2 * 256 + 1 + 16 + 1] = 4;
+        let e = Chip8::new().load_state(&state).unwrap_err();
+        assert_eq!(e.to_string(), "save state is invalid: unknown CHIP-8X color");
+    }
+
+    #[test]
+    fn load() {
+        let e = Chip8::new().load_rom("does/not/exist.ch8").unwrap_err();
+        assert!(matches!(e, super::Chip8Error::Load(_)));
+        assert!(e.to_string().ends_with("exist.ch8 does not exist"), "{}", e);
+    }
+
+    #[test]
+    fn io() {
+        let io = std::io::Error::other("disk on fire");
+        assert_eq!(super::Chip8Error::from(io).to_string(), "disk on fire");
+    }
+
+    #[test]
+    fn state_round_trip() {
+        let mut chip8 = Chip8::new();
+        chip8.load_rom_bytes(&[0x60, 0x05, 0x22, 0x06, 0x00, 0x00, 0xD0, 0x05]).unwrap();
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        chip8.delay_timer = 7;
+
+        let mut restored = Chip8::new();
+        restored.load_state(&chip8.save_state()).unwrap();
+
+        assert_eq!(restored.save_state(), chip8.save_state());
+        assert_eq!(restored.pc, PROGRAM_START + 8);
+        assert_eq!(restored.stack.as_slice(), [PROGRAM_START + 4]);
+
+        chip8.display.set_resolution(crate::Resolution::High);
+        chip8.display[40][100] = true;
+        restored.load_state(&chip8.save_state()).unwrap();
+        assert_eq!(restored.display, chip8.display);
+    }
+}
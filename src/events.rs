@@ -0,0 +1,25 @@
+//! Notifications of what the machine does, for visualizers that would
+//! rather be told than poll; see [`Chip8::set_observer`](crate::Chip8::set_observer).
+
+/// Something the machine just did. Each is sent after the change it
+/// describes has been made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MachineEvent {
+    /// 00E0, or HI-RES CHIP-8's 0230, cleared the selected planes.
+    DisplayCleared,
+    /// DXYN drew `rows` rows at (`x`, `y`), VX and VY as they were before
+    /// VF took the collision.
+    SpriteDrawn { x: u8, y: u8, rows: u8, collision: bool },
+    /// The sound timer went from zero to more, so the buzzer is on.
+    SoundStarted,
+    /// It's back to zero, counted down or set there by FX18.
+    SoundStopped,
+    /// An opcode the interpreter doesn't know, before
+    /// [`OpcodePolicy`](crate::OpcodePolicy) decides what to do with it.
+    UnknownOpcode { opcode: u16, pc: u16 },
+    /// The program ended at `pc`, as [`Chip8::finished`](crate::Chip8::finished)
+    /// says, on the step that got it there.
+    Finished { pc: u16 },
+    /// A step failed with an error the machine can't go on from.
+    Halted { pc: u16 },
+}
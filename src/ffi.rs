@@ -0,0 +1,473 @@
+//! A flat C API for embedding the interpreter, declared in `include/chip8.h`.
+//!
+//! Build the shared library with
+//! `cargo rustc --lib --release --no-default-features --features ffi --crate-type cdylib`.
+//!
+//! Every function checks its pointers and catches panics, reporting both as a
+//! [`Chip8Status`] instead of crashing the host application.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use crate::chip8::Chip8;
+use crate::error::Chip8Error;
+use crate::frontend::DEFAULT_INSTRUCTIONS_PER_FRAME;
+use crate::quirks::Quirks;
+
+/// Display width in pixels, until a SUPER-CHIP program switches to 128x64.
+pub const CHIP8_DISPLAY_WIDTH: usize = 64;
+/// Display height in pixels, until a SUPER-CHIP program switches to 128x64.
+pub const CHIP8_DISPLAY_HEIGHT: usize = 32;
+
+/// Quirk flag for `chip8_set_quirks`: 8XY6/8XYE copy VY into VX before
+/// shifting.
+pub const CHIP8_QUIRK_SHIFT_USES_VY: u32 = 1 << 0;
+/// Quirk flag for `chip8_set_quirks`: FX1E sets VF when I goes past 0xFFF.
+pub const CHIP8_QUIRK_I_OVERFLOW_SETS_VF: u32 = 1 << 1;
+/// Quirk flag for `chip8_set_quirks`: FX55/FX65 leave I at I + X + 1.
+pub const CHIP8_QUIRK_LOAD_STORE_INCREMENTS_I: u32 = 1 << 2;
+/// Quirk flag for `chip8_set_quirks`: DXYN wraps sprites around the screen
+/// edges instead of clipping them.
+pub const CHIP8_QUIRK_WRAP_SPRITES: u32 = 1 << 3;
+/// Quirk flag for `chip8_set_quirks`: `chip8_run_frame` stops after a DXYN.
+pub const CHIP8_QUIRK_DISPLAY_WAIT: u32 = 1 << 4;
+/// Quirk flag for `chip8_set_quirks`: 8XY1/8XY2/8XY3 clear VF.
+pub const CHIP8_QUIRK_VF_RESET: u32 = 1 << 5;
+/// Quirk flag for `chip8_set_quirks`: BXNN jumps to XNN plus VX.
+pub const CHIP8_QUIRK_JUMP_USES_VX: u32 = 1 << 6;
+/// Quirk flag for `chip8_set_quirks`: 00CN/00FB/00FC scroll half as far in
+/// 64x32 mode.
+pub const CHIP8_QUIRK_HALF_SCROLL: u32 = 1 << 7;
+/// Quirk flag for `chip8_set_quirks`: memory is XO-CHIP's 64 KB, with
+/// F000 NNNN.
+pub const CHIP8_QUIRK_EXTENDED_MEMORY: u32 = 1 << 8;
+/// Quirk flag for `chip8_set_quirks`: FX55/FX65 leave I at I + X, winning
+/// over `CHIP8_QUIRK_LOAD_STORE_INCREMENTS_I`.
+pub const CHIP8_QUIRK_LOAD_STORE_INCREMENTS_I_BY_X: u32 = 1 << 9;
+/// Quirk flag for `chip8_set_quirks`: HI-RES CHIP-8 ROMs switch to 64x64.
+pub const CHIP8_QUIRK_TWO_PAGE_HIRES: u32 = 1 << 10;
+/// Quirk flag for `chip8_set_quirks`: CHIP-8X's color and other
+/// instructions run.
+pub const CHIP8_QUIRK_CHIP8X: u32 = 1 << 11;
+const KNOWN_QUIRKS: u32 = CHIP8_QUIRK_SHIFT_USES_VY
+    | CHIP8_QUIRK_I_OVERFLOW_SETS_VF
+    | CHIP8_QUIRK_LOAD_STORE_INCREMENTS_I
+    | CHIP8_QUIRK_WRAP_SPRITES
+    | CHIP8_QUIRK_DISPLAY_WAIT
+    | CHIP8_QUIRK_VF_RESET
+    | CHIP8_QUIRK_JUMP_USES_VX
+    | CHIP8_QUIRK_HALF_SCROLL
+    | CHIP8_QUIRK_EXTENDED_MEMORY
+    | CHIP8_QUIRK_LOAD_STORE_INCREMENTS_I_BY_X
+    | CHIP8_QUIRK_TWO_PAGE_HIRES
+    | CHIP8_QUIRK_CHIP8X;
+
+/// A machine owned by C code, created with `chip8_new` and released with
+/// `chip8_free`.
+pub struct Chip8Machine {
+    chip8: Chip8,
+    instructions_per_frame: u32,
+}
+
+/// Result of every call. The variants after `Panic` mirror `Chip8Error`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Status {
+    Ok = 0,
+    /// A required pointer was null.
+    NullPointer,
+    /// An argument was out of range, such as a too small buffer.
+    InvalidArgument,
+    /// The interpreter panicked; the machine may be in an inconsistent state.
+    Panic,
+    RomTooLarge,
+    InvalidOpcode,
+    StackOverflow,
+    StackUnderflow,
+    MemoryOutOfBounds,
+    InvalidKey,
+    PcOutOfBounds,
+    StateVersionMismatch,
+    InvalidState,
+    Load,
+    Io,
+    UnsupportedPlatform,
+    MachineCodeCall,
+    ProtectedWrite,
+    BlockOutOfBounds,
+    FatalOpcode,
+    EmptyRom,
+}
+
+impl From<&Chip8Error> for Chip8Status {
+    fn from(error: &Chip8Error) -> Self {
+        match error {
+            Chip8Error::RomTooLarge { .. } => Chip8Status::RomTooLarge,
+            Chip8Error::EmptyRom => Chip8Status::EmptyRom,
+            Chip8Error::InvalidOpcode { .. } => Chip8Status::InvalidOpcode,
+            Chip8Error::FatalOpcode { .. } => Chip8Status::FatalOpcode,
+            Chip8Error::UnsupportedPlatform { .. } => Chip8Status::UnsupportedPlatform,
+            Chip8Error::MachineCodeCall { .. } => Chip8Status::MachineCodeCall,
+            Chip8Error::ProtectedWrite { .. } => Chip8Status::ProtectedWrite,
+            Chip8Error::BlockOutOfBounds { .. } => Chip8Status::BlockOutOfBounds,
+            Chip8Error::StackOverflow { .. } => Chip8Status::StackOverflow,
+            Chip8Error::StackUnderflow { .. } => Chip8Status::StackUnderflow,
+            Chip8Error::MemoryOutOfBounds { .. } => Chip8Status::MemoryOutOfBounds,
+            Chip8Error::InvalidKey { .. } => Chip8Status::InvalidKey,
+            Chip8Error::PcOutOfBounds { .. } => Chip8Status::PcOutOfBounds,
+            Chip8Error::StateVersionMismatch { .. } => Chip8Status::StateVersionMismatch,
+            Chip8Error::InvalidState { .. } => Chip8Status::InvalidState,
+            #[cfg(feature = "serde")]
+            Chip8Error::Json(_) => Chip8Status::InvalidState,
+            Chip8Error::Load(_) => Chip8Status::Load,
+            Chip8Error::Io(_) => Chip8Status::Io,
+        }
+    }
+}
+
+/// Runs `f` on the machine behind `machine`, turning errors and panics into a
+/// status.
+///
+/// # Safety
+///
+/// `machine` must be null or a live pointer from [`chip8_new`].
+unsafe fn with_machine(
+    machine: *mut Chip8Machine,
+    f: impl FnOnce(&mut Chip8Machine) -> Result<Chip8Status, Chip8Error>,
+) -> Chip8Status {
+    // SAFETY: the caller guarantees a non-null pointer came from chip8_new.
+    let Some(machine) = (unsafe { machine.as_mut() }) else {
+        return Chip8Status::NullPointer;
+    };
+    match panic::catch_unwind(AssertUnwindSafe(|| f(machine))) {
+        Ok(Ok(status)) => status,
+        Ok(Err(e)) => Chip8Status::from(&e),
+        Err(_) => Chip8Status::Panic,
+    }
+}
+
+/// Creates a machine with an empty program, or returns null if that fails.
+#[unsafe(no_mangle)]
+pub extern "C" fn chip8_new() -> *mut Chip8Machine {
+    panic::catch_unwind(|| {
+        Box::into_raw(Box::new(Chip8Machine {
+            chip8: Chip8::new(),
+            instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME,
+        }))
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Releases a machine. Null is ignored.
+///
+/// # Safety
+///
+/// `machine` must be null or a pointer from [`chip8_new`] that hasn't been
+/// freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_free(machine: *mut Chip8Machine) {
+    if !machine.is_null() {
+        // SAFETY: the caller guarantees the pointer came from chip8_new and
+        // is freed only once.
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(unsafe { Box::from_raw(machine) })));
+    }
+}
+
+/// Copies `len` bytes of program from `rom` into memory at 0x200. The rest
+/// of the machine is left as it is, so load into a fresh one.
+///
+/// # Safety
+///
+/// `machine` must be null or live, and `rom` must be null or point to `len`
+/// readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_load_rom(
+    machine: *mut Chip8Machine,
+    rom: *const u8,
+    len: usize,
+) -> Chip8Status {
+    if rom.is_null() {
+        return Chip8Status::NullPointer;
+    }
+    // SAFETY: the caller guarantees `rom` points to `len` bytes.
+    let rom = unsafe { slice::from_raw_parts(rom, len) };
+    // SAFETY: forwarded from the caller.
+    unsafe {
+        with_machine(machine, |machine| {
+            machine.chip8.load_rom_bytes(rom)?;
+            Ok(Chip8Status::Ok)
+        })
+    }
+}
+
+/// Reseeds the CXNN random generator so runs can be reproduced.
+///
+/// # Safety
+///
+/// `machine` must be null or live.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_seed(machine: *mut Chip8Machine, seed: u64) -> Chip8Status {
+    // SAFETY: forwarded from the caller.
+    unsafe {
+        with_machine(machine, |machine| {
+            machine.chip8.seed_rng(seed);
+            Ok(Chip8Status::Ok)
+        })
+    }
+}
+
+/// Sets the quirks from a combination of `CHIP8_QUIRK_*` flags, replacing
+/// the previous ones. Unknown flags are rejected with `InvalidArgument`.
+///
+/// # Safety
+///
+/// `machine` must be null or live.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_set_quirks(machine: *mut Chip8Machine, flags: u32) -> Chip8Status {
+    // SAFETY: forwarded from the caller.
+    unsafe {
+        with_machine(machine, |machine| {
+            if flags & !KNOWN_QUIRKS != 0 {
+                return Ok(Chip8Status::InvalidArgument);
+            }
+            machine.chip8.quirks = Quirks {
+                shift_uses_vy: flags & CHIP8_QUIRK_SHIFT_USES_VY != 0,
+                i_overflow_sets_vf: flags & CHIP8_QUIRK_I_OVERFLOW_SETS_VF != 0,
+                load_store_increments_i: flags & CHIP8_QUIRK_LOAD_STORE_INCREMENTS_I != 0,
+                wrap_sprites: flags & CHIP8_QUIRK_WRAP_SPRITES != 0,
+                display_wait: flags & CHIP8_QUIRK_DISPLAY_WAIT != 0,
+                vf_reset: flags & CHIP8_QUIRK_VF_RESET != 0,
+                jump_uses_vx: flags & CHIP8_QUIRK_JUMP_USES_VX != 0,
+                half_scroll: flags & CHIP8_QUIRK_HALF_SCROLL != 0,
+                extended_memory: flags & CHIP8_QUIRK_EXTENDED_MEMORY != 0,
+                load_store_increments_i_by_x: flags & CHIP8_QUIRK_LOAD_STORE_INCREMENTS_I_BY_X != 0,
+                two_page_hires: flags & CHIP8_QUIRK_TWO_PAGE_HIRES != 0,
+                chip8x: flags & CHIP8_QUIRK_CHIP8X != 0,
+            };
+            Ok(Chip8Status::Ok)
+        })
+    }
+}
+
+/// Executes one instruction.
+///
+/// # Safety
+///
+/// `machine` must be null or live.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_step(machine: *mut Chip8Machine) -> Chip8Status {
+    // SAFETY: forwarded from the caller.
+    unsafe {
+        with_machine(machine, |machine| {
+            machine.chip8.step()?;
+            Ok(Chip8Status::Ok)
+        })
+    }
+}
+
+/// Runs one 60 Hz frame with the keypad state `keys`, where bit N is set
+/// while key N is held, then ticks the timers. Unknown opcodes are skipped
+/// as in the desktop emulator.
+///
+/// # Safety
+///
+/// `machine` must be null or live.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_run_frame(machine: *mut Chip8Machine, keys: u16) -> Chip8Status {
+    // SAFETY: forwarded from the caller.
+    unsafe {
+        with_machine(machine, |machine| {
+            for (index, key) in machine.chip8.keys.iter_mut().enumerate() {
+                *key = keys & (1 << index) != 0;
+            }
+            machine.chip8.emulate_frames(1, machine.instructions_per_frame)?;
+            Ok(Chip8Status::Ok)
+        })
+    }
+}
+
+/// Sets how many instructions `chip8_run_frame` executes; the default is 10.
+///
+/// # Safety
+///
+/// `machine` must be null or live.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_set_instructions_per_frame(
+    machine: *mut Chip8Machine,
+    count: u32,
+) -> Chip8Status {
+    // SAFETY: forwarded from the caller.
+    unsafe {
+        with_machine(machine, |machine| {
+            machine.instructions_per_frame = count;
+            Ok(Chip8Status::Ok)
+        })
+    }
+}
+
+/// Copies the display into `out`, one byte per pixel (1 lit, 0 unlit), row
+/// by row. `len` must be at least `chip8_display_width(machine) *
+/// chip8_display_height(machine)`.
+///
+/// # Safety
+///
+/// `machine` must be null or live, and `out` must be null or point to `len`
+/// writable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_display(
+    machine: *mut Chip8Machine,
+    out: *mut u8,
+    len: usize,
+) -> Chip8Status {
+    if out.is_null() {
+        return Chip8Status::NullPointer;
+    }
+    // SAFETY: the caller guarantees `out` points to `len` writable bytes.
+    let out = unsafe { slice::from_raw_parts_mut(out, len) };
+    // SAFETY: forwarded from the caller.
+    unsafe {
+        with_machine(machine, |machine| {
+            let pixels = machine.chip8.display.pixels();
+            if len < pixels.len() {
+                return Ok(Chip8Status::InvalidArgument);
+            }
+            for (byte, &lit) in out.iter_mut().zip(pixels) {
+                *byte = lit as u8;
+            }
+            Ok(Chip8Status::Ok)
+        })
+    }
+}
+
+/// Display width in pixels right now, 64 or 128. Zero for a null machine.
+///
+/// # Safety
+///
+/// `machine` must be null or live.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_display_width(machine: *const Chip8Machine) -> usize {
+    // SAFETY: the caller guarantees a non-null pointer came from chip8_new.
+    unsafe { machine.as_ref() }.map_or(0, |machine| machine.chip8.display.width())
+}
+
+/// Display height in pixels right now, 32 or 64. Zero for a null machine.
+///
+/// # Safety
+///
+/// `machine` must be null or live.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_display_height(machine: *const Chip8Machine) -> usize {
+    // SAFETY: the caller guarantees a non-null pointer came from chip8_new.
+    unsafe { machine.as_ref() }.map_or(0, |machine| machine.chip8.display.height())
+}
+
+/// Whether the buzzer should be sounding. False for a null machine.
+///
+/// # Safety
+///
+/// `machine` must be null or live.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_sound_active(machine: *const Chip8Machine) -> bool {
+    // SAFETY: the caller guarantees a non-null pointer came from chip8_new.
+    unsafe { machine.as_ref() }.is_some_and(|machine| machine.chip8.sound_timer > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn null_pointers_are_reported() {
+        unsafe {
+            assert_eq!(chip8_step(ptr::null_mut()), Chip8Status::NullPointer);
+            assert_eq!(chip8_run_frame(ptr::null_mut(), 0), Chip8Status::NullPointer);
+            assert!(!chip8_sound_active(ptr::null()));
+            assert_eq!(chip8_display_width(ptr::null()), 0);
+            chip8_free(ptr::null_mut());
+
+            let machine = chip8_new();
+            assert_eq!(chip8_load_rom(machine, ptr::null(), 2), Chip8Status::NullPointer);
+            assert_eq!(chip8_display(machine, ptr::null_mut(), 2048), Chip8Status::NullPointer);
+            chip8_free(machine);
+        }
+    }
+
+    #[test]
+    fn errors_map_to_statuses() {
+        unsafe {
+            let machine = chip8_new();
+            // 200: 00EE  return with an empty stack
+            assert_eq!(chip8_load_rom(machine, [0x00, 0xEE].as_ptr(), 2), Chip8Status::Ok);
+            assert_eq!(chip8_step(machine), Chip8Status::StackUnderflow);
+
+            let rom = [0u8; 4096];
+            assert_eq!(chip8_load_rom(machine, rom.as_ptr(), rom.len()), Chip8Status::RomTooLarge);
+            assert_eq!(chip8_load_rom(machine, rom.as_ptr(), 0), Chip8Status::EmptyRom);
+            assert_eq!(chip8_set_quirks(machine, 1 << 31), Chip8Status::InvalidArgument);
+            assert_eq!(chip8_set_quirks(machine, CHIP8_QUIRK_SHIFT_USES_VY), Chip8Status::Ok);
+            assert!((*machine).chip8.quirks.shift_uses_vy);
+            assert!(!(*machine).chip8.quirks.i_overflow_sets_vf);
+            assert_eq!(chip8_set_quirks(machine, CHIP8_QUIRK_I_OVERFLOW_SETS_VF), Chip8Status::Ok);
+            assert!((*machine).chip8.quirks.i_overflow_sets_vf && !(*machine).chip8.quirks.shift_uses_vy);
+            assert_eq!(chip8_set_quirks(machine, CHIP8_QUIRK_LOAD_STORE_INCREMENTS_I), Chip8Status::Ok);
+            assert!((*machine).chip8.quirks.load_store_increments_i && !(*machine).chip8.quirks.i_overflow_sets_vf);
+            assert_eq!(chip8_set_quirks(machine, CHIP8_QUIRK_WRAP_SPRITES), Chip8Status::Ok);
+            assert!((*machine).chip8.quirks.wrap_sprites && !(*machine).chip8.quirks.load_store_increments_i);
+            assert_eq!(chip8_set_quirks(machine, CHIP8_QUIRK_DISPLAY_WAIT), Chip8Status::Ok);
+            assert!((*machine).chip8.quirks.display_wait && !(*machine).chip8.quirks.wrap_sprites);
+            assert_eq!(chip8_set_quirks(machine, CHIP8_QUIRK_VF_RESET), Chip8Status::Ok);
+            assert!((*machine).chip8.quirks.vf_reset && !(*machine).chip8.quirks.display_wait);
+            assert_eq!(chip8_set_quirks(machine, CHIP8_QUIRK_JUMP_USES_VX), Chip8Status::Ok);
+            assert!((*machine).chip8.quirks.jump_uses_vx && !(*machine).chip8.quirks.vf_reset);
+            assert_eq!(chip8_set_quirks(machine, CHIP8_QUIRK_HALF_SCROLL), Chip8Status::Ok);
+            assert!((*machine).chip8.quirks.half_scroll && !(*machine).chip8.quirks.jump_uses_vx);
+            assert_eq!(chip8_set_quirks(machine, CHIP8_QUIRK_EXTENDED_MEMORY), Chip8Status::Ok);
+            assert!((*machine).chip8.quirks.extended_memory && !(*machine).chip8.quirks.half_scroll);
+            assert_eq!(chip8_set_quirks(machine, CHIP8_QUIRK_LOAD_STORE_INCREMENTS_I_BY_X), Chip8Status::Ok);
+            assert!(
+                (*machine).chip8.quirks.load_store_increments_i_by_x && !(*machine).chip8.quirks.extended_memory
+            );
+            assert_eq!(chip8_set_quirks(machine, CHIP8_QUIRK_TWO_PAGE_HIRES), Chip8Status::Ok);
+            assert!((*machine).chip8.quirks.two_page_hires && !(*machine).chip8.quirks.load_store_increments_i_by_x);
+            assert_eq!(chip8_set_quirks(machine, CHIP8_QUIRK_CHIP8X), Chip8Status::Ok);
+            assert!((*machine).chip8.quirks.chip8x && !(*machine).chip8.quirks.two_page_hires);
+
+            let mut small = [0u8; 16];
+            assert_eq!(
+                chip8_display(machine, small.as_mut_ptr(), small.len()),
+                Chip8Status::InvalidArgument
+            );
+            chip8_free(machine);
+        }
+    }
+
+    #[test]
+    fn hires_displays_need_a_bigger_buffer() {
+        unsafe {
+            let machine = chip8_new();
+            // 200: 00FF  128x64
+            assert_eq!(chip8_load_rom(machine, [0x00, 0xFF].as_ptr(), 2), Chip8Status::Ok);
+            assert_eq!((chip8_display_width(machine), chip8_display_height(machine)), (64, 32));
+            assert_eq!(chip8_step(machine), Chip8Status::Ok);
+            assert_eq!((chip8_display_width(machine), chip8_display_height(machine)), (128, 64));
+
+            let mut lores = [0u8; CHIP8_DISPLAY_WIDTH * CHIP8_DISPLAY_HEIGHT];
+            assert_eq!(chip8_display(machine, lores.as_mut_ptr(), lores.len()), Chip8Status::InvalidArgument);
+            let mut hires = [1u8; 128 * 64];
+            assert_eq!(chip8_display(machine, hires.as_mut_ptr(), hires.len()), Chip8Status::Ok);
+            assert!(hires.iter().all(|&lit| lit == 0));
+            chip8_free(machine);
+        }
+    }
+
+    #[test]
+    fn panics_become_a_status() {
+        let mut machine = Chip8Machine {
+            chip8: Chip8::new(),
+            instructions_per_frame: 1,
+        };
+        let status = unsafe { with_machine(&mut machine, |_| panic!("boom")) };
+        assert_eq!(status, Chip8Status::Panic);
+    }
+}
@@ -0,0 +1,119 @@
+//! Turning the display into rows of character cells, for frontends that draw
+//! with text.
+
+use super::Palette;
+
+/// Width of the rendered grid in cells.
+pub const CELL_COLUMNS: usize = 64;
+/// Height of the rendered grid in cells: each cell covers two pixel rows.
+pub const CELL_ROWS: usize = 16;
+
+/// Upper half block: the foreground color paints the top pixel, the
+/// background color the bottom one.
+pub const HALF_BLOCK: char = '▀';
+
+/// One styled character cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub ch: char,
+    pub fg: u32,
+    pub bg: u32,
+}
+
+/// Renders a `width` x `height` display as [`CELL_ROWS`] rows of
+/// [`CELL_COLUMNS`] half-block cells.
+///
+/// Displays larger than 64x32, such as the 128x64 hires mode, are
+/// downsampled first; a downsampled pixel is lit if any pixel it covers is.
+pub fn render_cells(pixels: &[bool], width: usize, height: usize, palette: Palette) -> Vec<Vec<Cell>> {
+    let factor = (width / CELL_COLUMNS).max(1);
+    let lit = |x: usize, y: usize| {
+        (0..factor).any(|dy| {
+            (0..factor).any(|dx| {
+                let (px, py) = (x * factor + dx, y * factor + dy);
+                px < width && py < height && pixels[py * width + px]
+            })
+        })
+    };
+
+    (0..CELL_ROWS)
+        .map(|row| {
+            (0..CELL_COLUMNS)
+                .map(|column| Cell {
+                    ch: HALF_BLOCK,
+                    fg: palette.color(lit(column, row * 2)),
+                    bg: palette.color(lit(column, row * 2 + 1)),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PALETTE: Palette = Palette { off: 0, on: 1, plane2: 2, both: 3 };
+
+    fn display(width: usize, height: usize, lit: &[(usize, usize)]) -> Vec<bool> {
+        let mut pixels = vec![false; width * height];
+        for &(x, y) in lit {
+            pixels[y * width + x] = true;
+        }
+        pixels
+    }
+
+    #[test]
+    fn each_cell_covers_two_rows() {
+        let pixels = display(64, 32, &[(0, 0), (1, 1), (2, 0), (2, 1), (63, 31)]);
+        let cells = render_cells(&pixels, 64, 32, PALETTE);
+
+        assert_eq!(cells.len(), CELL_ROWS);
+        assert!(cells.iter().all(|row| row.len() == CELL_COLUMNS));
+
+        let colors = |cell: Cell| (cell.fg, cell.bg);
+        assert_eq!(colors(cells[0][0]), (1, 0));
+        assert_eq!(colors(cells[0][1]), (0, 1));
+        assert_eq!(colors(cells[0][2]), (1, 1));
+        assert_eq!(colors(cells[0][3]), (0, 0));
+        assert_eq!(colors(cells[15][63]), (0, 1));
+        assert!(cells.iter().flatten().all(|cell| cell.ch == HALF_BLOCK));
+    }
+
+    #[test]
+    fn uses_the_palette() {
+        let palette = Palette {
+            off: 0x112233,
+            on: 0xAABBCC,
+            ..Palette::default()
+        };
+        let cells = render_cells(&display(64, 32, &[(5, 7)]), 64, 32, palette);
+
+        assert_eq!(
+            cells[3][5],
+            Cell {
+                ch: HALF_BLOCK,
+                fg: 0x112233,
+                bg: 0xAABBCC
+            }
+        );
+        assert_eq!(cells[0][0].fg, 0x112233);
+    }
+
+    #[test]
+    fn hires_displays_are_downsampled() {
+        // Any lit pixel within a 2x2 block lights the downsampled pixel.
+        let pixels = display(128, 64, &[(1, 1), (126, 0), (4, 2), (5, 3), (127, 63)]);
+        let cells = render_cells(&pixels, 128, 64, PALETTE);
+
+        assert_eq!(cells.len(), CELL_ROWS);
+        assert_eq!((cells[0][0].fg, cells[0][0].bg), (1, 0));
+        assert_eq!((cells[0][63].fg, cells[0][63].bg), (1, 0));
+        assert_eq!((cells[0][2].fg, cells[0][2].bg), (0, 1));
+        assert_eq!((cells[15][63].fg, cells[15][63].bg), (0, 1));
+
+        let lit = cells.iter().flatten().filter(|c| c.fg == 1).count()
+            + cells.iter().flatten().filter(|c| c.bg == 1).count();
+        assert_eq!(lit, 4);
+    }
+}
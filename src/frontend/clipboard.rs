@@ -0,0 +1,204 @@
+//! Copying the display to the clipboard: as an image where the frontend's
+//! clipboard takes images, or else as the same ASCII art the `txt` dump
+//! prints.
+
+use crate::display::Display;
+use crate::pretty::Screen;
+use crate::render::Palette;
+
+/// Window pixels per CHIP-8 pixel in a copied image, big enough to see
+/// without zooming in.
+pub const CLIPBOARD_SCALE: usize = 4;
+
+/// The display as an RGBA image, four bytes a pixel, rows top to bottom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClipboardImage {
+    pub width: usize,
+    pub height: usize,
+    pub rgba: Vec<u8>,
+}
+
+impl ClipboardImage {
+    /// `display` in `palette`'s colors at [`CLIPBOARD_SCALE`].
+    pub fn new(display: &Display, palette: Palette) -> Self {
+        let (width, height) = (display.width() * CLIPBOARD_SCALE, display.height() * CLIPBOARD_SCALE);
+        let mut rgba = Vec::with_capacity(width * height * 4);
+        for y in 0..height {
+            for x in 0..width {
+                let index = y / CLIPBOARD_SCALE * display.width() + x / CLIPBOARD_SCALE;
+                let color = palette.color_of(display.color(index));
+                let [_, r, g, b] = color.to_be_bytes();
+                rgba.extend([r, g, b, 0xFF]);
+            }
+        }
+        Self { width, height, rgba }
+    }
+}
+
+impl ClipboardImage {
+    /// The image as a PNG file, for clipboards that take one. The pixels
+    /// are stored uncompressed, which keeps this short and is still small
+    /// at [`CLIPBOARD_SCALE`].
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut raw = Vec::with_capacity((self.width * 4 + 1) * self.height);
+        for row in self.rgba.chunks(self.width * 4) {
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+
+        // A zlib stream of stored deflate blocks.
+        let mut zlib = vec![0x78, 0x01];
+        let blocks = raw.chunks(0xFFFF);
+        let count = blocks.len();
+        for (index, block) in blocks.enumerate() {
+            let len = block.len() as u16;
+            zlib.push((index + 1 == count) as u8);
+            zlib.extend(len.to_le_bytes());
+            zlib.extend((!len).to_le_bytes());
+            zlib.extend_from_slice(block);
+        }
+        zlib.extend(adler32(&raw).to_be_bytes());
+
+        let mut header = Vec::with_capacity(13);
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        // 8 bits a channel, RGBA, no interlacing.
+        header.extend([8, 6, 0, 0, 0]);
+
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        for (kind, data) in [(b"IHDR", &header), (b"IDAT", &zlib), (b"IEND", &Vec::new())] {
+            png.extend((data.len() as u32).to_be_bytes());
+            let start = png.len();
+            png.extend_from_slice(kind);
+            png.extend_from_slice(data);
+            let crc = crc32(&png[start..]);
+            png.extend(crc.to_be_bytes());
+        }
+        png
+    }
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { crc >> 1 ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// The system clipboard, or whatever a frontend copies to.
+pub trait Clipboard: std::fmt::Debug {
+    fn set_text(&mut self, text: &str) -> Result<(), String>;
+
+    /// Puts `image` on the clipboard. Clipboards that only take text leave
+    /// this failing, and the screen is copied as text instead.
+    fn set_image(&mut self, _image: &ClipboardImage) -> Result<(), String> {
+        Err("this clipboard only takes text".to_string())
+    }
+}
+
+/// How the screen ended up on the clipboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Copied {
+    Image,
+    Text,
+}
+
+/// Copies `display` to `clipboard` as an image, falling back to text if that
+/// doesn't work. Fails only if the text can't be copied either.
+pub fn copy_screen(
+    clipboard: &mut dyn Clipboard,
+    display: &Display,
+    palette: Palette,
+) -> Result<Copied, String> {
+    match clipboard.set_image(&ClipboardImage::new(display, palette)) {
+        Ok(()) => Ok(Copied::Image),
+        Err(e) => {
+            log::debug!(target: super::LOG_TARGET, "Copying the screen as text: {}", e);
+            clipboard.set_text(&Screen(display).to_string()).map(|()| Copied::Text)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct TextOnly(Option<String>);
+
+    impl Clipboard for TextOnly {
+        fn set_text(&mut self, text: &str) -> Result<(), String> {
+            self.0 = Some(text.to_string());
+            Ok(())
+        }
+    }
+
+    fn display() -> Display {
+        let mut display = Display::default();
+        display[0][1] = true;
+        display
+    }
+
+    #[test]
+    fn images_are_scaled_in_the_palette_colors() {
+        let palette = Palette { on: 0x123456, off: 0x000080, ..Palette::default() };
+        let image = ClipboardImage::new(&display(), palette);
+        assert_eq!((image.width, image.height, image.rgba.len()), (256, 128, 256 * 128 * 4));
+        let pixel = |x: usize, y: usize| &image.rgba[(y * image.width + x) * 4..][..4];
+        assert_eq!(pixel(3, 3), [0x00, 0x00, 0x80, 0xFF]);
+        assert_eq!(pixel(4, 0), [0x12, 0x34, 0x56, 0xFF]);
+        assert_eq!(pixel(7, 3), [0x12, 0x34, 0x56, 0xFF]);
+        assert_eq!(pixel(8, 0), [0x00, 0x00, 0x80, 0xFF]);
+    }
+
+    #[test]
+    fn pngs_store_the_rows_behind_a_filter_byte() {
+        let image = ClipboardImage::new(&display(), Palette::default());
+        let png = image.to_png();
+        assert_eq!(png[..8], *b"\x89PNG\r\n\x1a\n");
+        assert_eq!(png[12..24], *b"IHDR\0\0\x01\0\0\0\0\x80");
+        // IEND's well-known CRC checks the checksum.
+        assert_eq!(png[png.len() - 12..], *b"\0\0\0\0IEND\xAE\x42\x60\x82");
+
+        let idat = &png[33 + 8..];
+        let mut raw = Vec::new();
+        let mut block = &idat[2..];
+        loop {
+            let len = u16::from_le_bytes([block[1], block[2]]) as usize;
+            raw.extend_from_slice(&block[5..5 + len]);
+            if block[0] == 1 {
+                break;
+            }
+            block = &block[5 + len..];
+        }
+        assert_eq!(raw.len(), (image.width * 4 + 1) * image.height);
+        let row = image.width * 4;
+        assert_eq!(raw[0], 0);
+        assert_eq!(raw[1..=row], image.rgba[..row]);
+        assert_eq!(raw[row + 1], 0);
+    }
+
+    #[test]
+    fn text_only_clipboards_get_the_txt_dump_screen() {
+        let mut clipboard = TextOnly::default();
+        let copied = copy_screen(&mut clipboard, &display(), Palette::default());
+        assert_eq!(copied, Ok(Copied::Text));
+        let text = clipboard.0.unwrap();
+        assert_eq!(text, Screen(&display()).to_string());
+        assert!(text.starts_with(".#.."));
+        assert_eq!(text.lines().count(), 32);
+    }
+}
@@ -0,0 +1,273 @@
+//! The debugger's text commands, shared by the in-window console and the
+//! stdin REPL: each line is parsed into a [`Command`], sent however the
+//! caller sends them, and the [`Reply`] formatted back into lines of text.
+//!
+//! Numbers are hexadecimal, with or without a `0x` prefix, as addresses and
+//! registers are everywhere else in a CHIP-8 debugger.
+
+use super::control::{Command, Registers, Reply};
+
+/// Bytes `mem` reads when it isn't given a length.
+const DEFAULT_READ_LEN: u16 = 0x10;
+/// Bytes per line of a memory dump.
+const DUMP_WIDTH: usize = 8;
+
+/// Every command with its arguments and what it does, for `help`.
+pub const COMMANDS: [(&str, &str); 11] = [
+    ("pause", "stop the machine"),
+    ("resume", "start it again"),
+    ("step", "run one instruction"),
+    ("reset", "restart the ROM"),
+    ("regs", "show the registers"),
+    ("mem ADDR [LEN]", "dump LEN bytes (default 10)"),
+    ("poke ADDR BYTE..", "write bytes to memory"),
+    ("break ADDR", "stop before ADDR"),
+    ("delete ADDR", "remove that breakpoint"),
+    ("key KEY up|down", "release or hold a key"),
+    ("help", "list these commands"),
+];
+
+/// A parsed line.
+#[derive(Debug)]
+pub enum Line {
+    /// Nothing but whitespace.
+    Empty,
+    Help,
+    Command(Command),
+}
+
+/// Parses one line of input. Command names may be shortened to any prefix
+/// that's still unambiguous, so `s` steps and `re` is an error.
+pub fn parse_line(line: &str) -> Result<Line, String> {
+    let mut words = line.split_whitespace();
+    let Some(word) = words.next() else {
+        return Ok(Line::Empty);
+    };
+    let args: Vec<&str> = words.collect();
+    let name = command_name(word)?;
+
+    let expect = |count: usize| {
+        if args.len() == count {
+            Ok(())
+        } else {
+            Err(format!("usage: {}", usage(name)))
+        }
+    };
+    let command = match name {
+        "help" => return Ok(Line::Help),
+        "pause" => expect(0).map(|()| Command::Pause)?,
+        "resume" => expect(0).map(|()| Command::Resume)?,
+        "step" => expect(0).map(|()| Command::Step)?,
+        "reset" => expect(0).map(|()| Command::Reset)?,
+        "regs" => expect(0).map(|()| Command::ReadRegisters)?,
+        "mem" => match args[..] {
+            [addr] => Command::ReadMemory { addr: address(addr)?, len: DEFAULT_READ_LEN },
+            [addr, len] => Command::ReadMemory { addr: address(addr)?, len: number(len, 0x1000)? as u16 },
+            _ => return Err(format!("usage: {}", usage(name))),
+        },
+        "poke" => match &args[..] {
+            [addr, bytes @ ..] if !bytes.is_empty() => Command::WriteMemory {
+                addr: address(addr)?,
+                bytes: bytes.iter().map(|byte| number(byte, 0xFF).map(|byte| byte as u8)).collect::<Result<_, _>>()?,
+            },
+            _ => return Err(format!("usage: {}", usage(name))),
+        },
+        "break" => expect(1).and_then(|()| address(args[0])).map(Command::SetBreakpoint)?,
+        "delete" => expect(1).and_then(|()| address(args[0])).map(Command::ClearBreakpoint)?,
+        "key" => match args[..] {
+            [key, state] => Command::Key {
+                key: number(key, 0xF)? as u8,
+                pressed: match state {
+                    "down" => true,
+                    "up" => false,
+                    _ => return Err(format!("expected up or down, got {:?}", state)),
+                },
+            },
+            _ => return Err(format!("usage: {}", usage(name))),
+        },
+        _ => unreachable!("every name in COMMANDS is handled"),
+    };
+    Ok(Line::Command(command))
+}
+
+/// The full name of the command `word` abbreviates.
+fn command_name(word: &str) -> Result<&'static str, String> {
+    let names = || COMMANDS.iter().map(|(usage, _)| usage.split(' ').next().unwrap_or(usage));
+    if let Some(name) = names().find(|name| *name == word) {
+        return Ok(name);
+    }
+    let mut matches = names().filter(|name| name.starts_with(word));
+    match (matches.next(), matches.next()) {
+        (Some(name), None) => Ok(name),
+        (Some(first), Some(second)) => Err(format!("{:?} could be {} or {}", word, first, second)),
+        (None, _) => Err(format!("unknown command {:?}; try help", word)),
+    }
+}
+
+fn usage(name: &str) -> &'static str {
+    COMMANDS
+        .iter()
+        .map(|(usage, _)| *usage)
+        .find(|usage| usage.split(' ').next() == Some(name))
+        .unwrap_or("")
+}
+
+/// A hexadecimal number no larger than `max`.
+fn number(text: &str, max: u32) -> Result<u32, String> {
+    let digits = text.strip_prefix("0x").unwrap_or(text);
+    match u32::from_str_radix(digits, 16) {
+        Ok(value) if value <= max => Ok(value),
+        Ok(_) => Err(format!("{} is larger than {:X}", text, max)),
+        Err(_) => Err(format!("{:?} isn't a hex number", text)),
+    }
+}
+
+fn address(text: &str) -> Result<u16, String> {
+    number(text, 0xFFF).map(|addr| addr as u16)
+}
+
+/// Parses `line`, sends its command with `send` and describes the outcome,
+/// one string per line of output. Errors, from parsing or from `send`, come
+/// back as a line starting `error:`.
+pub fn run_line(line: &str, send: impl FnOnce(Command) -> Result<Reply, String>) -> Vec<String> {
+    let command = match parse_line(line) {
+        Ok(Line::Empty) => return Vec::new(),
+        Ok(Line::Help) => {
+            return COMMANDS.iter().map(|(usage, what)| format!("{:<18}{}", usage, what)).collect();
+        }
+        Ok(Line::Command(command)) => command,
+        Err(e) => return vec![format!("error: {}", e)],
+    };
+    let dump_from = match &command {
+        Command::ReadMemory { addr, .. } => *addr,
+        _ => 0,
+    };
+    match send(command) {
+        Ok(reply) => format_reply(&reply, dump_from),
+        Err(e) => vec![format!("error: {}", e)],
+    }
+}
+
+/// `reply` as lines of text. Memory is dumped with addresses counting from
+/// `dump_from`.
+fn format_reply(reply: &Reply, dump_from: u16) -> Vec<String> {
+    match reply {
+        Reply::Done => vec!["ok".to_string()],
+        Reply::Stepped { pc } => vec![format!("pc {:03X}", pc)],
+        Reply::Registers(registers) => format_registers(registers),
+        Reply::Memory(bytes) => bytes
+            .chunks(DUMP_WIDTH)
+            .enumerate()
+            .map(|(row, chunk)| {
+                let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02X}", byte)).collect();
+                format!("{:03X}: {}", dump_from as usize + row * DUMP_WIDTH, hex.join(" "))
+            })
+            .collect(),
+        Reply::State { state, .. } => vec![format!("{} bytes of state", state.len())],
+    }
+}
+
+pub(crate) fn format_registers(registers: &Registers) -> Vec<String> {
+    let row = |range: std::ops::Range<usize>| -> String {
+        let values: Vec<String> = range.map(|n| format!("V{:X}={:02X}", n, registers.v[n])).collect();
+        values.join(" ")
+    };
+    let stack: Vec<String> = registers.stack.iter().map(|addr| format!("{:03X}", addr)).collect();
+    let mut lines = vec![
+        row(0..8),
+        row(8..16),
+        format!(
+            "PC={:03X} I={:03X} DT={:02X} ST={:02X}",
+            registers.pc, registers.i, registers.delay_timer, registers.sound_timer
+        ),
+        format!("stack [{}]{}", stack.join(" "), if registers.paused { " paused" } else { "" }),
+    ];
+    if let Some(reason) = &registers.halted {
+        lines.push(format!("halted: {}", reason));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Command {
+        match parse_line(line) {
+            Ok(Line::Command(command)) => command,
+            other => panic!("{:?} parsed as {:?}", line, other),
+        }
+    }
+
+    #[test]
+    fn commands_take_hex_arguments() {
+        assert!(matches!(parse("break 2a0"), Command::SetBreakpoint(0x2A0)));
+        assert!(matches!(parse("  delete 0x2A0 "), Command::ClearBreakpoint(0x2A0)));
+        assert!(matches!(parse("mem 200"), Command::ReadMemory { addr: 0x200, len: 0x10 }));
+        assert!(matches!(parse("mem 200 4"), Command::ReadMemory { addr: 0x200, len: 4 }));
+        assert!(matches!(parse("key f down"), Command::Key { key: 0xF, pressed: true }));
+        match parse("poke 300 de ad") {
+            Command::WriteMemory { addr, bytes } => assert_eq!((addr, bytes), (0x300, vec![0xDE, 0xAD])),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn names_can_be_shortened_while_unambiguous() {
+        assert!(matches!(parse("s"), Command::Step));
+        assert!(parse_line("res").is_err());
+        assert!(matches!(parse("resu"), Command::Resume));
+        assert!(matches!(parse("rese"), Command::Reset));
+        assert!(matches!(parse_line("h"), Ok(Line::Help)));
+        assert!(matches!(parse_line("  "), Ok(Line::Empty)));
+        assert_eq!(parse_line("re").unwrap_err(), "\"re\" could be resume or reset");
+        assert_eq!(parse_line("jump").unwrap_err(), "unknown command \"jump\"; try help");
+    }
+
+    #[test]
+    fn bad_arguments_explain_themselves() {
+        assert_eq!(parse_line("break").unwrap_err(), "usage: break ADDR");
+        assert_eq!(parse_line("break 1000").unwrap_err(), "1000 is larger than FFF");
+        assert_eq!(parse_line("poke 200 xyz").unwrap_err(), "\"xyz\" isn't a hex number");
+        assert_eq!(parse_line("key 1 sideways").unwrap_err(), "expected up or down, got \"sideways\"");
+        assert_eq!(parse_line("pause now").unwrap_err(), "usage: pause");
+    }
+
+    #[test]
+    fn replies_are_formatted_as_lines() {
+        let run = |line: &str, reply: Reply| run_line(line, |_| Ok(reply));
+        assert_eq!(run("pause", Reply::Done), ["ok"]);
+        assert_eq!(run("step", Reply::Stepped { pc: 0x202 }), ["pc 202"]);
+        assert_eq!(
+            run("mem 1fe 9", Reply::Memory((0..9).collect())),
+            ["1FE: 00 01 02 03 04 05 06 07", "206: 08"]
+        );
+
+        let registers = Registers {
+            v: [0xAB; 16],
+            i: 0x300,
+            pc: 0x204,
+            stack: vec![0x200, 0x220],
+            delay_timer: 1,
+            sound_timer: 0,
+            paused: true,
+            halted: None,
+        };
+        let lines = run("regs", Reply::Registers(registers));
+        assert_eq!(lines[0], "V0=AB V1=AB V2=AB V3=AB V4=AB V5=AB V6=AB V7=AB");
+        assert_eq!(lines[2..], ["PC=204 I=300 DT=01 ST=00", "stack [200 220] paused"]);
+    }
+
+    #[test]
+    fn errors_and_help_never_reach_the_machine() {
+        let refuse = |_| -> Result<Reply, String> { panic!("sent a command") };
+        assert_eq!(run_line("nope", refuse), ["error: unknown command \"nope\"; try help"]);
+        assert!(run_line("", refuse).is_empty());
+        let help = run_line("help", refuse);
+        assert_eq!(help.len(), COMMANDS.len());
+        assert_eq!(help[0], "pause             stop the machine");
+        assert_eq!(run_line("step", |_| Err("the emulator is no longer running".to_string())), [
+            "error: the emulator is no longer running"
+        ]);
+    }
+}
@@ -0,0 +1,306 @@
+//! The drop-down debug console toggled with the backtick key: a line being
+//! typed, the lines typed before it, and the output they printed. It runs
+//! the [`commands`](super::commands) the stdin REPL does. Drawing it is up to
+//! the frontend, from a [`ConsoleView`].
+
+use std::collections::VecDeque;
+
+/// Output lines kept for scrolling back; older ones are dropped.
+pub const CONSOLE_SCROLLBACK: usize = 200;
+/// Entered lines kept for Up and Down.
+const HISTORY_LEN: usize = 50;
+
+/// Lines of output shown above the input line while the console is open.
+pub const CONSOLE_ROWS: usize = 7;
+
+/// Console keys that aren't also menu keys, which the console takes over
+/// while it's open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleKey {
+    /// Drop the console down, or put it away.
+    Toggle,
+    /// A character typed. Frontends send these whether or not the console is
+    /// open, alongside whatever else the key does.
+    Char(char),
+    Backspace,
+    PageUp,
+    PageDown,
+}
+
+/// The line being typed, with a cursor and the history of lines entered
+/// before it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineEditor {
+    text: String,
+    /// In characters, from the start of the line.
+    cursor: usize,
+    /// Oldest first.
+    history: Vec<String>,
+    /// The entry shown while browsing with Up and Down, and the line that
+    /// was being typed before browsing started.
+    browsing: Option<(usize, String)>,
+}
+
+impl LineEditor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Byte offset of the character at `cursor`.
+    fn offset(&self, cursor: usize) -> usize {
+        self.text.char_indices().nth(cursor).map_or(self.text.len(), |(offset, _)| offset)
+    }
+
+    pub fn insert(&mut self, ch: char) {
+        let offset = self.offset(self.cursor);
+        self.text.insert(offset, ch);
+        self.cursor += 1;
+    }
+
+    /// Deletes the character before the cursor.
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            let offset = self.offset(self.cursor);
+            self.text.remove(offset);
+        }
+    }
+
+    pub fn left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.text.chars().count());
+    }
+
+    /// Replaces the line with the entry before the one shown, keeping what
+    /// was typed to come back to.
+    pub fn previous(&mut self) {
+        let index = match &self.browsing {
+            Some((index, _)) => index.saturating_sub(1),
+            None if self.history.is_empty() => return,
+            None => {
+                self.browsing = Some((0, self.text.clone()));
+                self.history.len() - 1
+            }
+        };
+        if let Some((shown, _)) = &mut self.browsing {
+            *shown = index;
+        }
+        self.show(self.history[index].clone());
+    }
+
+    /// Replaces the line with the entry after the one shown, or with what
+    /// was typed before browsing once past the newest.
+    pub fn next(&mut self) {
+        let Some((index, draft)) = self.browsing.take() else {
+            return;
+        };
+        if index + 1 < self.history.len() {
+            self.browsing = Some((index + 1, draft));
+            self.show(self.history[index + 1].clone());
+        } else {
+            self.show(draft);
+        }
+    }
+
+    fn show(&mut self, text: String) {
+        self.cursor = text.chars().count();
+        self.text = text;
+    }
+
+    /// Takes the line, leaving an empty one, and adds it to the history
+    /// unless it's blank or repeats the newest entry.
+    pub fn submit(&mut self) -> String {
+        let line = std::mem::take(&mut self.text);
+        self.cursor = 0;
+        self.browsing = None;
+        if !line.trim().is_empty() && self.history.last() != Some(&line) {
+            if self.history.len() == HISTORY_LEN {
+                self.history.remove(0);
+            }
+            self.history.push(line.clone());
+        }
+        line
+    }
+}
+
+/// The console: whether it's down, the line editor, and the output lines
+/// printed so far.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Console {
+    open: bool,
+    pub editor: LineEditor,
+    output: VecDeque<String>,
+    /// Lines scrolled back from the newest output.
+    scroll: usize,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    /// Adds a line of output, scrolling back to the newest.
+    pub fn print(&mut self, line: impl Into<String>) {
+        if self.output.len() == CONSOLE_SCROLLBACK {
+            self.output.pop_front();
+        }
+        self.output.push_back(line.into());
+        self.scroll = 0;
+    }
+
+    /// Scrolls back through older output by `lines`, as far as the oldest.
+    pub fn scroll_up(&mut self, lines: usize) {
+        self.scroll = (self.scroll + lines).min(self.output.len().saturating_sub(1));
+    }
+
+    pub fn scroll_down(&mut self, lines: usize) {
+        self.scroll = self.scroll.saturating_sub(lines);
+    }
+
+    /// What to draw, with up to `rows` lines of output above the input line.
+    pub fn view(&self, rows: usize) -> ConsoleView {
+        let end = self.output.len() - self.scroll;
+        let start = end.saturating_sub(rows);
+        ConsoleView {
+            lines: self.output.range(start..end).cloned().collect(),
+            input: self.editor.text().to_string(),
+            cursor: self.editor.cursor(),
+            scrolled: self.scroll > 0,
+        }
+    }
+}
+
+/// The open console as a frontend should draw it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsoleView {
+    /// Output, oldest first, ending at the newest unless scrolled back.
+    pub lines: Vec<String>,
+    pub input: String,
+    /// Where the cursor is in `input`, in characters.
+    pub cursor: usize,
+    /// Whether there's newer output below `lines`.
+    pub scrolled: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(text: &str) -> LineEditor {
+        let mut editor = LineEditor::new();
+        text.chars().for_each(|ch| editor.insert(ch));
+        editor
+    }
+
+    #[test]
+    fn editing_happens_at_the_cursor() {
+        let mut editor = typed("mem 20");
+        editor.left();
+        editor.left();
+        editor.insert('3');
+        assert_eq!((editor.text(), editor.cursor()), ("mem 320", 5));
+        editor.backspace();
+        editor.backspace();
+        assert_eq!((editor.text(), editor.cursor()), ("mem20", 3));
+        for _ in 0..10 {
+            editor.right();
+        }
+        assert_eq!(editor.cursor(), 5);
+        editor.insert('é');
+        editor.insert('!');
+        assert_eq!(editor.text(), "mem20é!");
+        editor.left();
+        editor.backspace();
+        assert_eq!(editor.text(), "mem20!");
+    }
+
+    #[test]
+    fn history_remembers_what_was_being_typed() {
+        let mut editor = LineEditor::new();
+        editor.previous();
+        assert_eq!(editor.text(), "");
+        for line in ["regs", "step", "step", "   "] {
+            line.chars().for_each(|ch| editor.insert(ch));
+            assert_eq!(editor.submit(), line);
+        }
+        "mem".chars().for_each(|ch| editor.insert(ch));
+
+        editor.previous();
+        assert_eq!((editor.text(), editor.cursor()), ("step", 4));
+        editor.previous();
+        editor.previous();
+        assert_eq!(editor.text(), "regs");
+        editor.next();
+        assert_eq!(editor.text(), "step");
+        editor.next();
+        assert_eq!(editor.text(), "mem");
+        editor.next();
+        assert_eq!(editor.text(), "mem");
+    }
+
+    #[test]
+    fn history_keeps_the_newest_entries() {
+        let mut editor = LineEditor::new();
+        for n in 0..HISTORY_LEN + 5 {
+            n.to_string().chars().for_each(|ch| editor.insert(ch));
+            editor.submit();
+        }
+        for _ in 0..HISTORY_LEN + 5 {
+            editor.previous();
+        }
+        assert_eq!(editor.text(), "5");
+    }
+
+    #[test]
+    fn the_view_follows_the_newest_output_until_scrolled() {
+        let mut console = Console::new();
+        assert_eq!(console.view(3).lines, Vec::<String>::new());
+        for n in 0..5 {
+            console.print(n.to_string());
+        }
+        assert_eq!(console.view(3).lines, ["2", "3", "4"]);
+        console.scroll_up(2);
+        let view = console.view(3);
+        assert_eq!((view.lines, view.scrolled), (vec!["0".to_string(), "1".into(), "2".into()], true));
+        console.scroll_up(100);
+        assert_eq!(console.view(3).lines, ["0"]);
+        console.scroll_down(1);
+        assert_eq!(console.view(3).lines, ["0", "1"]);
+        console.print("5");
+        assert!(!console.view(3).scrolled);
+        assert_eq!(console.view(3).lines, ["3", "4", "5"]);
+    }
+
+    #[test]
+    fn scrollback_is_bounded() {
+        let mut console = Console::new();
+        for n in 0..CONSOLE_SCROLLBACK + 1 {
+            console.print(n.to_string());
+        }
+        console.scroll_up(usize::MAX / 2);
+        assert_eq!(console.view(1).lines, ["1"]);
+    }
+}
@@ -0,0 +1,209 @@
+//! Commands from outside the frame loop, such as a debugger or a remote UI.
+//!
+//! A [`Controller`] sends [`Command`]s to the [`Driver`] it was paired with by
+//! [`channel`]. The driver applies them at the start of its next frame, so the
+//! frame loop stays the only place the machine is touched, and sends back a
+//! [`Reply`]. Clients that [`Command::Subscribe`] also get [`Event`]s as the
+//! machine runs.
+//!
+//! [`Driver`]: super::Driver
+
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use thiserror::Error;
+
+use crate::chip8::Chip8;
+use crate::error::Chip8Error;
+use crate::memory_map::MemoryMap;
+
+/// Something a [`Controller`] asks the frame loop to do.
+#[derive(Debug)]
+pub enum Command {
+    /// Stop running instructions and ticking timers until [`Command::Resume`].
+    Pause,
+    Resume,
+    /// Put the machine back the way it was when the driver first ran with
+    /// this channel, or when the last [`Command::LoadRom`] loaded it.
+    Reset,
+    /// Pause, then execute exactly one instruction.
+    Step,
+    /// Replace the machine with a fresh one running `rom`. Quirks and the
+    /// decode cache setting carry over.
+    LoadRom(Vec<u8>),
+    /// Press or release a keypad key (0x0-0xF). It stays that way until
+    /// changed, by the controller or the frontend.
+    Key { key: u8, pressed: bool },
+    ReadRegisters,
+    /// Overwrite the registers and timers; the stack is left alone.
+    WriteRegisters {
+        v: [u8; 16],
+        i: u16,
+        pc: u16,
+        delay_timer: u8,
+        sound_timer: u8,
+    },
+    /// Read `len` bytes of memory starting at `addr`.
+    ReadMemory { addr: u16, len: u16 },
+    /// Copy `bytes` into memory starting at `addr`.
+    WriteMemory { addr: u16, bytes: Vec<u8> },
+    /// The whole machine, as [`Chip8::save_state`] writes it, and its
+    /// memory map.
+    SaveState,
+    /// Pause before executing the instruction at `addr`.
+    SetBreakpoint(u16),
+    ClearBreakpoint(u16),
+    /// Send the events `filter` accepts to `events` until it's dropped.
+    Subscribe { filter: EventFilter, events: Sender<Event> },
+}
+
+/// The frame loop's answer to a [`Command`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    Done,
+    /// Where `pc` ended up after [`Command::Step`].
+    Stepped { pc: u16 },
+    Registers(Registers),
+    Memory(Vec<u8>),
+    /// A [`Chip8::save_state`] snapshot, for [`Command::SaveState`]. The
+    /// map isn't part of the snapshot, so it comes separately.
+    State { state: Vec<u8>, map: MemoryMap },
+}
+
+/// A copy of the CPU state, as returned for [`Command::ReadRegisters`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Registers {
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    /// Return addresses, outermost call first.
+    pub stack: Vec<u16>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    /// Whether the machine is paused, by a controller or a focus loss.
+    pub paused: bool,
+    /// Why the machine stopped, once it has.
+    pub halted: Option<String>,
+}
+
+/// Something that happened in the frame loop, sent to subscribers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// The machine stopped on an error.
+    Halted { reason: String },
+    /// The machine paused at a breakpoint, before executing `pc`.
+    Breakpoint { pc: u16 },
+    /// A frame was presented. `display_hash` changes whenever the display
+    /// does, so clients can tell when to fetch it again.
+    Frame { frame: u64, display_hash: u64 },
+}
+
+/// Which [`Event`]s a subscriber wants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EventFilter {
+    pub halt: bool,
+    pub breakpoint: bool,
+    pub frame: bool,
+}
+
+impl EventFilter {
+    pub fn accepts(&self, event: &Event) -> bool {
+        match event {
+            Event::Halted { .. } => self.halt,
+            Event::Breakpoint { .. } => self.breakpoint,
+            Event::Frame { .. } => self.frame,
+        }
+    }
+}
+
+/// Why a [`Command`] didn't get a [`Reply`].
+#[derive(Debug, Error)]
+pub enum ControlError {
+    #[error(transparent)]
+    Machine(#[from] Chip8Error),
+    #[error("the emulator is no longer running")]
+    Disconnected,
+}
+
+#[derive(Debug)]
+pub(crate) struct Request {
+    pub(crate) command: Command,
+    pub(crate) reply: Sender<Result<Reply, Chip8Error>>,
+}
+
+/// Sends [`Command`]s to a running frame loop. Cheap to clone, one per
+/// client.
+#[derive(Debug, Clone)]
+pub struct Controller {
+    requests: Sender<Request>,
+}
+
+impl Controller {
+    /// Sends `command` and waits for the frame loop to apply it, which takes
+    /// up to a frame.
+    pub fn send(&self, command: Command) -> Result<Reply, ControlError> {
+        let (reply, response) = mpsc::channel();
+        self.requests
+            .send(Request { command, reply })
+            .map_err(|_| ControlError::Disconnected)?;
+        Ok(response.recv().map_err(|_| ControlError::Disconnected)??)
+    }
+}
+
+/// The frame loop's end of a [`channel`], handed to [`Driver::attach_control`].
+///
+/// [`Driver::attach_control`]: super::Driver::attach_control
+#[derive(Debug)]
+pub struct ControlReceiver {
+    requests: Receiver<Request>,
+}
+
+impl ControlReceiver {
+    /// The requests sent since the last call, without waiting.
+    pub(crate) fn pending(&self) -> impl Iterator<Item = Request> + '_ {
+        self.requests.try_iter()
+    }
+}
+
+/// A connected [`Controller`] and [`ControlReceiver`].
+pub fn channel() -> (Controller, ControlReceiver) {
+    let (requests, receiver) = mpsc::channel();
+    (Controller { requests }, ControlReceiver { requests: receiver })
+}
+
+/// FNV-1a over the display's pixels, one byte per pixel.
+pub fn display_hash(chip8: &Chip8) -> u64 {
+    chip8
+        .display
+        .pixels()
+        .iter()
+        .fold(0xCBF2_9CE4_8422_2325, |hash, &lit| {
+            (hash ^ lit as u64).wrapping_mul(0x0100_0000_01B3)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_hash_follows_the_pixels() {
+        let mut chip8 = Chip8::new();
+        let blank = display_hash(&chip8);
+        chip8.display[3][7] = true;
+        let one = display_hash(&chip8);
+        assert_ne!(blank, one);
+
+        chip8.display[3][7] = false;
+        chip8.display[7][3] = true;
+        assert_ne!(one, display_hash(&chip8));
+        chip8.display[7][3] = false;
+        assert_eq!(blank, display_hash(&chip8));
+    }
+
+    #[test]
+    fn send_fails_once_the_receiver_is_gone() {
+        let (controller, receiver) = channel();
+        drop(receiver);
+        assert!(matches!(controller.send(Command::Pause), Err(ControlError::Disconnected)));
+    }
+}
@@ -0,0 +1,423 @@
+//! What a headless run should end with, for `--verify-state`, and writing
+//! one down from a known-good run for `--write-state-expectation`.
+//!
+//! The file is a small subset of TOML. Every field is optional, and only
+//! the ones given are checked:
+//!
+//! ```text
+//! pc = 0x21A
+//! i = 0x2F0
+//! v0 = 0x07
+//! vF = 1
+//! display-hash = "9f2c1e0b6a7d4c83"
+//! halt = "none"
+//!
+//! [memory]
+//! 0x300 = 0xFF
+//! ```
+//!
+//! The display hash is [`display_hash`] as a string, because TOML integers
+//! stop at `i64`. `halt` is one of [`HALT_REASONS`]. Blank lines and
+//! anything after a `#` are ignored.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use super::control::display_hash;
+use crate::{Chip8, Chip8Error};
+
+/// What `halt` can be: `none` for a machine that kept running, otherwise
+/// the kind of error it stopped on.
+pub const HALT_REASONS: &[&str] = &[
+    "none",
+    "rom-too-large",
+    "empty-rom",
+    "invalid-opcode",
+    "fatal-opcode",
+    "unsupported-platform",
+    "machine-code-call",
+    "stack-overflow",
+    "stack-underflow",
+    "memory-out-of-bounds",
+    "block-out-of-bounds",
+    "protected-write",
+    "invalid-key",
+    "pc-out-of-bounds",
+    "state-version-mismatch",
+    "invalid-state",
+    "json",
+    "load",
+    "io",
+];
+
+/// The [`HALT_REASONS`] entry for a machine that stopped on `error`.
+pub fn halt_reason(error: Option<&Chip8Error>) -> &'static str {
+    match error {
+        None => "none",
+        Some(Chip8Error::RomTooLarge { .. }) => "rom-too-large",
+        Some(Chip8Error::EmptyRom) => "empty-rom",
+        Some(Chip8Error::InvalidOpcode { .. }) => "invalid-opcode",
+        Some(Chip8Error::FatalOpcode { .. }) => "fatal-opcode",
+        Some(Chip8Error::UnsupportedPlatform { .. }) => "unsupported-platform",
+        Some(Chip8Error::MachineCodeCall { .. }) => "machine-code-call",
+        Some(Chip8Error::StackOverflow { .. }) => "stack-overflow",
+        Some(Chip8Error::StackUnderflow { .. }) => "stack-underflow",
+        Some(Chip8Error::MemoryOutOfBounds { .. }) => "memory-out-of-bounds",
+        Some(Chip8Error::BlockOutOfBounds { .. }) => "block-out-of-bounds",
+        Some(Chip8Error::ProtectedWrite { .. }) => "protected-write",
+        Some(Chip8Error::InvalidKey { .. }) => "invalid-key",
+        Some(Chip8Error::PcOutOfBounds { .. }) => "pc-out-of-bounds",
+        Some(Chip8Error::StateVersionMismatch { .. }) => "state-version-mismatch",
+        Some(Chip8Error::InvalidState { .. }) => "invalid-state",
+        #[cfg(feature = "serde")]
+        Some(Chip8Error::Json(_)) => "json",
+        Some(Chip8Error::Load(_)) => "load",
+        Some(Chip8Error::Io(_)) => "io",
+    }
+}
+
+/// The parts of the machine a run should end with. `None` and missing
+/// memory addresses aren't checked.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateExpectation {
+    pub v: [Option<u8>; 16],
+    pub i: Option<u16>,
+    pub pc: Option<u16>,
+    pub memory: BTreeMap<u16, u8>,
+    pub display_hash: Option<u64>,
+    /// One of [`HALT_REASONS`].
+    pub halt: Option<&'static str>,
+}
+
+/// A field that didn't match, with both values formatted the way the file
+/// writes them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub field: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: expected {}, actual {}", self.field, self.expected, self.actual)
+    }
+}
+
+/// `mismatches` as a heading and a line each, for stderr.
+pub fn report(mismatches: &[Mismatch]) -> String {
+    let mut report = match mismatches.len() {
+        1 => "1 field doesn't match".to_string(),
+        count => format!("{} fields don't match", count),
+    };
+    for mismatch in mismatches {
+        report.push_str("\n  ");
+        report.push_str(&mismatch.to_string());
+    }
+    report
+}
+
+/// An integer in decimal or with a `0x` or `0b` prefix, `_` allowed
+/// between digits as in TOML.
+fn parse_int<T: TryFrom<u64>>(text: &str) -> Option<T> {
+    let digits = text.replace('_', "");
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16)
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        u64::from_str_radix(binary, 2)
+    } else {
+        digits.parse()
+    };
+    value.ok().and_then(|value| T::try_from(value).ok())
+}
+
+fn parse_string(text: &str) -> Option<&str> {
+    text.strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .or_else(|| text.strip_prefix('\'').and_then(|rest| rest.strip_suffix('\'')))
+}
+
+impl StateExpectation {
+    /// Parses the file's text, failing with the line number of the first
+    /// line that doesn't make sense.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut expectation = Self::default();
+        let mut in_memory = false;
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let error = |what: String| format!("line {}: {}", index + 1, what);
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(table) = line.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+                in_memory = match table.trim() {
+                    "memory" => true,
+                    table => return Err(error(format!("unknown table [{}]", table))),
+                };
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(error(format!("expected key = value, got {:?}", line)));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            let key = parse_string(key).unwrap_or(key);
+            if in_memory {
+                // Any u16 is in XO-CHIP's 64 KB.
+                let addr = parse_int::<u16>(key)
+                    .ok_or_else(|| error(format!("{:?} isn't a memory address", key)))?;
+                let byte = parse_int(value).ok_or_else(|| error(format!("invalid byte: {}", value)))?;
+                expectation.memory.insert(addr, byte);
+                continue;
+            }
+            match key {
+                "pc" | "i" => {
+                    let addr = parse_int(value).ok_or_else(|| error(format!("invalid {}: {}", key, value)))?;
+                    if key == "pc" {
+                        expectation.pc = Some(addr);
+                    } else {
+                        expectation.i = Some(addr);
+                    }
+                }
+                "display-hash" => {
+                    let hash = parse_string(value)
+                        .and_then(|hash| u64::from_str_radix(hash, 16).ok())
+                        .ok_or_else(|| error(format!("invalid display-hash: {}", value)))?;
+                    expectation.display_hash = Some(hash);
+                }
+                "halt" => {
+                    let reason = parse_string(value)
+                        .and_then(|reason| HALT_REASONS.iter().find(|&&known| known == reason))
+                        .ok_or_else(|| {
+                            error(format!("invalid halt: {} (expected one of: {})", value, HALT_REASONS.join(", ")))
+                        })?;
+                    expectation.halt = Some(reason);
+                }
+                _ => {
+                    let register = key
+                        .strip_prefix(['v', 'V'])
+                        .filter(|digit| digit.len() == 1)
+                        .and_then(|digit| u8::from_str_radix(digit, 16).ok())
+                        .ok_or_else(|| error(format!("unknown field {:?}", key)))?;
+                    let byte = parse_int(value).ok_or_else(|| error(format!("invalid {}: {}", key, value)))?;
+                    expectation.v[register as usize] = Some(byte);
+                }
+            }
+        }
+        Ok(expectation)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Self::parse(&text)
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+
+    /// Everything about `chip8` as it is now, having stopped on `halted` if
+    /// it did. Only the bytes that differ from `initial`, the memory the run
+    /// started from, are kept: the rest is the ROM and the font.
+    pub fn capture(chip8: &Chip8, halted: Option<&Chip8Error>, initial: &[u8]) -> Self {
+        let memory = chip8
+            .memory
+            .iter()
+            .zip(initial)
+            .enumerate()
+            .filter(|(_, (now, before))| now != before)
+            .map(|(addr, (&now, _))| (addr as u16, now))
+            .collect();
+        Self {
+            v: chip8.v.map(Some),
+            i: Some(chip8.i),
+            pc: Some(chip8.pc),
+            memory,
+            display_hash: Some(display_hash(chip8)),
+            halt: Some(halt_reason(halted)),
+        }
+    }
+
+    /// The fields `chip8` gets wrong, in the order the file writes them.
+    pub fn check(&self, chip8: &Chip8, halted: Option<&Chip8Error>) -> Vec<Mismatch> {
+        let mut mismatches = Vec::new();
+        let mut compare = |field: String, expected: String, actual: String| {
+            if expected != actual {
+                mismatches.push(Mismatch { field, expected, actual });
+            }
+        };
+        if let Some(pc) = self.pc {
+            compare("pc".to_string(), format!("0x{:03X}", pc), format!("0x{:03X}", chip8.pc));
+        }
+        if let Some(i) = self.i {
+            compare("i".to_string(), format!("0x{:03X}", i), format!("0x{:03X}", chip8.i));
+        }
+        for (register, expected) in self.v.iter().enumerate() {
+            if let Some(expected) = expected {
+                compare(
+                    format!("v{:X}", register),
+                    format!("0x{:02X}", expected),
+                    format!("0x{:02X}", chip8.v[register]),
+                );
+            }
+        }
+        if let Some(hash) = self.display_hash {
+            compare(
+                "display-hash".to_string(),
+                format!("{:016x}", hash),
+                format!("{:016x}", display_hash(chip8)),
+            );
+        }
+        if let Some(halt) = self.halt {
+            compare("halt".to_string(), halt.to_string(), halt_reason(halted).to_string());
+        }
+        for (&addr, &expected) in &self.memory {
+            compare(
+                format!("memory[0x{:03X}]", addr),
+                format!("0x{:02X}", expected),
+                format!("0x{:02X}", chip8.memory[addr as usize]),
+            );
+        }
+        mismatches
+    }
+}
+
+impl fmt::Display for StateExpectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# What the machine should end the run with.")?;
+        if let Some(pc) = self.pc {
+            writeln!(f, "pc = 0x{:03X}", pc)?;
+        }
+        if let Some(i) = self.i {
+            writeln!(f, "i = 0x{:03X}", i)?;
+        }
+        for (register, value) in self.v.iter().enumerate() {
+            if let Some(value) = value {
+                writeln!(f, "v{:X} = 0x{:02X}", register, value)?;
+            }
+        }
+        if let Some(hash) = self.display_hash {
+            writeln!(f, "display-hash = \"{:016x}\"", hash)?;
+        }
+        if let Some(halt) = self.halt {
+            writeln!(f, "halt = \"{}\"", halt)?;
+        }
+        if !self.memory.is_empty() {
+            writeln!(f, "\n[memory]")?;
+            for (addr, byte) in &self.memory {
+                writeln!(f, "0x{:03X} = 0x{:02X}", addr, byte)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsing_takes_any_subset_of_the_fields() {
+        let text = "\
+# Just the counter.
+pc = 0x21A   # after the loop
+v3 = 7
+VF = 0b1
+
+[memory]
+0x300 = 0xFF
+\"0x301\" = 1_0
+";
+        let expectation = StateExpectation::parse(text).unwrap();
+        assert_eq!(expectation.pc, Some(0x21A));
+        assert_eq!(expectation.i, None);
+        assert_eq!(expectation.v[3], Some(7));
+        assert_eq!(expectation.v[0xF], Some(1));
+        assert_eq!(expectation.v.iter().flatten().count(), 2);
+        assert_eq!(expectation.memory, BTreeMap::from([(0x300, 0xFF), (0x301, 10)]));
+        assert_eq!(expectation.display_hash, None);
+        assert_eq!(expectation.halt, None);
+
+        let expectation = StateExpectation::parse("display-hash = \"00000000000000ff\"\nhalt = 'stack-overflow'").unwrap();
+        assert_eq!(expectation.display_hash, Some(0xFF));
+        assert_eq!(expectation.halt, Some("stack-overflow"));
+        assert_eq!(StateExpectation::parse("").unwrap(), StateExpectation::default());
+    }
+
+    #[test]
+    fn parse_errors_name_the_line() {
+        for (text, error) in [
+            ("pc = 0x21A\nsp = 2", "line 2: unknown field \"sp\""),
+            ("v3 = 0x100", "line 1: invalid v3: 0x100"),
+            ("vG = 1", "line 1: unknown field \"vG\""),
+            ("pc 0x200", "line 1: expected key = value, got \"pc 0x200\""),
+            ("[stack]", "line 1: unknown table [stack]"),
+            ("[memory]\n0x10000 = 1", "line 2: \"0x10000\" isn't a memory address"),
+            ("display-hash = 12", "line 1: invalid display-hash: 12"),
+        ] {
+            assert_eq!(StateExpectation::parse(text), Err(error.to_string()), "{:?}", text);
+        }
+        assert!(StateExpectation::parse("halt = \"tired\"").unwrap_err().starts_with("line 1: invalid halt: \"tired\""));
+    }
+
+    #[test]
+    fn only_the_given_fields_are_checked() {
+        let mut chip8 = Chip8::new();
+        chip8.pc = 0x21C;
+        chip8.v[3] = 5;
+        chip8.v[4] = 9;
+        chip8.memory[0x300] = 0x12;
+
+        let all_right = StateExpectation::parse("pc = 0x21C\nv3 = 5\n[memory]\n0x300 = 0x12").unwrap();
+        assert_eq!(all_right.check(&chip8, None), []);
+
+        let expectation = StateExpectation::parse("pc = 0x21A\nv3 = 7\nhalt = \"none\"\n[memory]\n0x300 = 0x12\n0x301 = 1").unwrap();
+        let underflow = Chip8Error::StackUnderflow { pc: 0x200 };
+        let fields = |mismatches: Vec<Mismatch>| mismatches.into_iter().map(|m| m.field).collect::<Vec<_>>();
+        assert_eq!(fields(expectation.check(&chip8, None)), ["pc", "v3", "memory[0x301]"]);
+        assert_eq!(fields(expectation.check(&chip8, Some(&underflow))), ["pc", "v3", "halt", "memory[0x301]"]);
+    }
+
+    #[test]
+    fn mismatches_are_reported_field_by_field() {
+        let mut chip8 = Chip8::new();
+        chip8.pc = 0x21C;
+        chip8.v[3] = 5;
+        let expectation = StateExpectation::parse("pc = 0x21A\nv3 = 7\nhalt = \"stack-overflow\"").unwrap();
+        assert_eq!(
+            report(&expectation.check(&chip8, None)),
+            "3 fields don't match\n  \
+             pc: expected 0x21A, actual 0x21C\n  \
+             v3: expected 0x07, actual 0x05\n  \
+             halt: expected stack-overflow, actual none"
+        );
+        let mismatch = Mismatch { field: "i".to_string(), expected: "0x300".to_string(), actual: "0x000".to_string() };
+        assert_eq!(report(&[mismatch]), "1 field doesn't match\n  i: expected 0x300, actual 0x000");
+    }
+
+    #[test]
+    fn captured_expectations_round_trip_and_match_their_run() {
+        let mut chip8 = Chip8::new();
+        let initial = chip8.memory;
+        chip8.pc = 0x202;
+        chip8.i = 0x300;
+        chip8.v[0xA] = 0x42;
+        chip8.memory[0x300] = 0x80;
+        chip8.display[0][0] = true;
+
+        let expectation = StateExpectation::capture(&chip8, None, &initial);
+        assert_eq!(expectation.memory, BTreeMap::from([(0x300, 0x80)]));
+        assert_eq!(expectation.halt, Some("none"));
+        let text = expectation.to_string();
+        assert!(text.contains("pc = 0x202\ni = 0x300\nv0 = 0x00\n"), "{}", text);
+        assert!(text.contains("vA = 0x42\n"), "{}", text);
+        assert!(text.ends_with("halt = \"none\"\n\n[memory]\n0x300 = 0x80\n"), "{}", text);
+        assert_eq!(StateExpectation::parse(&text), Ok(expectation.clone()));
+        assert_eq!(expectation.check(&chip8, None), []);
+
+        chip8.display[0][0] = false;
+        let mismatches = expectation.check(&chip8, None);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].field, "display-hash");
+    }
+}
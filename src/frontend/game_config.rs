@@ -0,0 +1,275 @@
+//! Settings remembered per game, keyed by [`Chip8::rom_hash`](crate::Chip8::rom_hash),
+//! and the order settings from different places override each other in.
+//!
+//! The file is plain text, a section per ROM with `key = value` lines:
+//!
+//! ```text
+//! secondary-bindings = false
+//!
+//! [9f3a1c0d52e8b7a4]
+//! cpu-hz = 1800
+//! scale = 6
+//! secondary-bindings = true
+//! ```
+//!
+//! Settings before the first section apply to every game, unless its own
+//! section says otherwise. Blank lines and lines starting with `#` are
+//! ignored.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use super::{MAX_SCALE, MIN_SCALE};
+
+/// What's remembered for one game. Settings it doesn't have come from
+/// elsewhere; see [`resolve`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GameSettings {
+    /// The clock the game runs at, in instructions per second.
+    pub cpu_hz: Option<u32>,
+    /// Window pixels per display pixel.
+    pub scale: Option<usize>,
+    /// Whether the arrows, Space and Enter press keypad keys too; see
+    /// [`secondary`](super::secondary).
+    pub secondary_bindings: Option<bool>,
+}
+
+impl GameSettings {
+    /// These settings, with `defaults`' filling in the ones they don't have.
+    pub fn or(self, defaults: &GameSettings) -> GameSettings {
+        GameSettings {
+            cpu_hz: self.cpu_hz.or(defaults.cpu_hz),
+            scale: self.scale.or(defaults.scale),
+            secondary_bindings: self.secondary_bindings.or(defaults.secondary_bindings),
+        }
+    }
+}
+
+/// Every game's settings, by ROM hash.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GameConfig {
+    /// The settings before the first section, for every game.
+    defaults: GameSettings,
+    games: BTreeMap<u64, GameSettings>,
+}
+
+impl GameConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses the file's text, failing with the line number of the first
+    /// line that doesn't make sense.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut config = Self::new();
+        let mut section = None;
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            let error = |what: String| format!("line {}: {}", index + 1, what);
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(hash) = line.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+                let hash = u64::from_str_radix(hash, 16)
+                    .map_err(|_| error(format!("{:?} isn't a ROM hash", hash)))?;
+                config.games.entry(hash).or_default();
+                section = Some(hash);
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(error(format!("expected key = value, got {:?}", line)));
+            };
+            let settings = match section {
+                Some(hash) => config.games.entry(hash).or_default(),
+                None => &mut config.defaults,
+            };
+            match key.trim() {
+                "cpu-hz" => {
+                    let value = value.trim();
+                    settings.cpu_hz = Some(match value.parse() {
+                        Ok(hz @ 1..) => hz,
+                        _ => return Err(error(format!("invalid cpu-hz: {}", value))),
+                    });
+                }
+                "scale" => {
+                    let value = value.trim();
+                    settings.scale = Some(match value.parse() {
+                        Ok(scale @ MIN_SCALE..=MAX_SCALE) => scale,
+                        _ => return Err(error(format!("invalid scale: {}", value))),
+                    });
+                }
+                "secondary-bindings" => {
+                    let value = value.trim();
+                    settings.secondary_bindings = Some(match value {
+                        "true" => true,
+                        "false" => false,
+                        _ => return Err(error(format!("invalid secondary-bindings: {} (true or false)", value))),
+                    });
+                }
+                key => return Err(error(format!("unknown setting {:?}", key))),
+            }
+        }
+        Ok(config)
+    }
+
+    /// Reads the file at `path`. A missing file is an empty config.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::new()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+
+    /// The settings remembered for the ROM with `hash`, if any are.
+    pub fn game(&self, hash: u64) -> Option<&GameSettings> {
+        self.games.get(&hash)
+    }
+
+    /// The settings for every game without its own.
+    pub fn defaults(&self) -> &GameSettings {
+        &self.defaults
+    }
+
+    /// What applies to the ROM with `hash`: its own settings over the
+    /// defaults.
+    pub fn settings(&self, hash: u64) -> GameSettings {
+        self.game(hash).cloned().unwrap_or_default().or(&self.defaults)
+    }
+
+    pub fn game_mut(&mut self, hash: u64) -> &mut GameSettings {
+        self.games.entry(hash).or_default()
+    }
+}
+
+impl fmt::Display for GameConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# Per-game settings, by ROM hash.")?;
+        write_settings(f, &self.defaults)?;
+        for (hash, settings) in &self.games {
+            writeln!(f, "\n[{:016x}]", hash)?;
+            write_settings(f, settings)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_settings(f: &mut fmt::Formatter<'_>, settings: &GameSettings) -> fmt::Result {
+    if let Some(hz) = settings.cpu_hz {
+        writeln!(f, "cpu-hz = {}", hz)?;
+    }
+    if let Some(scale) = settings.scale {
+        writeln!(f, "scale = {}", scale)?;
+    }
+    if let Some(on) = settings.secondary_bindings {
+        writeln!(f, "secondary-bindings = {}", on)?;
+    }
+    Ok(())
+}
+
+/// Where a setting's value came from, in increasing order of precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SettingSource {
+    /// What the emulator uses when nothing says otherwise.
+    Default,
+    /// The ROM's section of the per-game config.
+    GameConfig,
+    /// An option on the command line, which always wins.
+    CommandLine,
+}
+
+impl fmt::Display for SettingSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SettingSource::Default => "the default",
+            SettingSource::GameConfig => "per-game config",
+            SettingSource::CommandLine => "the command line",
+        })
+    }
+}
+
+/// The value a setting takes: the command line's over the game's over the
+/// default, with where it came from.
+pub fn resolve<T>(default: T, game: Option<T>, command_line: Option<T>) -> (T, SettingSource) {
+    match (command_line, game) {
+        (Some(value), _) => (value, SettingSource::CommandLine),
+        (None, Some(value)) => (value, SettingSource::GameConfig),
+        (None, None) => (default, SettingSource::Default),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_file_round_trips() {
+        let mut config = GameConfig::new();
+        config.game_mut(0x9F3A1C0D52E8B7A4).cpu_hz = Some(1800);
+        config.game_mut(0x1).cpu_hz = Some(600);
+        config.game_mut(0x1).scale = Some(4);
+        let text = config.to_string();
+        assert!(text.contains("[9f3a1c0d52e8b7a4]\ncpu-hz = 1800\n"), "{}", text);
+        assert!(text.contains("cpu-hz = 600\nscale = 4\n"), "{}", text);
+        assert_eq!(GameConfig::parse(&text).unwrap(), config);
+        assert_eq!(config.game(0x2), None);
+    }
+
+    #[test]
+    fn comments_blanks_and_spacing_are_allowed() {
+        let config = GameConfig::parse("# mine\n\n  [00000000000000ab]  \ncpu-hz=900\n").unwrap();
+        assert_eq!(config.game(0xAB).unwrap().cpu_hz, Some(900));
+    }
+
+    #[test]
+    fn bad_lines_are_reported_by_number() {
+        let parse = |text: &str| GameConfig::parse(text).unwrap_err();
+        assert_eq!(parse("secondary-bindings = no"), "line 1: invalid secondary-bindings: no (true or false)");
+        assert_eq!(parse("[ab]\n\ncpu-hz = fast"), "line 3: invalid cpu-hz: fast");
+        assert_eq!(parse("[ab]\ncpu-hz = 0"), "line 2: invalid cpu-hz: 0");
+        assert_eq!(parse("[ab]\nscale = 11"), "line 2: invalid scale: 11");
+        assert_eq!(parse("[pong]"), "line 1: \"pong\" isn't a ROM hash");
+        assert_eq!(parse("[ab]\nvolume = 3"), "line 2: unknown setting \"volume\"");
+        assert_eq!(parse("[ab]\ncpu-hz"), "line 2: expected key = value, got \"cpu-hz\"");
+    }
+
+    #[test]
+    fn settings_before_any_section_apply_to_every_game() {
+        let text = "secondary-bindings = false\ncpu-hz = 900\n\n[ab]\nsecondary-bindings = true\n\n[cd]\nscale = 3\n";
+        let config = GameConfig::parse(text).unwrap();
+        assert_eq!(config.defaults(), &GameSettings { cpu_hz: Some(900), scale: None, secondary_bindings: Some(false) });
+        assert_eq!(config.settings(0xAB), GameSettings { cpu_hz: Some(900), scale: None, secondary_bindings: Some(true) });
+        assert_eq!(config.settings(0xCD), GameSettings { cpu_hz: Some(900), scale: Some(3), secondary_bindings: Some(false) });
+        assert_eq!(config.settings(0xEF), *config.defaults());
+        assert_eq!(GameConfig::parse(&config.to_string()).unwrap(), config);
+        assert!(config.to_string().starts_with("# Per-game settings, by ROM hash.\ncpu-hz = 900\nsecondary-bindings = false\n\n[00000000000000ab]\n"));
+    }
+
+    #[test]
+    fn secondary_bindings_precedence() {
+        let resolved = |text: &str, hash: u64| {
+            let config = GameConfig::parse(text).unwrap();
+            resolve(true, config.settings(hash).secondary_bindings, None)
+        };
+        assert_eq!(resolved("", 0xAB), (true, SettingSource::Default));
+        assert_eq!(resolved("secondary-bindings = false", 0xAB), (false, SettingSource::GameConfig));
+        assert_eq!(resolved("secondary-bindings = false\n[ab]\nsecondary-bindings = true", 0xAB), (true, SettingSource::GameConfig));
+        assert_eq!(resolved("[ab]\nsecondary-bindings = false", 0xAB), (false, SettingSource::GameConfig));
+        assert_eq!(resolved("[ab]\nsecondary-bindings = false", 0xCD), (true, SettingSource::Default));
+    }
+
+    #[test]
+    fn cpu_clock_precedence() {
+        assert_eq!(resolve(600, None, None), (600, SettingSource::Default));
+        assert_eq!(resolve(600, Some(1800), None), (1800, SettingSource::GameConfig));
+        assert_eq!(resolve(600, Some(1800), Some(900)), (900, SettingSource::CommandLine));
+        assert_eq!(resolve(600, None, Some(900)), (900, SettingSource::CommandLine));
+        assert_eq!(format!("cpu clock 1800 Hz from {}", SettingSource::GameConfig), "cpu clock 1800 Hz from per-game config");
+    }
+}
@@ -0,0 +1,254 @@
+//! The F1 help overlay: every key a frontend binds and what it does, built
+//! from the frontend's own binding table so it can't drift from what the
+//! keys really do.
+
+use std::fmt;
+
+use super::InputEvent;
+
+/// Lines on one page of the help overlay, headings included. Sized to fit
+/// both the windowed help screen at its usual text size and the terminal's
+/// playfield.
+pub const HELP_PAGE_LINES: usize = 12;
+
+/// How many times the windowed help screen enlarges the overlay font for a
+/// window at `scale`. The screen is drawn at the default scale and shrinks
+/// with the window, so below 5x the text grows to stay readable.
+pub const fn help_text_scale(scale: usize) -> usize {
+    if scale < 5 { 3 } else { 2 }
+}
+
+/// Lines on one page of the windowed help screen with its text at
+/// `text_scale`. The screen holds seven more lines of text at scale 2 than
+/// [`HELP_PAGE_LINES`], for its title, footer, blank lines and margins, and
+/// proportionally fewer as the text grows.
+pub const fn help_page_lines(text_scale: usize) -> usize {
+    (HELP_PAGE_LINES + 7) * 2 / text_scale - 7
+}
+
+/// One key a frontend binds: the name of the key as the user knows it, and
+/// the event pressing it sends. Keypad keys are bound to their press.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Binding {
+    pub key: &'static str,
+    pub event: InputEvent,
+}
+
+/// The sections of the help overlay, in the order they're listed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Group {
+    GameKeys,
+    Emulator,
+    Debug,
+}
+
+impl Group {
+    const ALL: [Group; 3] = [Group::GameKeys, Group::Emulator, Group::Debug];
+
+    fn of(event: &InputEvent) -> Group {
+        match event {
+            InputEvent::Key { .. } => Group::GameKeys,
+            InputEvent::DumpState
+            | InputEvent::Console(_)
+            | InputEvent::Pause
+            | InputEvent::Step
+            | InputEvent::Registers => Group::Debug,
+            _ => Group::Emulator,
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Group::GameKeys => "Game Keys",
+            Group::Emulator => "Emulator",
+            Group::Debug => "Debug",
+        }
+    }
+}
+
+/// A line of the help overlay.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HelpLine {
+    Heading(Group),
+    /// `keys` are every key bound to the action, joined with ` / `.
+    Entry { keys: String, action: String },
+}
+
+/// Headings as their title, and entries indented with the keys in a column
+/// wide enough for the longest binding.
+impl fmt::Display for HelpLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HelpLine::Heading(group) => f.write_str(group.title()),
+            HelpLine::Entry { keys, action } => write!(f, "  {:<14}{}", keys, action),
+        }
+    }
+}
+
+/// Lists `bindings` under their group's heading, in table order within each
+/// group. `describe` says what an event does, or `None` for one that does
+/// nothing right now, such as a feature that's turned off; those bindings
+/// are left out. Keys with the same description share a line, and empty
+/// groups get no heading.
+pub fn help_lines(bindings: &[Binding], describe: impl Fn(&InputEvent) -> Option<String>) -> Vec<HelpLine> {
+    let mut lines = Vec::new();
+    for group in Group::ALL {
+        let mut entries: Vec<(String, String)> = Vec::new();
+        for binding in bindings.iter().filter(|binding| Group::of(&binding.event) == group) {
+            let Some(action) = describe(&binding.event) else {
+                continue;
+            };
+            match entries.iter_mut().find(|(_, existing)| *existing == action) {
+                Some((keys, _)) => {
+                    keys.push_str(" / ");
+                    keys.push_str(binding.key);
+                }
+                None => entries.push((binding.key.to_string(), action)),
+            }
+        }
+        if !entries.is_empty() {
+            lines.push(HelpLine::Heading(group));
+            lines.extend(entries.into_iter().map(|(keys, action)| HelpLine::Entry { keys, action }));
+        }
+    }
+    lines
+}
+
+/// Splits `lines` into pages of at most `rows` lines. A page that starts
+/// partway through a group repeats its heading, and a heading never ends a
+/// page with its entries on the next.
+pub fn paginate(lines: &[HelpLine], rows: usize) -> Vec<Vec<HelpLine>> {
+    assert!(rows >= 2, "help pages need room for a heading and an entry");
+    let mut pages: Vec<Vec<HelpLine>> = Vec::new();
+    let mut page: Vec<HelpLine> = Vec::new();
+    let mut group = None;
+    for line in lines {
+        if let HelpLine::Heading(heading) = line {
+            group = Some(*heading);
+            if page.len() + 1 >= rows {
+                pages.push(std::mem::take(&mut page));
+            }
+        } else if page.len() == rows {
+            pages.push(std::mem::take(&mut page));
+            page.extend(group.map(HelpLine::Heading));
+        }
+        page.push(line.clone());
+    }
+    if !page.is_empty() || pages.is_empty() {
+        pages.push(page);
+    }
+    pages
+}
+
+/// One page of the open help overlay, as a frontend should draw it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HelpPage {
+    pub lines: Vec<HelpLine>,
+    /// This page's index, counting from 0.
+    pub page: usize,
+    pub pages: usize,
+}
+
+impl HelpPage {
+    /// The title to show above the lines, with the page number when there's
+    /// more than one.
+    pub fn title(&self) -> String {
+        if self.pages > 1 {
+            format!("Help (page {}/{})", self.page + 1, self.pages)
+        } else {
+            "Help".to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(keys: &str, action: &str) -> HelpLine {
+        HelpLine::Entry { keys: keys.to_string(), action: action.to_string() }
+    }
+
+    fn describe(event: &InputEvent) -> Option<String> {
+        match event {
+            InputEvent::Key { key, .. } => Some(format!("Keypad {:X}", key)),
+            InputEvent::SpeedUp => Some("Speed up".to_string()),
+            InputEvent::Quit => Some("Quit".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn bindings_are_grouped_merged_and_filtered() {
+        let bindings = [
+            Binding { key: "+", event: InputEvent::SpeedUp },
+            Binding { key: "X", event: InputEvent::Key { key: 0, pressed: true } },
+            Binding { key: "F9", event: InputEvent::DumpState },
+            Binding { key: "=", event: InputEvent::SpeedUp },
+            Binding { key: "1", event: InputEvent::Key { key: 1, pressed: true } },
+            Binding { key: "Ctrl+C", event: InputEvent::Quit },
+        ];
+        assert_eq!(
+            help_lines(&bindings, describe),
+            [
+                HelpLine::Heading(Group::GameKeys),
+                entry("X", "Keypad 0"),
+                entry("1", "Keypad 1"),
+                HelpLine::Heading(Group::Emulator),
+                entry("+ / =", "Speed up"),
+                entry("Ctrl+C", "Quit"),
+            ]
+        );
+        // Nothing described, nothing listed: not even headings.
+        assert!(help_lines(&bindings, |_| None).is_empty());
+    }
+
+    #[test]
+    fn pages_repeat_the_heading_they_continue() {
+        let mut lines = vec![HelpLine::Heading(Group::GameKeys)];
+        lines.extend((0..5).map(|n| entry(&n.to_string(), "key")));
+        let pages = paginate(&lines, 4);
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[0].len(), 4);
+        assert_eq!(pages[1][0], HelpLine::Heading(Group::GameKeys));
+        assert_eq!(pages[1][1..], lines[4..]);
+    }
+
+    #[test]
+    fn headings_are_not_left_at_the_bottom_of_a_page() {
+        let lines = [
+            HelpLine::Heading(Group::GameKeys),
+            entry("1", "a"),
+            entry("2", "b"),
+            HelpLine::Heading(Group::Emulator),
+            entry("+", "c"),
+        ];
+        let pages = paginate(&lines, 4);
+        assert_eq!(pages, [lines[..3].to_vec(), lines[3..].to_vec()]);
+        assert_eq!(paginate(&lines, 5), [lines.to_vec()]);
+        // There's always a page to show, if an empty one.
+        assert_eq!(paginate(&[], 4), [Vec::new()]);
+    }
+
+    #[test]
+    fn small_windows_get_fewer_lines_a_page() {
+        assert_eq!(help_page_lines(help_text_scale(10)), HELP_PAGE_LINES);
+        assert_eq!(help_page_lines(help_text_scale(5)), HELP_PAGE_LINES);
+        assert!(help_page_lines(help_text_scale(2)) < HELP_PAGE_LINES);
+        assert!(help_page_lines(help_text_scale(1)) >= 2);
+    }
+
+    #[test]
+    fn entries_line_their_actions_up() {
+        assert_eq!(HelpLine::Heading(Group::GameKeys).to_string(), "Game Keys");
+        assert_eq!(entry("Left / Right", "Menu: adjust").to_string(), "  Left / Right  Menu: adjust");
+        assert_eq!(entry("F1", "Show this help").to_string(), "  F1            Show this help");
+    }
+
+    #[test]
+    fn titles_number_pages_only_when_there_are_several() {
+        let page = |page, pages| HelpPage { lines: Vec::new(), page, pages }.title();
+        assert_eq!(page(0, 1), "Help");
+        assert_eq!(page(1, 3), "Help (page 2/3)");
+    }
+}
@@ -0,0 +1,141 @@
+//! The keypad reference card, opened from the help overlay: the 4x4 CHIP-8
+//! keypad as the original machines laid it out, with the keys bound to each
+//! cell. Like the help overlay it's built from the frontend's bindings, so it
+//! shows remapped keys as they are.
+
+use super::InputEvent;
+use super::help::Binding;
+
+/// The keypad's keys, top row first, as on the COSMAC VIP.
+pub const KEYPAD_ROWS: [[u8; 4]; 4] = [[0x1, 0x2, 0x3, 0xC], [0x4, 0x5, 0x6, 0xD], [0x7, 0x8, 0x9, 0xE], [0xA, 0x0, 0xB, 0xF]];
+
+/// What each keypad key is bound to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeypadCard {
+    /// Names of the keys bound to each keypad key, indexed by keypad key, in
+    /// binding order.
+    pub keys: [Vec<&'static str>; 16],
+    /// A keypad key waiting for a key to be bound to it, to highlight.
+    pub awaiting: Option<u8>,
+}
+
+impl KeypadCard {
+    pub fn new(bindings: &[Binding]) -> Self {
+        let mut keys: [Vec<&'static str>; 16] = Default::default();
+        for binding in bindings {
+            if let InputEvent::Key { key, .. } = binding.event
+                && let Some(names) = keys.get_mut(key as usize)
+                && !names.contains(&binding.key)
+            {
+                names.push(binding.key);
+            }
+        }
+        Self { keys, awaiting: None }
+    }
+
+    /// The keys bound to `key`, joined with ` / `, or `-` for none.
+    pub fn label(&self, key: u8) -> String {
+        match self.keys.get(key as usize) {
+            Some(names) if !names.is_empty() => names.join(" / "),
+            _ => "-".to_string(),
+        }
+    }
+}
+
+/// Where [`card_layout`] put one keypad key's cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CardCell {
+    pub key: u8,
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Lays the keypad's cells out in [`KEYPAD_ROWS`] order over an area
+/// `width` x `height` units large, in pixels or character cells, with `gap`
+/// units between cells and around the edge. Cells are all the same size,
+/// and whatever doesn't divide evenly is split between the edges so the
+/// grid stays centered.
+pub fn card_layout(width: usize, height: usize, gap: usize) -> [CardCell; 16] {
+    let cell_width = width.saturating_sub(5 * gap) / 4;
+    let cell_height = height.saturating_sub(5 * gap) / 4;
+    let left = width.saturating_sub(4 * cell_width + 3 * gap) / 2;
+    let top = height.saturating_sub(4 * cell_height + 3 * gap) / 2;
+
+    let mut cells = [CardCell { key: 0, x: 0, y: 0, width: cell_width, height: cell_height }; 16];
+    for (row, keys) in KEYPAD_ROWS.iter().enumerate() {
+        for (column, &key) in keys.iter().enumerate() {
+            cells[row * 4 + column] = CardCell {
+                key,
+                x: left + column * (cell_width + gap),
+                y: top + row * (cell_height + gap),
+                width: cell_width,
+                height: cell_height,
+            };
+        }
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::menu::MenuKey;
+
+    fn key(name: &'static str, key: u8) -> Binding {
+        Binding { key: name, event: InputEvent::Key { key, pressed: true } }
+    }
+
+    #[test]
+    fn cells_list_every_key_bound_to_them() {
+        let bindings = [
+            key("X", 0x0),
+            key("Up", 0x5),
+            Binding { key: "Esc", event: InputEvent::Menu(MenuKey::Escape) },
+            key("W", 0x5),
+            key("Up", 0x5),
+        ];
+        let card = KeypadCard::new(&bindings);
+        assert_eq!(card.label(0x5), "Up / W");
+        assert_eq!(card.label(0x0), "X");
+        assert_eq!(card.label(0xF), "-");
+        assert_eq!(card.label(0x10), "-");
+        assert_eq!(card.awaiting, None);
+    }
+
+    #[test]
+    fn cells_show_the_secondary_bindings_after_the_block() {
+        let mut bindings = vec![key("2", 0x2), key("W", 0x5), key("E", 0x6)];
+        bindings.extend(crate::frontend::secondary::bindings());
+        let card = KeypadCard::new(&bindings);
+        assert_eq!(card.label(0x2), "2 / Up");
+        assert_eq!(card.label(0x5), "W / Space");
+        assert_eq!(card.label(0x6), "E / Right / Enter");
+        assert_eq!(card.label(0x8), "Down");
+    }
+
+    #[test]
+    fn the_grid_follows_the_original_keypad() {
+        let cells = card_layout(64, 16, 0);
+        let keys: Vec<u8> = cells.iter().map(|cell| cell.key).collect();
+        assert_eq!(keys, KEYPAD_ROWS.as_flattened());
+        assert_eq!(cells[0], CardCell { key: 1, x: 0, y: 0, width: 16, height: 4 });
+        assert_eq!(cells[15], CardCell { key: 0xF, x: 48, y: 12, width: 16, height: 4 });
+    }
+
+    #[test]
+    fn the_grid_scales_and_stays_centered() {
+        for (width, height, gap) in [(640, 320, 10), (1280, 640, 10), (97, 50, 3)] {
+            let cells = card_layout(width, height, gap);
+            let last = cells[15];
+            let (right, bottom) = (width - (last.x + last.width), height - (last.y + last.height));
+            assert!(cells[0].x >= gap && right >= gap, "{width}x{height}");
+            assert!(cells[0].x.abs_diff(right) <= 1, "{width}x{height}: {} vs {}", cells[0].x, right);
+            assert!(cells[0].y.abs_diff(bottom) <= 1, "{width}x{height}");
+            assert_eq!(cells[1].x - cells[0].x, last.width + gap);
+        }
+        assert_eq!(card_layout(640, 320, 10)[0].width, 147);
+        assert_eq!(card_layout(1280, 640, 10)[0].width, 307);
+    }
+}
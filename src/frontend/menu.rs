@@ -0,0 +1,218 @@
+//! The pause menu behind Escape: which item is selected and what choosing
+//! it does. Drawing it is up to the frontend, from a [`MenuView`].
+
+use super::Palette;
+
+/// Color schemes the Theme item cycles through, the first being the default.
+pub const THEMES: [(&str, Palette); 4] = [
+    ("Classic", Palette { off: 0x000000, on: 0xFFFFFF, plane2: 0xAAAAAA, both: 0x555555 }),
+    ("Amber", Palette { off: 0x1A1000, on: 0xFFB000, plane2: 0xB07000, both: 0x603C00 }),
+    ("Phosphor", Palette { off: 0x001A00, on: 0x33FF66, plane2: 0x22AA44, both: 0x115522 }),
+    ("LCD", Palette { off: 0x9BBC0F, on: 0x0F380F, plane2: 0x8BAC0F, both: 0x306230 }),
+];
+
+/// The menu's items, top to bottom.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuItem {
+    Resume,
+    Reset,
+    SaveState,
+    LoadState,
+    Theme,
+    Speed,
+    Quit,
+}
+
+impl MenuItem {
+    pub const ALL: [MenuItem; 7] = [
+        MenuItem::Resume,
+        MenuItem::Reset,
+        MenuItem::SaveState,
+        MenuItem::LoadState,
+        MenuItem::Theme,
+        MenuItem::Speed,
+        MenuItem::Quit,
+    ];
+}
+
+/// The keys the menu responds to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuKey {
+    Escape,
+    Up,
+    Down,
+    Left,
+    Right,
+    Enter,
+}
+
+/// What the driver should do about a key the menu handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuAction {
+    Resume,
+    Reset,
+    SaveState,
+    LoadState,
+    NextTheme,
+    PreviousTheme,
+    SpeedUp,
+    SlowDown,
+    Quit,
+}
+
+/// Whether the menu is open, and which item is selected while it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PauseMenu {
+    open: bool,
+    selected: usize,
+}
+
+impl PauseMenu {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn selected(&self) -> MenuItem {
+        MenuItem::ALL[self.selected]
+    }
+
+    /// Moves the menu along for `key`. Escape opens a closed menu with
+    /// Resume selected; every other key is ignored until then. Choosing
+    /// Resume, Reset or either state item closes the menu again, while
+    /// Theme and Speed stay open to be adjusted with Left and Right.
+    pub fn handle(&mut self, key: MenuKey) -> Option<MenuAction> {
+        if !self.open {
+            if key == MenuKey::Escape {
+                *self = Self { open: true, selected: 0 };
+            }
+            return None;
+        }
+
+        let count = MenuItem::ALL.len();
+        let action = match (key, self.selected()) {
+            (MenuKey::Escape, _) => Some(MenuAction::Resume),
+            (MenuKey::Up, _) => {
+                self.selected = (self.selected + count - 1) % count;
+                None
+            }
+            (MenuKey::Down, _) => {
+                self.selected = (self.selected + 1) % count;
+                None
+            }
+            (MenuKey::Enter, MenuItem::Resume) => Some(MenuAction::Resume),
+            (MenuKey::Enter, MenuItem::Reset) => Some(MenuAction::Reset),
+            (MenuKey::Enter, MenuItem::SaveState) => Some(MenuAction::SaveState),
+            (MenuKey::Enter, MenuItem::LoadState) => Some(MenuAction::LoadState),
+            (MenuKey::Enter | MenuKey::Right, MenuItem::Theme) => Some(MenuAction::NextTheme),
+            (MenuKey::Left, MenuItem::Theme) => Some(MenuAction::PreviousTheme),
+            (MenuKey::Right, MenuItem::Speed) => Some(MenuAction::SpeedUp),
+            (MenuKey::Left, MenuItem::Speed) => Some(MenuAction::SlowDown),
+            (MenuKey::Enter, MenuItem::Quit) => Some(MenuAction::Quit),
+            _ => None,
+        };
+        if matches!(
+            action,
+            Some(MenuAction::Resume | MenuAction::Reset | MenuAction::SaveState | MenuAction::LoadState)
+        ) {
+            self.open = false;
+        }
+        action
+    }
+}
+
+/// The open menu as a frontend should draw it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MenuView {
+    /// One label per [`MenuItem`], in order, with current settings filled
+    /// in.
+    pub labels: Vec<String>,
+    pub selected: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open() -> PauseMenu {
+        let mut menu = PauseMenu::new();
+        assert_eq!(menu.handle(MenuKey::Escape), None);
+        assert!(menu.is_open());
+        menu
+    }
+
+    /// Opens the menu and moves down to `item`.
+    fn at(item: MenuItem) -> PauseMenu {
+        let mut menu = open();
+        while menu.selected() != item {
+            menu.handle(MenuKey::Down);
+        }
+        menu
+    }
+
+    #[test]
+    fn only_escape_opens_it() {
+        let mut menu = PauseMenu::new();
+        for key in [MenuKey::Up, MenuKey::Down, MenuKey::Left, MenuKey::Right, MenuKey::Enter] {
+            assert_eq!(menu.handle(key), None);
+            assert!(!menu.is_open());
+        }
+        assert_eq!(open().selected(), MenuItem::Resume);
+    }
+
+    #[test]
+    fn selection_wraps_both_ways() {
+        let mut menu = open();
+        menu.handle(MenuKey::Up);
+        assert_eq!(menu.selected(), MenuItem::Quit);
+        menu.handle(MenuKey::Down);
+        menu.handle(MenuKey::Down);
+        assert_eq!(menu.selected(), MenuItem::Reset);
+    }
+
+    #[test]
+    fn reopening_starts_at_resume() {
+        let mut menu = at(MenuItem::Speed);
+        assert_eq!(menu.handle(MenuKey::Escape), Some(MenuAction::Resume));
+        assert!(!menu.is_open());
+        menu.handle(MenuKey::Escape);
+        assert_eq!(menu.selected(), MenuItem::Resume);
+    }
+
+    #[test]
+    fn choosing_an_item_that_resumes_play_closes_the_menu() {
+        let cases = [
+            (MenuItem::Resume, MenuAction::Resume, false),
+            (MenuItem::Reset, MenuAction::Reset, false),
+            (MenuItem::SaveState, MenuAction::SaveState, false),
+            (MenuItem::LoadState, MenuAction::LoadState, false),
+            (MenuItem::Theme, MenuAction::NextTheme, true),
+            (MenuItem::Quit, MenuAction::Quit, true),
+        ];
+        for (item, action, stays_open) in cases {
+            let mut menu = at(item);
+            assert_eq!(menu.handle(MenuKey::Enter), Some(action), "{:?}", item);
+            assert_eq!(menu.is_open(), stays_open, "{:?}", item);
+        }
+        assert_eq!(at(MenuItem::Speed).handle(MenuKey::Enter), None);
+    }
+
+    #[test]
+    fn left_and_right_adjust_settings_only() {
+        let mut theme = at(MenuItem::Theme);
+        assert_eq!(theme.handle(MenuKey::Right), Some(MenuAction::NextTheme));
+        assert_eq!(theme.handle(MenuKey::Left), Some(MenuAction::PreviousTheme));
+        let mut speed = at(MenuItem::Speed);
+        assert_eq!(speed.handle(MenuKey::Right), Some(MenuAction::SpeedUp));
+        assert_eq!(speed.handle(MenuKey::Left), Some(MenuAction::SlowDown));
+        assert!(speed.is_open());
+
+        let mut reset = at(MenuItem::Reset);
+        assert_eq!(reset.handle(MenuKey::Left), None);
+        assert_eq!(reset.handle(MenuKey::Right), None);
+        assert_eq!(reset.selected(), MenuItem::Reset);
+    }
+}
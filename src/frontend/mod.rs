@@ -0,0 +1,1633 @@
+//! The interface between the interpreter and whatever is showing its display,
+//! reading its keypad and playing its buzzer, plus the frame loop that ties
+//! them together.
+
+pub mod cells;
+pub mod clipboard;
+pub mod commands;
+pub mod console;
+pub mod control;
+pub mod expectation;
+pub mod game_config;
+pub mod help;
+pub mod keypad_card;
+pub mod menu;
+pub mod registers;
+pub mod rpl_flags;
+pub mod script;
+pub mod secondary;
+pub mod speed;
+pub mod timer;
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+
+use crate::chip8::{AUDIO_PATTERN_BYTES, Chip8, DEFAULT_PITCH, RPL_FLAGS};
+use crate::color_map::ColorMap;
+use crate::config::Chip8Config;
+use crate::disasm::disassemble;
+use crate::display::Display;
+use crate::error::Chip8Error;
+use crate::overlay::indicators::Indicators;
+use crate::pretty::{Ascii, Glyphs, Snapshot};
+use crate::watch::WatchHit;
+pub use crate::render::{Palette, PixelFormat};
+
+use clipboard::{Clipboard, Copied};
+use console::{CONSOLE_ROWS, Console, ConsoleKey, ConsoleView};
+use control::{Command, ControlReceiver, Event, EventFilter, Registers, Reply, Request};
+use game_config::GameConfig;
+use help::{Binding, HELP_PAGE_LINES, HelpPage};
+use keypad_card::KeypadCard;
+use menu::{MenuAction, MenuItem, MenuKey, MenuView, PauseMenu, THEMES};
+use registers::RegistersView;
+use script::InputTimeline;
+use speed::{COARSE_STEP, FINE_STEP, FrameBudget, Speed, SpeedLimits, Timing};
+use timer::{SpeedrunTimer, TimerStart, TimerView};
+
+/// Instructions executed per frame at 100% speed, the base clock.
+pub const DEFAULT_INSTRUCTIONS_PER_FRAME: u32 = 10;
+
+const LOG_TARGET: &str = "chip8::frontend";
+/// The smallest and largest window scales [`InputEvent::SetScale`] and the
+/// scale steps reach.
+pub const MIN_SCALE: usize = 1;
+pub const MAX_SCALE: usize = 10;
+/// How long [`Frame::notice`] stays up: a second and a half.
+const NOTICE_FRAMES: u32 = 90;
+
+/// One frame handed to a [`VideoSink`].
+#[derive(Debug, Clone, Copy)]
+pub struct Frame<'a> {
+    /// Row-major logical pixels, `width * height` of them. These are plane
+    /// 1's; XO-CHIP programs can draw on `plane2` as well.
+    pub pixels: &'a [bool],
+    /// XO-CHIP's second plane, laid out like `pixels` and unlit unless the
+    /// program selected it with FN01.
+    pub plane2: &'a [bool],
+    pub width: usize,
+    pub height: usize,
+    pub palette: Palette,
+    /// CHIP-8X's colors, which take the palette's place while the
+    /// [chip8x](crate::Quirks::chip8x) quirk is on.
+    pub color_map: Option<&'a ColorMap>,
+    /// Whether the pixels or colors differ from the last frame the driver
+    /// presented, so frontends can skip redrawing a still screen when
+    /// nothing over it has changed either; see [`Frame::is_still`]. The
+    /// first frame always has.
+    pub display_changed: bool,
+    /// Instructions per frame at this speed, on average; the frames
+    /// themselves carry fractions over to run at [`Speed::hz`] exactly.
+    pub instructions_per_frame: u32,
+    /// The speed `instructions_per_frame` comes from.
+    pub speed: Speed,
+    /// Window pixels per display pixel, when the frontend told the driver
+    /// its scale with [`Driver::set_window_scale`]. The frontend resizes to
+    /// it; the pixels stay `width` x `height` whatever it is.
+    pub scale: Option<usize>,
+    /// Whether the machine is paused, because the frontend lost focus or a
+    /// controller asked.
+    pub paused: bool,
+    /// Why the machine stopped, once it has.
+    pub halted: Option<&'a Chip8Error>,
+    /// Where the program ended, on a 00FD or a jump to itself, once it has.
+    /// The display stays as it left it.
+    pub finished: Option<u16>,
+    /// [`Chip8::state_hash`], when the driver was asked to show it.
+    pub state_hash: Option<u64>,
+    /// The pause menu, while it's open.
+    pub menu: Option<&'a MenuView>,
+    /// The help overlay, while it's open. It goes over the menu if both are.
+    pub help: Option<&'a HelpPage>,
+    /// The keypad card, when it's been chosen from the help overlay. It's
+    /// shown in the help's place.
+    pub keypad_card: Option<&'a KeypadCard>,
+    /// The debug console, while it's down. It goes over the display and the
+    /// menu, under the help overlay.
+    pub console: Option<&'a ConsoleView>,
+    /// The register readout, while the machine is held or always once
+    /// [`InputEvent::Registers`] turned it on. It goes over the display,
+    /// under the console.
+    pub registers: Option<&'a RegistersView>,
+    /// The speedrun timer, when the driver was given one.
+    pub timer: Option<&'a TimerView>,
+    /// A short message confirming something just happened, like the screen
+    /// being copied, to show briefly over the display.
+    pub notice: Option<&'a str>,
+    /// Whether Escape quits straight away rather than opening the menu, for
+    /// frontends that say what it does.
+    pub escape_quits: bool,
+    /// The status indicators to show over the display; see
+    /// [`overlay::indicators`](crate::overlay::indicators).
+    pub indicators: Indicators,
+}
+
+impl Frame<'_> {
+    /// The pixels mapped through the palette, ready for a 0RGB framebuffer.
+    pub fn to_rgb(&self) -> Vec<u32> {
+        match self.color_map {
+            Some(map) => self
+                .colors()
+                .enumerate()
+                .map(|(index, planes)| map.rgb(index % self.width, index / self.width, self.width, self.height, planes != 0))
+                .collect(),
+            None => self.colors().map(|planes| self.palette.color_of(planes)).collect(),
+        }
+    }
+
+    /// Which planes each pixel is lit in, plane 1 in bit 0.
+    fn colors(&self) -> impl ExactSizeIterator<Item = u8> + '_ {
+        self.pixels.iter().zip(self.plane2).map(|(&one, &two)| one as u8 | (two as u8) << 1)
+    }
+
+    /// Whether a frontend can leave the last frame it showed up instead of
+    /// drawing this one: the display hasn't [changed](Self::display_changed)
+    /// and nothing goes over it, in this frame or the last. `overlaid` is
+    /// the frontend's note of whether anything went over the last one.
+    pub fn is_still(&self, overlaid: &mut bool) -> bool {
+        let bare = self.halted.is_none()
+            && self.keypad_card.is_none()
+            && self.help.is_none()
+            && self.menu.is_none()
+            && self.state_hash.is_none()
+            && self.indicators.is_empty()
+            && self.timer.is_none()
+            && self.notice.is_none()
+            && self.registers.is_none()
+            && self.console.is_none();
+        let still = bare && !*overlaid && !self.display_changed;
+        *overlaid = !bare;
+        still
+    }
+
+    /// Prints where the program ended or why the machine stopped, with the
+    /// speedrun time, unless `reported` says it already has. For windowed
+    /// frontends, which stay open until Escape.
+    pub fn report_stop(&self, reported: &mut bool) {
+        if *reported {
+            return;
+        }
+        if let Some(pc) = self.finished {
+            println!("Program halted at 0x{:03X}", pc);
+        } else if let Some(e) = self.halted {
+            eprintln!("✗ Machine stopped: {}", e);
+        } else {
+            return;
+        }
+        if let Some(timer) = self.timer {
+            println!("Final time: {}", timer.time());
+        }
+        println!("Press ESC to exit.");
+        *reported = true;
+    }
+
+    /// The pixels mapped through the palette into `buf` as `format`,
+    /// returning how many bytes that took.
+    ///
+    /// Panics if `buf` is shorter than [`PixelFormat::buffer_len`].
+    pub fn render_into(&self, buf: &mut [u8], format: PixelFormat) -> usize {
+        match self.color_map {
+            Some(_) => crate::render::rgb_into(&self.to_rgb(), buf, format),
+            None => crate::render::render_planes_into(self.colors(), self.palette, buf, format),
+        }
+    }
+}
+
+/// Something that can show frames, such as a window or a terminal.
+pub trait VideoSink {
+    fn present(&mut self, frame: &Frame<'_>);
+}
+
+/// Input reported by an [`InputSource`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEvent {
+    /// A keypad key (0x0-0xF) was pressed or released.
+    Key { key: u8, pressed: bool },
+    /// Faster or slower by [`COARSE_STEP`](speed::COARSE_STEP)...
+    SpeedUp,
+    SlowDown,
+    /// ...or by [`FINE_STEP`](speed::FINE_STEP).
+    FineSpeedUp,
+    FineSlowDown,
+    /// Back to 100%.
+    ResetSpeed,
+    /// Set the window scale, [`MIN_SCALE`] to [`MAX_SCALE`]...
+    SetScale(usize),
+    /// ...or step it by one.
+    ScaleUp,
+    ScaleDown,
+    /// The frontend gained (`true`) or lost (`false`) input focus.
+    Focus(bool),
+    /// Write the machine to the file given to [`Driver::set_state_dump`].
+    DumpState,
+    /// Escape, the arrow keys or Enter, for the pause menu. Frontends send
+    /// these whether or not the menu is open.
+    Menu(MenuKey),
+    /// Open or close the help overlay.
+    Help,
+    /// Mark a split on the speedrun timer.
+    Split,
+    /// Remember the current speed for this ROM, in the file given to
+    /// [`Driver::set_game_config`].
+    SaveGameSettings,
+    /// Copy the display to the clipboard given to [`Driver::set_clipboard`].
+    /// Ctrl+C is typed into the debug console instead while it's open.
+    CopyScreen,
+    /// Write the display as a text [`Snapshot`] to where
+    /// [`Driver::set_ascii_dump`] said.
+    AsciiDump,
+    /// Flip a window option. Frontends act on these themselves, and the
+    /// driver ignores them.
+    Window(WindowToggle),
+    /// A key for the debug console. While it's open the menu keys edit its
+    /// line instead: Left and Right move the cursor, Up and Down go through
+    /// the history, Enter runs the line and Escape closes it.
+    Console(ConsoleKey),
+    /// Pause the machine, or resume it if it's paused.
+    Pause,
+    /// Pause the machine if it's running and execute one instruction,
+    /// printing it to the console and the log.
+    Step,
+    /// Show the register readout all the time, or only while the machine is
+    /// held again.
+    Registers,
+    Quit,
+}
+
+/// A window option that can be flipped while running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowToggle {
+    AlwaysOnTop,
+    Borderless,
+    Resizable,
+}
+
+/// Something that produces keypad and control input.
+pub trait InputSource {
+    /// Returns the events that happened since the last call.
+    fn drain_events(&mut self) -> Vec<InputEvent>;
+}
+
+/// A [`VideoSink`] that shows nothing, for running without a display.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullVideo;
+
+impl VideoSink for NullVideo {
+    fn present(&mut self, _frame: &Frame<'_>) {}
+}
+
+/// An [`InputSource`] that never reports anything.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullInput;
+
+impl InputSource for NullInput {
+    fn drain_events(&mut self) -> Vec<InputEvent> {
+        Vec::new()
+    }
+}
+
+/// A command for the buzzer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioCommand {
+    BuzzerOn,
+    /// Play XO-CHIP's pattern in a loop instead of the buzzer. Sent again,
+    /// without a `BuzzerOff` between, when the program changes the pattern
+    /// or its pitch while it's playing.
+    PatternOn(AudioPattern),
+    BuzzerOff,
+}
+
+/// What an XO-CHIP program asked to hear: the samples F002 loaded and the
+/// pitch FX3A set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioPattern {
+    pub samples: [u8; AUDIO_PATTERN_BYTES],
+    pub pitch: u8,
+}
+
+/// The number of the pattern's 1-bit samples XO-CHIP plays a second at
+/// `pitch`: 4000 at the default of 64, an octave up or down for every 48
+/// steps either side.
+pub fn playback_rate(pitch: u8) -> f32 {
+    4000.0 * 2f32.powf((pitch as f32 - DEFAULT_PITCH as f32) / 48.0)
+}
+
+/// Something that can sound the buzzer.
+pub trait AudioSink {
+    fn command(&mut self, command: AudioCommand);
+
+    /// Whether the buzzer can't be heard, such as when there's no output
+    /// device.
+    fn muted(&self) -> bool {
+        false
+    }
+}
+
+/// Pitch of the buzzer in Hz.
+pub const BUZZER_FREQUENCY: f32 = 440.0;
+
+/// The buzzer's waveform as mono `f32` samples in -1.0..=1.0, for audio
+/// backends that are handed a buffer to fill rather than a tone to play.
+#[derive(Debug, Clone)]
+pub struct Tone {
+    sample_rate: u32,
+    /// Position within the current period, in 0.0..1.0.
+    phase: f32,
+}
+
+impl Tone {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            phase: 0.0,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn fill(&mut self, out: &mut [f32]) {
+        for sample in out {
+            *sample = self.next_sample();
+        }
+    }
+
+    fn next_sample(&mut self) -> f32 {
+        let value = (self.phase * std::f32::consts::TAU).sin();
+        self.phase = (self.phase + BUZZER_FREQUENCY / self.sample_rate as f32).fract();
+        value
+    }
+}
+
+impl Iterator for Tone {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        Some(self.next_sample())
+    }
+}
+
+/// An [`AudioPattern`] resampled to `sample_rate`, looping, as mono `f32`
+/// samples: 1.0 for a set bit and -1.0 for a clear one.
+#[derive(Debug, Clone)]
+pub struct PatternTone {
+    pattern: AudioPattern,
+    sample_rate: u32,
+    /// Which of the pattern's bits is playing, with the fraction of the way
+    /// through it, in 0.0..128.0.
+    position: f32,
+}
+
+impl PatternTone {
+    pub fn new(pattern: AudioPattern, sample_rate: u32) -> Self {
+        Self { pattern, sample_rate, position: 0.0 }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn fill(&mut self, out: &mut [f32]) {
+        for sample in out {
+            *sample = self.next_sample();
+        }
+    }
+
+    fn next_sample(&mut self) -> f32 {
+        const BITS: f32 = (AUDIO_PATTERN_BYTES * 8) as f32;
+        let bit = self.position as usize;
+        let value = if self.pattern.samples[bit / 8] & (0x80 >> (bit % 8)) != 0 { 1.0 } else { -1.0 };
+        self.position = (self.position + playback_rate(self.pattern.pitch) / self.sample_rate as f32) % BITS;
+        value
+    }
+}
+
+impl Iterator for PatternTone {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        Some(self.next_sample())
+    }
+}
+
+/// An [`AudioSink`] that ignores every command.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullAudio;
+
+impl AudioSink for NullAudio {
+    fn command(&mut self, _command: AudioCommand) {}
+
+    fn muted(&self) -> bool {
+        true
+    }
+}
+
+/// What a [`Hooks`] callback wants the frame loop to do next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookAction {
+    Continue,
+    /// Pause the machine, as [`Command::Pause`] would.
+    Pause,
+}
+
+/// Code run by a [`Driver`] at fixed points in each frame, such as a script.
+///
+/// Every callback gets the machine to inspect or change. Installing hooks
+/// makes the driver execute one instruction at a time, so leave the `wants_*`
+/// methods returning `false` for callbacks that aren't used.
+pub trait Hooks: std::fmt::Debug {
+    /// At the start of each frame the machine runs, before its instructions.
+    fn frame_start(&mut self, _chip8: &mut Chip8, _frame: u64) -> HookAction {
+        HookAction::Continue
+    }
+
+    /// Whether [`Hooks::address_hit`] should be called before running the
+    /// instruction at `pc`.
+    fn wants_address(&self, _pc: u16) -> bool {
+        false
+    }
+
+    fn address_hit(&mut self, _chip8: &mut Chip8, _pc: u16) -> HookAction {
+        HookAction::Continue
+    }
+
+    /// Whether [`Hooks::draw`] should be called after each DXYN.
+    fn wants_draws(&self) -> bool {
+        false
+    }
+
+    fn draw(&mut self, _chip8: &mut Chip8) -> HookAction {
+        HookAction::Continue
+    }
+}
+
+/// The frame loop's state between frames, for frontends that are handed
+/// control once per frame, like a windowing event loop, instead of looping
+/// themselves with [`run_emulator`].
+#[derive(Debug)]
+pub struct Driver {
+    /// Stop running the machine, and silence the buzzer, while the frontend
+    /// has reported losing focus.
+    pub pause_on_focus_loss: bool,
+    /// Hand the frontend [`Chip8::state_hash`] with every frame, to show
+    /// alongside the display.
+    pub show_hash: bool,
+    /// Quit on Escape instead of opening the pause menu.
+    pub classic_escape: bool,
+    speed: Speed,
+    budget: FrameBudget,
+    halted: Option<Chip8Error>,
+    /// Where the program ended; see [`Chip8::finished`].
+    finished: Option<u16>,
+    /// What the audio sink was last told to play, or `None` if it's quiet.
+    sound: Option<AudioCommand>,
+    focused: bool,
+    frames: u64,
+    control: Option<ControlReceiver>,
+    /// Paused by [`Command::Pause`], [`Command::Step`] or a breakpoint.
+    held: bool,
+    breakpoints: BTreeSet<u16>,
+    /// The breakpoint the machine last stopped at, which it runs past when
+    /// resumed instead of stopping there again.
+    resume_past: Option<u16>,
+    /// The watched access that paused the machine, until it runs again.
+    watch_hit: Option<WatchHit>,
+    /// Show the register readout while running too.
+    show_registers: bool,
+    /// The registers as the machine last started running or stepping, for
+    /// the readout to mark what changed since.
+    registers_before: Option<Registers>,
+    /// What [`Command::Reset`] and the menu's Reset restore, saved on the
+    /// first frame.
+    reset_state: Option<Vec<u8>>,
+    menu: PauseMenu,
+    /// The menu's save slot.
+    quick_save: Option<Vec<u8>>,
+    /// Index into [`THEMES`].
+    theme: usize,
+    /// What the frontend's keys send, for the help overlay.
+    bindings: Vec<Binding>,
+    /// The help page shown, while the overlay is open.
+    help: Option<usize>,
+    /// Showing the keypad card in place of the help page.
+    keypad_card: bool,
+    console: Console,
+    timer: Option<SpeedrunTimer>,
+    subscribers: Vec<(EventFilter, Sender<Event>)>,
+    timeline: InputTimeline,
+    /// Drop the frontend's keypad events while a timeline plays.
+    timeline_exclusive: bool,
+    hooks: Option<Box<dyn Hooks>>,
+    /// Where [`InputEvent::DumpState`] and quitting write the machine as JSON.
+    #[cfg(feature = "serde")]
+    state_dump: Option<PathBuf>,
+    /// Where [`InputEvent::SaveGameSettings`] remembers the speed.
+    game_config: Option<PathBuf>,
+    /// Where quitting writes the RPL flags, and what the file has now.
+    rpl_flags: Option<(PathBuf, [u8; RPL_FLAGS])>,
+    /// Where [`InputEvent::CopyScreen`] copies the display.
+    clipboard: Option<Box<dyn Clipboard>>,
+    /// How the machine [`Command::LoadRom`] loads into is set up.
+    config: Option<Chip8Config>,
+    /// What [`Frame::notice`] says, and for how many more frames.
+    notice: Option<(&'static str, u32)>,
+    ascii_dump: Option<AsciiDump>,
+    /// The frontend's window scale, once it's said what it is.
+    scale: Option<usize>,
+    /// The display and colors of the last frame presented, for
+    /// [`Frame::display_changed`].
+    shown: Option<(Display, ColorMap)>,
+}
+
+/// Where and how [`InputEvent::AsciiDump`] writes the display.
+struct AsciiDump {
+    glyphs: Glyphs,
+    out: Box<dyn Write>,
+    /// Frames to write it after as well, without being asked.
+    at: BTreeSet<u64>,
+}
+
+impl fmt::Debug for AsciiDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsciiDump").field("glyphs", &self.glyphs).field("at", &self.at).finish_non_exhaustive()
+    }
+}
+
+impl Default for Driver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Driver {
+    pub fn new() -> Self {
+        Self {
+            pause_on_focus_loss: false,
+            show_hash: false,
+            classic_escape: false,
+            speed: Speed::default(),
+            budget: FrameBudget::default(),
+            halted: None,
+            finished: None,
+            sound: None,
+            focused: true,
+            frames: 0,
+            control: None,
+            held: false,
+            breakpoints: BTreeSet::new(),
+            resume_past: None,
+            watch_hit: None,
+            show_registers: false,
+            registers_before: None,
+            reset_state: None,
+            menu: PauseMenu::new(),
+            quick_save: None,
+            theme: 0,
+            bindings: Vec::new(),
+            help: None,
+            keypad_card: false,
+            console: Console::new(),
+            timer: None,
+            subscribers: Vec::new(),
+            timeline: InputTimeline::new(),
+            timeline_exclusive: false,
+            hooks: None,
+            #[cfg(feature = "serde")]
+            state_dump: None,
+            game_config: None,
+            rpl_flags: None,
+            clipboard: None,
+            config: None,
+            notice: None,
+            ascii_dump: None,
+            scale: None,
+            shown: None,
+        }
+    }
+
+    /// Writes the machine to `path` as JSON, in the format described in
+    /// [`state_json`](crate::state_json), on [`InputEvent::DumpState`] and
+    /// when the frontend quits.
+    #[cfg(feature = "serde")]
+    pub fn set_state_dump(&mut self, path: impl Into<PathBuf>) {
+        self.state_dump = Some(path.into());
+    }
+
+    /// Writes the machine to the file given to [`Driver::set_state_dump`], if
+    /// there is one, with this driver's frame count.
+    #[cfg(feature = "serde")]
+    pub fn dump_state(&self, chip8: &Chip8) -> std::io::Result<()> {
+        let Some(path) = &self.state_dump else {
+            return Ok(());
+        };
+        let mut state = chip8.json_state();
+        state.frames = Some(self.frames);
+        std::fs::write(path, state.to_json() + "\n")?;
+        log::info!(target: LOG_TARGET, "Wrote the machine state to {}", path.display());
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    fn try_dump_state(&self, chip8: &Chip8) {
+        if let Err(e) = self.dump_state(chip8) {
+            log::warn!(target: LOG_TARGET, "Failed to write the machine state: {}", e);
+        }
+    }
+
+    /// Writes the machine's [RPL flags](Chip8::rpl_flags) to `path` when the
+    /// frontend quits, if they're no longer the `saved` ones the file has;
+    /// see [`rpl_flags`](self::rpl_flags).
+    pub fn set_rpl_flags(&mut self, path: impl Into<PathBuf>, saved: [u8; RPL_FLAGS]) {
+        self.rpl_flags = Some((path.into(), saved));
+    }
+
+    fn try_save_rpl_flags(&mut self, chip8: &Chip8) {
+        let Some((path, saved)) = &mut self.rpl_flags else {
+            return;
+        };
+        if *saved == chip8.rpl_flags {
+            return;
+        }
+        match rpl_flags::save(path, &chip8.rpl_flags) {
+            Ok(()) => {
+                *saved = chip8.rpl_flags;
+                log::info!(target: LOG_TARGET, "Wrote the RPL flags to {}", path.display());
+            }
+            Err(e) => log::warn!(target: LOG_TARGET, "Failed to write the RPL flags to {}: {}", path.display(), e),
+        }
+    }
+
+    /// Writes what's kept when the frontend quits.
+    fn save_on_quit(&mut self, chip8: &Chip8) {
+        #[cfg(feature = "serde")]
+        self.try_dump_state(chip8);
+        self.try_save_rpl_flags(chip8);
+    }
+
+    /// Saves the speed to the per-game config at `path`, keyed by the ROM's
+    /// hash, on [`InputEvent::SaveGameSettings`]; see
+    /// [`game_config`](self::game_config).
+    pub fn set_game_config(&mut self, path: impl Into<PathBuf>) {
+        self.game_config = Some(path.into());
+    }
+
+    /// Writes the current speed, and the window scale if the frontend has
+    /// one, into the per-game config, keeping whatever else it holds. Does
+    /// nothing without a config file or a ROM.
+    pub fn save_game_settings(&mut self, chip8: &Chip8) -> Result<(), String> {
+        let (Some(path), Some(hash)) = (&self.game_config, chip8.rom_hash()) else {
+            return Ok(());
+        };
+        let mut config = GameConfig::load(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let hz = self.speed.hz();
+        let game = config.game_mut(hash);
+        game.cpu_hz = Some(hz);
+        game.scale = self.scale.or(game.scale);
+        config.save(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let saved = match self.scale {
+            Some(scale) => format!("cpu clock {} Hz and scale {}x", hz, scale),
+            None => format!("cpu clock {} Hz", hz),
+        };
+        log::info!(target: LOG_TARGET, "Saved {} for ROM {:016x} to {}", saved, hash, path.display());
+        self.console.print(format!("Saved {} for this game", saved));
+        Ok(())
+    }
+
+    /// Copies the display to `clipboard` on [`InputEvent::CopyScreen`].
+    pub fn set_clipboard(&mut self, clipboard: impl Clipboard + 'static) {
+        self.clipboard = Some(Box::new(clipboard));
+    }
+
+    /// Sets up the machine [`Command::LoadRom`] loads into as `config` says,
+    /// seed and all. It keeps the running machine's quirks, which may have
+    /// changed since. Without a config the new machine is set up like the
+    /// running one, with a fresh seed.
+    pub fn set_config(&mut self, config: Chip8Config) {
+        self.config = Some(config);
+    }
+
+    /// Copies the display in the current theme's colors, and says whether
+    /// that worked over the display.
+    fn copy_screen(&mut self, chip8: &Chip8) {
+        let Some(clipboard) = &mut self.clipboard else {
+            return;
+        };
+        let notice = match clipboard::copy_screen(clipboard.as_mut(), &chip8.display, THEMES[self.theme].1) {
+            Ok(copied) => {
+                let what = if copied == Copied::Image { "an image" } else { "text" };
+                log::info!(target: LOG_TARGET, "Copied the screen to the clipboard as {}", what);
+                "copied"
+            }
+            Err(e) => {
+                log::warn!(target: LOG_TARGET, "Failed to copy the screen: {}", e);
+                "copy failed"
+            }
+        };
+        self.notice = Some((notice, NOTICE_FRAMES));
+    }
+
+    /// Writes the display to `out` as a text [`Snapshot`] drawn with
+    /// `glyphs`, on [`InputEvent::AsciiDump`].
+    pub fn set_ascii_dump(&mut self, glyphs: Glyphs, out: impl Write + 'static) {
+        let at = self.ascii_dump.take().map(|dump| dump.at).unwrap_or_default();
+        self.ascii_dump = Some(AsciiDump { glyphs, out: Box::new(out), at });
+    }
+
+    /// Also writes the snapshot once each of `frames` has been presented,
+    /// numbered as [`Driver::frames`] counts. Does nothing until
+    /// [`Driver::set_ascii_dump`] says where.
+    pub fn set_ascii_dump_at(&mut self, frames: impl IntoIterator<Item = u64>) {
+        if let Some(dump) = &mut self.ascii_dump {
+            dump.at = frames.into_iter().collect();
+        }
+    }
+
+    fn write_ascii_dump(&mut self, chip8: &Chip8) {
+        let Some(dump) = &mut self.ascii_dump else {
+            return;
+        };
+        let art = Ascii::new(chip8.display.pixels(), chip8.display.width(), chip8.display.height(), dump.glyphs);
+        let snapshot = Snapshot { art, frame: self.frames };
+        if let Err(e) = writeln!(dump.out, "{}", snapshot).and_then(|()| dump.out.flush()) {
+            log::warn!(target: LOG_TARGET, "Failed to write the screen: {}", e);
+        }
+    }
+
+    /// Runs `hooks` at their points in every frame from now on.
+    pub fn set_hooks(&mut self, hooks: impl Hooks + 'static) {
+        self.hooks = Some(Box::new(hooks));
+    }
+
+    /// Plays `timeline`'s events at their frames, counted from the first one
+    /// this driver runs. They're merged with the frontend's input, or with
+    /// `exclusive` replace its keypad events; quitting and the other
+    /// controls keep working either way.
+    pub fn set_input_timeline(&mut self, timeline: InputTimeline, exclusive: bool) {
+        self.timeline = timeline;
+        self.timeline_exclusive = exclusive;
+    }
+
+    /// Keeps the speed keys and the menu within `limits`, starting at 100%
+    /// or the nearest limit.
+    pub fn set_speed_limits(&mut self, limits: SpeedLimits) {
+        self.speed = Speed::new(limits);
+    }
+
+    /// Runs at `percent` of the base clock, within the limits.
+    pub fn set_speed(&mut self, percent: u32) {
+        self.speed.set_percent(percent);
+    }
+
+    /// Runs at `hz`, within the limits.
+    pub fn set_clock_hz(&mut self, hz: u32) {
+        self.speed.set_hz(hz);
+    }
+
+    pub fn speed(&self) -> Speed {
+        self.speed
+    }
+
+    /// Spends each frame's time as `timing` says, starting afresh.
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.budget = FrameBudget::new(timing);
+    }
+
+    pub fn timing(&self) -> Timing {
+        self.budget.timing()
+    }
+
+    /// Tells the driver the frontend's window scale, kept within
+    /// [`MIN_SCALE`] and [`MAX_SCALE`], which turns on the scale events and
+    /// saving the scale with the game's settings.
+    pub fn set_window_scale(&mut self, scale: usize) {
+        self.scale = Some(scale.clamp(MIN_SCALE, MAX_SCALE));
+    }
+
+    pub fn window_scale(&self) -> Option<usize> {
+        self.scale
+    }
+
+    /// Times the run from `start`, in emulated frames, for speedrunning. The
+    /// timer starts over whenever the machine is reset.
+    pub fn set_speedrun_timer(&mut self, start: TimerStart) {
+        self.timer = Some(SpeedrunTimer::new(start));
+    }
+
+    /// Lists `bindings` in the help overlay, for frontends to say which of
+    /// their keys send what.
+    pub fn set_bindings(&mut self, bindings: Vec<Binding>) {
+        self.bindings = bindings;
+    }
+
+    /// Frames run so far.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// The error the machine stopped on, once it has.
+    pub fn halted(&self) -> Option<&Chip8Error> {
+        self.halted.as_ref()
+    }
+
+    /// Where the program ended, if it has, on a 00FD or a 1NNN jumping to
+    /// itself. The machine isn't run any more after that, though its timers
+    /// still run down.
+    pub fn finished(&self) -> Option<u16> {
+        self.finished
+    }
+
+    /// Takes commands from the [`control::Controller`] paired with `receiver`,
+    /// applying them at the start of each frame.
+    pub fn attach_control(&mut self, receiver: ControlReceiver) {
+        self.control = Some(receiver);
+    }
+
+    /// Whether the machine is currently paused, by a focus loss, a
+    /// controller, the pause menu or the help overlay.
+    pub fn paused(&self) -> bool {
+        self.held || self.menu.is_open() || self.help.is_some() || (self.pause_on_focus_loss && !self.focused)
+    }
+
+    /// Runs one frame: applies the pending input, scripted input and control
+    /// commands,
+    /// executes the current number of instructions, ticks the timers, updates the buzzer and presents the
+    /// display.
+    ///
+    /// Returns `false` once `input` reports [`InputEvent::Quit`], without
+    /// running or presenting anything more.
+    pub fn frame<V, I, A>(
+        &mut self,
+        chip8: &mut Chip8,
+        video: &mut V,
+        input: &mut I,
+        audio: &mut A,
+    ) -> bool
+    where
+        V: VideoSink,
+        I: InputSource,
+        A: AudioSink,
+    {
+        if self.reset_state.is_none() {
+            self.reset_state = Some(chip8.save_state());
+            // Rather than run the ROM until it trips over the extension.
+            if let Some(detection) = chip8.platform() {
+                self.halt(chip8, detection.into());
+            }
+        }
+        let mut events = input.drain_events();
+        if self.timeline_exclusive {
+            events.retain(|event| !matches!(event, InputEvent::Key { .. }));
+        }
+        events.extend_from_slice(self.timeline.events_at(self.frames));
+        for event in events {
+            match event {
+                InputEvent::Quit => {
+                    self.save_on_quit(chip8);
+                    return false;
+                }
+                InputEvent::DumpState => {
+                    #[cfg(feature = "serde")]
+                    self.try_dump_state(chip8);
+                }
+                InputEvent::Help if self.help.is_some() => self.help = None,
+                InputEvent::Help => {
+                    self.help = Some(0);
+                    self.keypad_card = false;
+                    chip8.keys = [false; 16];
+                }
+                InputEvent::Menu(key) if self.help.is_some() => self.turn_help_page(key),
+                InputEvent::Console(ConsoleKey::Toggle) => {
+                    self.console.toggle();
+                    chip8.keys = [false; 16];
+                }
+                InputEvent::Console(_) if !self.console.is_open() => {}
+                InputEvent::Console(key) => self.console_key(key),
+                InputEvent::Menu(MenuKey::Enter) if self.console.is_open() => self.run_console_line(chip8),
+                InputEvent::Menu(key) if self.console.is_open() => self.edit_console_line(key),
+                InputEvent::Menu(MenuKey::Escape) if self.escape_quits() => {
+                    self.save_on_quit(chip8);
+                    return false;
+                }
+                InputEvent::Menu(_) if self.escape_quits() => {}
+                InputEvent::Menu(key) => {
+                    let opening = !self.menu.is_open();
+                    let action = self.menu.handle(key);
+                    if opening && self.menu.is_open() {
+                        // Keys held when the menu opened would otherwise stay
+                        // down, their releases going to the menu.
+                        chip8.keys = [false; 16];
+                    }
+                    if let Some(action) = action
+                        && !self.menu_action(chip8, action)
+                    {
+                        return false;
+                    }
+                }
+                // The guest doesn't see the keypad while the menu, help or
+                // console is open.
+                InputEvent::Key { .. } if self.menu.is_open() || self.help.is_some() || self.console.is_open() => {}
+                InputEvent::Key { key, pressed } => {
+                    chip8.set_key(key, pressed);
+                    if pressed && let Some(timer) = &mut self.timer {
+                        timer.input();
+                    }
+                }
+                InputEvent::Split => {
+                    if let Some(timer) = &mut self.timer {
+                        timer.split();
+                    }
+                }
+                InputEvent::SaveGameSettings => {
+                    if let Err(e) = self.save_game_settings(chip8) {
+                        log::warn!(target: LOG_TARGET, "Failed to save the game settings: {}", e);
+                    }
+                }
+                InputEvent::CopyScreen if self.console.is_open() => {}
+                InputEvent::CopyScreen => self.copy_screen(chip8),
+                InputEvent::AsciiDump => self.write_ascii_dump(chip8),
+                InputEvent::Window(_) => {}
+                // So are these, and the menu and help keep the machine paused
+                // themselves.
+                InputEvent::Pause | InputEvent::Step
+                    if self.console.is_open() || self.menu.is_open() || self.help.is_some() => {}
+                InputEvent::Pause => self.held = !self.held,
+                InputEvent::Step => self.step_instruction(chip8),
+                InputEvent::Registers => self.show_registers = !self.show_registers,
+                // The speed keys are characters typed into the console.
+                InputEvent::SpeedUp
+                | InputEvent::SlowDown
+                | InputEvent::FineSpeedUp
+                | InputEvent::FineSlowDown
+                | InputEvent::ResetSpeed
+                    if self.console.is_open() => {}
+                InputEvent::SpeedUp => self.speed.step_up(COARSE_STEP),
+                InputEvent::SlowDown => self.speed.step_down(COARSE_STEP),
+                InputEvent::FineSpeedUp => self.speed.step_up(FINE_STEP),
+                InputEvent::FineSlowDown => self.speed.step_down(FINE_STEP),
+                InputEvent::ResetSpeed => self.speed.reset(),
+                InputEvent::SetScale(scale) if self.scale.is_some() => self.set_window_scale(scale),
+                InputEvent::ScaleUp => self.scale = self.scale.map(|scale| (scale + 1).min(MAX_SCALE)),
+                InputEvent::ScaleDown => self.scale = self.scale.map(|scale| (scale - 1).max(MIN_SCALE)),
+                InputEvent::SetScale(_) => {}
+                InputEvent::Focus(focused) => self.focused = focused,
+            }
+        }
+        self.apply_control(chip8);
+
+        if !self.paused()
+            && self.halted.is_none()
+            && let Some(hooks) = &mut self.hooks
+            && hooks.frame_start(chip8, self.frames) == HookAction::Pause
+        {
+            self.held = true;
+        }
+
+        let paused = self.paused();
+        let mut beep = false;
+        if !paused {
+            if self.halted.is_none() && self.finished.is_none() {
+                self.execute(chip8);
+            }
+
+            let turbo = self.speed.is_overclocked();
+            if let Some(timer) = &mut self.timer {
+                timer.tick(turbo);
+            }
+
+            beep = chip8.sound_timer > 0;
+            chip8.tick_timers();
+        }
+
+        let sound = beep.then_some(match chip8.audio_pattern {
+            Some(samples) => AudioCommand::PatternOn(AudioPattern { samples, pitch: chip8.pitch }),
+            None => AudioCommand::BuzzerOn,
+        });
+        if sound != self.sound {
+            audio.command(sound.unwrap_or(AudioCommand::BuzzerOff));
+            self.sound = sound;
+        }
+
+        log::trace!(target: LOG_TARGET, "Frame {}: state {:016x}", self.frames, chip8.state_hash());
+        let menu = self.menu.is_open().then(|| MenuView {
+            labels: MenuItem::ALL.iter().map(|&item| self.menu_label(item)).collect(),
+            selected: MenuItem::ALL.iter().position(|&item| item == self.menu.selected()).unwrap_or(0),
+        });
+        let help = self.help.map(|page| {
+            let mut pages = self.help_pages();
+            let count = pages.len();
+            let page = page.min(count - 1);
+            HelpPage { lines: pages.swap_remove(page), page, pages: count }
+        });
+        let keypad_card = (self.help.is_some() && self.keypad_card).then(|| KeypadCard::new(&self.bindings));
+        let console = self.console.is_open().then(|| self.console.view(CONSOLE_ROWS));
+        let registers = (self.show_registers || self.held).then(|| {
+            let now = self.registers(chip8);
+            RegistersView::new(&now, self.registers_before.as_ref().unwrap_or(&now))
+        });
+        let timer = self.timer.as_ref().map(SpeedrunTimer::view);
+        let notice = self.notice.map(|(notice, _)| notice);
+        self.notice = self.notice.and_then(|(notice, frames)| (frames > 1).then(|| (notice, frames - 1)));
+        let display_changed = !self
+            .shown
+            .as_ref()
+            .is_some_and(|(display, colors)| *display == chip8.display && *colors == chip8.color_map);
+        if display_changed {
+            self.shown = Some((chip8.display.clone(), chip8.color_map));
+        }
+        video.present(&Frame {
+            pixels: chip8.display.pixels(),
+            plane2: chip8.display.plane(1),
+            width: chip8.display.width(),
+            height: chip8.display.height(),
+            palette: THEMES[self.theme].1,
+            color_map: chip8.quirks.chip8x.then_some(&chip8.color_map),
+            display_changed,
+            instructions_per_frame: self.speed.instructions_per_frame(),
+            speed: self.speed,
+            scale: self.scale,
+            paused,
+            halted: self.halted.as_ref(),
+            finished: self.finished,
+            state_hash: self.show_hash.then(|| chip8.state_hash()),
+            menu: menu.as_ref(),
+            help: help.as_ref(),
+            keypad_card: keypad_card.as_ref(),
+            console: console.as_ref(),
+            registers: registers.as_ref(),
+            timer: timer.as_ref(),
+            notice,
+            escape_quits: self.escape_quits(),
+            indicators: Indicators {
+                paused,
+                turbo: self.speed.is_overclocked(),
+                recording: false,
+                muted: audio.muted(),
+            },
+        });
+        self.frames += 1;
+        if self.ascii_dump.as_ref().is_some_and(|dump| dump.at.contains(&self.frames)) {
+            self.write_ascii_dump(chip8);
+        }
+        if !self.subscribers.is_empty() {
+            self.publish(Event::Frame {
+                frame: self.frames,
+                display_hash: control::display_hash(chip8),
+            });
+        }
+        true
+    }
+
+    /// Escape quits rather than opening the menu when asked to, and once the
+    /// machine has stopped or the program ended, as the frontends say.
+    fn escape_quits(&self) -> bool {
+        self.classic_escape || self.halted.is_some() || self.finished.is_some()
+    }
+
+    /// Puts the machine back as it was on the first frame.
+    fn reset(&mut self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        if let Some(state) = &self.reset_state {
+            chip8.load_state(state)?;
+        }
+        self.halted = None;
+        self.finished = None;
+        self.resume_past = None;
+        self.budget.reset();
+        if let Some(timer) = &mut self.timer {
+            timer.reset();
+        }
+        Ok(())
+    }
+
+    /// Carries out what the pause menu chose. Returns `false` to quit.
+    fn menu_action(&mut self, chip8: &mut Chip8, action: MenuAction) -> bool {
+        match action {
+            MenuAction::Resume => {}
+            MenuAction::Reset => {
+                if let Err(e) = self.reset(chip8) {
+                    log::warn!(target: LOG_TARGET, "Could not reset: {}", e);
+                }
+            }
+            MenuAction::SaveState => self.quick_save = Some(chip8.save_state()),
+            MenuAction::LoadState => {
+                if let Some(state) = &self.quick_save {
+                    match chip8.load_state(state) {
+                        Ok(()) => {
+                            self.finished = None;
+                            self.resume_past = None;
+                        }
+                        Err(e) => log::warn!(target: LOG_TARGET, "Could not load the saved state: {}", e),
+                    }
+                }
+            }
+            MenuAction::NextTheme => self.theme = (self.theme + 1) % THEMES.len(),
+            MenuAction::PreviousTheme => self.theme = (self.theme + THEMES.len() - 1) % THEMES.len(),
+            MenuAction::SpeedUp => self.speed.step_up(COARSE_STEP),
+            MenuAction::SlowDown => self.speed.step_down(COARSE_STEP),
+            MenuAction::Quit => {
+                self.save_on_quit(chip8);
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Moves the open help overlay along for `key`: Escape closes it, Enter
+    /// switches between its pages and the keypad card, and the arrows turn
+    /// the pages, wrapping around.
+    fn turn_help_page(&mut self, key: MenuKey) {
+        let Some(page) = self.help else {
+            return;
+        };
+        let count = self.help_pages().len();
+        self.help = match key {
+            MenuKey::Escape => None,
+            MenuKey::Enter => {
+                self.keypad_card = !self.keypad_card;
+                Some(page)
+            }
+            _ if self.keypad_card => Some(page),
+            MenuKey::Right | MenuKey::Down => Some((page + 1) % count),
+            MenuKey::Left | MenuKey::Up => Some((page + count - 1) % count),
+        };
+    }
+
+    fn console_key(&mut self, key: ConsoleKey) {
+        match key {
+            ConsoleKey::Toggle => self.console.toggle(),
+            // The backtick toggles the console, so it isn't typed too.
+            ConsoleKey::Char(ch) if ch.is_control() || ch == '`' => {}
+            ConsoleKey::Char(ch) => self.console.editor.insert(ch),
+            ConsoleKey::Backspace => self.console.editor.backspace(),
+            ConsoleKey::PageUp => self.console.scroll_up(CONSOLE_ROWS),
+            ConsoleKey::PageDown => self.console.scroll_down(CONSOLE_ROWS),
+        }
+    }
+
+    fn edit_console_line(&mut self, key: MenuKey) {
+        let editor = &mut self.console.editor;
+        match key {
+            MenuKey::Escape => self.console.close(),
+            MenuKey::Left => editor.left(),
+            MenuKey::Right => editor.right(),
+            MenuKey::Up => editor.previous(),
+            MenuKey::Down => editor.next(),
+            MenuKey::Enter => {}
+        }
+    }
+
+    /// Runs the console's line as a [`commands`] line, the same as the stdin
+    /// REPL would, echoing it above its output.
+    fn run_console_line(&mut self, chip8: &mut Chip8) {
+        let line = self.console.editor.submit();
+        self.console.print(format!("> {}", line));
+        let output = commands::run_line(&line, |command| self.dispatch(chip8, command).map_err(|e| e.to_string()));
+        for line in output {
+            self.console.print(line);
+        }
+    }
+
+    /// The help overlay's pages, as many lines to each as the window has
+    /// room for at its scale.
+    fn help_pages(&self) -> Vec<Vec<help::HelpLine>> {
+        let lines = help::help_lines(&self.bindings, |event| self.describe(event));
+        let rows = self.scale.map_or(HELP_PAGE_LINES, |scale| help::help_page_lines(help::help_text_scale(scale)));
+        help::paginate(&lines, rows)
+    }
+
+    /// What `event` does for the help overlay, or `None` if it does nothing
+    /// with the current settings.
+    fn describe(&self, event: &InputEvent) -> Option<String> {
+        let classic = self.classic_escape;
+        let text = match event {
+            InputEvent::Key { key, .. } => return Some(format!("Keypad {:X}", key)),
+            InputEvent::SpeedUp => "Speed up",
+            InputEvent::SlowDown => "Slow down",
+            InputEvent::FineSpeedUp => "Speed up a little",
+            InputEvent::FineSlowDown => "Slow down a little",
+            InputEvent::ResetSpeed => "Normal speed",
+            InputEvent::SetScale(scale) if self.scale.is_some() => return Some(format!("Window scale {}x", scale)),
+            InputEvent::ScaleUp if self.scale.is_some() => "Bigger window",
+            InputEvent::ScaleDown if self.scale.is_some() => "Smaller window",
+            InputEvent::SetScale(_) | InputEvent::ScaleUp | InputEvent::ScaleDown => return None,
+            InputEvent::Focus(_) => return None,
+            #[cfg(feature = "serde")]
+            InputEvent::DumpState if self.state_dump.is_some() => "Dump the state as JSON",
+            InputEvent::DumpState => return None,
+            InputEvent::Menu(MenuKey::Escape) if classic => "Quit",
+            InputEvent::Menu(_) if classic => return None,
+            InputEvent::Menu(MenuKey::Escape) => "Pause menu",
+            InputEvent::Menu(MenuKey::Up | MenuKey::Down) => "Menu: select",
+            InputEvent::Menu(MenuKey::Left | MenuKey::Right) => "Menu: adjust",
+            InputEvent::Menu(MenuKey::Enter) => "Menu: choose",
+            InputEvent::Help => "Show this help",
+            InputEvent::Split if self.timer.is_some() => "Speedrun split",
+            InputEvent::Split => return None,
+            InputEvent::SaveGameSettings if self.game_config.is_some() => "Save game settings",
+            InputEvent::SaveGameSettings => return None,
+            InputEvent::CopyScreen if self.clipboard.is_some() => "Copy the screen",
+            InputEvent::CopyScreen => return None,
+            InputEvent::AsciiDump if self.ascii_dump.is_some() => "Print the screen as text",
+            InputEvent::AsciiDump => return None,
+            InputEvent::Window(WindowToggle::AlwaysOnTop) => "Keep the window on top",
+            InputEvent::Window(WindowToggle::Borderless) => "Borderless window",
+            InputEvent::Window(WindowToggle::Resizable) => "Resizable window",
+            InputEvent::Console(ConsoleKey::Toggle) => "Debug console",
+            InputEvent::Console(_) => return None,
+            InputEvent::Pause => "Pause or resume",
+            InputEvent::Step => "Step one instruction",
+            InputEvent::Registers => "Register readout",
+            InputEvent::Quit => "Quit",
+        };
+        Some(text.to_string())
+    }
+
+    fn menu_label(&self, item: MenuItem) -> String {
+        match item {
+            MenuItem::Resume => "Resume".to_string(),
+            MenuItem::Reset => "Reset".to_string(),
+            MenuItem::SaveState => "Save State".to_string(),
+            MenuItem::LoadState if self.quick_save.is_none() => "Load State (none saved)".to_string(),
+            MenuItem::LoadState => "Load State".to_string(),
+            MenuItem::Theme => format!("Theme: < {} >", THEMES[self.theme].0),
+            MenuItem::Speed => format!("Speed: < {} >", self.speed.label()),
+            MenuItem::Quit => "Quit".to_string(),
+        }
+    }
+
+    /// Executes this frame's instructions, stopping early at a breakpoint or
+    /// when a hook pauses, and notices when the program has ended.
+    fn execute(&mut self, chip8: &mut Chip8) {
+        self.budget.start(self.speed);
+        self.watch_hit = None;
+        self.registers_before = Some(self.registers(chip8));
+        let stepwise = !self.breakpoints.is_empty() || self.hooks.is_some() || !chip8.watchpoints().is_empty();
+        let result = match self.budget.instructions() {
+            Some(count) if !stepwise => chip8.run_instructions(count),
+            _ => self.run_stepwise(chip8),
+        };
+        match result {
+            Err(e) => self.halt(chip8, e),
+            Ok(()) => {
+                if let Some(pc) = chip8.finished() {
+                    self.finish_program(pc);
+                }
+            }
+        }
+    }
+
+    fn run_stepwise(&mut self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        while self.budget.take(chip8) {
+            let pc = chip8.pc;
+            let resuming = self.resume_past.take() == Some(pc);
+            if !resuming && self.breakpoints.contains(&pc) {
+                self.held = true;
+                self.resume_past = Some(pc);
+                for line in self.stop_report(chip8) {
+                    log::info!(target: LOG_TARGET, "{}", line);
+                    self.console.print(line);
+                }
+                self.publish(Event::Breakpoint { pc });
+                break;
+            }
+
+            let mut draws = false;
+            if let Some(hooks) = &mut self.hooks {
+                if !resuming && hooks.wants_address(pc) {
+                    if hooks.address_hit(chip8, pc) == HookAction::Pause {
+                        self.held = true;
+                        self.resume_past = Some(pc);
+                        break;
+                    }
+                    // The hook jumped somewhere else; start over from there.
+                    if chip8.pc != pc {
+                        continue;
+                    }
+                }
+                draws = hooks.wants_draws() && chip8.peek_opcode(pc) & 0xF000 == 0xD000;
+            }
+
+            chip8.run_instructions(1)?;
+
+            if let Some(hit) = chip8.watch_hit() {
+                self.held = true;
+                self.watch_hit = Some(hit);
+                for line in self.stop_report(chip8) {
+                    log::info!(target: LOG_TARGET, "{}", line);
+                    self.console.print(line);
+                }
+                break;
+            }
+            if draws
+                && let Some(hooks) = &mut self.hooks
+                && hooks.draw(chip8) == HookAction::Pause
+            {
+                self.held = true;
+                break;
+            }
+            if chip8.waiting_for_vblank() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Holds the machine and executes the instruction at `pc`, for
+    /// [`InputEvent::Step`], printing it as [`disassemble`] would.
+    fn step_instruction(&mut self, chip8: &mut Chip8) {
+        self.held = true;
+        if self.halted.is_some() {
+            return;
+        }
+        let pc = chip8.pc;
+        let next = chip8.addressable_memory().get(pc as usize..).unwrap_or_default();
+        let line = disassemble(&next[..next.len().min(4)], pc)
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| format!("{:03X}: pc out of range", pc));
+        log::info!(target: LOG_TARGET, "{}", line);
+        self.console.print(line);
+        self.registers_before = Some(self.registers(chip8));
+        if let Err(e) = chip8.run_instructions(1) {
+            self.halt(chip8, e);
+        }
+        self.watch_hit = chip8.watch_hit();
+        if let Some(hit) = self.watch_hit {
+            let line = format!("Watchpoint: {}", hit);
+            log::info!(target: LOG_TARGET, "{}", line);
+            self.console.print(line);
+        }
+    }
+
+    fn halt(&mut self, chip8: &Chip8, error: Chip8Error) {
+        self.stop_timer();
+        self.publish(Event::Halted {
+            reason: chip8.memory_map().explain(&error),
+        });
+        self.halted = Some(error);
+    }
+
+    fn finish_program(&mut self, pc: u16) {
+        self.stop_timer();
+        let line = format!("Program halted at 0x{:03X}", pc);
+        log::info!(target: LOG_TARGET, "{}", line);
+        self.console.print(line);
+        self.finished = Some(pc);
+    }
+
+    fn stop_timer(&mut self) {
+        if let Some(frames) = self.timer.as_mut().and_then(SpeedrunTimer::finish) {
+            let mark = if self.timer.as_ref().is_some_and(SpeedrunTimer::is_invalid) { " (turbo used)" } else { "" };
+            let line = format!("Final time: {}{}", timer::format_time(frames), mark);
+            log::info!(target: LOG_TARGET, "{}", line);
+            self.console.print(line);
+        }
+    }
+
+    /// Sends `event` to the subscribers that want it, forgetting the ones
+    /// that have gone away.
+    fn publish(&mut self, event: Event) {
+        self.subscribers
+            .retain(|(filter, events)| !filter.accepts(&event) || events.send(event.clone()).is_ok());
+    }
+
+    fn apply_control(&mut self, chip8: &mut Chip8) {
+        let Some(control) = &self.control else {
+            return;
+        };
+        let requests: Vec<Request> = control.pending().collect();
+        for Request { command, reply } in requests {
+            let result = self.dispatch(chip8, command);
+            // The controller may have stopped waiting; that's its business.
+            let _ = reply.send(result);
+        }
+    }
+
+    fn dispatch(&mut self, chip8: &mut Chip8, command: Command) -> Result<Reply, Chip8Error> {
+        match command {
+            Command::Pause => self.held = true,
+            Command::Resume => self.held = false,
+            Command::Reset => self.reset(chip8)?,
+            Command::Step => {
+                self.held = true;
+                if self.halted.is_none()
+                    && let Err(e) = chip8.run_instructions(1)
+                {
+                    self.halt(chip8, e);
+                }
+                return Ok(Reply::Stepped { pc: chip8.pc });
+            }
+            Command::LoadRom(rom) => {
+                let config = self.config.unwrap_or_else(|| Chip8Config {
+                    quirks: chip8.quirks,
+                    seed: None,
+                    program_start: chip8.program_start(),
+                    font: chip8.font(),
+                    font_base: chip8.font_base(),
+                    sys_policy: chip8.sys_policy,
+                    opcode_policy: chip8.opcode_policy,
+                    strict_memory: chip8.strict_memory,
+                    warn_odd_length: chip8.warn_odd_length,
+                });
+                let mut fresh = Chip8::with_config(&config);
+                fresh.quirks = chip8.quirks;
+                fresh.set_decode_cache(chip8.decode_cache_enabled());
+                for watchpoint in chip8.watchpoints() {
+                    fresh.add_watchpoint(watchpoint.clone());
+                }
+                fresh.load_rom_bytes(&rom)?;
+                fresh.rpl_flags = chip8.rpl_flags;
+                fresh.observer = chip8.observer.take();
+                *chip8 = fresh;
+                self.reset_state = Some(chip8.save_state());
+                self.halted = None;
+                self.finished = None;
+                self.resume_past = None;
+                self.budget.reset();
+                if let Some(timer) = &mut self.timer {
+                    timer.reset();
+                }
+            }
+            Command::Key { key, pressed } => {
+                if !chip8.set_key(key, pressed) {
+                    return Err(Chip8Error::InvalidKey { key, pc: chip8.pc });
+                }
+            }
+            Command::ReadRegisters => return Ok(Reply::Registers(self.registers(chip8))),
+            Command::WriteRegisters { v, i, pc, delay_timer, sound_timer } => {
+                chip8.v = v;
+                chip8.i = i;
+                chip8.pc = pc;
+                chip8.delay_timer = delay_timer;
+                chip8.sound_timer = sound_timer;
+                self.finished = None;
+                self.resume_past = None;
+            }
+            Command::ReadMemory { addr, len } => {
+                let start = addr as usize;
+                let end = start + len as usize;
+                let bytes = chip8
+                    .addressable_memory()
+                    .get(start..end)
+                    .ok_or(Chip8Error::MemoryOutOfBounds { addr: end - 1, pc: chip8.pc })?;
+                return Ok(Reply::Memory(bytes.to_vec()));
+            }
+            Command::SaveState => {
+                return Ok(Reply::State {
+                    state: chip8.save_state(),
+                    map: chip8.memory_map(),
+                });
+            }
+            Command::WriteMemory { addr, bytes } => {
+                let start = addr as usize;
+                let end = start + bytes.len();
+                let size = chip8.memory_size();
+                chip8.memory[..size]
+                    .get_mut(start..end)
+                    .ok_or(Chip8Error::MemoryOutOfBounds { addr: end - 1, pc: chip8.pc })?
+                    .copy_from_slice(&bytes);
+                chip8.invalidate_decode_cache();
+            }
+            Command::SetBreakpoint(addr) => {
+                self.breakpoints.insert(addr);
+            }
+            Command::ClearBreakpoint(addr) => {
+                self.breakpoints.remove(&addr);
+            }
+            Command::Subscribe { filter, events } => self.subscribers.push((filter, events)),
+        }
+        Ok(Reply::Done)
+    }
+
+    fn registers(&self, chip8: &Chip8) -> Registers {
+        Registers {
+            v: chip8.v,
+            i: chip8.i,
+            pc: chip8.pc,
+            stack: chip8.stack.as_slice().to_vec(),
+            delay_timer: chip8.delay_timer,
+            sound_timer: chip8.sound_timer,
+            paused: self.paused(),
+            halted: self.halted.as_ref().map(ToString::to_string),
+        }
+    }
+
+    /// Pauses the machine whenever it's about to execute the instruction at
+    /// `addr`, as the console's `break` does.
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+
+    /// The breakpoint `chip8` is held at, if it stopped at one and hasn't
+    /// moved on since.
+    pub fn breakpoint_hit(&self, chip8: &Chip8) -> Option<u16> {
+        self.resume_past.filter(|&pc| self.held && pc == chip8.pc && self.breakpoints.contains(&pc))
+    }
+
+    /// The watched access that paused the machine, while it's still paused
+    /// after it.
+    pub fn watch_hit(&self) -> Option<WatchHit> {
+        self.watch_hit.filter(|_| self.held)
+    }
+
+    /// What the console and the log show on stopping at a breakpoint or a
+    /// watchpoint: where, or what was accessed, then the registers as the
+    /// `regs` command prints them.
+    pub fn stop_report(&self, chip8: &Chip8) -> Vec<String> {
+        let first = match self.watch_hit() {
+            Some(hit) => format!("Watchpoint: {}", hit),
+            None => format!("Breakpoint at 0x{:03X}", chip8.pc),
+        };
+        let mut lines = vec![first];
+        lines.extend(commands::format_registers(&self.registers(chip8)));
+        lines
+    }
+
+    /// Runs frames until the user quits.
+    ///
+    /// Pacing is left to the frontend, typically by blocking in
+    /// [`VideoSink::present`] until the next 60 Hz vsync.
+    pub fn run<V, I, A>(
+        mut self,
+        chip8: &mut Chip8,
+        video: &mut V,
+        input: &mut I,
+        audio: &mut A,
+    ) -> Result<(), Chip8Error>
+    where
+        V: VideoSink,
+        I: InputSource,
+        A: AudioSink,
+    {
+        while self.frame(chip8, video, input, audio) {}
+        self.finish()
+    }
+
+    /// How the run ended: the error the machine stopped on, if it did. A
+    /// program that [finished](Driver::finished) ended fine.
+    pub fn finish(self) -> Result<(), Chip8Error> {
+        match self.halted {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Runs `chip8` one frame at a time until `input` reports [`InputEvent::Quit`],
+/// with a default [`Driver`].
+///
+/// If the machine stopped on an error, that error is returned once the user
+/// quits; until then the last frame keeps being presented with `halted` set.
+pub fn run_emulator<V, I, A>(
+    chip8: &mut Chip8,
+    video: &mut V,
+    input: &mut I,
+    audio: &mut A,
+) -> Result<(), Chip8Error>
+where
+    V: VideoSink,
+    I: InputSource,
+    A: AudioSink,
+{
+    Driver::new().run(chip8, video, input, audio)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tone_is_a_sine_at_the_buzzer_frequency() {
+        let mut tone = Tone::new(44_100);
+        let mut samples = vec![0.0; 44_100];
+        tone.fill(&mut samples);
+
+        assert_eq!(samples[0], 0.0);
+        assert!(samples.iter().all(|s| (-1.0..=1.0).contains(s)));
+        let rising_zero_crossings = samples.windows(2).filter(|w| w[0] < 0.0 && w[1] >= 0.0).count();
+        assert!((439..=441).contains(&rising_zero_crossings), "{rising_zero_crossings}");
+    }
+
+    #[test]
+    fn patterns_play_a_bit_per_sample_at_the_default_pitch() {
+        assert_eq!(playback_rate(DEFAULT_PITCH), 4000.0);
+        assert_eq!(playback_rate(DEFAULT_PITCH + 48), 8000.0);
+        assert_eq!(playback_rate(DEFAULT_PITCH - 48), 2000.0);
+
+        let mut samples = [0; AUDIO_PATTERN_BYTES];
+        samples[..8].fill(0xFF);
+        let pattern = AudioPattern { samples, pitch: DEFAULT_PITCH };
+        let mut out = vec![0.0; 256];
+        PatternTone::new(pattern, 4000).fill(&mut out);
+        assert!(out[..64].iter().all(|&s| s == 1.0) && out[64..128].iter().all(|&s| s == -1.0));
+        assert_eq!(out[..128], out[128..], "it loops");
+
+        // At twice the rate each bit lasts two samples.
+        let mut out = vec![0.0; 256];
+        PatternTone::new(pattern, 8000).fill(&mut out);
+        assert!(out[..128].iter().all(|&s| s == 1.0) && out[128..].iter().all(|&s| s == -1.0));
+    }
+}
@@ -0,0 +1,102 @@
+//! The register readout: V0-VF, I, PC, the timers and the stack in hex,
+//! shown over the display while the machine is held, or all the time once
+//! it's toggled on. Values that changed since the machine last ran are
+//! marked, so stepping shows what each instruction did.
+
+use std::ops::Range;
+
+use super::control::Registers;
+
+/// Lines in a [`RegistersView`].
+pub const REGISTER_ROWS: usize = 4;
+
+/// One value, such as `V3=2A`, and whether it changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisterCell {
+    pub text: String,
+    pub changed: bool,
+}
+
+/// The readout, a row of cells per line: V0-V7, V8-VF, PC, I and the
+/// timers, then the stack's depth and its return addresses, outermost
+/// first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegistersView {
+    pub rows: [Vec<RegisterCell>; REGISTER_ROWS],
+}
+
+impl RegistersView {
+    /// The readout of `now`, marking what differs from `before`.
+    pub fn new(now: &Registers, before: &Registers) -> Self {
+        let cell = |text: String, changed: bool| RegisterCell { text, changed };
+        let v = |range: Range<usize>| -> Vec<RegisterCell> {
+            range.map(|n| cell(format!("V{:X}={:02X}", n, now.v[n]), now.v[n] != before.v[n])).collect()
+        };
+        let mut stack = vec![cell(format!("SP={:X}", now.stack.len()), now.stack.len() != before.stack.len())];
+        stack.extend(
+            now.stack.iter().enumerate().map(|(depth, addr)| cell(format!("{:03X}", addr), before.stack.get(depth) != Some(addr))),
+        );
+        Self {
+            rows: [
+                v(0..8),
+                v(8..16),
+                vec![
+                    cell(format!("PC={:03X}", now.pc), now.pc != before.pc),
+                    cell(format!("I={:03X}", now.i), now.i != before.i),
+                    cell(format!("DT={:02X}", now.delay_timer), now.delay_timer != before.delay_timer),
+                    cell(format!("ST={:02X}", now.sound_timer), now.sound_timer != before.sound_timer),
+                ],
+                stack,
+            ],
+        }
+    }
+
+    /// Every cell with its row and the column it starts at, the cells on a
+    /// row a space apart.
+    pub fn cells(&self) -> impl Iterator<Item = (usize, usize, &RegisterCell)> {
+        self.rows.iter().enumerate().flat_map(|(row, cells)| {
+            let mut column = 0;
+            cells.iter().map(move |cell| {
+                let start = column;
+                column += cell.text.chars().count() + 1;
+                (row, start, cell)
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registers() -> Registers {
+        Registers {
+            v: [0; 16],
+            i: 0x300,
+            pc: 0x204,
+            stack: vec![0x202],
+            delay_timer: 0,
+            sound_timer: 0,
+            paused: true,
+            halted: None,
+        }
+    }
+
+    #[test]
+    fn the_readout_marks_what_changed_and_lays_cells_a_space_apart() {
+        let before = registers();
+        let mut now = registers();
+        now.v[0xA] = 0x2A;
+        now.pc = 0x206;
+        now.stack.push(0x210);
+        let view = RegistersView::new(&now, &before);
+
+        let changed: Vec<(usize, usize, &str)> =
+            view.cells().filter(|(_, _, cell)| cell.changed).map(|(row, column, cell)| (row, column, cell.text.as_str())).collect();
+        assert_eq!(changed, [(1, 12, "VA=2A"), (2, 0, "PC=206"), (3, 0, "SP=2"), (3, 9, "210")]);
+        let stack: Vec<&str> = view.rows[3].iter().map(|cell| cell.text.as_str()).collect();
+        assert_eq!(stack, ["SP=2", "202", "210"]);
+
+        assert!(RegistersView::new(&now, &now).cells().all(|(_, _, cell)| !cell.changed));
+    }
+}
@@ -0,0 +1,70 @@
+//! The file FX75's [RPL user flags](crate::Chip8::rpl_flags) live in between
+//! runs, next to the ROM, as the HP48 kept them between games.
+//!
+//! It's the flags' raw bytes and nothing else. Files with fewer than
+//! [`RPL_FLAGS`] bytes, like the 8 other SUPER-CHIP emulators write, fill
+//! the first flags and leave the rest 0.
+
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::chip8::RPL_FLAGS;
+
+/// Where the flags for the ROM at `rom` go: beside it, with the extension
+/// swapped for `.rpl`.
+pub fn path_for(rom: &Path) -> PathBuf {
+    rom.with_extension("rpl")
+}
+
+/// Reads the flags at `path`, or `None` if no game has saved any yet.
+pub fn load(path: &Path) -> io::Result<Option<[u8; RPL_FLAGS]>> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    if bytes.len() > RPL_FLAGS {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("{} bytes is more than the {} RPL flags", bytes.len(), RPL_FLAGS),
+        ));
+    }
+    let mut flags = [0; RPL_FLAGS];
+    flags[..bytes.len()].copy_from_slice(&bytes);
+    Ok(Some(flags))
+}
+
+pub fn save(path: &Path, flags: &[u8; RPL_FLAGS]) -> io::Result<()> {
+    std::fs::write(path, flags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_round_trip_through_the_file_beside_the_rom() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = path_for(&dir.path().join("Ant.ch8"));
+        assert_eq!(path, dir.path().join("Ant.rpl"));
+        assert_eq!(load(&path).unwrap(), None);
+
+        let mut flags = [0; RPL_FLAGS];
+        flags[..3].copy_from_slice(&[0x12, 0x34, 0x56]);
+        flags[15] = 0xFF;
+        save(&path, &flags).unwrap();
+        assert_eq!(load(&path).unwrap(), Some(flags));
+    }
+
+    #[test]
+    fn short_files_fill_the_first_flags_and_long_ones_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("flags.rpl");
+        std::fs::write(&path, [1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        let flags = load(&path).unwrap().unwrap();
+        assert_eq!(flags[..9], [1, 2, 3, 4, 5, 6, 7, 8, 0]);
+
+        std::fs::write(&path, [0; RPL_FLAGS + 1]).unwrap();
+        assert_eq!(load(&path).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,181 @@
+//! Keypad input scheduled by frame number, so a run can be reproduced
+//! exactly.
+//!
+//! An [`InputTimeline`] is played back by a [`Driver`] alongside, or instead
+//! of, the frontend's own input; see [`Driver::set_input_timeline`]. Timelines
+//! are usually written by hand as input scripts, one event per line:
+//!
+//! ```text
+//! # frame  event     key
+//! 120      key_down  5
+//! 130      key_up    5
+//! ```
+//!
+//! or several at once in the compact form:
+//!
+//! ```text
+//! @120 +5 -7
+//! ```
+//!
+//! Frame 0 is the first frame the driver runs. Keys are hex digits 0-F, and
+//! `#` starts a comment.
+//!
+//! [`Driver`]: super::Driver
+//! [`Driver::set_input_timeline`]: super::Driver::set_input_timeline
+
+use std::collections::BTreeMap;
+
+use thiserror::Error;
+
+use super::InputEvent;
+
+/// Why an input script couldn't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("line {line}: {message}")]
+pub struct ScriptError {
+    /// 1-based line number.
+    pub line: usize,
+    pub message: String,
+}
+
+/// Input events keyed by the frame they happen at.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputTimeline {
+    frames: BTreeMap<u64, Vec<InputEvent>>,
+}
+
+impl InputTimeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses an input script, as described in the [module docs](self).
+    pub fn parse(script: &str) -> Result<Self, ScriptError> {
+        let mut timeline = Self::new();
+        for (index, line) in script.lines().enumerate() {
+            let error = |message: String| ScriptError { line: index + 1, message };
+            let line = line.split('#').next().unwrap_or_default();
+            let mut words = line.split_whitespace();
+            let Some(first) = words.next() else {
+                continue;
+            };
+
+            if let Some(frame) = first.strip_prefix('@') {
+                let frame = parse_frame(frame).map_err(error)?;
+                let mut any = false;
+                for word in words {
+                    let pressed = match word.chars().next() {
+                        Some('+') => true,
+                        Some('-') => false,
+                        _ => return Err(error(format!("expected +key or -key, found '{}'", word))),
+                    };
+                    let key = parse_key(&word[1..]).map_err(error)?;
+                    timeline.push(frame, InputEvent::Key { key, pressed });
+                    any = true;
+                }
+                if !any {
+                    return Err(error(format!("@{} has no keys", frame)));
+                }
+            } else {
+                let frame = parse_frame(first).map_err(error)?;
+                let pressed = match words.next() {
+                    Some("key_down") => true,
+                    Some("key_up") => false,
+                    Some(other) => {
+                        return Err(error(format!("expected key_down or key_up, found '{}'", other)));
+                    }
+                    None => return Err(error("expected key_down or key_up after the frame".to_string())),
+                };
+                let key = parse_key(words.next().unwrap_or_default()).map_err(error)?;
+                if let Some(extra) = words.next() {
+                    return Err(error(format!("unexpected '{}' after the key", extra)));
+                }
+                timeline.push(frame, InputEvent::Key { key, pressed });
+            }
+        }
+        Ok(timeline)
+    }
+
+    /// Schedules `event` for `frame`, after any already there.
+    pub fn push(&mut self, frame: u64, event: InputEvent) {
+        self.frames.entry(frame).or_default().push(event);
+    }
+
+    /// The events for `frame`, in the order they were added.
+    pub fn events_at(&self, frame: u64) -> &[InputEvent] {
+        self.frames.get(&frame).map_or(&[], Vec::as_slice)
+    }
+
+    /// The frame of the last event, if there are any.
+    pub fn last_frame(&self) -> Option<u64> {
+        self.frames.keys().next_back().copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+fn parse_frame(word: &str) -> Result<u64, String> {
+    word.parse()
+        .map_err(|_| format!("'{}' is not a frame number", word))
+}
+
+fn parse_key(word: &str) -> Result<u8, String> {
+    let digits = word.strip_prefix("0x").unwrap_or(word);
+    match u8::from_str_radix(digits, 16) {
+        Ok(key @ 0..=0xF) if !digits.is_empty() => Ok(key),
+        _ if word.is_empty() => Err("missing key".to_string()),
+        _ => Err(format!("'{}' is not a keypad key (0-F)", word)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: u8, pressed: bool) -> InputEvent {
+        InputEvent::Key { key, pressed }
+    }
+
+    #[test]
+    fn both_forms_schedule_the_same_events() {
+        let long = InputTimeline::parse("120 key_down 5\n120 key_up 7\n130 key_up 5\n").unwrap();
+        let compact = InputTimeline::parse("@120 +5 -7\n@130 -5").unwrap();
+        assert_eq!(long, compact);
+        assert_eq!(long.events_at(120), [key(5, true), key(7, false)]);
+        assert_eq!(long.events_at(121), []);
+        assert_eq!(long.last_frame(), Some(130));
+    }
+
+    #[test]
+    fn comments_blank_lines_and_hex_keys() {
+        let timeline = InputTimeline::parse("# setup\n\n  3 key_down A  # fire\n@4 +0xF\n").unwrap();
+        assert_eq!(timeline.events_at(3), [key(0xA, true)]);
+        assert_eq!(timeline.events_at(4), [key(0xF, true)]);
+        assert!(InputTimeline::parse("# nothing\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let cases = [
+            ("1 key_down 5\nsoon key_down 5", 2, "'soon' is not a frame number"),
+            ("1 key_press 5", 1, "expected key_down or key_up, found 'key_press'"),
+            ("\n\n1 key_down G", 3, "'G' is not a keypad key (0-F)"),
+            ("1 key_down 10", 1, "'10' is not a keypad key (0-F)"),
+            ("1 key_down", 1, "missing key"),
+            ("1 key_down 5 6", 1, "unexpected '6' after the key"),
+            ("@1 5", 1, "expected +key or -key, found '5'"),
+            ("@1", 1, "@1 has no keys"),
+            ("@x +5", 1, "'x' is not a frame number"),
+        ];
+        for (script, line, message) in cases {
+            let error = InputTimeline::parse(script).unwrap_err();
+            assert_eq!(error, ScriptError { line, message: message.to_string() }, "{script:?}");
+        }
+        assert_eq!(
+            InputTimeline::parse("\n1 key_down G").unwrap_err().to_string(),
+            "line 2: 'G' is not a keypad key (0-F)"
+        );
+    }
+}
@@ -0,0 +1,176 @@
+//! The secondary keypad bindings: the arrows for 2, 4, 6 and 8, which most
+//! games move with, and Space and Enter for 5 and 6, which most fire with.
+//! They press keypad keys alongside the 4x4 block rather than instead of
+//! it, and can be turned off for games that use those keys for something
+//! else.
+
+use super::InputEvent;
+use super::help::Binding;
+
+/// A key the secondary layer binds, for frontends to map to their own key
+/// codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecondaryKey {
+    Up,
+    Down,
+    Left,
+    Right,
+    Space,
+    Enter,
+}
+
+/// Each secondary key, its name and the keypad key it presses.
+pub const SECONDARY_KEYS: [(SecondaryKey, &str, u8); 6] = [
+    (SecondaryKey::Up, "Up", 0x2),
+    (SecondaryKey::Left, "Left", 0x4),
+    (SecondaryKey::Right, "Right", 0x6),
+    (SecondaryKey::Down, "Down", 0x8),
+    (SecondaryKey::Space, "Space", 0x5),
+    (SecondaryKey::Enter, "Enter", 0x6),
+];
+
+/// The keypad key `key` presses.
+pub fn keypad_key(key: SecondaryKey) -> u8 {
+    SECONDARY_KEYS.iter().find(|&&(secondary, _, _)| secondary == key).map_or(0, |&(_, _, keypad)| keypad)
+}
+
+/// The secondary keys as bindings, for the help overlay and the keypad
+/// card.
+pub fn bindings() -> impl Iterator<Item = Binding> {
+    SECONDARY_KEYS.iter().map(|&(_, name, key)| Binding { key: name, event: InputEvent::Key { key, pressed: true } })
+}
+
+/// Where a keypad key press came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    /// The 4x4 block.
+    Primary,
+    Secondary,
+}
+
+/// The keys each layer holds, merged so a keypad key stays down while any
+/// key bound to it is. With the secondary layer turned off its keys press
+/// nothing.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeypadLayers {
+    secondary: bool,
+    /// How many keys of each layer hold each keypad key: Right and Enter are
+    /// both 6.
+    held: [[u8; 16]; 2],
+}
+
+impl KeypadLayers {
+    pub fn new(secondary: bool) -> Self {
+        Self { secondary, ..Self::default() }
+    }
+
+    pub fn secondary(&self) -> bool {
+        self.secondary
+    }
+
+    /// Whether keypad `key` is down on either layer.
+    pub fn is_down(&self, key: u8) -> bool {
+        self.held.iter().any(|layer| layer.get(key as usize).is_some_and(|&count| count > 0))
+    }
+
+    /// Every keypad key, down or not.
+    pub fn keys(&self) -> [bool; 16] {
+        core::array::from_fn(|key| self.is_down(key as u8))
+    }
+
+    /// Presses or releases one key bound to keypad `key` on `layer`. Returns
+    /// the event to send if that changed whether the keypad key is down.
+    pub fn set(&mut self, layer: Layer, key: u8, pressed: bool) -> Option<InputEvent> {
+        if layer == Layer::Secondary && !self.secondary {
+            return None;
+        }
+        let was_down = self.is_down(key);
+        let count = self.held[layer as usize].get_mut(key as usize)?;
+        *count = if pressed { count.saturating_add(1) } else { count.saturating_sub(1) };
+        let down = self.is_down(key);
+        (down != was_down).then_some(InputEvent::Key { key, pressed: down })
+    }
+
+    /// Lets go of everything, as when the window loses focus.
+    pub fn release_all(&mut self) {
+        self.held = Default::default();
+    }
+}
+
+/// The keypad keys held, for frontends that poll which keys are down
+/// rather than report presses: `primary` from the 4x4 block and `secondary`
+/// from the other layer, each indexed by keypad key.
+pub fn merge(primary: [bool; 16], secondary: [bool; 16]) -> [bool; 16] {
+    core::array::from_fn(|key| primary[key] || secondary[key])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(key: u8) -> Option<InputEvent> {
+        Some(InputEvent::Key { key, pressed: true })
+    }
+
+    fn release(key: u8) -> Option<InputEvent> {
+        Some(InputEvent::Key { key, pressed: false })
+    }
+
+    #[test]
+    fn the_arrows_space_and_enter_press_the_usual_keys() {
+        assert_eq!(
+            [SecondaryKey::Up, SecondaryKey::Down, SecondaryKey::Left, SecondaryKey::Right].map(keypad_key),
+            [0x2, 0x8, 0x4, 0x6]
+        );
+        assert_eq!([SecondaryKey::Space, SecondaryKey::Enter].map(keypad_key), [0x5, 0x6]);
+        let names: Vec<_> = bindings().map(|binding| binding.key).collect();
+        assert_eq!(names, ["Up", "Left", "Right", "Down", "Space", "Enter"]);
+    }
+
+    #[test]
+    fn a_keypad_key_stays_down_while_either_layer_holds_it() {
+        let mut layers = KeypadLayers::new(true);
+        assert_eq!(layers.set(Layer::Primary, 0x2, true), press(0x2));
+        assert_eq!(layers.set(Layer::Secondary, 0x2, true), None);
+        assert_eq!(layers.set(Layer::Primary, 0x2, false), None);
+        assert!(layers.is_down(0x2));
+        assert_eq!(layers.set(Layer::Secondary, 0x2, false), release(0x2));
+
+        // Right and Enter are both 6.
+        assert_eq!(layers.set(Layer::Secondary, 0x6, true), press(0x6));
+        assert_eq!(layers.set(Layer::Secondary, 0x6, true), None);
+        assert_eq!(layers.set(Layer::Secondary, 0x6, false), None);
+        assert_eq!(layers.set(Layer::Secondary, 0x6, false), release(0x6));
+        // A stray release doesn't go negative.
+        assert_eq!(layers.set(Layer::Secondary, 0x6, false), None);
+        assert_eq!(layers.set(Layer::Primary, 0x10, true), None);
+    }
+
+    #[test]
+    fn polled_keys_merge() {
+        let mut primary = [false; 16];
+        primary[0x5] = true;
+        let mut secondary = [false; 16];
+        secondary[0x2] = true;
+        secondary[0x5] = true;
+        let keys = merge(primary, secondary);
+        assert!(keys[0x2] && keys[0x5]);
+        assert_eq!(keys.iter().filter(|&&down| down).count(), 2);
+
+        let mut layers = KeypadLayers::new(true);
+        layers.set(Layer::Primary, 0x5, true);
+        layers.set(Layer::Secondary, 0x2, true);
+        assert_eq!(layers.keys(), keys);
+        layers.release_all();
+        assert_eq!(layers.keys(), [false; 16]);
+    }
+
+    #[test]
+    fn a_turned_off_layer_presses_nothing() {
+        let mut layers = KeypadLayers::new(false);
+        assert!(!layers.secondary());
+        assert_eq!(layers.set(Layer::Secondary, 0x2, true), None);
+        assert_eq!(layers.keys(), [false; 16]);
+        assert_eq!(layers.set(Layer::Primary, 0x2, true), press(0x2));
+    }
+}
@@ -0,0 +1,394 @@
+//! Emulation speed as a clock rate in Hz: what the speed keys step, what's
+//! shown to the user, and how it turns into instructions each frame.
+
+use core::ops::RangeInclusive;
+
+use super::DEFAULT_INSTRUCTIONS_PER_FRAME;
+use crate::Chip8;
+use crate::vip_timing::{self, CYCLES_PER_FRAME};
+
+/// The timers' rate, which frames run at.
+const FRAME_RATE: u32 = 60;
+/// The base clock, 100%, in instructions per second.
+pub const BASE_HZ: u32 = DEFAULT_INSTRUCTIONS_PER_FRAME * FRAME_RATE;
+/// How far `+` and `-` move the speed, in percent of the base clock...
+pub const COARSE_STEP: u32 = 25;
+/// ...and how far they move it with Shift.
+pub const FINE_STEP: u32 = 5;
+
+/// The slowest and fastest speeds allowed, in percent of the base clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpeedLimits {
+    pub min_percent: u32,
+    pub max_percent: u32,
+}
+
+/// 20% to 500%, the range the speed keys have always covered.
+impl Default for SpeedLimits {
+    fn default() -> Self {
+        Self { min_percent: 20, max_percent: 500 }
+    }
+}
+
+impl SpeedLimits {
+    /// The limits, unless `min_percent` is 0 or above `max_percent`.
+    pub fn new(min_percent: u32, max_percent: u32) -> Result<Self, String> {
+        if min_percent == 0 || min_percent > max_percent {
+            return Err(format!("Invalid speed range: {}% to {}%", min_percent, max_percent));
+        }
+        Ok(Self { min_percent, max_percent })
+    }
+
+    /// The slowest and fastest clocks.
+    pub fn hz_range(&self) -> RangeInclusive<u32> {
+        percent_hz(self.min_percent)..=percent_hz(self.max_percent)
+    }
+
+    fn clamp(&self, hz: u32) -> u32 {
+        hz.clamp(percent_hz(self.min_percent), percent_hz(self.max_percent))
+    }
+}
+
+/// `percent` of the base clock, in Hz.
+fn percent_hz(percent: u32) -> u32 {
+    BASE_HZ.saturating_mul(percent) / 100
+}
+
+/// The current speed and the limits it's kept within.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Speed {
+    hz: u32,
+    limits: SpeedLimits,
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Self::new(SpeedLimits::default())
+    }
+}
+
+impl Speed {
+    /// The base clock, or the nearest limit if that's outside them.
+    pub fn new(limits: SpeedLimits) -> Self {
+        Self { hz: limits.clamp(BASE_HZ), limits }
+    }
+
+    /// The speed as a percentage of the base clock, to the nearest one.
+    pub fn percent(&self) -> u32 {
+        (self.hz * 100 + BASE_HZ / 2) / BASE_HZ
+    }
+
+    pub fn limits(&self) -> SpeedLimits {
+        self.limits
+    }
+
+    /// Sets the speed to `percent` of the base clock, kept within the
+    /// limits.
+    pub fn set_percent(&mut self, percent: u32) {
+        self.set_hz(percent_hz(percent));
+    }
+
+    /// Runs at `hz`, kept within the limits.
+    pub fn set_hz(&mut self, hz: u32) {
+        self.hz = self.limits.clamp(hz);
+    }
+
+    /// Up to the next multiple of `step` percent of the base clock, so
+    /// speeds stay on round numbers after a fine adjustment or `--hz`.
+    pub fn step_up(&mut self, step: u32) {
+        let step = percent_hz(step);
+        self.set_hz((self.hz / step + 1) * step);
+    }
+
+    /// Down to the previous multiple of `step` percent.
+    pub fn step_down(&mut self, step: u32) {
+        let step = percent_hz(step);
+        self.set_hz(self.hz.div_ceil(step).saturating_sub(1) * step);
+    }
+
+    pub fn reset(&mut self) {
+        self.set_hz(BASE_HZ);
+    }
+
+    /// Whether this is faster than the base clock.
+    pub fn is_overclocked(&self) -> bool {
+        self.hz > BASE_HZ
+    }
+
+    /// The instruction rate, which [`FrameBudget`] keeps to exactly.
+    pub fn hz(&self) -> u32 {
+        self.hz
+    }
+
+    /// Instructions per frame at this speed, on average and to the nearest
+    /// whole one.
+    pub fn instructions_per_frame(&self) -> u32 {
+        (self.hz + FRAME_RATE / 2) / FRAME_RATE
+    }
+
+    /// The speed as `150% (900 Hz)`.
+    pub fn label(&self) -> String {
+        format!("{}% ({} Hz)", self.percent(), self.hz)
+    }
+}
+
+/// What a frame's worth of emulated time buys.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Timing {
+    /// Every instruction takes the same time, [`Speed::hz`] of them a
+    /// second.
+    #[default]
+    Fixed,
+    /// Each instruction takes as many machine cycles as it did on the
+    /// COSMAC VIP; see [`vip_timing`]. The speed scales the VIP's clock.
+    Vip,
+}
+
+impl Timing {
+    /// Names accepted by [`Timing::from_name`].
+    pub const NAMES: &'static [&'static str] = &["fixed", "vip"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fixed" => Some(Self::Fixed),
+            "vip" => Some(Self::Vip),
+            _ => None,
+        }
+    }
+}
+
+/// How many instructions each frame gets. With [`Timing::Fixed`] the
+/// fraction of one that doesn't fit carries into the next frame, so 750 Hz
+/// runs 12 and 13 in turn rather than rounding to 780; with
+/// [`Timing::Vip`] an instruction that overruns the frame's cycles takes
+/// them from the next.
+///
+/// Time is counted in frames, a sixtieth of a second each, which the
+/// frontends pace to the wall clock; that keeps headless runs and recorded
+/// input reproducible however fast the host is. A frame cut short by the
+/// display wait or a breakpoint doesn't get its leftovers back; the machine
+/// was idle for them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameBudget {
+    timing: Timing,
+    /// Instruction-sixtieths owed from earlier frames, always under one
+    /// instruction.
+    carry: u32,
+    /// Fixed timing's instructions left this frame.
+    instructions: u32,
+    /// VIP timing's cycles left this frame, below zero after an overrun.
+    cycles: i64,
+}
+
+impl FrameBudget {
+    pub fn new(timing: Timing) -> Self {
+        Self { timing, ..Self::default() }
+    }
+
+    pub fn timing(&self) -> Timing {
+        self.timing
+    }
+
+    /// Gives a new frame its time at `speed`.
+    pub fn start(&mut self, speed: Speed) {
+        match self.timing {
+            Timing::Fixed => {
+                let owed = self.carry + speed.hz();
+                self.carry = owed % FRAME_RATE;
+                self.instructions = owed / FRAME_RATE;
+            }
+            Timing::Vip => {
+                let frame = CYCLES_PER_FRAME as u64 * speed.hz() as u64 / BASE_HZ as u64;
+                self.cycles = self.cycles.min(0) + frame as i64;
+            }
+        }
+    }
+
+    /// The instructions left this frame, when that doesn't depend on which
+    /// ones they are.
+    pub fn instructions(&self) -> Option<u32> {
+        (self.timing == Timing::Fixed).then_some(self.instructions)
+    }
+
+    /// Whether the machine has time left this frame for its next
+    /// instruction, charging it if so.
+    pub fn take(&mut self, chip8: &Chip8) -> bool {
+        match self.timing {
+            Timing::Fixed => {
+                let left = self.instructions > 0;
+                self.instructions = self.instructions.saturating_sub(1);
+                left
+            }
+            Timing::Vip => {
+                let left = self.cycles > 0;
+                if left {
+                    self.cycles -= vip_timing::next_cycles(chip8) as i64;
+                }
+                left
+            }
+        }
+    }
+
+    /// Drops whatever was carried over, so runs from a reset repeat exactly.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.timing);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rng;
+
+    /// The instructions each of `count` frames gets.
+    fn frames(budget: &mut FrameBudget, speed: Speed, count: usize) -> Vec<u32> {
+        (0..count)
+            .map(|_| {
+                budget.start(speed);
+                budget.instructions().unwrap()
+            })
+            .collect()
+    }
+
+    /// How many `program` instructions a VIP frame at `speed` takes, after
+    /// `warmup` frames.
+    fn vip_frame(program: &[u8], speed: Speed, warmup: usize) -> u32 {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.load_rom_bytes(&program.repeat(3584 / program.len())).unwrap();
+        let mut budget = FrameBudget::new(Timing::Vip);
+        let mut count = 0;
+        for _ in 0..=warmup {
+            budget.start(speed);
+            count = 0;
+            while budget.take(&chip8) {
+                chip8.step().unwrap();
+                count += 1;
+            }
+        }
+        assert_eq!(budget.instructions(), None);
+        count
+    }
+
+    #[test]
+    fn vip_frames_spend_cycles_so_draws_fit_fewer() {
+        let speed = Speed::default();
+        // 6XNN: 46 cycles, 80 of them to 3668 and a debt of 12.
+        assert_eq!(vip_frame(&[0x60, 0x01], speed, 0), 80);
+        assert_eq!(vip_frame(&[0x60, 0x01], speed, 1), 80);
+        // D01F at x = 0: 40 + 26 + 15 * 30 = 516 cycles.
+        assert_eq!(vip_frame(&[0xD0, 0x1F], speed, 0), 8);
+        // Twice the clock, twice the cycles.
+        let mut fast = speed;
+        fast.set_percent(200);
+        assert_eq!(vip_frame(&[0x60, 0x01], fast, 0), 160);
+        assert_eq!(Timing::from_name("vip"), Some(Timing::Vip));
+        assert_eq!(Timing::from_name("cycle"), None);
+    }
+
+    #[test]
+    fn steps_land_on_multiples_of_the_step() {
+        let mut speed = Speed::default();
+        speed.step_up(COARSE_STEP);
+        assert_eq!(speed.percent(), 125);
+        speed.step_down(FINE_STEP);
+        speed.step_down(FINE_STEP);
+        assert_eq!(speed.percent(), 115);
+        speed.step_up(COARSE_STEP);
+        assert_eq!(speed.percent(), 125);
+        speed.step_down(FINE_STEP);
+        speed.step_down(COARSE_STEP);
+        assert_eq!(speed.percent(), 100);
+        speed.reset();
+        assert_eq!(speed.percent(), 100);
+    }
+
+    #[test]
+    fn the_limits_hold() {
+        let mut speed = Speed::new(SpeedLimits::new(50, 150).unwrap());
+        for _ in 0..10 {
+            speed.step_up(COARSE_STEP);
+        }
+        assert_eq!(speed.percent(), 150);
+        for _ in 0..10 {
+            speed.step_down(COARSE_STEP);
+        }
+        assert_eq!(speed.percent(), 50);
+        speed.set_percent(1000);
+        assert_eq!(speed.percent(), 150);
+
+        // 100% may be outside them.
+        assert_eq!(Speed::new(SpeedLimits::new(200, 300).unwrap()).percent(), 200);
+        assert!(SpeedLimits::new(0, 100).is_err());
+        assert!(SpeedLimits::new(200, 100).is_err());
+    }
+
+    #[test]
+    fn slow_speeds_still_run_and_say_so() {
+        let mut speed = Speed::new(SpeedLimits::new(5, 500).unwrap());
+        speed.set_percent(5);
+        assert_eq!(speed.label(), "5% (30 Hz)");
+        // Half an instruction a frame runs one every other frame.
+        let mut budget = FrameBudget::default();
+        assert_eq!(frames(&mut budget, speed, 4), [0, 1, 0, 1]);
+        speed.set_percent(20);
+        assert_eq!(speed.label(), "20% (120 Hz)");
+        // What used to print as "Speed: 0x".
+        speed.set_percent(80);
+        assert_eq!(speed.label(), "80% (480 Hz)");
+    }
+
+    #[test]
+    fn instructions_follow_the_percentage() {
+        let mut speed = Speed::default();
+        assert_eq!((speed.instructions_per_frame(), speed.hz()), (10, 600));
+        speed.set_percent(125);
+        // 12.5 rounds half up.
+        assert_eq!(speed.instructions_per_frame(), 13);
+        assert_eq!(speed.label(), "125% (750 Hz)");
+        speed.set_percent(500);
+        assert_eq!(speed.instructions_per_frame(), 50);
+    }
+
+    #[test]
+    fn clocks_convert_to_and_from_percentages() {
+        let mut speed = Speed::default();
+        assert!(!speed.is_overclocked());
+        speed.set_hz(1800);
+        assert_eq!((speed.percent(), speed.hz()), (300, 1800));
+        assert!(speed.is_overclocked());
+        speed.set_percent(125);
+        assert_eq!(speed.hz(), 750);
+        // Clocks between percentages are kept as they are.
+        speed.set_hz(700);
+        assert_eq!(speed.label(), "117% (700 Hz)");
+        speed.set_hz(100_000);
+        assert_eq!((speed.percent(), speed.hz()), (500, 3000));
+        assert_eq!(SpeedLimits::default().hz_range(), 120..=3000);
+    }
+
+    #[test]
+    fn steps_from_an_odd_clock_land_on_round_ones() {
+        let mut speed = Speed::default();
+        speed.set_hz(700);
+        speed.step_up(COARSE_STEP);
+        assert_eq!(speed.hz(), 750);
+        speed.set_hz(700);
+        speed.step_down(FINE_STEP);
+        assert_eq!(speed.hz(), 690);
+    }
+
+    #[test]
+    fn budgets_carry_the_fraction_and_keep_the_clock_exact() {
+        let mut speed = Speed::default();
+        speed.set_percent(125);
+        let mut budget = FrameBudget::default();
+        assert_eq!(frames(&mut budget, speed, 4), [12, 13, 12, 13]);
+        // A second of frames runs the clock's worth, for odd clocks too.
+        for hz in [600, 700, 750, 1234] {
+            speed.set_hz(hz);
+            budget.reset();
+            assert_eq!(frames(&mut budget, speed, 60).iter().sum::<u32>(), hz);
+        }
+        assert_eq!(Speed::default().instructions_per_frame(), DEFAULT_INSTRUCTIONS_PER_FRAME);
+    }
+}
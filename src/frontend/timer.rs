@@ -0,0 +1,232 @@
+//! The speedrun timer: emulated frames counted from a reset, or from the
+//! first key pressed after one, with splits marked along the way. It counts
+//! frames rather than reading a clock, so a run takes the same time however
+//! fast the host is, and stops with the machine when it's paused.
+
+/// Frames the timer counts per second: the timers' 60 Hz.
+pub const FRAMES_PER_SECOND: u64 = 60;
+/// Splits a frontend should list under the time, the newest last.
+pub const SPLITS_SHOWN: usize = 3;
+
+/// When the timer starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerStart {
+    /// As soon as the machine is reset, or on the first frame.
+    Reset,
+    /// On the first keypad press after a reset.
+    FirstInput,
+}
+
+/// `frames` as minutes, seconds and milliseconds.
+pub fn frame_time(frames: u64) -> (u64, u64, u64) {
+    let millis = frames * 1000 / FRAMES_PER_SECOND;
+    (millis / 60_000, millis / 1000 % 60, millis % 1000)
+}
+
+/// `frames` as `m:ss.mmm`.
+pub fn format_time(frames: u64) -> String {
+    let (minutes, seconds, millis) = frame_time(frames);
+    format!("{}:{:02}.{:03}", minutes, seconds, millis)
+}
+
+/// The timer's state between frames.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpeedrunTimer {
+    start: TimerStart,
+    frames: u64,
+    running: bool,
+    /// Stopped for good, by the machine halting, until the next reset.
+    finished: bool,
+    /// The time at each split, in frames.
+    splits: Vec<u64>,
+    /// Some of the run was played faster than the default speed.
+    invalid: bool,
+}
+
+impl SpeedrunTimer {
+    pub fn new(start: TimerStart) -> Self {
+        Self {
+            start,
+            frames: 0,
+            running: start == TimerStart::Reset,
+            finished: false,
+            splits: Vec::new(),
+            invalid: false,
+        }
+    }
+
+    /// Starts over, as the machine was reset.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.start);
+    }
+
+    /// A keypad key was pressed, which starts a timer waiting for one.
+    pub fn input(&mut self) {
+        if self.start == TimerStart::FirstInput && !self.finished {
+            self.running = true;
+        }
+    }
+
+    /// Counts one emulated frame, if the timer's running. Frames played with
+    /// `turbo` on invalidate the run.
+    pub fn tick(&mut self, turbo: bool) {
+        if self.running {
+            self.frames += 1;
+            self.invalid |= turbo;
+        }
+    }
+
+    /// Marks a split at the current time, if the timer's running.
+    pub fn split(&mut self) {
+        if self.running {
+            self.splits.push(self.frames);
+        }
+    }
+
+    /// Stops the timer for good, returning the final time in frames if it
+    /// was running.
+    pub fn finish(&mut self) -> Option<u64> {
+        let was_running = std::mem::replace(&mut self.running, false);
+        self.finished = true;
+        was_running.then_some(self.frames)
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn splits(&self) -> &[u64] {
+        &self.splits
+    }
+
+    pub fn is_invalid(&self) -> bool {
+        self.invalid
+    }
+
+    /// What a frontend should draw.
+    pub fn view(&self) -> TimerView {
+        let skip = self.splits.len().saturating_sub(SPLITS_SHOWN);
+        TimerView {
+            frames: self.frames,
+            splits: self.splits.iter().enumerate().skip(skip).map(|(index, &split)| (index + 1, split)).collect(),
+            running: self.running,
+            invalid: self.invalid,
+        }
+    }
+}
+
+/// The timer as a frontend should draw it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimerView {
+    pub frames: u64,
+    /// The last [`SPLITS_SHOWN`] splits, oldest first, each with its number
+    /// counting from 1.
+    pub splits: Vec<(usize, u64)>,
+    /// Whether it's counting; it isn't while waiting for the first input or
+    /// once the machine has halted.
+    pub running: bool,
+    /// Whether turbo was used, which doesn't count as a fair run.
+    pub invalid: bool,
+}
+
+impl TimerView {
+    /// The time, marked with a `*` when the run doesn't count.
+    pub fn time(&self) -> String {
+        let mark = if self.invalid { "*" } else { "" };
+        format!("{}{}", format_time(self.frames), mark)
+    }
+
+    /// Each listed split as `#n m:ss.mmm`.
+    pub fn split_lines(&self) -> impl Iterator<Item = String> + '_ {
+        self.splits.iter().map(|&(number, frames)| format!("#{} {}", number, format_time(frames)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_convert_at_sixty_a_second() {
+        assert_eq!(frame_time(0), (0, 0, 0));
+        assert_eq!(frame_time(1), (0, 0, 16));
+        assert_eq!(frame_time(59), (0, 0, 983));
+        assert_eq!(frame_time(60), (0, 1, 0));
+        assert_eq!(frame_time(60 * 60 + 30), (1, 0, 500));
+        assert_eq!(format_time(60 * 75 + 6), "1:15.100");
+        assert_eq!(format_time(60 * 60 * 61), "61:00.000");
+    }
+
+    #[test]
+    fn timing_from_reset_counts_straight_away() {
+        let mut timer = SpeedrunTimer::new(TimerStart::Reset);
+        timer.tick(false);
+        timer.tick(false);
+        assert_eq!(timer.frames(), 2);
+        timer.reset();
+        assert_eq!(timer.frames(), 0);
+        timer.tick(false);
+        assert_eq!(timer.frames(), 1);
+    }
+
+    #[test]
+    fn timing_from_input_waits_for_a_key() {
+        let mut timer = SpeedrunTimer::new(TimerStart::FirstInput);
+        timer.tick(false);
+        timer.split();
+        assert_eq!((timer.frames(), timer.splits()), (0, &[][..]));
+        assert!(!timer.view().running);
+        timer.input();
+        timer.tick(false);
+        assert_eq!(timer.frames(), 1);
+        timer.reset();
+        timer.tick(false);
+        assert_eq!(timer.frames(), 0);
+    }
+
+    #[test]
+    fn splits_keep_their_numbers_as_older_ones_scroll_off() {
+        let mut timer = SpeedrunTimer::new(TimerStart::Reset);
+        for _ in 0..5 {
+            timer.tick(false);
+            timer.split();
+        }
+        assert_eq!(timer.splits(), [1, 2, 3, 4, 5]);
+        let view = timer.view();
+        assert_eq!(view.splits, [(3, 3), (4, 4), (5, 5)]);
+        assert_eq!(view.split_lines().next().unwrap(), "#3 0:00.050");
+    }
+
+    #[test]
+    fn finishing_stops_the_clock_until_a_reset() {
+        let mut timer = SpeedrunTimer::new(TimerStart::FirstInput);
+        timer.input();
+        timer.tick(false);
+        assert_eq!(timer.finish(), Some(1));
+        assert_eq!(timer.finish(), None);
+        timer.input();
+        timer.tick(false);
+        assert_eq!(timer.frames(), 1);
+        timer.reset();
+        timer.input();
+        timer.tick(false);
+        assert_eq!(timer.frames(), 1);
+    }
+
+    #[test]
+    fn turbo_marks_the_run() {
+        let mut timer = SpeedrunTimer::new(TimerStart::FirstInput);
+        // Not while waiting to start.
+        timer.tick(true);
+        assert!(!timer.is_invalid());
+        timer.input();
+        timer.tick(false);
+        assert_eq!(timer.view().time(), "0:00.016");
+        timer.tick(true);
+        timer.tick(false);
+        assert!(timer.is_invalid());
+        assert_eq!(timer.view().time(), "0:00.050*");
+        timer.reset();
+        assert!(!timer.is_invalid());
+    }
+}
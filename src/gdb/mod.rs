@@ -0,0 +1,545 @@
+//! A GDB Remote Serial Protocol stub, so `gdb`, `lldb` and the UIs built on
+//! them can attach to a running emulator.
+//!
+//! Registers are numbered, and sent by `g`, in this order. Multi-byte ones
+//! are little-endian:
+//!
+//! | Number | Register | Bytes |                                   |
+//! |--------|----------|-------|-----------------------------------|
+//! | 0-15   | V0-VF    | 1     |                                   |
+//! | 16     | I        | 2     |                                   |
+//! | 17     | PC       | 2     |                                   |
+//! | 18     | SP       | 1     | call stack depth; writes ignored  |
+//! | 19     | DT       | 1     | delay timer                       |
+//! | 20     | ST       | 1     | sound timer                       |
+//!
+//! Supported packets are `?`, `g`, `G`, `p`, `P`, `m`, `M`, `X`, `c`, `s`,
+//! `Z0`/`z0` (the kind is ignored), `D`, `k`, `qSupported` and `qRcmd`;
+//! anything else gets the empty reply that means "not supported". Stops are reported as
+//! `S05` (SIGTRAP) for breakpoints and steps, `S02` (SIGINT) after Ctrl-C,
+//! and `S04` (SIGILL) once the machine has halted on an error.
+//!
+//! `qRcmd` takes three `monitor` commands: `snap` saves the machine, `diff`
+//! lists what has changed since, as [`StateDiff`](crate::StateDiff) formats
+//! it, and `dump addr [len]` prints memory with each line's address labeled
+//! by its [region](crate::memory_map).
+//!
+//! The machine is paused while a debugger is attached. Detaching clears its
+//! breakpoints and lets the machine run again.
+
+pub mod packet;
+
+use std::collections::{BTreeSet, VecDeque};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use log::{info, warn};
+
+use crate::Chip8;
+use crate::memory_map::MemoryMap;
+use crate::frontend::control::{Command, ControlError, Controller, Event, EventFilter, Registers, Reply};
+
+use packet::{Decoder, Incoming, encode, from_hex, to_hex};
+
+const LOG_TARGET: &str = "chip8::gdb";
+
+/// Bytes in the `g` packet's register block.
+const REGISTER_BYTES: usize = 23;
+
+/// How often a running machine is checked for an interrupt from the client.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Listens on `addr` and serves debuggers one at a time from a background
+/// thread until the process exits. Returns the address it's listening on.
+pub fn spawn(addr: impl ToSocketAddrs, controller: Controller) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)?;
+    let local_addr = listener.local_addr()?;
+    info!(target: LOG_TARGET, "GDB server listening on {}", local_addr);
+
+    thread::Builder::new().name("gdb-server".to_string()).spawn(move || {
+        for stream in listener.incoming() {
+            match stream.and_then(|stream| Session::new(stream, controller.clone())) {
+                Ok(session) => {
+                    if let Err(e) = session.run() {
+                        warn!(target: LOG_TARGET, "Debugger connection failed: {}", e);
+                    }
+                }
+                Err(e) => warn!(target: LOG_TARGET, "Failed to accept a debugger: {}", e),
+            }
+        }
+    })?;
+    Ok(local_addr)
+}
+
+/// Why a session ends early.
+enum Stop {
+    Io(io::Error),
+    /// The frame loop has gone.
+    Disconnected,
+}
+
+impl From<io::Error> for Stop {
+    fn from(e: io::Error) -> Self {
+        Stop::Io(e)
+    }
+}
+
+/// What a command wants from the machine that isn't there.
+struct Failed;
+
+impl From<ControlError> for Failed {
+    fn from(_: ControlError) -> Self {
+        Failed
+    }
+}
+
+struct Session {
+    stream: TcpStream,
+    decoder: Decoder,
+    incoming: VecDeque<Incoming>,
+    controller: Controller,
+    events: Receiver<Event>,
+    /// Set by this debugger, so they can be cleared when it detaches.
+    breakpoints: BTreeSet<u16>,
+    /// Saved by `monitor snap`, for `monitor diff`.
+    snapshot: Option<(Chip8, MemoryMap)>,
+}
+
+impl Session {
+    fn new(stream: TcpStream, controller: Controller) -> io::Result<Self> {
+        let (sender, events) = mpsc::channel();
+        let filter = EventFilter { halt: true, breakpoint: true, frame: false };
+        let subscribed = controller.send(Command::Subscribe { filter, events: sender });
+        if subscribed.is_err() {
+            return Err(io::Error::other("the emulator is no longer running"));
+        }
+        Ok(Self {
+            stream,
+            decoder: Decoder::new(),
+            incoming: VecDeque::new(),
+            controller,
+            events,
+            breakpoints: BTreeSet::new(),
+            snapshot: None,
+        })
+    }
+
+    fn run(mut self) -> io::Result<()> {
+        info!(target: LOG_TARGET, "Debugger attached");
+        let result = self.serve();
+        // Leave the machine running with none of our breakpoints.
+        for addr in std::mem::take(&mut self.breakpoints) {
+            let _ = self.controller.send(Command::ClearBreakpoint(addr));
+        }
+        let _ = self.controller.send(Command::Resume);
+        info!(target: LOG_TARGET, "Debugger detached");
+        match result {
+            Ok(()) | Err(Stop::Disconnected) => Ok(()),
+            Err(Stop::Io(e)) => Err(e),
+        }
+    }
+
+    fn serve(&mut self) -> Result<(), Stop> {
+        self.send(Command::Pause)?;
+        while let Some(incoming) = self.receive()? {
+            match incoming {
+                Incoming::Packet(packet) => {
+                    self.stream.write_all(b"+")?;
+                    match self.handle(&packet)? {
+                        Some(reply) => self.stream.write_all(&encode(&reply))?,
+                        None => return Ok(()),
+                    }
+                }
+                Incoming::BadChecksum => self.stream.write_all(b"-")?,
+                // Already stopped.
+                Incoming::Interrupt => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// The next message from the client, or `None` once it has hung up.
+    fn receive(&mut self) -> io::Result<Option<Incoming>> {
+        loop {
+            if let Some(incoming) = self.incoming.pop_front() {
+                return Ok(Some(incoming));
+            }
+            if !self.read_some()? {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Reads whatever the client has sent into `incoming`, returning `false`
+    /// at end of stream.
+    fn read_some(&mut self) -> io::Result<bool> {
+        let mut buffer = [0; 1024];
+        let read = self.stream.read(&mut buffer)?;
+        for &byte in &buffer[..read] {
+            self.incoming.extend(self.decoder.push(byte));
+        }
+        Ok(read > 0)
+    }
+
+    fn send(&self, command: Command) -> Result<Reply, Stop> {
+        match self.controller.send(command) {
+            Ok(reply) => Ok(reply),
+            Err(ControlError::Disconnected) => Err(Stop::Disconnected),
+            // Pausing, stepping and breakpoints can't fail on the machine's
+            // side; the commands that can go through `Failed` instead.
+            Err(ControlError::Machine(_)) => Ok(Reply::Done),
+        }
+    }
+
+    /// Applies one packet, returning the reply, or `None` to end the session.
+    fn handle(&mut self, packet: &[u8]) -> Result<Option<Vec<u8>>, Stop> {
+        let (kind, args) = match packet.split_first() {
+            Some((&kind, args)) => (kind, args),
+            None => return Ok(Some(Vec::new())),
+        };
+        let reply = match kind {
+            b'?' => self.stop_reply()?,
+            b'g' => match self.registers() {
+                Ok(registers) => to_hex(&encode_registers(&registers)).into_bytes(),
+                Err(Failed) => error(),
+            },
+            b'G' => ok_or_error(self.write_all_registers(args)),
+            b'p' => ok_or_error_with(self.read_register(args)),
+            b'P' => ok_or_error(self.write_register(args)),
+            b'm' => ok_or_error_with(self.read_memory(args)),
+            b'M' => ok_or_error(self.write_memory(args, true)),
+            b'X' => ok_or_error(self.write_memory(args, false)),
+            b'c' => {
+                if let Err(Failed) = self.jump(args) {
+                    return Ok(Some(error()));
+                }
+                self.resume()?
+            }
+            b's' => {
+                if let Err(Failed) = self.jump(args) {
+                    return Ok(Some(error()));
+                }
+                self.send(Command::Step)?;
+                self.stop_reply()?
+            }
+            b'Z' | b'z' => match parse_breakpoint(args) {
+                Some(addr) if kind == b'Z' => {
+                    self.send(Command::SetBreakpoint(addr))?;
+                    self.breakpoints.insert(addr);
+                    b"OK".to_vec()
+                }
+                Some(addr) => {
+                    self.send(Command::ClearBreakpoint(addr))?;
+                    self.breakpoints.remove(&addr);
+                    b"OK".to_vec()
+                }
+                None => Vec::new(),
+            },
+            b'q' if args.starts_with(b"Supported") => b"PacketSize=1000".to_vec(),
+            b'q' if args == b"Attached" => b"1".to_vec(),
+            b'q' if args.starts_with(b"Rcmd,") => match from_hex(&args[5..]) {
+                Some(command) => match self.monitor(&command) {
+                    Ok(output) => to_hex(output.as_bytes()).into_bytes(),
+                    Err(Failed) => error(),
+                },
+                None => error(),
+            },
+            b'D' => {
+                self.stream.write_all(&encode(b"OK"))?;
+                return Ok(None);
+            }
+            b'k' => return Ok(None),
+            _ => Vec::new(),
+        };
+        Ok(Some(reply))
+    }
+
+    /// `S05` while stopped normally, `S04` once halted.
+    fn stop_reply(&self) -> Result<Vec<u8>, Stop> {
+        match self.send(Command::ReadRegisters)? {
+            Reply::Registers(Registers { halted: Some(_), .. }) => Ok(b"S04".to_vec()),
+            _ => Ok(b"S05".to_vec()),
+        }
+    }
+
+    /// Runs the machine until it stops at a breakpoint, halts, or the client
+    /// interrupts it, and returns the stop reply.
+    fn resume(&mut self) -> Result<Vec<u8>, Stop> {
+        if let Reply::Registers(Registers { halted: Some(_), .. }) = self.send(Command::ReadRegisters)? {
+            return Ok(b"S04".to_vec());
+        }
+        // Anything from before this run is stale.
+        while self.events.try_recv().is_ok() {}
+        self.send(Command::Resume)?;
+
+        self.stream.set_read_timeout(Some(POLL_INTERVAL))?;
+        let stop = loop {
+            match self.events.try_recv() {
+                Ok(Event::Breakpoint { .. }) => break b"S05".to_vec(),
+                Ok(Event::Halted { .. }) => break b"S04".to_vec(),
+                Ok(Event::Frame { .. }) => continue,
+                Err(mpsc::TryRecvError::Disconnected) => return Err(Stop::Disconnected),
+                Err(mpsc::TryRecvError::Empty) => {}
+            }
+            if let Some(at) = self.incoming.iter().position(|m| *m == Incoming::Interrupt) {
+                self.incoming.remove(at);
+                self.send(Command::Pause)?;
+                break b"S02".to_vec();
+            }
+            match self.read_some() {
+                Ok(true) => {}
+                Ok(false) => return Err(Stop::Io(ErrorKind::UnexpectedEof.into())),
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(e) => return Err(Stop::Io(e)),
+            }
+        };
+        self.stream.set_read_timeout(None)?;
+        Ok(stop)
+    }
+
+    fn registers(&self) -> Result<Registers, Failed> {
+        match self.controller.send(Command::ReadRegisters)? {
+            Reply::Registers(registers) => Ok(registers),
+            _ => Err(Failed),
+        }
+    }
+
+    fn set_registers(&self, registers: &Registers) -> Result<(), Failed> {
+        self.controller.send(Command::WriteRegisters {
+            v: registers.v,
+            i: registers.i,
+            pc: registers.pc,
+            delay_timer: registers.delay_timer,
+            sound_timer: registers.sound_timer,
+        })?;
+        Ok(())
+    }
+
+    fn write_all_registers(&self, args: &[u8]) -> Result<(), Failed> {
+        let bytes = from_hex(args).filter(|bytes| bytes.len() == REGISTER_BYTES).ok_or(Failed)?;
+        let mut registers = self.registers()?;
+        decode_registers(&bytes, &mut registers);
+        self.set_registers(&registers)
+    }
+
+    fn read_register(&self, args: &[u8]) -> Result<Vec<u8>, Failed> {
+        let number = parse_hex(args).ok_or(Failed)?;
+        let (start, len) = register_span(number).ok_or(Failed)?;
+        let bytes = encode_registers(&self.registers()?);
+        Ok(to_hex(&bytes[start..start + len]).into_bytes())
+    }
+
+    fn write_register(&self, args: &[u8]) -> Result<(), Failed> {
+        let (number, value) = split_at_byte(args, b'=').ok_or(Failed)?;
+        let (start, len) = parse_hex(number).and_then(register_span).ok_or(Failed)?;
+        let value = from_hex(value).filter(|value| value.len() == len).ok_or(Failed)?;
+        let mut registers = self.registers()?;
+        let mut bytes = encode_registers(&registers);
+        bytes[start..start + len].copy_from_slice(&value);
+        decode_registers(&bytes, &mut registers);
+        self.set_registers(&registers)
+    }
+
+    /// Runs a `monitor` command, returning what it prints.
+    fn monitor(&mut self, command: &[u8]) -> Result<String, Failed> {
+        match command.trim_ascii() {
+            b"snap" => {
+                self.snapshot = Some(self.machine()?);
+                Ok("Saved a snapshot.\n".to_string())
+            }
+            b"diff" => match &self.snapshot {
+                Some((snapshot, map)) => {
+                    let mut diff = snapshot.diff(&self.machine()?.0);
+                    diff.map = map.clone();
+                    Ok(format!("{}\n", diff))
+                }
+                None => Ok("No snapshot yet; take one with `monitor snap`.\n".to_string()),
+            },
+            command => match command.strip_prefix(b"dump") {
+                Some(args) => {
+                    let text = std::str::from_utf8(args).map_err(|_| Failed)?;
+                    let mut words = text.split_whitespace();
+                    let hex = |word: &str| usize::from_str_radix(word.trim_start_matches("0x"), 16).ok();
+                    let addr = words.next().and_then(hex).ok_or(Failed)?;
+                    let len = match words.next() {
+                        Some(word) => hex(word).ok_or(Failed)?,
+                        None => 0x40,
+                    };
+                    let (chip8, map) = self.machine()?;
+                    Ok(map.hex_dump(chip8.addressable_memory(), addr..addr.saturating_add(len)))
+                }
+                None => Ok("Monitor commands: snap, diff, dump addr [len].\n".to_string()),
+            },
+        }
+    }
+
+    fn machine(&self) -> Result<(Chip8, MemoryMap), Failed> {
+        let Reply::State { state, map } = self.controller.send(Command::SaveState)? else {
+            return Err(Failed);
+        };
+        let mut chip8 = Chip8::new();
+        chip8.load_state(&state).map_err(|_| Failed)?;
+        Ok((chip8, map))
+    }
+
+    fn read_memory(&self, args: &[u8]) -> Result<Vec<u8>, Failed> {
+        let (addr, len) = split_at_byte(args, b',').ok_or(Failed)?;
+        let addr = parse_hex(addr).ok_or(Failed)?;
+        let len = parse_hex(len).ok_or(Failed)?;
+        match self.controller.send(Command::ReadMemory { addr, len })? {
+            Reply::Memory(bytes) => Ok(to_hex(&bytes).into_bytes()),
+            _ => Err(Failed),
+        }
+    }
+
+    /// `M addr,len:hex` when `hex`, else `X addr,len:binary`.
+    fn write_memory(&self, args: &[u8], hex: bool) -> Result<(), Failed> {
+        let (range, data) = split_at_byte(args, b':').ok_or(Failed)?;
+        let (addr, len) = split_at_byte(range, b',').ok_or(Failed)?;
+        let addr = parse_hex(addr).ok_or(Failed)?;
+        let len: usize = parse_hex::<u16>(len).ok_or(Failed)?.into();
+        let bytes = if hex { from_hex(data).ok_or(Failed)? } else { data.to_vec() };
+        if bytes.len() != len {
+            return Err(Failed);
+        }
+        if !bytes.is_empty() {
+            self.controller.send(Command::WriteMemory { addr, bytes })?;
+        }
+        Ok(())
+    }
+
+    /// The optional address `c` and `s` resume from.
+    fn jump(&self, args: &[u8]) -> Result<(), Failed> {
+        if args.is_empty() {
+            return Ok(());
+        }
+        let mut registers = self.registers()?;
+        registers.pc = parse_hex(args).ok_or(Failed)?;
+        self.set_registers(&registers)
+    }
+}
+
+fn error() -> Vec<u8> {
+    b"E01".to_vec()
+}
+
+fn ok_or_error(result: Result<(), Failed>) -> Vec<u8> {
+    match result {
+        Ok(()) => b"OK".to_vec(),
+        Err(Failed) => error(),
+    }
+}
+
+fn ok_or_error_with(result: Result<Vec<u8>, Failed>) -> Vec<u8> {
+    result.unwrap_or_else(|Failed| error())
+}
+
+fn split_at_byte(bytes: &[u8], separator: u8) -> Option<(&[u8], &[u8])> {
+    let at = bytes.iter().position(|&byte| byte == separator)?;
+    Some((&bytes[..at], &bytes[at + 1..]))
+}
+
+fn parse_hex<T: TryFrom<u64>>(digits: &[u8]) -> Option<T> {
+    let digits = std::str::from_utf8(digits).ok()?;
+    T::try_from(u64::from_str_radix(digits, 16).ok()?).ok()
+}
+
+/// The address of a `Z0`/`z0` packet's `0,addr,kind`.
+fn parse_breakpoint(args: &[u8]) -> Option<u16> {
+    let rest = args.strip_prefix(b"0,")?;
+    let (addr, _kind) = split_at_byte(rest, b',')?;
+    parse_hex(addr)
+}
+
+/// Where register `number` sits in the `g` block, and how wide it is.
+fn register_span(number: usize) -> Option<(usize, usize)> {
+    match number {
+        0..=15 => Some((number, 1)),
+        16 => Some((16, 2)),
+        17 => Some((18, 2)),
+        18..=20 => Some((number + 2, 1)),
+        _ => None,
+    }
+}
+
+fn encode_registers(registers: &Registers) -> [u8; REGISTER_BYTES] {
+    let mut bytes = [0; REGISTER_BYTES];
+    bytes[..16].copy_from_slice(&registers.v);
+    bytes[16..18].copy_from_slice(&registers.i.to_le_bytes());
+    bytes[18..20].copy_from_slice(&registers.pc.to_le_bytes());
+    bytes[20] = registers.stack.len() as u8;
+    bytes[21] = registers.delay_timer;
+    bytes[22] = registers.sound_timer;
+    bytes
+}
+
+/// Updates `registers` from a `g` block; SP is ignored.
+fn decode_registers(bytes: &[u8], registers: &mut Registers) {
+    registers.v.copy_from_slice(&bytes[..16]);
+    registers.i = u16::from_le_bytes([bytes[16], bytes[17]]);
+    registers.pc = u16::from_le_bytes([bytes[18], bytes[19]]);
+    registers.delay_timer = bytes[21];
+    registers.sound_timer = bytes[22];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registers() -> Registers {
+        Registers {
+            v: core::array::from_fn(|i| i as u8 * 0x11),
+            i: 0x0ABC,
+            pc: 0x0204,
+            stack: vec![0x200, 0x300],
+            delay_timer: 7,
+            sound_timer: 9,
+            paused: true,
+            halted: None,
+        }
+    }
+
+    #[test]
+    fn register_block_layout() {
+        let bytes = encode_registers(&registers());
+        assert_eq!(
+            to_hex(&bytes),
+            "00112233445566778899aabbccddeeff\
+             bc0a\
+             0402\
+             02\
+             07\
+             09"
+        );
+
+        let mut decoded = registers();
+        decoded.v = [0; 16];
+        decoded.pc = 0;
+        decode_registers(&bytes, &mut decoded);
+        assert_eq!(decoded, registers());
+    }
+
+    #[test]
+    fn register_numbers_map_onto_the_block() {
+        let bytes = encode_registers(&registers());
+        let read = |number| register_span(number).map(|(start, len)| &bytes[start..start + len]);
+        assert_eq!(read(0), Some(&[0x00][..]));
+        assert_eq!(read(15), Some(&[0xFF][..]));
+        assert_eq!(read(16), Some(&[0xBC, 0x0A][..]));
+        assert_eq!(read(17), Some(&[0x04, 0x02][..]));
+        assert_eq!(read(18), Some(&[2][..]));
+        assert_eq!(read(20), Some(&[9][..]));
+        assert_eq!(read(21), None);
+    }
+
+    #[test]
+    fn parses_breakpoint_packets() {
+        assert_eq!(parse_breakpoint(b"0,204,2"), Some(0x204));
+        assert_eq!(parse_breakpoint(b"1,204,2"), None);
+        assert_eq!(parse_breakpoint(b"0,10000,2"), None);
+        assert_eq!(parse_breakpoint(b"0,204"), None);
+    }
+}
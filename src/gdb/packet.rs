@@ -0,0 +1,209 @@
+//! Framing for the GDB Remote Serial Protocol.
+//!
+//! Packets are sent as `$payload#cs`. `cs` is the sum of the payload bytes
+//! as sent, modulo 256, in two hex digits. Within a payload, `$`, `#`, `}`
+//! and `*` are escaped as `}` followed by the byte XOR 0x20. The receiver
+//! acknowledges each packet with `+`, or `-` to ask for it again. Outside a
+//! packet, a single 0x03 byte asks the target to stop.
+
+/// Something the client sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Incoming {
+    /// A packet with a good checksum, unescaped.
+    Packet(Vec<u8>),
+    /// A packet whose checksum didn't match; answer with `-`.
+    BadChecksum,
+    /// Ctrl-C: stop the target.
+    Interrupt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Between packets, where only `$`, acks and interrupts mean anything.
+    Idle,
+    Payload,
+    /// After a `}` in the payload.
+    Escape,
+    /// After the `#`, with the first checksum digit once it arrives.
+    Checksum(Option<u8>),
+}
+
+/// Turns the byte stream from the client into [`Incoming`] messages.
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    state: State,
+    payload: Vec<u8>,
+    /// Running sum of the payload bytes as sent, before unescaping.
+    sum: u8,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self {
+            state: State::Idle,
+            payload: Vec::new(),
+            sum: 0,
+        }
+    }
+
+    /// Feeds one byte, returning a message once it's complete.
+    pub fn push(&mut self, byte: u8) -> Option<Incoming> {
+        match self.state {
+            State::Idle => match byte {
+                b'$' => {
+                    self.payload.clear();
+                    self.sum = 0;
+                    self.state = State::Payload;
+                }
+                0x03 => return Some(Incoming::Interrupt),
+                // Acks for our own packets, which are never resent, and noise.
+                _ => {}
+            },
+            State::Payload => match byte {
+                b'#' => self.state = State::Checksum(None),
+                // A new packet before this one ended: start over.
+                b'$' => {
+                    self.payload.clear();
+                    self.sum = 0;
+                }
+                b'}' => {
+                    self.sum = self.sum.wrapping_add(byte);
+                    self.state = State::Escape;
+                }
+                _ => {
+                    self.sum = self.sum.wrapping_add(byte);
+                    self.payload.push(byte);
+                }
+            },
+            State::Escape => {
+                self.sum = self.sum.wrapping_add(byte);
+                self.payload.push(byte ^ 0x20);
+                self.state = State::Payload;
+            }
+            State::Checksum(None) => self.state = State::Checksum(Some(byte)),
+            State::Checksum(Some(high)) => {
+                self.state = State::Idle;
+                let expected = hex_digit(high).zip(hex_digit(byte)).map(|(h, l)| h << 4 | l);
+                return Some(if expected == Some(self.sum) {
+                    Incoming::Packet(std::mem::take(&mut self.payload))
+                } else {
+                    Incoming::BadChecksum
+                });
+            }
+        }
+        None
+    }
+}
+
+/// Frames `payload` as a packet, escaping it as needed.
+pub fn encode(payload: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(payload.len() + 4);
+    packet.push(b'$');
+    let mut sum = 0u8;
+    for &byte in payload {
+        let escaped: &[u8] = match byte {
+            b'$' | b'#' | b'}' | b'*' => &[b'}', byte ^ 0x20],
+            _ => &[byte],
+        };
+        for &byte in escaped {
+            sum = sum.wrapping_add(byte);
+            packet.push(byte);
+        }
+    }
+    packet.extend_from_slice(format!("#{:02x}", sum).as_bytes());
+    packet
+}
+
+fn hex_digit(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|digit| digit as u8)
+}
+
+/// Lower-case hex, two digits per byte.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn from_hex(hex: &[u8]) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    hex.chunks(2)
+        .map(|pair| Some(hex_digit(pair[0])? << 4 | hex_digit(pair[1])?))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(bytes: &[u8]) -> Vec<Incoming> {
+        let mut decoder = Decoder::new();
+        bytes.iter().filter_map(|&byte| decoder.push(byte)).collect()
+    }
+
+    #[test]
+    fn decodes_packets_with_good_checksums() {
+        assert_eq!(decode_all(b"$?#3f"), [Incoming::Packet(b"?".to_vec())]);
+        assert_eq!(
+            decode_all(b"+$g#67+$m200,4#5f"),
+            [Incoming::Packet(b"g".to_vec()), Incoming::Packet(b"m200,4".to_vec())]
+        );
+        assert_eq!(decode_all(b"$#00"), [Incoming::Packet(Vec::new())]);
+        // Either case of hex digit.
+        assert_eq!(decode_all(b"$Z0,2a0,2#D7"), decode_all(b"$Z0,2a0,2#d7"));
+    }
+
+    #[test]
+    fn rejects_bad_checksums() {
+        assert_eq!(decode_all(b"$g#68"), [Incoming::BadChecksum]);
+        assert_eq!(decode_all(b"$g#zz"), [Incoming::BadChecksum]);
+        // And carries on with the next packet.
+        assert_eq!(
+            decode_all(b"$g#00$g#67"),
+            [Incoming::BadChecksum, Incoming::Packet(b"g".to_vec())]
+        );
+    }
+
+    #[test]
+    fn interrupts_only_count_between_packets() {
+        assert_eq!(decode_all(b"\x03"), [Incoming::Interrupt]);
+        let packet = encode(b"X200,1:\x03");
+        assert_eq!(decode_all(&packet), [Incoming::Packet(b"X200,1:\x03".to_vec())]);
+    }
+
+    #[test]
+    fn a_dollar_restarts_an_unfinished_packet() {
+        assert_eq!(decode_all(b"$garbage$g#67"), [Incoming::Packet(b"g".to_vec())]);
+    }
+
+    #[test]
+    fn escapes_round_trip() {
+        let payload = b"X200,4:}#$*";
+        let packet = encode(payload);
+        assert_eq!(&packet[..8], b"$X200,4:");
+        assert_eq!(&packet[8..16], b"}]}\x03}\x04}\x0a");
+        assert_eq!(decode_all(&packet), [Incoming::Packet(payload.to_vec())]);
+    }
+
+    #[test]
+    fn checksums_cover_the_escaped_bytes() {
+        // '}' (0x7D) + ']' (0x5D) = 0xDA
+        assert_eq!(encode(b"}"), b"$}]#da");
+        assert_eq!(encode(b"OK"), b"$OK#9a");
+        assert_eq!(encode(b""), b"$#00");
+    }
+
+    #[test]
+    fn hex_round_trips() {
+        assert_eq!(to_hex(&[0x00, 0xAB, 0x12]), "00ab12");
+        assert_eq!(from_hex(b"00AB12"), Some(vec![0x00, 0xAB, 0x12]));
+        assert_eq!(from_hex(b"abc"), None);
+        assert_eq!(from_hex(b"zz"), None);
+    }
+}
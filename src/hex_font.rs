@@ -0,0 +1,160 @@
+//! The 4x5 hex digits FX29 points I at, which the interpreter keeps below
+//! the program.
+
+/// Where the font goes unless [`Chip8::load_font`](crate::Chip8::load_font)
+/// puts it elsewhere. This interpreter has always kept it at 0x000, and
+/// moving it would change every pinned state hash; most others use 0x050,
+/// which ROMs that peek at the font may expect.
+pub const FONT_BASE: u16 = 0x000;
+
+/// Bytes per glyph.
+pub const GLYPH_BYTES: u16 = 5;
+
+/// Where SUPER-CHIP's 8x10 digits go, for FX30: past both the usual font
+/// and a 4x5 font at 0x050, where most other interpreters keep it.
+pub const BIG_FONT_BASE: u16 = 0x0A0;
+
+/// Bytes per 8x10 glyph.
+pub const BIG_GLYPH_BYTES: u16 = 10;
+
+/// SUPER-CHIP's 8x10 digits, 0 to 9; it has no big letters.
+pub const BIG_FONTSET: [u8; 100] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C,
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C,
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF,
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C,
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06,
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C,
+    0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C,
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60,
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C,
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C
+];
+
+/// The usual font, as most interpreters since CHIP-48 have drawn it.
+pub const FONTSET: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0,
+    0x20, 0x60, 0x20, 0x20, 0x70,
+    0xF0, 0x10, 0xF0, 0x80, 0xF0,
+    0xF0, 0x10, 0xF0, 0x10, 0xF0,
+    0x90, 0x90, 0xF0, 0x10, 0x10,
+    0xF0, 0x80, 0xF0, 0x10, 0xF0,
+    0xF0, 0x80, 0xF0, 0x90, 0xF0,
+    0xF0, 0x10, 0x20, 0x40, 0x40,
+    0xF0, 0x90, 0xF0, 0x90, 0xF0,
+    0xF0, 0x90, 0xF0, 0x10, 0xF0,
+    0xF0, 0x90, 0xF0, 0x90, 0x90,
+    0xE0, 0x90, 0xE0, 0x90, 0xE0,
+    0xF0, 0x80, 0x80, 0x80, 0xF0,
+    0xE0, 0x90, 0x90, 0x90, 0xE0,
+    0xF0, 0x80, 0xF0, 0x80, 0xF0,
+    0xF0, 0x80, 0xF0, 0x80, 0x80
+];
+
+/// The COSMAC VIP interpreter's own glyphs, squarer than [`FONTSET`].
+const VIP: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0,
+    0x60, 0x20, 0x20, 0x20, 0x70,
+    0xF0, 0x10, 0xF0, 0x80, 0xF0,
+    0xF0, 0x10, 0xF0, 0x10, 0xF0,
+    0xA0, 0xA0, 0xF0, 0x20, 0x20,
+    0xF0, 0x80, 0xF0, 0x10, 0xF0,
+    0xF0, 0x80, 0xF0, 0x90, 0xF0,
+    0xF0, 0x10, 0x10, 0x10, 0x10,
+    0xF0, 0x90, 0xF0, 0x90, 0xF0,
+    0xF0, 0x90, 0xF0, 0x10, 0xF0,
+    0xF0, 0x90, 0xF0, 0x90, 0x90,
+    0xF0, 0x50, 0x70, 0x50, 0xF0,
+    0xF0, 0x80, 0x80, 0x80, 0xF0,
+    0xF0, 0x50, 0x50, 0x50, 0xF0,
+    0xF0, 0x80, 0xF0, 0x80, 0xF0,
+    0xF0, 0x80, 0xF0, 0x80, 0x80
+];
+
+/// Rounded corners on the digits and curved letters.
+const ROUNDED: [u8; 80] = [
+    0x60, 0x90, 0x90, 0x90, 0x60,
+    0x20, 0x60, 0x20, 0x20, 0x70,
+    0xE0, 0x10, 0x60, 0x80, 0xF0,
+    0xE0, 0x10, 0x60, 0x10, 0xE0,
+    0x90, 0x90, 0xF0, 0x10, 0x10,
+    0xF0, 0x80, 0xE0, 0x10, 0xE0,
+    0x60, 0x80, 0xE0, 0x90, 0x60,
+    0xF0, 0x10, 0x20, 0x40, 0x40,
+    0x60, 0x90, 0x60, 0x90, 0x60,
+    0x60, 0x90, 0x70, 0x10, 0x60,
+    0x60, 0x90, 0xF0, 0x90, 0x90,
+    0xE0, 0x90, 0xE0, 0x90, 0xE0,
+    0x70, 0x80, 0x80, 0x80, 0x70,
+    0xE0, 0x90, 0x90, 0x90, 0xE0,
+    0xF0, 0x80, 0xE0, 0x80, 0xF0,
+    0xF0, 0x80, 0xE0, 0x80, 0x80
+];
+
+/// A set of glyphs for the hex digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HexFont {
+    /// [`FONTSET`].
+    #[default]
+    Standard,
+    Vip,
+    Rounded,
+}
+
+impl HexFont {
+    /// Names accepted by [`HexFont::from_name`].
+    pub const NAMES: &'static [&'static str] = &["standard", "vip", "rounded"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "standard" => Some(Self::Standard),
+            "vip" => Some(Self::Vip),
+            "rounded" => Some(Self::Rounded),
+            _ => None,
+        }
+    }
+
+    /// Whether a 4x5 font at `base` stays below the program and clear of
+    /// [`BIG_FONTSET`].
+    pub const fn fits_at(base: u16) -> bool {
+        let end = base as usize + 16 * GLYPH_BYTES as usize;
+        let below_big = end <= BIG_FONT_BASE as usize;
+        let above_big = base as usize >= BIG_FONT_BASE as usize + BIG_FONTSET.len();
+        end <= crate::chip8::PROGRAM_START as usize && (below_big || above_big)
+    }
+
+    /// The 16 glyphs, 0 to F.
+    pub fn glyphs(self) -> &'static [u8; 80] {
+        match self {
+            Self::Standard => &FONTSET,
+            Self::Vip => &VIP,
+            Self::Rounded => &ROUNDED,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_font_has_a_name_and_distinct_glyphs() {
+        for &name in HexFont::NAMES {
+            let font = HexFont::from_name(name).unwrap();
+            let glyphs: Vec<&[u8]> = font.glyphs().chunks(GLYPH_BYTES as usize).collect();
+            for (digit, glyph) in glyphs.iter().enumerate() {
+                // Four pixels wide, in the high nibble.
+                assert!(glyph.iter().all(|row| row & 0x0F == 0), "{name} {digit:X}");
+                assert_eq!(glyphs.iter().filter(|other| other == &glyph).count(), 1, "{name} {digit:X}");
+            }
+        }
+        assert_eq!(HexFont::from_name("comic"), None);
+    }
+
+    #[test]
+    fn fonts_fit_around_the_big_font() {
+        assert!(HexFont::fits_at(FONT_BASE) && HexFont::fits_at(0x050));
+        assert!(HexFont::fits_at(0x104) && HexFont::fits_at(0x1B0));
+        assert!(!HexFont::fits_at(0x051) && !HexFont::fits_at(0x103) && !HexFont::fits_at(0x1B1));
+    }
+}
@@ -0,0 +1,374 @@
+use core::fmt;
+
+/// A decoded CHIP-8 instruction.
+///
+/// Register operands are stored as indices into `v`; address and immediate
+/// operands keep the width they have in the opcode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// 00CN, SUPER-CHIP's scroll down N pixels
+    ScrollDown(u8),
+    /// 00DN, XO-CHIP's scroll up N pixels
+    ScrollUp(u8),
+    /// 00E0
+    ClearScreen,
+    /// 00EE
+    Return,
+    /// 00FB, SUPER-CHIP's scroll right 4 pixels
+    ScrollRight,
+    /// 00FC, SUPER-CHIP's scroll left 4 pixels
+    ScrollLeft,
+    /// 00FD, SUPER-CHIP's exit
+    Exit,
+    /// 00FE, SUPER-CHIP's 64x32 mode
+    LowRes,
+    /// 00FF, SUPER-CHIP's 128x64 mode
+    HighRes,
+    /// 1NNN
+    Jump(u16),
+    /// 2NNN
+    Call(u16),
+    /// 3XNN
+    SkipIfEq { x: usize, nn: u8 },
+    /// 4XNN
+    SkipIfNe { x: usize, nn: u8 },
+    /// 5XY0
+    SkipIfRegEq { x: usize, y: usize },
+    /// 5XY2, XO-CHIP's save VX-VY to I on, counting down if X > Y
+    StoreRange { x: usize, y: usize },
+    /// 5XY3, XO-CHIP's load VX-VY from I on, counting down if X > Y
+    LoadRange { x: usize, y: usize },
+    /// 6XNN
+    SetVx { x: usize, nn: u8 },
+    /// 7XNN
+    AddVx { x: usize, nn: u8 },
+    /// 8XY0
+    Copy { x: usize, y: usize },
+    /// 8XY1
+    Or { x: usize, y: usize },
+    /// 8XY2
+    And { x: usize, y: usize },
+    /// 8XY3
+    Xor { x: usize, y: usize },
+    /// 8XY4
+    AddReg { x: usize, y: usize },
+    /// 8XY5
+    SubReg { x: usize, y: usize },
+    /// 8XY6
+    ShiftRight { x: usize, y: usize },
+    /// 8XY7
+    SubReverse { x: usize, y: usize },
+    /// 8XYE
+    ShiftLeft { x: usize, y: usize },
+    /// 9XY0
+    SkipIfRegNe { x: usize, y: usize },
+    /// ANNN
+    SetIndex(u16),
+    /// F000 NNNN, XO-CHIP's I = NNNN, whose address is the next two bytes
+    LongIndex,
+    /// BNNN
+    JumpOffset(u16),
+    /// CXNN
+    Random { x: usize, nn: u8 },
+    /// DXYN, with DXY0 a 16x16 sprite in high resolution
+    Draw { x: usize, y: usize, n: u8 },
+    /// EX9E
+    SkipIfKey { x: usize },
+    /// EXA1
+    SkipIfNotKey { x: usize },
+    /// FN01, XO-CHIP's select the planes in N to draw on
+    SelectPlanes(u8),
+    /// FX07
+    GetDelay { x: usize },
+    /// FX0A
+    WaitKey { x: usize },
+    /// FX15
+    SetDelay { x: usize },
+    /// FX18
+    SetSound { x: usize },
+    /// F002, XO-CHIP's load the 16-byte audio pattern at I
+    LoadAudio,
+    /// FX3A, XO-CHIP's set the audio pattern's pitch to VX
+    SetPitch { x: usize },
+    /// FX1E
+    AddIndex { x: usize },
+    /// FX29
+    FontChar { x: usize },
+    /// FX30, SUPER-CHIP's point I at an 8x10 digit
+    BigFontChar { x: usize },
+    /// FX33
+    Bcd { x: usize },
+    /// FX55
+    StoreRegs { x: usize },
+    /// FX65
+    LoadRegs { x: usize },
+    /// FX75, SUPER-CHIP's save V0-VX to the RPL user flags
+    StoreFlags { x: usize },
+    /// FX85, SUPER-CHIP's load V0-VX from the RPL user flags
+    LoadFlags { x: usize },
+}
+
+impl Instruction {
+    /// The opcode that decodes to this instruction. F000's address isn't
+    /// part of it; it's the two bytes after.
+    pub fn opcode(self) -> u16 {
+        let xy = |x: usize, y: usize, n: u16| (x as u16) << 8 | (y as u16) << 4 | n;
+        let xnn = |x: usize, nn: u8| (x as u16) << 8 | nn as u16;
+        let fx = |x: usize, nn: u16| 0xF000 | (x as u16) << 8 | nn;
+        match self {
+            Instruction::ScrollDown(n) => 0x00C0 | n as u16,
+            Instruction::ScrollUp(n) => 0x00D0 | n as u16,
+            Instruction::ClearScreen => 0x00E0,
+            Instruction::Return => 0x00EE,
+            Instruction::ScrollRight => 0x00FB,
+            Instruction::ScrollLeft => 0x00FC,
+            Instruction::Exit => 0x00FD,
+            Instruction::LowRes => 0x00FE,
+            Instruction::HighRes => 0x00FF,
+            Instruction::Jump(nnn) => 0x1000 | nnn,
+            Instruction::Call(nnn) => 0x2000 | nnn,
+            Instruction::SkipIfEq { x, nn } => 0x3000 | xnn(x, nn),
+            Instruction::SkipIfNe { x, nn } => 0x4000 | xnn(x, nn),
+            Instruction::SkipIfRegEq { x, y } => 0x5000 | xy(x, y, 0x0),
+            Instruction::StoreRange { x, y } => 0x5000 | xy(x, y, 0x2),
+            Instruction::LoadRange { x, y } => 0x5000 | xy(x, y, 0x3),
+            Instruction::SetVx { x, nn } => 0x6000 | xnn(x, nn),
+            Instruction::AddVx { x, nn } => 0x7000 | xnn(x, nn),
+            Instruction::Copy { x, y } => 0x8000 | xy(x, y, 0x0),
+            Instruction::Or { x, y } => 0x8000 | xy(x, y, 0x1),
+            Instruction::And { x, y } => 0x8000 | xy(x, y, 0x2),
+            Instruction::Xor { x, y } => 0x8000 | xy(x, y, 0x3),
+            Instruction::AddReg { x, y } => 0x8000 | xy(x, y, 0x4),
+            Instruction::SubReg { x, y } => 0x8000 | xy(x, y, 0x5),
+            Instruction::ShiftRight { x, y } => 0x8000 | xy(x, y, 0x6),
+            Instruction::SubReverse { x, y } => 0x8000 | xy(x, y, 0x7),
+            Instruction::ShiftLeft { x, y } => 0x8000 | xy(x, y, 0xE),
+            Instruction::SkipIfRegNe { x, y } => 0x9000 | xy(x, y, 0x0),
+            Instruction::SetIndex(nnn) => 0xA000 | nnn,
+            Instruction::LongIndex => 0xF000,
+            Instruction::JumpOffset(nnn) => 0xB000 | nnn,
+            Instruction::Random { x, nn } => 0xC000 | xnn(x, nn),
+            Instruction::Draw { x, y, n } => 0xD000 | xy(x, y, n as u16),
+            Instruction::SkipIfKey { x } => 0xE000 | xnn(x, 0x9E),
+            Instruction::SkipIfNotKey { x } => 0xE000 | xnn(x, 0xA1),
+            Instruction::SelectPlanes(n) => fx(n as usize, 0x01),
+            Instruction::GetDelay { x } => fx(x, 0x07),
+            Instruction::WaitKey { x } => fx(x, 0x0A),
+            Instruction::SetDelay { x } => fx(x, 0x15),
+            Instruction::SetSound { x } => fx(x, 0x18),
+            Instruction::LoadAudio => 0xF002,
+            Instruction::SetPitch { x } => fx(x, 0x3A),
+            Instruction::AddIndex { x } => fx(x, 0x1E),
+            Instruction::FontChar { x } => fx(x, 0x29),
+            Instruction::BigFontChar { x } => fx(x, 0x30),
+            Instruction::Bcd { x } => fx(x, 0x33),
+            Instruction::StoreRegs { x } => fx(x, 0x55),
+            Instruction::LoadRegs { x } => fx(x, 0x65),
+            Instruction::StoreFlags { x } => fx(x, 0x75),
+            Instruction::LoadFlags { x } => fx(x, 0x85),
+        }
+    }
+}
+
+/// Decodes a raw opcode, returning `None` for opcodes the interpreter
+/// doesn't implement.
+pub fn decode(opcode: u16) -> Option<Instruction> {
+    let x = ((opcode & 0x0F00) >> 8) as usize;
+    let y = ((opcode & 0x00F0) >> 4) as usize;
+    let n = (opcode & 0x000F) as u8;
+    let nn = (opcode & 0x00FF) as u8;
+    let nnn = opcode & 0x0FFF;
+
+    let instruction = match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00C0..=0x00CF => Instruction::ScrollDown(n),
+            0x00D0..=0x00DF => Instruction::ScrollUp(n),
+            0x00E0 => Instruction::ClearScreen,
+            0x00EE => Instruction::Return,
+            0x00FB => Instruction::ScrollRight,
+            0x00FC => Instruction::ScrollLeft,
+            0x00FD => Instruction::Exit,
+            0x00FE => Instruction::LowRes,
+            0x00FF => Instruction::HighRes,
+            _ => return None,
+        },
+        0x1000 => Instruction::Jump(nnn),
+        0x2000 => Instruction::Call(nnn),
+        0x3000 => Instruction::SkipIfEq { x, nn },
+        0x4000 => Instruction::SkipIfNe { x, nn },
+        // The low nibble is reserved, other than XO-CHIP's 5XY2 and 5XY3.
+        0x5000 => match n {
+            0x0 => Instruction::SkipIfRegEq { x, y },
+            0x2 => Instruction::StoreRange { x, y },
+            0x3 => Instruction::LoadRange { x, y },
+            _ => return None,
+        },
+        0x6000 => Instruction::SetVx { x, nn },
+        0x7000 => Instruction::AddVx { x, nn },
+        0x8000 => match n {
+            0x0 => Instruction::Copy { x, y },
+            0x1 => Instruction::Or { x, y },
+            0x2 => Instruction::And { x, y },
+            0x3 => Instruction::Xor { x, y },
+            0x4 => Instruction::AddReg { x, y },
+            0x5 => Instruction::SubReg { x, y },
+            0x6 => Instruction::ShiftRight { x, y },
+            0x7 => Instruction::SubReverse { x, y },
+            0xE => Instruction::ShiftLeft { x, y },
+            _ => return None,
+        },
+        0x9000 => match n {
+            0x0 => Instruction::SkipIfRegNe { x, y },
+            _ => return None,
+        },
+        0xA000 => Instruction::SetIndex(nnn),
+        0xB000 => Instruction::JumpOffset(nnn),
+        0xC000 => Instruction::Random { x, nn },
+        0xD000 => Instruction::Draw { x, y, n },
+        0xE000 => match nn {
+            0x9E => Instruction::SkipIfKey { x },
+            0xA1 => Instruction::SkipIfNotKey { x },
+            _ => return None,
+        },
+        _ => match nn {
+            0x00 if x == 0 => Instruction::LongIndex,
+            0x01 => Instruction::SelectPlanes(x as u8),
+            0x02 if x == 0 => Instruction::LoadAudio,
+            0x07 => Instruction::GetDelay { x },
+            0x0A => Instruction::WaitKey { x },
+            0x15 => Instruction::SetDelay { x },
+            0x18 => Instruction::SetSound { x },
+            0x1E => Instruction::AddIndex { x },
+            0x29 => Instruction::FontChar { x },
+            0x30 => Instruction::BigFontChar { x },
+            0x33 => Instruction::Bcd { x },
+            0x3A => Instruction::SetPitch { x },
+            0x55 => Instruction::StoreRegs { x },
+            0x65 => Instruction::LoadRegs { x },
+            0x75 => Instruction::StoreFlags { x },
+            0x85 => Instruction::LoadFlags { x },
+            _ => return None,
+        },
+    };
+
+    Some(instruction)
+}
+
+/// The instruction's mnemonic and operands, in Cowgod's style with
+/// SUPER-CHIP's and XO-CHIP's additions, as [`disassemble`](crate::disassemble)
+/// prints them.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Instruction::ScrollDown(n) => write!(f, "SCD {}", n),
+            Instruction::ScrollUp(n) => write!(f, "SCU {}", n),
+            Instruction::ClearScreen => f.write_str("CLS"),
+            Instruction::Return => f.write_str("RET"),
+            Instruction::ScrollRight => f.write_str("SCR"),
+            Instruction::ScrollLeft => f.write_str("SCL"),
+            Instruction::Exit => f.write_str("EXIT"),
+            Instruction::LowRes => f.write_str("LOW"),
+            Instruction::HighRes => f.write_str("HIGH"),
+            Instruction::Jump(nnn) => write!(f, "JP 0x{:03X}", nnn),
+            Instruction::Call(nnn) => write!(f, "CALL 0x{:03X}", nnn),
+            Instruction::SkipIfEq { x, nn } => write!(f, "SE V{:X}, 0x{:02X}", x, nn),
+            Instruction::SkipIfNe { x, nn } => write!(f, "SNE V{:X}, 0x{:02X}", x, nn),
+            Instruction::SkipIfRegEq { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::StoreRange { x, y } => write!(f, "SAVE V{:X} - V{:X}", x, y),
+            Instruction::LoadRange { x, y } => write!(f, "LOAD V{:X} - V{:X}", x, y),
+            Instruction::SetVx { x, nn } => write!(f, "LD V{:X}, 0x{:02X}", x, nn),
+            Instruction::AddVx { x, nn } => write!(f, "ADD V{:X}, 0x{:02X}", x, nn),
+            Instruction::Copy { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::And { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::AddReg { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::SubReg { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::ShiftRight { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::SubReverse { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::ShiftLeft { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SkipIfRegNe { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::SetIndex(nnn) => write!(f, "LD I, 0x{:03X}", nnn),
+            // The address is in the next two bytes, which only the listing has.
+            Instruction::LongIndex => f.write_str("LD I, long"),
+            Instruction::JumpOffset(nnn) => write!(f, "JP V0, 0x{:03X}", nnn),
+            Instruction::Random { x, nn } => write!(f, "RND V{:X}, 0x{:02X}", x, nn),
+            Instruction::Draw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::SkipIfKey { x } => write!(f, "SKP V{:X}", x),
+            Instruction::SkipIfNotKey { x } => write!(f, "SKNP V{:X}", x),
+            Instruction::SelectPlanes(n) => write!(f, "PLANE {}", n),
+            Instruction::GetDelay { x } => write!(f, "LD V{:X}, DT", x),
+            Instruction::WaitKey { x } => write!(f, "LD V{:X}, K", x),
+            Instruction::SetDelay { x } => write!(f, "LD DT, V{:X}", x),
+            Instruction::SetSound { x } => write!(f, "LD ST, V{:X}", x),
+            Instruction::LoadAudio => f.write_str("AUDIO"),
+            Instruction::SetPitch { x } => write!(f, "PITCH V{:X}", x),
+            Instruction::AddIndex { x } => write!(f, "ADD I, V{:X}", x),
+            Instruction::FontChar { x } => write!(f, "LD F, V{:X}", x),
+            Instruction::BigFontChar { x } => write!(f, "LD HF, V{:X}", x),
+            Instruction::Bcd { x } => write!(f, "LD B, V{:X}", x),
+            Instruction::StoreRegs { x } => write!(f, "LD [I], V{:X}", x),
+            Instruction::LoadRegs { x } => write!(f, "LD V{:X}, [I]", x),
+            Instruction::StoreFlags { x } => write!(f, "LD R, V{:X}", x),
+            Instruction::LoadFlags { x } => write!(f, "LD V{:X}, R", x),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether `opcode` is one of the instructions CHIP-8 defines, with
+    /// SUPER-CHIP's 00CN, 00FB-00FF, FX30, FX75 and FX85 and XO-CHIP's
+    /// 00DN, 5XY2, 5XY3, F000, FN01, F002 and FX3A, spelled out nibble by
+    /// nibble.
+    fn documented(opcode: u16) -> bool {
+        let low = opcode & 0x000F;
+        let nn = opcode & 0x00FF;
+        match opcode >> 12 {
+            0x0 => matches!(opcode, 0x00C0..=0x00DF | 0x00E0 | 0x00EE | 0x00FB..=0x00FF),
+            0x5 => matches!(low, 0x0 | 0x2 | 0x3),
+            0x9 => low == 0,
+            0x8 => matches!(low, 0x0..=0x7 | 0xE),
+            0xE => matches!(nn, 0x9E | 0xA1),
+            0xF => {
+                matches!(opcode, 0xF000 | 0xF002)
+                    || matches!(nn, 0x01 | 0x07 | 0x0A | 0x15 | 0x18 | 0x1E | 0x29 | 0x30 | 0x33 | 0x3A | 0x55 | 0x65 | 0x75 | 0x85)
+            }
+            _ => true,
+        }
+    }
+
+    #[test]
+    fn only_documented_opcodes_decode() {
+        let mut valid = 0;
+        for opcode in 0..=u16::MAX {
+            assert_eq!(decode(opcode).is_some(), documented(opcode), "{opcode:04X}");
+            valid += decode(opcode).is_some() as u32;
+        }
+        // Ten whole groups, 5XY0, 5XY2, 5XY3 and 9XY0, nine 8XYN, two EX__, fourteen
+        // FX__, F000 and F002, the sixteen 00CN and 00DN, and seven other 00__.
+        assert_eq!(valid, 10 * 4096 + 4 * 256 + 9 * 256 + 2 * 16 + 14 * 16 + 2 + 2 * 16 + 7);
+    }
+
+    #[test]
+    fn instructions_encode_back_to_their_opcodes() {
+        for opcode in 0..=u16::MAX {
+            if let Some(instruction) = decode(opcode) {
+                assert_eq!(instruction.opcode(), opcode, "{instruction:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn reserved_low_nibbles_are_unknown() {
+        assert_eq!(decode(0x5120), Some(Instruction::SkipIfRegEq { x: 1, y: 2 }));
+        assert_eq!(decode(0x9120), Some(Instruction::SkipIfRegNe { x: 1, y: 2 }));
+        assert_eq!(decode(0x5122), Some(Instruction::StoreRange { x: 1, y: 2 }));
+        assert_eq!(decode(0x5213), Some(Instruction::LoadRange { x: 2, y: 1 }));
+        for opcode in [0x5121, 0x5124, 0x512F, 0x9121, 0x912F, 0x8128, 0x812F] {
+            assert_eq!(decode(opcode), None, "{opcode:04X}");
+        }
+    }
+}
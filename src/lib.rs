@@ -0,0 +1,91 @@
+//! CHIP-8 interpreter core, independent of any window or audio backend.
+//!
+//! With default features disabled the interpreter builds for `no_std`
+//! targets without an allocator: load programs with
+//! [`Chip8::load_rom_bytes`], seed it through [`Chip8::with_rng`], set it up
+//! with [`Chip8::apply_config`] and run it a frame at a time with
+//! [`Chip8::emulate_frames`], drawing [`Chip8::display`] after each.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+mod asm;
+mod chip8;
+pub mod color_map;
+mod config;
+#[cfg(feature = "control-socket")]
+pub mod control_socket;
+#[cfg(feature = "alloc")]
+mod decode_cache;
+#[cfg(feature = "alloc")]
+mod diff;
+#[cfg(feature = "alloc")]
+mod disasm;
+mod display;
+mod error;
+mod events;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod frontend;
+#[cfg(feature = "std")]
+pub mod gdb;
+mod hex_font;
+mod instruction;
+#[cfg(feature = "std")]
+mod loader;
+#[cfg(feature = "alloc")]
+mod lockstep;
+pub mod memory_map;
+pub mod overlay;
+pub mod platform;
+mod pretty;
+mod quirks;
+pub mod render;
+mod rng;
+pub mod rom_db;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+mod sha1;
+mod stack;
+mod state;
+#[cfg(feature = "serde")]
+pub mod state_json;
+pub mod vip_timing;
+mod watch;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use chip8::{
+    AUDIO_PATTERN_BYTES, Chip8, DEFAULT_PITCH, EXTENDED_MEMORY_SIZE, MEMORY_SIZE, PROGRAM_START, OpcodePolicy, RPL_FLAGS, STACK_DEPTH, StepReport, SysPolicy,
+};
+#[cfg(feature = "alloc")]
+pub use chip8::Observer;
+#[cfg(feature = "alloc")]
+pub use asm::{AsmError, assemble};
+#[cfg(feature = "alloc")]
+pub use diff::{DisplayChange, MemoryChange, Register, RegisterChange, StateDiff};
+#[cfg(feature = "alloc")]
+pub use disasm::disassemble;
+pub use display::{Display, MAX_PIXELS, PLANES, Resolution};
+pub use config::Chip8Config;
+pub use error::Chip8Error;
+pub use events::MachineEvent;
+pub use hex_font::{BIG_FONT_BASE, BIG_FONTSET, FONT_BASE, FONTSET, HexFont};
+#[cfg(feature = "std")]
+pub use error::LoadError;
+pub use instruction::{Instruction, decode};
+#[cfg(feature = "std")]
+pub use loader::{ROM_PATH_VAR, default_search_path, read_rom};
+#[cfg(feature = "alloc")]
+pub use lockstep::{Divergence, Lockstep};
+pub use pretty::{Ascii, Glyphs, Pbm, Screen, Snapshot};
+pub use quirks::Quirks;
+pub use rng::{RandomSource, Rng};
+pub use stack::Stack;
+pub use state::STATE_VERSION;
+pub use watch::{Access, WatchHit, Watchpoint};
+#[cfg(feature = "serde")]
+pub use state_json::{JSON_STATE_VERSION, JsonState};
@@ -0,0 +1,261 @@
+//! Reading ROM files from disk, with errors that say what actually went
+//! wrong and which file the user probably meant.
+
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::error::LoadError;
+
+/// Environment variable holding extra directories to search for ROMs,
+/// separated like `PATH`.
+pub const ROM_PATH_VAR: &str = "CHIP8_ROM_PATH";
+
+const MAX_SUGGESTIONS: usize = 5;
+/// How many directory levels below each search path entry are scanned.
+const SEARCH_DEPTH: usize = 2;
+
+/// Directories searched for near-miss suggestions: the entries of
+/// `CHIP8_ROM_PATH`, then `roms` in the working directory.
+pub fn default_search_path() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = env::var_os(ROM_PATH_VAR)
+        .map(|paths| env::split_paths(&paths).collect())
+        .unwrap_or_default();
+    dirs.push(PathBuf::from("roms"));
+    dirs
+}
+
+/// Reads the ROM at `path`, rejecting directories and files larger than
+/// `max_size` before reading them.
+pub fn read_rom(path: &Path, max_size: usize, search_path: &[PathBuf]) -> Result<Vec<u8>, LoadError> {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let classify = |error: std::io::Error| match error.kind() {
+        ErrorKind::NotFound => LoadError::NotFound {
+            path: absolute.clone(),
+            suggestions: suggestions(path, search_path),
+        },
+        ErrorKind::PermissionDenied => LoadError::PermissionDenied { path: absolute.clone() },
+        ErrorKind::IsADirectory => LoadError::IsDirectory { path: absolute.clone() },
+        _ => LoadError::Io {
+            path: absolute.clone(),
+            source: error,
+        },
+    };
+
+    let metadata = fs::metadata(path).map_err(classify)?;
+    if metadata.is_dir() {
+        return Err(LoadError::IsDirectory { path: absolute });
+    }
+    if metadata.len() > max_size as u64 {
+        return Err(LoadError::TooLarge {
+            path: absolute,
+            size: metadata.len(),
+            max: max_size,
+        });
+    }
+    if metadata.len() == 0 {
+        return Err(LoadError::Empty { path: absolute });
+    }
+
+    fs::read(path).map_err(classify)
+}
+
+/// Existing files that look like what `requested` was meant to be: the same
+/// name with a `.ch8` extension, a case-insensitive match next to it, or ROMs
+/// in the search path whose name contains the requested stem.
+fn suggestions(requested: &Path, search_path: &[PathBuf]) -> Vec<PathBuf> {
+    let Some(stem) = requested.file_stem().map(|s| s.to_string_lossy().to_lowercase()) else {
+        return Vec::new();
+    };
+    let mut found = Vec::new();
+
+    let with_extension = requested.with_extension("ch8");
+    if with_extension != requested && with_extension.is_file() {
+        found.push(with_extension);
+    }
+
+    let dir = match requested.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    for candidate in sorted_entries(dir) {
+        if candidate.is_file() && lowercase_stem(&candidate).as_deref() == Some(stem.as_str()) {
+            found.push(candidate);
+        }
+    }
+
+    for dir in search_path {
+        collect_matching_roms(dir, &stem, SEARCH_DEPTH, &mut found);
+    }
+
+    let mut unique = Vec::new();
+    for path in found {
+        if path != requested && !unique.contains(&path) {
+            unique.push(path);
+        }
+    }
+    unique.truncate(MAX_SUGGESTIONS);
+    unique
+}
+
+fn collect_matching_roms(dir: &Path, stem: &str, depth: usize, found: &mut Vec<PathBuf>) {
+    for entry in sorted_entries(dir) {
+        if entry.is_dir() {
+            if depth > 0 {
+                collect_matching_roms(&entry, stem, depth - 1, found);
+            }
+        } else if entry.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("ch8"))
+            && lowercase_stem(&entry).is_some_and(|name| name.contains(stem))
+        {
+            found.push(entry);
+        }
+    }
+}
+
+fn sorted_entries(dir: &Path) -> Vec<PathBuf> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| entries.filter_map(|entry| entry.ok().map(|e| e.path())).collect())
+        .unwrap_or_default();
+    entries.sort();
+    entries
+}
+
+fn lowercase_stem(path: &Path) -> Option<String> {
+    path.file_stem().map(|s| s.to_string_lossy().to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touch(path: &Path, len: usize) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, vec![0u8; len]).unwrap();
+    }
+
+    #[test]
+    fn reads_an_existing_rom() {
+        let dir = tempfile::tempdir().unwrap();
+        let rom = dir.path().join("ok.ch8");
+        fs::write(&rom, [0x00, 0xE0]).unwrap();
+
+        assert_eq!(read_rom(&rom, 3584, &[]).unwrap(), vec![0x00, 0xE0]);
+    }
+
+    #[test]
+    fn missing_file_suggests_the_ch8_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(&dir.path().join("pong.ch8"), 2);
+
+        let e = read_rom(&dir.path().join("pong"), 3584, &[]).unwrap_err();
+
+        match &e {
+            LoadError::NotFound { path, suggestions } => {
+                assert!(path.is_absolute());
+                assert_eq!(suggestions, &vec![dir.path().join("pong.ch8")]);
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        assert!(e.to_string().contains("does not exist\nDid you mean:\n  "), "{e}");
+    }
+
+    #[test]
+    fn missing_file_suggests_case_insensitive_and_search_path_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        let library = dir.path().join("library");
+        touch(&dir.path().join("Brix.ch8"), 2);
+        touch(&library.join("games").join("Brix [Andreas Gustafsson, 1990].ch8"), 2);
+        touch(&library.join("games").join("Brix [Andreas Gustafsson, 1990].txt"), 2);
+        touch(&library.join("games").join("Pong.ch8"), 2);
+
+        let e = read_rom(&dir.path().join("brix.ch8"), 3584, std::slice::from_ref(&library)).unwrap_err();
+
+        let LoadError::NotFound { suggestions, .. } = e else {
+            panic!("expected NotFound");
+        };
+        assert_eq!(
+            suggestions,
+            vec![
+                dir.path().join("Brix.ch8"),
+                library.join("games").join("Brix [Andreas Gustafsson, 1990].ch8"),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_file_without_near_misses_has_no_suggestions() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let e = read_rom(&dir.path().join("nothing.ch8"), 3584, &[]).unwrap_err();
+
+        assert!(matches!(&e, LoadError::NotFound { suggestions, .. } if suggestions.is_empty()));
+        assert!(!e.to_string().contains("Did you mean"));
+    }
+
+    #[test]
+    fn directory_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let e = read_rom(dir.path(), 3584, &[]).unwrap_err();
+
+        assert!(matches!(e, LoadError::IsDirectory { .. }));
+        assert_eq!(e.to_string(), format!("{} is a directory, not a ROM file", dir.path().display()));
+    }
+
+    #[test]
+    fn oversized_file_reports_its_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let rom = dir.path().join("big.ch8");
+        touch(&rom, 4000);
+
+        let e = read_rom(&rom, 3584, &[]).unwrap_err();
+
+        assert!(matches!(e, LoadError::TooLarge { size: 4000, max: 3584, .. }));
+        assert_eq!(
+            e.to_string(),
+            format!("{} is 4000 bytes but at most 3584 bytes fit in memory", rom.display())
+        );
+    }
+
+    #[test]
+    fn empty_file_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let rom = dir.path().join("empty.ch8");
+        touch(&rom, 0);
+
+        let e = read_rom(&rom, 3584, &[]).unwrap_err();
+
+        assert!(matches!(e, LoadError::Empty { .. }));
+        assert_eq!(e.to_string(), format!("{} is empty, so there's no program to run", rom.display()));
+    }
+
+    #[test]
+    fn full_size_file_loads() {
+        let dir = tempfile::tempdir().unwrap();
+        let rom = dir.path().join("full.ch8");
+        touch(&rom, 3584);
+
+        assert_eq!(read_rom(&rom, 3584, &[]).unwrap().len(), 3584);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_file_reports_permission_denied() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let rom = dir.path().join("locked.ch8");
+        touch(&rom, 2);
+        fs::set_permissions(&rom, fs::Permissions::from_mode(0o000)).unwrap();
+
+        // Permission bits don't apply to root, so there's nothing to test there.
+        if fs::read(&rom).is_ok() {
+            return;
+        }
+
+        let e = read_rom(&rom, 3584, &[]).unwrap_err();
+        assert!(matches!(e, LoadError::PermissionDenied { .. }));
+        assert_eq!(e.to_string(), format!("permission denied reading {}", rom.display()));
+    }
+}
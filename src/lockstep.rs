@@ -0,0 +1,164 @@
+use alloc::string::ToString;
+use core::fmt;
+
+use crate::chip8::Chip8;
+use crate::diff::StateDiff;
+use crate::error::Chip8Error;
+
+/// Runs two machines on the same program and input, one instruction at a
+/// time, and reports the first instruction after which their states differ.
+pub struct Lockstep {
+    pub a: Chip8,
+    pub b: Chip8,
+    steps: u64,
+}
+
+/// Where and how the two machines first went their separate ways.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Number of instructions both machines had executed, including the
+    /// diverging one.
+    pub step: u64,
+    /// Address of the instruction that caused the divergence.
+    pub pc: u16,
+    pub opcode: u16,
+    /// From machine `a` to machine `b`.
+    pub diff: StateDiff,
+}
+
+impl Lockstep {
+    pub fn new(a: Chip8, b: Chip8) -> Self {
+        Self { a, b, steps: 0 }
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn set_keys(&mut self, keys: [bool; 16]) {
+        self.a.keys = keys;
+        self.b.keys = keys;
+    }
+
+    pub fn tick_timers(&mut self) {
+        self.a.tick_timers();
+        self.b.tick_timers();
+    }
+
+    /// Steps both machines once, returning the divergence if their states no
+    /// longer match.
+    ///
+    /// If the states still match but a machine reported an error, that error
+    /// is returned instead (the first machine's if both failed).
+    pub fn step(&mut self) -> Result<Option<Divergence>, Chip8Error> {
+        let pc = self.a.pc;
+        let opcode = self.a.peek_opcode(pc);
+
+        let result_a = self.a.step();
+        let result_b = self.b.step();
+        self.steps += 1;
+
+        let diff = self.a.diff(&self.b);
+        if diff.is_empty() {
+            result_a.and(result_b).map(|_| None)
+        } else {
+            Ok(Some(Divergence {
+                step: self.steps,
+                pc,
+                opcode,
+                diff,
+            }))
+        }
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Diverged after {} instructions at PC 0x{:03X} (opcode 0x{:04X})",
+            self.step, self.pc, self.opcode
+        )?;
+        for line in self.diff.to_string().lines() {
+            write!(f, "\n  {}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::diff::{MemoryChange, Register, RegisterChange};
+    use crate::quirks::Quirks;
+
+    fn machine(quirks: Quirks, program: &[u8]) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.quirks = quirks;
+        chip8.seed_rng(1);
+        chip8.load_rom_bytes(program).unwrap();
+        chip8
+    }
+
+    // 200: 6005  V0 = 0x05
+    // 202: 6130  V1 = 0x30
+    // 204: C2FF  V2 = random (identical under the shared seed)
+    // 206: 8016  V0 >>= 1, or V0 = V1 >> 1 with the VIP shift quirk
+    // 208: 1208  jump to self
+    const SHIFT_PROGRAM: [u8; 10] = [0x60, 0x05, 0x61, 0x30, 0xC2, 0xFF, 0x80, 0x16, 0x12, 0x08];
+
+    #[test]
+    fn shift_quirk_divergence_is_reported_at_the_shift() {
+        let mut lockstep = Lockstep::new(
+            machine(Quirks::chip8(), &SHIFT_PROGRAM),
+            machine(Quirks::schip(), &SHIFT_PROGRAM),
+        );
+
+        let divergence = (0..10)
+            .find_map(|_| lockstep.step().unwrap())
+            .expect("machines diverge");
+
+        assert_eq!(divergence.step, 4);
+        assert_eq!(divergence.pc, 0x206);
+        assert_eq!(divergence.opcode, 0x8016);
+        assert_eq!(
+            divergence.diff.registers,
+            [
+                RegisterChange { register: Register::V(0), old: 0x18, new: 0x02 },
+                RegisterChange { register: Register::V(0xF), old: 0, new: 1 },
+            ]
+        );
+        assert!(divergence.diff.memory.is_empty());
+    }
+
+    #[test]
+    fn identical_configurations_never_diverge() {
+        let mut lockstep = Lockstep::new(
+            machine(Quirks::chip8(), &SHIFT_PROGRAM),
+            machine(Quirks::chip8(), &SHIFT_PROGRAM),
+        );
+
+        assert!((0..50).all(|_| lockstep.step().unwrap().is_none()));
+        assert_eq!(lockstep.steps(), 50);
+    }
+
+    #[test]
+    fn divergence_report_lists_differences() {
+        let divergence = Divergence {
+            step: 4,
+            pc: 0x206,
+            opcode: 0x8016,
+            diff: StateDiff {
+                registers: vec![RegisterChange { register: Register::V(0), old: 0x18, new: 0x02 }],
+                memory: vec![MemoryChange { start: 0x300, old: vec![1, 0], new: vec![2, 0xFF] }],
+                ..StateDiff::default()
+            },
+        };
+
+        assert_eq!(
+            divergence.to_string(),
+            "Diverged after 4 instructions at PC 0x206 (opcode 0x8016)\n  \
+             V0: 0x18 -> 0x02\n  memory[0x300..=0x301]: 01 00 -> 02 FF (work RAM+0x100)"
+        );
+    }
+}
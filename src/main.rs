@@ -1,527 +1,1110 @@
-use std::fs;
-use std::env;
-use minifb::{Key, Window, WindowOptions};
-use rodio::{OutputStream, Sink, source::SineWave};
-use std::time::Duration;
-
-struct Chip8 {
-    memory: [u8; 4096],
-    v: [u8; 16],
-    i: u16,
-    pc: u16,
-    stack: Vec<u16>,
-    delay_timer: u8,
-    sound_timer: u8,
-    display: [[bool; 64]; 32],
-    keys: [bool; 16],
-}
+mod backend;
+#[cfg(feature = "frontend-minifb")]
+mod compare;
 
-const FONTSET: [u8; 80] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0,
-    0x20, 0x60, 0x20, 0x20, 0x70,
-    0xF0, 0x10, 0xF0, 0x80, 0xF0,
-    0xF0, 0x10, 0xF0, 0x10, 0xF0,
-    0x90, 0x90, 0xF0, 0x10, 0x10,
-    0xF0, 0x80, 0xF0, 0x10, 0xF0,
-    0xF0, 0x80, 0xF0, 0x90, 0xF0,
-    0xF0, 0x10, 0x20, 0x40, 0x40,
-    0xF0, 0x90, 0xF0, 0x90, 0xF0,
-    0xF0, 0x90, 0xF0, 0x10, 0xF0,
-    0xF0, 0x90, 0xF0, 0x90, 0x90,
-    0xE0, 0x90, 0xE0, 0x90, 0xE0,
-    0xF0, 0x80, 0x80, 0x80, 0xF0,
-    0xE0, 0x90, 0x90, 0x90, 0xE0,
-    0xF0, 0x80, 0xF0, 0x80, 0xF0,
-    0xF0, 0x80, 0xF0, 0x80, 0x80
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use log::{LevelFilter, info};
+
+use chip8_emulator::frontend::commands;
+use chip8_emulator::frontend::control::Controller;
+use chip8_emulator::frontend::expectation::{self, StateExpectation};
+use chip8_emulator::frontend::game_config::{self, GameConfig};
+use chip8_emulator::frontend::rpl_flags;
+use chip8_emulator::frontend::script::InputTimeline;
+use chip8_emulator::frontend::speed::{BASE_HZ, SpeedLimits, Timing};
+use chip8_emulator::frontend::timer::TimerStart;
+use chip8_emulator::frontend::{Driver, MAX_SCALE, MIN_SCALE, NullAudio, NullInput, NullVideo};
+use chip8_emulator::rom_db::{self, KnownRom};
+use chip8_emulator::{Chip8, Chip8Config, Chip8Error, BIG_FONT_BASE, BIG_FONTSET, FONT_BASE, Glyphs, HexFont, EXTENDED_MEMORY_SIZE, MEMORY_SIZE, PROGRAM_START, OpcodePolicy, Quirks, SysPolicy, Access, Watchpoint, default_search_path};
+
+use backend::{WINDOW_SCALE, WindowConfig};
+
+#[cfg(not(any(
+    feature = "frontend-minifb",
+    feature = "backend-sdl2",
+    feature = "backend-terminal",
+    feature = "backend-pixels"
+)))]
+compile_error!(
+    "the emulator needs a video backend: enable `frontend-minifb`, `backend-sdl2`, `backend-terminal` or `backend-pixels`"
+);
+
+const FRONTEND_TARGET: &str = "chip8::frontend";
+
+/// Where F6 remembers each game's speed, unless `--game-config` says.
+const DEFAULT_GAME_CONFIG: &str = "chip8_games.cfg";
+
+const USAGE: &str = "Usage: cargo run [rom_path] [--backend name] [--scale N] [--fullscreen] \
+     [--always-on-top] [--borderless] [--no-resize] [--pause-on-focus-loss] [--show-hash] [--classic-escape] [--profile chip8|hires-chip8|chip8x|chip48|schip|xochip] [--QUIRK|--no-QUIRK] [--no-autodetect] [--sys ignore|warn-once|halt] [--opcodes continue|warn-once|halt] [--font standard|vip|rounded] [--font-base ADDR] [--start-addr ADDR] [--strict-memory] [--warn-odd-length] [--seed N] [--control-port N] [--gdb-port N] [--repl] [--timer reset|input] [--speed PERCENT] [--hz N] [--timing fixed|vip] [--min-speed PERCENT] [--max-speed PERCENT] [--game-config file] [--rpl-flags file] [--input-script file|-] [--exclusive] \
+     [--script file.rhai] [--dump-state-json file] [--headless] [--dump-format txt|json|pbm] [--frames N] [--verify-state file] [--write-state-expectation file] [--ascii-dump-at N] [--ascii-glyphs half|XY] [--break addr] [--watch[-read] addr[..end]] [--compare profileA profileB] [--disasm] [--log-level level]
+       cargo run info rom_path
+       cargo run --assemble in.asm [-o out.ch8]
+QUIRK is one of shift-uses-vy, fx1e-sets-vf, load-store-increments-i, wrap-sprites, display-wait, vf-reset,
+jump-uses-vx, half-scroll, extended-memory, load-store-increments-i-by-x, two-page-hires or chip8x, and overrides --profile. Without --profile, games in the ROM database get theirs. CHIP-8X ROMs need --start-addr 300 too.
+Exit status: 0 success, 1 the source didn't assemble, 2 bad arguments, 3 a file or the ROM couldn't be loaded,
+4 the machine stopped on an error, 5 a save state couldn't be read, 6 --verify-state found a different state.";
+
+/// Backends compiled into this build, the default first.
+const BACKENDS: &[&str] = &[
+    #[cfg(feature = "frontend-minifb")]
+    "minifb",
+    #[cfg(feature = "backend-sdl2")]
+    "sdl2",
+    #[cfg(feature = "backend-terminal")]
+    "terminal",
+    #[cfg(feature = "backend-pixels")]
+    "pixels",
 ];
 
-impl Chip8 {
-    fn new() -> Self {
-        let mut memory = [0u8; 4096];
-        let v = [0u8; 16];
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Backend {
+    #[cfg(feature = "frontend-minifb")]
+    Minifb,
+    #[cfg(feature = "backend-sdl2")]
+    Sdl2,
+    #[cfg(feature = "backend-terminal")]
+    Terminal,
+    #[cfg(feature = "backend-pixels")]
+    Pixels,
+}
 
-        memory[0..80].copy_from_slice(&FONTSET);
+impl Backend {
+    fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            #[cfg(feature = "frontend-minifb")]
+            "minifb" => Ok(Backend::Minifb),
+            #[cfg(feature = "backend-sdl2")]
+            "sdl2" => Ok(Backend::Sdl2),
+            #[cfg(feature = "backend-terminal")]
+            "terminal" => Ok(Backend::Terminal),
+            #[cfg(feature = "backend-pixels")]
+            "pixels" => Ok(Backend::Pixels),
+            _ => Err(format!(
+                "Unknown backend '{}' (expected one of: {})",
+                name,
+                BACKENDS.join(", ")
+            )),
+        }
+    }
 
-        let stack = Vec::new();
+    /// Whether it opens a window, which can be scaled.
+    fn has_window(self) -> bool {
+        match self {
+            #[cfg(feature = "backend-terminal")]
+            Backend::Terminal => false,
+            #[allow(unreachable_patterns)]
+            _ => true,
+        }
+    }
 
-        Self {
-            memory,
-            v,
-            i: 0,
-            pc: 0x200,
-            stack,
-            delay_timer: 0,
-            sound_timer: 0,
-            display: [[false; 64]; 32],
-            keys: [false; 16]
+    /// Whether F7, F8 and F12 flip the window options while it runs.
+    fn toggles_window_options(self) -> bool {
+        match self {
+            #[cfg(feature = "frontend-minifb")]
+            Backend::Minifb => true,
+            #[cfg(feature = "backend-pixels")]
+            Backend::Pixels => true,
+            #[allow(unreachable_patterns)]
+            _ => false,
         }
     }
 
-    fn load_rom(&mut self, rom_path: &str) -> std::io::Result<()> {
-        let rom_data = fs::read(rom_path)?;
+    /// Opens the backend's window and audio and runs `chip8` with `driver`
+    /// until the user quits, with the `secondary` keypad bindings or without.
+    fn run(self, chip8: &mut Chip8, mut driver: Driver, window: WindowConfig, secondary: bool) -> Result<(), Chip8Error> {
+        match self {
+            #[cfg(feature = "frontend-minifb")]
+            Backend::Minifb => {
+                let (mut video, mut input) = backend::minifb_window::open(window, secondary);
+                info!(target: FRONTEND_TARGET, "Window opened");
+                let mut audio = speaker();
+                driver.set_bindings(backend::minifb_window::bindings(secondary));
+                driver.run(chip8, &mut video, &mut input, &mut audio)
+            }
+            #[cfg(feature = "backend-sdl2")]
+            Backend::Sdl2 => {
+                let (mut video, mut input, mut audio) = backend::sdl::open(window, secondary)
+                    .unwrap_or_else(|e| panic!("Failed to create window: {}", e));
+                info!(target: FRONTEND_TARGET, "Window opened");
+                driver.set_bindings(backend::sdl::bindings(secondary));
+                driver.run(chip8, &mut video, &mut input, &mut audio)
+            }
+            #[cfg(feature = "backend-terminal")]
+            Backend::Terminal => {
+                if window != WindowConfig::default() {
+                    log::warn!(target: FRONTEND_TARGET, "The window options don't apply to the terminal");
+                }
+                let (mut video, mut input, mut audio) = backend::terminal::open(secondary)
+                    .unwrap_or_else(|e| panic!("Failed to set up the terminal: {}", e));
+                driver.set_bindings(backend::terminal::bindings(secondary));
+                driver.run(chip8, &mut video, &mut input, &mut audio)
+            }
+            #[cfg(feature = "backend-pixels")]
+            Backend::Pixels => {
+                driver.set_bindings(backend::pixels_window::bindings(secondary));
+                backend::pixels_window::run(chip8, driver, window, secondary, speaker())
+            }
+        }
+    }
 
-        self.memory[0x200..0x200 + rom_data.len()].copy_from_slice(&rom_data);
-        Ok(())
+    fn show_error_window(self, title: &str, message: &str) {
+        match self {
+            #[cfg(feature = "frontend-minifb")]
+            Backend::Minifb => backend::minifb_window::show_error_window(title, message),
+            #[cfg(feature = "backend-sdl2")]
+            Backend::Sdl2 => backend::sdl::show_error_window(title, message),
+            // The error is already on stderr, which is the terminal.
+            #[cfg(feature = "backend-terminal")]
+            Backend::Terminal => {
+                let _ = (title, message);
+            }
+            #[cfg(feature = "backend-pixels")]
+            Backend::Pixels => backend::pixels_window::show_error_window(title, message),
+        }
     }
+}
 
-    fn fetch(&mut self) -> u16 {
-        let high_byte = self.memory[self.pc as usize];
-        let low_byte = self.memory[self.pc as usize + 1];
+/// The buzzer for the windowed backends without an audio device of their own.
+#[cfg(all(
+    feature = "audio-rodio",
+    any(feature = "frontend-minifb", feature = "backend-pixels")
+))]
+fn speaker() -> backend::rodio_audio::RodioAudio {
+    backend::rodio_audio::RodioAudio::open()
+}
 
-        let opcode = ((high_byte as u16) << 8) | (low_byte as u16);
-        self.pc += 2;
+#[cfg(all(
+    not(feature = "audio-rodio"),
+    any(feature = "frontend-minifb", feature = "backend-pixels")
+))]
+fn speaker() -> chip8_emulator::frontend::NullAudio {
+    chip8_emulator::frontend::NullAudio
+}
 
-        opcode
+struct Args {
+    rom_path: String,
+    backend: Backend,
+    window: WindowConfig,
+    /// `--scale`, which goes into `window` once the game's is known.
+    scale: Option<usize>,
+    pause_on_focus_loss: bool,
+    show_hash: bool,
+    classic_escape: bool,
+    /// `--profile`, which wins over the one the ROM database suggests.
+    profile: Option<Quirks>,
+    /// `--QUIRK` and `--no-QUIRK`, which win over any profile.
+    quirk_flags: Vec<(&'static str, bool)>,
+    autodetect: bool,
+    sys_policy: SysPolicy,
+    opcode_policy: OpcodePolicy,
+    font: HexFont,
+    /// `--font-base`, which always leaves room for the font below the program.
+    font_base: u16,
+    /// `--start-addr`, 0x600 for ETI-660 programs.
+    start_addr: u16,
+    strict_memory: bool,
+    warn_odd_length: bool,
+    /// `--seed`, for CXNN sequences that repeat from run to run.
+    seed: Option<u64>,
+    control_port: Option<u16>,
+    gdb_port: Option<u16>,
+    repl: bool,
+    timer: Option<TimerStart>,
+    /// `--hz`, or `--speed` as a clock, in Hz.
+    speed: Option<u32>,
+    speed_limits: SpeedLimits,
+    timing: Timing,
+    game_config: PathBuf,
+    /// `--rpl-flags`, where FX75's flags are kept instead of beside the ROM.
+    rpl_flags: Option<PathBuf>,
+    input_script: Option<String>,
+    script: Option<String>,
+    dump_state_json: Option<String>,
+    exclusive: bool,
+    headless: bool,
+    dump_format: Option<DumpFormat>,
+    frames: Option<u64>,
+    verify_state: Option<String>,
+    write_state_expectation: Option<String>,
+    ascii_dump_at: Vec<u64>,
+    /// `--break`, where to pause before executing.
+    breakpoints: Vec<u16>,
+    /// `--watch` and `--watch-read`.
+    watchpoints: Vec<Watchpoint>,
+    ascii_glyphs: Glyphs,
+    compare: Option<(String, String)>,
+    /// Print the ROM's disassembly and exit.
+    disasm: bool,
+    log_level: LevelFilter,
+}
+
+/// `--QUIRK` or `--no-QUIRK`, without the dashes, as the quirk's name and
+/// whether it turns it on.
+fn quirk_flag(flag: &str) -> Option<(&'static str, bool)> {
+    let (name, on) = match flag.strip_prefix("no-") {
+        Some(name) => (name, false),
+        None => (flag, true),
+    };
+    Quirks::NAMES.iter().find(|&&known| known == name).map(|&name| (name, on))
+}
+
+impl Args {
+    /// The machine the arguments ask for. Its quirks leave out the ROM
+    /// database's, which aren't known until the ROM is loaded, but they have
+    /// to include XO-CHIP's 64 KB before a ROM that needs it goes in.
+    fn config(&self) -> Chip8Config {
+        Chip8Config {
+            quirks: self.quirks(None),
+            seed: self.seed,
+            program_start: self.start_addr,
+            font: self.font,
+            font_base: self.font_base,
+            sys_policy: self.sys_policy,
+            opcode_policy: self.opcode_policy,
+            strict_memory: self.strict_memory,
+            warn_odd_length: self.warn_odd_length,
+        }
     }
 
-    fn execute(&mut self, opcode: u16) {
-        match opcode & 0xF000 {
-            0x0000 => {
-                match opcode {
-                    0x00E0 => self.display = [[false; 64]; 32],
-                    0x00EE => self.op_00ee(),
-                    _ => println!("Unknown 0x0--- opcode: 0x{:04X}", opcode),
-                }
+    /// The quirks to run with: `--profile`, else `detected`, else the
+    /// defaults, with the switches on top wherever they came on the line.
+    fn quirks(&self, detected: Option<Quirks>) -> Quirks {
+        let mut quirks = self.profile.or(detected).unwrap_or_default();
+        for &(name, on) in &self.quirk_flags {
+            if let Some(flag) = quirks.flag_mut(name) {
+                *flag = on;
             }
+        }
+        quirks
+    }
+}
 
-            0x1000 => {
-                let nnn = opcode & 0x0FFF;
-                self.pc = nnn;
+fn parse_args() -> Result<Args, String> {
+    let mut rom_path = None;
+    let mut backend = Backend::from_name(BACKENDS[0])?;
+    let mut window = WindowConfig::default();
+    let mut scale = None;
+    let mut pause_on_focus_loss = false;
+    let mut show_hash = false;
+    let mut classic_escape = false;
+    let mut profile = None;
+    let mut quirk_flags = Vec::new();
+    let mut autodetect = true;
+    let mut sys_policy = SysPolicy::default();
+    let mut opcode_policy = OpcodePolicy::default();
+    let mut font = HexFont::default();
+    let mut font_base = FONT_BASE;
+    let mut start_addr = PROGRAM_START;
+    let mut strict_memory = false;
+    let mut warn_odd_length = false;
+    let mut seed = None;
+    let mut control_port = None;
+    let mut gdb_port = None;
+    let mut repl = false;
+    let mut timer = None;
+    let mut speed = None;
+    let mut hz = None;
+    let mut timing = Timing::default();
+    let mut limits = SpeedLimits::default();
+    let mut game_config = PathBuf::from(DEFAULT_GAME_CONFIG);
+    let mut rpl_flags = None;
+    let mut input_script = None;
+    let mut script = None;
+    let mut dump_state_json = None;
+    let mut exclusive = false;
+    let mut headless = false;
+    let mut dump_format = None;
+    let mut frames = None;
+    let mut verify_state = None;
+    let mut write_state_expectation = None;
+    let mut ascii_dump_at = Vec::new();
+    let mut breakpoints = Vec::new();
+    let mut watchpoints = Vec::new();
+    let mut ascii_glyphs = Glyphs::default();
+    let mut compare = None;
+    let mut disasm = false;
+    let mut log_level = LevelFilter::Warn;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--backend" => {
+                let name = args.next().ok_or("--backend needs a name")?;
+                backend = Backend::from_name(&name)?;
             }
-
-            0x6000 => {
-                let x = ((opcode & 0x0F00) >> 8) as usize;
-                let nn = (opcode & 0x00FF) as u8;
-                self.v[x] =  nn;
+            "--scale" => {
+                let value = args.next().ok_or("--scale needs a value")?;
+                scale = Some(match value.parse() {
+                    Ok(scale @ MIN_SCALE..=MAX_SCALE) => scale,
+                    _ => return Err(format!("Invalid scale: {} ({} to {})", value, MIN_SCALE, MAX_SCALE)),
+                });
             }
-
-            0x7000 => {
-                let x = ((opcode & 0x0F00) >> 8) as usize;
-                let nn = (opcode & 0x00FF) as u8;
-                self.v[x] = self.v[x].wrapping_add(nn);
+            "--fullscreen" => window.fullscreen = true,
+            "--always-on-top" => window.always_on_top = true,
+            "--borderless" => window.borderless = true,
+            "--no-resize" => window.resizable = false,
+            "--pause-on-focus-loss" => pause_on_focus_loss = true,
+            "--show-hash" => show_hash = true,
+            "--classic-escape" => classic_escape = true,
+            "--control-port" => {
+                let port = args.next().ok_or("--control-port needs a port number")?;
+                control_port = Some(port.parse().map_err(|_| format!("Invalid port: {}", port))?);
             }
-
-            0xA000 => {
-                let nnn = opcode & 0x0FFF;
-                self.i = nnn;
+            "--gdb-port" => {
+                let port = args.next().ok_or("--gdb-port needs a port number")?;
+                gdb_port = Some(port.parse().map_err(|_| format!("Invalid port: {}", port))?);
             }
-
-            0xD000 => {
-                let x = ((opcode & 0x0F00) >> 8) as usize;
-                let y = ((opcode & 0x00F0) >> 4) as usize;
-                let n = (opcode & 0x000F) as u8;
-                self.op_dxyn(x, y, n);
+            "--repl" => repl = true,
+            "--profile" => {
+                let name = args.next().ok_or("--profile needs a name")?;
+                profile = Some(Quirks::from_profile(&name).ok_or_else(|| {
+                    format!("Unknown profile: {} (expected one of: {})", name, Quirks::PROFILES.join(", "))
+                })?);
             }
-
-            0xB000 => {
-                let nnn = opcode & 0x0FFF;
-                self.op_bnnn(nnn);
+            "--no-autodetect" => autodetect = false,
+            "--strict-memory" => strict_memory = true,
+            "--warn-odd-length" => warn_odd_length = true,
+            "--seed" => {
+                let value = args.next().ok_or("--seed needs a number")?;
+                let parsed = match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+                    Some(hex) => u64::from_str_radix(hex, 16),
+                    None => value.parse(),
+                };
+                seed = Some(parsed.map_err(|_| format!("Invalid seed: {}", value))?);
             }
-            
-            0xC000 => {
-                let x = ((opcode & 0x0F00) >> 8) as usize;
-                let nn = (opcode & 0x00FF) as u8;
-                self.op_cxnn(x, nn);
+            "--sys" => {
+                let name = args.next().ok_or("--sys needs ignore, warn-once or halt")?;
+                sys_policy = SysPolicy::from_name(&name).ok_or_else(|| format!("Unknown 0NNN policy: {}", name))?;
             }
-            
-            0xF000 => {
-                let x = ((opcode & 0x0F00) >> 8) as usize;
-                let nn = opcode & 0x00FF;
-                
-                match nn {
-                    0x07 => self.op_fx07(x),
-                    0x0A => self.op_fx0a(x),
-                    0x15 => self.op_fx15(x),
-                    0x18 => self.op_fx18(x),
-                    0x1E => self.op_fx1e(x),
-                    0x29 => self.op_fx29(x),
-                    0x33 => self.op_fx33(x),
-                    0x55 => self.op_fx55(x),
-                    0x65 => self.op_fx65(x),
-                    _ => println!("Unknown FX-- opcode: 0x{:04X}", opcode),
-                }
+            "--opcodes" => {
+                let name = args.next().ok_or("--opcodes needs continue, warn-once or halt")?;
+                opcode_policy =
+                    OpcodePolicy::from_name(&name).ok_or_else(|| format!("Unknown opcode policy: {}", name))?;
             }
-
-            // Conditional Skips...
-
-            0x3000 => {
-                let x = ((opcode & 0x0F00) >> 8) as usize;
-                let nn = (opcode & 0x00FF) as u8;
-                self.op_3xnn(x, nn);
-            }
-            
-            0x4000 => {
-                let x = ((opcode & 0x0F00) >> 8) as usize;
-                let nn = (opcode & 0x00FF) as u8;
-                self.op_4xnn(x, nn);
-            }
-            
-            0x5000 => {
-                let x = ((opcode & 0x0F00) >> 8) as usize;
-                let y = ((opcode & 0x00F0) >> 4) as usize;
-                self.op_5xy0(x, y);
-            }
-            
-            0x9000 => {
-                let x = ((opcode & 0x0F00) >> 8) as usize;
-                let y = ((opcode & 0x00F0) >> 4) as usize;
-                self.op_9xy0(x, y);
-            }
-
-            // Math Operations...
-
-            0x8000 => {
-                let x = ((opcode & 0x0F00) >> 8) as usize;
-                let y = ((opcode & 0x00F0) >> 4) as usize;
-                let op = opcode & 0x000F;
-                
-                match op {
-                    0x0 => self.op_8xy0(x, y),
-                    0x1 => self.op_8xy1(x, y),
-                    0x2 => self.op_8xy2(x, y),
-                    0x3 => self.op_8xy3(x, y),
-                    0x4 => self.op_8xy4(x, y),
-                    0x5 => self.op_8xy5(x, y),
-                    0x6 => self.op_8xy6(x, y),
-                    0x7 => self.op_8xy7(x, y),
-                    0xE => self.op_8xye(x, y),
-                    _ => println!("Unknown 8XY_ opcode: 0x{:04X}", opcode),
-                }
+            "--font" => {
+                let name = args.next().ok_or("--font needs standard, vip or rounded")?;
+                font = HexFont::from_name(&name).ok_or_else(|| format!("Unknown font: {}", name))?;
             }
-
-            0x2000 => {
-                let nnn = opcode & 0x0FFF;
-                self.op_2nnn(nnn);
+            "--font-base" => {
+                let value = args.next().ok_or("--font-base needs an address")?;
+                let digits = value.trim_start_matches("0x").trim_start_matches("0X");
+                font_base = u16::from_str_radix(digits, 16)
+                    .ok()
+                    .filter(|&base| HexFont::fits_at(base))
+                    .ok_or_else(|| {
+                        format!(
+                            "Invalid font base: {} (the font needs 80 bytes below 0x{:03X}, clear of the big font at 0x{:03X}-0x{:03X})",
+                            value,
+                            PROGRAM_START,
+                            BIG_FONT_BASE,
+                            BIG_FONT_BASE as usize + BIG_FONTSET.len() - 1
+                        )
+                    })?;
             }
-
-            // Keyboard Handling...
-
-            0xE000 => {
-                let x = ((opcode & 0x0F00) >> 8) as usize;
-                let nn = opcode & 0x00FF;
-                
-                match nn {
-                    0x9E => self.op_ex9e(x),
-                    0xA1 => self.op_exa1(x),
-                    _ => println!("Unknown EX-- opcode: 0x{:04X}", opcode),
+            "--break" => {
+                let value = args.next().ok_or("--break needs an address")?;
+                let digits = value.trim_start_matches("0x").trim_start_matches("0X");
+                breakpoints.push(u16::from_str_radix(digits, 16).map_err(|_| format!("Invalid breakpoint address: {}", value))?);
+            }
+            "--watch" | "--watch-read" => {
+                let access = if arg == "--watch" { Access::Write } else { Access::Read };
+                let value = args.next().ok_or_else(|| format!("{} needs an address or a range", arg))?;
+                watchpoints.push(
+                    Watchpoint::parse(&value, access).ok_or_else(|| format!("Invalid watchpoint: {} (use 0x6F0 or 0x6F0..0x700)", value))?,
+                );
+            }
+            "--start-addr" => {
+                let value = args.next().ok_or("--start-addr needs an address")?;
+                let digits = value.trim_start_matches("0x").trim_start_matches("0X");
+                start_addr = u16::from_str_radix(digits, 16)
+                    .ok()
+                    .filter(|&addr| (PROGRAM_START as usize..MEMORY_SIZE).contains(&(addr as usize)))
+                    .ok_or_else(|| {
+                        format!("Invalid start address: {} (programs start between 0x{:03X} and 0x{:03X})", value, PROGRAM_START, MEMORY_SIZE - 1)
+                    })?;
+            }
+            "--timer" => {
+                let start = args.next().ok_or("--timer needs reset or input")?;
+                timer = Some(match start.as_str() {
+                    "reset" => TimerStart::Reset,
+                    "input" => TimerStart::FirstInput,
+                    _ => return Err(format!("Unknown timer start: {}", start)),
+                });
+            }
+            "--timing" => {
+                let name = args.next().ok_or("--timing needs fixed or vip")?;
+                timing = Timing::from_name(&name).ok_or_else(|| format!("Unknown timing: {}", name))?;
+            }
+            "--hz" => {
+                let value = args.next().ok_or("--hz needs a clock rate")?;
+                hz = Some(value.parse().map_err(|_| format!("Invalid clock rate: {}", value))?);
+            }
+            "--speed" | "--min-speed" | "--max-speed" => {
+                let value = args.next().ok_or_else(|| format!("{} needs a percentage", arg))?;
+                let percent = value
+                    .trim_end_matches('%')
+                    .parse()
+                    .map_err(|_| format!("Invalid speed: {}", value))?;
+                match arg.as_str() {
+                    "--speed" => speed = Some(percent),
+                    "--min-speed" => limits.min_percent = percent,
+                    _ => limits.max_percent = percent,
                 }
             }
-
-            _ => {
-                println!("Unimplemented opcode: 0x{:04X}", opcode);
+            "--game-config" => game_config = args.next().ok_or("--game-config needs a file")?.into(),
+            "--rpl-flags" => rpl_flags = Some(args.next().ok_or("--rpl-flags needs a file")?.into()),
+            "--input-script" => {
+                input_script = Some(args.next().ok_or("--input-script needs a file, or - for stdin")?);
+            }
+            "--script" => script = Some(args.next().ok_or("--script needs a file")?),
+            "--dump-state-json" => {
+                dump_state_json = Some(args.next().ok_or("--dump-state-json needs a file")?);
+            }
+            "--exclusive" => exclusive = true,
+            "--headless" => headless = true,
+            "--dump-format" => {
+                let name = args.next().ok_or("--dump-format needs txt, json or pbm")?;
+                dump_format = Some(match name.as_str() {
+                    "txt" => DumpFormat::Txt,
+                    "json" => DumpFormat::Json,
+                    "pbm" => DumpFormat::Pbm,
+                    _ => return Err(format!("Unknown dump format: {}", name)),
+                });
+            }
+            "--frames" | "--max-frames" | "--exit-after-frames" => {
+                let count = args.next().ok_or_else(|| format!("{} needs a count", arg))?;
+                frames = Some(count.parse().map_err(|_| format!("Invalid frame count: {}", count))?);
+            }
+            "--verify-state" => verify_state = Some(args.next().ok_or("--verify-state needs a file")?),
+            "--write-state-expectation" => {
+                write_state_expectation = Some(args.next().ok_or("--write-state-expectation needs a file")?);
             }
+            "--ascii-dump-at" => {
+                let frame = args.next().ok_or("--ascii-dump-at needs a frame number")?;
+                ascii_dump_at.push(frame.parse().map_err(|_| format!("Invalid frame number: {}", frame))?);
+            }
+            "--ascii-glyphs" => {
+                let glyphs = args.next().ok_or("--ascii-glyphs needs half or two characters")?;
+                ascii_glyphs = glyphs.parse().map_err(|e| format!("Invalid glyphs {:?}: {}", glyphs, e))?;
+            }
+            "--compare" => match (args.next(), args.next()) {
+                (Some(a), Some(b)) => compare = Some((a, b)),
+                _ => return Err("--compare needs two profile names".to_string()),
+            },
+            "--disasm" => disasm = true,
+            "--log-level" => {
+                let level = args.next().ok_or("--log-level needs a value")?;
+                log_level = level
+                    .parse()
+                    .map_err(|_| format!("Invalid log level: {}", level))?;
+            }
+            _ if arg.starts_with("--") => match quirk_flag(&arg[2..]) {
+                Some(flag) => quirk_flags.push(flag),
+                None => return Err(format!("Unknown option: {}", arg)),
+            },
+            _ => rom_path = Some(arg),
         }
     }
 
-    fn op_dxyn(&mut self, x: usize, y: usize, n: u8) {
-        let x_start = (self.v[x] as usize) % 64;
-        let y_start = (self.v[y] as usize) % 32;
-
-        self.v[0xF] = 0;
-
-        for row in 0..n {
-            let sprite_byte = self.memory[(self.i + row as u16) as usize];
-
-            for col in 0..8 {
-                let bit = (sprite_byte >> (7 - col)) & 1;
-
-                if bit == 1 {
-                    let screen_x = (x_start + col) % 64;
-                    let screen_y = (y_start + row as usize) % 32;
+    let speed_limits = SpeedLimits::new(limits.min_percent, limits.max_percent)?;
+    if let Some(percent) = speed
+        && !(speed_limits.min_percent..=speed_limits.max_percent).contains(&percent)
+    {
+        return Err(format!(
+            "Speed {}% is outside {}% to {}%",
+            percent, speed_limits.min_percent, speed_limits.max_percent
+        ));
+    }
+    if let Some(hz) = hz
+        && !speed_limits.hz_range().contains(&hz)
+    {
+        let range = speed_limits.hz_range();
+        return Err(format!("Clock {} Hz is outside {} Hz to {} Hz", hz, range.start(), range.end()));
+    }
+    // --hz wins over --speed, wherever they came on the line.
+    let speed = hz.or(speed.map(|percent| BASE_HZ * percent / 100));
+
+    Ok(Args {
+        rom_path: rom_path.unwrap_or_else(|| "Pong.ch8".to_string()),
+        backend,
+        window,
+        scale,
+        pause_on_focus_loss,
+        show_hash,
+        classic_escape,
+        profile,
+        quirk_flags,
+        autodetect,
+        sys_policy,
+        opcode_policy,
+        font,
+        font_base,
+        start_addr,
+        strict_memory,
+        warn_odd_length,
+        seed,
+        control_port,
+        gdb_port,
+        repl,
+        timer,
+        speed,
+        speed_limits,
+        timing,
+        game_config,
+        rpl_flags,
+        input_script,
+        script,
+        dump_state_json,
+        exclusive,
+        headless,
+        dump_format,
+        frames,
+        verify_state,
+        write_state_expectation,
+        ascii_dump_at,
+        breakpoints,
+        watchpoints,
+        ascii_glyphs,
+        compare,
+        disasm,
+        log_level,
+    })
+}
 
-                    let old_pixel = self.display[screen_y][screen_x];
-                    self.display[screen_y][screen_x] ^= true;
+/// Reads and parses the input script at `source`, `-` meaning stdin.
+fn read_input_script(source: &str) -> Result<InputTimeline, String> {
+    let mut script = String::new();
+    let read = if source == "-" {
+        io::stdin().read_to_string(&mut script).map(drop)
+    } else {
+        fs::read_to_string(source).map(|text| script = text)
+    };
+    read.map_err(|e| format!("Failed to read input script {}: {}", source, e))?;
+    InputTimeline::parse(&script).map_err(|e| format!("Input script {}: {}", source, e))
+}
 
-                    if old_pixel && !self.display[screen_y][screen_x] {
-                        self.v[0xF] = 1;
-                    }
-                }
+/// Reads debugger commands from stdin for `--repl`, one per line, and prints
+/// what they return. It's the same command set as the in-window console.
+fn spawn_repl(controller: Controller) {
+    let repl = move || {
+        for line in io::stdin().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            let output = commands::run_line(&line, |command| controller.send(command).map_err(|e| e.to_string()));
+            for line in output {
+                println!("{}", line);
             }
         }
+    };
+    if let Err(e) = std::thread::Builder::new().name("repl".to_string()).spawn(repl) {
+        eprintln!("✗ Failed to start the REPL: {}", e);
     }
+}
 
-    fn op_8xye(&mut self, x: usize, _y: usize) {
-        self.v[0xF] = (self.v[x] >> 7) & 1;
-        self.v[x] <<= 1;
-    }
-
-    fn op_8xy0(&mut self, x: usize, y: usize) {
-        self.v[x] = self.v[y];
-    }
+/// How `--dump-format` prints the machine at the end of a headless run.
+#[derive(Clone, Copy)]
+enum DumpFormat {
+    /// Registers and the display as ASCII art.
+    Txt,
+    Json,
+    /// The display alone, as a plain PBM image.
+    Pbm,
+}
 
-    fn op_8xy1(&mut self, x: usize, y: usize) {
-        self.v[x] |= self.v[y];
-    }
+/// What to check the machine against at the end of a headless run, and
+/// where to write what it ended with.
+#[derive(Default)]
+struct StateChecks {
+    verify: Option<(String, StateExpectation)>,
+    write: Option<String>,
+}
 
-    fn op_8xy2(&mut self, x: usize, y: usize) {
-        self.v[x] &= self.v[y];
+/// Runs without a window until `frames` have run, the machine halts, it
+/// reaches a `--break` address or a `--watch` access, or the program ends on
+/// a 00FD or a jump to itself, as fast as the host allows, then prints the
+/// machine to stdout if asked.
+///
+/// Returns whether the machine matched `--verify-state`. A halt the file
+/// expects isn't an error.
+fn run_headless(
+    chip8: &mut Chip8,
+    mut driver: Driver,
+    frames: Option<u64>,
+    dump: Option<DumpFormat>,
+    checks: StateChecks,
+) -> Result<bool, Chip8Error> {
+    let initial = chip8.memory;
+    while frames.is_none_or(|frames| driver.frames() < frames)
+        && driver.halted().is_none()
+        && driver.finished().is_none()
+        && driver.breakpoint_hit(chip8).is_none()
+        && driver.watch_hit().is_none()
+    {
+        driver.frame(chip8, &mut NullVideo, &mut NullInput, &mut NullAudio);
     }
-
-    fn op_8xy3(&mut self, x: usize, y: usize) {
-        self.v[x] ^= self.v[y];
+    if let Some(pc) = driver.finished() {
+        println!("Program halted at 0x{:03X}", pc);
     }
-
-    fn op_8xy4(&mut self, x: usize, y: usize) {
-        let sum = self.v[x] as u16 + self.v[y] as u16;
-        self.v[0xF] = if sum > 0xFF { 1 } else { 0 };
-        self.v[x] = sum as u8;
+    if driver.breakpoint_hit(chip8).is_some() || driver.watch_hit().is_some() {
+        for line in driver.stop_report(chip8) {
+            println!("{}", line);
+        }
     }
-
-    fn op_8xy5(&mut self, x: usize, y: usize) {
-        self.v[0xF] = if self.v[x] >= self.v[y] { 1 } else { 0 };
-        self.v[x] = self.v[x].wrapping_sub(self.v[y]);
-    }    
-
-    fn op_8xy6(&mut self, x: usize, _y: usize) {
-        self.v[0xF] = self.v[x] & 1;
-        self.v[x] >>= 1;
+    match dump {
+        Some(DumpFormat::Txt) => println!("{}", chip8),
+        Some(DumpFormat::Pbm) => println!("{}", chip8.pbm()),
+        #[cfg(feature = "serde")]
+        Some(DumpFormat::Json) => println!("{}", chip8.to_json()),
+        #[cfg(not(feature = "serde"))]
+        Some(DumpFormat::Json) => unreachable!("rejected when parsing arguments"),
+        None => {}
     }
-
-    fn op_8xy7(&mut self, x: usize, y: usize) {
-        self.v[0xF] = if self.v[y] >= self.v[x] { 1 } else { 0 };
-        self.v[x] = self.v[y] - self.v[x];
+    #[cfg(feature = "serde")]
+    driver.dump_state(chip8)?;
+    if let Some(path) = &checks.write {
+        StateExpectation::capture(chip8, driver.halted(), &initial).save(path.as_ref())?;
+        println!("Wrote the state expectation to {}", path);
     }
-
-    fn op_3xnn(&mut self, x: usize, nn: u8) {
-        if self.v[x] == nn {
-            self.pc += 2;
+    if let Some((path, expected)) = &checks.verify {
+        let mismatches = expected.check(chip8, driver.halted());
+        if !mismatches.is_empty() {
+            eprintln!("✗ The state doesn't match {}: {}", path, expectation::report(&mismatches));
+            return Ok(false);
+        }
+        println!("✓ The state matches {}", path);
+        if expected.halt.is_some() {
+            return Ok(true);
         }
     }
+    driver.finish().map(|()| true)
+}
 
-    fn op_4xnn(&mut self, x: usize, nn: u8) {
-        if self.v[x] != nn {
-            self.pc += 2;
-        }
+/// Logs go to stderr at `--log-level`, refined by `RUST_LOG` directives such
+/// as `chip8::cpu=trace`.
+fn init_logging(level: LevelFilter) {
+    let mut builder = env_logger::Builder::new();
+    builder.filter_level(level);
+    if let Ok(filters) = env::var("RUST_LOG") {
+        builder.parse_filters(&filters);
     }
+    builder.init();
+}
 
-    fn op_5xy0(&mut self, x: usize, y: usize) {
-        if self.v[x] == self.v[y] {
-            self.pc += 2;
+pub(crate) const EXIT_USAGE: u8 = 2;
+/// A file couldn't be read or written, or the ROM couldn't be loaded.
+const EXIT_LOAD: u8 = 3;
+/// The machine stopped on an error while running.
+const EXIT_RUNTIME: u8 = 4;
+/// A save state couldn't be read.
+const EXIT_STATE: u8 = 5;
+/// `--verify-state` found the machine in a different state.
+const EXIT_MISMATCH: u8 = 6;
+
+/// Exit status for a run that ended in `error`.
+pub(crate) fn exit_code(error: &Chip8Error) -> ExitCode {
+    match error {
+        Chip8Error::Io(_) | Chip8Error::Load(_) | Chip8Error::RomTooLarge { .. } | Chip8Error::EmptyRom => {
+            ExitCode::from(EXIT_LOAD)
         }
+        Chip8Error::StateVersionMismatch { .. } | Chip8Error::InvalidState { .. } => ExitCode::from(EXIT_STATE),
+        #[cfg(feature = "serde")]
+        Chip8Error::Json(_) => ExitCode::from(EXIT_STATE),
+        _ => ExitCode::from(EXIT_RUNTIME),
     }
+}
 
-    fn op_9xy0(&mut self, x: usize, y: usize) {
-        if self.v[x] != self.v[y] {
-            self.pc += 2;
+/// Reads `rom_path` to load at `start` with room for the largest memory
+/// there is, leaving [`Chip8::load_rom_bytes`] to hold it to the memory of
+/// the profile it's loaded under.
+fn read_rom_any_size(rom_path: &str, start: u16) -> Result<Vec<u8>, Chip8Error> {
+    let max = EXTENDED_MEMORY_SIZE - start as usize;
+    Ok(chip8_emulator::read_rom(rom_path.as_ref(), max, &default_search_path())?)
+}
+
+/// `info`: what's known about a ROM without running it.
+fn run_info(rom_path: &str) -> ExitCode {
+    let mut chip8 = Chip8::new();
+    let loaded = read_rom_any_size(rom_path, PROGRAM_START).and_then(|rom| {
+        chip8.quirks = rom_db::lookup(&rom).map(KnownRom::quirks).unwrap_or_default();
+        // Nothing but XO-CHIP has the memory for a program this big.
+        chip8.quirks.extended_memory |= rom.len() > MEMORY_SIZE - PROGRAM_START as usize;
+        chip8.load_rom_bytes(&rom).map(|()| rom.len())
+    });
+    let len = match loaded {
+        Ok(len) => len,
+        Err(e) => {
+            eprintln!("✗ {}", e);
+            return exit_code(&e);
         }
+    };
+    println!("ROM:      {} ({} bytes)", rom_path, len);
+    if let Some(hash) = chip8.rom_hash() {
+        println!("Hash:     {:016x}", hash);
     }
-
-    fn op_2nnn(&mut self, nnn: u16) {
-        self.stack.push(self.pc);
-        self.pc = nnn;
+    match chip8.platform() {
+        Some(detection) => println!("Platform: {}", Chip8Error::from(detection)),
+        None => println!("Platform: CHIP-8"),
     }
+    ExitCode::SUCCESS
+}
 
-    fn op_00ee(&mut self) {
-        let popped_addr = self.stack.pop().expect("Couldn't pop addr from stack!");
-        self.pc = popped_addr;
+/// `--disasm`: the ROM's listing, loaded at `--start-addr` under the quirks
+/// it would run with.
+fn run_disasm(args: &Args) -> ExitCode {
+    let mut chip8 = Chip8::with_config(&args.config());
+    let loaded = read_rom_any_size(&args.rom_path, args.start_addr).and_then(|rom| {
+        let detected = rom_db::lookup(&rom).filter(|_| args.autodetect);
+        chip8.quirks = args.quirks(detected.map(KnownRom::quirks));
+        chip8.load_rom_bytes(&rom).map(|()| rom)
+    });
+    match loaded {
+        Ok(rom) => {
+            for line in chip8_emulator::disassemble(&rom, args.start_addr) {
+                println!("{}", line);
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("✗ {}", e);
+            exit_code(&e)
+        }
     }
+}
 
-    fn op_bnnn(&mut self, nnn: u16) {
-        self.pc = nnn + (self.v[0x0] as u16)
+/// `--assemble`: `source` assembled to load at [`PROGRAM_START`], written to
+/// `out`.
+fn run_assemble(source: &str, out: &Path) -> ExitCode {
+    let text = match fs::read_to_string(source) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("✗ Couldn't read {}: {}", source, e);
+            return ExitCode::from(EXIT_LOAD);
+        }
+    };
+    let rom = match chip8_emulator::assemble(&text, PROGRAM_START) {
+        Ok(rom) => rom,
+        Err(e) => {
+            eprintln!("✗ {}: {}", source, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(e) = fs::write(out, &rom) {
+        eprintln!("✗ Couldn't write {}: {}", out.display(), e);
+        return ExitCode::from(EXIT_LOAD);
     }
+    println!("Wrote {} bytes to {}", rom.len(), out.display());
+    ExitCode::SUCCESS
+}
 
-     fn op_cxnn(&mut self, x: usize, nn: u8) {
-         let random_byte: u8 = rand::random();
-         self.v[x] = random_byte & nn;
-     }
-
-     fn op_fx07(&mut self, x: usize) {
-        self.v[x] = self.delay_timer;
+fn main() -> ExitCode {
+    let mut raw = env::args().skip(1);
+    match raw.next().as_deref() {
+        Some("info") => {
+            return match (raw.next(), raw.next()) {
+                (Some(rom_path), None) => run_info(&rom_path),
+                _ => {
+                    eprintln!("✗ info needs a ROM and nothing else");
+                    eprintln!("\n{}", USAGE);
+                    ExitCode::from(EXIT_USAGE)
+                }
+            };
+        }
+        Some("--assemble") => {
+            let rest: Vec<String> = raw.collect();
+            return match rest.as_slice() {
+                [source] => run_assemble(source, &Path::new(source).with_extension("ch8")),
+                [source, flag, out] if flag == "-o" => run_assemble(source, Path::new(out)),
+                _ => {
+                    eprintln!("✗ --assemble needs a source file and optionally -o and an output file");
+                    eprintln!("\n{}", USAGE);
+                    ExitCode::from(EXIT_USAGE)
+                }
+            };
+        }
+        _ => {}
     }
+    let mut args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("✗ {}", e);
+            eprintln!("\n{}", USAGE);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+    init_logging(args.log_level);
+    let rom_path = args.rom_path.as_str();
 
-    fn op_fx15(&mut self, x: usize) {
-        self.delay_timer = self.v[x];
+    if args.disasm {
+        return run_disasm(&args);
     }
-
-    fn op_fx18(&mut self, x: usize) {
-        self.sound_timer = self.v[x];
+    if let Some((a, b)) = &args.compare {
+        #[cfg(feature = "frontend-minifb")]
+        return compare::run_compare(rom_path, a, b);
+        #[cfg(not(feature = "frontend-minifb"))]
+        {
+            let _ = (a, b);
+            eprintln!("✗ --compare needs the minifb backend");
+            return ExitCode::from(EXIT_USAGE);
+        }
     }
 
-    fn op_fx1e(&mut self, x: usize) {
-        self.i += self.v[x] as u16;
+    let timeline = match args.input_script.as_deref().map(read_input_script) {
+        Some(Ok(timeline)) => timeline,
+        Some(Err(e)) => {
+            eprintln!("✗ {}", e);
+            return ExitCode::from(EXIT_USAGE);
+        }
+        None => InputTimeline::new(),
+    };
+
+    #[cfg(feature = "scripting")]
+    let script = match &args.script {
+        Some(path) => {
+            use chip8_emulator::scripting::{DEFAULT_BUDGET, Script};
+            match Script::from_file(path, DEFAULT_BUDGET) {
+                Ok(script) => Some(script),
+                Err(e) => {
+                    eprintln!("✗ Failed to load script {}: {}", path, e);
+                    return ExitCode::from(EXIT_USAGE);
+                }
+            }
+        }
+        None => None,
+    };
+    #[cfg(not(feature = "scripting"))]
+    if args.script.is_some() {
+        eprintln!("✗ --script needs the scripting feature");
+        return ExitCode::from(EXIT_USAGE);
     }
-
-    fn op_fx29(&mut self, x: usize) {
-        self.i = (self.v[x] as u16) * 5;
+    #[cfg(not(feature = "serde"))]
+    if args.dump_state_json.is_some() || matches!(args.dump_format, Some(DumpFormat::Json)) {
+        eprintln!("✗ --dump-state-json and --dump-format json need the serde feature");
+        return ExitCode::from(EXIT_USAGE);
     }
-
-    fn op_fx33(&mut self, x: usize) {
-        self.memory[self.i as usize] = self.v[x] / 100;
-        self.memory[self.i as usize + 1] = (self.v[x] / 10) % 10;
-        self.memory[self.i as usize + 2] = self.v[x] % 10;
+    if args.repl && args.input_script.as_deref() == Some("-") {
+        eprintln!("✗ --repl and --input-script - both read stdin");
+        return ExitCode::from(EXIT_USAGE);
     }
-
-    fn op_fx55(&mut self, x: usize) {
-        for misc in 0..=x {
-            self.memory[self.i as usize + misc] = self.v[misc];
-        }
+    #[cfg(feature = "backend-terminal")]
+    if args.repl && args.backend == Backend::Terminal && !args.headless {
+        eprintln!("✗ --repl can't share the terminal with the terminal backend; use the ` console");
+        return ExitCode::from(EXIT_USAGE);
     }
-
-    fn op_fx65(&mut self, x: usize) {
-        for misc in 0..=x {
-            self.v[misc] = self.memory[self.i as usize + misc];
-        }
+    if args.dump_format.is_some() && !args.headless {
+        eprintln!("✗ --dump-format only applies with --headless");
+        return ExitCode::from(EXIT_USAGE);
     }
-
-    fn op_ex9e(&mut self, x: usize) {
-        if self.keys[self.v[x] as usize] {
-            self.pc += 2;
-        }
+    if (args.verify_state.is_some() || args.write_state_expectation.is_some()) && !args.headless {
+        eprintln!("✗ --verify-state and --write-state-expectation only apply with --headless");
+        return ExitCode::from(EXIT_USAGE);
     }
-
-    fn op_exa1(&mut self, x: usize) {
-        if !self.keys[self.v[x] as usize] {
-            self.pc += 2;
+    let mut checks = StateChecks { write: args.write_state_expectation.clone(), ..Default::default() };
+    if let Some(path) = &args.verify_state {
+        match StateExpectation::load(path.as_ref()) {
+            Ok(expected) => checks.verify = Some((path.clone(), expected)),
+            Err(e) => {
+                eprintln!("✗ Failed to read {}: {}", path, e);
+                return ExitCode::from(EXIT_USAGE);
+            }
         }
     }
 
-    fn op_fx0a(&mut self, x: usize) {
-        let mut key_pressed = false;
-    
-        for i in 0..16 {
-            if self.keys[i] {
-                self.v[x] = i as u8;
-                key_pressed = true;
-                break;
-            }
-        }
-    
-        if !key_pressed {
-            self.pc = self.pc.saturating_sub(2);
-        }
-    }    
-
-    fn get_display_buffer(&self) -> Vec<u32> {
-        let mut buffer = vec![0u32; 64 * 32];
-        
-        for y in 0..32 {
-            for x in 0..64 {
-                let pixel = if self.display[y][x] {
-                    0xFFFFFF
-                } else {
-                    0x000000
-                };
-                buffer[y * 64 + x] = pixel;
-            }
-        }
-        
-        buffer
-    }
-
-    fn update_keys(&mut self, window: &Window) {
-        self.keys = [false; 16];
-        
-        self.keys[0x1] = window.is_key_down(Key::Key1);
-        self.keys[0x2] = window.is_key_down(Key::Key2);
-        self.keys[0x3] = window.is_key_down(Key::Key3);
-        self.keys[0xC] = window.is_key_down(Key::Key4);
-        
-        self.keys[0x4] = window.is_key_down(Key::Q);
-        self.keys[0x5] = window.is_key_down(Key::W);
-        self.keys[0x6] = window.is_key_down(Key::E);
-        self.keys[0xD] = window.is_key_down(Key::R);
-        
-        self.keys[0x7] = window.is_key_down(Key::A);
-        self.keys[0x8] = window.is_key_down(Key::S);
-        self.keys[0x9] = window.is_key_down(Key::D);
-        self.keys[0xE] = window.is_key_down(Key::F);
-        
-        self.keys[0xA] = window.is_key_down(Key::Z);
-        self.keys[0x0] = window.is_key_down(Key::X);
-        self.keys[0xB] = window.is_key_down(Key::C);
-        self.keys[0xF] = window.is_key_down(Key::V);
+    let mut chip8 = Chip8::with_config(&args.config());
+    for watchpoint in &args.watchpoints {
+        chip8.add_watchpoint(watchpoint.clone());
     }
-}
 
-fn main() {
-    let mut chip8 = Chip8::new();
-    
-    // Get ROM from command line or use default
-    let args: Vec<String> = env::args().collect();
-    let rom_path = if args.len() > 1 { &args[1] } else { "Pong.ch8" };
-    
     println!("╔═════════════════════════════════════════════╗");
     println!("║   CHIP-8 EMULATOR - RUST EDITION BY INCENIX ║");
     println!("╚═════════════════════════════════════════════╝");
     println!("\nLoading ROM: {}", rom_path);
     
-    match chip8.load_rom(rom_path) {
-        Ok(_) => println!("✓ ROM loaded successfully!\n"),
+    let flags_path = args.rpl_flags.clone().unwrap_or_else(|| rpl_flags::path_for(Path::new(rom_path)));
+    // The database's quirks go in before loading: they decide how much
+    // memory the ROM has to fit in.
+    let mut detected = None;
+    let loaded = read_rom_any_size(rom_path, args.start_addr).and_then(|rom| {
+        detected = rom_db::lookup(&rom).filter(|_| args.autodetect);
+        chip8.quirks = args.quirks(detected.map(KnownRom::quirks));
+        chip8.load_rom_bytes(&rom)
+    });
+    match loaded {
+        Ok(()) => {
+            println!("✓ ROM loaded successfully!");
+            if let Some(known) = detected {
+                if args.profile.is_some() {
+                    println!("✓ Recognized {} ({} quirks, but --profile wins)", known.title, known.profile);
+                } else {
+                    println!("✓ Recognized {} ({} quirks)", known.title, known.profile);
+                }
+            }
+            match rpl_flags::load(&flags_path) {
+                Ok(Some(flags)) => {
+                    chip8.rpl_flags = flags;
+                    println!("✓ Loaded RPL flags from {}", flags_path.display());
+                }
+                Ok(None) => {}
+                Err(e) => log::warn!(target: FRONTEND_TARGET, "Ignoring {}: {}", flags_path.display(), e),
+            }
+            println!();
+        }
         Err(e) => {
             eprintln!("✗ Failed to load ROM: {}", e);
-            eprintln!("\nUsage: cargo run [rom_path]");
-            return;
+            eprintln!("\n{}", USAGE);
+            // Launched from a file manager there's no console to read, so
+            // the error is shown in a window as well.
+            if !args.headless {
+                args.backend.show_error_window("Failed to load ROM", &e.to_string());
+            }
+            return exit_code(&e);
         }
     }
 
-    let mut window = Window::new(
-        "Chip-8 Emulator",
-        64 * 10,
-        32 * 10,
-        WindowOptions::default(),
-    )
-    .expect("Failed to create window");
-
-    window.limit_update_rate(Some(Duration::from_micros(16600)));
-
-    // Audio setup
-    let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-    let sink = Sink::try_new(&stream_handle).unwrap();
-    let mut beeping = false;
-    
-    let mut instructions_per_frame = 10;
-
-    println!("Controls:");
-    println!("  ESC      - Exit emulator");
-    println!("  +/=      - Speed up");
-    println!("  -        - Slow down");
-    println!("  1234     - Keys 1, 2, 3, C");
-    println!("  QWER     - Keys 4, 5, 6, D");
-    println!("  ASDF     - Keys 7, 8, 9, E");
-    println!("  ZXCV     - Keys A, 0, B, F");
-    println!("\nEmulator running...\n");
-
-    while window.is_open() && !window.is_key_down(Key::Escape) {
-        chip8.update_keys(&window);
-        
-        if window.is_key_pressed(Key::Equal, minifb::KeyRepeat::No) {
-            instructions_per_frame = (instructions_per_frame + 2).min(50);
-            println!("Speed: {}x", instructions_per_frame / 10);
+    if !args.headless {
+        println!("Controls:");
+        if args.classic_escape {
+            println!("  ESC      - Exit emulator");
+        } else {
+            println!("  ESC      - Pause menu (arrows and Enter to choose)");
         }
-        if window.is_key_pressed(Key::Minus, minifb::KeyRepeat::No) {
-            instructions_per_frame = (instructions_per_frame - 2).max(2);
-            println!("Speed: {}x", instructions_per_frame / 10);
+        println!("  =        - Speed up (Shift for a little)");
+        println!("  -        - Slow down (Shift for a little)");
+        println!("  0        - Normal speed");
+        println!("  1234     - Keys 1, 2, 3, C");
+        println!("  QWER     - Keys 4, 5, 6, D");
+        println!("  ASDF     - Keys 7, 8, 9, E");
+        println!("  ZXCV     - Keys A, 0, B, F");
+        println!("  Arrows   - Keys 2, 4, 6, 8 (Space 5, Enter 6) unless the game config turns them off");
+        if args.dump_state_json.is_some() {
+            println!("  F9       - Dump the machine state as JSON");
         }
-        
-        for _ in 0..instructions_per_frame {
-            if chip8.pc >= 4094 {
-                break;
-            }
-            
-            let opcode = chip8.fetch();
-            chip8.execute(opcode);
+        println!("  F10      - Print the screen as text");
+        if args.backend.toggles_window_options() {
+            println!("  F7/F8/F12 - Toggle always on top, borderless, resizable");
+        }
+        if args.timer.is_some() {
+            println!("  F2       - Speedrun split");
         }
+        println!("  F6       - Save this game's speed and window scale");
+        println!("  Ctrl+C   - Copy the screen (quits in the terminal)");
+        if args.backend.has_window() {
+            println!("  Ctrl+1-0 - Window scale 1x to 10x (Ctrl+= and Ctrl+- to step)");
+        }
+        println!("  P        - Pause or resume");
+        println!("  N or .   - Step one instruction");
+        println!("  F3       - Register readout, always or only while paused");
+        println!("  F1       - Help");
+        println!("  `        - Debug console");
+    }
+    println!("\nEmulator running...\n");
 
-        if chip8.delay_timer > 0 {
-            chip8.delay_timer -= 1;
+    let mut driver = Driver::new();
+    driver.pause_on_focus_loss = args.pause_on_focus_loss;
+    driver.show_hash = args.show_hash;
+    driver.classic_escape = args.classic_escape;
+    driver.set_speed_limits(args.speed_limits);
+    driver.set_timing(args.timing);
+    driver.set_config(args.config());
+    let game = match GameConfig::load(&args.game_config) {
+        Ok(config) => chip8.rom_hash().map(|hash| config.settings(hash)).unwrap_or_default(),
+        Err(e) => {
+            log::warn!(target: FRONTEND_TARGET, "Ignoring {}: {}", args.game_config.display(), e);
+            Default::default()
         }
-        if chip8.sound_timer > 0 {
-            chip8.sound_timer -= 1;
-            
-            if !beeping {
-                sink.append(SineWave::new(440.0));
-                beeping = true;
+    };
+    let (cpu_hz, source) = game_config::resolve(BASE_HZ, game.cpu_hz, args.speed);
+    driver.set_clock_hz(cpu_hz);
+    info!(target: FRONTEND_TARGET, "cpu clock {} Hz from {}", driver.speed().hz(), source);
+    if !args.headless && args.backend.has_window() {
+        let (scale, source) = game_config::resolve(WINDOW_SCALE, game.scale, args.scale);
+        info!(target: FRONTEND_TARGET, "window scale {}x from {}", scale, source);
+        args.window.scale = scale;
+        driver.set_window_scale(scale);
+    } else if let Some(scale) = args.scale {
+        args.window.scale = scale;
+    }
+    let (secondary, source) = game_config::resolve(true, game.secondary_bindings, None);
+    info!(target: FRONTEND_TARGET, "secondary bindings {} from {}", if secondary { "on" } else { "off" }, source);
+    driver.set_game_config(&args.game_config);
+    driver.set_rpl_flags(flags_path, chip8.rpl_flags);
+    driver.set_ascii_dump(args.ascii_glyphs, io::stdout());
+    driver.set_ascii_dump_at(args.ascii_dump_at.iter().copied());
+    for &addr in &args.breakpoints {
+        driver.add_breakpoint(addr);
+    }
+    if !args.headless {
+        driver.set_clipboard(backend::clipboard::SystemClipboard);
+    }
+    if let Some(start) = args.timer {
+        driver.set_speedrun_timer(start);
+    }
+    #[cfg(feature = "serde")]
+    if let Some(path) = &args.dump_state_json {
+        driver.set_state_dump(path);
+    }
+    if args.control_port.is_some() || args.gdb_port.is_some() || args.repl {
+        let (controller, receiver) = chip8_emulator::frontend::control::channel();
+        if let Some(port) = args.control_port {
+            #[cfg(feature = "control-socket")]
+            match chip8_emulator::control_socket::spawn(("127.0.0.1", port), controller.clone()) {
+                Ok(addr) => println!("Control socket listening on {}\n", addr),
+                Err(e) => {
+                    eprintln!("✗ Failed to open control port {}: {}", port, e);
+                    return ExitCode::from(EXIT_USAGE);
+                }
+            }
+            #[cfg(not(feature = "control-socket"))]
+            {
+                let _ = port;
+                eprintln!("✗ --control-port needs the control-socket feature");
+                return ExitCode::from(EXIT_USAGE);
             }
-        } else if beeping {
-            sink.stop();
-            beeping = false;
         }
+        if let Some(port) = args.gdb_port {
+            match chip8_emulator::gdb::spawn(("127.0.0.1", port), controller.clone()) {
+                Ok(addr) => println!("GDB server listening on {}\n", addr),
+                Err(e) => {
+                    eprintln!("✗ Failed to open GDB port {}: {}", port, e);
+                    return ExitCode::from(EXIT_USAGE);
+                }
+            }
+        }
+        if args.repl {
+            spawn_repl(controller);
+        }
+        driver.attach_control(receiver);
+    }
 
-        let buffer = chip8.get_display_buffer();
-        window
-            .update_with_buffer(&buffer, 64, 32)
-            .expect("Failed to update window");
+    // A script on its own ends the headless run after its last event, and
+    // screenshots after the last one.
+    let frames = args.frames.or_else(|| {
+        let script_end = timeline.last_frame().map(|last| last + 1);
+        script_end.into_iter().chain(args.ascii_dump_at.iter().copied()).max()
+    });
+    driver.set_input_timeline(timeline, args.exclusive);
+    #[cfg(feature = "scripting")]
+    if let Some(script) = script {
+        driver.set_hooks(script);
     }
-    
+    let result = if args.headless {
+        run_headless(&mut chip8, driver, frames, args.dump_format, checks)
+    } else {
+        let result = args.backend.run(&mut chip8, driver, args.window, secondary);
+        info!(target: FRONTEND_TARGET, "Window closed");
+        result.map(|()| true)
+    };
+
     println!("\nEmulator closed. Thanks for playing!");
-}
\ No newline at end of file
+
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(EXIT_MISMATCH),
+        Err(e) => exit_code(&e),
+    }
+}
@@ -0,0 +1,281 @@
+//! Names for the parts of the address space, so addresses in dumps and
+//! errors say what they point at.
+
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+use core::fmt::{self, Write};
+use core::ops::Range;
+
+use crate::chip8::{MEMORY_SIZE, PROGRAM_START};
+use crate::hex_font::{BIG_FONT_BASE, BIG_FONTSET, BIG_GLYPH_BYTES as BIG_FONT_GLYPH, FONT_BASE, GLYPH_BYTES as FONT_GLYPH};
+#[cfg(feature = "alloc")]
+use crate::error::Chip8Error;
+
+/// Where things are in memory for a particular load.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryMap {
+    /// The 4x5 hex digits, five bytes each.
+    pub font: Range<u16>,
+    /// SUPER-CHIP's 8x10 digits, ten bytes each, for machines that have them.
+    /// This one always does, at [`BIG_FONT_BASE`].
+    pub big_font: Option<Range<u16>>,
+    /// The program as loaded. Everything from its end to the top of memory
+    /// is work RAM.
+    pub rom: Range<u16>,
+    /// Where memory ends: [`MEMORY_SIZE`], or XO-CHIP's 64 KB with
+    /// [extended memory](crate::Quirks::extended_memory).
+    pub size: usize,
+}
+
+/// What an address is part of, from [`MemoryMap::region`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Font { digit: u8, row: u8 },
+    BigFont { digit: u8, row: u8 },
+    /// Below the program and outside the fonts: the interpreter's own space
+    /// on the original machines.
+    Reserved,
+    /// `offset` bytes into the program.
+    Rom { offset: u16 },
+    /// `offset` bytes past the end of the program.
+    WorkRam { offset: u16 },
+    /// Past the end of memory.
+    Outside,
+}
+
+impl MemoryMap {
+    /// The map for a `rom_len` byte program loaded the usual way, with the
+    /// font at [`FONT_BASE`] and the big font at [`BIG_FONT_BASE`].
+    pub fn new(rom_len: usize) -> Self {
+        let rom_end = (PROGRAM_START as usize + rom_len).min(MEMORY_SIZE) as u16;
+        Self {
+            font: FONT_BASE..FONT_BASE + 16 * FONT_GLYPH,
+            big_font: Some(BIG_FONT_BASE..BIG_FONT_BASE + BIG_FONTSET.len() as u16),
+            rom: PROGRAM_START..rom_end,
+            size: MEMORY_SIZE,
+        }
+    }
+
+    pub fn region(&self, addr: usize) -> Region {
+        let Ok(addr) = u16::try_from(addr) else {
+            return Region::Outside;
+        };
+        if addr as usize >= self.size {
+            Region::Outside
+        } else if self.font.contains(&addr) {
+            let offset = addr - self.font.start;
+            Region::Font {
+                digit: (offset / FONT_GLYPH) as u8,
+                row: (offset % FONT_GLYPH) as u8,
+            }
+        } else if let Some(big_font) = self.big_font.as_ref().filter(|range| range.contains(&addr)) {
+            let offset = addr - big_font.start;
+            Region::BigFont {
+                digit: (offset / BIG_FONT_GLYPH) as u8,
+                row: (offset % BIG_FONT_GLYPH) as u8,
+            }
+        } else if self.rom.contains(&addr) {
+            Region::Rom { offset: addr - self.rom.start }
+        } else if addr >= self.rom.end {
+            Region::WorkRam { offset: addr - self.rom.end }
+        } else {
+            Region::Reserved
+        }
+    }
+
+    /// `addr` formatted with its region, as in `0x202 (ROM+0x002)`.
+    pub fn annotate(&self, addr: usize) -> Annotated {
+        Annotated { addr, region: self.region(addr) }
+    }
+
+    /// `error`'s message with the addresses in it annotated.
+    #[cfg(feature = "alloc")]
+    pub fn explain(&self, error: &Chip8Error) -> String {
+        let mut text = alloc::format!("{}", error);
+        let pc = match *error {
+            Chip8Error::MemoryOutOfBounds { pc, .. }
+            | Chip8Error::InvalidOpcode { pc, .. }
+            | Chip8Error::FatalOpcode { pc, .. }
+            | Chip8Error::UnsupportedPlatform { pc, .. }
+            | Chip8Error::MachineCodeCall { pc, .. }
+            | Chip8Error::ProtectedWrite { pc, .. }
+            | Chip8Error::BlockOutOfBounds { pc, .. }
+            | Chip8Error::InvalidKey { pc, .. }
+            | Chip8Error::StackOverflow { pc, .. } => pc,
+            Chip8Error::PcOutOfBounds { pc } | Chip8Error::StackUnderflow { pc } => pc,
+            _ => return text,
+        };
+        let _ = write!(text, "; PC {}", self.annotate(pc as usize));
+        text
+    }
+
+    /// `memory[range]` as hex, 16 bytes a line, each line starting with its
+    /// annotated address.
+    #[cfg(feature = "alloc")]
+    pub fn hex_dump(&self, memory: &[u8], range: Range<usize>) -> String {
+        let mut dump = String::new();
+        let end = range.end.min(memory.len());
+        let mut addr = range.start;
+        while addr < end {
+            let row = &memory[addr..end.min(addr + 16)];
+            let _ = write!(dump, "{:<24}", self.annotate(addr));
+            for byte in row {
+                let _ = write!(dump, " {:02X}", byte);
+            }
+            dump.push('\n');
+            addr += row.len();
+        }
+        dump
+    }
+}
+
+impl Default for MemoryMap {
+    /// The map with no program loaded.
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Region::Font { digit, row } => write!(f, "font digit {:X}, row {}", digit, row),
+            Region::BigFont { digit, row } => write!(f, "big font digit {}, row {}", digit, row),
+            Region::Reserved => write!(f, "interpreter area"),
+            Region::Rom { offset } => write!(f, "ROM+0x{:03X}", offset),
+            Region::WorkRam { offset } => write!(f, "work RAM+0x{:03X}", offset),
+            Region::Outside => write!(f, "outside memory"),
+        }
+    }
+}
+
+/// An address and its [`Region`], from [`MemoryMap::annotate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Annotated {
+    pub addr: usize,
+    pub region: Region,
+}
+
+impl fmt::Display for Annotated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Padded as a whole, so dumps can line columns up.
+        let mut buffer = [0u8; 48];
+        let mut cursor = Cursor { buffer: &mut buffer, len: 0 };
+        write!(cursor, "0x{:03X} ({})", self.addr, self.region)?;
+        let len = cursor.len;
+        f.pad(core::str::from_utf8(&buffer[..len]).map_err(|_| fmt::Error)?)
+    }
+}
+
+/// Formats into a fixed buffer, without allocating.
+struct Cursor<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+}
+
+impl fmt::Write for Cursor<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        self.buffer.get_mut(self.len..end).ok_or(fmt::Error)?.copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn regions_at_their_boundaries() {
+        let map = MemoryMap::new(0x300);
+        let cases = [
+            (0x000, Region::Font { digit: 0, row: 0 }),
+            (0x004, Region::Font { digit: 0, row: 4 }),
+            (0x005, Region::Font { digit: 1, row: 0 }),
+            (0x04F, Region::Font { digit: 0xF, row: 4 }),
+            (0x050, Region::Reserved),
+            (0x0A0, Region::BigFont { digit: 0, row: 0 }),
+            (0x103, Region::BigFont { digit: 9, row: 9 }),
+            (0x104, Region::Reserved),
+            (0x1FF, Region::Reserved),
+            (0x200, Region::Rom { offset: 0 }),
+            (0x4FF, Region::Rom { offset: 0x2FF }),
+            (0x500, Region::WorkRam { offset: 0 }),
+            (0xFFF, Region::WorkRam { offset: 0xAFF }),
+            (0x1000, Region::Outside),
+            (usize::MAX, Region::Outside),
+        ];
+        for (addr, region) in cases {
+            assert_eq!(map.region(addr), region, "0x{addr:X}");
+        }
+    }
+
+    #[test]
+    fn empty_and_full_roms() {
+        let empty = MemoryMap::new(0);
+        assert_eq!(empty.region(0x200), Region::WorkRam { offset: 0 });
+        let full = MemoryMap::new(MEMORY_SIZE - PROGRAM_START as usize);
+        assert_eq!(full.region(0xFFF), Region::Rom { offset: 0xDFF });
+        // Longer than fits is clamped rather than wrapping.
+        assert_eq!(MemoryMap::new(usize::MAX / 2).rom.end, MEMORY_SIZE as u16);
+    }
+
+    #[test]
+    fn big_font_sits_between_the_font_and_the_program() {
+        let map = MemoryMap { big_font: Some(0x050..0x0F0), ..MemoryMap::new(2) };
+        assert_eq!(map.region(0x04F), Region::Font { digit: 0xF, row: 4 });
+        assert_eq!(map.region(0x050), Region::BigFont { digit: 0, row: 0 });
+        assert_eq!(map.region(0x063), Region::BigFont { digit: 1, row: 9 });
+        assert_eq!(map.region(0x0EF), Region::BigFont { digit: 15, row: 9 });
+        assert_eq!(map.region(0x0F0), Region::Reserved);
+    }
+
+    #[test]
+    fn extended_memory_is_work_ram_up_to_64_kb() {
+        let map = MemoryMap { size: 0x10000, ..MemoryMap::new(0x300) };
+        assert_eq!(map.region(0x1000), Region::WorkRam { offset: 0xB00 });
+        assert_eq!(map.region(0xFFFF), Region::WorkRam { offset: 0xFAFF });
+        assert_eq!(map.region(0x10000), Region::Outside);
+    }
+
+    #[test]
+    fn annotations_read_naturally() {
+        let map = MemoryMap::new(0x300);
+        assert_eq!(map.annotate(0x007).to_string(), "0x007 (font digit 1, row 2)");
+        assert_eq!(map.annotate(0x400).to_string(), "0x400 (ROM+0x200)");
+        assert_eq!(map.annotate(0x510).to_string(), "0x510 (work RAM+0x010)");
+        assert_eq!(map.annotate(0x0AB).to_string(), "0x0AB (big font digit 1, row 1)");
+        assert_eq!(map.annotate(0x150).to_string(), "0x150 (interpreter area)");
+        assert_eq!(map.annotate(0x1000).to_string(), "0x1000 (outside memory)");
+        assert_eq!(format!("[{:<20}]", map.annotate(0x200)), "[0x200 (ROM+0x000)   ]");
+    }
+
+    #[test]
+    fn errors_and_dumps_are_annotated() {
+        let map = MemoryMap::new(4);
+        let error = Chip8Error::MemoryOutOfBounds { addr: 0x1000, pc: 0x202 };
+        assert_eq!(
+            map.explain(&error),
+            "memory access at 0x1000 is out of bounds (instruction at 0x202); PC 0x202 (ROM+0x002)"
+        );
+        let error = Chip8Error::StackUnderflow { pc: 0x200 };
+        assert_eq!(
+            map.explain(&error),
+            "stack underflow: return with an empty call stack (instruction at 0x200); PC 0x200 (ROM+0x000)"
+        );
+        let error = Chip8Error::InvalidState { reason: "truncated" };
+        assert_eq!(map.explain(&error), error.to_string());
+
+        let mut memory = [0u8; MEMORY_SIZE];
+        memory[0x200..0x204].copy_from_slice(&[0x60, 0x05, 0x12, 0x00]);
+        assert_eq!(
+            map.hex_dump(&memory, 0x1F8..0x214),
+            "0x1F8 (interpreter area) 00 00 00 00 00 00 00 00 60 05 12 00 00 00 00 00\n\
+             0x208 (work RAM+0x004)   00 00 00 00 00 00 00 00 00 00 00 00\n"
+        );
+        // Clamped to memory.
+        assert_eq!(map.hex_dump(&memory, 0xFFE..0x2000), "0xFFE (work RAM+0xDFA)   00 00\n");
+    }
+}
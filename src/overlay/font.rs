@@ -0,0 +1,76 @@
+//! The overlay font, parsed at compile time from `font5x7.txt`.
+
+pub const GLYPH_WIDTH: usize = 5;
+pub const GLYPH_HEIGHT: usize = 7;
+
+const FIRST: u8 = b' ';
+const COUNT: usize = 95;
+
+/// One entry per printable ASCII character starting at space; each row holds
+/// the glyph's columns with the leftmost pixel in bit 4.
+pub const GLYPHS: [[u8; GLYPH_HEIGHT]; COUNT] = parse(include_bytes!("font5x7.txt"));
+
+/// Returns the glyph for `c`, or `?` for characters outside printable ASCII.
+pub fn glyph(c: char) -> &'static [u8; GLYPH_HEIGHT] {
+    let index = match c {
+        ' '..='~' => c as usize - FIRST as usize,
+        _ => '?' as usize - FIRST as usize,
+    };
+    &GLYPHS[index]
+}
+
+const fn parse(src: &[u8]) -> [[u8; GLYPH_HEIGHT]; COUNT] {
+    let mut glyphs = [[0u8; GLYPH_HEIGHT]; COUNT];
+    let mut seen: u128 = 0;
+    let mut current = COUNT;
+    let mut row = GLYPH_HEIGHT;
+    let mut pos = 0;
+
+    while pos < src.len() {
+        let start = pos;
+        while pos < src.len() && src[pos] != b'\n' {
+            pos += 1;
+        }
+        let mut end = pos;
+        pos += 1;
+        if end > start && src[end - 1] == b'\r' {
+            end -= 1;
+        }
+
+        if end == start || src[start] == b'/' {
+            continue;
+        }
+
+        if src[start] == b':' {
+            assert!(row == GLYPH_HEIGHT, "glyph has fewer than 7 rows");
+            // Editors like to strip the trailing space from the space glyph's header.
+            let c = if end - start > 1 { src[start + 1] } else { b' ' };
+            assert!(c >= FIRST && c <= b'~', "glyph header is not printable ASCII");
+            current = (c - FIRST) as usize;
+            assert!(seen & (1 << current) == 0, "glyph defined twice");
+            seen |= 1 << current;
+            row = 0;
+            continue;
+        }
+
+        assert!(current < COUNT && row < GLYPH_HEIGHT, "glyph row outside of a glyph");
+        assert!(end - start == GLYPH_WIDTH, "glyph row is not 5 columns wide");
+        let mut bits = 0u8;
+        let mut col = 0;
+        while col < GLYPH_WIDTH {
+            bits <<= 1;
+            match src[start + col] {
+                b'#' => bits |= 1,
+                b'.' => {}
+                _ => panic!("glyph rows may only contain '#' and '.'"),
+            }
+            col += 1;
+        }
+        glyphs[current][row] = bits;
+        row += 1;
+    }
+
+    assert!(row == GLYPH_HEIGHT, "glyph has fewer than 7 rows");
+    assert!(seen == (1 << COUNT) - 1, "font is missing glyphs");
+    glyphs
+}
@@ -0,0 +1,213 @@
+//! Small status indicators in the top-right corner of the screen: paused,
+//! turbo, recording and muted.
+//!
+//! Where each goes is worked out by [`layout`] without touching a buffer, so
+//! frontends that draw some other way can place them the same. They're only
+//! ever drawn over a frontend's own screen, never into the display's pixels,
+//! so anything taken from those leaves them out.
+
+use super::{CHAR_ADVANCE, GLYPH_HEIGHT, LINE_HEIGHT, draw_glyph, draw_text, fill_rect};
+
+/// A state worth showing on screen, in the order they're placed from the
+/// right.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indicator {
+    Paused,
+    /// Running faster than the default speed.
+    Turbo,
+    Recording,
+    /// No audio output.
+    Muted,
+}
+
+impl Indicator {
+    pub const ALL: [Indicator; 4] = [Indicator::Paused, Indicator::Turbo, Indicator::Recording, Indicator::Muted];
+
+    /// The indicator's icon, in the same 5x7 layout as the overlay font.
+    pub fn icon(self) -> &'static [u8; GLYPH_HEIGHT] {
+        match self {
+            // Two bars.
+            Indicator::Paused => &[0b00000, 0b11011, 0b11011, 0b11011, 0b11011, 0b11011, 0b00000],
+            // A double chevron.
+            Indicator::Turbo => &[0b00000, 0b10100, 0b01010, 0b00101, 0b01010, 0b10100, 0b00000],
+            // A dot.
+            Indicator::Recording => &[0b00000, 0b01110, 0b11111, 0b11111, 0b11111, 0b01110, 0b00000],
+            // A speaker.
+            Indicator::Muted => &[0b00010, 0b00110, 0b11110, 0b11110, 0b11110, 0b00110, 0b00010],
+        }
+    }
+
+    /// Text drawn after the icon, if any.
+    pub fn label(self) -> &'static str {
+        match self {
+            Indicator::Paused => "PAUSED",
+            Indicator::Muted => "x",
+            Indicator::Turbo | Indicator::Recording => "",
+        }
+    }
+
+    pub fn color(self) -> u32 {
+        match self {
+            Indicator::Paused => 0xFFFF55,
+            Indicator::Turbo => 0x55FFFF,
+            Indicator::Recording => 0xFF3333,
+            Indicator::Muted => 0xAAAAAA,
+        }
+    }
+
+    /// The indicator as text, for frontends that draw with characters.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Indicator::Paused => "⏸ PAUSED",
+            Indicator::Turbo => "»",
+            Indicator::Recording => "●",
+            Indicator::Muted => "🔇",
+        }
+    }
+
+    /// Character cells taken up by the icon and label.
+    fn cells(self) -> usize {
+        1 + self.label().chars().count()
+    }
+}
+
+/// Which indicators are showing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Indicators {
+    pub paused: bool,
+    pub turbo: bool,
+    pub recording: bool,
+    pub muted: bool,
+}
+
+impl Indicators {
+    pub fn is_active(&self, indicator: Indicator) -> bool {
+        match indicator {
+            Indicator::Paused => self.paused,
+            Indicator::Turbo => self.turbo,
+            Indicator::Recording => self.recording,
+            Indicator::Muted => self.muted,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.active().next().is_none()
+    }
+
+    /// The active indicators, in [`Indicator::ALL`] order.
+    pub fn active(self) -> impl Iterator<Item = Indicator> {
+        Indicator::ALL.into_iter().filter(move |&indicator| self.is_active(indicator))
+    }
+}
+
+/// Where [`layout`] put an indicator: the box its icon and label fill.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    pub indicator: Indicator,
+    pub x: i32,
+    pub y: i32,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Places the active `indicators` at `scale` on a screen `width` pixels
+/// wide: right to left along the top, a blank cell apart, starting a row
+/// further down for any that would run off the left edge. The left of the
+/// screen stays clear for as long as the row has room, for other overlays.
+pub fn layout(indicators: Indicators, width: usize, scale: usize) -> impl Iterator<Item = Placement> {
+    let margin = scale as i32;
+    let gap = (CHAR_ADVANCE * scale) as i32;
+    let height = LINE_HEIGHT * scale;
+    let right = width as i32 - margin;
+    let mut cursor = right;
+    let mut y = margin;
+
+    indicators.active().map(move |indicator| {
+        let width = indicator.cells() * CHAR_ADVANCE * scale;
+        let mut x = cursor - width as i32;
+        if x < margin && cursor != right {
+            y += height as i32 + margin;
+            x = right - width as i32;
+        }
+        cursor = x - gap;
+        Placement { indicator, x, y, width, height }
+    })
+}
+
+/// Draws the active `indicators` where [`layout`] puts them, each on a
+/// black box so it reads over any display.
+pub fn draw_indicators(buf: &mut [u32], stride: usize, indicators: Indicators, scale: usize) {
+    for Placement { indicator, x, y, width, height } in layout(indicators, stride, scale) {
+        fill_rect(buf, stride, x, y, width, height, 0x000000);
+        let color = indicator.color();
+        draw_glyph(buf, stride, x, y, indicator.icon(), color, scale);
+        draw_text(buf, stride, x + (CHAR_ADVANCE * scale) as i32, y, indicator.label(), color, None, scale);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn boxes(indicators: Indicators, width: usize, scale: usize) -> ([(Indicator, i32, i32, usize); 4], usize) {
+        let mut out = [(Indicator::Paused, 0, 0, 0); 4];
+        let mut count = 0;
+        for (slot, placement) in out.iter_mut().zip(layout(indicators, width, scale)) {
+            *slot = (placement.indicator, placement.x, placement.y, placement.width);
+            count += 1;
+        }
+        (out, count)
+    }
+
+    const ALL_ON: Indicators = Indicators { paused: true, turbo: true, recording: true, muted: true };
+
+    #[test]
+    fn nothing_active_places_nothing() {
+        assert!(Indicators::default().is_empty());
+        assert_eq!(layout(Indicators::default(), 640, 2).count(), 0);
+    }
+
+    #[test]
+    fn indicators_run_right_to_left_a_cell_apart() {
+        let (placed, count) = boxes(ALL_ON, 640, 2);
+        assert_eq!(count, 4);
+        // Cells are 12 pixels at scale 2, with a 2 pixel margin.
+        assert_eq!(placed[0], (Indicator::Paused, 640 - 2 - 7 * 12, 2, 7 * 12));
+        assert_eq!(placed[1], (Indicator::Turbo, 554 - 12 - 12, 2, 12));
+        assert_eq!(placed[2], (Indicator::Recording, 530 - 12 - 12, 2, 12));
+        assert_eq!(placed[3], (Indicator::Muted, 506 - 12 - 24, 2, 24));
+        assert!(layout(ALL_ON, 640, 2).all(|placement| placement.height == 16));
+    }
+
+    #[test]
+    fn only_active_indicators_take_space() {
+        let (placed, count) = boxes(Indicators { turbo: true, muted: true, ..Default::default() }, 100, 1);
+        assert_eq!(count, 2);
+        assert_eq!(placed[0], (Indicator::Turbo, 100 - 1 - 6, 1, 6));
+        assert_eq!(placed[1], (Indicator::Muted, 93 - 6 - 12, 1, 12));
+    }
+
+    #[test]
+    fn indicators_that_do_not_fit_start_a_new_row() {
+        // Room for PAUSED and the chevron on the first row only.
+        let (placed, count) = boxes(ALL_ON, 64, 1);
+        assert_eq!(count, 4);
+        assert_eq!(placed[0], (Indicator::Paused, 63 - 42, 1, 42));
+        assert_eq!(placed[1], (Indicator::Turbo, 21 - 6 - 6, 1, 6));
+        assert_eq!(placed[2], (Indicator::Recording, 63 - 6, 10, 6));
+        assert_eq!(placed[3], (Indicator::Muted, 57 - 6 - 12, 10, 12));
+    }
+
+    #[test]
+    fn draws_the_icon_in_its_color_on_black() {
+        let mut buf = [0x123456u32; 8 * 10];
+        draw_indicators(&mut buf, 8, Indicators { recording: true, ..Default::default() }, 1);
+        // The box spans x 1..7 and y 1..9.
+        assert_eq!(buf[0], 0x123456);
+        assert_eq!(buf[8 + 1], 0x000000);
+        // The dot's widest row, then the cell's blank column.
+        let row = (1 + 3) * 8 + 1;
+        assert_eq!(buf[row..row + 6], [0xFF3333, 0xFF3333, 0xFF3333, 0xFF3333, 0xFF3333, 0x000000]);
+        assert_eq!(buf[9 * 8 + 1], 0x123456);
+    }
+}
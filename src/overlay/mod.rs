@@ -0,0 +1,244 @@
+//! Text and shape drawing into 0RGB `u32` framebuffers, for frontends that
+//! need to put messages on screen.
+//!
+//! All drawing clips against the buffer, so callers can pass coordinates that
+//! lie partially or entirely outside of it.
+
+mod font;
+pub mod indicators;
+
+#[cfg(feature = "alloc")]
+use alloc::{string::String, vec::Vec};
+
+pub use font::{GLYPH_HEIGHT, GLYPH_WIDTH, glyph};
+
+/// Horizontal distance between the starts of two characters, unscaled.
+pub const CHAR_ADVANCE: usize = GLYPH_WIDTH + 1;
+/// Vertical distance between the tops of two lines, unscaled.
+pub const LINE_HEIGHT: usize = GLYPH_HEIGHT + 1;
+
+/// Size in pixels of `text` drawn at `scale`, with `\n` starting a new line.
+pub fn measure_text(text: &str, scale: usize) -> (usize, usize) {
+    let lines = text.split('\n');
+    let columns = lines.clone().map(|line| line.chars().count()).max().unwrap_or(0);
+    let rows = lines.count();
+
+    (columns * CHAR_ADVANCE * scale, rows * LINE_HEIGHT * scale)
+}
+
+/// Draws `text` with its top-left corner at (`x`, `y`). Each character cell,
+/// including the spacing around the glyph, is filled with `bg` if given.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_text(
+    buf: &mut [u32],
+    stride: usize,
+    x: i32,
+    y: i32,
+    text: &str,
+    fg: u32,
+    bg: Option<u32>,
+    scale: usize,
+) {
+    let advance = (CHAR_ADVANCE * scale) as i32;
+    let line_height = (LINE_HEIGHT * scale) as i32;
+
+    for (line_index, line) in text.split('\n').enumerate() {
+        let top = y + line_index as i32 * line_height;
+
+        for (column, c) in line.chars().enumerate() {
+            let left = x + column as i32 * advance;
+
+            if let Some(bg) = bg {
+                fill_rect(buf, stride, left, top, CHAR_ADVANCE * scale, LINE_HEIGHT * scale, bg);
+            }
+
+            draw_glyph(buf, stride, left, top, glyph(c), fg, scale);
+        }
+    }
+}
+
+/// Draws one glyph's lit pixels with its top-left corner at (`x`, `y`).
+fn draw_glyph(buf: &mut [u32], stride: usize, x: i32, y: i32, bits: &[u8; GLYPH_HEIGHT], fg: u32, scale: usize) {
+    for (row, bits) in bits.iter().enumerate() {
+        for col in 0..GLYPH_WIDTH {
+            if bits & (1 << (GLYPH_WIDTH - 1 - col)) != 0 {
+                fill_rect(buf, stride, x + (col * scale) as i32, y + (row * scale) as i32, scale, scale, fg);
+            }
+        }
+    }
+}
+
+/// Fills a `w` x `h` rectangle with its top-left corner at (`x`, `y`).
+pub fn fill_rect(buf: &mut [u32], stride: usize, x: i32, y: i32, w: usize, h: usize, color: u32) {
+    if stride == 0 {
+        return;
+    }
+    let height = buf.len() / stride;
+
+    let x0 = x.max(0) as usize;
+    let y0 = y.max(0) as usize;
+    let x1 = (x + w as i32).clamp(0, stride as i32) as usize;
+    let y1 = (y + h as i32).clamp(0, height as i32) as usize;
+
+    for row in y0..y1 {
+        if x0 < x1 {
+            buf[row * stride + x0..row * stride + x1].fill(color);
+        }
+    }
+}
+
+/// Outlines a `w` x `h` rectangle with its top-left corner at (`x`, `y`),
+/// one pixel thick and inside those bounds.
+pub fn draw_box(buf: &mut [u32], stride: usize, x: i32, y: i32, w: usize, h: usize, color: u32) {
+    if w == 0 || h == 0 {
+        return;
+    }
+    let right = x + w as i32 - 1;
+    let bottom = y + h as i32 - 1;
+    fill_rect(buf, stride, x, y, w, 1, color);
+    fill_rect(buf, stride, x, bottom, w, 1, color);
+    fill_rect(buf, stride, x, y, 1, h, color);
+    fill_rect(buf, stride, right, y, 1, h, color);
+}
+
+/// Splits `text` into lines of at most `max_columns` characters, breaking at
+/// spaces where possible and keeping explicit line breaks.
+#[cfg(feature = "alloc")]
+pub fn wrap_text(text: &str, max_columns: usize) -> Vec<String> {
+    let max_columns = max_columns.max(1);
+    let mut lines = Vec::new();
+
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        let mut line_len = 0;
+
+        for word in paragraph.split_whitespace() {
+            let mut word: Vec<char> = word.chars().collect();
+
+            if line_len > 0 && line_len + 1 + word.len() > max_columns {
+                lines.push(core::mem::take(&mut line));
+                line_len = 0;
+            }
+
+            // Words longer than a whole line are broken wherever they overflow.
+            while word.len() > max_columns {
+                if line_len > 0 {
+                    lines.push(core::mem::take(&mut line));
+                    line_len = 0;
+                }
+                lines.push(word.drain(..max_columns).collect());
+            }
+
+            if line_len > 0 {
+                line.push(' ');
+                line_len += 1;
+            }
+            line.extend(word.iter());
+            line_len += word.len();
+        }
+
+        lines.push(line);
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn wraps_at_word_boundaries() {
+        assert_eq!(
+            wrap_text("failed to load the ROM file", 10),
+            vec!["failed to", "load the", "ROM file"]
+        );
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn breaks_words_longer_than_a_line() {
+        assert_eq!(
+            wrap_text("see /very/long/path.ch8 now", 8),
+            vec!["see", "/very/lo", "ng/path.", "ch8 now"]
+        );
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn keeps_explicit_line_breaks_and_blank_lines() {
+        assert_eq!(wrap_text("one\n\ntwo three", 20), vec!["one", "", "two three"]);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn exact_fit_stays_on_one_line() {
+        assert_eq!(wrap_text("ab cd", 5), vec!["ab cd"]);
+        assert_eq!(wrap_text("ab cde", 5), vec!["ab", "cde"]);
+    }
+
+    #[test]
+    fn measures_longest_line_in_character_cells() {
+        assert_eq!(measure_text("abc\nde", 2), (3 * CHAR_ADVANCE * 2, 2 * LINE_HEIGHT * 2));
+        assert_eq!(measure_text("", 1), (0, LINE_HEIGHT));
+    }
+
+    #[test]
+    fn draws_glyph_pixels() {
+        let mut buf = vec![0u32; 6 * 8];
+        draw_text(&mut buf, 6, 0, 0, "T", 1, None, 1);
+
+        let rows: Vec<String> = buf
+            .chunks(6)
+            .map(|row| row.iter().map(|&p| if p == 1 { '#' } else { '.' }).collect())
+            .collect();
+        assert_eq!(
+            rows,
+            vec!["#####.", "..#...", "..#...", "..#...", "..#...", "..#...", "..#...", "......"]
+        );
+    }
+
+    #[test]
+    fn text_is_clipped_at_buffer_edges() {
+        let mut buf = vec![0u32; 4 * 4];
+        draw_text(&mut buf, 4, -2, -3, "T", 1, Some(2), 1);
+
+        // Only the stem of the "T" and the cell spacing to its right remain.
+        assert_eq!(buf, [1, 2, 2, 2].repeat(4));
+
+        let mut buf = vec![0u32; 4 * 4];
+        draw_text(&mut buf, 4, 3, 2, "T", 1, None, 2);
+        assert_eq!(&buf[8..], &[0, 0, 0, 1, 0, 0, 0, 1]);
+    }
+
+    fn picture(buf: &[u32], stride: usize) -> Vec<String> {
+        buf.chunks(stride)
+            .map(|row| row.iter().map(|&p| if p == 1 { '#' } else { '.' }).collect())
+            .collect()
+    }
+
+    #[test]
+    fn rectangles_fill_and_outline() {
+        let mut buf = vec![0u32; 6 * 5];
+        fill_rect(&mut buf, 6, 1, 1, 2, 2, 1);
+        draw_box(&mut buf, 6, 3, 0, 3, 4, 1);
+        assert_eq!(picture(&buf, 6), vec!["...###", ".###.#", ".###.#", "...###", "......"]);
+    }
+
+    #[test]
+    fn rectangles_are_clipped_at_buffer_edges() {
+        let mut buf = vec![0u32; 4 * 3];
+        // Only the top and right sides of the box are on screen.
+        draw_box(&mut buf, 4, -1, 1, 3, 5, 1);
+        fill_rect(&mut buf, 4, 3, -10, 100, 11, 1);
+        assert_eq!(picture(&buf, 4), vec!["...#", "##..", ".#.."]);
+
+        // Nothing at all, or nothing on screen, draws nothing.
+        let mut buf = vec![0u32; 4 * 3];
+        draw_box(&mut buf, 4, 0, 0, 0, 3, 1);
+        draw_box(&mut buf, 4, 4, 3, 2, 2, 1);
+        fill_rect(&mut buf, 0, 0, 0, 4, 4, 1);
+        assert!(buf.iter().all(|&p| p == 0));
+    }
+}
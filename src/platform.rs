@@ -0,0 +1,175 @@
+//! Recognizing ROMs written for CHIP-8 extensions the interpreter doesn't
+//! support, so they can be turned away with one clear message instead of a
+//! stream of unknown opcodes and a frozen display.
+//!
+//! Only opcodes [`decode`] rejects are classified: anything the interpreter
+//! runs is taken to be plain CHIP-8, even where an extension gives the same
+//! opcode another meaning.
+
+use core::fmt;
+
+use crate::error::Chip8Error;
+use crate::instruction::decode;
+
+/// Instructions [`prescan`] looks at from the start of a ROM. Extensions
+/// announce themselves early, MegaChip ROMs with 0011 to switch the mode on,
+/// and this stays clear of the data most ROMs keep after their code.
+pub const PRESCAN_INSTRUCTIONS: usize = 16;
+
+/// A CHIP-8 extension the interpreter doesn't support.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    MegaChip,
+    Chip8X,
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Platform::MegaChip => "MegaChip",
+            Platform::Chip8X => "CHIP-8X",
+        })
+    }
+}
+
+/// Which extension opcodes belong to, as `(platform, mask, value,
+/// conclusive)`: an opcode matches when `opcode & mask == value`. The first
+/// match wins, so CHIP-8X's 02A0 goes before MegaChip's 02NN.
+///
+/// Conclusive opcodes mean nothing to a plain CHIP-8 interpreter, so a ROM
+/// running one must be for the extension. The others are also 0NNN machine
+/// code calls, which plenty of CHIP-8 ROMs have and which have always been
+/// skipped, so they only say which extension an opcode would belong to.
+const EXTENSIONS: [(Platform, u16, u16, bool); 11] = [
+    // 02A0 steps the background color.
+    (Platform::Chip8X, 0xFFFF, 0x02A0, false),
+    // 5XY1 adds packed nibbles.
+    (Platform::Chip8X, 0xF00F, 0x5001, true),
+    // EXF2 and EXF5 read the second keypad.
+    (Platform::Chip8X, 0xF0FF, 0xE0F2, true),
+    (Platform::Chip8X, 0xF0FF, 0xE0F5, true),
+    // FXF8 and FXFB use the I/O port.
+    (Platform::Chip8X, 0xF0FF, 0xF0F8, true),
+    (Platform::Chip8X, 0xF0FF, 0xF0FB, true),
+    // 0010 and 0011 leave and enter MegaChip mode...
+    (Platform::MegaChip, 0xFFFE, 0x0010, true),
+    // ...and in it, 01NN sets a long I, 02NN-07NN load the palette, size
+    // sprites, set the alpha and play and stop sounds, and 08NN and 09NN set
+    // the blend mode and collision color.
+    (Platform::MegaChip, 0xFF00, 0x0100, false),
+    (Platform::MegaChip, 0xFE00, 0x0200, false),
+    (Platform::MegaChip, 0xFC00, 0x0400, false),
+    (Platform::MegaChip, 0xFE00, 0x0800, false),
+];
+
+fn lookup(opcode: u16) -> Option<(Platform, bool)> {
+    if decode(opcode).is_some() {
+        return None;
+    }
+    EXTENSIONS
+        .iter()
+        .find(|&&(_, mask, value, _)| opcode & mask == value)
+        .map(|&(platform, _, _, conclusive)| (platform, conclusive))
+}
+
+/// The extension `opcode` belongs to, if the interpreter can't run it and
+/// it's one an extension defines.
+pub fn classify(opcode: u16) -> Option<Platform> {
+    lookup(opcode).map(|(platform, _)| platform)
+}
+
+/// The extension a ROM running `opcode` must be for, if it's one no plain
+/// CHIP-8 ROM would run.
+pub fn identify(opcode: u16) -> Option<Platform> {
+    lookup(opcode).filter(|&(_, conclusive)| conclusive).map(|(platform, _)| platform)
+}
+
+/// An opcode found in a ROM that identifies an extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Detection {
+    pub platform: Platform,
+    pub opcode: u16,
+    /// Where the opcode is.
+    pub addr: u16,
+}
+
+/// What running into the opcode would have stopped the machine with.
+impl From<Detection> for Chip8Error {
+    fn from(detection: Detection) -> Self {
+        Chip8Error::UnsupportedPlatform {
+            platform: detection.platform,
+            opcode: detection.opcode,
+            pc: detection.addr,
+        }
+    }
+}
+
+/// The first opcode among the first [`PRESCAN_INSTRUCTIONS`] instructions
+/// of `rom`, loaded at `start`, that [identifies](identify) an extension.
+pub fn prescan(rom: &[u8], start: u16) -> Option<Detection> {
+    rom.chunks_exact(2).take(PRESCAN_INSTRUCTIONS).enumerate().find_map(|(index, pair)| {
+        let opcode = u16::from_be_bytes([pair[0], pair[1]]);
+        identify(opcode).map(|platform| Detection {
+            platform,
+            opcode,
+            addr: start + 2 * index as u16,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn megachip_opcodes() {
+        for opcode in [0x0010, 0x0011, 0x0100, 0x01FF, 0x0203, 0x0408, 0x0601, 0x0700, 0x0801, 0x09FF] {
+            assert_eq!(classify(opcode), Some(Platform::MegaChip), "{:04X}", opcode);
+        }
+        // Not MegaChip's, and not anything else's either.
+        for opcode in [0x0000, 0x0012, 0x00AB, 0x0A00, 0x0FFF] {
+            assert_eq!(classify(opcode), None, "{:04X}", opcode);
+        }
+    }
+
+    #[test]
+    fn chip8x_opcodes() {
+        for opcode in [0x02A0, 0x5121, 0xE1F2, 0xEAF5, 0xF3F8, 0xF0FB] {
+            assert_eq!(classify(opcode), Some(Platform::Chip8X), "{:04X}", opcode);
+        }
+    }
+
+    #[test]
+    fn only_opcodes_nothing_else_uses_identify_a_platform() {
+        assert_eq!(identify(0x0011), Some(Platform::MegaChip));
+        assert_eq!(identify(0xE1F2), Some(Platform::Chip8X));
+        assert_eq!(identify(0x5121), Some(Platform::Chip8X));
+        // Machine code calls on anything else.
+        assert_eq!(identify(0x0203), None);
+        assert_eq!(identify(0x02A0), None);
+    }
+
+    #[test]
+    fn opcodes_the_interpreter_runs_are_chip8() {
+        // 00E0, 5XY0, BNNN and EX9E all run.
+        for opcode in [0x00E0, 0x00EE, 0x5120, 0xB123, 0xE19E, 0xF065] {
+            assert_eq!(classify(opcode), None, "{:04X}", opcode);
+        }
+    }
+
+    #[test]
+    fn the_prescan_reports_the_first_extension_opcode() {
+        // 200: 00E0  clear
+        // 202: 0203  load the palette, or call 203
+        // 204: 0011  MegaChip on
+        let rom = [0x00, 0xE0, 0x02, 0x03, 0x00, 0x11];
+        assert_eq!(prescan(&rom, 0x200), Some(Detection { platform: Platform::MegaChip, opcode: 0x0011, addr: 0x204 }));
+        assert_eq!(prescan(&[0x00, 0xE0, 0x12, 0x00], 0x200), None);
+
+        // Past the first instructions is taken to be data.
+        let mut rom = [0x00, 0xE0].repeat(PRESCAN_INSTRUCTIONS);
+        rom.extend([0xF0, 0xF8]);
+        assert_eq!(identify(0xF0F8), Some(Platform::Chip8X));
+        assert_eq!(prescan(&rom, 0x200), None);
+    }
+}
@@ -0,0 +1,429 @@
+//! Printing machines: a compact `Debug`, a multi-line `Display` for people,
+//! and the display as ASCII art or a PBM image.
+
+use core::fmt;
+
+use crate::chip8::Chip8;
+use crate::display::Display;
+
+/// The display as a line of `#` (lit) and `.` (unlit) per row.
+#[derive(Clone, Copy)]
+pub struct Screen<'a>(pub &'a Display);
+
+impl fmt::Display for Screen<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Ascii::new(self.0.pixels(), self.0.width(), self.0.height(), Glyphs::default()).fmt(f)
+    }
+}
+
+/// The display as a plain (`P1`) PBM image, a row of `1` (lit) and `0`
+/// (unlit) digits per line. PBM viewers draw the lit pixels black.
+#[derive(Clone, Copy)]
+pub struct Pbm<'a>(pub &'a Display);
+
+impl fmt::Display for Pbm<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (width, height) = (self.0.width(), self.0.height());
+        write!(f, "P1\n{} {}", width, height)?;
+        for row in self.0.pixels().chunks(width) {
+            f.write_str("\n")?;
+            for &lit in row {
+                f.write_str(if lit { "1" } else { "0" })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How [`Ascii`] draws pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Glyphs {
+    /// A character for each pixel.
+    Chars { lit: char, unlit: char },
+    /// A half block character for each two rows of pixels, so a 128x64
+    /// hires display fits a terminal.
+    HalfBlocks,
+}
+
+/// `#` and `.`, as the `txt` dump has always printed.
+impl Default for Glyphs {
+    fn default() -> Self {
+        Glyphs::Chars { lit: '#', unlit: '.' }
+    }
+}
+
+impl core::str::FromStr for Glyphs {
+    type Err = &'static str;
+
+    /// `half`, or two characters: the lit one, then the unlit one.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "half" {
+            return Ok(Glyphs::HalfBlocks);
+        }
+        let mut chars = s.chars();
+        match (chars.next(), chars.next(), chars.next()) {
+            (Some(lit), Some(unlit), None) if lit != unlit => Ok(Glyphs::Chars { lit, unlit }),
+            _ => Err("expected half, or two different characters for lit and unlit"),
+        }
+    }
+}
+
+/// A `width` x `height` display of row-major pixels as lines of text, with
+/// no newline after the last.
+#[derive(Clone, Copy)]
+pub struct Ascii<'a> {
+    pixels: &'a [bool],
+    width: usize,
+    height: usize,
+    glyphs: Glyphs,
+}
+
+impl<'a> Ascii<'a> {
+    /// Panics if `pixels` isn't `width * height` long.
+    pub fn new(pixels: &'a [bool], width: usize, height: usize, glyphs: Glyphs) -> Self {
+        assert_eq!(pixels.len(), width * height, "a {}x{} display", width, height);
+        Self { pixels, width, height, glyphs }
+    }
+
+    /// How many characters wide each line is.
+    pub fn columns(&self) -> usize {
+        self.width
+    }
+
+    /// How many lines there are: one per row of pixels, or per two with
+    /// half blocks.
+    pub fn rows(&self) -> usize {
+        match self.glyphs {
+            Glyphs::Chars { .. } => self.height,
+            Glyphs::HalfBlocks => self.height.div_ceil(2),
+        }
+    }
+
+    fn lit(&self, x: usize, y: usize) -> bool {
+        y < self.height && self.pixels[y * self.width + x]
+    }
+
+    /// Writes line `row`.
+    fn line(&self, f: &mut fmt::Formatter<'_>, row: usize) -> fmt::Result {
+        for x in 0..self.width {
+            let ch = match self.glyphs {
+                Glyphs::Chars { lit, unlit } => if self.lit(x, row) { lit } else { unlit },
+                Glyphs::HalfBlocks => match (self.lit(x, 2 * row), self.lit(x, 2 * row + 1)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                },
+            };
+            fmt::Write::write_char(f, ch)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Ascii<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in 0..self.rows() {
+            if row > 0 {
+                writeln!(f)?;
+            }
+            self.line(f, row)?;
+        }
+        Ok(())
+    }
+}
+
+/// [`Ascii`] framed in a border with the frame number in the top edge, for
+/// pasting a screenshot as text:
+///
+/// ```text
+/// +- frame 120 ----------+
+/// |......................|
+/// +----------------------+
+/// ```
+///
+/// The output depends only on the pixels, the glyphs and the frame number.
+#[derive(Clone, Copy)]
+pub struct Snapshot<'a> {
+    pub art: Ascii<'a>,
+    pub frame: u64,
+}
+
+impl fmt::Display for Snapshot<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let columns = self.art.columns();
+        let digits = self.frame.checked_ilog10().map_or(1, |log| log as usize + 1);
+        // "- frame N ", then dashes to the corner.
+        write!(f, "+- frame {} ", self.frame)?;
+        for _ in "- frame  ".len() + digits..columns {
+            f.write_str("-")?;
+        }
+        writeln!(f, "+")?;
+        for row in 0..self.art.rows() {
+            f.write_str("|")?;
+            self.art.line(f, row)?;
+            writeln!(f, "|")?;
+        }
+        f.write_str("+")?;
+        for _ in 0..columns {
+            f.write_str("-")?;
+        }
+        f.write_str("+")
+    }
+}
+
+impl<R> Chip8<R> {
+    /// The display, for printing with [`Screen`]'s `Display`.
+    pub fn screen(&self) -> Screen<'_> {
+        Screen(&self.display)
+    }
+
+    /// The display, for printing with [`Pbm`]'s `Display`.
+    pub fn pbm(&self) -> Pbm<'_> {
+        Pbm(&self.display)
+    }
+}
+
+/// One line, leaving out memory and the display's pixels.
+impl<R> fmt::Debug for Chip8<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lit = self.display.lit();
+        write!(
+            f,
+            "Chip8 {{ pc: 0x{:03X}, i: 0x{:03X}, v: {:02X?}, stack: {:03X?}, dt: {}, st: {}, lit: {}, quirks: {:?} }}",
+            self.pc,
+            self.i,
+            self.v,
+            self.stack.as_slice(),
+            self.delay_timer,
+            self.sound_timer,
+            lit,
+            self.quirks
+        )
+    }
+}
+
+/// Registers in a grid, the stack, held keys and the display.
+impl<R> fmt::Display for Chip8<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "PC 0x{:03X}  I 0x{:03X}  DT {:3}  ST {:3}",
+            self.pc, self.i, self.delay_timer, self.sound_timer
+        )?;
+        for (row, registers) in self.v.chunks(8).enumerate() {
+            for (column, value) in registers.iter().enumerate() {
+                if column > 0 {
+                    f.write_str("  ")?;
+                }
+                write!(f, "V{:X} {:02X}", row * 8 + column, value)?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "stack: {:03X?}", self.stack.as_slice())?;
+        f.write_str("keys:")?;
+        if !self.keys.contains(&true) {
+            f.write_str(" none")?;
+        }
+        for (key, _) in self.keys.iter().enumerate().filter(|(_, held)| **held) {
+            write!(f, " {:X}", key)?;
+        }
+        writeln!(f)?;
+        write!(f, "{}", self.screen())
+    }
+}
+
+/// Compares everything that affects execution: memory, registers, stack,
+/// timers, display, audio pattern and pitch, CHIP-8X colors, keys, quirks
+/// and the random generator. The decode cache
+/// and the record of what ROM was loaded are left out.
+impl<R: PartialEq> PartialEq for Chip8<R> {
+    fn eq(&self, other: &Self) -> bool {
+        self.memory == other.memory
+            && self.v == other.v
+            && self.i == other.i
+            && self.pc == other.pc
+            && self.stack == other.stack
+            && self.delay_timer == other.delay_timer
+            && self.sound_timer == other.sound_timer
+            && self.display == other.display
+            && self.audio_pattern == other.audio_pattern
+            && self.pitch == other.pitch
+            && self.color_map == other.color_map
+            && self.keys == other.keys
+            && self.quirks == other.quirks
+            && self.rng == other.rng
+    }
+}
+
+impl<R: Eq> Eq for Chip8<R> {}
+
+/// Asserts that two machines are equal, as [`assert_eq!`] does, but on
+/// failure prints what differs as a [`StateDiff`](crate::StateDiff) ahead of
+/// both machines in full.
+#[cfg(feature = "alloc")]
+#[macro_export]
+macro_rules! assert_chip8_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if left != right {
+                    panic!(
+                        "machines differ:\n{}\n\nleft:\n{}\n\nright:\n{}",
+                        left.diff(right),
+                        left,
+                        right
+                    );
+                }
+            }
+        }
+    };
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use alloc::format;
+    use alloc::string::ToString;
+
+    use crate::rng::Rng;
+
+    use super::*;
+
+    fn machine() -> Chip8 {
+        Chip8::with_rng(Rng::new(3))
+    }
+
+    #[test]
+    fn screens_are_32_lines_of_64() {
+        let mut chip8 = machine();
+        chip8.display[0][0] = true;
+        chip8.display[31][63] = true;
+        let text = chip8.screen().to_string();
+        let lines: alloc::vec::Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 32);
+        assert!(lines.iter().all(|line| line.len() == 64));
+        assert!(lines[0].starts_with("#."));
+        assert!(lines[31].ends_with(".#"));
+        assert!(!text.ends_with('\n'));
+    }
+
+    #[test]
+    fn pbms_have_a_header_and_a_line_per_row() {
+        let mut chip8 = machine();
+        chip8.display[0][1] = true;
+        let text = chip8.pbm().to_string();
+        let lines: alloc::vec::Vec<_> = text.lines().collect();
+        assert_eq!(lines[..2], ["P1", "64 32"]);
+        assert_eq!(lines.len(), 2 + 32);
+        assert!(lines[2].starts_with("010"));
+        assert!(lines[2..].iter().all(|line| line.len() == 64));
+    }
+
+    /// A `width` x `height` display with the pixels at `lit` on.
+    fn display(width: usize, height: usize, lit: &[(usize, usize)]) -> alloc::vec::Vec<bool> {
+        let mut pixels = alloc::vec![false; width * height];
+        for &(x, y) in lit {
+            pixels[y * width + x] = true;
+        }
+        pixels
+    }
+
+    #[test]
+    fn snapshots_are_framed_with_the_frame_number() {
+        let pixels = display(12, 3, &[(0, 0), (11, 0), (5, 1), (6, 2)]);
+        let art = Ascii::new(&pixels, 12, 3, Glyphs::default());
+        assert_eq!(
+            Snapshot { art, frame: 42 }.to_string(),
+            "+- frame 42 -+\n\
+             |#..........#|\n\
+             |.....#......|\n\
+             |......#.....|\n\
+             +------------+"
+        );
+
+        let glyphs = "@ ".parse().unwrap();
+        let art = Ascii::new(&pixels, 12, 3, glyphs);
+        assert_eq!(art.to_string(), "@          @\n     @      \n      @     ");
+        assert!("#".parse::<Glyphs>().is_err());
+        assert!("##".parse::<Glyphs>().is_err());
+    }
+
+    #[test]
+    fn half_blocks_fit_two_rows_in_a_line() {
+        // An odd height leaves the last line's bottom halves unlit.
+        let pixels = display(12, 3, &[(0, 0), (1, 1), (2, 0), (2, 1), (3, 2)]);
+        let art = Ascii::new(&pixels, 12, 3, Glyphs::HalfBlocks);
+        assert_eq!(art.rows(), 2);
+        assert_eq!(art.to_string(), "▀▄█         \n   ▀        ");
+
+        // A hires display.
+        let pixels = display(128, 64, &[(0, 0), (127, 63)]);
+        let text = Snapshot { art: Ascii::new(&pixels, 128, 64, Glyphs::HalfBlocks), frame: 7 }.to_string();
+        let lines: alloc::vec::Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 34);
+        assert!(lines.iter().all(|line| line.chars().count() == 130));
+        assert!(lines[0].starts_with("+- frame 7 ---"));
+        assert!(lines[1].starts_with("|▀ "));
+        assert!(lines[32].ends_with(" ▄|"));
+    }
+
+    #[test]
+    fn the_snapshot_of_a_machine_matches_its_screen() {
+        let mut chip8 = machine();
+        chip8.display[3][9] = true;
+        let art = Ascii::new(chip8.display.pixels(), 64, 32, Glyphs::default());
+        let text = Snapshot { art, frame: 0 }.to_string();
+        let inner: alloc::vec::Vec<_> = text.lines().skip(1).take(32).map(|line| &line[1..65]).collect();
+        assert_eq!(inner.join("\n"), chip8.screen().to_string());
+        assert_eq!(text.lines().next().unwrap().len(), 66);
+    }
+
+    #[test]
+    fn debug_is_one_line() {
+        let mut chip8 = machine();
+        chip8.v[0xA] = 0x2A;
+        assert!(chip8.stack.push(0x202));
+        chip8.display[1][1] = true;
+        assert_eq!(
+            format!("{:?}", chip8),
+            "Chip8 { pc: 0x200, i: 0x000, v: [00, 00, 00, 00, 00, 00, 00, 00, 00, 00, 2A, 00, 00, 00, 00, 00], \
+             stack: [202], dt: 0, st: 0, lit: 1, quirks: Quirks { shift_uses_vy: false, i_overflow_sets_vf: false, load_store_increments_i: false, wrap_sprites: false, display_wait: false, vf_reset: false, jump_uses_vx: false, half_scroll: false, extended_memory: false, load_store_increments_i_by_x: false, two_page_hires: false, chip8x: false } }"
+        );
+    }
+
+    #[test]
+    fn equality_covers_the_whole_machine() {
+        assert_eq!(machine(), machine());
+        let edits: [fn(&mut Chip8); 6] = [
+            |c| c.memory[0xFFF] = 1,
+            |c| c.display[31][63] = true,
+            |c| c.keys[0xF] = true,
+            |c| c.quirks.shift_uses_vy = true,
+            |c| assert!(c.stack.push(0x200)),
+            |c| c.rng = Rng::new(4),
+        ];
+        for edit in edits {
+            let mut other = machine();
+            edit(&mut other);
+            assert_ne!(machine(), other);
+        }
+
+        // The decode cache is bookkeeping, not state.
+        let mut cached = machine();
+        cached.set_decode_cache(true);
+        assert_eq!(machine(), cached);
+    }
+
+    #[test]
+    #[should_panic(expected = "machines differ:\nV3: 0x00 -> 0x07\n\nleft:\nPC 0x200")]
+    fn assert_helper_leads_with_the_diff() {
+        let mut right = machine();
+        right.v[3] = 7;
+        assert_chip8_eq!(machine(), right);
+    }
+
+    #[test]
+    fn assert_helper_passes_equal_machines() {
+        assert_chip8_eq!(machine(), machine());
+    }
+}
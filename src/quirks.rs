@@ -0,0 +1,190 @@
+/// Behavioral differences between CHIP-8 interpreters.
+///
+/// The default matches what this emulator has always done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Quirks {
+    /// 8XY6/8XYE copy VY into VX before shifting, as on the COSMAC VIP,
+    /// instead of shifting VX in place.
+    pub shift_uses_vy: bool,
+    /// FX1E sets VF to 1 when I goes past the top of memory and to 0
+    /// otherwise, as the Amiga interpreter did and Spacefight 2091!
+    /// expects. I wraps either way.
+    pub i_overflow_sets_vf: bool,
+    /// FX55/FX65 leave I pointing past the last register copied, at
+    /// I + X + 1, as the COSMAC VIP did, instead of leaving it alone.
+    pub load_store_increments_i: bool,
+    /// DXYN wraps every pixel of a sprite around the screen edges, as this
+    /// emulator used to, instead of wrapping only the starting position and
+    /// clipping what runs off the right and bottom.
+    pub wrap_sprites: bool,
+    /// DXYN waits for the next frame, as the COSMAC VIP waited for the
+    /// vertical blank, so games draw at most one sprite a frame.
+    pub display_wait: bool,
+    /// 8XY1/8XY2/8XY3 clear VF after the result, as the COSMAC VIP's logic
+    /// routines did.
+    pub vf_reset: bool,
+    /// BXNN jumps to XNN plus VX rather than to NNN plus V0, as SUPER-CHIP did.
+    pub jump_uses_vx: bool,
+    /// 00CN, 00FB and 00FC scroll by half as many pixels in 64x32 mode,
+    /// rounded down, as SUPER-CHIP 1.1 did by scrolling its 128x64 screen
+    /// either way. Otherwise they scroll whole pixels at any resolution,
+    /// as modern interpreters do.
+    pub half_scroll: bool,
+    /// Memory grows to XO-CHIP's 64 KB, I and the addresses computed from it
+    /// wrap at 0xFFFF instead of 0xFFF, F000 NNNN can point I anywhere in
+    /// it, and skips step over all four of F000 NNNN's bytes.
+    pub extended_memory: bool,
+    /// FX55/FX65 leave I at I + X, on the last register copied, as CHIP-48
+    /// did. This wins over [`load_store_increments_i`](Self::load_store_increments_i).
+    pub load_store_increments_i_by_x: bool,
+    /// A 1260 at 0x200 switches to the COSMAC VIP's two-page 64x64
+    /// [resolution](crate::Resolution::TwoPage) and carries on at 0x2C0, and
+    /// 0230 clears the screen, as the patched interpreter HI-RES CHIP-8 ROMs
+    /// bring along did.
+    pub two_page_hires: bool,
+    /// CHIP-8X's instructions run: BXY0 and BXYN color the screen instead
+    /// of jumping, 02A0 steps the background color and 5XY1 adds packed
+    /// nibbles, all kept in the [`ColorMap`](crate::color_map::ColorMap).
+    /// EXF2 and EXF5 see a second keypad nobody presses, FXF8's tones and
+    /// FXFB's input go nowhere, and CHIP-8X ROMs still need a program start
+    /// of 0x300.
+    pub chip8x: bool,
+}
+
+impl Quirks {
+    /// Names accepted by [`Quirks::from_profile`].
+    pub const PROFILES: &'static [&'static str] = &["chip8", "hires-chip8", "chip8x", "chip48", "schip", "xochip"];
+
+    /// Names accepted by [`Quirks::flag_mut`], which are also the
+    /// command-line switches.
+    pub const NAMES: &'static [&'static str] = &[
+        "shift-uses-vy",
+        "fx1e-sets-vf",
+        "load-store-increments-i",
+        "wrap-sprites",
+        "display-wait",
+        "vf-reset",
+        "jump-uses-vx",
+        "half-scroll",
+        "extended-memory",
+        "load-store-increments-i-by-x",
+        "two-page-hires",
+        "chip8x",
+    ];
+
+    /// The original COSMAC VIP interpreter.
+    pub fn chip8() -> Self {
+        Self { shift_uses_vy: true, load_store_increments_i: true, display_wait: true, vf_reset: true, ..Self::default() }
+    }
+
+    /// The COSMAC VIP running a HI-RES CHIP-8 ROM.
+    pub fn hires_chip8() -> Self {
+        Self { two_page_hires: true, ..Self::chip8() }
+    }
+
+    /// The COSMAC VIP with the VP-590 color board, running CHIP-8X.
+    pub fn chip8x() -> Self {
+        Self { chip8x: true, ..Self::chip8() }
+    }
+
+    /// CHIP-48, SUPER-CHIP's predecessor on the HP48, for the calculator
+    /// games written before SUPER-CHIP.
+    pub fn chip48() -> Self {
+        Self { jump_uses_vx: true, load_store_increments_i_by_x: true, ..Self::default() }
+    }
+
+    /// SUPER-CHIP 1.1.
+    pub fn schip() -> Self {
+        Self { jump_uses_vx: true, half_scroll: true, ..Self::default() }
+    }
+
+    /// XO-CHIP, as Octo runs it.
+    pub fn xochip() -> Self {
+        Self {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            wrap_sprites: true,
+            extended_memory: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn from_profile(name: &str) -> Option<Self> {
+        match name {
+            "chip8" => Some(Self::chip8()),
+            "hires-chip8" => Some(Self::hires_chip8()),
+            "chip8x" => Some(Self::chip8x()),
+            "chip48" => Some(Self::chip48()),
+            "schip" => Some(Self::schip()),
+            "xochip" => Some(Self::xochip()),
+            _ => None,
+        }
+    }
+
+    /// The quirk called `name`, one of [`Quirks::NAMES`].
+    pub fn flag_mut(&mut self, name: &str) -> Option<&mut bool> {
+        Some(match name {
+            "shift-uses-vy" => &mut self.shift_uses_vy,
+            "fx1e-sets-vf" => &mut self.i_overflow_sets_vf,
+            "load-store-increments-i" => &mut self.load_store_increments_i,
+            "wrap-sprites" => &mut self.wrap_sprites,
+            "display-wait" => &mut self.display_wait,
+            "vf-reset" => &mut self.vf_reset,
+            "jump-uses-vx" => &mut self.jump_uses_vx,
+            "half-scroll" => &mut self.half_scroll,
+            "extended-memory" => &mut self.extended_memory,
+            "load-store-increments-i-by-x" => &mut self.load_store_increments_i_by_x,
+            "two-page-hires" => &mut self.two_page_hires,
+            "chip8x" => &mut self.chip8x,
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_set_their_quirks() {
+        // shift, fx1e, load/store, wrap, display wait, vf reset, jump,
+        // half scroll, extended memory, load/store by X, two-page hires,
+        // CHIP-8X
+        let table = [
+            ("chip8", [true, false, true, false, true, true, false, false, false, false, false, false]),
+            ("hires-chip8", [true, false, true, false, true, true, false, false, false, false, true, false]),
+            ("chip8x", [true, false, true, false, true, true, false, false, false, false, false, true]),
+            ("chip48", [false, false, false, false, false, false, true, false, false, true, false, false]),
+            ("schip", [false, false, false, false, false, false, true, true, false, false, false, false]),
+            ("xochip", [true, false, true, true, false, false, false, false, true, false, false, false]),
+        ];
+        assert_eq!(table.map(|(name, _)| name), Quirks::PROFILES);
+        for (name, flags) in table {
+            let mut quirks = Quirks::from_profile(name).unwrap();
+            let set: Vec<bool> = Quirks::NAMES.iter().map(|flag| *quirks.flag_mut(flag).unwrap()).collect();
+            assert_eq!(set, flags, "{name}");
+        }
+        assert_eq!(Quirks::from_profile("vip"), None);
+    }
+
+    #[test]
+    fn flags_override_a_profile() {
+        let mut quirks = Quirks::chip8();
+        *quirks.flag_mut("display-wait").unwrap() = false;
+        *quirks.flag_mut("wrap-sprites").unwrap() = true;
+        assert_eq!(quirks, Quirks { display_wait: false, wrap_sprites: true, ..Quirks::chip8() });
+        assert!(quirks.flag_mut("clipping").is_none());
+
+        let mut names = Quirks::NAMES.to_vec();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), 12);
+        for name in Quirks::NAMES {
+            let mut quirks = Quirks::default();
+            *quirks.flag_mut(name).unwrap() = true;
+            assert_ne!(quirks, Quirks::default(), "{name}");
+        }
+    }
+}
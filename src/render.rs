@@ -0,0 +1,260 @@
+//! Turning the display into framebuffer bytes for whatever is showing it.
+//!
+//! Each [`PixelFormat`] has one fixed byte layout, the same on every host:
+//!
+//! | format     | bytes per pixel | layout of one pixel                                  |
+//! |------------|-----------------|------------------------------------------------------|
+//! | `Argb8888` | 4               | `B G R A`: a little-endian `0xAARRGGBB`              |
+//! | `Rgba8888` | 4               | `R G B A`                                            |
+//! | `Rgb565`   | 2               | a little-endian `RRRRRGGG GGGBBBBB`                  |
+//! | `Mono1`    | 1/8             | one bit, most significant first, set when lit        |
+//!
+//! Alpha is always 0xFF. `Mono1` packs pixels continuously rather than
+//! padding rows; CHIP-8's 64-pixel rows are a whole 8 bytes anyway.
+
+use crate::chip8::Chip8;
+
+/// Colors for unlit and lit pixels, as 0RGB, and for pixels lit in XO-CHIP's
+/// second plane.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub off: u32,
+    /// Lit in plane 1, the only one anything but XO-CHIP draws on.
+    pub on: u32,
+    /// Lit in plane 2 alone...
+    pub plane2: u32,
+    /// ...and in both.
+    pub both: u32,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            off: 0x000000,
+            on: 0xFFFFFF,
+            plane2: 0xAAAAAA,
+            both: 0x555555,
+        }
+    }
+}
+
+impl Palette {
+    pub fn color(&self, lit: bool) -> u32 {
+        if lit { self.on } else { self.off }
+    }
+
+    /// The color for a pixel lit in the planes in `planes`, plane 1 in bit
+    /// 0, as [`Display::color`](crate::Display::color) gives them.
+    pub fn color_of(&self, planes: u8) -> u32 {
+        match planes & 0b11 {
+            0 => self.off,
+            1 => self.on,
+            2 => self.plane2,
+            _ => self.both,
+        }
+    }
+}
+
+/// A framebuffer layout, as described in the [module docs](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// What minifb and SDL's 32-bit textures read as a `u32` on
+    /// little-endian hosts.
+    Argb8888,
+    /// What pixels, SDL's `RGBA32` and canvas `ImageData` want.
+    Rgba8888,
+    /// 16-bit color for small LCDs. Panels that take big-endian words over
+    /// SPI need each pair of bytes swapped.
+    Rgb565,
+    /// 1bpp for monochrome displays.
+    Mono1,
+}
+
+impl PixelFormat {
+    /// Bytes needed for `pixels` pixels.
+    pub const fn buffer_len(self, pixels: usize) -> usize {
+        match self {
+            PixelFormat::Argb8888 | PixelFormat::Rgba8888 => pixels * 4,
+            PixelFormat::Rgb565 => pixels * 2,
+            PixelFormat::Mono1 => pixels.div_ceil(8),
+        }
+    }
+}
+
+/// Writes `pixels`, mapped through `palette`, into `buf` as `format`, and
+/// returns how many bytes that took. `Mono1` ignores the palette.
+///
+/// Panics if `buf` is shorter than [`PixelFormat::buffer_len`].
+pub fn render_into(pixels: &[bool], palette: Palette, buf: &mut [u8], format: PixelFormat) -> usize {
+    encode(pixels.iter().map(|&lit| (lit, palette.color(lit))), pixels.len(), buf, format)
+}
+
+/// Writes pixels given as the planes they're lit in, like
+/// [`Display::colors`](crate::Display::colors), mapped through `palette`,
+/// into `buf` as `format`, and returns how many bytes that took. `Mono1`
+/// lights pixels lit in any plane.
+///
+/// Panics if `buf` is shorter than [`PixelFormat::buffer_len`].
+pub fn render_planes_into(
+    colors: impl ExactSizeIterator<Item = u8>,
+    palette: Palette,
+    buf: &mut [u8],
+    format: PixelFormat,
+) -> usize {
+    let count = colors.len();
+    encode(colors.map(|planes| (planes != 0, palette.color_of(planes))), count, buf, format)
+}
+
+/// Writes 0RGB `colors`, such as an already drawn screen, into `buf` as
+/// `format`, and returns how many bytes that took. For `Mono1` any color
+/// but black is lit.
+///
+/// Panics if `buf` is shorter than [`PixelFormat::buffer_len`].
+pub fn rgb_into(colors: &[u32], buf: &mut [u8], format: PixelFormat) -> usize {
+    encode(colors.iter().map(|&rgb| (rgb & 0xFFFFFF != 0, rgb)), colors.len(), buf, format)
+}
+
+fn encode(pixels: impl Iterator<Item = (bool, u32)>, count: usize, buf: &mut [u8], format: PixelFormat) -> usize {
+    let len = format.buffer_len(count);
+    assert!(buf.len() >= len, "{:?} needs {} bytes for {} pixels, got {}", format, len, count, buf.len());
+    let buf = &mut buf[..len];
+
+    match format {
+        PixelFormat::Argb8888 => {
+            for (out, (_, rgb)) in buf.chunks_exact_mut(4).zip(pixels) {
+                out.copy_from_slice(&(0xFF000000 | rgb).to_le_bytes());
+            }
+        }
+        PixelFormat::Rgba8888 => {
+            for (out, (_, rgb)) in buf.chunks_exact_mut(4).zip(pixels) {
+                let [_, r, g, b] = rgb.to_be_bytes();
+                out.copy_from_slice(&[r, g, b, 0xFF]);
+            }
+        }
+        PixelFormat::Rgb565 => {
+            for (out, (_, rgb)) in buf.chunks_exact_mut(2).zip(pixels) {
+                let [_, r, g, b] = rgb.to_be_bytes();
+                let word = (r as u16 >> 3) << 11 | (g as u16 >> 2) << 5 | b as u16 >> 3;
+                out.copy_from_slice(&word.to_le_bytes());
+            }
+        }
+        PixelFormat::Mono1 => {
+            buf.fill(0);
+            for (index, (lit, _)) in pixels.enumerate() {
+                if lit {
+                    buf[index / 8] |= 0x80 >> (index % 8);
+                }
+            }
+        }
+    }
+    len
+}
+
+impl<R> Chip8<R> {
+    /// The display, both planes, written into `buf` as `format`; see
+    /// [`render_planes_into`]. Under the [chip8x](crate::Quirks::chip8x)
+    /// quirk the colors are the [`color_map`](Chip8::color_map)'s instead.
+    pub fn render_into(&self, palette: Palette, buf: &mut [u8], format: PixelFormat) -> usize {
+        let count = self.display.colors().len();
+        encode(self.pixel_colors(palette), count, buf, format)
+    }
+
+    /// Whether each pixel is lit in any plane, and its 0RGB.
+    pub(crate) fn pixel_colors(&self, palette: Palette) -> impl Iterator<Item = (bool, u32)> + '_ {
+        let (width, height) = (self.display.width(), self.display.height());
+        self.display.colors().enumerate().map(move |(index, planes)| {
+            let lit = planes != 0;
+            if self.quirks.chip8x {
+                (lit, self.color_map.rgb(index % width, index / width, width, height, lit))
+            } else {
+                (lit, palette.color_of(planes))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lit, unlit, lit, lit.
+    const PIXELS: [bool; 4] = [true, false, true, true];
+    const PALETTE: Palette = Palette { off: 0x123456, on: 0xFF8001, plane2: 0x00FF00, both: 0x0000FF };
+
+    fn render(format: PixelFormat) -> ([u8; 16], usize) {
+        let mut buf = [0xAA; 16];
+        let len = render_into(&PIXELS, PALETTE, &mut buf, format);
+        (buf, len)
+    }
+
+    #[test]
+    fn argb8888_is_little_endian_words() {
+        let (buf, len) = render(PixelFormat::Argb8888);
+        assert_eq!(len, 16);
+        assert_eq!(buf[..8], [0x01, 0x80, 0xFF, 0xFF, 0x56, 0x34, 0x12, 0xFF]);
+        assert_eq!(u32::from_le_bytes(buf[12..16].try_into().unwrap()), 0xFFFF8001);
+    }
+
+    #[test]
+    fn rgba8888_is_bytes_in_order() {
+        let (buf, len) = render(PixelFormat::Rgba8888);
+        assert_eq!(len, 16);
+        assert_eq!(buf[..8], [0xFF, 0x80, 0x01, 0xFF, 0x12, 0x34, 0x56, 0xFF]);
+    }
+
+    #[test]
+    fn rgb565_keeps_the_top_bits_little_endian() {
+        let (buf, len) = render(PixelFormat::Rgb565);
+        assert_eq!(len, 8);
+        // 0xFF8001 -> 11111 100000 00000; 0x123456 -> 00010 001101 01010.
+        assert_eq!(buf[..8], [0x00, 0xFC, 0xAA, 0x11, 0x00, 0xFC, 0x00, 0xFC]);
+        // Untouched past the end.
+        assert_eq!(buf[8], 0xAA);
+
+        let mut white = [0; 2];
+        rgb_into(&[0xFFFFFF], &mut white, PixelFormat::Rgb565);
+        assert_eq!(white, [0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn mono1_packs_most_significant_bit_first() {
+        let (buf, len) = render(PixelFormat::Mono1);
+        assert_eq!(len, 1);
+        assert_eq!(buf[..2], [0b1011_0000, 0xAA]);
+
+        let mut chip8 = Chip8::with_rng(crate::rng::Rng::new(1));
+        chip8.display[0][0] = true;
+        chip8.display[0][9] = true;
+        chip8.display[31][63] = true;
+        let mut screen = [0xFF; 256];
+        assert_eq!(chip8.render_into(PALETTE, &mut screen, PixelFormat::Mono1), 256);
+        assert_eq!(screen[..2], [0x80, 0x40]);
+        assert!(screen[2..255].iter().all(|&byte| byte == 0));
+        assert_eq!(screen[255], 0x01);
+    }
+
+    #[test]
+    fn planes_pick_one_of_four_colors() {
+        let mut buf = [0; 16];
+        assert_eq!(render_planes_into([0, 1, 2, 3].into_iter(), PALETTE, &mut buf, PixelFormat::Rgba8888), 16);
+        assert_eq!(buf[..4], [0x12, 0x34, 0x56, 0xFF]);
+        assert_eq!(buf[8..], [0x00, 0xFF, 0x00, 0xFF, 0x00, 0x00, 0xFF, 0xFF]);
+
+        let mut mono = [0; 1];
+        render_planes_into([0, 2, 0, 3].into_iter(), PALETTE, &mut mono, PixelFormat::Mono1);
+        assert_eq!(mono, [0b0101_0000]);
+    }
+
+    #[test]
+    fn rgb_buffers_light_anything_but_black() {
+        let mut buf = [0; 1];
+        rgb_into(&[0x000000, 0x0F0F0F, 0xFF000000, 0x000001], &mut buf, PixelFormat::Mono1);
+        assert_eq!(buf, [0b0101_0000]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Rgba8888 needs 16 bytes for 4 pixels, got 15")]
+    fn short_buffers_panic() {
+        render_into(&PIXELS, PALETTE, &mut [0; 15], PixelFormat::Rgba8888);
+    }
+}
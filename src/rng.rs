@@ -0,0 +1,52 @@
+/// Per-instance pseudo-random generator backing CXNN.
+///
+/// This is xorshift64* (Marsaglia's xorshift with shifts 12/25/27, output
+/// multiplied by 0x2545F4914F6CDD1D), taking the high byte of each output.
+/// It is implemented here rather than borrowed from `rand` so that a given
+/// seed produces the same sequence on every platform and release.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// xorshift gets stuck at zero, so a zero seed is replaced by this one.
+    const ZERO_SEED_REPLACEMENT: u64 = 0x9E37_79B9_7F4A_7C15;
+
+    pub fn new(seed: u64) -> Self {
+        let state = if seed == 0 { Self::ZERO_SEED_REPLACEMENT } else { seed };
+        Self { state }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn from_entropy() -> Self {
+        Self::new(rand::random())
+    }
+
+    /// The current generator state; passing it to [`Rng::new`] resumes the
+    /// sequence from this point.
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn next_u8(&mut self) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+}
+
+/// Where CXNN gets its random bytes from.
+///
+/// [`Rng`] is the default; embedded targets can plug in a hardware generator
+/// with [`Chip8::with_rng`](crate::Chip8::with_rng).
+pub trait RandomSource {
+    fn next_u8(&mut self) -> u8;
+}
+
+impl RandomSource for Rng {
+    fn next_u8(&mut self) -> u8 {
+        Rng::next_u8(self)
+    }
+}
@@ -0,0 +1,77 @@
+//! A few entries from the community CHIP-8 program database, keyed by the
+//! SHA-1 of the ROM, so well-known games get the quirks they were written
+//! for without anyone looking them up.
+
+use crate::quirks::Quirks;
+use crate::sha1::sha1;
+
+/// A ROM the database knows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KnownRom {
+    /// The SHA-1 of the ROM file, in lowercase hex.
+    pub sha1: &'static str,
+    pub title: &'static str,
+    /// One of [`Quirks::PROFILES`].
+    pub profile: &'static str,
+}
+
+impl KnownRom {
+    /// The quirks the game needs.
+    pub fn quirks(&self) -> Quirks {
+        Quirks::from_profile(self.profile).unwrap_or_default()
+    }
+}
+
+/// Every ROM the database knows.
+pub const KNOWN_ROMS: &[KnownRom] = &[
+    KnownRom { sha1: "6f6509f38220e057a7e32ebb22dd353c1078e3e7", title: "Blitz (David Winter)", profile: "chip8" },
+    KnownRom { sha1: "f13766c14aeb02ad8d4d103cb5eadd282d20cddc", title: "Brix (Andreas Gustafsson, 1990)", profile: "chip8" },
+    KnownRom { sha1: "da710f631f8e35534d0b9170bcf892a60f49c43d", title: "Vertical Brix (Paul Robson, 1996)", profile: "chip8" },
+    KnownRom { sha1: "fc724ae0125f5f1ac94a79fe3afc6318b1f57556", title: "Kaleidoscope (Joseph Weisbecker, 1978)", profile: "chip8" },
+    KnownRom { sha1: "8d56a781bf16acccb307177b80ff326f62aabbdc", title: "Hires Test (Tom Swan, 1979)", profile: "hires-chip8" },
+    KnownRom { sha1: "70aa0e7f25f0f0fd6ec7c59e427bf1d03ee95617", title: "Hires Maze (David Winter)", profile: "hires-chip8" },
+    KnownRom { sha1: "066e7a84efde433e4d937d8aa41518666955086c", title: "Astro Dodge Hires (Revival Studios, 2008)", profile: "hires-chip8" },
+    KnownRom { sha1: "5c28a5f85289c9d859f95fd5eadbdcb1c30bb08b", title: "Space Invaders (David Winter)", profile: "schip" },
+    KnownRom { sha1: "d40abc54374e4343639f993e897e00904ddf85d9", title: "Blinky (Hans Christian Egeberg, 1991)", profile: "schip" },
+];
+
+/// The entry for `rom`, if the database has one.
+pub fn lookup(rom: &[u8]) -> Option<&'static KnownRom> {
+    let digest = sha1(rom);
+    KNOWN_ROMS.iter().find(|known| matches_digest(known.sha1, &digest))
+}
+
+fn matches_digest(hex: &str, digest: &[u8; 20]) -> bool {
+    hex.len() == 40
+        && digest
+            .iter()
+            .zip(hex.as_bytes().chunks_exact(2))
+            .all(|(&byte, pair)| core::str::from_utf8(pair).ok().and_then(|pair| u8::from_str_radix(pair, 16).ok()) == Some(byte))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_are_well_formed() {
+        for known in KNOWN_ROMS {
+            assert!(known.sha1.len() == 40 && known.sha1.bytes().all(|c| c.is_ascii_digit() || (b'a'..=b'f').contains(&c)), "{known:?}");
+            assert!(Quirks::PROFILES.contains(&known.profile), "{known:?}");
+        }
+    }
+
+    #[test]
+    fn known_roms_are_found_by_their_bytes() {
+        let blitz = lookup(include_bytes!("../roms/games/Blitz [David Winter].ch8")).unwrap();
+        assert_eq!((blitz.title, blitz.quirks()), ("Blitz (David Winter)", Quirks::chip8()));
+        let invaders = lookup(include_bytes!("../roms/games/Space Invaders [David Winter].ch8")).unwrap();
+        assert_eq!(invaders.quirks(), Quirks::schip());
+        let maze = lookup(include_bytes!("../roms/hires/Hires Maze [David Winter, 199x].ch8")).unwrap();
+        assert_eq!(maze.quirks(), Quirks::hires_chip8());
+
+        // The alternate dump differs by a byte or more.
+        assert_eq!(lookup(include_bytes!("../roms/games/Space Invaders [David Winter] (alt).ch8")), None);
+        assert_eq!(lookup(&[0x12, 0x00]), None);
+    }
+}
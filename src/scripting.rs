@@ -0,0 +1,370 @@
+//! Rhai scripts hooked into the frame loop, for automation and cheats.
+//!
+//! A script's top level runs once, when it's loaded, and registers callbacks:
+//!
+//! ```text
+//! on_frame(|frame| { if frame == 60 { press(5); } });
+//! on_address(0x2A4, || { set_reg(3, 9); });   // infinite lives
+//! on_draw(|| { if pixel(0, 0) { pause(); } });
+//! ```
+//!
+//! Inside a callback the script can use:
+//!
+//! - `reg(x)`, `set_reg(x, value)`: V0-VF
+//! - `reg_i()`, `set_reg_i(value)`, `pc()`, `set_pc(addr)`
+//! - `peek(addr)`, `poke(addr, byte)`: memory
+//! - `press(key)`, `release(key)`, `key_down(key)`: the keypad
+//! - `pixel(x, y)`: whether a display pixel is lit
+//! - `frame()`: frames run so far
+//! - `pause()`: pause the machine once the callback returns
+//!
+//! and `print`, as usual. Each call into the script, including the initial
+//! load, may use at most its operation budget; a callback that fails or runs
+//! over is logged and dropped.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
+
+use log::warn;
+use rhai::{Dynamic, Engine, EvalAltResult, FnPtr, FuncArgs, AST};
+use thiserror::Error;
+
+use crate::Chip8;
+use crate::frontend::{HookAction, Hooks};
+
+/// Operations a script may use per call when no budget is given.
+pub const DEFAULT_BUDGET: u64 = 100_000;
+
+#[derive(Debug, Error)]
+pub enum ScriptError {
+    #[error("couldn't read the script: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Parse(#[from] rhai::ParseError),
+    #[error("{0}")]
+    Run(#[from] Box<EvalAltResult>),
+}
+
+type Result<T> = std::result::Result<T, Box<EvalAltResult>>;
+
+/// What the script's functions see, shared with the closures registered on
+/// the engine.
+#[derive(Default)]
+struct State {
+    /// The driver's machine, swapped in for the length of each callback.
+    machine: Chip8,
+    attached: bool,
+    frame: u64,
+    pause: bool,
+    on_frame: Vec<FnPtr>,
+    on_draw: Vec<FnPtr>,
+    on_address: BTreeMap<u16, Vec<FnPtr>>,
+}
+
+impl State {
+    fn machine(&mut self) -> Result<&mut Chip8> {
+        if self.attached {
+            Ok(&mut self.machine)
+        } else {
+            Err("the machine is only available inside callbacks".into())
+        }
+    }
+}
+
+/// A loaded script, ready to be given to [`Driver::set_hooks`].
+///
+/// [`Driver::set_hooks`]: crate::frontend::Driver::set_hooks
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    state: Rc<RefCell<State>>,
+}
+
+impl fmt::Debug for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.borrow();
+        f.debug_struct("Script")
+            .field("on_frame", &state.on_frame.len())
+            .field("on_draw", &state.on_draw.len())
+            .field("on_address", &state.on_address.keys().collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Script {
+    /// Compiles `source` and runs its top level, allowing `budget` operations
+    /// for that and for each callback after.
+    pub fn load(source: &str, budget: u64) -> std::result::Result<Self, ScriptError> {
+        let state = Rc::new(RefCell::new(State::default()));
+        let mut engine = Engine::new();
+        engine.set_max_operations(budget);
+        register(&mut engine, &state);
+
+        let ast = engine.compile(source)?;
+        engine.run_ast(&ast)?;
+        Ok(Self { engine, ast, state })
+    }
+
+    pub fn from_file(path: impl AsRef<Path>, budget: u64) -> std::result::Result<Self, ScriptError> {
+        Self::load(&std::fs::read_to_string(path)?, budget)
+    }
+
+    /// Calls each of `callbacks` with the machine swapped in, keeping the
+    /// ones that succeed.
+    fn call(&self, chip8: &mut Chip8, callbacks: Vec<FnPtr>, args: impl FuncArgs + Clone) -> Vec<FnPtr> {
+        {
+            let mut state = self.state.borrow_mut();
+            std::mem::swap(&mut state.machine, chip8);
+            state.attached = true;
+        }
+        let kept = callbacks
+            .into_iter()
+            .filter(|callback| match callback.call::<Dynamic>(&self.engine, &self.ast, args.clone()) {
+                Ok(_) => true,
+                Err(e) => {
+                    warn!(target: "chip8::script", "dropping callback {}: {}", callback.fn_name(), e);
+                    false
+                }
+            })
+            .collect();
+        let mut state = self.state.borrow_mut();
+        state.attached = false;
+        std::mem::swap(&mut state.machine, chip8);
+        kept
+    }
+
+    fn take_pause(&self) -> HookAction {
+        if std::mem::take(&mut self.state.borrow_mut().pause) {
+            HookAction::Pause
+        } else {
+            HookAction::Continue
+        }
+    }
+}
+
+impl Hooks for Script {
+    fn frame_start(&mut self, chip8: &mut Chip8, frame: u64) -> HookAction {
+        let callbacks = {
+            let mut state = self.state.borrow_mut();
+            state.frame = frame;
+            std::mem::take(&mut state.on_frame)
+        };
+        let mut kept = self.call(chip8, callbacks, (frame as i64,));
+        // Callbacks may have registered more.
+        let mut state = self.state.borrow_mut();
+        kept.append(&mut state.on_frame);
+        state.on_frame = kept;
+        drop(state);
+        self.take_pause()
+    }
+
+    fn wants_address(&self, pc: u16) -> bool {
+        self.state.borrow().on_address.contains_key(&pc)
+    }
+
+    fn address_hit(&mut self, chip8: &mut Chip8, pc: u16) -> HookAction {
+        let callbacks = self.state.borrow_mut().on_address.remove(&pc).unwrap_or_default();
+        let mut kept = self.call(chip8, callbacks, ());
+        let mut state = self.state.borrow_mut();
+        if let Some(added) = state.on_address.get_mut(&pc) {
+            kept.append(added);
+        }
+        if !kept.is_empty() {
+            state.on_address.insert(pc, kept);
+        }
+        drop(state);
+        self.take_pause()
+    }
+
+    fn wants_draws(&self) -> bool {
+        !self.state.borrow().on_draw.is_empty()
+    }
+
+    fn draw(&mut self, chip8: &mut Chip8) -> HookAction {
+        let callbacks = std::mem::take(&mut self.state.borrow_mut().on_draw);
+        let mut kept = self.call(chip8, callbacks, ());
+        let mut state = self.state.borrow_mut();
+        kept.append(&mut state.on_draw);
+        state.on_draw = kept;
+        drop(state);
+        self.take_pause()
+    }
+}
+
+fn checked<T: TryFrom<i64>>(value: i64, what: &str, max: i64) -> Result<T> {
+    match T::try_from(value) {
+        Ok(value_t) if value <= max => Ok(value_t),
+        _ => Err(format!("{} {} is out of range (0-{:#X})", what, value, max).into()),
+    }
+}
+
+fn register(engine: &mut Engine, state: &Rc<RefCell<State>>) {
+    fn with<T>(state: &Rc<RefCell<State>>, f: impl FnOnce(&mut Chip8) -> T) -> Result<T> {
+        Ok(f(state.borrow_mut().machine()?))
+    }
+
+    let s = Rc::clone(state);
+    engine.register_fn("reg", move |x: i64| -> Result<i64> {
+        let x: usize = checked(x, "register", 0xF)?;
+        with(&s, |chip8| chip8.v[x] as i64)
+    });
+    let s = Rc::clone(state);
+    engine.register_fn("set_reg", move |x: i64, value: i64| -> Result<()> {
+        let x: usize = checked(x, "register", 0xF)?;
+        let value: u8 = checked(value, "value", 0xFF)?;
+        with(&s, |chip8| chip8.v[x] = value)
+    });
+    let s = Rc::clone(state);
+    engine.register_fn("reg_i", move || -> Result<i64> { with(&s, |chip8| chip8.i as i64) });
+    let s = Rc::clone(state);
+    engine.register_fn("set_reg_i", move |value: i64| -> Result<()> {
+        let value: u16 = checked(value, "value", 0xFFFF)?;
+        with(&s, |chip8| chip8.i = value)
+    });
+    let s = Rc::clone(state);
+    engine.register_fn("pc", move || -> Result<i64> { with(&s, |chip8| chip8.pc as i64) });
+    let s = Rc::clone(state);
+    engine.register_fn("set_pc", move |addr: i64| -> Result<()> {
+        let top = with(&s, |chip8| chip8.memory_size() as i64 - 1)?;
+        let addr: u16 = checked(addr, "address", top)?;
+        with(&s, |chip8| chip8.pc = addr)
+    });
+
+    let s = Rc::clone(state);
+    engine.register_fn("peek", move |addr: i64| -> Result<i64> {
+        let top = with(&s, |chip8| chip8.memory_size() as i64 - 1)?;
+        let addr: usize = checked(addr, "address", top)?;
+        with(&s, |chip8| chip8.memory[addr] as i64)
+    });
+    let s = Rc::clone(state);
+    engine.register_fn("poke", move |addr: i64, byte: i64| -> Result<()> {
+        let top = with(&s, |chip8| chip8.memory_size() as i64 - 1)?;
+        let addr: usize = checked(addr, "address", top)?;
+        let byte: u8 = checked(byte, "value", 0xFF)?;
+        with(&s, |chip8| {
+            chip8.memory[addr] = byte;
+            chip8.invalidate_decode_cache();
+        })
+    });
+
+    for (name, pressed) in [("press", true), ("release", false)] {
+        let s = Rc::clone(state);
+        engine.register_fn(name, move |key: i64| -> Result<()> {
+            let key: usize = checked(key, "key", 0xF)?;
+            with(&s, |chip8| chip8.keys[key] = pressed)
+        });
+    }
+    let s = Rc::clone(state);
+    engine.register_fn("key_down", move |key: i64| -> Result<bool> {
+        let key: usize = checked(key, "key", 0xF)?;
+        with(&s, |chip8| chip8.keys[key])
+    });
+    let s = Rc::clone(state);
+    engine.register_fn("pixel", move |x: i64, y: i64| -> Result<bool> {
+        let (width, height) = with(&s, |chip8| (chip8.display.width() as i64, chip8.display.height() as i64))?;
+        let x: usize = checked(x, "x", width - 1)?;
+        let y: usize = checked(y, "y", height - 1)?;
+        with(&s, |chip8| chip8.display[y][x])
+    });
+
+    let s = Rc::clone(state);
+    engine.register_fn("frame", move || s.borrow().frame as i64);
+    let s = Rc::clone(state);
+    engine.register_fn("pause", move || s.borrow_mut().pause = true);
+
+    let s = Rc::clone(state);
+    engine.register_fn("on_frame", move |callback: FnPtr| s.borrow_mut().on_frame.push(callback));
+    let s = Rc::clone(state);
+    engine.register_fn("on_draw", move |callback: FnPtr| s.borrow_mut().on_draw.push(callback));
+    let s = Rc::clone(state);
+    engine.register_fn("on_address", move |addr: i64, callback: FnPtr| -> Result<()> {
+        let addr: u16 = checked(addr, "address", 0xFFFF)?;
+        s.borrow_mut().on_address.entry(addr).or_default().push(callback);
+        Ok(())
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::{Driver, NullAudio, NullInput, NullVideo};
+
+    /// 200: 6000  V0 = 0
+    /// 202: 7001  V0 += 1
+    /// 204: D001  draw 1 row at (V0, V0)
+    /// 206: 1202  jump to 202
+    const DRAW_LOOP: [u8; 8] = [0x60, 0x00, 0x70, 0x01, 0xD0, 0x01, 0x12, 0x02];
+
+    fn run(source: &str, frames: u64) -> (Chip8, Driver) {
+        let mut chip8 = Chip8::new();
+        chip8.load_rom_bytes(&DRAW_LOOP).unwrap();
+        let mut driver = Driver::new();
+        driver.set_hooks(Script::load(source, DEFAULT_BUDGET).unwrap());
+        for _ in 0..frames {
+            assert!(driver.frame(&mut chip8, &mut NullVideo, &mut NullInput, &mut NullAudio));
+        }
+        assert!(driver.halted().is_none());
+        (chip8, driver)
+    }
+
+    #[test]
+    fn hooks_run_at_their_points() {
+        let source = "
+            on_frame(|frame| { poke(0x300, frame); });
+            on_address(0x202, || { poke(0x301, peek(0x301) + 1); });
+            on_draw(|| { if reg(0) == 3 { press(7); } });
+        ";
+        let (chip8, _) = run(source, 3);
+        assert_eq!(chip8.memory[0x300], 2);
+        // Once per pass through the loop, as V0 counts the passes too.
+        assert!(chip8.v[0] > 3);
+        assert_eq!(chip8.memory[0x301], chip8.v[0]);
+        assert!(chip8.keys[7]);
+    }
+
+    #[test]
+    fn address_hooks_can_patch_the_machine() {
+        // Keep V0 at 0x10, however often it's incremented.
+        let (chip8, _) = run("on_address(0x204, || set_reg(0, 0x10));", 2);
+        // Or one more, if the frame ended between 202 and 204.
+        assert!(matches!(chip8.v[0], 0x10 | 0x11), "{}", chip8.v[0]);
+        let (chip8, _) = run("on_address(0x202, || set_pc(0x206));", 2);
+        assert_eq!(chip8.v[0], 0);
+    }
+
+    #[test]
+    fn pause_stops_the_machine() {
+        let (chip8, driver) = run("on_draw(|| if reg(0) == 5 { pause() });", 3);
+        assert!(driver.paused());
+        assert_eq!(chip8.v[0], 5);
+        assert_eq!(chip8.pc, 0x206);
+    }
+
+    #[test]
+    fn budgets_stop_runaway_scripts() {
+        assert!(matches!(Script::load("loop {}", 1_000), Err(ScriptError::Run(_))));
+
+        // A callback over budget is dropped, and the others keep running.
+        let source = "
+            on_frame(|frame| { if frame == 1 { loop {} } });
+            on_frame(|frame| { poke(0x300, frame); });
+        ";
+        let (chip8, driver) = run(source, 4);
+        assert_eq!(chip8.memory[0x300], 3);
+        assert!(!driver.paused());
+        let script = Script::load(source, DEFAULT_BUDGET).unwrap();
+        assert_eq!(script.state.borrow().on_frame.len(), 2);
+    }
+
+    #[test]
+    fn bad_arguments_and_top_level_access_are_errors() {
+        assert!(Script::load("reg(0)", DEFAULT_BUDGET).is_err());
+        let (chip8, _) = run("on_frame(|frame| { poke(0x300, 1); set_reg(16, 0); poke(0x301, 1); });", 2);
+        // The first call fails part way through and isn't repeated.
+        assert_eq!(chip8.memory[0x300..0x302], [1, 0]);
+        assert!(Script::load("on_address(0x10000, || ())", DEFAULT_BUDGET).is_err());
+    }
+}
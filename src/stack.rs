@@ -0,0 +1,138 @@
+use core::fmt;
+
+use crate::chip8::STACK_DEPTH;
+
+/// The call stack: up to [`STACK_DEPTH`] return addresses in a fixed array,
+/// so subroutine calls never allocate.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stack {
+    entries: [u16; STACK_DEPTH],
+    depth: usize,
+}
+
+impl Stack {
+    pub const fn new() -> Self {
+        Self {
+            entries: [0; STACK_DEPTH],
+            depth: 0,
+        }
+    }
+
+    /// Pushes `addr`, or returns false if the stack is already full. The
+    /// machine turns that into a [`StackOverflow`](crate::Chip8Error::StackOverflow)
+    /// with the context it has.
+    #[must_use]
+    pub fn push(&mut self, addr: u16) -> bool {
+        let Some(entry) = self.entries.get_mut(self.depth) else {
+            return false;
+        };
+        *entry = addr;
+        self.depth += 1;
+        true
+    }
+
+    /// The most recent return address, or `None` if the stack is empty.
+    pub fn pop(&mut self) -> Option<u16> {
+        self.depth = self.depth.checked_sub(1)?;
+        Some(self.entries[self.depth])
+    }
+
+    pub fn len(&self) -> usize {
+        self.depth
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.depth == 0
+    }
+
+    /// The return addresses in use, outermost call first.
+    pub fn as_slice(&self) -> &[u16] {
+        &self.entries[..self.depth]
+    }
+
+    pub fn clear(&mut self) {
+        self.depth = 0;
+    }
+}
+
+/// Stacks are equal when they hold the same return addresses; whatever is
+/// left in the unused slots doesn't count.
+impl PartialEq for Stack {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl Eq for Stack {}
+
+/// The return addresses, outermost first, as `[202, 20A]`.
+impl fmt::Display for Stack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[")?;
+        for (index, addr) in self.as_slice().iter().enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{addr:03X}")?;
+        }
+        f.write_str("]")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    #[test]
+    fn matches_a_bounded_vec() {
+        let mut rng = Rng::new(0x57AC);
+        let mut stack = Stack::new();
+        let mut model: Vec<u16> = Vec::new();
+
+        for _ in 0..10_000 {
+            let byte = rng.next_u8();
+            // Lean towards pushing so the stack regularly fills up.
+            if byte < 150 {
+                let addr = u16::from(byte) << 4;
+                let expected = if model.len() < STACK_DEPTH {
+                    model.push(addr);
+                    true
+                } else {
+                    false
+                };
+                assert_eq!(stack.push(addr), expected);
+            } else {
+                assert_eq!(stack.pop(), model.pop());
+            }
+
+            assert_eq!(stack.as_slice(), model.as_slice());
+            assert_eq!(stack.len(), model.len());
+        }
+    }
+
+    #[test]
+    fn equality_ignores_unused_slots() {
+        let mut a = Stack::new();
+        assert!(a.push(0x200));
+        assert!(a.push(0x300));
+        assert_eq!(a.pop(), Some(0x300));
+
+        let mut b = Stack::new();
+        assert!(b.push(0x200));
+
+        assert_eq!(a, b);
+        b.clear();
+        assert!(b.is_empty());
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn displays_the_return_addresses() {
+        let mut stack = Stack::new();
+        assert_eq!(stack.to_string(), "[]");
+        assert!(stack.push(0x202));
+        assert!(stack.push(0x3FE));
+        assert_eq!(stack.to_string(), "[202, 3FE]");
+    }
+}
@@ -0,0 +1,213 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::color_map::{ColorMap, ZONES};
+use crate::chip8::{AUDIO_PATTERN_BYTES, Chip8, EXTENDED_MEMORY_SIZE, Fnv1a, MEMORY_SIZE, STACK_DEPTH};
+use crate::display::{Display, PLANES, Resolution};
+use crate::error::Chip8Error;
+use crate::rng::Rng;
+use crate::stack::Stack;
+
+const MAGIC: &[u8; 4] = b"C8ST";
+
+/// Version of the save state layout written by [`Chip8::save_state`].
+/// Version 3 is laid out like 2, but is only written by machines with
+/// SUPER-CHIP's big font in memory, which older states don't have; version
+/// 4 added XO-CHIP's second plane, 5 its audio, 6 its 64 KB of memory and 7
+/// CHIP-8X's colors.
+pub const STATE_VERSION: u16 = 7;
+
+impl Chip8 {
+    /// Serializes the machine into a compact binary snapshot.
+    ///
+    /// The layout is: magic `C8ST`, version (u16 LE), the memory size (u8, 0
+    /// for 4 KB and 1 for XO-CHIP's 64 KB) and that much memory, V0-VF, I and PC
+    /// (u16 LE), stack depth (u8) followed by that many u16 LE entries, the
+    /// delay and sound timers, the resolution (0 for 64x32, 1 for 128x64, 2
+    /// for 64x64),
+    /// the selected planes (u8, plane 1 in bit 0), each plane of the display
+    /// packed MSB-first row by row, whether an audio pattern is loaded (u8)
+    /// and the pattern (16 bytes, 0 if not), the pitch (u8), CHIP-8X's
+    /// background and the foreground of each of its 64 zones (u8 each), and
+    /// the RNG state (u64 LE).
+    /// Quirks and key state are not included, so a state only loads into a
+    /// machine with the same [extended memory](crate::Quirks::extended_memory)
+    /// setting.
+    #[cfg(feature = "alloc")]
+    pub fn save_state(&self) -> Vec<u8> {
+        // The largest display packs one bit per pixel per plane.
+        let mut out = Vec::with_capacity(self.memory_size() + PLANES * crate::display::MAX_PIXELS / 8 + 64);
+        self.write_state(|bytes| out.extend_from_slice(bytes));
+        out
+    }
+
+    /// A fingerprint of the machine for spotting desyncs: the 64-bit FNV-1a
+    /// hash of what [`Chip8::save_state`] would write, computed without
+    /// allocating. It covers exactly what a save state does, and stays the
+    /// same across platforms and releases until [`STATE_VERSION`] changes.
+    pub fn state_hash(&self) -> u64 {
+        let mut hash = Fnv1a::new();
+        self.write_state(|bytes| hash.write(bytes));
+        hash.finish()
+    }
+
+    /// Hands the save state layout to `out` a piece at a time.
+    fn write_state(&self, mut out: impl FnMut(&[u8])) {
+        out(MAGIC);
+        out(&STATE_VERSION.to_le_bytes());
+        out(&[self.quirks.extended_memory as u8]);
+        out(self.addressable_memory());
+        out(&self.v);
+        out(&self.i.to_le_bytes());
+        out(&self.pc.to_le_bytes());
+        out(&[self.stack.len() as u8]);
+        for addr in self.stack.as_slice() {
+            out(&addr.to_le_bytes());
+        }
+        out(&[self.delay_timer, self.sound_timer]);
+        out(&[match self.display.resolution() {
+            Resolution::Low => 0,
+            Resolution::High => 1,
+            Resolution::TwoPage => 2,
+        }]);
+        out(&[self.display.selected_planes()]);
+        for plane in 0..PLANES {
+            for chunk in self.display.plane(plane).chunks(8) {
+                out(&[chunk.iter().fold(0u8, |byte, &pixel| (byte << 1) | pixel as u8)]);
+            }
+        }
+        out(&[self.audio_pattern.is_some() as u8]);
+        out(&self.audio_pattern.unwrap_or_default());
+        out(&[self.pitch]);
+        out(&[self.color_map.background()]);
+        out(self.color_map.zones());
+        out(&self.rng.state().to_le_bytes());
+    }
+
+    /// Restores a snapshot produced by [`Chip8::save_state`].
+    ///
+    /// The machine is left untouched if the snapshot is rejected.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), Chip8Error> {
+        let mut reader = Reader { data };
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(Chip8Error::InvalidState { reason: "missing C8ST header" });
+        }
+        let version = reader.u16()?;
+        if version != STATE_VERSION {
+            return Err(Chip8Error::StateVersionMismatch {
+                found: version,
+                expected: STATE_VERSION,
+            });
+        }
+
+        let size = match reader.u8()? {
+            0 => MEMORY_SIZE,
+            1 => EXTENDED_MEMORY_SIZE,
+            _ => return Err(Chip8Error::InvalidState { reason: "unknown memory size" }),
+        };
+        if size != self.memory_size() {
+            return Err(Chip8Error::InvalidState { reason: "memory size doesn't match the extended-memory quirk" });
+        }
+        let mut memory = [0u8; EXTENDED_MEMORY_SIZE];
+        memory[..size].copy_from_slice(reader.take(size)?);
+        let v: [u8; 16] = reader.array()?;
+        let i = reader.u16()?;
+        let pc = reader.u16()?;
+
+        let depth = reader.u8()? as usize;
+        if depth > STACK_DEPTH {
+            return Err(Chip8Error::InvalidState { reason: "stack deeper than 16 entries" });
+        }
+        let mut stack = Stack::new();
+        for _ in 0..depth {
+            let pushed = stack.push(reader.u16()?);
+            debug_assert!(pushed);
+        }
+
+        let delay_timer = reader.u8()?;
+        let sound_timer = reader.u8()?;
+
+        let resolution = match reader.u8()? {
+            0 => Resolution::Low,
+            1 => Resolution::High,
+            2 => Resolution::TwoPage,
+            _ => return Err(Chip8Error::InvalidState { reason: "unknown display resolution" }),
+        };
+        let mut display = Display::new(resolution);
+        let selected = reader.u8()?;
+        if selected >= 1 << PLANES {
+            return Err(Chip8Error::InvalidState { reason: "unknown display planes" });
+        }
+        display.select_planes(selected);
+        for plane in 0..PLANES {
+            let packed = reader.take(resolution.pixels() / 8)?;
+            for (index, pixel) in display.plane_mut(plane).iter_mut().enumerate() {
+                *pixel = (packed[index / 8] >> (7 - index % 8)) & 1 == 1;
+            }
+        }
+
+        let has_pattern = match reader.u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err(Chip8Error::InvalidState { reason: "unknown audio pattern flag" }),
+        };
+        let pattern: [u8; AUDIO_PATTERN_BYTES] = reader.array()?;
+        let pitch = reader.u8()?;
+        let background = reader.u8()?;
+        let zones: [u8; ZONES] = reader.array()?;
+        let color_map =
+            ColorMap::from_parts(background, zones).ok_or(Chip8Error::InvalidState { reason: "unknown CHIP-8X color" })?;
+
+        let rng_state = u64::from_le_bytes(reader.array()?);
+        if !reader.data.is_empty() {
+            return Err(Chip8Error::InvalidState { reason: "trailing bytes" });
+        }
+
+        self.memory = memory;
+        self.v = v;
+        self.i = i;
+        self.pc = pc;
+        self.stack = stack;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.display = display;
+        self.audio_pattern = has_pattern.then_some(pattern);
+        self.pitch = pitch;
+        self.color_map = color_map;
+        self.rng = Rng::new(rng_state);
+        self.key_wait = None;
+        self.invalidate_decode_cache();
+
+        Ok(())
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Chip8Error> {
+        if self.data.len() < len {
+            return Err(Chip8Error::InvalidState { reason: "truncated" });
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Chip8Error> {
+        let (head, rest) = self.data.split_first_chunk().ok_or(Chip8Error::InvalidState { reason: "truncated" })?;
+        self.data = rest;
+        Ok(*head)
+    }
+
+    fn u8(&mut self) -> Result<u8, Chip8Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Chip8Error> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+}
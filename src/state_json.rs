@@ -0,0 +1,482 @@
+//! The machine as a JSON document, for analysis tools outside the emulator.
+//!
+//! Unlike the binary save state this is meant to be read by other programs,
+//! so the layout only changes along with [`JSON_STATE_VERSION`]. Version 2
+//! is an object with these fields, always in this order:
+//!
+//! | field         | type                 | contents                                   |
+//! |---------------|----------------------|--------------------------------------------|
+//! | `version`     | number               | [`JSON_STATE_VERSION`]                     |
+//! | `v`           | 16 numbers           | V0-VF                                      |
+//! | `i`           | number               | I                                          |
+//! | `pc`          | number               | the program counter                        |
+//! | `stack`       | array of numbers     | return addresses, oldest first             |
+//! | `delay_timer` | number               |                                            |
+//! | `sound_timer` | number               |                                            |
+//! | `quirks`      | object               | every [`Quirks`] flag, by field name       |
+//! | `rom_hash`    | string or null       | [`Chip8::rom_hash`] as 16 hex digits       |
+//! | `frames`      | number or null       | frames run, when written by a frontend     |
+//! | `rng_state`   | string               | the CXNN generator's state, 16 hex digits  |
+//! | `memory`      | string               | all 4096 bytes, standard base64            |
+//! | `display`     | 32 strings           | rows top to bottom, 64 `0`/`1` characters  |
+//!
+//! In SUPER-CHIP's high resolution `display` is 64 rows of 128 instead,
+//! in HI-RES CHIP-8's two pages 64 rows of 64, and with the [extended memory](Quirks::extended_memory) quirk `memory`
+//! is all 65536 of XO-CHIP's bytes.
+//! XO-CHIP programs add more fields at the end, each left out while it
+//! has nothing to say:
+//!
+//! | field           | type                 | contents                                   |
+//! |-----------------|----------------------|--------------------------------------------|
+//! | `planes`        | number               | the planes FN01 selected, plane 1 in bit 0 |
+//! | `plane2`        | array of strings     | the second plane, laid out like `display`  |
+//! | `audio_pattern` | string               | F002's 16 bytes, 32 hex digits             |
+//! | `pitch`         | number               | FX3A's pitch, when it isn't 64             |
+//!
+//! CHIP-8X programs add theirs after those, likewise left out until they
+//! set them:
+//!
+//! | field        | type   | contents                                          |
+//! |--------------|--------|---------------------------------------------------|
+//! | `background` | number | the background 02A0 stepped to, 0-3               |
+//! | `zones`      | string | each 8x4 zone's foreground, 64 digits 0-7, by row |
+//!
+//! Last comes the keypad, left out while no key is down:
+//!
+//! | field  | type   | contents                                   |
+//! |--------|--------|--------------------------------------------|
+//! | `keys` | string | the keys, 16 `0`/`1` characters from key 0 |
+//!
+//! `display` is plane 1.
+//!
+//! [`Chip8`] implements `Serialize` and `Deserialize` as this document, so
+//! other serde formats get the same fields.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::{Deserialize, Serialize};
+
+use crate::chip8::{AUDIO_PATTERN_BYTES, Chip8, DEFAULT_PITCH, EXTENDED_MEMORY_SIZE, MEMORY_SIZE};
+use crate::color_map::{ColorMap, ZONES};
+use crate::display::{Display, PLANES, Resolution};
+use crate::error::Chip8Error;
+use crate::quirks::Quirks;
+use crate::rng::Rng;
+use crate::stack::Stack;
+
+/// Version of the layout written by [`Chip8::to_json`]. Version 1 left out
+/// the quirks added after it while they were off.
+pub const JSON_STATE_VERSION: u32 = 2;
+
+/// The document described in the [module docs](self), field for field.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JsonState {
+    pub version: u32,
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub stack: Vec<u16>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub quirks: Quirks,
+    pub rom_hash: Option<String>,
+    pub frames: Option<u64>,
+    pub rng_state: String,
+    pub memory: String,
+    pub display: Vec<String>,
+    /// Left out when only plane 1 is selected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub planes: Option<u8>,
+    /// Left out when nothing is lit in it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plane2: Option<Vec<String>>,
+    /// Left out until F002 loads a pattern.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_pattern: Option<String>,
+    /// Left out at the default pitch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pitch: Option<u8>,
+    /// Left out at the first background.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<u8>,
+    /// Left out until BXY0 or BXYN colors a zone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zones: Option<String>,
+    /// Left out while no key is down.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keys: Option<String>,
+}
+
+impl JsonState {
+    /// Pretty-printed, as written by `--dump-state-json`.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("the state has no maps or non-string keys")
+    }
+}
+
+impl Chip8 {
+    /// The machine as a [`JsonState`], with no frame count.
+    pub fn json_state(&self) -> JsonState {
+        JsonState {
+            version: JSON_STATE_VERSION,
+            v: self.v,
+            i: self.i,
+            pc: self.pc,
+            stack: self.stack.as_slice().to_vec(),
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            quirks: self.quirks,
+            rom_hash: self.rom_hash().map(|hash| format!("{:016x}", hash)),
+            frames: None,
+            rng_state: format!("{:016x}", self.rng.state()),
+            memory: BASE64.encode(self.addressable_memory()),
+            display: plane_rows(&self.display, 0),
+            planes: Some(self.display.selected_planes()).filter(|&planes| planes != 1),
+            plane2: self.display.plane(1).contains(&true).then(|| plane_rows(&self.display, 1)),
+            audio_pattern: self.audio_pattern.map(|pattern| pattern.iter().map(|byte| format!("{:02x}", byte)).collect()),
+            pitch: Some(self.pitch).filter(|&pitch| pitch != DEFAULT_PITCH),
+            background: Some(self.color_map.background()).filter(|&background| background != 0),
+            zones: (self.color_map.zones() != ColorMap::new().zones())
+                .then(|| self.color_map.zones().iter().map(|zone| char::from(b'0' + zone)).collect()),
+            keys: self.keys.contains(&true).then(|| self.keys.iter().map(|&down| if down { '1' } else { '0' }).collect()),
+        }
+    }
+
+    /// The machine as the JSON document described in [`state_json`](crate::state_json).
+    pub fn to_json(&self) -> String {
+        self.json_state().to_json()
+    }
+
+    /// A machine restored from [`Chip8::to_json`]'s output, with the decode
+    /// cache off.
+    pub fn from_json(json: &str) -> Result<Self, Chip8Error> {
+        Self::from_json_state(&serde_json::from_str(json)?)
+    }
+
+    pub fn from_json_state(state: &JsonState) -> Result<Self, Chip8Error> {
+        let invalid = |reason| Chip8Error::InvalidState { reason };
+        if state.version != JSON_STATE_VERSION {
+            return Err(Chip8Error::StateVersionMismatch {
+                found: state.version.try_into().unwrap_or(u16::MAX),
+                expected: JSON_STATE_VERSION as u16,
+            });
+        }
+
+        let bytes = BASE64.decode(&state.memory).map_err(|_| invalid("memory is not base64"))?;
+        let expected = if state.quirks.extended_memory { EXTENDED_MEMORY_SIZE } else { MEMORY_SIZE };
+        if bytes.len() != expected {
+            return Err(invalid(if state.quirks.extended_memory {
+                "memory is not 65536 bytes"
+            } else {
+                "memory is not 4096 bytes"
+            }));
+        }
+        let mut memory = [0u8; EXTENDED_MEMORY_SIZE];
+        memory[..bytes.len()].copy_from_slice(&bytes);
+
+        let mut stack = Stack::new();
+        for &addr in &state.stack {
+            if !stack.push(addr) {
+                return Err(invalid("stack deeper than 16 entries"));
+            }
+        }
+
+        let resolution = match state.display.len() {
+            32 => Resolution::Low,
+            64 if state.display[0].len() == 64 => Resolution::TwoPage,
+            64 => Resolution::High,
+            _ => return Err(invalid("display is not 32 or 64 rows")),
+        };
+        let mut display = Display::new(resolution);
+        read_rows(&mut display, 0, &state.display)?;
+        if let Some(rows) = &state.plane2 {
+            if rows.len() != state.display.len() {
+                return Err(invalid("plane2 is not as tall as display"));
+            }
+            read_rows(&mut display, 1, rows)?;
+        }
+        match state.planes {
+            Some(planes) if planes >= 1 << PLANES => return Err(invalid("planes is not a set of planes")),
+            Some(planes) => display.select_planes(planes),
+            None => {}
+        }
+
+        let hex = |text: &str| u64::from_str_radix(text, 16).ok().filter(|_| text.len() == 16);
+        let rng_state = hex(&state.rng_state).ok_or(invalid("rng_state is not 16 hex digits"))?;
+        let rom_hash = match &state.rom_hash {
+            Some(text) => Some(hex(text).ok_or(invalid("rom_hash is not 16 hex digits"))?),
+            None => None,
+        };
+
+        let audio_pattern = match &state.audio_pattern {
+            Some(text) => Some(read_pattern(text).ok_or(invalid("audio_pattern is not 32 hex digits"))?),
+            None => None,
+        };
+
+        let zones = match &state.zones {
+            Some(text) => read_zones(text).ok_or(invalid("zones is not 64 digits 0-7"))?,
+            None => *ColorMap::new().zones(),
+        };
+        let color_map =
+            ColorMap::from_parts(state.background.unwrap_or(0), zones).ok_or(invalid("background is not 0-3"))?;
+
+        let mut keys = [false; 16];
+        if let Some(text) = &state.keys {
+            if text.len() != keys.len() {
+                return Err(invalid("keys is not 16 characters"));
+            }
+            for (down, c) in keys.iter_mut().zip(text.bytes()) {
+                *down = match c {
+                    b'1' => true,
+                    b'0' => false,
+                    _ => return Err(invalid("keys must be 0 or 1")),
+                };
+            }
+        }
+
+        let mut chip8 = Chip8::with_rng(Rng::new(rng_state));
+        chip8.memory = memory;
+        chip8.v = state.v;
+        chip8.i = state.i;
+        chip8.pc = state.pc;
+        chip8.stack = stack;
+        chip8.delay_timer = state.delay_timer;
+        chip8.sound_timer = state.sound_timer;
+        chip8.display = display;
+        chip8.audio_pattern = audio_pattern;
+        chip8.pitch = state.pitch.unwrap_or(DEFAULT_PITCH);
+        chip8.color_map = color_map;
+        chip8.keys = keys;
+        chip8.quirks = state.quirks;
+        chip8.rom_hash = rom_hash;
+        Ok(chip8)
+    }
+}
+
+impl Serialize for Chip8 {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.json_state().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Chip8 {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let state = JsonState::deserialize(deserializer)?;
+        Self::from_json_state(&state).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Plane `plane` of `display` as rows of `0`/`1` characters.
+fn plane_rows(display: &Display, plane: usize) -> Vec<String> {
+    display
+        .plane(plane)
+        .chunks_exact(display.width())
+        .map(|row| row.iter().map(|&lit| if lit { '1' } else { '0' }).collect())
+        .collect()
+}
+
+/// Fills plane `plane` of `display` from `rows`, which must be one per
+/// display row.
+fn read_rows(display: &mut Display, plane: usize, rows: &[String]) -> Result<(), Chip8Error> {
+    let invalid = |reason| Chip8Error::InvalidState { reason };
+    let width = display.width();
+    for (text, row) in rows.iter().zip(display.plane_mut(plane).chunks_exact_mut(width)) {
+        if text.len() != width {
+            return Err(invalid(if width == 64 {
+                "display row is not 64 pixels"
+            } else {
+                "display row is not 128 pixels"
+            }));
+        }
+        for (pixel, c) in row.iter_mut().zip(text.bytes()) {
+            *pixel = match c {
+                b'1' => true,
+                b'0' => false,
+                _ => return Err(invalid("display pixels must be 0 or 1")),
+            };
+        }
+    }
+    Ok(())
+}
+
+/// The pattern `text` spells in hex, two digits a byte.
+fn read_pattern(text: &str) -> Option<[u8; AUDIO_PATTERN_BYTES]> {
+    if text.len() != 2 * AUDIO_PATTERN_BYTES || !text.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut pattern = [0; AUDIO_PATTERN_BYTES];
+    for (byte, digits) in pattern.iter_mut().zip(text.as_bytes().chunks_exact(2)) {
+        *byte = u8::from_str_radix(core::str::from_utf8(digits).ok()?, 16).ok()?;
+    }
+    Some(pattern)
+}
+
+fn read_zones(text: &str) -> Option<[u8; ZONES]> {
+    let digits: [u8; ZONES] = text.as_bytes().try_into().ok()?;
+    digits.iter().all(|digit| (b'0'..=b'7').contains(digit)).then(|| digits.map(|digit| digit - b'0'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn machine() -> Chip8 {
+        let mut chip8 = Chip8::with_rng(Rng::new(42));
+        // 200: 2204  call 204
+        // 202: 1202  jump to 202
+        // 204: A000  I = 0 (the font's 0)
+        // 206: 6305  V3 = 5
+        // 208: D335  draw it at (5, 5)
+        // 20A: C0FF  V0 = random
+        chip8.load_rom_bytes(&[0x22, 0x04, 0x12, 0x02, 0xA0, 0x00, 0x63, 0x05, 0xD3, 0x35, 0xC0, 0xFF]).unwrap();
+        for _ in 0..5 {
+            chip8.step().unwrap();
+        }
+        chip8.delay_timer = 12;
+        chip8.quirks = Quirks::chip8();
+        chip8
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let chip8 = machine();
+        let restored = Chip8::from_json(&chip8.to_json()).unwrap();
+        crate::assert_chip8_eq!(restored, chip8);
+        assert_eq!(restored.rom_hash(), chip8.rom_hash());
+        assert_eq!(restored.to_json(), chip8.to_json());
+    }
+
+    #[test]
+    fn hires_displays_are_64_rows_of_128() {
+        let mut chip8 = machine();
+        chip8.display.set_resolution(Resolution::High);
+        chip8.display[63][127] = true;
+        let state = chip8.json_state();
+        assert_eq!((state.display.len(), state.display[63].len()), (64, 128));
+        assert!(state.display[63].ends_with('1'));
+        crate::assert_chip8_eq!(Chip8::from_json_state(&state).unwrap(), chip8);
+
+        let mut state = state;
+        state.display[0].push('0');
+        assert!(Chip8::from_json_state(&state).unwrap_err().to_string().contains("128 pixels"));
+    }
+
+    #[test]
+    fn two_page_displays_are_64_rows_of_64() {
+        let mut chip8 = machine();
+        chip8.display.set_resolution(Resolution::TwoPage);
+        chip8.display[63][63] = true;
+        let state = chip8.json_state();
+        assert_eq!((state.display.len(), state.display[63].len()), (64, 64));
+        crate::assert_chip8_eq!(Chip8::from_json_state(&state).unwrap(), chip8);
+
+        let mut state = state;
+        state.display[1].push('0');
+        assert!(Chip8::from_json_state(&state).unwrap_err().to_string().contains("64 pixels"));
+    }
+
+    #[test]
+    fn the_second_plane_is_only_written_when_it_has_something() {
+        let mut chip8 = machine();
+        assert!(!chip8.to_json().contains("plane"));
+
+        chip8.display.select_planes(0b11);
+        chip8.display.plane_mut(1)[64 * 31 + 63] = true;
+        let state = chip8.json_state();
+        assert_eq!(state.planes, Some(0b11));
+        assert!(state.plane2.as_ref().unwrap()[31].ends_with('1'));
+        crate::assert_chip8_eq!(Chip8::from_json_state(&state).unwrap(), chip8);
+        assert_eq!(Chip8::from_json_state(&state).unwrap().display, chip8.display);
+
+        let mut state = state;
+        state.plane2.as_mut().unwrap().pop();
+        assert!(Chip8::from_json_state(&state).unwrap_err().to_string().contains("as tall"));
+    }
+
+    #[test]
+    fn audio_is_only_written_once_a_program_sets_it() {
+        let mut chip8 = machine();
+        assert!(!chip8.to_json().contains("audio_pattern") && !chip8.to_json().contains("pitch"));
+
+        chip8.audio_pattern = Some([0x0F; AUDIO_PATTERN_BYTES]);
+        chip8.pitch = 100;
+        let state = chip8.json_state();
+        assert_eq!(state.audio_pattern.as_deref(), Some("0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f"));
+        assert_eq!(state.pitch, Some(100));
+        crate::assert_chip8_eq!(Chip8::from_json_state(&state).unwrap(), chip8);
+
+        let mut state = state;
+        state.audio_pattern = Some("0f".into());
+        assert!(Chip8::from_json_state(&state).unwrap_err().to_string().contains("audio_pattern"));
+        state.audio_pattern = Some("+f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f".into());
+        assert!(Chip8::from_json_state(&state).unwrap_err().to_string().contains("audio_pattern"));
+    }
+
+    #[test]
+    fn chip8x_colors_are_only_written_once_a_program_sets_them() {
+        let mut chip8 = machine();
+        assert!(!chip8.to_json().contains("background") && !chip8.to_json().contains("zones"));
+
+        chip8.color_map.step_background();
+        chip8.color_map.fill(0, 0, 8, 8, 0);
+        chip8.color_map.fill(7, 7, 1, 1, 6);
+        let state = chip8.json_state();
+        assert_eq!(state.background, Some(1));
+        assert_eq!(state.zones.as_deref(), Some(&*format!("{}6", "0".repeat(63))));
+        crate::assert_chip8_eq!(Chip8::from_json_state(&state).unwrap(), chip8);
+
+        let mut state = state;
+        state.zones = Some("8".repeat(64));
+        assert!(Chip8::from_json_state(&state).unwrap_err().to_string().contains("zones"));
+        state.zones = None;
+        state.background = Some(4);
+        assert!(Chip8::from_json_state(&state).unwrap_err().to_string().contains("background"));
+    }
+
+    #[test]
+    fn extended_memory_writes_all_64_kb() {
+        let mut chip8 = machine();
+        chip8.quirks.extended_memory = true;
+        chip8.memory[0xFFFF] = 0x5A;
+        let state = chip8.json_state();
+        assert_eq!(BASE64.decode(&state.memory).unwrap().len(), EXTENDED_MEMORY_SIZE);
+        crate::assert_chip8_eq!(Chip8::from_json_state(&state).unwrap(), chip8);
+
+        let mut state = state;
+        state.quirks.extended_memory = false;
+        assert!(Chip8::from_json_state(&state).unwrap_err().to_string().contains("4096 bytes"));
+    }
+
+    #[test]
+    fn rejects_malformed_documents() {
+        let good = machine().json_state();
+        let reject = |edit: fn(&mut JsonState)| {
+            let mut state = good.clone();
+            edit(&mut state);
+            match Chip8::from_json_state(&state) {
+                Ok(_) => panic!("accepted {:?}", state),
+                Err(e) => e.to_string(),
+            }
+        };
+
+        assert_eq!(
+            reject(|s| s.version = 1),
+            "save state version 1 is not supported (expected 2)"
+        );
+        assert!(reject(|s| s.memory.truncate(8)).contains("4096 bytes"));
+        assert!(reject(|s| s.memory.push('!')).contains("base64"));
+        assert!(reject(|s| s.stack = vec![0x200; 17]).contains("stack deeper"));
+        assert!(reject(|s| s.display[31].push('0')).contains("64 pixels"));
+        assert!(reject(|s| s.display[0].replace_range(0..1, "x")).contains("0 or 1"));
+        assert!(reject(|s| s.display.pop().map(drop).unwrap()).contains("32 or 64 rows"));
+        assert!(reject(|s| s.rng_state = "ff".into()).contains("rng_state"));
+        assert!(reject(|s| s.rom_hash = Some("nope".into())).contains("rom_hash"));
+
+        assert!(matches!(Chip8::from_json("{\"version\": 1}"), Err(Chip8Error::Json(_))));
+        assert!(matches!(Chip8::from_json("not json"), Err(Chip8Error::Json(_))));
+    }
+}
@@ -0,0 +1,165 @@
+//! What each instruction cost on the COSMAC VIP, in machine cycles of the
+//! 1802 (eight clocks each at 1.76 MHz), for running games at the pace
+//! they were written for rather than a flat instruction rate.
+//!
+//! The figures follow Laurence Scotford's annotated disassembly of the VIP
+//! interpreter, rounded where a routine's cost depends on more than the
+//! instruction, the registers and the keypad.
+
+use crate::chip8::Chip8;
+use crate::instruction::{Instruction, decode};
+use crate::rng::RandomSource;
+
+/// Machine cycles in a 60 Hz frame.
+pub const CYCLES_PER_FRAME: u32 = 3668;
+
+/// Fetching and dispatching any instruction, before it does anything.
+const FETCH: u32 = 40;
+/// Extra cycles for a skip that's taken.
+const SKIP_TAKEN: u32 = 4;
+
+/// The cost of the instruction at the machine's PC, given its registers and
+/// keys as they are now. Opcodes the interpreter doesn't know cost the
+/// fetch alone.
+pub fn next_cycles<R: RandomSource>(chip8: &Chip8<R>) -> u32 {
+    let opcode = chip8.peek_opcode(chip8.pc);
+    FETCH
+        + decode(opcode).map_or(0, |instruction| instruction_cycles(instruction, &chip8.v, &chip8.keys))
+}
+
+/// The cost of `instruction` after the fetch, with registers `v` and keypad
+/// `keys`.
+pub fn instruction_cycles(instruction: Instruction, v: &[u8; 16], keys: &[bool; 16]) -> u32 {
+    let skip = |taken: bool, cost: u32| if taken { cost + SKIP_TAKEN } else { cost };
+    let key = |x: usize| keys.get(v[x] as usize).copied().unwrap_or(false);
+    match instruction {
+        // The clear loops over all 256 bytes of the display.
+        Instruction::ClearScreen => 3078,
+        Instruction::Return => 10,
+        // SUPER-CHIP's and XO-CHIP's, which the VIP never ran; a nominal
+        // cost.
+        Instruction::ScrollDown(_)
+        | Instruction::ScrollUp(_)
+        | Instruction::ScrollRight
+        | Instruction::ScrollLeft
+        | Instruction::Exit
+        | Instruction::LowRes
+        | Instruction::HighRes
+        | Instruction::SelectPlanes(_)
+        | Instruction::SetPitch { .. } => 10,
+        Instruction::Jump(_) => 12,
+        Instruction::Call(_) => 26,
+        Instruction::SkipIfEq { x, nn } => skip(v[x] == nn, 10),
+        Instruction::SkipIfNe { x, nn } => skip(v[x] != nn, 10),
+        Instruction::SkipIfRegEq { x, y } => skip(v[x] == v[y], 14),
+        Instruction::SkipIfRegNe { x, y } => skip(v[x] != v[y], 14),
+        Instruction::SetVx { .. } => 6,
+        Instruction::AddVx { .. } => 10,
+        Instruction::Copy { .. } => 12,
+        // The 8XY_ arithmetic runs through a routine built in RAM.
+        Instruction::Or { .. }
+        | Instruction::And { .. }
+        | Instruction::Xor { .. }
+        | Instruction::AddReg { .. }
+        | Instruction::SubReg { .. }
+        | Instruction::ShiftRight { .. }
+        | Instruction::SubReverse { .. }
+        | Instruction::ShiftLeft { .. } => 44,
+        Instruction::SetIndex(_) => 12,
+        // XO-CHIP's, priced as an ANNN with a second fetch.
+        Instruction::LongIndex => 12 + FETCH,
+        Instruction::JumpOffset(nnn) => {
+            let crosses_page = (nnn + v[0] as u16) & 0xF00 != nnn & 0xF00;
+            if crosses_page { 24 } else { 22 }
+        }
+        Instruction::Random { .. } => 36,
+        Instruction::Draw { x, n, .. } => draw_cycles(v[x], n),
+        Instruction::SkipIfKey { x } => skip(key(x), 14),
+        Instruction::SkipIfNotKey { x } => skip(!key(x), 14),
+        Instruction::GetDelay { .. } | Instruction::SetDelay { .. } | Instruction::SetSound { .. } => 10,
+        // Each check for a key; waiting takes as many as it takes.
+        Instruction::WaitKey { .. } => 10,
+        Instruction::AddIndex { .. } | Instruction::FontChar { .. } | Instruction::BigFontChar { .. } => 16,
+        // The VIP counts each digit up one subtraction at a time.
+        Instruction::Bcd { x } => {
+            let value = v[x] as u32;
+            80 + 16 * (value / 100 + value / 10 % 10 + value % 10)
+        }
+        Instruction::StoreRegs { x } | Instruction::LoadRegs { x } => 14 + 14 * (x as u32 + 1),
+        // SUPER-CHIP's again, priced like the memory copies.
+        Instruction::StoreFlags { x } | Instruction::LoadFlags { x } => 14 + 14 * (x as u32 + 1),
+        Instruction::LoadAudio => 14 + 14 * 16,
+        // XO-CHIP's, priced like FX55 and FX65 for the same registers.
+        Instruction::StoreRange { x, y } | Instruction::LoadRange { x, y } => 14 + 14 * (x.abs_diff(y) as u32 + 1),
+    }
+}
+
+/// DXYN: setting up, then each of the N rows, which costs more when the
+/// sprite straddles two display bytes and has to be shifted into place a bit
+/// at a time.
+fn draw_cycles(vx: u8, n: u8) -> u32 {
+    let shift = (vx % 8) as u32;
+    let row = if shift == 0 { 30 } else { 46 + 8 * shift };
+    26 + n as u32 * row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V: [u8; 16] = [0; 16];
+    const KEYS: [bool; 16] = [false; 16];
+
+    #[test]
+    fn draws_cost_more_than_arithmetic_and_grow_with_the_sprite() {
+        let alu = instruction_cycles(Instruction::AddReg { x: 0, y: 1 }, &V, &KEYS);
+        let draw = |vx: u8, n: u8| {
+            let mut v = V;
+            v[0] = vx;
+            instruction_cycles(Instruction::Draw { x: 0, y: 1, n }, &v, &KEYS)
+        };
+        assert!(instruction_cycles(Instruction::SetVx { x: 0, nn: 1 }, &V, &KEYS) < alu);
+        assert!(alu < draw(0, 1));
+        assert_eq!(draw(0, 2) - draw(0, 1), draw(0, 3) - draw(0, 2));
+        assert!(draw(3, 5) > draw(8, 5));
+        assert_eq!(draw(8, 5), draw(16, 5));
+        assert_eq!(draw(0, 0), 26);
+    }
+
+    #[test]
+    fn taken_skips_and_page_crossings_cost_extra() {
+        let mut v = V;
+        v[1] = 7;
+        let not_taken = instruction_cycles(Instruction::SkipIfEq { x: 1, nn: 6 }, &v, &KEYS);
+        let taken = instruction_cycles(Instruction::SkipIfEq { x: 1, nn: 7 }, &v, &KEYS);
+        assert_eq!(taken, not_taken + SKIP_TAKEN);
+
+        let mut keys = KEYS;
+        keys[7] = true;
+        assert_eq!(instruction_cycles(Instruction::SkipIfKey { x: 1 }, &v, &keys), 14 + SKIP_TAKEN);
+        assert_eq!(instruction_cycles(Instruction::SkipIfNotKey { x: 1 }, &v, &keys), 14);
+
+        v[0] = 0x20;
+        assert_eq!(instruction_cycles(Instruction::JumpOffset(0x2C0), &v, &KEYS), 22);
+        assert_eq!(instruction_cycles(Instruction::JumpOffset(0x2F0), &v, &KEYS), 24);
+    }
+
+    #[test]
+    fn bcd_and_block_copies_scale_with_their_work() {
+        let mut v = V;
+        v[2] = 199;
+        assert_eq!(instruction_cycles(Instruction::Bcd { x: 2 }, &v, &KEYS), 80 + 16 * 19);
+        assert_eq!(instruction_cycles(Instruction::Bcd { x: 0 }, &v, &KEYS), 80);
+        assert_eq!(instruction_cycles(Instruction::LoadRegs { x: 0xF }, &V, &KEYS), 14 + 14 * 16);
+    }
+
+    #[test]
+    fn the_next_instruction_includes_the_fetch() {
+        let mut chip8 = Chip8::with_rng(crate::Rng::new(1));
+        // 200: 6001  V0 = 1   202: 0123  not an instruction
+        chip8.load_rom_bytes(&[0x60, 0x01, 0x01, 0x23]).unwrap();
+        assert_eq!(next_cycles(&chip8), FETCH + 6);
+        chip8.pc = 0x202;
+        assert_eq!(next_cycles(&chip8), FETCH);
+    }
+}
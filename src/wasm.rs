@@ -0,0 +1,120 @@
+//! JavaScript bindings for running the interpreter in a web page.
+//!
+//! The page owns the frame loop: call [`WasmChip8::run_frame`] from
+//! `requestAnimationFrame`, draw [`WasmChip8::display_rgba`] into an
+//! `ImageData`, and start or stop a tone when [`WasmChip8::sound_active`]
+//! changes.
+//!
+//! Build the module with
+//! `cargo rustc --lib --release --target wasm32-unknown-unknown --no-default-features --features wasm --crate-type cdylib`
+//! and generate the JavaScript glue with `wasm-bindgen --target web` on the
+//! resulting `.wasm` file.
+
+use wasm_bindgen::prelude::*;
+
+use crate::chip8::Chip8;
+use crate::frontend::{DEFAULT_INSTRUCTIONS_PER_FRAME, Palette, PixelFormat};
+
+/// A machine with a ROM loaded, driven one 60 Hz frame at a time.
+#[wasm_bindgen]
+pub struct WasmChip8 {
+    chip8: Chip8,
+    instructions_per_frame: u32,
+    /// Why the machine stopped, once it has.
+    halted: Option<String>,
+}
+
+#[wasm_bindgen]
+impl WasmChip8 {
+    /// Creates a machine running `rom`, passed from JavaScript as a
+    /// `Uint8Array`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(rom: &[u8]) -> Result<WasmChip8, JsError> {
+        let mut chip8 = Chip8::new();
+        chip8.load_rom_bytes(rom)?;
+
+        Ok(Self {
+            chip8,
+            instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME,
+            halted: None,
+        })
+    }
+
+    /// Reseeds the CXNN random generator so runs can be reproduced.
+    pub fn seed(&mut self, seed: u64) {
+        self.chip8.seed_rng(seed);
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn instructions_per_frame(&self) -> u32 {
+        self.instructions_per_frame
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_instructions_per_frame(&mut self, count: u32) {
+        self.instructions_per_frame = count;
+    }
+
+    /// Runs one frame with the keypad state `keys`, where bit N is set while
+    /// key N is held, then ticks the timers.
+    ///
+    /// Once the machine has stopped on an error every call fails with it.
+    pub fn run_frame(&mut self, keys: u16) -> Result<(), JsError> {
+        if let Some(message) = &self.halted {
+            return Err(JsError::new(message));
+        }
+
+        for (index, key) in self.chip8.keys.iter_mut().enumerate() {
+            *key = keys & (1 << index) != 0;
+        }
+        if let Err(e) = self.chip8.emulate_frames(1, self.instructions_per_frame) {
+            let message = e.to_string();
+            self.halted = Some(message.clone());
+            return Err(JsError::new(&message));
+        }
+
+        Ok(())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> usize {
+        self.chip8.display.width()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> usize {
+        self.chip8.display.height()
+    }
+
+    /// The display as RGBA bytes, row by row, ready to wrap in a
+    /// `Uint8ClampedArray` for `ImageData`.
+    pub fn display_rgba(&self) -> Vec<u8> {
+        let mut rgba = vec![0; PixelFormat::Rgba8888.buffer_len(self.chip8.display.pixels().len())];
+        self.chip8.render_into(Palette::default(), &mut rgba, PixelFormat::Rgba8888);
+        rgba
+    }
+
+    /// Whether the buzzer should be sounding.
+    pub fn sound_active(&self) -> bool {
+        self.chip8.sound_timer > 0
+    }
+
+    /// Whether the machine has stopped on an error.
+    #[wasm_bindgen(getter)]
+    pub fn halted(&self) -> bool {
+        self.halted.is_some()
+    }
+
+    /// A snapshot of the machine, see [`Chip8::save_state`].
+    pub fn save_state(&self) -> Vec<u8> {
+        self.chip8.save_state()
+    }
+
+    /// Restores a snapshot from [`WasmChip8::save_state`], which also clears
+    /// a previous halt.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), JsError> {
+        self.chip8.load_state(data)?;
+        self.halted = None;
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+//! Watchpoints: addresses that stop the machine when an instruction reads or
+//! writes them, for finding out what clobbers a byte. See
+//! [`Chip8::add_watchpoint`](crate::Chip8::add_watchpoint).
+//!
+//! Only instructions' accesses count: FX33, FX55, FX65, 5XY2, 5XY3, the
+//! sprite DXYN draws and F002's pattern. Fetching instructions, and code
+//! poking `memory` directly, don't.
+
+use core::fmt;
+use core::ops::Range;
+
+/// Which way an access went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// A range of addresses to watch for one kind of access.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub addrs: Range<usize>,
+    pub access: Access,
+}
+
+impl Watchpoint {
+    /// A watchpoint on `text`, a hex address such as `0x6F0` or a range
+    /// such as `0x6F0..0x700`, not including its end.
+    pub fn parse(text: &str, access: Access) -> Option<Self> {
+        let addr = |text: &str| {
+            let digits = text.trim().trim_start_matches("0x").trim_start_matches("0X");
+            u16::from_str_radix(digits, 16).ok().map(usize::from)
+        };
+        let addrs = match text.split_once("..") {
+            Some((start, end)) => addr(start)?..addr(end)?,
+            None => addr(text).map(|addr| addr..addr + 1)?,
+        };
+        (!addrs.is_empty()).then_some(Self { addrs, access })
+    }
+
+    /// Whether an `access` to `addr` trips this watchpoint.
+    pub fn watches(&self, addr: usize, access: Access) -> bool {
+        self.access == access && self.addrs.contains(&addr)
+    }
+}
+
+/// The first watched access an instruction made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit {
+    /// Where the instruction was.
+    pub pc: u16,
+    pub opcode: u16,
+    pub addr: u16,
+    pub access: Access,
+    /// The byte before the access, the same as `new` for a read.
+    pub old: u8,
+    pub new: u8,
+}
+
+/// `204: F633 wrote 0x6F0, 0x00 -> 0x02`, or `206: F265 read 0x6F0, 0x02`.
+impl fmt::Display for WatchHit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:03X}: {:04X} ", self.pc, self.opcode)?;
+        match self.access {
+            Access::Read => write!(f, "read 0x{:03X}, 0x{:02X}", self.addr, self.new),
+            Access::Write => write!(f, "wrote 0x{:03X}, 0x{:02X} -> 0x{:02X}", self.addr, self.old, self.new),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watchpoints_are_an_address_or_a_range() {
+        let single = Watchpoint::parse("0x6F0", Access::Write).unwrap();
+        assert_eq!(single.addrs, 0x6F0..0x6F1);
+        assert_eq!(Watchpoint::parse("6f0..0x700", Access::Read).unwrap().addrs, 0x6F0..0x700);
+        assert!(single.watches(0x6F0, Access::Write) && !single.watches(0x6F0, Access::Read));
+
+        for bad in ["", "0x", "xyz", "0x700..0x6F0", "0x10000"] {
+            assert_eq!(Watchpoint::parse(bad, Access::Write), None, "{bad}");
+        }
+    }
+}
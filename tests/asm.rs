@@ -0,0 +1,105 @@
+//! The assembler, mostly by round trips through the disassembler.
+#![cfg(feature = "alloc")]
+
+use chip8_emulator::{AsmError, PROGRAM_START, assemble, disassemble};
+
+/// The mnemonics of a listing, without the addresses and opcodes.
+fn mnemonics(listing: &[String]) -> Vec<&str> {
+    listing.iter().map(|line| line[11..].trim_end()).collect()
+}
+
+#[test]
+fn the_disassembly_snapshot_assembles_back_to_its_rom() {
+    let listing = include_str!("snapshots/disasm.txt");
+    let source: Vec<&str> = listing.lines().map(|line| &line[11..]).collect();
+    assert_eq!(assemble(&source.join("\n"), PROGRAM_START).unwrap(), include_bytes!("fixtures/disasm.ch8"));
+}
+
+#[test]
+fn every_mnemonic_survives_a_round_trip() {
+    let source = "\
+CLS
+RET
+SCD 3
+SCU 15
+SCR
+SCL
+EXIT
+LOW
+HIGH
+JP 0x2AB
+CALL 0xFFF
+SE V1, 0x7F
+SNE VF, 0x00
+SE V2, V3
+SNE V4, V5
+SAVE V1 - V6
+LOAD VE - V0
+LD V9, 0xFF
+ADD V8, 0x01
+LD V1, V2
+OR V1, V2
+AND V1, V2
+XOR V1, V2
+ADD V1, V2
+SUB V1, V2
+SHR V1, V2
+SUBN V1, V2
+SHL V1, V2
+LD I, 0x300
+LD I, 0xFFFF
+JP V0, 0x400
+RND VA, 0x0F
+DRW V0, V1, 0
+SKP V3
+SKNP V3
+PLANE 3
+LD V2, DT
+LD V2, K
+LD DT, V2
+LD ST, V2
+AUDIO
+PITCH V6
+ADD I, V2
+LD F, V2
+LD HF, V2
+LD B, V2
+LD [I], VF
+LD VF, [I]
+LD R, V7
+LD V7, R";
+    let listing = disassemble(&assemble(source, PROGRAM_START).unwrap(), PROGRAM_START);
+    assert_eq!(mnemonics(&listing), source.lines().collect::<Vec<_>>());
+}
+
+#[test]
+fn labels_resolve_forwards_and_backwards() {
+    let source = "\
+start:  LD I, sprite   ; forward
+        DRW V0, V0, 1
+loop:   JP loop
+        CALL start
+sprite: db 0b10000001, 0xFF, 7";
+    let rom = assemble(source, PROGRAM_START).unwrap();
+    assert_eq!(
+        mnemonics(&disassemble(&rom[..8], PROGRAM_START)),
+        ["LD I, 0x208", "DRW V0, V0, 1", "JP 0x204", "CALL 0x200"]
+    );
+    assert_eq!(rom[8..], [0x81, 0xFF, 0x07]);
+}
+
+#[test]
+fn long_loads_take_four_bytes_and_move_later_labels() {
+    let rom = assemble("LD I, long end\nLD I, 0x1234\nend: CLS", 0x200).unwrap();
+    assert_eq!(rom, [0xF0, 0x00, 0x02, 0x08, 0xF0, 0x00, 0x12, 0x34, 0x00, 0xE0]);
+}
+
+#[test]
+fn errors_say_which_line() {
+    let error = |source: &str| assemble(source, PROGRAM_START).unwrap_err();
+    assert_eq!(error("CLS\n\n  LD V0, 0x100"), AsmError { line: 3, message: "0x100 is more than 0xFF".to_string() });
+    assert_eq!(error("JP nowhere").line, 1);
+    assert_eq!(error("CLS\nFROB V1").line, 2);
+    assert_eq!(error("a: CLS\na: CLS").to_string(), "line 2: a is defined twice");
+    assert_eq!(error("DRW V0, V1").message, "unknown instruction DRW with 2 operands");
+}
@@ -0,0 +1,50 @@
+#!/bin/sh
+# Builds every supported feature combination, so a change that only breaks
+# one of them doesn't go unnoticed. Run from anywhere: `sh tests/build_matrix.sh`.
+#
+# Library combinations are built and tested; backend combinations are only
+# type-checked, since linking them needs system libraries (SDL2, ALSA, a GPU
+# driver) that may not be installed.
+set -eu
+
+cd "$(dirname "$0")/.."
+
+run() {
+    echo "==> cargo $*"
+    cargo "$@"
+}
+
+# The interpreter alone, headless.
+run build --lib --no-default-features
+run build --lib --no-default-features --features alloc
+run build --lib --no-default-features --features std
+run test --no-default-features
+run test --no-default-features --features alloc
+run test --no-default-features --features std
+
+# Bare metal, if the target is installed.
+if rustup target list --installed 2>/dev/null | grep -qx thumbv7em-none-eabihf; then
+    run build --lib --no-default-features --target thumbv7em-none-eabihf
+    run build --lib --no-default-features --features alloc --target thumbv7em-none-eabihf
+fi
+
+# Each backend on its own, then everything together.
+run check --all-targets
+for backend in frontend-minifb backend-sdl2 backend-terminal backend-pixels; do
+    run check --all-targets --no-default-features --features "$backend"
+done
+run check --all-targets --no-default-features --features "frontend-minifb audio-rodio"
+run check --all-targets --all-features
+
+# The C API, including its integration test against the shared library.
+run test --no-default-features --features ffi
+
+# Rhai scripting, off by default.
+run test --lib --features scripting scripting
+
+# The browser bindings.
+if rustup target list --installed 2>/dev/null | grep -qx wasm32-unknown-unknown; then
+    run check --lib --no-default-features --features wasm --target wasm32-unknown-unknown
+fi
+
+echo "All feature combinations build."
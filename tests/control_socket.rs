@@ -0,0 +1,210 @@
+//! Drives a running frame loop over the control socket, as an external tool
+//! would.
+#![cfg(feature = "control-socket")]
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use serde_json::{Value, json};
+
+use chip8_emulator::Chip8;
+use chip8_emulator::control_socket;
+use chip8_emulator::frontend::control;
+use chip8_emulator::frontend::{Driver, Frame, InputEvent, InputSource, NullAudio, VideoSink};
+
+/// 200: 6005  V0 = 5
+/// 202: 7001  V0 += 1
+/// 204: 1202  jump to 202
+const COUNTER: [u8; 6] = [0x60, 0x05, 0x70, 0x01, 0x12, 0x02];
+
+/// Paces the loop a little, the way vsync would.
+struct Throttle;
+
+impl VideoSink for Throttle {
+    fn present(&mut self, _frame: &Frame<'_>) {
+        thread::sleep(Duration::from_millis(2));
+    }
+}
+
+struct StopFlag(Arc<AtomicBool>);
+
+impl InputSource for StopFlag {
+    fn drain_events(&mut self) -> Vec<InputEvent> {
+        if self.0.load(Ordering::Relaxed) {
+            vec![InputEvent::Quit]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+struct Emulator {
+    port: u16,
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+impl Emulator {
+    fn start(program: &[u8]) -> Self {
+        let (controller, receiver) = control::channel();
+        let port = control_socket::spawn("127.0.0.1:0", controller).unwrap().port();
+
+        let mut chip8 = Chip8::new();
+        chip8.load_rom_bytes(program).unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut input = StopFlag(Arc::clone(&stop));
+        let thread = thread::spawn(move || {
+            let mut driver = Driver::new();
+            driver.attach_control(receiver);
+            let _ = driver.run(&mut chip8, &mut Throttle, &mut input, &mut NullAudio);
+        });
+        Self { port, stop, thread }
+    }
+
+    fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.thread.join().unwrap();
+    }
+}
+
+/// A connection that sets events aside while waiting for responses.
+struct Client {
+    writer: TcpStream,
+    reader: BufReader<TcpStream>,
+    events: VecDeque<Value>,
+    next_id: u64,
+}
+
+impl Client {
+    fn connect(port: u16) -> Self {
+        let writer = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        writer.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+        let reader = BufReader::new(writer.try_clone().unwrap());
+        Self { writer, reader, events: VecDeque::new(), next_id: 0 }
+    }
+
+    fn read_line(&mut self) -> Value {
+        let mut line = String::new();
+        self.reader.read_line(&mut line).unwrap();
+        serde_json::from_str(&line).unwrap_or_else(|e| panic!("{e}: {line:?}"))
+    }
+
+    fn send_line(&mut self, line: &str) -> Value {
+        writeln!(self.writer, "{line}").unwrap();
+        loop {
+            let line = self.read_line();
+            if line.get("event").is_some() {
+                self.events.push_back(line);
+            } else {
+                return line;
+            }
+        }
+    }
+
+    /// Sends `request` with a fresh id and returns the matching response.
+    fn request(&mut self, mut request: Value) -> Value {
+        self.next_id += 1;
+        request["id"] = json!(self.next_id);
+        let response = self.send_line(&request.to_string());
+        assert_eq!(response["id"], json!(self.next_id), "{response}");
+        response
+    }
+
+    fn ok(&mut self, request: Value) -> Value {
+        let response = self.request(request);
+        assert_eq!(response["ok"], json!(true), "{response}");
+        response
+    }
+
+    /// Waits for the next event named `name`, dropping others.
+    fn event(&mut self, name: &str) -> Value {
+        loop {
+            let event = self.events.pop_front().unwrap_or_else(|| self.read_line());
+            if event["event"] == name {
+                return event;
+            }
+        }
+    }
+}
+
+#[test]
+fn external_client_debugs_a_running_machine() {
+    let emulator = Emulator::start(&COUNTER);
+    let mut client = Client::connect(emulator.port);
+
+    client.ok(json!({ "cmd": "pause" }));
+    let registers = client.ok(json!({ "cmd": "registers" }))["registers"].clone();
+    assert_eq!(registers["paused"], json!(true));
+
+    client.ok(json!({ "cmd": "reset" }));
+    assert_eq!(client.ok(json!({ "cmd": "step" }))["pc"], json!(0x202));
+    let memory = client.ok(json!({ "cmd": "read-memory", "addr": 0x200, "len": 6 }));
+    assert_eq!(memory["memory"], json!(COUNTER));
+
+    // Run into a breakpoint, then past it to the same one again.
+    client.ok(json!({ "cmd": "subscribe", "events": ["breakpoint", "frame"] }));
+    client.ok(json!({ "cmd": "set-breakpoint", "addr": 0x204 }));
+    client.ok(json!({ "cmd": "resume" }));
+    assert_eq!(client.event("breakpoint")["pc"], json!(0x204));
+    let registers = client.ok(json!({ "cmd": "registers" }))["registers"].clone();
+    assert_eq!(registers["pc"], json!(0x204));
+    assert_eq!(registers["v"][0], json!(6));
+    assert_eq!(registers["paused"], json!(true));
+
+    client.ok(json!({ "cmd": "resume" }));
+    assert_eq!(client.event("breakpoint")["pc"], json!(0x204));
+    let registers = client.ok(json!({ "cmd": "registers" }))["registers"].clone();
+    assert_eq!(registers["v"][0], json!(7));
+
+    let frame = client.event("frame");
+    assert_eq!(frame["display_hash"].as_str().map(str::len), Some(16), "{frame}");
+
+    // Keys are checked, and a reset goes back to the machine as loaded.
+    assert_eq!(client.request(json!({ "cmd": "press", "key": 16 }))["ok"], json!(false));
+    client.ok(json!({ "cmd": "press", "key": 3 }));
+    client.ok(json!({ "cmd": "release", "key": 3 }));
+    client.ok(json!({ "cmd": "reset" }));
+    let registers = client.ok(json!({ "cmd": "registers" }))["registers"].clone();
+    assert_eq!(registers["pc"], json!(0x200));
+    assert_eq!(registers["v"][0], json!(0));
+
+    // A new program that halts straight away.
+    client.ok(json!({ "cmd": "clear-breakpoint", "addr": 0x204 }));
+    client.ok(json!({ "cmd": "subscribe", "events": ["halt"] }));
+    client.ok(json!({ "cmd": "load-rom", "data": [0x00, 0xEE] }));
+    client.ok(json!({ "cmd": "resume" }));
+    let halt = client.event("halt");
+    assert!(halt["error"].as_str().unwrap().contains("stack underflow"), "{halt}");
+    let registers = client.ok(json!({ "cmd": "registers" }))["registers"].clone();
+    assert!(registers["halted"].is_string(), "{registers}");
+
+    // Bad requests get an error and leave the connection usable.
+    let response = client.send_line("{\"cmd\": \"jump\"}");
+    assert_eq!(response["ok"], json!(false));
+    assert!(response["error"].as_str().unwrap().starts_with("invalid request"));
+    let response = client.request(json!({ "cmd": "load-rom" }));
+    assert_eq!(response["ok"], json!(false));
+    client.ok(json!({ "cmd": "pause" }));
+
+    emulator.stop();
+    let response = client.request(json!({ "cmd": "registers" }));
+    assert_eq!(response["error"], json!("the emulator is no longer running"));
+}
+
+#[test]
+fn out_of_range_memory_reads_are_refused() {
+    let emulator = Emulator::start(&COUNTER);
+    let mut client = Client::connect(emulator.port);
+
+    let response = client.request(json!({ "cmd": "read-memory", "addr": 0xFFE, "len": 4 }));
+    assert_eq!(response["ok"], json!(false), "{response}");
+    let response = client.ok(json!({ "cmd": "read-memory", "addr": 0xFFC, "len": 4 }));
+    assert_eq!(response["memory"], json!([0, 0, 0, 0]));
+
+    emulator.stop();
+}
@@ -0,0 +1,23 @@
+//! The disassembler's listing, against a fixture ROM with code, data, an
+//! XO-CHIP long load and an odd byte at the end.
+#![cfg(feature = "alloc")]
+
+use chip8_emulator::{Instruction, PROGRAM_START, disassemble};
+
+#[test]
+fn the_fixture_lists_as_the_snapshot() {
+    let rom = include_bytes!("fixtures/disasm.ch8");
+    assert_eq!(disassemble(rom, PROGRAM_START).join("\n"), include_str!("snapshots/disasm.txt").trim_end());
+}
+
+#[test]
+fn listings_start_where_the_rom_is_loaded() {
+    assert_eq!(disassemble(&[0x00, 0xE0], 0x600), ["600: 00E0  CLS"]);
+    assert!(disassemble(&[], PROGRAM_START).is_empty());
+}
+
+#[test]
+fn a_long_load_cut_short_shows_no_address() {
+    assert_eq!(disassemble(&[0xF0, 0x00, 0x12], PROGRAM_START), ["200: F000  LD I, long", "202: 12    .byte 0x12"]);
+    assert_eq!(Instruction::LongIndex.to_string(), "LD I, long");
+}
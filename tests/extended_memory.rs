@@ -0,0 +1,50 @@
+//! XO-CHIP's 64 KB, through a ROM too large for 4 KB that keeps data past
+//! 0x1000 and writes more higher up.
+#![cfg(feature = "std")]
+
+use chip8_emulator::{Chip8, Chip8Error, LoadError, Quirks, Rng};
+
+/// 200: F000 2000  I = 0x2000
+/// 204: 6A5A       VA = 0x5A
+/// 206: 6BA5       VB = 0xA5
+/// 208: 5AB2       save VA..=VB at I
+/// 20A: F000 1000  I = 0x1000
+/// 20E: F165       load V0..=V1 from I
+/// 210: 3042       skip the next instruction if V0 = 0x42
+/// 212: F000 0000  I = 0, all four bytes skipped
+/// 216: 1216       jump to self
+/// 1000: 42 99     the data
+const ROM: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/extended_memory.ch8");
+
+#[test]
+fn xo_chip_programs_reach_past_0x1000() {
+    let mut chip8 = Chip8::with_rng(Rng::new(1));
+    chip8.quirks = Quirks::xochip();
+    chip8.load_rom(ROM).unwrap();
+    for _ in 0..8 {
+        chip8.step().unwrap();
+    }
+
+    assert_eq!(chip8.memory[0x2000..0x2002], [0x5A, 0xA5]);
+    assert_eq!((chip8.v[0], chip8.v[1]), (0x42, 0x99));
+    assert_eq!(chip8.i, 0x1002, "the skip stepped over the whole F000 0000");
+    assert_eq!(chip8.finished(), Some(0x216));
+}
+
+#[test]
+fn classic_machines_keep_to_4_kb() {
+    let mut chip8 = Chip8::with_rng(Rng::new(1));
+    assert!(matches!(
+        chip8.load_rom(ROM),
+        Err(Chip8Error::Load(LoadError::TooLarge { size: 0xE02, max: 0xE00, .. }))
+    ));
+
+    // F000 still loads I, but only within the 4 KB there is.
+    chip8.load_rom_bytes(&[0xF0, 0x00, 0x21, 0x23, 0xFF, 0x1E]).unwrap();
+    chip8.v[0xF] = 0xFF;
+    chip8.step().unwrap();
+    assert_eq!((chip8.i, chip8.pc), (0x123, 0x204));
+    chip8.i = 0xFFF;
+    chip8.step().unwrap();
+    assert_eq!(chip8.i, 0x0FE);
+}
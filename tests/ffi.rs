@@ -0,0 +1,90 @@
+//! The C API from C: builds the shared library, compiles `tests/ffi/smoke.c`
+//! against `include/chip8.h` and runs it.
+//!
+//! The C part is skipped, with a note, when there's no C compiler (`$CC`,
+//! else `cc`).
+#![cfg(all(feature = "ffi", unix))]
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const GENERATED_HEADER: &str = include_str!(concat!(env!("OUT_DIR"), "/chip8.h"));
+
+fn manifest_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+#[test]
+fn checked_in_header_is_current() {
+    let checked_in = fs::read_to_string(manifest_dir().join("include/chip8.h")).unwrap();
+    assert!(
+        checked_in == GENERATED_HEADER,
+        "include/chip8.h is out of date; copy it from {}/chip8.h",
+        env!("OUT_DIR")
+    );
+}
+
+/// Builds the crate as a shared library, returning the directory it's in.
+fn build_cdylib() -> PathBuf {
+    // A separate target directory, so this doesn't wait on the lock held by
+    // the `cargo test` running it.
+    let target_dir = manifest_dir().join("target").join("ffi");
+    let output = Command::new(env!("CARGO"))
+        .args(["rustc", "--lib", "--no-default-features", "--features", "ffi"])
+        .args(["--crate-type", "cdylib"])
+        .arg("--manifest-path")
+        .arg(manifest_dir().join("Cargo.toml"))
+        .env("CARGO_TARGET_DIR", &target_dir)
+        .output()
+        .expect("failed to run cargo");
+    assert!(
+        output.status.success(),
+        "building the shared library failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    target_dir.join("debug")
+}
+
+#[test]
+fn c_program_runs_against_the_shared_library() {
+    let cc = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    if Command::new(&cc).arg("--version").output().is_err() {
+        eprintln!("skipping: no C compiler ({cc})");
+        return;
+    }
+
+    let lib_dir = build_cdylib();
+    let exe = lib_dir.join("ffi_smoke");
+    let output = Command::new(&cc)
+        .arg(manifest_dir().join("tests/ffi/smoke.c"))
+        .arg("-I")
+        .arg(manifest_dir().join("include"))
+        .args(["-std=c99", "-Wall", "-Werror", "-o"])
+        .arg(&exe)
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lchip8_emulator")
+        .output()
+        .expect("failed to run the C compiler");
+    assert!(
+        output.status.success(),
+        "compiling smoke.c failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // `cargo test` puts its own deps directory on the library path, ahead of
+    // the rpath, and a stale copy of the library there would win.
+    let output = Command::new(&exe)
+        .env("LD_LIBRARY_PATH", &lib_dir)
+        .output()
+        .expect("failed to run the C program");
+    assert!(
+        output.status.success(),
+        "smoke.c failed:\n{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
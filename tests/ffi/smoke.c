@@ -0,0 +1,69 @@
+/* Drives the C API the way an embedding application would. Built and run by
+ * tests/ffi.rs; exits non-zero on the first failed check. */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "chip8.h"
+
+#define CHECK(condition)                                                  \
+    do {                                                                  \
+        if (!(condition)) {                                               \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__,        \
+                    __LINE__, #condition);                                \
+            return 1;                                                     \
+        }                                                                 \
+    } while (0)
+
+int main(void) {
+    /* 200: 6005  V0 = 5
+     * 202: 6103  V1 = 3
+     * 204: F029  I = font sprite for V0
+     * 206: D115  draw it at (V1, V1)
+     * 208: E0A1  skip the next instruction unless key V0 is down
+     * 20A: F118  sound timer = V1
+     * 20C: 1208  jump to 208 */
+    static const uint8_t program[] = {
+        0x60, 0x05, 0x61, 0x03, 0xF0, 0x29, 0xD1, 0x15,
+        0xE0, 0xA1, 0xF1, 0x18, 0x12, 0x08,
+    };
+    uint8_t display[CHIP8_DISPLAY_WIDTH * CHIP8_DISPLAY_HEIGHT];
+
+    Chip8Machine *machine = chip8_new();
+    CHECK(machine != NULL);
+    CHECK(chip8_seed(machine, 42) == CHIP8_STATUS_OK);
+    CHECK(chip8_set_quirks(machine, CHIP8_QUIRK_SHIFT_USES_VY) == CHIP8_STATUS_OK);
+    CHECK(chip8_load_rom(machine, program, sizeof program) == CHIP8_STATUS_OK);
+
+    CHECK(chip8_run_frame(machine, 0) == CHIP8_STATUS_OK);
+    CHECK(!chip8_sound_active(machine));
+    CHECK(chip8_run_frame(machine, 1 << 5) == CHIP8_STATUS_OK);
+    CHECK(chip8_sound_active(machine));
+
+    /* The top row of the "5" glyph is 0xF0, drawn at (3, 3). */
+    memset(display, 0xAA, sizeof display);
+    CHECK(chip8_display(machine, display, sizeof display) == CHIP8_STATUS_OK);
+    for (int x = 0; x < CHIP8_DISPLAY_WIDTH; x++) {
+        int lit = x >= 3 && x < 7;
+        CHECK(display[3 * CHIP8_DISPLAY_WIDTH + x] == lit);
+    }
+    CHECK(display[0] == 0);
+    CHECK(chip8_display(machine, display, 16) == CHIP8_STATUS_INVALID_ARGUMENT);
+
+    /* Errors come back as statuses, and null is never dereferenced. */
+    static const uint8_t underflow[] = {0x00, 0xEE};
+    Chip8Machine *returns = chip8_new();
+    CHECK(returns != NULL);
+    CHECK(chip8_load_rom(returns, underflow, sizeof underflow) == CHIP8_STATUS_OK);
+    CHECK(chip8_step(returns) == CHIP8_STATUS_STACK_UNDERFLOW);
+    chip8_free(returns);
+    CHECK(chip8_step(NULL) == CHIP8_STATUS_NULL_POINTER);
+    CHECK(chip8_load_rom(machine, NULL, 2) == CHIP8_STATUS_NULL_POINTER);
+    CHECK(chip8_set_quirks(machine, 0x80000000u) == CHIP8_STATUS_INVALID_ARGUMENT);
+
+    chip8_free(machine);
+    chip8_free(NULL);
+
+    puts("ffi smoke test passed");
+    return 0;
+}
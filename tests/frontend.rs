@@ -0,0 +1,1303 @@
+//! Drives the shared frame loop with recording mocks instead of a window.
+#![cfg(feature = "std")]
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::thread;
+
+use chip8_emulator::frontend::clipboard::{Clipboard, ClipboardImage};
+use chip8_emulator::frontend::console::{ConsoleKey, ConsoleView};
+use chip8_emulator::frontend::control::{self, Command, Reply};
+use chip8_emulator::frontend::game_config::GameConfig;
+use chip8_emulator::frontend::help::{Binding, Group, HelpLine, HelpPage};
+use chip8_emulator::frontend::keypad_card::KeypadCard;
+use chip8_emulator::frontend::menu::{MenuKey, MenuView, THEMES};
+use chip8_emulator::frontend::registers::RegistersView;
+use chip8_emulator::frontend::speed::{Speed, SpeedLimits, Timing};
+use chip8_emulator::frontend::timer::{TimerStart, TimerView};
+use chip8_emulator::frontend::{
+    AudioCommand, AudioPattern, AudioSink, DEFAULT_INSTRUCTIONS_PER_FRAME, Frame, InputEvent, InputSource,
+    NullAudio, Palette, VideoSink, Driver, run_emulator,
+};
+use chip8_emulator::overlay::indicators::Indicators;
+use chip8_emulator::{AUDIO_PATTERN_BYTES, Access, Ascii, Chip8, Chip8Config, Chip8Error, DEFAULT_PITCH, Glyphs, OpcodePolicy, Snapshot, Watchpoint};
+
+/// What a [`MockVideo`] saw in one presented frame.
+#[derive(Debug, Clone, PartialEq)]
+struct Presented {
+    size: (usize, usize),
+    lit: Vec<usize>,
+    display_changed: bool,
+    still: bool,
+    instructions_per_frame: u32,
+    speed: Speed,
+    scale: Option<usize>,
+    paused: bool,
+    halted: bool,
+    finished: Option<u16>,
+    palette: Palette,
+    menu: Option<MenuView>,
+    help: Option<HelpPage>,
+    keypad_card: Option<KeypadCard>,
+    console: Option<ConsoleView>,
+    registers: Option<RegistersView>,
+    timer: Option<TimerView>,
+    notice: Option<String>,
+    escape_quits: bool,
+    indicators: Indicators,
+}
+
+#[derive(Default)]
+struct MockVideo {
+    frames: Vec<Presented>,
+    overlaid: bool,
+}
+
+impl VideoSink for MockVideo {
+    fn present(&mut self, frame: &Frame<'_>) {
+        assert_eq!(frame.pixels.len(), frame.width * frame.height);
+        self.frames.push(Presented {
+            size: (frame.width, frame.height),
+            lit: (0..frame.pixels.len()).filter(|&i| frame.pixels[i]).collect(),
+            display_changed: frame.display_changed,
+            still: frame.is_still(&mut self.overlaid),
+            instructions_per_frame: frame.instructions_per_frame,
+            speed: frame.speed,
+            scale: frame.scale,
+            paused: frame.paused,
+            halted: frame.halted.is_some(),
+            finished: frame.finished,
+            palette: frame.palette,
+            menu: frame.menu.cloned(),
+            help: frame.help.cloned(),
+            keypad_card: frame.keypad_card.cloned(),
+            console: frame.console.cloned(),
+            registers: frame.registers.cloned(),
+            timer: frame.timer.cloned(),
+            notice: frame.notice.map(str::to_string),
+            escape_quits: frame.escape_quits,
+            indicators: frame.indicators,
+        });
+    }
+}
+
+/// Hands out one batch of scripted events per frame, then quits.
+struct MockInput {
+    script: VecDeque<Vec<InputEvent>>,
+}
+
+impl MockInput {
+    fn new(script: Vec<Vec<InputEvent>>) -> Self {
+        Self { script: script.into() }
+    }
+
+    fn idle(frames: usize) -> Self {
+        Self::new(vec![Vec::new(); frames])
+    }
+}
+
+impl InputSource for MockInput {
+    fn drain_events(&mut self) -> Vec<InputEvent> {
+        self.script.pop_front().unwrap_or_else(|| vec![InputEvent::Quit])
+    }
+}
+
+/// Never reports anything, for tests that stop the loop themselves.
+struct NoInput;
+
+impl InputSource for NoInput {
+    fn drain_events(&mut self) -> Vec<InputEvent> {
+        Vec::new()
+    }
+}
+
+#[derive(Default)]
+struct MockAudio {
+    commands: Vec<AudioCommand>,
+}
+
+impl AudioSink for MockAudio {
+    fn command(&mut self, command: AudioCommand) {
+        self.commands.push(command);
+    }
+}
+
+fn machine(program: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.load_rom_bytes(program).unwrap();
+    chip8
+}
+
+fn run(
+    chip8: &mut Chip8,
+    input: &mut MockInput,
+) -> (Result<(), Chip8Error>, MockVideo, MockAudio) {
+    let mut video = MockVideo::default();
+    let mut audio = MockAudio::default();
+    let result = run_emulator(chip8, &mut video, input, &mut audio);
+    (result, video, audio)
+}
+
+// 200: A206  I = 0x206
+// 202: D011  draw the 1-row sprite at I at (V0, V1) = (0, 0)
+// 204: 1204  jump to self
+// 206: C0    sprite: two lit pixels
+const DRAW_PROGRAM: [u8; 7] = [0xA2, 0x06, 0xD0, 0x11, 0x12, 0x04, 0xC0];
+
+#[test]
+fn presents_one_frame_per_iteration_until_quit() {
+    let mut chip8 = machine(&DRAW_PROGRAM);
+    let (result, video, audio) = run(&mut chip8, &mut MockInput::idle(3));
+
+    assert!(result.is_ok());
+    assert_eq!(video.frames.len(), 3);
+    for frame in &video.frames {
+        assert_eq!(frame.lit, vec![0, 1]);
+        assert_eq!(frame.instructions_per_frame, DEFAULT_INSTRUCTIONS_PER_FRAME);
+        assert!(!frame.halted);
+    }
+    assert!(audio.commands.is_empty());
+}
+
+#[test]
+fn only_frames_after_a_change_say_the_display_changed() {
+    let mut chip8 = machine(&DRAW_PROGRAM);
+    let (_, video, _) = run(&mut chip8, &mut MockInput::idle(3));
+    let changed: Vec<bool> = video.frames.iter().map(|frame| frame.display_changed).collect();
+    assert_eq!(changed, [true, false, false]);
+}
+
+#[test]
+fn frames_stay_still_until_something_goes_over_the_display() {
+    let mut chip8 = machine(&DRAW_PROGRAM);
+    let mut script = vec![Vec::new(); 6];
+    script[2] = vec![InputEvent::Help];
+    script[3] = vec![InputEvent::Help];
+    let (_, video, _) = run(&mut chip8, &mut MockInput::new(script));
+    let still: Vec<bool> = video.frames.iter().map(|frame| frame.still).collect();
+    // The frame the help closes on is drawn too, to take it off the display.
+    assert_eq!(still, [false, true, false, false, true, true]);
+}
+
+#[test]
+fn the_display_wait_quirk_draws_once_a_frame() {
+    // 200: D011  draw   202: 7001  V0 += 1   204: 1200  jump back
+    let program = [0xD0, 0x11, 0x70, 0x01, 0x12, 0x00];
+    let mut chip8 = machine(&program);
+    chip8.quirks.display_wait = true;
+    let (result, video, _) = run(&mut chip8, &mut MockInput::idle(4));
+    assert!(result.is_ok());
+    assert_eq!(video.frames.len(), 4);
+    // Each frame after the first picks up after its draw and stops at the next.
+    assert_eq!(chip8.v[0], 3);
+    assert_eq!(chip8.pc, 0x202);
+
+    let mut chip8 = machine(&program);
+    assert!(run(&mut chip8, &mut MockInput::idle(4)).0.is_ok());
+    assert!(chip8.v[0] > 4, "{}", chip8.v[0]);
+}
+
+#[test]
+fn quitting_immediately_presents_nothing() {
+    let mut chip8 = machine(&DRAW_PROGRAM);
+    let (result, video, _) = run(&mut chip8, &mut MockInput::idle(0));
+
+    assert!(result.is_ok());
+    assert!(video.frames.is_empty());
+    assert_eq!(chip8.pc, 0x200);
+}
+
+// 200: 6005  V0 = 5
+// 202: 6103  V1 = 3
+// 204: E0A1  skip the next instruction unless key V0 is down
+// 206: F118  sound timer = V1
+// 208: 1204  jump to 204
+const KEY_BEEP_PROGRAM: [u8; 10] = [0x60, 0x05, 0x61, 0x03, 0xE0, 0xA1, 0xF1, 0x18, 0x12, 0x04];
+
+#[test]
+fn key_events_reach_the_machine_and_drive_the_buzzer() {
+    let mut chip8 = machine(&KEY_BEEP_PROGRAM);
+    let mut script = vec![Vec::new(); 10];
+    script[2] = vec![InputEvent::Key { key: 5, pressed: true }];
+    script[3] = vec![InputEvent::Key { key: 5, pressed: false }];
+
+    let mut video = MockVideo::default();
+    let mut audio = MockAudio::default();
+    let mut input = MockInput::new(script);
+    run_emulator(&mut chip8, &mut video, &mut input, &mut audio).unwrap();
+
+    assert_eq!(audio.commands, vec![AudioCommand::BuzzerOn, AudioCommand::BuzzerOff]);
+    assert!(!chip8.keys[5]);
+}
+
+#[test]
+fn buzzer_follows_the_sound_timer() {
+    // 200: 6004  V0 = 4
+    // 202: F018  sound timer = V0
+    // 204: 1204  jump to self
+    let program = [0x60, 0x04, 0xF0, 0x18, 0x12, 0x04];
+
+    // The timer is set and first ticked in frame 1 and reaches zero in frame
+    // 4, so the buzzer goes off in frame 5.
+    let commands = |frames| run(&mut machine(&program), &mut MockInput::idle(frames)).2.commands;
+    assert_eq!(commands(1), vec![AudioCommand::BuzzerOn]);
+    assert_eq!(commands(4), vec![AudioCommand::BuzzerOn]);
+    assert_eq!(commands(5), vec![AudioCommand::BuzzerOn, AudioCommand::BuzzerOff]);
+    assert_eq!(commands(20), vec![AudioCommand::BuzzerOn, AudioCommand::BuzzerOff]);
+}
+
+#[test]
+fn xo_chip_patterns_play_instead_of_the_buzzer_and_follow_the_pitch() {
+    // 200: A210  I = 210
+    // 202: F002  load the pattern at I
+    // 204: 6008  V0 = 8
+    // 206: F018  sound timer = V0
+    // 208: E09E  skip the next instruction if key V0 is down
+    // 20A: 1208  jump to 208
+    // 20C: F03A  pitch = V0
+    // 20E: 120E  jump to self
+    // 210: the pattern
+    let mut program = vec![
+        0xA2, 0x10, 0xF0, 0x02, 0x60, 0x08, 0xF0, 0x18, 0xE0, 0x9E, 0x12, 0x08, 0xF0, 0x3A, 0x12, 0x0E,
+    ];
+    let samples = [0xF0; AUDIO_PATTERN_BYTES];
+    program.extend_from_slice(&samples);
+
+    let mut script = vec![Vec::new(); 12];
+    script[2] = vec![InputEvent::Key { key: 8, pressed: true }];
+    let (_, _, audio) = run(&mut machine(&program), &mut MockInput::new(script));
+    assert_eq!(
+        audio.commands,
+        [
+            AudioCommand::PatternOn(AudioPattern { samples, pitch: DEFAULT_PITCH }),
+            AudioCommand::PatternOn(AudioPattern { samples, pitch: 8 }),
+            AudioCommand::BuzzerOff,
+        ]
+    );
+}
+
+#[test]
+fn speed_events_change_instructions_per_frame_within_limits() {
+    let mut chip8 = machine(&DRAW_PROGRAM);
+    let mut script = vec![vec![InputEvent::SpeedUp]; 30];
+    script.extend(vec![vec![InputEvent::SlowDown]; 30]);
+
+    let (_, video, _) = run(&mut chip8, &mut MockInput::new(script));
+
+    let speeds: Vec<u32> = video.frames.iter().map(|f| f.instructions_per_frame).collect();
+    // 125% rounds 12.5 up.
+    assert_eq!(speeds[0], 13);
+    assert_eq!(video.frames[0].speed.percent(), 125);
+    assert_eq!(speeds[29], 50);
+    assert_eq!(speeds[59], 2);
+}
+
+/// A program of nothing but `6000`, so the PC counts instructions run.
+fn counting_machine() -> Chip8 {
+    machine(&[0x60, 0x00].repeat(1700))
+}
+
+#[test]
+fn clocks_between_whole_instructions_per_frame_run_exactly() {
+    let mut chip8 = counting_machine();
+    let mut driver = Driver::new();
+    driver.set_clock_hz(700);
+    driver.run(&mut chip8, &mut MockVideo::default(), &mut MockInput::idle(60), &mut MockAudio::default()).unwrap();
+    assert_eq!(chip8.pc, 0x200 + 2 * 700);
+
+    // 750 Hz is 12.5 an instruction a frame, not 13 every frame.
+    let mut chip8 = counting_machine();
+    let mut driver = Driver::new();
+    driver.set_speed(125);
+    let mut video = MockVideo::default();
+    driver.run(&mut chip8, &mut video, &mut MockInput::idle(4), &mut MockAudio::default()).unwrap();
+    assert_eq!(chip8.pc, 0x200 + 2 * 50);
+    assert_eq!(video.frames[0].speed.label(), "125% (750 Hz)");
+}
+
+#[test]
+fn vip_timing_runs_cheap_instructions_faster_than_draws() {
+    let run_vip = |program: &[u8]| {
+        let mut chip8 = machine(&program.repeat(3584 / program.len()));
+        let mut driver = Driver::new();
+        driver.set_timing(Timing::Vip);
+        assert_eq!(driver.timing(), Timing::Vip);
+        driver.run(&mut chip8, &mut MockVideo::default(), &mut MockInput::idle(1), &mut MockAudio::default()).unwrap();
+        (chip8.pc - 0x200) / 2
+    };
+    // 6000 against D01F, a 15-row draw.
+    assert_eq!(run_vip(&[0x60, 0x00]), 80);
+    assert_eq!(run_vip(&[0xD0, 0x1F]), 8);
+
+    // The default stays a flat rate.
+    assert_eq!(Driver::new().timing(), Timing::Fixed);
+}
+
+#[test]
+fn paused_frames_owe_no_instructions() {
+    let mut chip8 = counting_machine();
+    let mut driver = Driver::new();
+    driver.pause_on_focus_loss = true;
+    driver.set_speed(125);
+    let script = vec![Vec::new(), vec![InputEvent::Focus(false)], Vec::new(), vec![InputEvent::Focus(true)]];
+    let mut video = MockVideo::default();
+    driver.run(&mut chip8, &mut video, &mut MockInput::new(script), &mut MockAudio::default()).unwrap();
+    let paused: Vec<bool> = video.frames.iter().map(|f| f.paused).collect();
+    assert_eq!(paused, [false, true, true, false]);
+    // The first and last frames, 12 and 13, with the half carried across.
+    assert_eq!(chip8.pc, 0x200 + 2 * 25);
+}
+
+#[test]
+fn the_pause_key_holds_the_machine_and_the_step_key_runs_one_instruction() {
+    let mut chip8 = counting_machine();
+    chip8.delay_timer = 50;
+    let script = vec![
+        vec![InputEvent::Pause],
+        Vec::new(),
+        vec![InputEvent::Step],
+        vec![InputEvent::Step],
+        vec![InputEvent::Pause],
+        vec![InputEvent::Console(ConsoleKey::Toggle)],
+    ];
+    let mut video = MockVideo::default();
+    Driver::new().run(&mut chip8, &mut video, &mut MockInput::new(script), &mut MockAudio::default()).unwrap();
+
+    let paused: Vec<bool> = video.frames.iter().map(|f| f.paused).collect();
+    assert_eq!(paused, [true, true, true, true, false, false]);
+    // Two steps, then two whole frames; the timers only ran in those.
+    assert_eq!(chip8.pc, 0x200 + 2 * (2 + 2 * DEFAULT_INSTRUCTIONS_PER_FRAME as u16));
+    assert_eq!(chip8.delay_timer, 48);
+    let console = video.frames[5].console.as_ref().expect("console open");
+    assert_eq!(console.lines, ["200: 6000  LD V0, 0x00", "202: 6000  LD V0, 0x00"]);
+
+    // The keypad still works while paused, for the steps that read it.
+    let mut chip8 = counting_machine();
+    let script = vec![vec![InputEvent::Pause], vec![InputEvent::Key { key: 5, pressed: true }]];
+    run(&mut chip8, &mut MockInput::new(script)).0.unwrap();
+    assert_eq!((chip8.pc, chip8.keys[5]), (0x200, true));
+}
+
+#[test]
+fn steps_from_the_end_of_memory_print_what_they_can() {
+    let stepped = |pc: u16| -> String {
+        let mut chip8 = machine(&COUNT_PROGRAM);
+        chip8.pc = pc;
+        let script = vec![vec![InputEvent::Pause], vec![InputEvent::Step], vec![InputEvent::Console(ConsoleKey::Toggle)]];
+        let mut video = MockVideo::default();
+        let _ = Driver::new().run(&mut chip8, &mut video, &mut MockInput::new(script), &mut MockAudio::default());
+        video.frames[2].console.as_ref().expect("console open").lines[0].clone()
+    };
+    assert!(stepped(0xFFF).starts_with("FFF: "), "{}", stepped(0xFFF));
+    // Past the addressable memory there's no instruction to show.
+    assert_eq!(stepped(0x1000), "1000: pc out of range");
+}
+
+#[test]
+fn the_register_readout_shows_while_held_and_marks_what_each_step_changed() {
+    let script = vec![Vec::new(), vec![InputEvent::Step], vec![InputEvent::Step], vec![InputEvent::Pause], vec![InputEvent::Registers]];
+    let mut video = MockVideo::default();
+    Driver::new()
+        .run(&mut machine(&COUNT_PROGRAM), &mut video, &mut MockInput::new(script), &mut MockAudio::default())
+        .unwrap();
+
+    let shown: Vec<bool> = video.frames.iter().map(|f| f.registers.is_some()).collect();
+    assert_eq!(shown, [false, true, true, false, true]);
+    let changed = |frame: usize| -> Vec<String> {
+        let registers = video.frames[frame].registers.as_ref().unwrap();
+        registers.cells().filter(|(_, _, cell)| cell.changed).map(|(_, _, cell)| cell.text.clone()).collect()
+    };
+    // 200: 7001  V0 += 1, after a frame of five loops.
+    assert_eq!(changed(1), ["V0=06", "PC=202"]);
+    // 202: 1200  jump back.
+    assert_eq!(changed(2), ["PC=200"]);
+}
+
+#[test]
+fn fine_steps_and_reset_move_the_speed_within_configured_limits() {
+    let script = vec![
+        vec![InputEvent::FineSpeedUp],
+        vec![InputEvent::SpeedUp],
+        vec![InputEvent::FineSlowDown, InputEvent::FineSlowDown],
+        vec![InputEvent::ResetSpeed],
+        vec![InputEvent::SlowDown; 10],
+    ];
+    let mut driver = Driver::new();
+    driver.set_speed_limits(SpeedLimits::new(50, 200).unwrap());
+    driver.set_speed(150);
+    let mut video = MockVideo::default();
+    driver
+        .run(&mut machine(&DRAW_PROGRAM), &mut video, &mut MockInput::new(script), &mut MockAudio::default())
+        .unwrap();
+
+    let percents: Vec<u32> = video.frames.iter().map(|f| f.speed.percent()).collect();
+    assert_eq!(percents, [155, 175, 165, 100, 50]);
+    assert_eq!(video.frames[4].speed.label(), "50% (300 Hz)");
+    assert_eq!(video.frames[4].instructions_per_frame, 5);
+}
+
+#[test]
+fn saving_game_settings_remembers_the_clock_by_rom_hash() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("games.cfg");
+    let mut config = GameConfig::new();
+    config.game_mut(0xAB).cpu_hz = Some(900);
+    config.save(&path).unwrap();
+
+    let script = vec![vec![InputEvent::SpeedUp; 8], vec![InputEvent::SaveGameSettings]];
+    let mut chip8 = machine(&DRAW_PROGRAM);
+    let mut driver = Driver::new();
+    driver.set_game_config(&path);
+    driver.set_window_scale(6);
+    driver
+        .run(&mut chip8, &mut MockVideo::default(), &mut MockInput::new(script), &mut MockAudio::default())
+        .unwrap();
+
+    let config = GameConfig::load(&path).unwrap();
+    let hash = chip8.rom_hash().unwrap();
+    assert_eq!(config.game(hash).unwrap().cpu_hz, Some(1800));
+    assert_eq!(config.game(hash).unwrap().scale, Some(6));
+    // Other games' settings are kept.
+    assert_eq!(config.game(0xAB).unwrap().cpu_hz, Some(900));
+}
+
+#[test]
+fn scale_changes_leave_the_frames_and_the_pause_alone() {
+    let script = vec![
+        vec![],
+        vec![InputEvent::SetScale(3)],
+        vec![InputEvent::Focus(false)],
+        vec![InputEvent::ScaleUp],
+        vec![InputEvent::Focus(true), InputEvent::ScaleDown, InputEvent::ScaleDown, InputEvent::ScaleDown, InputEvent::ScaleDown],
+        vec![InputEvent::SetScale(10), InputEvent::ScaleUp],
+    ];
+    let mut chip8 = machine(&DRAW_PROGRAM);
+    let mut driver = Driver::new();
+    driver.pause_on_focus_loss = true;
+    driver.set_window_scale(2);
+    let mut video = MockVideo::default();
+    driver.run(&mut chip8, &mut video, &mut MockInput::new(script), &mut MockAudio::default()).unwrap();
+
+    let scales: Vec<_> = video.frames.iter().map(|f| f.scale.unwrap()).collect();
+    assert_eq!(scales, [2, 3, 3, 4, 1, 10]);
+    let paused: Vec<_> = video.frames.iter().map(|f| f.paused).collect();
+    assert_eq!(paused, [false, false, true, true, false, false]);
+    // Whatever size the window is, a recording of the frames sees the
+    // display as it is.
+    for frame in &video.frames {
+        assert_eq!(frame.size, (64, 32));
+        assert_eq!(frame.lit, [0, 1]);
+    }
+}
+
+#[test]
+fn scale_events_do_nothing_for_frontends_without_a_scale() {
+    let script = vec![vec![InputEvent::SetScale(3), InputEvent::ScaleUp]];
+    let mut chip8 = machine(&DRAW_PROGRAM);
+    let (_, video, _) = run(&mut chip8, &mut MockInput::new(script));
+    assert_eq!(video.frames[0].scale, None);
+}
+
+/// What a [`MockClipboard`] was given, shared with the test.
+#[derive(Debug, Default)]
+struct Copies {
+    images: Vec<ClipboardImage>,
+    texts: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct MockClipboard {
+    copies: Rc<RefCell<Copies>>,
+    takes_images: bool,
+    broken: bool,
+}
+
+impl Clipboard for MockClipboard {
+    fn set_text(&mut self, text: &str) -> Result<(), String> {
+        if self.broken {
+            return Err("no clipboard".to_string());
+        }
+        self.copies.borrow_mut().texts.push(text.to_string());
+        Ok(())
+    }
+
+    fn set_image(&mut self, image: &ClipboardImage) -> Result<(), String> {
+        if self.broken || !self.takes_images {
+            return Err("no images".to_string());
+        }
+        self.copies.borrow_mut().images.push(image.clone());
+        Ok(())
+    }
+}
+
+fn copy_frames(clipboard: MockClipboard, script: Vec<Vec<InputEvent>>) -> Vec<Presented> {
+    let mut chip8 = machine(&DRAW_PROGRAM);
+    let mut driver = Driver::new();
+    driver.set_clipboard(clipboard);
+    let mut video = MockVideo::default();
+    driver.run(&mut chip8, &mut video, &mut MockInput::new(script), &mut MockAudio::default()).unwrap();
+    video.frames
+}
+
+#[test]
+fn copying_the_screen_falls_back_to_text_and_says_so() {
+    let clipboard = MockClipboard::default();
+    let frames = copy_frames(clipboard.clone(), vec![vec![], vec![InputEvent::CopyScreen], vec![]]);
+    assert_eq!(frames[0].notice, None);
+    assert_eq!(frames[1].notice.as_deref(), Some("copied"));
+    assert_eq!(frames[2].notice.as_deref(), Some("copied"));
+    let copies = clipboard.copies.borrow();
+    assert!(copies.images.is_empty());
+    assert_eq!(copies.texts.len(), 1);
+    assert!(copies.texts[0].starts_with("##......"), "{}", copies.texts[0]);
+
+    let clipboard = MockClipboard { takes_images: true, ..Default::default() };
+    copy_frames(clipboard.clone(), vec![vec![], vec![InputEvent::CopyScreen]]);
+    let copies = clipboard.copies.borrow();
+    assert!(copies.texts.is_empty());
+    let image = &copies.images[0];
+    assert_eq!((image.width, image.height), (256, 128));
+    let lit = THEMES[0].1.on.to_be_bytes();
+    assert_eq!(image.rgba[..4], [lit[1], lit[2], lit[3], 0xFF]);
+}
+
+#[test]
+fn copying_fails_without_stopping_and_not_from_the_console() {
+    let clipboard = MockClipboard { broken: true, ..Default::default() };
+    let frames = copy_frames(clipboard, vec![vec![InputEvent::CopyScreen]; 2]);
+    assert_eq!(frames[1].notice.as_deref(), Some("copy failed"));
+    assert!(!frames[1].halted);
+
+    // Ctrl+C is the console's while it's open.
+    let clipboard = MockClipboard::default();
+    let script = vec![vec![InputEvent::Console(ConsoleKey::Toggle), InputEvent::CopyScreen]];
+    let frames = copy_frames(clipboard.clone(), script);
+    assert_eq!(frames[0].notice, None);
+    assert!(clipboard.copies.borrow().texts.is_empty());
+}
+
+#[test]
+fn notices_clear_after_a_second_and_a_half() {
+    let mut script = vec![vec![InputEvent::CopyScreen]];
+    script.extend(vec![vec![]; 100]);
+    let frames = copy_frames(MockClipboard::default(), script);
+    assert_eq!(frames.iter().filter(|f| f.notice.is_some()).count(), 90);
+    assert_eq!(frames[90].notice, None);
+}
+
+/// Everything written to it, shared with the test.
+#[derive(Debug, Clone, Default)]
+struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn ascii_dumps_are_written_on_request_and_at_set_frames() {
+    let out = SharedOutput::default();
+    let mut chip8 = machine(&DRAW_PROGRAM);
+    let mut driver = Driver::new();
+    driver.set_ascii_dump(Glyphs::default(), out.clone());
+    driver.set_ascii_dump_at([1, 5]);
+    let script = vec![vec![InputEvent::AsciiDump], vec![], vec![InputEvent::AsciiDump]];
+    driver.run(&mut chip8, &mut MockVideo::default(), &mut MockInput::new(script), &mut MockAudio::default()).unwrap();
+
+    let blank = [false; 64 * 32];
+    let mut drawn = blank;
+    drawn[..2].fill(true);
+    let snapshot = |pixels: &[bool], frame| format!("{}\n", Snapshot { art: Ascii::new(pixels, 64, 32, Glyphs::default()), frame });
+    // Asked for before the first frame, due after it, and asked for again
+    // before the third. The run ends before frame 5 is due.
+    let expected = [snapshot(&blank, 0), snapshot(&drawn, 1), snapshot(&drawn, 2)].concat();
+    assert_eq!(String::from_utf8(out.0.borrow().clone()).unwrap(), expected);
+    assert!(expected.contains("\n|##..."));
+}
+
+#[test]
+fn roms_for_unsupported_extensions_halt_before_running() {
+    // 200: 00E0  clear
+    // 202: 0011  MegaChip on
+    let mut chip8 = machine(&[0x00, 0xE0, 0x00, 0x11]);
+    let (result, video, _) = run(&mut chip8, &mut MockInput::idle(3));
+
+    let e = result.unwrap_err();
+    assert_eq!(e.to_string(), "this ROM is for MegaChip, which isn't supported (opcode 0x0011 at 0x202)");
+    assert!(video.frames.iter().all(|f| f.halted));
+    // Not one instruction ran.
+    assert_eq!(chip8.pc, 0x200);
+}
+
+#[test]
+fn unsupported_extension_opcodes_halt_where_they_run() {
+    // 200-21E: 7001  V0 += 1, past where the prescan looks
+    // 220:     0203  a machine code call, skipped as unknown
+    // 222:     E0F2  CHIP-8X: skip if key V0 is down on the second keypad
+    let mut program = [0x70, 0x01].repeat(16);
+    program.extend([0x02, 0x03, 0xE0, 0xF2]);
+    let mut chip8 = machine(&program);
+    let (result, _, _) = run(&mut chip8, &mut MockInput::idle(5));
+
+    let e = result.unwrap_err();
+    assert!(matches!(e, Chip8Error::UnsupportedPlatform { opcode: 0xE0F2, pc: 0x222, .. }), "{:?}", e);
+    assert_eq!(e.to_string(), "this ROM is for CHIP-8X, which isn't supported (opcode 0xE0F2 at 0x222)");
+    assert_eq!(chip8.v[0], 16);
+}
+
+#[test]
+fn machine_errors_halt_and_are_returned_on_quit() {
+    // 200: 00EE  return with an empty stack
+    let mut chip8 = machine(&[0x00, 0xEE]);
+    let (result, video, _) = run(&mut chip8, &mut MockInput::idle(3));
+
+    let e = result.unwrap_err();
+    assert!(matches!(e, Chip8Error::StackUnderflow { pc: 0x200 }), "{:?}", e);
+    assert_eq!(e.to_string(), "stack underflow: return with an empty call stack (instruction at 0x200)");
+    assert_eq!(video.frames.len(), 3);
+    assert!(video.frames.iter().all(|f| f.halted));
+    assert_eq!(chip8.pc, 0x202);
+}
+
+#[test]
+fn programs_end_on_a_jump_to_themselves() {
+    let mut driver = Driver::new();
+    let mut chip8 = machine(&DRAW_PROGRAM);
+    chip8.sound_timer = 2;
+    let mut video = MockVideo::default();
+    let mut audio = MockAudio::default();
+    for _ in 0..3 {
+        driver.frame(&mut chip8, &mut video, &mut MockInput::idle(1), &mut audio);
+    }
+
+    assert_eq!(driver.finished(), Some(0x204));
+    assert!(driver.halted().is_none());
+    // The final frame stays up, and the buzzer still runs down.
+    assert!(video.frames.iter().all(|f| f.finished == Some(0x204) && f.lit == [0, 1] && !f.halted));
+    assert!(video.frames[0].escape_quits);
+    assert_eq!(audio.commands, [AudioCommand::BuzzerOn, AudioCommand::BuzzerOff]);
+    assert!(driver.finish().is_ok());
+}
+
+#[test]
+fn programs_end_on_an_exit() {
+    // 200: A206  I = 0x206
+    // 202: D011  draw
+    // 204: 00FD  exit
+    // 206: C0    sprite
+    let mut chip8 = machine(&[0xA2, 0x06, 0xD0, 0x11, 0x00, 0xFD, 0xC0]);
+    let (result, video, _) = run(&mut chip8, &mut MockInput::idle(3));
+
+    assert!(result.is_ok());
+    assert!(video.frames.iter().all(|f| f.finished == Some(0x204) && f.lit == [0, 1] && !f.halted));
+    assert_eq!(chip8.pc, 0x204);
+}
+
+#[test]
+fn frames_are_presented_at_the_programs_resolution() {
+    // 200: A208  I = 0x208
+    // 202: 00FF  128x64
+    // 204: D011  draw
+    // 206: 00FD  exit
+    // 208: C0    sprite
+    let mut chip8 = machine(&[0xA2, 0x08, 0x00, 0xFF, 0xD0, 0x11, 0x00, 0xFD, 0xC0]);
+    let (result, video, _) = run(&mut chip8, &mut MockInput::idle(2));
+
+    assert!(result.is_ok());
+    assert!(video.frames.iter().all(|f| f.size == (128, 64) && f.lit == [0, 1]));
+}
+
+#[test]
+fn waiting_for_a_key_is_not_the_end() {
+    // 200: F00A  wait for a key
+    // 202: 1202  jump to self
+    let mut driver = Driver::new();
+    let mut chip8 = machine(&[0xF0, 0x0A, 0x12, 0x02]);
+    let mut video = MockVideo::default();
+    let script = vec![
+        Vec::new(),
+        Vec::new(),
+        vec![InputEvent::Key { key: 5, pressed: true }],
+        vec![InputEvent::Key { key: 5, pressed: false }],
+    ];
+    let mut input = MockInput::new(script);
+    for _ in 0..2 {
+        driver.frame(&mut chip8, &mut video, &mut input, &mut NullAudio);
+    }
+    assert_eq!((chip8.pc, driver.finished()), (0x200, None));
+
+    for _ in 0..2 {
+        driver.frame(&mut chip8, &mut video, &mut input, &mut NullAudio);
+    }
+    assert_eq!(driver.finished(), Some(0x202));
+    assert_eq!(video.frames.iter().map(|f| f.finished).collect::<Vec<_>>(), [None, None, None, Some(0x202)]);
+}
+
+#[test]
+fn running_off_the_end_of_memory_halts() {
+    // 200: 1FFE  jump to the last instruction in memory
+    // FFE: 6A01  VA = 1
+    let mut program = vec![0x1F, 0xFE];
+    program.resize(0xFFE - 0x200, 0);
+    program.extend([0x6A, 0x01]);
+    let mut chip8 = machine(&program);
+    let (result, video, _) = run(&mut chip8, &mut MockInput::idle(2));
+
+    let e = result.unwrap_err();
+    assert!(matches!(e, Chip8Error::PcOutOfBounds { pc: 0x1000 }), "{:?}", e);
+    assert_eq!(chip8.v[0xA], 1);
+    assert!(video.frames.iter().all(|f| f.halted));
+}
+
+#[test]
+fn unknown_opcodes_are_skipped() {
+    // 200: FFFF  not an instruction
+    // 202: 6042  V0 = 0x42
+    // 204: 1204  jump to self
+    let mut chip8 = machine(&[0xFF, 0xFF, 0x60, 0x42, 0x12, 0x04]);
+    let (result, video, _) = run(&mut chip8, &mut MockInput::idle(1));
+
+    assert!(result.is_ok());
+    assert!(!video.frames[0].halted);
+    assert_eq!(chip8.v[0], 0x42);
+}
+
+#[test]
+fn focus_loss_pauses_the_machine_and_buzzer_when_enabled() {
+    let mut script = vec![Vec::new(); 7];
+    script[1] = vec![InputEvent::Focus(false)];
+    script[3] = vec![InputEvent::Focus(true)];
+
+    // The program from buzzer_follows_the_sound_timer.
+    let program = [0x60, 0x04, 0xF0, 0x18, 0x12, 0x04];
+    let mut chip8 = machine(&program);
+    let mut driver = Driver::new();
+    driver.pause_on_focus_loss = true;
+    let mut video = MockVideo::default();
+    let mut audio = MockAudio::default();
+    driver
+        .run(&mut chip8, &mut video, &mut MockInput::new(script.clone()), &mut audio)
+        .unwrap();
+
+    let paused: Vec<bool> = video.frames.iter().map(|f| f.paused).collect();
+    assert_eq!(paused, [false, true, true, false, false, false, false]);
+    assert_eq!(
+        audio.commands,
+        [
+            AudioCommand::BuzzerOn,
+            AudioCommand::BuzzerOff,
+            AudioCommand::BuzzerOn,
+            AudioCommand::BuzzerOff
+        ]
+    );
+    assert_eq!(chip8.sound_timer, 0);
+
+    // Without the option focus changes are ignored.
+    let (_, video, audio) = run(&mut machine(&program), &mut MockInput::new(script));
+    assert!(video.frames.iter().all(|f| !f.paused));
+    assert_eq!(audio.commands, [AudioCommand::BuzzerOn, AudioCommand::BuzzerOff]);
+}
+
+#[test]
+fn controller_commands_apply_between_frames() {
+    // 200: 7001  V0 += 1
+    // 202: 1200  jump to 200
+    let mut chip8 = machine(&[0x70, 0x01, 0x12, 0x00]);
+    let (controller, receiver) = control::channel();
+    let mut driver = Driver::new();
+    driver.attach_control(receiver);
+
+    let client = thread::spawn(move || {
+        let registers = || match controller.send(Command::ReadRegisters).unwrap() {
+            Reply::Registers(registers) => registers,
+            other => panic!("{other:?}"),
+        };
+        controller.send(Command::Pause).unwrap();
+        let before = registers();
+        let Reply::Stepped { pc } = controller.send(Command::Step).unwrap() else {
+            panic!("no step");
+        };
+        let after = registers();
+        (before, pc, after)
+    });
+
+    let mut video = MockVideo::default();
+    let mut audio = MockAudio::default();
+    while !client.is_finished() {
+        assert!(driver.frame(&mut chip8, &mut video, &mut NoInput, &mut audio));
+    }
+    let (before, pc, after) = client.join().unwrap();
+
+    // Paused, only the one stepped instruction ran.
+    assert!(before.paused && after.paused);
+    assert_eq!(after.pc, pc);
+    if before.pc == 0x200 {
+        assert_eq!((after.pc, after.v[0]), (0x202, before.v[0].wrapping_add(1)));
+    } else {
+        assert_eq!((after.pc, after.v[0]), (0x200, before.v[0]));
+    }
+    assert!(video.frames.last().unwrap().paused);
+}
+
+#[test]
+fn reloaded_roms_get_a_machine_set_up_as_configured() {
+    // 200: C0FF  V0 = random   202: 1202  jump to itself
+    let program = [0xC0, 0xFF, 0x12, 0x02];
+    let config = Chip8Config { opcode_policy: OpcodePolicy::Halt, seed: Some(7), ..Chip8Config::default() };
+    let mut expected = Chip8::with_config(&config);
+    expected.load_rom_bytes(&program).unwrap();
+    expected.step().unwrap();
+
+    let mut chip8 = machine(&COUNT_PROGRAM);
+    chip8.rpl_flags[0] = 0x42;
+    let (controller, receiver) = control::channel();
+    let mut driver = Driver::new();
+    driver.set_config(config);
+    driver.attach_control(receiver);
+    let client = thread::spawn(move || {
+        controller.send(Command::Pause).unwrap();
+        controller.send(Command::LoadRom(program.to_vec())).unwrap();
+        controller.send(Command::Step).unwrap();
+        match controller.send(Command::ReadRegisters).unwrap() {
+            Reply::Registers(registers) => registers,
+            other => panic!("{other:?}"),
+        }
+    });
+
+    let mut video = MockVideo::default();
+    let mut audio = MockAudio::default();
+    while !client.is_finished() {
+        assert!(driver.frame(&mut chip8, &mut video, &mut NoInput, &mut audio));
+    }
+    let registers = client.join().unwrap();
+
+    assert_eq!(registers.v[0], expected.v[0]);
+    assert_eq!(chip8.opcode_policy, OpcodePolicy::Halt);
+    assert_eq!(chip8.rpl_flags[0], 0x42);
+}
+
+// 200: 7001  V0 += 1
+// 202: 1200  jump to 200
+const COUNT_PROGRAM: [u8; 4] = [0x70, 0x01, 0x12, 0x00];
+
+fn menu(keys: &[MenuKey]) -> Vec<InputEvent> {
+    keys.iter().map(|&key| InputEvent::Menu(key)).collect()
+}
+
+#[test]
+fn escape_opens_a_pause_menu_that_holds_the_machine_and_keypad() {
+    use MenuKey::*;
+    let mut script = vec![Vec::new(); 6];
+    script[1] = menu(&[Escape]);
+    script[2] = vec![InputEvent::Key { key: 5, pressed: true }];
+    script[3] = menu(&[Down, Down, Down, Down, Right]);
+    script[4] = menu(&[Escape]);
+
+    let mut chip8 = machine(&COUNT_PROGRAM);
+    chip8.keys[3] = true;
+    let (result, video, _) = run(&mut chip8, &mut MockInput::new(script));
+
+    assert!(result.is_ok());
+    let paused: Vec<bool> = video.frames.iter().map(|f| f.paused).collect();
+    assert_eq!(paused, [false, true, true, true, false, false]);
+    // Two instructions a count, at ten a frame, over three running frames.
+    assert_eq!(chip8.v[0], 15);
+    // Keys held going in are let go, and presses while open are dropped.
+    assert_eq!(chip8.keys, [false; 16]);
+
+    let open = video.frames[1].menu.as_ref().unwrap();
+    assert_eq!(open.selected, 0);
+    assert_eq!(open.labels[0], "Resume");
+    assert_eq!(open.labels[3], "Load State (none saved)");
+    let themed = video.frames[3].menu.as_ref().unwrap();
+    assert_eq!(themed.selected, 4);
+    assert_eq!(themed.labels[4], format!("Theme: < {} >", THEMES[1].0));
+    assert!(video.frames[4].menu.is_none());
+    assert_eq!(video.frames[5].palette, THEMES[1].1);
+    assert!(video.frames.iter().all(|f| !f.escape_quits));
+}
+
+#[test]
+fn menu_saves_loads_resets_and_quits() {
+    use MenuKey::*;
+    let script = vec![
+        // Saved before this frame runs, with V0 still 0.
+        menu(&[Escape, Down, Down, Enter]),
+        Vec::new(),
+        Vec::new(),
+        menu(&[Escape, Down, Down, Down, Enter]),
+        menu(&[Escape, Down, Enter]),
+        menu(&[Escape, Up, Enter]),
+    ];
+    let mut chip8 = machine(&COUNT_PROGRAM);
+    let mut values = Vec::new();
+    let mut driver = Driver::new();
+    let mut input = MockInput::new(script);
+    let (mut video, mut audio) = (MockVideo::default(), MockAudio::default());
+    while driver.frame(&mut chip8, &mut video, &mut input, &mut audio) {
+        values.push(chip8.v[0]);
+    }
+
+    // Counting five a frame; the load and the reset each go back to 0.
+    assert_eq!(values, [5, 10, 15, 5, 5]);
+    assert_eq!(video.frames.len(), 5);
+}
+
+#[test]
+fn escape_quits_with_classic_escape_or_once_halted() {
+    let script = || MockInput::new(vec![Vec::new(), menu(&[MenuKey::Escape]), Vec::new()]);
+
+    let mut driver = Driver::new();
+    driver.classic_escape = true;
+    let mut video = MockVideo::default();
+    let mut chip8 = machine(&COUNT_PROGRAM);
+    driver.run(&mut chip8, &mut video, &mut script(), &mut MockAudio::default()).unwrap();
+    assert_eq!(video.frames.len(), 1);
+    assert!(video.frames[0].escape_quits);
+
+    // 200: 00EE  return with an empty stack
+    let (result, video, _) = run(&mut machine(&[0x00, 0xEE]), &mut script());
+    assert!(matches!(result, Err(Chip8Error::StackUnderflow { .. })));
+    assert_eq!(video.frames.len(), 1);
+    assert!(video.frames[0].escape_quits);
+}
+
+/// A frontend's worth of bindings: the whole keypad, then the hotkeys.
+fn bindings() -> Vec<Binding> {
+    let mut bindings: Vec<Binding> = (0..16)
+        .map(|key| Binding {
+            key: ["X", "1", "2", "3", "Q", "W", "E", "A", "S", "D", "Z", "C", "4", "R", "F", "V"][key],
+            event: InputEvent::Key { key: key as u8, pressed: true },
+        })
+        .collect();
+    bindings.extend([
+        Binding { key: "=", event: InputEvent::SpeedUp },
+        Binding { key: "Esc", event: InputEvent::Menu(MenuKey::Escape) },
+        Binding { key: "Up", event: InputEvent::Menu(MenuKey::Up) },
+        Binding { key: "Down", event: InputEvent::Menu(MenuKey::Down) },
+        Binding { key: "F1", event: InputEvent::Help },
+        Binding { key: "F9", event: InputEvent::DumpState },
+    ]);
+    bindings
+}
+
+#[test]
+fn f1_opens_a_paged_help_overlay_that_holds_the_machine() {
+    use MenuKey::*;
+    let mut script = vec![Vec::new(); 7];
+    script[1] = vec![InputEvent::Help];
+    script[2] = vec![InputEvent::Key { key: 5, pressed: true }, InputEvent::Menu(Right)];
+    script[3] = menu(&[Right]);
+    script[4] = menu(&[Right]);
+    script[5] = menu(&[Escape]);
+    script[6] = vec![InputEvent::Help, InputEvent::Help];
+
+    let mut chip8 = machine(&COUNT_PROGRAM);
+    let mut driver = Driver::new();
+    driver.set_bindings(bindings());
+    let mut video = MockVideo::default();
+    driver
+        .run(&mut chip8, &mut video, &mut MockInput::new(script), &mut MockAudio::default())
+        .unwrap();
+
+    let paused: Vec<bool> = video.frames.iter().map(|f| f.paused).collect();
+    assert_eq!(paused, [false, true, true, true, true, false, false]);
+    assert_eq!(chip8.v[0], 15);
+    assert!(!chip8.keys[5]);
+    // Escape closed the help rather than opening the menu.
+    assert!(video.frames.iter().all(|f| f.menu.is_none()));
+
+    let pages: Vec<Option<(usize, usize)>> =
+        video.frames.iter().map(|f| f.help.as_ref().map(|help| (help.page, help.pages))).collect();
+    assert_eq!(pages, [None, Some((0, 2)), Some((1, 2)), Some((0, 2)), Some((1, 2)), None, None]);
+
+    let first = &video.frames[1].help.as_ref().unwrap().lines;
+    assert_eq!(first.len(), 12);
+    assert_eq!(first[0], HelpLine::Heading(Group::GameKeys));
+    assert_eq!(first[1], HelpLine::Entry { keys: "X".to_string(), action: "Keypad 0".to_string() });
+    let second = &video.frames[2].help.as_ref().unwrap().lines;
+    assert_eq!(second[0], HelpLine::Heading(Group::GameKeys));
+    let text: Vec<String> = second.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        text[6..],
+        [
+            "Emulator",
+            "  =             Speed up",
+            "  Esc           Pause menu",
+            "  Up / Down     Menu: select",
+            "  F1            Show this help",
+        ]
+    );
+    // F9 does nothing without a dump file, so there's no Debug section.
+    assert!(!second.contains(&HelpLine::Heading(Group::Debug)));
+}
+
+#[test]
+fn small_window_scales_split_help_into_more_pages() {
+    let pages_at = |scale| {
+        let mut script = vec![Vec::new(); 2];
+        script[1] = vec![InputEvent::Help];
+        let mut driver = Driver::new();
+        driver.set_bindings(bindings());
+        driver.set_window_scale(scale);
+        let mut video = MockVideo::default();
+        driver
+            .run(&mut machine(&COUNT_PROGRAM), &mut video, &mut MockInput::new(script), &mut MockAudio::default())
+            .unwrap();
+        let help = video.frames[1].help.clone().unwrap();
+        (help.pages, help.lines.len())
+    };
+    assert_eq!(pages_at(10), (2, 12));
+    let (pages, lines) = pages_at(2);
+    assert!(pages > 2, "{} pages", pages);
+    assert!(lines < 12);
+}
+
+#[test]
+fn help_follows_classic_escape() {
+    let mut driver = Driver::new();
+    driver.classic_escape = true;
+    driver.set_bindings(bindings());
+    let mut video = MockVideo::default();
+    let mut input = MockInput::new(vec![vec![InputEvent::Help, InputEvent::Menu(MenuKey::Right)], menu(&[MenuKey::Escape])]);
+    let mut chip8 = machine(&COUNT_PROGRAM);
+    driver.run(&mut chip8, &mut video, &mut input, &mut MockAudio::default()).unwrap();
+
+    // Escape closes the help first, even when it would otherwise quit.
+    assert_eq!(video.frames.len(), 2);
+    let help = video.frames[0].help.as_ref().unwrap();
+    let text: Vec<String> = help.lines.iter().map(ToString::to_string).collect();
+    assert_eq!(help.page, help.pages - 1);
+    assert!(text.contains(&"  Esc           Quit".to_string()));
+    assert!(!text.iter().any(|line| line.contains("Menu")));
+    assert!(video.frames[1].help.is_none());
+}
+
+#[test]
+fn indicators_follow_pausing_speed_and_audio() {
+    let script = vec![
+        Vec::new(),
+        vec![InputEvent::SpeedUp],
+        menu(&[MenuKey::Escape]),
+        vec![InputEvent::SlowDown],
+    ];
+    let (_, video, _) = run(&mut machine(&COUNT_PROGRAM), &mut MockInput::new(script.clone()));
+    let shown: Vec<(bool, bool, bool)> = video
+        .frames
+        .iter()
+        .map(|f| (f.indicators.paused, f.indicators.turbo, f.indicators.muted))
+        .collect();
+    assert_eq!(shown, [(false, false, false), (false, true, false), (true, true, false), (true, false, false)]);
+    assert!(video.frames.iter().all(|f| !f.indicators.recording));
+
+    let mut video = MockVideo::default();
+    Driver::new()
+        .run(&mut machine(&COUNT_PROGRAM), &mut video, &mut MockInput::new(script), &mut NullAudio)
+        .unwrap();
+    assert!(video.frames.iter().all(|f| f.indicators.muted));
+}
+
+#[test]
+fn enter_in_the_help_overlay_switches_to_the_keypad_card() {
+    use MenuKey::*;
+    let script = vec![
+        vec![InputEvent::Help],
+        menu(&[Enter]),
+        menu(&[Right]),
+        menu(&[Enter]),
+        menu(&[Enter, Escape]),
+        vec![InputEvent::Help],
+    ];
+    let mut bindings = bindings();
+    bindings.push(Binding { key: "Space", event: InputEvent::Key { key: 5, pressed: true } });
+    let mut driver = Driver::new();
+    driver.set_bindings(bindings);
+    let mut video = MockVideo::default();
+    driver
+        .run(&mut machine(&COUNT_PROGRAM), &mut video, &mut MockInput::new(script), &mut MockAudio::default())
+        .unwrap();
+
+    let shown: Vec<(bool, Option<usize>)> = video
+        .frames
+        .iter()
+        .map(|f| (f.keypad_card.is_some(), f.help.as_ref().map(|help| help.page)))
+        .collect();
+    assert_eq!(
+        shown,
+        [(false, Some(0)), (true, Some(0)), (true, Some(0)), (false, Some(0)), (false, None), (false, Some(0))]
+    );
+    assert!(!video.frames[4].paused);
+
+    let card = video.frames[1].keypad_card.as_ref().unwrap();
+    assert_eq!(card.label(0x5), "W / Space");
+    assert_eq!(card.label(0xF), "V");
+    assert_eq!(card.awaiting, None);
+}
+
+fn typed(text: &str) -> Vec<InputEvent> {
+    text.chars().map(|ch| InputEvent::Console(ConsoleKey::Char(ch))).collect()
+}
+
+#[test]
+fn the_console_runs_debugger_commands_without_touching_the_keypad() {
+    use MenuKey::*;
+    let mut line = typed("break 202");
+    line.extend([InputEvent::Key { key: 1, pressed: true }, InputEvent::SpeedUp]);
+    let mut regs = typed("regs");
+    regs.push(InputEvent::Menu(Enter));
+    let script = vec![
+        vec![InputEvent::Console(ConsoleKey::Toggle)],
+        line,
+        menu(&[Enter]),
+        regs,
+        menu(&[Up, Up]),
+        vec![InputEvent::Console(ConsoleKey::Toggle)],
+        typed("`x"),
+    ];
+    let mut chip8 = machine(&COUNT_PROGRAM);
+    let (result, video, _) = run(&mut chip8, &mut MockInput::new(script));
+    result.unwrap();
+
+    let frames = &video.frames;
+    let console = |frame: usize| frames[frame].console.as_ref().expect("console open");
+    assert_eq!(console(0).input, "");
+    // Open, it doesn't pause the machine, but keeps the keypad and speed
+    // keys for itself.
+    assert!(!frames[0].paused);
+    assert_eq!((console(1).input.as_str(), console(1).cursor), ("break 202", 9));
+    assert_eq!(frames[1].instructions_per_frame, DEFAULT_INSTRUCTIONS_PER_FRAME);
+    assert!(!chip8.keys[1]);
+
+    // The machine reached the breakpoint in the same frame, which says so
+    // with the registers.
+    assert_eq!(console(2).lines[..3], ["> break 202", "ok", "Breakpoint at 0x202"]);
+    assert_eq!(console(2).input, "");
+    let registers = &console(3).lines;
+    let regs = registers.iter().position(|line| line == "> regs").expect("regs echoed");
+    assert!(registers[regs + 1].starts_with("V0="), "{:?}", registers);
+    assert_eq!(console(4).input, "break 202");
+
+    // The breakpoint set from the console held the machine.
+    assert!(frames[5].console.is_none());
+    assert!(frames[5].paused);
+    assert!(frames[6].console.is_none());
+}
+
+#[test]
+fn breakpoints_stop_before_their_instruction_with_the_registers_as_they_were() {
+    // 200: 6005  V0 = 5   202: 6107  V1 = 7   204: 7001  V0 += 1   206: 1204  loop
+    let mut chip8 = machine(&[0x60, 0x05, 0x61, 0x07, 0x70, 0x01, 0x12, 0x04]);
+    let mut driver = Driver::new();
+    driver.add_breakpoint(0x206);
+    let mut video = MockVideo::default();
+    for _ in 0..3 {
+        driver.frame(&mut chip8, &mut video, &mut NoInput, &mut MockAudio::default());
+    }
+
+    assert_eq!(driver.breakpoint_hit(&chip8), Some(0x206));
+    assert_eq!((chip8.pc, chip8.v[0], chip8.v[1]), (0x206, 6, 7));
+    // It stopped during the first frame, so the ones after are paused.
+    assert!(!video.frames[0].paused && video.frames[1..].iter().all(|frame| frame.paused));
+    let report = driver.stop_report(&chip8);
+    assert_eq!(report[0], "Breakpoint at 0x206");
+    assert!(report[1].starts_with("V0=06 V1=07 V2=00"), "{:?}", report);
+
+    // Stepping on leaves the breakpoint behind.
+    driver.frame(&mut chip8, &mut video, &mut MockInput::new(vec![vec![InputEvent::Step]]), &mut MockAudio::default());
+    assert_eq!((chip8.pc, driver.breakpoint_hit(&chip8)), (0x204, None));
+}
+
+#[test]
+fn watchpoints_pause_after_the_instruction_that_touched_them() {
+    // 200: A300  I = 0x300   202: 6A2A  VA = 42   204: FA33  BCD of VA
+    // 206: 1206  loop
+    let mut chip8 = machine(&[0xA3, 0x00, 0x6A, 0x2A, 0xFA, 0x33, 0x12, 0x06]);
+    chip8.add_watchpoint(Watchpoint::parse("0x301..0x303", Access::Write).unwrap());
+    let mut driver = Driver::new();
+    driver.frame(&mut chip8, &mut MockVideo::default(), &mut NoInput, &mut MockAudio::default());
+
+    assert_eq!(chip8.pc, 0x206);
+    let hit = driver.watch_hit().unwrap();
+    assert_eq!((hit.pc, hit.opcode, hit.addr, hit.old, hit.new), (0x204, 0xFA33, 0x301, 0, 4));
+    assert_eq!(driver.stop_report(&chip8)[0], "Watchpoint: 204: FA33 wrote 0x301, 0x00 -> 0x04");
+
+    // Resuming runs on until something touches them again, which nothing does.
+    driver.frame(&mut chip8, &mut MockVideo::default(), &mut MockInput::new(vec![vec![InputEvent::Pause]]), &mut MockAudio::default());
+    assert_eq!(driver.watch_hit(), None);
+}
+
+#[test]
+fn the_speedrun_timer_counts_unpaused_frames_from_the_first_key() {
+    use MenuKey::*;
+    let script = vec![
+        Vec::new(),
+        vec![InputEvent::Key { key: 1, pressed: true }],
+        vec![InputEvent::Split],
+        menu(&[Escape]),
+        menu(&[Escape]),
+        vec![InputEvent::SpeedUp],
+        // Reset from the menu: the timer waits for a key again.
+        menu(&[Escape, Down, Enter]),
+    ];
+    let mut driver = Driver::new();
+    driver.set_speedrun_timer(TimerStart::FirstInput);
+    let mut video = MockVideo::default();
+    driver
+        .run(&mut machine(&COUNT_PROGRAM), &mut video, &mut MockInput::new(script), &mut MockAudio::default())
+        .unwrap();
+
+    let timers: Vec<&TimerView> = video.frames.iter().map(|f| f.timer.as_ref().unwrap()).collect();
+    let frames: Vec<u64> = timers.iter().map(|timer| timer.frames).collect();
+    assert_eq!(frames, [0, 1, 2, 2, 3, 4, 0]);
+    assert_eq!(timers[2].splits, [(1, 1)]);
+    assert!(!timers[0].running && timers[1].running);
+    assert!(!timers[4].invalid);
+    assert_eq!(timers[5].time(), "0:00.066*");
+    assert!(!timers[6].invalid && !timers[6].running);
+}
+
+#[test]
+fn frames_carry_no_timer_unless_asked() {
+    let (_, video, _) = run(&mut machine(&COUNT_PROGRAM), &mut MockInput::idle(2));
+    assert!(video.frames.iter().all(|frame| frame.timer.is_none()));
+}
+
+#[test]
+fn quitting_writes_rpl_flags_that_changed() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("game.rpl");
+    let quit = |program: &[u8], saved: [u8; 16]| {
+        let mut chip8 = machine(program);
+        let mut driver = Driver::new();
+        driver.set_rpl_flags(&path, saved);
+        driver
+            .run(&mut chip8, &mut MockVideo::default(), &mut MockInput::idle(1), &mut MockAudio::default())
+            .unwrap();
+    };
+
+    // 200: 1200  jump to self, leaving the flags alone
+    quit(&[0x12, 0x00], [0; 16]);
+    assert!(!path.exists());
+
+    // 200: 6007  V0 = 7   202: F075  save V0   204: 1204  jump to self
+    quit(&[0x60, 0x07, 0xF0, 0x75, 0x12, 0x04], [0; 16]);
+    let mut expected = [0; 16];
+    expected[0] = 7;
+    assert_eq!(std::fs::read(&path).unwrap(), expected);
+
+    // Writing somewhere that can't be written doesn't stop the quit.
+    let mut chip8 = machine(&[0x60, 0x07, 0xF0, 0x75, 0x12, 0x04]);
+    let mut driver = Driver::new();
+    driver.set_rpl_flags(dir.path(), [0; 16]);
+    driver
+        .run(&mut chip8, &mut MockVideo::default(), &mut MockInput::idle(1), &mut MockAudio::default())
+        .unwrap();
+}
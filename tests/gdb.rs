@@ -0,0 +1,204 @@
+//! A scripted GDB client against the stub, over a real socket.
+#![cfg(feature = "std")]
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use chip8_emulator::Chip8;
+use chip8_emulator::frontend::control;
+use chip8_emulator::frontend::{Driver, Frame, InputEvent, InputSource, NullAudio, VideoSink};
+use chip8_emulator::gdb;
+use chip8_emulator::gdb::packet::{Decoder, Incoming, encode, from_hex, to_hex};
+
+/// 200: 6005  V0 = 5
+/// 202: 7001  V0 += 1
+/// 204: 1202  jump to 202
+const COUNTER: [u8; 6] = [0x60, 0x05, 0x70, 0x01, 0x12, 0x02];
+
+struct Throttle;
+
+impl VideoSink for Throttle {
+    fn present(&mut self, _frame: &Frame<'_>) {
+        thread::sleep(Duration::from_millis(2));
+    }
+}
+
+struct StopFlag(Arc<AtomicBool>);
+
+impl InputSource for StopFlag {
+    fn drain_events(&mut self) -> Vec<InputEvent> {
+        if self.0.load(Ordering::Relaxed) { vec![InputEvent::Quit] } else { Vec::new() }
+    }
+}
+
+/// Runs the counter with a GDB server attached, returning the port and what
+/// stops it. The thread hands back the machine and whether it ended paused.
+fn start() -> (u16, Arc<AtomicBool>, JoinHandle<(Chip8, bool)>) {
+    let (controller, receiver) = control::channel();
+    let port = gdb::spawn("127.0.0.1:0", controller).unwrap().port();
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut input = StopFlag(Arc::clone(&stop));
+    let thread = thread::spawn(move || {
+        let mut chip8 = Chip8::new();
+        chip8.load_rom_bytes(&COUNTER).unwrap();
+        let mut driver = Driver::new();
+        driver.attach_control(receiver);
+        while driver.frame(&mut chip8, &mut Throttle, &mut input, &mut NullAudio) {}
+        (chip8, driver.paused())
+    });
+    (port, stop, thread)
+}
+
+struct Client {
+    stream: TcpStream,
+    decoder: Decoder,
+}
+
+impl Client {
+    fn connect(port: u16) -> Self {
+        let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+        Self { stream, decoder: Decoder::new() }
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let mut byte = [0];
+        self.stream.read_exact(&mut byte).unwrap();
+        byte[0]
+    }
+
+    fn reply(&mut self) -> String {
+        loop {
+            let byte = self.read_byte();
+            if let Some(incoming) = self.decoder.push(byte) {
+                let Incoming::Packet(payload) = incoming else {
+                    panic!("{incoming:?}");
+                };
+                self.stream.write_all(b"+").unwrap();
+                return String::from_utf8(payload).unwrap();
+            }
+        }
+    }
+
+    /// Sends `payload` as a packet, checks it's acknowledged and returns the
+    /// reply.
+    fn command(&mut self, payload: &[u8]) -> String {
+        self.stream.write_all(&encode(payload)).unwrap();
+        assert_eq!(self.read_byte(), b'+');
+        self.reply()
+    }
+
+    /// Runs `monitor command`, returning what it printed.
+    fn monitor(&mut self, command: &str) -> String {
+        let reply = self.command(format!("qRcmd,{}", to_hex(command.as_bytes())).as_bytes());
+        String::from_utf8(from_hex(reply.as_bytes()).unwrap()).unwrap()
+    }
+}
+
+/// PC and V0 from a `g` reply.
+fn pc_and_v0(block: &str) -> (u16, u8) {
+    assert_eq!(block.len(), 46, "{block}");
+    let pc = u16::from_str_radix(&block[36..38], 16).unwrap()
+        | u16::from_str_radix(&block[38..40], 16).unwrap() << 8;
+    (pc, u8::from_str_radix(&block[0..2], 16).unwrap())
+}
+
+#[test]
+fn debugger_sets_a_breakpoint_steps_and_edits_the_machine() {
+    let (port, stop, emulator) = start();
+    let mut client = Client::connect(port);
+
+    assert_eq!(client.command(b"qSupported:multiprocess+"), "PacketSize=1000");
+    assert_eq!(client.command(b"?"), "S05");
+    assert_eq!(client.command(b"vMustReplyEmpty"), "");
+
+    assert_eq!(client.command(b"Z0,204,2"), "OK");
+    assert_eq!(client.command(b"c"), "S05");
+    let (pc, v0) = pc_and_v0(&client.command(b"g"));
+    assert_eq!(pc, 0x204);
+    assert!(v0 >= 6);
+
+    assert!(client.monitor("diff").starts_with("No snapshot yet"));
+    assert_eq!(client.monitor("snap"), "Saved a snapshot.\n");
+    assert_eq!(client.monitor("diff"), "no changes\n");
+    assert_eq!(client.command(b"s"), "S05");
+    assert_eq!(client.monitor("diff"), "PC: 0x204 -> 0x202\n");
+    assert_eq!(
+        client.monitor("dump 0x200 8"),
+        "0x200 (ROM+0x000)        60 05 70 01 12 02 00 00\n"
+    );
+    assert!(client.monitor("help").starts_with("Monitor commands"));
+    assert_eq!(client.command(b"p11"), "0202");
+    assert_eq!(client.command(b"c"), "S05");
+    assert_eq!(client.command(b"p0"), format!("{:02x}", v0 + 1));
+
+    // Registers: set V0 and jump back to the start with P, then G.
+    assert_eq!(client.command(b"P0=2a"), "OK");
+    assert_eq!(client.command(b"p0"), "2a");
+    let mut block = client.command(b"g");
+    block.replace_range(36..40, "0002");
+    assert_eq!(client.command(format!("G{block}").as_bytes()), "OK");
+    assert_eq!(client.command(b"p11"), "0002");
+    assert_eq!(client.command(b"G00"), "E01");
+
+    // Memory, in hex and as escaped binary.
+    assert_eq!(client.command(b"m200,6"), "600570011202");
+    assert_eq!(client.command(b"M300,2:abcd"), "OK");
+    assert_eq!(client.command(b"X302,4:}#$*"), "OK");
+    assert_eq!(client.command(b"X302,0:"), "OK");
+    assert_eq!(client.command(b"m300,6"), "abcd7d23242a");
+    assert_eq!(client.command(b"mfff,4"), "E01");
+
+    // Without the breakpoint it runs until interrupted.
+    assert_eq!(client.command(b"z0,204,2"), "OK");
+    client.stream.write_all(&encode(b"c")).unwrap();
+    assert_eq!(client.read_byte(), b'+');
+    thread::sleep(Duration::from_millis(50));
+    client.stream.write_all(&[0x03]).unwrap();
+    assert_eq!(client.reply(), "S02");
+
+    // A corrupted packet is refused, then resent.
+    client.stream.write_all(b"$g#00").unwrap();
+    assert_eq!(client.read_byte(), b'-');
+    assert_eq!(client.command(b"g").len(), 46);
+
+    assert_eq!(client.command(b"D"), "OK");
+    drop(client);
+    thread::sleep(Duration::from_millis(50));
+
+    stop.store(true, Ordering::Relaxed);
+    let (chip8, paused) = emulator.join().unwrap();
+    assert!(!paused, "detaching should let the machine run on");
+    assert_eq!(&chip8.memory[0x300..0x306], [0xAB, 0xCD, b'}', b'#', b'$', b'*']);
+}
+
+#[test]
+fn halts_are_reported_as_sigill() {
+    let (controller, receiver) = control::channel();
+    let port = gdb::spawn("127.0.0.1:0", controller).unwrap().port();
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut input = StopFlag(Arc::clone(&stop));
+    let emulator = thread::spawn(move || {
+        let mut chip8 = Chip8::new();
+        // 200: 00EE  return with an empty stack
+        chip8.load_rom_bytes(&[0x00, 0xEE]).unwrap();
+        let mut driver = Driver::new();
+        driver.attach_control(receiver);
+        while driver.frame(&mut chip8, &mut Throttle, &mut input, &mut NullAudio) {}
+    });
+
+    // It may well have halted before the client connects.
+    let mut client = Client::connect(port);
+    assert_eq!(client.command(b"c"), "S04");
+    assert_eq!(client.command(b"?"), "S04");
+    assert_eq!(client.command(b"s"), "S04");
+    client.stream.write_all(&encode(b"k")).unwrap();
+    assert_eq!(client.read_byte(), b'+');
+
+    stop.store(true, Ordering::Relaxed);
+    emulator.join().unwrap();
+}
@@ -0,0 +1,85 @@
+//! Input scripts played back through the frame loop, headless.
+#![cfg(feature = "std")]
+
+use chip8_emulator::Chip8;
+use chip8_emulator::frontend::script::InputTimeline;
+use chip8_emulator::frontend::{Driver, InputEvent, InputSource, NullAudio, NullInput, NullVideo};
+
+// 200: 6505  V5 = 5
+// 202: E59E  skip the next instruction if key V5 is down
+// 204: 1202  jump to 202
+// 206: 7101  V1 += 1: a press was seen
+// 208: E5A1  skip the next instruction if key V5 is up
+// 20A: 1208  jump to 208
+// 20C: 1202  jump to 202
+const COUNT_PRESSES: [u8; 14] = [
+    0x65, 0x05, 0xE5, 0x9E, 0x12, 0x02, 0x71, 0x01, 0xE5, 0xA1, 0x12, 0x08, 0x12, 0x02,
+];
+
+/// Presses seen after running `frames` frames of `script` with `live` input.
+fn presses_seen(script: &str, exclusive: bool, live: &mut impl InputSource, frames: u64) -> u8 {
+    let mut chip8 = Chip8::new();
+    chip8.load_rom_bytes(&COUNT_PRESSES).unwrap();
+    let mut driver = Driver::new();
+    driver.set_input_timeline(InputTimeline::parse(script).unwrap(), exclusive);
+    while driver.frames() < frames {
+        assert!(driver.frame(&mut chip8, &mut NullVideo, live, &mut NullAudio));
+    }
+    assert!(driver.halted().is_none());
+    chip8.v[1]
+}
+
+#[test]
+fn scripted_presses_reach_ex9e_at_their_frames() {
+    let script = "\
+# two presses of key 5, then a press of another key
+0 key_down 5
+3 key_up 5
+@10 +5 +7
+@12 -5
+@20 -7
+";
+    assert_eq!(presses_seen(script, false, &mut NullInput, 30), 2);
+    // Stopping before the second press.
+    assert_eq!(presses_seen(script, false, &mut NullInput, 10), 1);
+    // Still held down at the end: seen once, never released.
+    assert_eq!(presses_seen("@5 +5", false, &mut NullInput, 30), 1);
+}
+
+/// Presses key 5 every other frame, as a player mashing a button would.
+struct Masher {
+    frame: u64,
+}
+
+impl InputSource for Masher {
+    fn drain_events(&mut self) -> Vec<InputEvent> {
+        self.frame += 1;
+        vec![InputEvent::Key { key: 5, pressed: self.frame % 2 == 1 }]
+    }
+}
+
+#[test]
+fn exclusive_scripts_ignore_live_keys() {
+    let script = "@4 +5\n@6 -5";
+    assert_eq!(presses_seen(script, true, &mut Masher { frame: 0 }, 20), 1);
+    assert!(presses_seen(script, false, &mut Masher { frame: 0 }, 20) > 1);
+}
+
+#[test]
+fn quitting_still_works_with_an_exclusive_script() {
+    struct QuitAt(u64);
+    impl InputSource for QuitAt {
+        fn drain_events(&mut self) -> Vec<InputEvent> {
+            self.0 = self.0.saturating_sub(1);
+            if self.0 == 0 { vec![InputEvent::Quit] } else { Vec::new() }
+        }
+    }
+
+    let mut chip8 = Chip8::new();
+    chip8.load_rom_bytes(&COUNT_PRESSES).unwrap();
+    let mut driver = Driver::new();
+    driver.set_input_timeline(InputTimeline::parse("@0 +5").unwrap(), true);
+    let mut input = QuitAt(3);
+    while driver.frame(&mut chip8, &mut NullVideo, &mut input, &mut NullAudio) {}
+    assert_eq!(driver.frames(), 2);
+}
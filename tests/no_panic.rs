@@ -0,0 +1,154 @@
+//! Guest programs must never be able to panic the interpreter; every fault
+//! has to come back as a `Chip8Error` from `step`.
+
+use chip8_emulator::{Chip8, Chip8Error, EXTENDED_MEMORY_SIZE, Quirks, Rng};
+
+fn run(program: &[u8], steps: usize) -> Chip8 {
+    let mut chip8 = Chip8::with_rng(Rng::new(7));
+    chip8.load_rom_bytes(program).unwrap();
+    for _ in 0..steps {
+        if chip8.step().is_err() {
+            break;
+        }
+    }
+    chip8
+}
+
+#[test]
+fn arbitrary_programs_do_not_panic() {
+    let mut rng = Rng::new(0xC8);
+
+    for _ in 0..300 {
+        let len = 2 + rng.next_u8() as usize * 4;
+        let program: Vec<u8> = (0..len).map(|_| rng.next_u8()).collect();
+
+        let mut chip8 = Chip8::with_rng(Rng::new(rng.next_u8() as u64));
+        chip8.load_rom_bytes(&program).unwrap();
+
+        for step in 0..2000 {
+            if step % 50 == 0 {
+                let mask = rng.next_u8() as u16 | (rng.next_u8() as u16) << 8;
+                chip8.keys = std::array::from_fn(|key| mask & (1 << key) != 0);
+                chip8.tick_timers();
+            }
+            // Unknown opcodes are reported but leave the machine runnable.
+            if let Err(e) = chip8.step()
+                && !matches!(e, Chip8Error::InvalidOpcode { .. })
+            {
+                break;
+            }
+        }
+    }
+}
+
+// The same, under every profile and with the decode cache on, as the fuzz
+// target in `fuzz/` runs it. Every other run with 64 KB of memory starts
+// from a copy at the top of it, where the PC wraps.
+#[test]
+fn arbitrary_programs_do_not_panic_under_any_profile() {
+    let mut rng = Rng::new(0x5C);
+
+    for round in 0..300 {
+        let len = 2 + rng.next_u8() as usize * 4;
+        let program: Vec<u8> = (0..len).map(|_| rng.next_u8()).collect();
+
+        let mut chip8 = Chip8::with_rng(Rng::new(round as u64));
+        let profile = Quirks::PROFILES[round % Quirks::PROFILES.len()];
+        chip8.quirks = Quirks::from_profile(profile).unwrap();
+        #[cfg(feature = "alloc")]
+        chip8.set_decode_cache(round % 2 == 1);
+        chip8.load_rom_bytes(&program).unwrap();
+        if chip8.quirks.extended_memory && (round / Quirks::PROFILES.len()).is_multiple_of(2) {
+            let top = EXTENDED_MEMORY_SIZE - len;
+            chip8.memory[top..].copy_from_slice(&program);
+            chip8.pc = top as u16;
+        }
+
+        for step in 0..2000 {
+            if step % 16 == 0 {
+                let mask = rng.next_u8() as u16 | (rng.next_u8() as u16) << 8;
+                chip8.keys = std::array::from_fn(|key| mask & (1 << key) != 0);
+                chip8.tick_timers();
+            }
+            if let Err(e) = chip8.step()
+                && !e.is_recoverable()
+            {
+                break;
+            }
+        }
+    }
+}
+
+#[test]
+fn index_register_wraps_instead_of_overflowing() {
+    // 200: 60FF  V0 = 0xFF
+    // 202: F01E  I += V0
+    // 204: 1202  loop
+    let chip8 = run(&[0x60, 0xFF, 0xF0, 0x1E, 0x12, 0x02], 1000);
+    assert!(chip8.i < 0xFFFF);
+}
+
+#[test]
+fn reverse_subtraction_underflow_wraps() {
+    // 200: 6005  V0 = 5
+    // 202: 6103  V1 = 3
+    // 204: 8017  V0 = V1 - V0
+    let chip8 = run(&[0x60, 0x05, 0x61, 0x03, 0x80, 0x17], 3);
+    assert_eq!(chip8.v[0], 0xFE);
+}
+
+#[test]
+fn sprites_past_memory_wrap_and_register_loads_are_errors() {
+    // 200: AFFF  I = 0xFFF
+    // 202: D01F  draw 15 rows from 0xFFF, wrapping to 0x000
+    let mut chip8 = run(&[0xAF, 0xFF, 0xD0, 0x1F], 1);
+    assert!(chip8.step().is_ok());
+    assert_eq!(chip8.pc, 0x204);
+
+    // 200: AFFF  I = 0xFFF
+    // 202: FF65  load V0..VF from 0xFFF
+    let mut chip8 = run(&[0xAF, 0xFF, 0xFF, 0x65], 1);
+    assert!(chip8.step().is_err());
+}
+
+#[test]
+fn jump_with_offset_past_memory_is_an_error() {
+    // 200: 60FF  V0 = 0xFF
+    // 202: BFFF  jump to 0xFFF + V0
+    let mut chip8 = run(&[0x60, 0xFF, 0xBF, 0xFF], 2);
+    assert_eq!(chip8.pc, 0x10FE);
+    assert!(chip8.step().is_err());
+}
+
+#[test]
+fn key_skips_at_the_top_of_64_kb_wrap() {
+    for (opcode, held) in [(0x9E, true), (0xA1, false)] {
+        let mut chip8 = Chip8::with_rng(Rng::new(1));
+        chip8.quirks.extended_memory = true;
+        chip8.load_rom_bytes(&[0x00, 0xE0]).unwrap();
+        // FFFC: E09E or E0A1, skipping on key V0
+        chip8.memory[0xFFFC..0xFFFE].copy_from_slice(&[0xE0, opcode]);
+        chip8.pc = 0xFFFC;
+        chip8.keys[0] = held;
+        chip8.step().unwrap();
+        assert_eq!(chip8.pc, 0x0000);
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn restored_state_with_wild_pc_is_an_error() {
+    let mut state = Chip8::with_rng(Rng::new(1)).save_state();
+    // PC sits right after the magic, version, memory size, memory, V
+    // registers and I.
+    let pc_offset = 4 + 2 + 1 + 4096 + 16 + 2;
+    state[pc_offset..pc_offset + 2].copy_from_slice(&0xFFFFu16.to_le_bytes());
+
+    let mut chip8 = Chip8::with_rng(Rng::new(1));
+    chip8.load_state(&state).unwrap();
+    assert_eq!(chip8.pc, 0xFFFF);
+    assert!(chip8.step().is_err());
+
+    chip8.set_decode_cache(true);
+    assert!(chip8.step().is_err());
+}
@@ -0,0 +1,51 @@
+//! The interpreter builds for a bare-metal target with default features off,
+//! with and without `alloc`.
+//!
+//! Skipped, with a note, when the target's core library isn't installed; see
+//! `rustup target add thumbv7em-none-eabihf`.
+
+use std::path::Path;
+use std::process::Command;
+
+const TARGET: &str = "thumbv7em-none-eabihf";
+
+fn target_installed() -> bool {
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    Command::new(rustc)
+        .args(["--print", "target-libdir", "--target", TARGET])
+        .output()
+        .is_ok_and(|output| {
+            output.status.success()
+                && Path::new(String::from_utf8_lossy(&output.stdout).trim()).exists()
+        })
+}
+
+fn build(features: &[&str]) {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let output = Command::new(env!("CARGO"))
+        .args(["build", "--lib", "--no-default-features", "--target", TARGET])
+        .args(features.iter().flat_map(|feature| ["--features", feature]))
+        .arg("--manifest-path")
+        .arg(Path::new(manifest_dir).join("Cargo.toml"))
+        // A separate target directory, so this doesn't wait on the lock held
+        // by the `cargo test` running it.
+        .env("CARGO_TARGET_DIR", Path::new(manifest_dir).join("target").join("no_std"))
+        .output()
+        .expect("failed to run cargo");
+
+    assert!(
+        output.status.success(),
+        "build for {TARGET} with features {features:?} failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn core_builds_for_a_bare_target() {
+    if !target_installed() {
+        eprintln!("skipping: the {TARGET} target is not installed");
+        return;
+    }
+    build(&[]);
+    build(&["alloc"]);
+}
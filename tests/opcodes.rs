@@ -0,0 +1,224 @@
+//! One instruction at a time: each test sets up a machine, steps it once
+//! (or a few times) and checks everything the instruction should have
+//! changed. The quirks have their own tests next to the interpreter.
+
+use chip8_emulator::{Chip8, Chip8Error, FONT_BASE, Rng};
+
+/// A machine with `program` loaded at 0x200.
+fn machine(program: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::with_rng(Rng::new(1));
+    chip8.load_rom_bytes(program).unwrap();
+    chip8
+}
+
+/// Runs the one instruction `opcode` on a machine `setup` prepared.
+fn execute(opcode: u16, setup: impl FnOnce(&mut Chip8)) -> Chip8 {
+    let mut chip8 = machine(&opcode.to_be_bytes());
+    setup(&mut chip8);
+    chip8.step().unwrap();
+    chip8
+}
+
+#[test]
+fn clear_screen_unlights_everything() {
+    let chip8 = execute(0x00E0, |chip8| {
+        chip8.display[0][0] = true;
+        chip8.display[31][63] = true;
+    });
+    assert!(!chip8.display.pixels().contains(&true));
+    assert_eq!(chip8.pc, 0x202);
+}
+
+#[test]
+fn calls_push_the_return_address_and_returns_pop_it() {
+    // 200: 2206  call 206   202: 6101  V1 = 1   206: 00EE  return
+    let mut chip8 = machine(&[0x22, 0x06, 0x61, 0x01, 0x00, 0x00, 0x00, 0xEE]);
+    chip8.step().unwrap();
+    assert_eq!((chip8.pc, chip8.stack.as_slice()), (0x206, &[0x202][..]));
+    chip8.step().unwrap();
+    assert_eq!((chip8.pc, chip8.stack.len()), (0x202, 0));
+    chip8.step().unwrap();
+    assert_eq!(chip8.v[1], 1);
+}
+
+#[test]
+fn returning_with_nothing_to_return_to_underflows() {
+    let mut chip8 = machine(&[0x00, 0xEE]);
+    assert!(matches!(chip8.step(), Err(Chip8Error::StackUnderflow { pc: 0x200 })));
+}
+
+#[test]
+fn jumps_go_straight_to_nnn() {
+    assert_eq!(execute(0x1ABC, |_| {}).pc, 0xABC);
+}
+
+#[test]
+fn skips_skip_exactly_when_their_condition_holds() {
+    let cases = [
+        (0x3142, 0x42, 0x00, true),
+        (0x3142, 0x41, 0x00, false),
+        (0x4142, 0x41, 0x00, true),
+        (0x4142, 0x42, 0x00, false),
+        (0x5120, 0x07, 0x07, true),
+        (0x5120, 0x07, 0x08, false),
+        (0x9120, 0x07, 0x08, true),
+        (0x9120, 0x07, 0x07, false),
+    ];
+    for (opcode, v1, v2, skips) in cases {
+        let chip8 = execute(opcode, |chip8| {
+            chip8.v[1] = v1;
+            chip8.v[2] = v2;
+        });
+        assert_eq!(chip8.pc, if skips { 0x204 } else { 0x202 }, "{opcode:04X} with V1 = {v1}, V2 = {v2}");
+    }
+}
+
+#[test]
+fn a_skip_at_the_end_of_memory_stops_at_the_next_fetch() {
+    let mut chip8 = machine(&[0x00, 0xE0]);
+    chip8.memory[0xFFE..0x1000].copy_from_slice(&[0x30, 0x00]);
+    chip8.pc = 0xFFE;
+    chip8.step().unwrap();
+    assert_eq!(chip8.pc, 0x1002);
+    assert!(matches!(chip8.step(), Err(Chip8Error::PcOutOfBounds { pc: 0x1002 })));
+}
+
+#[test]
+fn loads_and_adds_of_immediates_leave_vf_alone() {
+    assert_eq!(execute(0x6A2B, |_| {}).v[0xA], 0x2B);
+
+    let chip8 = execute(0x7AFF, |chip8| chip8.v[0xA] = 0x02);
+    assert_eq!((chip8.v[0xA], chip8.v[0xF]), (0x01, 0));
+}
+
+#[test]
+fn register_copies_and_logic_combine_vx_with_vy() {
+    let run = |opcode| execute(opcode, |chip8| {
+        chip8.v[1] = 0b1100;
+        chip8.v[2] = 0b1010;
+    })
+    .v[1];
+    assert_eq!(run(0x8120), 0b1010);
+    assert_eq!(run(0x8121), 0b1110);
+    assert_eq!(run(0x8122), 0b1000);
+    assert_eq!(run(0x8123), 0b0110);
+}
+
+#[test]
+fn set_index_loads_nnn() {
+    assert_eq!(execute(0xA123, |_| {}).i, 0x123);
+}
+
+#[test]
+fn random_bytes_are_masked_by_nn() {
+    for seed in 0..50 {
+        let mut chip8 = machine(&[0xC3, 0x0F]);
+        chip8.seed_rng(seed);
+        chip8.step().unwrap();
+        assert_eq!(chip8.v[3] & 0xF0, 0, "seed {seed}");
+    }
+}
+
+#[test]
+fn drawing_over_a_sprite_erases_it_and_sets_vf_once() {
+    // 200: A20A  I = 20A   202: D125  draw at (V1, V2)   204: D125  again
+    // 206: D125  and again, after VF was overwritten   20A: the 0 glyph
+    let mut chip8 = machine(&[0xA2, 0x0A, 0xD1, 0x25, 0xD1, 0x25, 0xD1, 0x25, 0x00, 0x00, 0xF0, 0x90, 0x90, 0x90, 0xF0]);
+    chip8.v[1] = 3;
+    chip8.v[2] = 4;
+    chip8.step().unwrap();
+
+    chip8.step().unwrap();
+    assert_eq!(chip8.v[0xF], 0);
+    assert_eq!(chip8.display.pixels().iter().filter(|&&lit| lit).count(), 14);
+    assert!(chip8.display[4][3] && chip8.display[4][6] && !chip8.display[5][4]);
+
+    // Every pixel collides, but VF is a flag, not a count.
+    chip8.step().unwrap();
+    assert_eq!(chip8.v[0xF], 1);
+    assert!(!chip8.display.pixels().contains(&true));
+
+    chip8.step().unwrap();
+    assert_eq!(chip8.v[0xF], 0);
+}
+
+#[test]
+fn key_skips_look_at_the_key_in_vx() {
+    for (opcode, down, skips) in [(0xE59E, true, true), (0xE59E, false, false), (0xE5A1, true, false), (0xE5A1, false, true)] {
+        let chip8 = execute(opcode, |chip8| {
+            chip8.v[5] = 0xC;
+            chip8.keys[0xC] = down;
+        });
+        assert_eq!(chip8.pc, if skips { 0x204 } else { 0x202 }, "{opcode:04X} with the key down: {down}");
+    }
+}
+
+#[test]
+fn timers_are_set_from_and_read_into_registers() {
+    // 200: 6A20  VA = 0x20   202: FA15  DT = VA   204: FA18  ST = VA   206: FB07  VB = DT
+    let mut chip8 = machine(&[0x6A, 0x20, 0xFA, 0x15, 0xFA, 0x18, 0xFB, 0x07]);
+    for _ in 0..3 {
+        chip8.step().unwrap();
+    }
+    assert_eq!((chip8.delay_timer, chip8.sound_timer), (0x20, 0x20));
+    chip8.tick_timers();
+    chip8.step().unwrap();
+    assert_eq!(chip8.v[0xB], 0x1F);
+}
+
+#[test]
+fn waiting_for_a_key_takes_a_press_and_a_release() {
+    let mut chip8 = machine(&[0xF4, 0x0A]);
+    chip8.step().unwrap();
+    assert_eq!(chip8.pc, 0x200);
+    chip8.keys[0x7] = true;
+    chip8.step().unwrap();
+    assert_eq!(chip8.pc, 0x200);
+    chip8.keys[0x7] = false;
+    chip8.step().unwrap();
+    assert_eq!((chip8.pc, chip8.v[4]), (0x202, 0x7));
+}
+
+#[test]
+fn adding_to_i_leaves_vf_alone() {
+    let chip8 = execute(0xF31E, |chip8| {
+        chip8.i = 0xFFF;
+        chip8.v[3] = 2;
+        chip8.v[0xF] = 9;
+    });
+    assert_eq!((chip8.i, chip8.v[0xF]), (0x001, 9));
+}
+
+#[test]
+fn font_chars_point_at_the_glyph_of_vx() {
+    let chip8 = execute(0xF229, |chip8| chip8.v[2] = 0xA);
+    assert_eq!(chip8.i, FONT_BASE + 0xA * 5);
+    assert_eq!(chip8.memory[chip8.i as usize..][..5], [0xF0, 0x90, 0xF0, 0x90, 0x90]);
+}
+
+#[test]
+fn bcd_writes_hundreds_tens_and_ones() {
+    for (value, digits) in [(0, [0, 0, 0]), (255, [2, 5, 5]), (137, [1, 3, 7]), (9, [0, 0, 9])] {
+        let chip8 = execute(0xF633, |chip8| {
+            chip8.v[6] = value;
+            chip8.i = 0x300;
+        });
+        assert_eq!(chip8.memory[0x300..0x303], digits, "{value}");
+        assert_eq!(chip8.i, 0x300);
+    }
+}
+
+#[test]
+fn registers_store_and_load_through_i_up_to_vx() {
+    let chip8 = execute(0xF255, |chip8| {
+        chip8.v[..4].copy_from_slice(&[1, 2, 3, 4]);
+        chip8.i = 0x300;
+    });
+    assert_eq!(chip8.memory[0x300..0x304], [1, 2, 3, 0]);
+
+    let chip8 = execute(0xF165, |chip8| {
+        chip8.memory[0x300..0x303].copy_from_slice(&[7, 8, 9]);
+        chip8.i = 0x300;
+    });
+    assert_eq!(chip8.v[..3], [7, 8, 0]);
+}
@@ -0,0 +1,41 @@
+//! The pretty `Display`, which `--dump-format txt` prints.
+
+use chip8_emulator::{Chip8, Rng};
+
+/// 200: 6A2A  VA = 0x2A
+/// 202: 6305  V3 = 5
+/// 204: A00A  I = 0x00A (the font's 2)
+/// 206: D335  draw it at (5, 5)
+/// 208: 220C  call 20C
+/// 20A: 120A  jump to 20A
+/// 20C: F315  DT = V3
+/// 20E: 120A  jump to 20A
+const PROGRAM: [u8; 16] = [
+    0x6A, 0x2A, 0x63, 0x05, 0xA0, 0x0A, 0xD3, 0x35, 0x22, 0x0C, 0x12, 0x0A, 0xF3, 0x15, 0x12, 0x0A,
+];
+
+fn machine() -> Chip8 {
+    let mut chip8 = Chip8::with_rng(Rng::new(1));
+    chip8.load_rom_bytes(&PROGRAM).unwrap();
+    for _ in 0..5 {
+        chip8.step().unwrap();
+    }
+    chip8.keys[0x5] = true;
+    chip8.keys[0xA] = true;
+    chip8
+}
+
+#[test]
+fn pretty_output_is_unchanged() {
+    assert_eq!(machine().to_string(), include_str!("snapshots/pretty.txt").trim_end());
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn restoring_a_save_state_gives_an_equal_machine() {
+    let chip8 = machine();
+    let mut restored = Chip8::with_rng(Rng::new(1));
+    restored.load_state(&chip8.save_state()).unwrap();
+    restored.keys = chip8.keys;
+    chip8_emulator::assert_chip8_eq!(restored, chip8);
+}
@@ -0,0 +1,73 @@
+//! `Chip8::state_hash`, which recorded movies and lockstep logs compare
+//! against, so its values must not drift.
+
+use chip8_emulator::{Chip8, STATE_VERSION, Rng};
+
+/// 200: 6A2A  VA = 0x2A
+/// 202: A20A  I = 0x20A
+/// 204: D001  draw 1 row at (V0, V0)
+/// 206: 220C  call 20C
+/// 208: 1208  jump to 208
+/// 20A: 81    the sprite
+/// 20C: F915  DT = V9
+/// 20E: 1208  jump to 208
+const PROGRAM: [u8; 16] = [
+    0x6A, 0x2A, 0xA2, 0x0A, 0xD0, 0x01, 0x22, 0x0C, 0x12, 0x08, 0x81, 0x00, 0xF9, 0x15, 0x12, 0x08,
+];
+
+fn machine() -> Chip8 {
+    let mut chip8 = Chip8::with_rng(Rng::new(1));
+    chip8.load_rom_bytes(&PROGRAM).unwrap();
+    for _ in 0..4 {
+        chip8.step().unwrap();
+    }
+    chip8
+}
+
+#[test]
+fn hashes_are_pinned() {
+    // Only change these alongside a STATE_VERSION bump.
+    assert_eq!(STATE_VERSION, 7);
+    assert_eq!(Chip8::with_rng(Rng::new(0)).state_hash(), 0xA96E_4DC5_9A37_92E9);
+    assert_eq!(machine().state_hash(), 0x73C4_B5B1_8DDF_968D);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn hash_covers_the_save_state() {
+    let chip8 = machine();
+    let fnv1a = chip8.save_state().iter().fold(0xCBF2_9CE4_8422_2325u64, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3)
+    });
+    assert_eq!(chip8.state_hash(), fnv1a);
+
+    let edits: [fn(&mut Chip8); 15] = [
+        |c| c.v[0xF] = 1,
+        |c| c.i += 1,
+        |c| c.pc += 2,
+        |c| assert!(c.stack.push(0x300)),
+        |c| c.sound_timer = 1,
+        |c| c.memory[0xFFF] = 1,
+        |c| c.quirks.extended_memory = true,
+        |c| c.display[31][63] = true,
+        |c| c.display.set_resolution(chip8_emulator::Resolution::High),
+        |c| c.display.select_planes(0b10),
+        |c| c.display.plane_mut(1)[0] = true,
+        |c| c.audio_pattern = Some([0; 16]),
+        |c| c.pitch = 65,
+        |c| c.color_map.step_background(),
+        |c| c.seed_rng(2),
+    ];
+    for edit in edits {
+        let mut other = machine();
+        edit(&mut other);
+        assert_ne!(other.state_hash(), chip8.state_hash());
+    }
+
+    // Like save states, the hash leaves out keys and the quirks other than
+    // how much memory there is.
+    let mut other = machine();
+    other.keys[0] = true;
+    other.quirks.shift_uses_vy = true;
+    assert_eq!(other.state_hash(), chip8.state_hash());
+}
@@ -0,0 +1,130 @@
+//! The JSON state layout, which outside tools rely on staying put.
+#![cfg(feature = "serde")]
+
+use chip8_emulator::frontend::{Driver, InputEvent, InputSource, NullAudio, NullVideo};
+use chip8_emulator::{Chip8, JSON_STATE_VERSION, Quirks, Rng};
+
+/// 200: 6A2A  VA = 0x2A
+/// 202: A20A  I = 0x20A
+/// 204: D001  draw 1 row at (V0, V0)
+/// 206: 220C  call 20C
+/// 208: 1208  jump to 208
+/// 20A: 81    the sprite
+/// 20C: F915  DT = V9
+/// 20E: 1208  jump to 208
+const PROGRAM: [u8; 16] = [
+    0x6A, 0x2A, 0xA2, 0x0A, 0xD0, 0x01, 0x22, 0x0C, 0x12, 0x08, 0x81, 0x00, 0xF9, 0x15, 0x12, 0x08,
+];
+
+fn machine() -> Chip8 {
+    let mut chip8 = Chip8::with_rng(Rng::new(1));
+    chip8.quirks = Quirks { shift_uses_vy: true, ..Quirks::default() };
+    chip8.load_rom_bytes(&PROGRAM).unwrap();
+    for _ in 0..4 {
+        chip8.step().unwrap();
+    }
+    chip8
+}
+
+#[test]
+fn version_2_layout_is_unchanged() {
+    // Regenerate with `to_json()` only alongside a JSON_STATE_VERSION bump,
+    // or when what a new machine has in memory changes.
+    assert_eq!(JSON_STATE_VERSION, 2);
+    assert_eq!(machine().to_json(), include_str!("snapshots/state_v2.json").trim_end());
+}
+
+#[test]
+fn snapshot_restores_the_machine() {
+    let restored = Chip8::from_json(include_str!("snapshots/state_v2.json")).unwrap();
+    chip8_emulator::assert_chip8_eq!(restored, machine());
+    assert_eq!(restored.stack.as_slice(), [0x208]);
+    assert_eq!(restored.pc, 0x20C);
+}
+
+#[test]
+fn every_quirk_is_written_on_or_off() {
+    let mut chip8 = machine();
+    let json = chip8.to_json();
+    assert!(json.contains("\"i_overflow_sets_vf\": false"), "{}", json);
+    assert!(json.contains("\"chip8x\": false"), "{}", json);
+
+    chip8.quirks.load_store_increments_i = true;
+    let json = chip8.to_json();
+    assert!(json.contains("\"load_store_increments_i\": true"), "{}", json);
+    assert_eq!(Chip8::from_json(&json).unwrap().quirks, chip8.quirks);
+}
+
+#[test]
+fn serde_round_trips_run_the_same_frames() {
+    // 200: A210  I = 0x210   202: C10F  V1 = random & 0xF   204: D115  draw at (V1, V1)
+    // 206: 6208  V2 = 8      208: F218  ST = V2            20A: E29E  skip if key V2
+    // 20C: 1202  loop        20E: 1200  start over         210: F0    the sprite
+    let program = [0xA2, 0x10, 0xC1, 0x0F, 0xD1, 0x15, 0x62, 0x08, 0xF2, 0x18, 0xE2, 0x9E, 0x12, 0x02, 0x12, 0x00, 0xF0];
+    let mut original = Chip8::with_rng(Rng::new(9));
+    original.load_rom_bytes(&program).unwrap();
+    original.keys[8] = true;
+    for _ in 0..25 {
+        original.step().unwrap();
+    }
+    original.tick_timers();
+
+    let bytes = serde_json::to_vec(&original).unwrap();
+    let mut restored: Chip8 = serde_json::from_slice(&bytes).unwrap();
+    chip8_emulator::assert_chip8_eq!(restored, original);
+    for _ in 0..10 {
+        for chip8 in [&mut original, &mut restored] {
+            for _ in 0..7 {
+                chip8.step().unwrap();
+            }
+            chip8.tick_timers();
+        }
+        assert_eq!(restored.display, original.display);
+    }
+    chip8_emulator::assert_chip8_eq!(restored, original);
+}
+
+#[test]
+fn keys_are_only_written_while_one_is_down() {
+    let mut chip8 = machine();
+    assert!(!chip8.to_json().contains("keys"));
+    chip8.keys[0xA] = true;
+    let json = chip8.to_json();
+    assert!(json.contains("\"keys\": \"0000000000100000\""), "{}", json);
+    assert_eq!(Chip8::from_json(&json).unwrap().keys, chip8.keys);
+}
+
+struct DumpThenQuit(u32);
+
+impl InputSource for DumpThenQuit {
+    fn drain_events(&mut self) -> Vec<InputEvent> {
+        self.0 += 1;
+        match self.0 {
+            2 => vec![InputEvent::DumpState],
+            5 => vec![InputEvent::Quit],
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[test]
+fn the_driver_dumps_on_the_hotkey_and_on_quit() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state.json");
+    let mut chip8 = machine();
+    let mut driver = Driver::new();
+    driver.set_state_dump(&path);
+    let mut input = DumpThenQuit(0);
+
+    driver.frame(&mut chip8, &mut NullVideo, &mut input, &mut NullAudio);
+    assert!(!path.exists());
+    driver.frame(&mut chip8, &mut NullVideo, &mut input, &mut NullAudio);
+    let hotkey: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(hotkey["frames"], 1);
+
+    while driver.frame(&mut chip8, &mut NullVideo, &mut input, &mut NullAudio) {}
+    let exit: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(exit["frames"], 4);
+    assert_eq!(exit["version"], 2);
+    assert_eq!(exit["rom_hash"], format!("{:016x}", chip8.rom_hash().unwrap()));
+}
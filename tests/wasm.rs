@@ -0,0 +1,82 @@
+//! The browser bindings, in a headless wasm environment with
+//! `cargo test --target wasm32-unknown-unknown --no-default-features --features wasm --test wasm`
+//! (using `wasm-bindgen-test-runner` as the target runner), and natively with
+//! `cargo test --features wasm --test wasm`.
+#![cfg(feature = "wasm")]
+
+use chip8_emulator::wasm::WasmChip8;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+const IBM_LOGO: &[u8] = include_bytes!("../ibm_logo.ch8");
+
+/// Hash of the RGBA display after the IBM logo has been drawn.
+const IBM_LOGO_DISPLAY_HASH: u64 = 0x105C_296E_5C74_6A95;
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
+fn run_frames(machine: &mut WasmChip8, frames: usize, keys: u16) {
+    for _ in 0..frames {
+        assert!(machine.run_frame(keys).is_ok());
+    }
+}
+
+#[test]
+fn ibm_logo_renders_after_sixty_frames() {
+    let mut machine = WasmChip8::new(IBM_LOGO).unwrap();
+    run_frames(&mut machine, 60, 0);
+
+    let rgba = machine.display_rgba();
+    assert_eq!(rgba.len(), machine.width() * machine.height() * 4);
+    assert!(rgba.chunks(4).all(|pixel| pixel[3] == 0xFF));
+    assert!(rgba.chunks(4).any(|pixel| pixel[..3] == [0xFF; 3]));
+    assert_eq!(fnv1a(&rgba), IBM_LOGO_DISPLAY_HASH);
+}
+
+#[test]
+fn keys_bitmask_drives_the_sound_timer() {
+    // 200: 6005  V0 = 5
+    // 202: 6103  V1 = 3
+    // 204: E0A1  skip the next instruction unless key V0 is down
+    // 206: F118  sound timer = V1
+    // 208: 1204  jump to 204
+    let rom = [0x60, 0x05, 0x61, 0x03, 0xE0, 0xA1, 0xF1, 0x18, 0x12, 0x04];
+    let mut machine = WasmChip8::new(&rom).unwrap();
+
+    run_frames(&mut machine, 2, 0);
+    assert!(!machine.sound_active());
+    run_frames(&mut machine, 1, 1 << 5);
+    assert!(machine.sound_active());
+    run_frames(&mut machine, 3, 1 << 4);
+    assert!(!machine.sound_active());
+}
+
+#[test]
+fn state_round_trips_through_bytes() {
+    let mut machine = WasmChip8::new(IBM_LOGO).unwrap();
+    run_frames(&mut machine, 60, 0);
+    let saved = machine.save_state();
+    let drawn = machine.display_rgba();
+
+    let mut restored = WasmChip8::new(&[0x12, 0x00]).unwrap();
+    assert!(restored.load_state(&saved).is_ok());
+    assert_eq!(restored.display_rgba(), drawn);
+    assert_eq!(restored.save_state(), saved);
+}
+
+// Errors become JavaScript exceptions, which only exist under wasm.
+#[cfg(target_arch = "wasm32")]
+#[test]
+fn machine_errors_stop_the_machine() {
+    // 200: 00EE  return with an empty stack
+    let mut machine = WasmChip8::new(&[0x00, 0xEE]).unwrap();
+
+    assert!(machine.run_frame(0).is_err());
+    assert!(machine.halted());
+    assert!(machine.run_frame(0).is_err());
+    assert!(WasmChip8::new(&[0; 4096]).is_err());
+}